@@ -32,7 +32,8 @@ fn main() {
             );
         }
         Err(Error::InvalidChecksum) => println!("Corrupt Mii selected"),
-        Err(Error::NoMiiSelected) => println!("No Mii selected"),
+        Err(Error::UserCancelled) => println!("No Mii selected"),
+        Err(e) => println!("Couldn't launch the Mii Selector: {e}"),
     }
 
     println!("\x1b[29;16HPress Start to exit");
@@ -0,0 +1,125 @@
+//! Activity Log data.
+//!
+//! The system keeps track of how long (and how many times) each title has been played.
+//! This information is shown to the user by the Activity Log application, but it can also be read by homebrew to build statistics viewers.
+//!
+//! # Notes
+//!
+//! The per-title records are kept by the PTM service in the `pld.dat` file of its system savedata (`0x00010022`),
+//! while the shared extdata archive `0xF000000B` only holds the Activity Log's icon cache.
+//! As such, reading the records requires the application to have access rights to the PTM system savedata.
+//!
+//! `pld.dat` starts with the log of play events, followed by the table of per-title summaries read by this module.
+//! See the file's layout at <https://www.3dbrew.org/wiki/Activity_Log#pld.dat>
+#![doc(alias = "playtime")]
+#![doc(alias = "pld")]
+
+use crate::services::fs::{MountedArchive, SharedArchive};
+
+use std::fs;
+use std::time::Duration;
+
+const MOUNT_NAME: &str = "pld";
+const FILE_PATH: &str = "pld:/pld.dat";
+/// Offset of the summary table within `pld.dat`, after the 8-byte header and the 0x11D28 play events of 8 bytes each.
+const SUMMARY_OFFSET: usize = 0x8E948;
+/// Amount of entries of the summary table.
+const SUMMARY_ENTRIES: usize = 0x1000;
+const RECORD_SIZE: usize = 0x10;
+
+/// Play statistics of a single title, as registered in the Activity Log.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TitleRecord {
+    title_id: u64,
+    playtime: Duration,
+    launch_count: u16,
+    first_played: u16,
+}
+
+impl TitleRecord {
+    /// Returns the ID of the title.
+    pub fn title_id(&self) -> u64 {
+        self.title_id
+    }
+
+    /// Returns the total time spent playing the title.
+    pub fn playtime(&self) -> Duration {
+        self.playtime
+    }
+
+    /// Returns the amount of times the title was launched.
+    pub fn launch_count(&self) -> u16 {
+        self.launch_count
+    }
+
+    /// Returns the day the title was first played, as the number of days since January 1st 2000.
+    pub fn first_played(&self) -> u16 {
+        self.first_played
+    }
+
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        let title_id = u64::from_le_bytes(raw[0x0..0x8].try_into().unwrap());
+
+        // Unused slots are either zeroed out or filled with 0xFF.
+        if title_id == 0 || title_id == u64::MAX {
+            return None;
+        }
+
+        Some(Self {
+            title_id,
+            playtime: Duration::from_secs(
+                u32::from_le_bytes(raw[0x8..0xC].try_into().unwrap()).into(),
+            ),
+            launch_count: u16::from_le_bytes([raw[0xC], raw[0xD]]),
+            first_played: u16::from_le_bytes([raw[0xE], raw[0xF]]),
+        })
+    }
+}
+
+/// Read all title records registered in the Activity Log.
+///
+/// # Errors
+///
+/// This function will return an error if the PTM system savedata couldn't be mounted (e.g. because of insufficient access rights),
+/// if the records file couldn't be read or if it's too short to contain the summary table.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::activity_log;
+///
+/// for record in activity_log::title_records()? {
+///     println!(
+///         "{:016X}: played {} times for {} minutes",
+///         record.title_id(),
+///         record.launch_count(),
+///         record.playtime().as_secs() / 60
+///     );
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub fn title_records() -> crate::Result<Vec<TitleRecord>> {
+    let _archive = MountedArchive::shared(SharedArchive::PtmSavedata, MOUNT_NAME)?;
+
+    let data = fs::read(FILE_PATH)?;
+
+    let summaries = data
+        .get(SUMMARY_OFFSET..SUMMARY_OFFSET + SUMMARY_ENTRIES * RECORD_SIZE)
+        .ok_or_else(|| {
+            crate::Error::Other(format!(
+                "pld.dat is too short to contain the summary table ({} bytes)",
+                data.len()
+            ))
+        })?;
+
+    Ok(summaries
+        .chunks_exact(RECORD_SIZE)
+        .filter_map(TitleRecord::from_bytes)
+        .collect())
+}
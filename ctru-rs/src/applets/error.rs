@@ -0,0 +1,381 @@
+//! Error applet.
+//!
+//! This applet displays error text as a pop-up message on the lower screen, or a system error code along with the system's own explanation of it.
+//!
+//! A [`PopUp`] can optionally carry an application-defined error code and a [`Report`]:
+//! when launched, it then writes a diagnostic file to the SD card and tells the user where to find it,
+//! so that bug reports from end users always reference the same code that was shown on screen.
+#![doc(alias = "Error")]
+
+use crate::services::{apt::Apt, cfgu::Language, gfx::Gfx};
+use crate::util::str16;
+
+use std::ffi::{CString, NulError};
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Maximum amount of bytes of an attached log file copied into a [`Report`].
+const LOG_TAIL_SIZE: usize = 16 * 1024;
+
+/// Configuration struct to set up the Error applet.
+#[doc(alias = "errorConf")]
+pub struct PopUp {
+    state: Box<ctru_sys::errorConf>,
+    text: String,
+    code: Option<u32>,
+    system_code: Option<u32>,
+    report: Option<Report>,
+    last_report: Option<PathBuf>,
+}
+
+/// Determines whether the Error applet will use word wrapping when displaying a message.
+#[doc(alias = "errorType")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum WordWrap {
+    /// Error text is centered in the error applet window and does not use word wrapping.
+    Disabled = ctru_sys::ERROR_TEXT,
+    /// Error text starts at the top of the error applet window and uses word wrapping.
+    Enabled = ctru_sys::ERROR_TEXT_WORD_WRAP,
+}
+
+/// Error returned by an unsuccessful [`PopUp::launch()`].
+#[doc(alias = "errorReturnCode")]
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Error {
+    /// The [`errorConf`](ctru_sys::errorConf) struct contained invalid data.
+    InvalidInput,
+    /// The user pressed the HOME button while the applet was open.
+    HomeButton,
+    /// The user performed a software reset while the applet was open.
+    SoftwareReset,
+    /// The user pressed the POWER button while the applet was open.
+    PowerButton,
+    /// The pop-up was shown, but its diagnostic report couldn't be written.
+    Report(crate::Error),
+}
+
+/// Diagnostic file written to the SD card when a [`PopUp`] is launched.
+///
+/// Each report is a plain text file named after the pop-up's error code and the time it was shown.
+/// It contains the error code and message, optional application-provided details, basic system information
+/// and (optionally) the end of an application log file, such as the one written by the [`logger`](crate::logger).
+#[derive(Clone, Debug)]
+pub struct Report {
+    directory: PathBuf,
+    details: String,
+    log: Option<PathBuf>,
+}
+
+impl Report {
+    /// Create a report configuration saving its files in the given directory (e.g. `sdmc:/3ds/my-app/reports`).
+    ///
+    /// The directory is created when the first report is written.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            details: String::new(),
+            log: None,
+        }
+    }
+
+    /// Add application-specific details (e.g. the current state or the last user action) to the report.
+    ///
+    /// These aren't shown to the user.
+    pub fn details(mut self, details: impl Into<String>) -> Self {
+        self.details = details.into();
+        self
+    }
+
+    /// Attach the end of a log file to the report.
+    ///
+    /// Only the last 16 KiB of the file are copied. Missing files are ignored.
+    pub fn attach_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.log = Some(path.into());
+        self
+    }
+
+    /// Write a report for the given error code and message, returning the path of the new file.
+    fn write(&self, code: Option<u32>, message: &str) -> crate::Result<PathBuf> {
+        fs::create_dir_all(&self.directory)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+
+        let path = self.directory.join(match code {
+            Some(code) => format!("error-{}-{timestamp}.txt", format_code(code)),
+            None => format!("error-{timestamp}.txt"),
+        });
+
+        let firm = crate::os::firm_version();
+        let kernel = crate::os::kernel_version();
+
+        let mut contents = String::new();
+        // Writing to a `String` never fails.
+        let _ = writeln!(
+            contents,
+            "Error code: {}",
+            code.map_or_else(|| String::from("none"), format_code)
+        );
+        let _ = writeln!(contents, "Time: {timestamp}");
+        let _ = writeln!(
+            contents,
+            "System: FIRM {}.{}.{}, kernel {}.{}.{}, {:?}",
+            firm.major(),
+            firm.minor(),
+            firm.revision(),
+            kernel.major(),
+            kernel.minor(),
+            kernel.revision(),
+            crate::env::launch_mode(),
+        );
+        if let Some(luma) = crate::os::luma_version() {
+            let _ = writeln!(contents, "Luma3DS: {}.{}", luma.major(), luma.minor());
+        }
+        let _ = writeln!(contents, "\nMessage:\n{message}");
+
+        if !self.details.is_empty() {
+            let _ = writeln!(contents, "\nDetails:\n{}", self.details);
+        }
+
+        if let Some(log) = self.log.as_deref().and_then(read_tail) {
+            let _ = writeln!(contents, "\nLog:\n{log}");
+        }
+
+        fs::write(&path, contents)?;
+
+        Ok(path)
+    }
+}
+
+impl PopUp {
+    /// Initializes the error applet with the provided word wrap setting.
+    #[doc(alias = "errorInit")]
+    pub fn new(word_wrap: WordWrap) -> Self {
+        let mut state = Box::<ctru_sys::errorConf>::default();
+
+        unsafe { ctru_sys::errorInit(state.as_mut(), word_wrap as _, 0) };
+
+        Self {
+            state,
+            text: String::new(),
+            code: None,
+            system_code: None,
+            report: None,
+            last_report: None,
+        }
+    }
+
+    /// Initializes the error applet to display a system error code (such as a failed [`ctru_sys::Result`]), the same way
+    /// system software does: as `XXX-YYYY`, along with the system's explanation of the error.
+    ///
+    /// # Notes
+    ///
+    /// The applet doesn't display any text in this mode: [`PopUp::set_text()`] and [`PopUp::set_error_code()`]
+    /// only affect the [`Report`], whose location can't be shown to the user.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # use ctru::services::{apt::Apt, gfx::Gfx};
+    /// #
+    /// # let gfx = Gfx::new().unwrap();
+    /// # let apt = Apt::new().unwrap();
+    /// #
+    /// use ctru::applets::error::PopUp;
+    /// use ctru::services::am::Am;
+    ///
+    /// if let Err(ctru::Error::Os(code)) = Am::new() {
+    ///     PopUp::for_system_code(code).launch(&apt, &gfx)?;
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "errorCode")]
+    pub fn for_system_code(code: ctru_sys::Result) -> Self {
+        let mut state = Box::<ctru_sys::errorConf>::default();
+
+        unsafe { ctru_sys::errorInit(state.as_mut(), ctru_sys::ERROR_CODE, 0) };
+
+        Self {
+            state,
+            text: String::new(),
+            code: None,
+            system_code: Some(code as u32),
+            report: None,
+            last_report: None,
+        }
+    }
+
+    /// Sets the language of the applet's interface (and of the system's explanation of error codes), instead of the system language.
+    #[doc(alias = "useLanguage")]
+    pub fn set_language(&mut self, language: Language) {
+        // The `*_LANGUAGE` applet types are their base type with this flag set.
+        self.state.type_ |= ctru_sys::ERROR_CODE_LANGUAGE;
+        self.state.useLanguage = language as _;
+    }
+
+    /// Sets the error text to display.
+    ///
+    /// # Notes
+    ///
+    /// The text will be truncated if it exceeds 1900 characters, including the error code and report location lines.
+    #[doc(alias = "errorText")]
+    pub fn set_text(&mut self, text: &str) {
+        self.text = String::from(text);
+    }
+
+    /// Sets the error text to display, rejecting text which contains NUL bytes.
+    ///
+    /// The applet stops displaying [`PopUp::set_text()`]'s text at the first NUL byte: this variant reports it instead,
+    /// which is useful when the text comes from user-provided data.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the given `&str` contains NUL bytes.
+    #[doc(alias = "errorText")]
+    pub fn try_set_text(&mut self, text: &str) -> Result<(), NulError> {
+        // Only used for validation: the text is encoded to UTF-16 when launching the applet.
+        CString::new(text)?;
+        self.text = String::from(text);
+
+        Ok(())
+    }
+
+    /// Sets an application-defined error code, shown below the text as `XXXX-YYYY` and referenced by the [`Report`].
+    ///
+    /// Use [`PopUp::for_system_code()`] to display system error codes instead.
+    pub fn set_error_code(&mut self, code: u32) {
+        self.code = Some(code);
+    }
+
+    /// Write a diagnostic [`Report`] every time the pop-up is launched.
+    pub fn set_report(&mut self, report: Report) {
+        self.report = Some(report);
+    }
+
+    /// Returns the path of the report written by the last [`PopUp::launch()`], if any.
+    pub fn last_report(&self) -> Option<&Path> {
+        self.last_report.as_deref()
+    }
+
+    /// Launches the error applet.
+    ///
+    /// If a [`Report`] was set, it is written before the applet is shown, and the user is told where to find it.
+    ///
+    /// # Errors
+    ///
+    /// Other than the errors returned by the applet itself, this function returns [`Error::Report`]
+    /// (after showing the pop-up) if the report couldn't be written.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # use ctru::services::{apt::Apt, gfx::Gfx};
+    /// #
+    /// # let gfx = Gfx::new().unwrap();
+    /// # let apt = Apt::new().unwrap();
+    /// #
+    /// use ctru::applets::error::{PopUp, Report, WordWrap};
+    ///
+    /// let mut popup = PopUp::new(WordWrap::Enabled);
+    /// popup.set_text("The save file is corrupted.");
+    /// popup.set_error_code(0x0001_0004);
+    /// popup.set_report(
+    ///     Report::new("sdmc:/3ds/my-app/reports")
+    ///         .details("Loading slot 2")
+    ///         .attach_log("sdmc:/3ds/my-app/log.txt"),
+    /// );
+    ///
+    /// popup.launch(&apt, &gfx)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "errorDisp")]
+    pub fn launch(&mut self, _apt: &Apt, _gfx: &Gfx) -> Result<(), Error> {
+        let mut text = self.text.clone();
+
+        if let Some(code) = self.code {
+            let _ = write!(text, "\n\nError code: {}", format_code(code));
+        }
+
+        let report = self
+            .report
+            .as_ref()
+            .map(|report| report.write(self.code.or(self.system_code), &self.text));
+
+        self.last_report = None;
+
+        match &report {
+            Some(Ok(path)) => {
+                let _ = write!(text, "\nA report was saved to {}", path.display());
+                self.last_report = Some(path.clone());
+            }
+            Some(Err(_)) => text.push_str("\nThe error report couldn't be saved."),
+            None => (),
+        }
+
+        match self.system_code {
+            Some(code) => unsafe { ctru_sys::errorCode(self.state.as_mut(), code as _) },
+            None => {
+                // Keep the last unit as NUL terminator.
+                let length = self.state.Text.len() - 1;
+                str16::encode_into(&text, &mut self.state.Text[..length]);
+                self.state.Text[length] = 0;
+            }
+        }
+
+        unsafe { ctru_sys::errorDisp(self.state.as_mut()) };
+
+        match self.state.returnCode {
+            ctru_sys::ERROR_NONE | ctru_sys::ERROR_SUCCESS => (),
+            ctru_sys::ERROR_HOME_BUTTON => return Err(Error::HomeButton),
+            ctru_sys::ERROR_SOFTWARE_RESET => return Err(Error::SoftwareReset),
+            ctru_sys::ERROR_POWER_BUTTON => return Err(Error::PowerButton),
+            _ => return Err(Error::InvalidInput),
+        }
+
+        match report {
+            Some(Err(e)) => Err(Error::Report(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Format an error code as its upper and lower 16 bits in hexadecimal, separated by a dash.
+fn format_code(code: u32) -> String {
+    format!("{:04X}-{:04X}", code >> 16, code & 0xFFFF)
+}
+
+/// Read the last [`LOG_TAIL_SIZE`] bytes of a file, if it exists.
+fn read_tail(path: &Path) -> Option<String> {
+    let data = fs::read(path).ok()?;
+    let start = data.len().saturating_sub(LOG_TAIL_SIZE);
+
+    Some(String::from_utf8_lossy(&data[start..]).into_owned())
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidInput => write!(f, "invalid parameters provided to the error applet"),
+            Self::HomeButton => write!(f, "the HOME button was pressed"),
+            Self::SoftwareReset => write!(f, "a software reset was performed"),
+            Self::PowerButton => write!(f, "the POWER button was pressed"),
+            Self::Report(e) => write!(f, "the error report couldn't be written: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -5,9 +5,10 @@
 
 use crate::mii::Mii;
 use crate::services::{apt::Apt, gfx::Gfx};
+use crate::util::str16;
 
 use bitflags::bitflags;
-use std::{ffi::CString, fmt};
+use std::fmt;
 
 /// Index of a Mii on the [`MiiSelector`] interface.
 ///
@@ -55,6 +56,31 @@ bitflags! {
     }
 }
 
+/// Magic number identifying a valid [`MiiSelector`] configuration, set by [`MiiSelector::new()`].
+///
+/// The applet rejects configurations holding another value.
+#[doc(alias = "MIISELECTOR_MAGIC")]
+pub const CONFIG_MAGIC: u32 = ctru_sys::MIISELECTOR_MAGIC;
+
+/// Maximum length of a [`MiiSelector`] title, in UTF-16 code units.
+pub const MAX_TITLE_LEN: usize = ctru_sys::MIISELECTOR_TITLE_LEN as usize - 1;
+
+/// Error returned by [`MiiSelector::try_set_title()`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum TitleError {
+    /// The title contains a NUL byte.
+    Nul {
+        /// Byte position of the first NUL byte.
+        position: usize,
+    },
+    /// The title is longer than [`MAX_TITLE_LEN`] UTF-16 code units.
+    TooLong {
+        /// Length of the title, in UTF-16 code units.
+        length: usize,
+    },
+}
+
 /// Configuration structure to setup the Mii Selector applet.
 #[doc(alias = "MiiSelectorConf")]
 #[derive(Clone, Debug)]
@@ -62,12 +88,26 @@ pub struct MiiSelector {
     config: Box<ctru_sys::MiiSelectorConf>,
 }
 
+/// Builder for a [`MiiSelector`] configuration.
+///
+/// The builder is consumed when launching the applet, so a configuration can never be accidentally reused
+/// after being partially modified.
+///
+/// See [`MiiSelector::builder()`] for more information.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct MiiSelectorBuilder {
+    selector: MiiSelector,
+}
+
 /// Return value of a successful [`MiiSelector::launch()`].
 #[non_exhaustive]
 #[derive(Clone, Debug)]
 pub struct Selection {
     /// Data of the selected Mii.
     pub mii_data: Mii,
+    /// Raw data of the selected Mii, e.g. to share it as a QR code with [`mii::to_qr_bytes()`](crate::mii::to_qr_bytes).
+    pub raw_mii_data: ctru_sys::MiiData,
     /// Type of the selected Mii.
     pub mii_type: MiiType,
 }
@@ -77,8 +117,17 @@ pub struct Selection {
 pub enum Error {
     /// The selected Mii's data is corrupt.
     InvalidChecksum,
-    /// Either the user cancelled the selection (see [`Options::ENABLE_CANCEL`]) or no valid Miis were available to select.
-    NoMiiSelected,
+    /// The applet was launched while the [`Gfx`] service wasn't active.
+    ///
+    /// See [`MiiSelectorBuilder::launch_with_gfx()`] to make sure at compile-time that the service is available.
+    GfxNotInitialized,
+    /// The applet closed without a selection.
+    ///
+    /// # Notes
+    ///
+    /// Either the user cancelled the selection (see [`Options::ENABLE_CANCEL`]) or no valid Miis were available to select:
+    /// the applet reports both cases with the same return code.
+    UserCancelled,
 }
 
 impl MiiSelector {
@@ -92,8 +141,39 @@ impl MiiSelector {
         Self { config }
     }
 
+    /// Returns a new [`MiiSelectorBuilder`] to configure the Mii Selector applet with chained calls.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # use ctru::services::gfx::Gfx;
+    /// #
+    /// # let gfx = Gfx::new().unwrap();
+    /// #
+    /// use ctru::applets::mii_selector::{Index, MiiSelector, Options};
+    ///
+    /// let selection = MiiSelector::builder()
+    ///     .title("Select a Mii!")
+    ///     .options(Options::ENABLE_CANCEL)
+    ///     .blocklist_user_mii(Index::Index(0))
+    ///     .launch_with_gfx(&gfx)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> MiiSelectorBuilder {
+        MiiSelectorBuilder {
+            selector: Self::new(),
+        }
+    }
+
     /// Set the title of the Mii Selector window.
     ///
+    /// Titles longer than [`MAX_TITLE_LEN`] UTF-16 code units are truncated on a character boundary.
+    /// Use [`MiiSelector::try_set_title()`] to reject them instead.
+    ///
     /// # Panics
     /// This function will panic if the given `&str` contains NUL bytes.
     ///
@@ -109,17 +189,66 @@ impl MiiSelector {
     /// ```
     #[doc(alias = "miiSelectorSetTitle")]
     pub fn set_title(&mut self, text: &str) {
-        // This can only fail if the text contains NUL bytes in the string... which seems
-        // unlikely and is documented
-        let c_text = CString::new(text).expect("Failed to convert the title text into a CString");
-        unsafe {
-            ctru_sys::miiSelectorSetTitle(self.config.as_mut(), c_text.as_ptr());
+        match self.try_set_title(text) {
+            Ok(()) => {}
+            Err(TitleError::TooLong { .. }) => self.write_title(text),
+            // This can only fail if the text contains NUL bytes in the string... which seems
+            // unlikely and is documented
+            Err(e) => panic!("Failed to set the Mii Selector title: {e}"),
         }
     }
 
+    /// Set the title of the Mii Selector window, without panicking on invalid text nor truncating it.
+    ///
+    /// Useful when the title comes from user-provided data.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the given `&str` contains NUL bytes,
+    /// or if it's longer than [`MAX_TITLE_LEN`] UTF-16 code units. The title is left unchanged in both cases.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() {
+    /// use ctru::applets::mii_selector::{MiiSelector, TitleError};
+    ///
+    /// let mut mii_selector = MiiSelector::new();
+    /// assert!(mii_selector.try_set_title("Select\0a Mii!").is_err());
+    ///
+    /// let long_title = "Mii".repeat(30);
+    /// assert_eq!(
+    ///     mii_selector.try_set_title(&long_title),
+    ///     Err(TitleError::TooLong { length: 90 })
+    /// );
+    /// # }
+    /// ```
+    #[doc(alias = "miiSelectorSetTitle")]
+    pub fn try_set_title(&mut self, text: &str) -> Result<(), TitleError> {
+        if let Some(position) = text.find('\0') {
+            return Err(TitleError::Nul { position });
+        }
+
+        let length = text.encode_utf16().count();
+        if length > MAX_TITLE_LEN {
+            return Err(TitleError::TooLong { length });
+        }
+
+        self.write_title(text);
+
+        Ok(())
+    }
+
+    fn write_title(&mut self, text: &str) {
+        // Unlike `miiSelectorSetTitle`, this never splits a surrogate pair when truncating.
+        str16::encode_into(text, &mut self.config.title[..MAX_TITLE_LEN]);
+        self.config.title[MAX_TITLE_LEN] = 0;
+    }
+
     /// Set the options of the Mii Selector.
     ///
-    /// This will overwrite any previously saved options. Use bitwise operations to set all your wanted options at once.
+    /// This will overwrite any previously saved options. Use bitwise operations to set all your wanted options at once,
+    /// or the individual setters (such as [`MiiSelector::set_enable_cancel()`]) to change a single one of them.
     ///
     /// # Example
     ///
@@ -138,6 +267,57 @@ impl MiiSelector {
         unsafe { ctru_sys::miiSelectorSetOptions(self.config.as_mut(), options.bits()) }
     }
 
+    /// Returns the options currently set.
+    pub fn options(&self) -> Options {
+        let mut options = Options::empty();
+
+        options.set(
+            Options::ENABLE_CANCEL,
+            self.config.enable_cancel_button != 0,
+        );
+        options.set(
+            Options::ENABLE_GUESTS,
+            self.config.enable_selecting_guests != 0,
+        );
+        options.set(Options::USE_TOP_SCREEN, self.config.show_on_top_screen != 0);
+        options.set(Options::START_WITH_GUESTS, self.config.show_guest_page != 0);
+
+        options
+    }
+
+    /// Show the Mii Selector window on the top screen (instead of the bottom screen).
+    pub fn set_use_top_screen(&mut self, enable: bool) {
+        self.config.show_on_top_screen = enable.into();
+    }
+
+    /// Show the cancel button.
+    pub fn set_enable_cancel(&mut self, enable: bool) {
+        self.config.enable_cancel_button = enable.into();
+    }
+
+    /// Make guest Miis available to select.
+    pub fn set_enable_guests(&mut self, enable: bool) {
+        self.config.enable_selecting_guests = enable.into();
+    }
+
+    /// Start the Mii Selector on the guests' page, rather than on the page of the user-made Miis.
+    ///
+    /// # Notes
+    ///
+    /// The applet doesn't offer any other choice of page, nor a way to sort the Miis: they're always listed in the order
+    /// of the console's Mii database.
+    #[doc(alias = "show_guest_page")]
+    pub fn set_start_with_guests(&mut self, enable: bool) {
+        self.config.show_guest_page = enable.into();
+    }
+
+    /// Returns the magic number of the configuration, which is [`CONFIG_MAGIC`] for valid configurations.
+    ///
+    /// Configurations created with [`MiiSelector::from_raw()`] may hold another value, in which case the applet will reject them.
+    pub fn magic(&self) -> u32 {
+        self.config.magic
+    }
+
     /// Allowlist a guest Mii based on its index.
     ///
     /// # Notes
@@ -252,6 +432,46 @@ impl MiiSelector {
         unsafe { ctru_sys::miiSelectorSetInitialIndex(self.config.as_mut(), index as u32) };
     }
 
+    /// Returns the raw `libctru` configuration of the Mii Selector.
+    ///
+    /// Together with [`MiiSelector::from_raw()`], this lets applications snapshot a configuration,
+    /// e.g. to check the result of their setup logic in tests.
+    pub fn as_raw(&self) -> &ctru_sys::MiiSelectorConf {
+        &self.config
+    }
+
+    /// Returns a mutable reference to the raw `libctru` configuration of the Mii Selector.
+    ///
+    /// This gives access to the fields of `MiiSelectorConf` without a dedicated setter, such as its unknown bytes.
+    ///
+    /// # Safety
+    ///
+    /// The applet trusts the configuration it's given: invalid values (e.g. a non-terminated title, or unknown bytes set to
+    /// values the applet doesn't expect) may make it misbehave or crash the system.
+    pub unsafe fn raw_config_mut(&mut self) -> &mut ctru_sys::MiiSelectorConf {
+        &mut self.config
+    }
+
+    /// Create a Mii Selector configuration from a raw `libctru` configuration.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// use ctru::applets::mii_selector::{MiiSelector, Options};
+    ///
+    /// let mut mii_selector = MiiSelector::new();
+    /// mii_selector.set_options(Options::ENABLE_CANCEL);
+    ///
+    /// let copy = MiiSelector::from_raw(*mii_selector.as_raw());
+    /// assert_eq!(mii_selector, copy);
+    /// ```
+    pub fn from_raw(config: ctru_sys::MiiSelectorConf) -> Self {
+        Self {
+            config: Box::new(config),
+        }
+    }
+
     /// Launch the Mii Selector.
     ///
     /// Depending on the configuration, the Mii Selector window will appear either
@@ -282,11 +502,15 @@ impl MiiSelector {
     /// ```
     #[doc(alias = "miiSelectorLaunch")]
     pub fn launch(&mut self, _apt: &Apt, _gfx: &Gfx) -> Result<Selection, Error> {
+        self.launch_raw()
+    }
+
+    fn launch_raw(&mut self) -> Result<Selection, Error> {
         let mut return_val = Box::<ctru_sys::MiiSelectorReturn>::default();
         unsafe { ctru_sys::miiSelectorLaunch(self.config.as_mut(), return_val.as_mut()) }
 
         if return_val.no_mii_selected != 0 {
-            return Err(Error::NoMiiSelected);
+            return Err(Error::UserCancelled);
         }
 
         if unsafe { ctru_sys::miiSelectorChecksumIsValid(return_val.as_mut()) } {
@@ -297,6 +521,186 @@ impl MiiSelector {
     }
 }
 
+impl MiiSelectorBuilder {
+    /// Set the title of the Mii Selector window.
+    ///
+    /// See [`MiiSelector::set_title()`].
+    ///
+    /// # Panics
+    /// This function will panic if the given `&str` contains NUL bytes.
+    pub fn title(mut self, text: &str) -> Self {
+        self.selector.set_title(text);
+        self
+    }
+
+    /// Set the title of the Mii Selector window, without panicking on invalid text.
+    ///
+    /// See [`MiiSelector::try_set_title()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the given `&str` contains NUL bytes, or if it's too long.
+    pub fn try_title(mut self, text: &str) -> Result<Self, TitleError> {
+        self.selector.try_set_title(text)?;
+        Ok(self)
+    }
+
+    /// Set the options of the Mii Selector, overwriting any previously set options.
+    ///
+    /// See [`MiiSelector::set_options()`].
+    pub fn options(mut self, options: Options) -> Self {
+        self.selector.set_options(options);
+        self
+    }
+
+    /// Show the Mii Selector window on the top screen (instead of the bottom screen).
+    ///
+    /// See [`MiiSelector::set_use_top_screen()`].
+    pub fn use_top_screen(mut self, enable: bool) -> Self {
+        self.selector.set_use_top_screen(enable);
+        self
+    }
+
+    /// Show the cancel button.
+    ///
+    /// See [`MiiSelector::set_enable_cancel()`].
+    pub fn enable_cancel(mut self, enable: bool) -> Self {
+        self.selector.set_enable_cancel(enable);
+        self
+    }
+
+    /// Make guest Miis available to select.
+    ///
+    /// See [`MiiSelector::set_enable_guests()`].
+    pub fn enable_guests(mut self, enable: bool) -> Self {
+        self.selector.set_enable_guests(enable);
+        self
+    }
+
+    /// Start the Mii Selector on the guests' page.
+    ///
+    /// See [`MiiSelector::set_start_with_guests()`].
+    pub fn start_with_guests(mut self, enable: bool) -> Self {
+        self.selector.set_start_with_guests(enable);
+        self
+    }
+
+    /// Allowlist a guest Mii based on its index.
+    ///
+    /// See [`MiiSelector::allowlist_guest_mii()`].
+    #[doc(alias = "whitelist")]
+    pub fn allowlist_guest_mii(mut self, mii_index: Index) -> Self {
+        self.selector.allowlist_guest_mii(mii_index);
+        self
+    }
+
+    /// Blocklist a guest Mii based on its index.
+    ///
+    /// See [`MiiSelector::blocklist_guest_mii()`].
+    #[doc(alias = "blacklist")]
+    pub fn blocklist_guest_mii(mut self, mii_index: Index) -> Self {
+        self.selector.blocklist_guest_mii(mii_index);
+        self
+    }
+
+    /// Allowlist a user-created Mii based on its index.
+    ///
+    /// See [`MiiSelector::allowlist_user_mii()`].
+    #[doc(alias = "whitelist")]
+    pub fn allowlist_user_mii(mut self, mii_index: Index) -> Self {
+        self.selector.allowlist_user_mii(mii_index);
+        self
+    }
+
+    /// Blocklist a user-created Mii based on its index.
+    ///
+    /// See [`MiiSelector::blocklist_user_mii()`].
+    #[doc(alias = "blacklist")]
+    pub fn blocklist_user_mii(mut self, mii_index: Index) -> Self {
+        self.selector.blocklist_user_mii(mii_index);
+        self
+    }
+
+    /// Set where the GUI cursor will start at.
+    ///
+    /// See [`MiiSelector::set_initial_index()`].
+    pub fn initial_index(mut self, index: usize) -> Self {
+        self.selector.set_initial_index(index);
+        self
+    }
+
+    /// Finish the configuration, returning a reusable [`MiiSelector`].
+    pub fn build(self) -> MiiSelector {
+        self.selector
+    }
+
+    /// Launch the Mii Selector with the built configuration.
+    ///
+    /// # Errors
+    ///
+    /// Other than the errors described in [`MiiSelector::launch()`], this function returns [`Error::GfxNotInitialized`]
+    /// if no [`Gfx`] handle is alive, since the applet would crash the console without it.
+    #[doc(alias = "miiSelectorLaunch")]
+    pub fn launch(mut self) -> Result<Selection, Error> {
+        if !Gfx::is_active() {
+            return Err(Error::GfxNotInitialized);
+        }
+
+        self.selector.launch_raw()
+    }
+
+    /// Launch the Mii Selector with the built configuration.
+    ///
+    /// Unlike [`MiiSelectorBuilder::launch()`], this function requires a reference to the [`Gfx`] service,
+    /// making sure the graphics are initialized before running the applet.
+    #[doc(alias = "miiSelectorLaunch")]
+    pub fn launch_with_gfx(mut self, _gfx: &Gfx) -> Result<Selection, Error> {
+        self.selector.launch_raw()
+    }
+}
+
+super::raw::impl_raw_config!(ctru_sys::MiiSelectorConf {
+    fields: [
+        enable_cancel_button,
+        enable_selecting_guests,
+        show_on_top_screen,
+        _unk0x3,
+        title,
+        _unk0x88,
+        show_guest_page,
+        _unk0x8D,
+        initial_index,
+        mii_guest_whitelist,
+        mii_whitelist,
+        _unk0xFE,
+        magic,
+    ],
+    bools: [],
+});
+
+impl PartialEq for MiiSelector {
+    fn eq(&self, other: &Self) -> bool {
+        super::raw::bytes(self.config.as_ref()) == super::raw::bytes(other.config.as_ref())
+    }
+}
+
+impl Eq for MiiSelector {}
+
+/// Serialized as the bytes of the fields of the raw configuration (see [`MiiSelector::as_raw()`]).
+#[cfg(feature = "serde")]
+impl serde::Serialize for MiiSelector {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        super::raw::serialize(self.config.as_ref(), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MiiSelector {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        super::raw::deserialize(deserializer).map(Self::from_raw)
+    }
+}
+
 impl Default for MiiSelector {
     fn default() -> Self {
         Self::new()
@@ -307,28 +711,40 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::InvalidChecksum => write!(f, "selected mii has invalid checksum"),
-            Self::NoMiiSelected => write!(f, "no mii was selected"),
+            Self::GfxNotInitialized => write!(f, "the gfx service is not initialized"),
+            Self::UserCancelled => write!(f, "no mii was selected"),
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+impl fmt::Display for TitleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nul { position } => write!(f, "title contains a NUL byte at position {position}"),
+            Self::TooLong { length } => write!(
+                f,
+                "title is {length} UTF-16 code units long, but at most {MAX_TITLE_LEN} are supported"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TitleError {}
+
 impl From<ctru_sys::MiiSelectorReturn> for Selection {
     fn from(ret: ctru_sys::MiiSelectorReturn) -> Self {
         let raw_mii_data = ret.mii;
-        let mut guest_mii_name = ret.guest_mii_name;
 
         Selection {
             mii_data: raw_mii_data.into(),
+            raw_mii_data,
             mii_type: if ret.guest_mii_index != 0xFFFFFFFF {
                 MiiType::Guest {
                     index: ret.guest_mii_index,
-                    name: {
-                        let utf16_be = &mut guest_mii_name;
-                        utf16_be.reverse();
-                        String::from_utf16(utf16_be.as_slice()).unwrap()
-                    },
+                    // Guest names are stored as big-endian UTF-16.
+                    name: str16::from_units(&ret.guest_mii_name.map(u16::from_be)),
                 }
             } else {
                 MiiType::User
@@ -342,3 +758,61 @@ impl From<u32> for Index {
         Self::Index(v)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_round_trip() {
+        let mut selector = MiiSelector::new();
+        selector.set_title("Pick one");
+        selector.blocklist_user_mii(Index::Index(2));
+
+        let copy = MiiSelector::from_raw(*selector.as_raw());
+        assert_eq!(selector, copy);
+
+        selector.set_use_top_screen(true);
+        assert_ne!(selector, copy);
+    }
+
+    #[test]
+    fn individual_options() {
+        let mut selector = MiiSelector::new();
+        assert_eq!(selector.magic(), CONFIG_MAGIC);
+
+        selector.set_options(Options::ENABLE_GUESTS);
+        selector.set_start_with_guests(true);
+        selector.set_enable_cancel(true);
+        assert_eq!(
+            selector.options(),
+            Options::ENABLE_GUESTS | Options::START_WITH_GUESTS | Options::ENABLE_CANCEL
+        );
+
+        selector.set_enable_guests(false);
+        assert!(!selector.options().contains(Options::ENABLE_GUESTS));
+    }
+
+    #[test]
+    fn title_length() {
+        let mut selector = MiiSelector::new();
+
+        let exact = "a".repeat(MAX_TITLE_LEN);
+        assert_eq!(selector.try_set_title(&exact), Ok(()));
+
+        let crab = "🦀".repeat(MAX_TITLE_LEN / 2 + 1);
+        assert_eq!(
+            selector.try_set_title(&crab),
+            Err(TitleError::TooLong {
+                length: MAX_TITLE_LEN + 1
+            })
+        );
+
+        // Truncating never leaves half of a surrogate pair.
+        selector.set_title(&crab);
+        assert_eq!(
+            str16::from_units(&selector.as_raw().title),
+            "🦀".repeat(MAX_TITLE_LEN / 2)
+        );
+    }
+}
@@ -8,5 +8,177 @@
 //!
 //! Applets block execution of the thread that launches them as long as the user doesn't close the applet.
 
+pub mod error;
 pub mod mii_selector;
 pub mod swkbd;
+
+/// Helpers to compare and (de)serialize applet configurations through their raw `libctru` structures.
+///
+/// Configurations are handled field by field (see [`RawConfig`](raw::RawConfig)), since reading the padding bytes of a structure
+/// or writing arbitrary bytes over its `bool` fields is undefined behaviour.
+mod raw {
+    use std::mem::size_of;
+
+    /// Byte range of a field of a raw configuration.
+    #[derive(Copy, Clone, Debug)]
+    pub(crate) struct Field {
+        offset: usize,
+        size: usize,
+        /// Whether the field holds `bool`s, for which only 0 and 1 are valid.
+        boolean: bool,
+    }
+
+    impl Field {
+        pub(crate) const fn new(offset: usize, size: usize, boolean: bool) -> Self {
+            Self {
+                offset,
+                size,
+                boolean,
+            }
+        }
+    }
+
+    /// Plain `libctru` configuration structure, described by the fields it is made of.
+    pub(crate) trait RawConfig: Copy + Default {
+        /// Returns the fields of the structure, which must not overlap.
+        ///
+        /// Fields holding pointers must be left out, since they're only valid within the process that set them.
+        fn fields() -> Vec<Field>;
+    }
+
+    /// Returns the size of the field selected by `_field`.
+    pub(crate) fn size_of_field<T, F>(_field: impl Fn(&T) -> &F) -> usize {
+        size_of::<F>()
+    }
+
+    /// Implement [`RawConfig`] for a structure, listing its integer fields and its `bool` fields.
+    macro_rules! impl_raw_config {
+        ($ty:ty { fields: [$($field:ident),* $(,)?], bools: [$($boolean:ident),* $(,)?] $(,)? }) => {
+            impl $crate::applets::raw::RawConfig for $ty {
+                fn fields() -> Vec<$crate::applets::raw::Field> {
+                    vec![
+                        $($crate::applets::raw::Field::new(
+                            std::mem::offset_of!($ty, $field),
+                            $crate::applets::raw::size_of_field(|raw: &$ty| &raw.$field),
+                            false,
+                        ),)*
+                        $($crate::applets::raw::Field::new(
+                            std::mem::offset_of!($ty, $boolean),
+                            $crate::applets::raw::size_of_field(|raw: &$ty| &raw.$boolean),
+                            true,
+                        ),)*
+                    ]
+                }
+            }
+        };
+    }
+
+    pub(crate) use impl_raw_config;
+
+    /// Returns the bytes of the fields of a configuration, one after the other.
+    pub(crate) fn bytes<T: RawConfig>(raw: &T) -> Vec<u8> {
+        let base = (raw as *const T).cast::<u8>();
+
+        T::fields()
+            .into_iter()
+            .flat_map(|field| {
+                // SAFETY: the range only covers a field of `raw`, which is always initialized.
+                unsafe { std::slice::from_raw_parts(base.add(field.offset), field.size) }
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Build a configuration from the bytes returned by [`bytes()`], starting from its default value.
+    ///
+    /// Returns `None` if the length doesn't match, or if a `bool` field holds another value than 0 or 1.
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_bytes<T: RawConfig>(data: &[u8]) -> Option<T> {
+        let fields = T::fields();
+        if data.len() != fields.iter().map(|field| field.size).sum::<usize>() {
+            return None;
+        }
+
+        let mut position = 0;
+        for field in &fields {
+            let bytes = &data[position..position + field.size];
+            if field.boolean && bytes.iter().any(|&byte| byte > 1) {
+                return None;
+            }
+            position += field.size;
+        }
+
+        let mut raw = T::default();
+        let base = (&mut raw as *mut T).cast::<u8>();
+
+        let mut position = 0;
+        for field in &fields {
+            // SAFETY: the range only covers a field of `raw`, and the bytes are valid for it:
+            // integer fields accept any value, and `bool` fields were checked above.
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    data[position..].as_ptr(),
+                    base.add(field.offset),
+                    field.size,
+                )
+            };
+            position += field.size;
+        }
+
+        Some(raw)
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn serialize<T: RawConfig, S: serde::Serializer>(
+        raw: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&bytes(raw))
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn deserialize<'de, T: RawConfig, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        use serde::de::{Error, SeqAccess, Visitor};
+        use std::fmt;
+        use std::marker::PhantomData;
+
+        struct RawVisitor<T>(PhantomData<T>);
+
+        impl<T: RawConfig> RawVisitor<T> {
+            fn read<E: Error>(data: &[u8]) -> Result<T, E> {
+                from_bytes(data).ok_or_else(|| {
+                    E::invalid_value(
+                        serde::de::Unexpected::Bytes(data),
+                        &"the fields of the configuration",
+                    )
+                })
+            }
+        }
+
+        impl<'de, T: RawConfig> Visitor<'de> for RawVisitor<T> {
+            type Value = T;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "the bytes of the fields of an applet configuration")
+            }
+
+            fn visit_bytes<E: Error>(self, data: &[u8]) -> Result<T, E> {
+                Self::read(data)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+                let mut data = Vec::new();
+
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    data.push(byte);
+                }
+
+                Self::read(&data)
+            }
+        }
+
+        deserializer.deserialize_bytes(RawVisitor(PhantomData))
+    }
+}
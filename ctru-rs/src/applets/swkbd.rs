@@ -1,27 +1,88 @@
 //! Software Keyboard applet.
 //!
 //! This applet opens a virtual keyboard on the console's bottom screen which lets the user write UTF-16 valid text.
+//! When the applet isn't available, the `ui` feature provides an on-screen keyboard drawn by the application itself,
+//! returning the same [`KeyboardResult`].
 #![doc(alias = "keyboard")]
 
-use crate::services::{apt::Apt, gfx::Gfx};
+use crate::font;
+use crate::services::{
+    apt::Apt,
+    cfgu::{Cfgu, Language},
+    gfx::Gfx,
+};
 use ctru_sys::{self, SwkbdState};
 
 use bitflags::bitflags;
 use libc;
 
-use std::ffi::{CStr, CString};
-use std::fmt::Display;
+use std::ffi::{CStr, CString, NulError};
+use std::fmt::{self, Display};
 use std::iter::once;
 use std::str;
 
 type CallbackFunction = dyn Fn(&CStr) -> (CallbackResult, Option<CString>);
 
+/// Outcome of a text input: the text written and the button used to close the keyboard.
+///
+/// The same result is returned by the applet ([`SoftwareKeyboard::get_string()`]) and by the
+/// on-screen keyboard widget of the `ui` module, so that either can be used as a fallback for the other.
+pub type KeyboardResult = Result<(String, Button), Error>;
+
 /// Configuration structure to setup the Software Keyboard applet.
 #[doc(alias = "SwkbdState")]
 pub struct SoftwareKeyboard {
     state: Box<SwkbdState>,
     callback: Option<Box<CallbackFunction>>,
     error_message: Option<CString>,
+    initial_text: Option<String>,
+    locale_filter: LocaleFilter,
+    locale_filter_message: CString,
+}
+
+/// Multi-line text editor keeping its text and the keyboard's state between invocations of the Software Keyboard.
+///
+/// Each call to [`TextEditor::edit()`] opens the keyboard on the current text, and replaces it with the user's input
+/// if the keyboard is closed with a button that [submits](SoftwareKeyboard::button_submits) it.
+/// Cancelling keeps the text from before the invocation.
+///
+/// Between invocations, the editor also hands back to the applet the status data it saved when it last closed,
+/// so it can restore its own editing state (such as its input mode) instead of starting from scratch.
+///
+/// # Notes
+///
+/// `libctru` doesn't let applications place the keyboard's cursor: it is restored only as far as the applet's status data does,
+/// and starts at the end of the text otherwise.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # use ctru::services::{apt::Apt, gfx::Gfx};
+/// #
+/// # let gfx = Gfx::new().unwrap();
+/// # let apt = Apt::new().unwrap();
+/// #
+/// use ctru::applets::swkbd::TextEditor;
+///
+/// let mut notes = TextEditor::new("Shopping list:\n");
+/// notes.keyboard_mut().set_hint_text("Write your notes");
+///
+/// // Calling `edit()` again continues editing the same notes.
+/// notes.edit(4096, &apt, &gfx)?;
+/// println!("{}", notes.text());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "swkbdSetStatusData")]
+pub struct TextEditor {
+    keyboard: SoftwareKeyboard,
+    text: String,
+    status: Box<ctru_sys::SwkbdStatusData>,
+    has_status: bool,
 }
 
 /// Configuration structure to setup the Parental Lock applet.
@@ -206,6 +267,41 @@ bitflags! {
     }
 }
 
+/// Conversion between the full-width and half-width forms of ASCII characters, applied by a [`LocaleFilter`].
+///
+/// Japanese, Chinese and Korean keyboards can write letters, digits and symbols in their full-width form
+/// (e.g. `ＡＢＣ１２３`), which look like the ASCII ones but don't compare equal to them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WidthConversion {
+    /// Keep the text as written.
+    #[default]
+    Keep,
+    /// Convert full-width forms (and the ideographic space) to ASCII.
+    HalfWidth,
+    /// Convert printable ASCII characters (and the space) to their full-width forms.
+    ///
+    /// Each converted character grows from 1 to 3 bytes.
+    FullWidth,
+}
+
+/// Locale-aware rules applied to the text written with a [`SoftwareKeyboard`].
+///
+/// See [`SoftwareKeyboard::set_locale_filter()`].
+///
+/// # Notes
+///
+/// The width conversion can change the length of the text: with [`WidthConversion::FullWidth`], ASCII text becomes
+/// up to three times as long (in bytes), and can exceed the limit set with [`SoftwareKeyboard::set_max_text_len()`].
+/// The text returned by the keyboard is truncated on a character boundary to fit the output buffer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LocaleFilter {
+    /// Conversion applied to the character widths of the input.
+    pub width: WidthConversion,
+    /// Whether to reject input containing characters missing from the system's shared font,
+    /// which the system (and any application drawing text with it) can't display.
+    pub require_font_glyphs: bool,
+}
+
 impl SoftwareKeyboard {
     /// Initialize a new configuration for the Software Keyboard applet depending on how many "exit" buttons are available to the user (1, 2 or 3).
     ///
@@ -233,6 +329,9 @@ impl SoftwareKeyboard {
                 state,
                 callback: None,
                 error_message: None,
+                initial_text: None,
+                locale_filter: LocaleFilter::default(),
+                locale_filter_message: CString::new(UNSUPPORTED_CHARACTER_MESSAGE).unwrap(),
             }
         }
     }
@@ -241,7 +340,8 @@ impl SoftwareKeyboard {
     ///
     /// # Notes
     ///
-    /// The text received from the keyboard will be truncated if it is longer than `max_bytes`.
+    /// The text received from the keyboard will be truncated if it is longer than `max_bytes`,
+    /// including after the [locale filter](SoftwareKeyboard::set_locale_filter())'s width conversion.
     /// Use [`SoftwareKeyboard::set_max_text_len()`] to make sure the buffer can contain the input text.
     ///
     /// # Example
@@ -264,12 +364,7 @@ impl SoftwareKeyboard {
     /// # }
     /// ```
     #[doc(alias = "swkbdInputText")]
-    pub fn get_string(
-        &mut self,
-        max_bytes: usize,
-        apt: &Apt,
-        gfx: &Gfx,
-    ) -> Result<(String, Button), Error> {
+    pub fn get_string(&mut self, max_bytes: usize, apt: &Apt, gfx: &Gfx) -> KeyboardResult {
         // Unfortunately the libctru API doesn't really provide a way to get the exact length
         // of the string that it receieves from the software keyboard. Instead it expects you
         // to pass in a buffer and hope that it's big enough to fit the entire string, so
@@ -294,7 +389,8 @@ impl SoftwareKeyboard {
     /// # Notes
     ///
     /// If the buffer is too small to contain the entire sequence received from the keyboard,
-    /// the output will be truncated.
+    /// the output will be truncated. The [locale filter](SoftwareKeyboard::set_locale_filter())'s width conversion
+    /// is applied to the buffer, truncating the converted text on a character boundary if it doesn't fit.
     ///
     /// # Example
     ///
@@ -319,7 +415,7 @@ impl SoftwareKeyboard {
     /// ```
     #[doc(alias = "swkbdInputText")]
     pub fn write_exact(&mut self, buf: &mut [u8], _apt: &Apt, _gfx: &Gfx) -> Result<Button, Error> {
-        unsafe {
+        let button = unsafe {
             // The filter callback gets reset every time the SoftwareKeyboard is used.
             ctru_sys::swkbdSetFilterCallback(
                 self.state.as_mut(),
@@ -328,17 +424,24 @@ impl SoftwareKeyboard {
             );
 
             match ctru_sys::swkbdInputText(self.state.as_mut(), buf.as_mut_ptr(), buf.len()) {
-                ctru_sys::SWKBD_BUTTON_NONE => Err(self.state.result.into()),
-                ctru_sys::SWKBD_BUTTON_LEFT => Ok(Button::Left),
-                ctru_sys::SWKBD_BUTTON_MIDDLE => Ok(Button::Middle),
-                ctru_sys::SWKBD_BUTTON_RIGHT => Ok(Button::Right),
+                ctru_sys::SWKBD_BUTTON_NONE => return Err(self.state.result.into()),
+                ctru_sys::SWKBD_BUTTON_LEFT => Button::Left,
+                ctru_sys::SWKBD_BUTTON_MIDDLE => Button::Middle,
+                ctru_sys::SWKBD_BUTTON_RIGHT => Button::Right,
                 _ => unreachable!(),
             }
-        }
+        };
+
+        self.locale_filter.normalize_buffer(buf);
+
+        Ok(button)
     }
 
     /// Set special features for this keyboard.
     ///
+    /// This overwrites every feature at once: [`SoftwareKeyboard::set_darken_top_screen()`], [`SoftwareKeyboard::set_fixed_width()`]
+    /// and [`SoftwareKeyboard::set_multiline()`] change a single one of them, leaving the others untouched.
+    ///
     /// # Example
     ///
     /// ```
@@ -420,6 +523,49 @@ impl SoftwareKeyboard {
         self.callback = callback;
     }
 
+    /// Configure locale-aware rules for the input, such as width normalization of Japanese text.
+    ///
+    /// Input rejected by the filter (see [`LocaleFilter::require_font_glyphs`]) lets the user try again with an error message,
+    /// set with [`SoftwareKeyboard::set_locale_filter_message()`].
+    /// Otherwise, the custom filter callback (see [`SoftwareKeyboard::set_filter_callback()`]) receives the normalized text,
+    /// which is also the text written by [`SoftwareKeyboard::get_string()`] and [`SoftwareKeyboard::write_exact()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::applets::swkbd::{LocaleFilter, SoftwareKeyboard};
+    /// use ctru::services::cfgu::Cfgu;
+    ///
+    /// let cfgu = Cfgu::new()?;
+    ///
+    /// let mut keyboard = SoftwareKeyboard::default();
+    /// keyboard.set_locale_filter(LocaleFilter::from_system(&cfgu)?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_locale_filter(&mut self, filter: LocaleFilter) {
+        self.locale_filter = filter;
+    }
+
+    /// Returns the locale-aware rules applied to the input.
+    pub fn locale_filter(&self) -> LocaleFilter {
+        self.locale_filter
+    }
+
+    /// Set the error message shown when the [locale filter](SoftwareKeyboard::set_locale_filter()) rejects the input,
+    /// e.g. to translate it in the system language.
+    ///
+    /// The message is cut at its first NUL byte. By default, an English message explains that the text can't be displayed.
+    pub fn set_locale_filter_message(&mut self, message: &str) {
+        let message = message.split('\0').next().unwrap_or_default();
+        self.locale_filter_message = CString::new(message).unwrap();
+    }
+
     /// Internal function called by the filter callback.
     extern "C" fn internal_callback(
         user: *mut libc::c_void,
@@ -433,12 +579,25 @@ impl SoftwareKeyboard {
             // Reset any leftover error message.
             (*this).error_message = None;
 
-            let text = CStr::from_ptr(text);
+            let filter = (*this).locale_filter;
+            let text = CStr::from_ptr(text).to_string_lossy();
+
+            if !filter.accepts(&text) {
+                (*this).error_message = Some((*this).locale_filter_message.clone());
+                if let Some(message) = &(*this).error_message {
+                    *pp_message = message.as_ptr();
+                }
+
+                return CallbackResult::Retry.into();
+            }
+
+            // The conversions never add NUL characters.
+            let text = CString::new(filter.normalize(&text)).unwrap();
 
             let result = {
                 // Run the callback if still available.
                 if let Some(callback) = &mut (*this).callback {
-                    let (res, cstr) = callback(text);
+                    let (res, cstr) = callback(&text);
 
                     // Due to how `libctru` operates, the user is expected to keep the error message alive until
                     // the end of the Software Keyboard prompt. We ensure that happens by saving it within the configuration.
@@ -502,10 +661,38 @@ impl SoftwareKeyboard {
     /// # }
     #[doc(alias = "swkbdSetInitialText")]
     pub fn set_initial_text(&mut self, text: &str) {
-        unsafe {
-            let nul_terminated: String = text.chars().chain(once('\0')).collect();
-            ctru_sys::swkbdSetInitialText(self.state.as_mut(), nul_terminated.as_ptr());
-        }
+        // `libctru` only stores the pointer, so the text must live as long as the configuration.
+        let nul_terminated: &str = self
+            .initial_text
+            .insert(text.chars().chain(once('\0')).collect());
+
+        unsafe { ctru_sys::swkbdSetInitialText(self.state.as_mut(), nul_terminated.as_ptr()) };
+    }
+
+    /// Set the initial text for this software keyboard to the contents of the [clipboard](crate::services::apt::clipboard).
+    ///
+    /// Combined with [`clipboard::copy()`](crate::services::apt::clipboard::copy) on the returned text,
+    /// this lets users carry text from one input to another.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # fn main() {
+    /// #
+    /// use ctru::applets::swkbd::SoftwareKeyboard;
+    /// use ctru::services::apt::clipboard;
+    ///
+    /// clipboard::copy("Copied text");
+    ///
+    /// let mut keyboard = SoftwareKeyboard::default();
+    /// keyboard.paste_initial_text();
+    /// #
+    /// # }
+    /// ```
+    #[doc(alias = "swkbdSetInitialText")]
+    pub fn paste_initial_text(&mut self) {
+        self.set_initial_text(&crate::services::apt::clipboard::paste());
     }
 
     /// Set the hint text for this software keyboard.
@@ -532,6 +719,21 @@ impl SoftwareKeyboard {
         }
     }
 
+    /// Set the hint text for this software keyboard, rejecting text which contains NUL bytes.
+    ///
+    /// [`SoftwareKeyboard::set_hint_text()`] silently cuts the text at the first NUL byte instead.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the given `&str` contains NUL bytes.
+    #[doc(alias = "swkbdSetHintText")]
+    pub fn try_set_hint_text(&mut self, text: &str) -> Result<(), NulError> {
+        let c_text = CString::new(text)?;
+        unsafe { ctru_sys::swkbdSetHintText(self.state.as_mut(), c_text.as_ptr().cast()) };
+
+        Ok(())
+    }
+
     /// Set a password mode for this software keyboard.
     ///
     /// Depending on the selected mode the input text will be concealed.
@@ -595,6 +797,41 @@ impl SoftwareKeyboard {
         }
     }
 
+    /// Darken the top screen while the keyboard is active.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # fn main() {
+    /// #
+    /// use ctru::applets::swkbd::SoftwareKeyboard;
+    /// let mut keyboard = SoftwareKeyboard::default();
+    ///
+    /// // Keep the application's top screen visible while typing.
+    /// keyboard.set_darken_top_screen(false);
+    /// #
+    /// # }
+    /// ```
+    pub fn set_darken_top_screen(&mut self, enable: bool) {
+        self.state.darken_top_screen = enable.into();
+    }
+
+    /// Show the input text in a fixed-width font.
+    pub fn set_fixed_width(&mut self, enable: bool) {
+        self.state.fixed_width = enable;
+    }
+
+    /// Let the user write multiple lines of text, with line breaks in the returned text.
+    pub fn set_multiline(&mut self, enable: bool) {
+        self.state.multiline = enable;
+    }
+
+    /// Returns whether pressing `button` accepts the keyboard's input (see [`SoftwareKeyboard::configure_button()`]).
+    pub fn button_submits(&self, button: Button) -> bool {
+        self.state.button_submits_text[button as usize]
+    }
+
     /// Configure the look and behavior of a button for this keyboard.
     ///
     /// # Arguments
@@ -634,6 +871,45 @@ impl SoftwareKeyboard {
         }
     }
 
+    /// Configure the look and behavior of a button for this keyboard, rejecting text which contains NUL bytes.
+    ///
+    /// [`SoftwareKeyboard::configure_button()`] silently cuts the text at the first NUL byte instead.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the given `&str` contains NUL bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// use ctru::applets::swkbd::{SoftwareKeyboard, Button};
+    ///
+    /// let mut keyboard = SoftwareKeyboard::default();
+    ///
+    /// let user_label = "Send\0";
+    /// assert!(keyboard.try_configure_button(Button::Right, user_label, true).is_err());
+    /// ```
+    #[doc(alias = "swkbdSetButton")]
+    pub fn try_configure_button(
+        &mut self,
+        button: Button,
+        text: &str,
+        submit: bool,
+    ) -> Result<(), NulError> {
+        let c_text = CString::new(text)?;
+        unsafe {
+            ctru_sys::swkbdSetButton(
+                self.state.as_mut(),
+                button.into(),
+                c_text.as_ptr().cast(),
+                submit,
+            )
+        };
+
+        Ok(())
+    }
+
     /// Configure the maximum number of UTF-16 code units that can be entered into the software
     /// keyboard. By default the limit is `65000` code units.
     ///
@@ -664,6 +940,136 @@ impl SoftwareKeyboard {
         // Activate the specific validation rule for maximum length.
         self.state.valid_input = ValidInput::FixedLen.into();
     }
+
+    /// Returns the raw `libctru` configuration of the keyboard.
+    ///
+    /// Together with [`SoftwareKeyboard::from_raw()`], this lets applications snapshot a configuration,
+    /// e.g. to check the result of their setup logic in tests.
+    pub fn as_raw(&self) -> &SwkbdState {
+        &self.state
+    }
+
+    /// Create a keyboard configuration from a raw `libctru` configuration.
+    ///
+    /// # Notes
+    ///
+    /// The pointers stored by `libctru` in the configuration (such as the initial text and the filter callback) are cleared,
+    /// since they may not be valid anymore: set them again with [`SoftwareKeyboard::set_initial_text()`]
+    /// and [`SoftwareKeyboard::set_filter_callback()`] if needed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// use ctru::applets::swkbd::{SoftwareKeyboard, ValidInput, Filters};
+    ///
+    /// let mut keyboard = SoftwareKeyboard::default();
+    /// keyboard.set_validation(ValidInput::NotEmptyNotBlank, Filters::DIGITS);
+    ///
+    /// let copy = SoftwareKeyboard::from_raw(*keyboard.as_raw());
+    /// assert_eq!(keyboard, copy);
+    /// ```
+    pub fn from_raw(mut state: SwkbdState) -> Self {
+        clear_pointers(&mut state);
+
+        Self {
+            state: Box::new(state),
+            callback: None,
+            error_message: None,
+            initial_text: None,
+            locale_filter: LocaleFilter::default(),
+            locale_filter_message: CString::new(UNSUPPORTED_CHARACTER_MESSAGE).unwrap(),
+        }
+    }
+}
+
+/// Clear the `SwkbdExtra` pointers of a configuration, which are only valid within the process that set them.
+fn clear_pointers(state: &mut SwkbdState) {
+    state.__bindgen_anon_1.extra = ctru_sys::SwkbdExtra::default();
+}
+
+// The `SwkbdExtra` pointers are left out.
+super::raw::impl_raw_config!(SwkbdState {
+    fields: [
+        type_,
+        num_buttons_m1,
+        valid_input,
+        password_mode,
+        is_parental_screen,
+        darken_top_screen,
+        filter_flags,
+        save_state_flags,
+        max_text_len,
+        dict_word_count,
+        max_digits,
+        button_text,
+        numpad_keys,
+        hint_text,
+        language,
+        initial_text_offset,
+        dict_offset,
+        initial_status_offset,
+        initial_learning_offset,
+        shared_memory_size,
+        version,
+        result,
+        status_offset,
+        learning_offset,
+        text_offset,
+        text_length,
+        callback_result,
+        callback_msg,
+    ],
+    bools: [
+        predictive_input,
+        multiline,
+        fixed_width,
+        allow_home,
+        allow_reset,
+        allow_power,
+        unknown,
+        default_qwerty,
+        button_submits_text,
+        skip_at_check,
+    ],
+});
+
+/// Configurations are equal if their raw `libctru` configurations match, ignoring the initial text and the filter callback.
+impl PartialEq for SoftwareKeyboard {
+    fn eq(&self, other: &Self) -> bool {
+        super::raw::bytes(self.state.as_ref()) == super::raw::bytes(other.state.as_ref())
+    }
+}
+
+impl Eq for SoftwareKeyboard {}
+
+impl fmt::Debug for SoftwareKeyboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SoftwareKeyboard")
+            .field("type", &self.state.type_)
+            .field("num_buttons", &(self.state.num_buttons_m1 + 1))
+            .field("valid_input", &self.state.valid_input)
+            .field("filter_flags", &self.state.filter_flags)
+            .field("max_text_len", &self.state.max_text_len)
+            .field("has_callback", &self.callback.is_some())
+            .field("locale_filter", &self.locale_filter)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Serialized as the bytes of the fields of the raw configuration (see [`SoftwareKeyboard::as_raw()`]), without padding or pointers.
+#[cfg(feature = "serde")]
+impl serde::Serialize for SoftwareKeyboard {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        super::raw::serialize(self.state.as_ref(), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SoftwareKeyboard {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        super::raw::deserialize(deserializer).map(Self::from_raw)
+    }
 }
 
 impl ParentalLock {
@@ -727,6 +1133,190 @@ impl ParentalLock {
     }
 }
 
+/// Default error message shown when a [`LocaleFilter`] rejects the input (see [`SoftwareKeyboard::set_locale_filter_message()`]).
+const UNSUPPORTED_CHARACTER_MESSAGE: &str =
+    "This text contains characters that can't be displayed.";
+
+/// Offset between the full-width forms (`U+FF01` to `U+FF5E`) and the printable ASCII characters.
+const FULL_WIDTH_OFFSET: u32 = 0xFEE0;
+
+impl LocaleFilter {
+    /// Returns the rules suiting the keyboards of the given system language.
+    ///
+    /// Japanese, Chinese and Korean text gets its full-width ASCII characters converted to half-width,
+    /// and characters missing from the shared font are rejected for every language.
+    pub fn for_language(language: Language) -> Self {
+        let width = match language {
+            Language::Japanese
+            | Language::SimplifiedChinese
+            | Language::TraditionalChinese
+            | Language::Korean => WidthConversion::HalfWidth,
+            _ => WidthConversion::Keep,
+        };
+
+        Self {
+            width,
+            require_font_glyphs: true,
+        }
+    }
+
+    /// Returns the rules suiting the console's system language (see [`LocaleFilter::for_language()`]).
+    #[doc(alias = "CFGU_GetSystemLanguage")]
+    pub fn from_system(cfgu: &Cfgu) -> crate::Result<Self> {
+        Ok(Self::for_language(cfgu.language()?))
+    }
+
+    /// Returns `text` with the [width conversion](LocaleFilter::width) applied.
+    pub fn normalize(&self, text: &str) -> String {
+        match self.width {
+            WidthConversion::Keep => text.to_owned(),
+            WidthConversion::HalfWidth => text.chars().map(to_half_width).collect(),
+            WidthConversion::FullWidth => text.chars().map(to_full_width).collect(),
+        }
+    }
+
+    /// Apply the width conversion to the NUL-terminated text in `buf`, truncating it on a character boundary
+    /// if the converted text (and its NUL terminator) doesn't fit.
+    fn normalize_buffer(&self, buf: &mut [u8]) {
+        if self.width == WidthConversion::Keep {
+            return;
+        }
+
+        let Some(capacity) = buf.len().checked_sub(1) else {
+            return;
+        };
+
+        let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        let normalized = self.normalize(&String::from_utf8_lossy(&buf[..len]));
+
+        let mut end = normalized.len().min(capacity);
+        while !normalized.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        buf[..end].copy_from_slice(&normalized.as_bytes()[..end]);
+        buf[end] = 0;
+    }
+
+    /// Returns whether the filter accepts `text`.
+    pub fn accepts(&self, text: &str) -> bool {
+        !self.require_font_glyphs || text.chars().all(font::has_glyph)
+    }
+}
+
+fn to_half_width(c: char) -> char {
+    match c {
+        '\u{3000}' => ' ',
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - FULL_WIDTH_OFFSET).unwrap(),
+        _ => c,
+    }
+}
+
+fn to_full_width(c: char) -> char {
+    match c {
+        ' ' => '\u{3000}',
+        '!'..='~' => char::from_u32(c as u32 + FULL_WIDTH_OFFSET).unwrap(),
+        _ => c,
+    }
+}
+
+impl TextEditor {
+    /// Create an editor starting with `text`, using a multi-line [`Kind::Normal`] keyboard with "Cancel" and "OK" buttons.
+    pub fn new(text: impl Into<String>) -> Self {
+        let mut keyboard = SoftwareKeyboard::new(Kind::Normal, ButtonConfig::LeftRight);
+        keyboard.set_multiline(true);
+
+        Self::with_keyboard(keyboard, text)
+    }
+
+    /// Create an editor starting with `text`, using the given keyboard configuration.
+    ///
+    /// The initial text of the configuration is replaced by the editor's text on each invocation.
+    pub fn with_keyboard(keyboard: SoftwareKeyboard, text: impl Into<String>) -> Self {
+        Self {
+            keyboard,
+            text: text.into(),
+            status: Box::default(),
+            has_status: false,
+        }
+    }
+
+    /// Returns the configuration of the keyboard, e.g. to set its hint text or its filters.
+    pub fn keyboard_mut(&mut self) -> &mut SoftwareKeyboard {
+        &mut self.keyboard
+    }
+
+    /// Returns the current text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replace the current text.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+    }
+
+    /// Returns the current text, consuming the editor.
+    pub fn into_text(self) -> String {
+        self.text
+    }
+
+    /// Forget the applet's saved state, so the next invocation starts from its default state.
+    pub fn reset_state(&mut self) {
+        self.has_status = false;
+    }
+
+    /// Open the keyboard on the current text, and returns the button used to close it.
+    ///
+    /// The text is replaced by the input if the button submits it. As with [`SoftwareKeyboard::get_string()`],
+    /// the input is truncated if it is longer than `max_bytes`.
+    #[doc(alias = "swkbdInputText")]
+    pub fn edit(&mut self, max_bytes: usize, apt: &Apt, gfx: &Gfx) -> Result<Button, Error> {
+        self.keyboard.set_initial_text(&self.text);
+
+        // The applet only reads the status data once it wrote it at least once.
+        unsafe {
+            ctru_sys::swkbdSetStatusData(
+                self.keyboard.state.as_mut(),
+                self.status.as_mut(),
+                self.has_status,
+                true,
+            )
+        };
+
+        let result = self.keyboard.get_string(max_bytes, apt, gfx);
+
+        // Don't leave a pointer to the status data in the configuration, which may be moved out of the editor.
+        unsafe {
+            ctru_sys::swkbdSetStatusData(
+                self.keyboard.state.as_mut(),
+                std::ptr::null_mut(),
+                false,
+                false,
+            )
+        };
+
+        let (text, button) = result?;
+        self.has_status = true;
+
+        if self.keyboard.button_submits(button) {
+            self.text = text;
+        }
+
+        Ok(button)
+    }
+}
+
+impl fmt::Debug for TextEditor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextEditor")
+            .field("keyboard", &self.keyboard)
+            .field("text", &self.text)
+            .field("has_status", &self.has_status)
+            .finish_non_exhaustive()
+    }
+}
+
 /// Creates a new [`SoftwareKeyboard`] configuration set to using a [`Kind::Normal`] keyboard and 2 [`Button`]s.
 impl Default for SoftwareKeyboard {
     fn default() -> Self {
@@ -802,3 +1392,59 @@ from_impl!(ValidInput, u32);
 from_impl!(ButtonConfig, i32);
 from_impl!(PasswordMode, u32);
 from_impl!(CallbackResult, u32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn width_conversion() {
+        let half = LocaleFilter {
+            width: WidthConversion::HalfWidth,
+            require_font_glyphs: false,
+        };
+        assert_eq!(half.normalize("ＡＢＣ\u{3000}１２３！～"), "ABC 123!~");
+        assert_eq!(half.normalize("日本語ｶﾅ"), "日本語ｶﾅ");
+
+        let full = LocaleFilter {
+            width: WidthConversion::FullWidth,
+            ..half
+        };
+        assert_eq!(full.normalize("ABC 123!~"), "ＡＢＣ\u{3000}１２３！～");
+        assert_eq!(full.normalize(&half.normalize("ｘ＋１")), "ｘ＋１");
+
+        assert_eq!(LocaleFilter::default().normalize("ＡB"), "ＡB");
+    }
+
+    #[test]
+    fn width_conversion_truncation() {
+        let full = LocaleFilter {
+            width: WidthConversion::FullWidth,
+            require_font_glyphs: false,
+        };
+
+        // "ａｂ" takes 6 bytes: only "ａ" fits in front of the NUL terminator.
+        let mut buf = *b"ab\0\0\0";
+        full.normalize_buffer(&mut buf);
+        assert_eq!(&buf[..4], &[0xEF, 0xBD, 0x81, 0]);
+
+        let half = LocaleFilter {
+            width: WidthConversion::HalfWidth,
+            ..full
+        };
+
+        let mut buf = [0xEF, 0xBD, 0x81, 0xEF, 0xBD, 0x82, 0];
+        half.normalize_buffer(&mut buf);
+        assert_eq!(&buf[..3], b"ab\0");
+    }
+
+    #[test]
+    fn editor_configuration() {
+        let mut editor = TextEditor::new("first line\n");
+        assert!(editor.keyboard_mut().as_raw().multiline);
+
+        editor.set_text("replaced");
+        assert_eq!(editor.text(), "replaced");
+        assert_eq!(editor.into_text(), "replaced");
+    }
+}
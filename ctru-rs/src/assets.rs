@@ -0,0 +1,423 @@
+//! Threaded asset loader.
+//!
+//! Loading textures, audio and other files from the SD card or RomFS can take a long time, which makes it unsuitable to do on the main loop.
+//! The [`Loader`] moves this work onto a dedicated worker thread: load requests are queued with a [`Priority`] and
+//! the caller immediately receives a [`LoadHandle`], which can be polled every frame or waited on.
+//!
+//! # Notes
+//!
+//! Threads running on the same core of the 3DS are not preempted. This means the worker thread will only make progress
+//! while the main thread is blocked (e.g. while waiting for the vertical blank with [`Gfx::wait_for_vblank()`](crate::services::gfx::Gfx::wait_for_vblank)),
+//! unless the worker is spawned on another core.
+#![doc(alias = "loading")]
+#![doc(alias = "resources")]
+
+use crate::linear::LinearAllocator;
+use crate::services::fs::ThreadSession;
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Priority of a load request.
+///
+/// Requests with higher priority are always processed before the ones with lower priority.
+/// Requests with the same priority are processed in the same order they were queued.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Assets that are not needed soon (e.g. the next level's data).
+    Low,
+    /// Default priority.
+    #[default]
+    Normal,
+    /// Assets needed as soon as possible (e.g. the sound effect for the next frame).
+    High,
+}
+
+/// Error returned by an unsuccessful load request.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Error {
+    /// The request was cancelled before it could be processed.
+    Cancelled,
+    /// The loading operation failed.
+    Failed(crate::Error),
+}
+
+/// Handle to a queued load request.
+///
+/// Dropping the handle cancels the request if it hasn't been processed yet.
+pub struct LoadHandle<T> {
+    slot: Arc<Slot<T>>,
+}
+
+/// Asset loader running on a dedicated worker thread.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::assets::{Loader, Priority};
+///
+/// let loader = Loader::new()?;
+///
+/// let music = loader.load_linear("romfs:/music.pcm", Priority::Low);
+/// let mut icon = loader.load_file("romfs:/icon.bin", Priority::High);
+///
+/// // Poll the requests within the main loop...
+/// if let Some(result) = icon.poll() {
+///     let icon_data = result?;
+/// }
+///
+/// // ...or wait for them to complete.
+/// let music_data = music.wait()?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct Loader {
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    condvar: Condvar,
+}
+
+struct QueueState {
+    jobs: BinaryHeap<Job>,
+    next_sequence: u64,
+    shutdown: bool,
+}
+
+struct Job {
+    priority: Priority,
+    sequence: u64,
+    // The argument signals whether the job should be run (`true`) or cancelled (`false`).
+    run: Box<dyn FnOnce(bool) + Send>,
+}
+
+struct Slot<T> {
+    result: Mutex<Option<Result<T, Error>>>,
+    condvar: Condvar,
+    cancelled: AtomicBool,
+    taken: AtomicBool,
+}
+
+impl Loader {
+    /// Spawn a new loader with its own worker thread.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the worker thread couldn't be spawned.
+    pub fn new() -> crate::Result<Self> {
+        Self::with_stack_size(0x8000)
+    }
+
+    /// Spawn a new loader whose worker thread uses a stack of the given size (in bytes).
+    ///
+    /// Loaders running custom decoding jobs (see [`Loader::load_with()`]) may need more stack than the default.
+    pub fn with_stack_size(stack_size: usize) -> crate::Result<Self> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState {
+                jobs: BinaryHeap::new(),
+                next_sequence: 0,
+                shutdown: false,
+            }),
+            condvar: Condvar::new(),
+        });
+
+        let worker_shared = Arc::clone(&shared);
+        let worker = thread::Builder::new()
+            .name(String::from("asset-loader"))
+            .stack_size(stack_size)
+            .spawn(move || worker_loop(&worker_shared))?;
+
+        Ok(Self {
+            shared,
+            worker: Some(worker),
+        })
+    }
+
+    /// Queue a request to read a whole file into memory.
+    pub fn load_file(&self, path: impl Into<PathBuf>, priority: Priority) -> LoadHandle<Vec<u8>> {
+        let path = path.into();
+
+        self.load_with(priority, move || Ok(std::fs::read(path)?))
+    }
+
+    /// Queue a request to read a whole file into [LINEAR memory](crate::linear).
+    ///
+    /// LINEAR memory is needed by data accessed by the GPU or the DSP, such as textures and audio [`Wave`](crate::services::ndsp::wave::Wave)s.
+    pub fn load_linear(
+        &self,
+        path: impl Into<PathBuf>,
+        priority: Priority,
+    ) -> LoadHandle<Box<[u8], LinearAllocator>> {
+        let path = path.into();
+
+        self.load_with(priority, move || {
+            let data = std::fs::read(path)?;
+
+            let mut buffer = Vec::with_capacity_in(data.len(), LinearAllocator);
+            buffer.extend_from_slice(&data);
+
+            Ok(buffer.into_boxed_slice())
+        })
+    }
+
+    /// Queue a custom loading job (e.g. reading and decoding a texture).
+    ///
+    /// The job will be run on the worker thread, unless the request gets cancelled before the worker reaches it.
+    /// If the job panics, the request fails with an [`Error::Failed`] and the worker moves on to the next one.
+    pub fn load_with<T, F>(&self, priority: Priority, job: F) -> LoadHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> crate::Result<T> + Send + 'static,
+    {
+        let slot = Arc::new(Slot {
+            result: Mutex::new(None),
+            condvar: Condvar::new(),
+            cancelled: AtomicBool::new(false),
+            taken: AtomicBool::new(false),
+        });
+
+        let job_slot = Arc::clone(&slot);
+        let run = Box::new(move |run: bool| {
+            let result = if run && !job_slot.cancelled.load(AtomicOrdering::Acquire) {
+                match panic::catch_unwind(AssertUnwindSafe(job)) {
+                    Ok(result) => result.map_err(Error::Failed),
+                    Err(payload) => Err(Error::Failed(crate::Error::Other(format!(
+                        "the loading job panicked: {}",
+                        panic_message(payload.as_ref())
+                    )))),
+                }
+            } else {
+                Err(Error::Cancelled)
+            };
+
+            job_slot.finish(result);
+        });
+
+        let mut state = self.shared.state.lock().unwrap();
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.jobs.push(Job {
+            priority,
+            sequence,
+            run,
+        });
+        drop(state);
+
+        self.shared.condvar.notify_one();
+
+        LoadHandle { slot }
+    }
+
+    /// Returns the amount of requests waiting to be processed.
+    pub fn pending(&self) -> usize {
+        self.shared.state.lock().unwrap().jobs.len()
+    }
+
+    /// Cancel all requests that haven't been processed yet.
+    pub fn cancel_all(&self) {
+        let jobs = std::mem::take(&mut self.shared.state.lock().unwrap().jobs);
+
+        for job in jobs {
+            (job.run)(false);
+        }
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.condvar.notify_all();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+
+        // Make sure nobody is left waiting on requests which will never be processed.
+        self.cancel_all();
+    }
+}
+
+fn worker_loop(shared: &Shared) {
+    // Load files without waiting for the filesystem requests of the other threads.
+    // If the FS service has no session left, the shared one does the job as well.
+    let _session = ThreadSession::open().ok();
+
+    loop {
+        let job = {
+            let mut state = shared.state.lock().unwrap();
+
+            loop {
+                if state.shutdown {
+                    return;
+                }
+
+                if let Some(job) = state.jobs.pop() {
+                    break job;
+                }
+
+                state = shared.condvar.wait(state).unwrap();
+            }
+        };
+
+        (job.run)(true);
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
+
+impl<T> LoadHandle<T> {
+    /// Returns `true` if the request has been processed (either successfully or not).
+    pub fn is_ready(&self) -> bool {
+        self.slot.result.lock().unwrap().is_some() || self.slot.taken.load(AtomicOrdering::Acquire)
+    }
+
+    /// Returns the result of the request if it has been processed, without blocking.
+    ///
+    /// # Notes
+    ///
+    /// The result is moved out of the handle, so only the first call after the request has been processed returns [`Some`].
+    pub fn poll(&mut self) -> Option<Result<T, Error>> {
+        let result = self.slot.result.lock().unwrap().take();
+
+        if result.is_some() {
+            self.slot.taken.store(true, AtomicOrdering::Release);
+        }
+
+        result
+    }
+
+    /// Block the current thread until the request has been processed and return its result.
+    ///
+    /// # Notes
+    ///
+    /// If the result was already taken via [`LoadHandle::poll()`], this function returns [`Error::Cancelled`].
+    pub fn wait(self) -> Result<T, Error> {
+        if self.slot.taken.load(AtomicOrdering::Acquire) {
+            return Err(Error::Cancelled);
+        }
+
+        let mut result = self.slot.result.lock().unwrap();
+
+        loop {
+            if let Some(result) = result.take() {
+                self.slot.taken.store(true, AtomicOrdering::Release);
+                return result;
+            }
+
+            result = self.slot.condvar.wait(result).unwrap();
+        }
+    }
+
+    /// Cancel the request.
+    ///
+    /// # Notes
+    ///
+    /// Requests that are already being processed by the worker thread cannot be cancelled.
+    pub fn cancel(&self) {
+        self.slot.cancelled.store(true, AtomicOrdering::Release);
+    }
+}
+
+impl<T> Drop for LoadHandle<T> {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl<T> Slot<T> {
+    fn finish(&self, result: Result<T, Error>) {
+        *self.result.lock().unwrap() = Some(result);
+        self.condvar.notify_all();
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.sequence == other.sequence
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // `BinaryHeap` is a max-heap: higher priorities come first, then older requests (lower sequence numbers).
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Cancelled => write!(f, "the load request was cancelled"),
+            Self::Failed(err) => write!(f, "the load request failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for crate::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Cancelled => crate::Error::Cancelled,
+            Error::Failed(err) => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panicking_job() {
+        let loader = Loader::new().unwrap();
+
+        let handle = loader.load_with(Priority::Normal, || -> crate::Result<()> {
+            panic!("corrupted asset")
+        });
+
+        match handle.wait() {
+            Err(Error::Failed(crate::Error::Other(message))) => {
+                assert_eq!(message, "the loading job panicked: corrupted asset")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        // The worker must still be alive to process the following requests.
+        let handle = loader.load_with(Priority::Normal, || Ok(42));
+        assert_eq!(handle.wait().unwrap(), 42);
+    }
+}
@@ -0,0 +1,8 @@
+//! Audio processing utilities.
+//!
+//! These helpers run on the CPU and complement the [`ndsp`](crate::services::ndsp) service, which plays the resulting samples.
+#![doc(alias = "sound")]
+
+pub mod resampler;
+
+pub use resampler::Resampler;
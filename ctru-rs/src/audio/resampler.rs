@@ -0,0 +1,279 @@
+//! Sample rate conversion.
+//!
+//! NDSP channels can play samples at any rate (see [`Channel::set_sample_rate()`](crate::services::ndsp::Channel::set_sample_rate)),
+//! but mixing sources with different rates into a single buffer, or streaming decoder output into a fixed-rate channel,
+//! requires converting the samples first. [`Resampler`] does that for interleaved 16 bit PCM data.
+//!
+//! # Notes
+//!
+//! The console's ARM11 CPU has no SIMD unit besides VFP. The inner loops therefore use fixed-point positions,
+//! contiguous coefficient tables and a fixed amount of taps per [`Quality`], which keeps them branch-free and cheap to run every frame.
+#![doc(alias = "sample rate")]
+
+use std::f32::consts::PI;
+
+/// Number of fractional positions precomputed in the sinc filter tables.
+const PHASE_BITS: u32 = 8;
+const PHASES: usize = 1 << PHASE_BITS;
+
+/// Resampling quality, trading CPU time for less aliasing.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Quality {
+    /// Linear interpolation between neighbouring samples. Very cheap, but adds audible aliasing to high frequencies.
+    Linear,
+    /// 8-tap windowed sinc filter. A good default for music.
+    #[default]
+    Medium,
+    /// 16-tap windowed sinc filter.
+    High,
+}
+
+/// Streaming sample rate converter for interleaved 16 bit PCM data.
+///
+/// Input can be fed in chunks of any size: the resampler keeps the samples it still needs between calls.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::audio::resampler::{Quality, Resampler};
+///
+/// // Convert stereo 44.1 kHz decoder output to 32 kHz.
+/// let mut resampler = Resampler::new(44100, 32000, 2, Quality::Medium);
+///
+/// let decoded = vec![0i16; 2 * 1024];
+/// let mut output = Vec::new();
+/// resampler.process(&decoded, &mut output);
+///
+/// assert!(output.len() <= resampler.max_output_len(decoded.len()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Resampler {
+    channels: usize,
+    input_rate: u32,
+    output_rate: u32,
+    quality: Quality,
+    /// Distance between two output samples, in input frames (32.32 fixed point).
+    step: u64,
+    /// Position of the next output sample within `buffer`, in frames (32.32 fixed point).
+    position: u64,
+    /// Pending input samples (interleaved).
+    buffer: Vec<i16>,
+    /// Sinc filter coefficients, `taps` per phase. Empty for linear interpolation.
+    table: Vec<f32>,
+}
+
+impl Quality {
+    fn taps(self) -> usize {
+        match self {
+            Self::Linear => 2,
+            Self::Medium => 8,
+            Self::High => 16,
+        }
+    }
+}
+
+impl Resampler {
+    /// Create a resampler converting `channels` interleaved channels from `input_rate` to `output_rate` (in Hz).
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if any of the parameters is 0.
+    pub fn new(input_rate: u32, output_rate: u32, channels: usize, quality: Quality) -> Self {
+        assert!(
+            input_rate > 0 && output_rate > 0,
+            "sample rates must not be 0"
+        );
+        assert!(channels > 0, "there must be at least one channel");
+
+        let mut resampler = Self {
+            channels,
+            input_rate,
+            output_rate,
+            quality,
+            step: (u64::from(input_rate) << 32) / u64::from(output_rate),
+            position: 0,
+            buffer: Vec::new(),
+            table: build_table(quality, input_rate, output_rate),
+        };
+        resampler.reset();
+
+        resampler
+    }
+
+    /// Returns the input sample rate.
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    /// Returns the output sample rate.
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Returns the amount of interleaved channels.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Returns the resampling quality.
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    /// Returns the maximum amount of samples (not frames) [`Resampler::process()`] can output for `input_len` input samples.
+    ///
+    /// Useful to reserve space in the output buffer beforehand.
+    pub fn max_output_len(&self, input_len: usize) -> usize {
+        let frames = (self.buffer.len() + input_len) / self.channels;
+        let output_frames = ((frames as u64) << 32) / self.step + 1;
+
+        output_frames as usize * self.channels
+    }
+
+    /// Drop any pending input, as if the resampler was just created.
+    ///
+    /// Call this when seeking or switching to a different stream.
+    pub fn reset(&mut self) {
+        let half = self.quality.taps() / 2;
+
+        // The filter is centered on the output position, so it needs some previous samples even for the first output.
+        self.buffer.clear();
+        self.buffer.resize((half - 1) * self.channels, 0);
+        self.position = ((half - 1) as u64) << 32;
+    }
+
+    /// Resample `input` (interleaved, with a length multiple of the channel count), appending the result to `output`.
+    ///
+    /// The output lags slightly behind the input, since the last few input samples are kept until the next call.
+    pub fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        debug_assert_eq!(
+            input.len() % self.channels,
+            0,
+            "input must contain whole frames"
+        );
+
+        self.buffer.extend_from_slice(input);
+        output.reserve(self.max_output_len(0));
+
+        let channels = self.channels;
+        let taps = self.quality.taps();
+        let half = taps / 2;
+        let frames = self.buffer.len() / channels;
+
+        while ((self.position >> 32) as usize) + half < frames {
+            let frame = (self.position >> 32) as usize;
+            let fraction = self.position as u32;
+
+            if self.table.is_empty() {
+                // 15 bit fraction, so that the products fit in an `i32`.
+                let weight = (fraction >> 17) as i32;
+
+                for channel in 0..channels {
+                    let a = i32::from(self.buffer[frame * channels + channel]);
+                    let b = i32::from(self.buffer[(frame + 1) * channels + channel]);
+
+                    output.push((a + (((b - a) * weight) >> 15)) as i16);
+                }
+            } else {
+                let phase = (fraction >> (32 - PHASE_BITS)) as usize;
+                let coefficients = &self.table[phase * taps..][..taps];
+                let first = frame + 1 - half;
+
+                for channel in 0..channels {
+                    let mut sum = 0.0;
+
+                    for (tap, coefficient) in coefficients.iter().enumerate() {
+                        let sample = self.buffer[(first + tap) * channels + channel];
+                        sum += f32::from(sample) * coefficient;
+                    }
+
+                    output.push(sum.clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16);
+                }
+            }
+
+            self.position += self.step;
+        }
+
+        // Drop the frames which won't be needed by the next outputs.
+        let first_needed = ((self.position >> 32) as usize + 1).saturating_sub(half);
+        let consumed = first_needed.min(frames);
+
+        self.buffer.drain(..consumed * channels);
+        self.position -= (consumed as u64) << 32;
+    }
+}
+
+/// Precompute the windowed sinc coefficients for each phase.
+fn build_table(quality: Quality, input_rate: u32, output_rate: u32) -> Vec<f32> {
+    if quality == Quality::Linear {
+        return Vec::new();
+    }
+
+    let taps = quality.taps();
+    let half = (taps / 2) as f32;
+    // When downsampling, the cutoff frequency must be lowered to the output's Nyquist frequency.
+    let cutoff = (output_rate as f32 / input_rate as f32).min(1.0);
+
+    let mut table = Vec::with_capacity(PHASES * taps);
+
+    for phase in 0..PHASES {
+        let fraction = phase as f32 / PHASES as f32;
+        let start = table.len();
+
+        for tap in 0..taps {
+            // Distance between this tap's sample and the output position.
+            let x = tap as f32 - (half - 1.0) - fraction;
+
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * x * cutoff).sin() / (PI * x * cutoff)
+            };
+
+            // Blackman window.
+            let t = x / half;
+            let window = if t.abs() >= 1.0 {
+                0.0
+            } else {
+                0.42 + 0.5 * (PI * t).cos() + 0.08 * (2.0 * PI * t).cos()
+            };
+
+            table.push(sinc * window);
+        }
+
+        // Normalize each phase, so that constant signals keep their level.
+        let sum: f32 = table[start..].iter().sum();
+        for coefficient in &mut table[start..] {
+            *coefficient /= sum;
+        }
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_signal() {
+        for quality in [Quality::Linear, Quality::Medium, Quality::High] {
+            let mut resampler = Resampler::new(48000, 32000, 2, quality);
+            let mut output = Vec::new();
+
+            for _ in 0..4 {
+                resampler.process(&[1000; 2 * 480], &mut output);
+            }
+
+            // 4 * 480 frames at 48 kHz are 4 * 320 frames at 32 kHz, minus the filter's latency.
+            let frames = output.len() / 2;
+            assert!(frames <= 4 * 320 && frames >= 4 * 320 - 16);
+
+            // Skip the first outputs, which include the initial silence.
+            assert!(output[32..]
+                .iter()
+                .all(|&sample| (sample - 1000).abs() <= 1));
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! On-device backtraces.
+//!
+//! Executables for the console don't carry debug information at runtime, so the standard library can't print function names in backtraces.
+//! This module instead resolves code addresses with a [`SymbolMap`] generated from the application's ELF file at build time,
+//! and uses it to symbolize [`capture()`]d backtraces, panic messages (see [`install_panic_hook()`]) and [crash reports](crate::fault).
+//!
+//! # Generating the symbol map
+//!
+//! The map is parsed from the output of `nm`, sorted by address and with demangled names:
+//!
+//! ```text
+//! arm-none-eabi-nm --defined-only --numeric-sort --demangle target/armv6k-nintendo-3ds/release/my-app.elf > romfs/symbols.txt
+//! ```
+//!
+//! It can be shipped in the RomFS and loaded with [`SymbolMap::parse()`], or converted once with [`SymbolMap::to_bytes()`]
+//! into a compact binary table, which [`SymbolMap::from_bytes()`] reads without any parsing (e.g. from [`include_bytes!`]).
+//! Embedding the table only grows the read-only data section, which the linker places after the code, so a second build with the
+//! embedded table keeps the same code addresses as the build the table was generated from.
+//!
+//! The application's build script can generate the map from the executable of its previous build, to embed it automatically:
+//!
+//! ```no_run
+//! // build.rs
+//! use std::path::PathBuf;
+//! use std::process::Command;
+//!
+//! fn main() {
+//!     let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//!     let profile = std::env::var("PROFILE").unwrap();
+//!     let elf = format!("target/armv6k-nintendo-3ds/{profile}/my-app.elf");
+//!
+//!     // The first build has no executable to read yet, and embeds an empty map.
+//!     let symbols = Command::new("arm-none-eabi-nm")
+//!         .args(["--defined-only", "--numeric-sort", "--demangle", &elf])
+//!         .output()
+//!         .ok()
+//!         .filter(|output| output.status.success())
+//!         .map(|output| output.stdout)
+//!         .unwrap_or_default();
+//!
+//!     std::fs::write(out_dir.join("symbols.txt"), symbols).unwrap();
+//!     println!("cargo:rerun-if-changed={elf}");
+//! }
+//! ```
+//!
+//! ```ignore
+//! // main.rs
+//! ctru::backtrace::set_symbol_map(ctru::backtrace::SymbolMap::parse(include_str!(concat!(
+//!     env!("OUT_DIR"),
+//!     "/symbols.txt"
+//! ))))?;
+//! ```
+//!
+//! Since the map is read from the previous executable, it only matches once the code stops changing:
+//! build twice after any change to the code (e.g. for releases).
+//!
+//! # Compact format
+//!
+//! | Offset     | Size         | Description                                                        |
+//! |------------|--------------|--------------------------------------------------------------------|
+//! | 0x0        | 0x4          | Magic number `SYMS`                                                |
+//! | 0x4        | 0x4          | Number of symbols (little endian)                                  |
+//! | 0x8        | 0x8 × count  | Symbols, sorted by address: address, name offset (little endian)   |
+//! | ...        | ...          | Names, each as UTF-8 bytes terminated by a NUL byte                |
+#![doc(alias = "symbolize")]
+#![doc(alias = "stack trace")]
+
+use crate::Error;
+
+use std::ffi::c_void;
+use std::fmt;
+use std::sync::OnceLock;
+
+const MAGIC: &[u8; 4] = b"SYMS";
+const HEADER_SIZE: usize = 8;
+const ENTRY_SIZE: usize = 8;
+
+/// Maximum amount of frames collected by [`capture()`].
+const MAX_FRAMES: usize = 64;
+
+/// Symbol map installed with [`set_symbol_map()`].
+static SYMBOLS: OnceLock<SymbolMap> = OnceLock::new();
+
+/// Table mapping code addresses to function names.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolMap {
+    /// Start addresses and names of the symbols, sorted by address.
+    symbols: Vec<(u32, String)>,
+}
+
+impl SymbolMap {
+    /// Parse the output of `nm` (as described in the [module documentation](self)).
+    ///
+    /// Only code symbols (types `t`, `T`, `w` and `W`) are kept, and lines in other formats are ignored.
+    pub fn parse(nm_output: &str) -> Self {
+        let mut symbols: Vec<(u32, String)> = nm_output
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, ' ');
+                let address = u32::from_str_radix(parts.next()?, 16).ok()?;
+                let kind = parts.next()?;
+                let name = parts.next()?;
+
+                matches!(kind, "t" | "T" | "w" | "W").then(|| (address, String::from(name)))
+            })
+            .collect();
+
+        symbols.sort_by_key(|(address, _)| *address);
+
+        Self { symbols }
+    }
+
+    /// Read a symbol map in the compact binary format.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the data doesn't start with the `SYMS` magic number or is truncated.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<Self> {
+        let invalid = || Error::Other(String::from("invalid symbol map"));
+
+        if data.len() < HEADER_SIZE || &data[0..4] != MAGIC {
+            return Err(invalid());
+        }
+
+        let count = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        let names_offset = count
+            .checked_mul(ENTRY_SIZE)
+            .and_then(|size| size.checked_add(HEADER_SIZE))
+            .ok_or_else(invalid)?;
+        let entries = data.get(HEADER_SIZE..names_offset).ok_or_else(invalid)?;
+        let names = &data[names_offset..];
+
+        let symbols = entries
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| {
+                let address = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+                let offset = u32::from_le_bytes(entry[4..8].try_into().unwrap()) as usize;
+
+                let name = names.get(offset..).ok_or_else(invalid)?;
+                let end = name.iter().position(|&b| b == 0).ok_or_else(invalid)?;
+
+                Ok((address, String::from_utf8_lossy(&name[..end]).into_owned()))
+            })
+            .collect::<crate::Result<_>>()?;
+
+        Ok(Self { symbols })
+    }
+
+    /// Encode the symbol map in the compact binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries = Vec::with_capacity(self.symbols.len() * ENTRY_SIZE);
+        let mut names = Vec::new();
+
+        for (address, name) in &self.symbols {
+            entries.extend_from_slice(&address.to_le_bytes());
+            entries.extend_from_slice(&(names.len() as u32).to_le_bytes());
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+
+        let mut data = Vec::with_capacity(HEADER_SIZE + entries.len() + names.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&(self.symbols.len() as u32).to_le_bytes());
+        data.extend_from_slice(&entries);
+        data.extend_from_slice(&names);
+
+        data
+    }
+
+    /// Returns the name of the function containing `address` and the offset of the address within it.
+    ///
+    /// The lowest bit (set for Thumb code) is ignored.
+    pub fn resolve(&self, address: u32) -> Option<(&str, u32)> {
+        let address = address & !1;
+        let index = match self
+            .symbols
+            .binary_search_by_key(&address, |(start, _)| *start)
+        {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+
+        let (start, name) = &self.symbols[index];
+
+        Some((name, address - start))
+    }
+
+    /// Returns the number of symbols in the map.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Returns `true` if the map doesn't contain any symbol.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+/// Install the symbol map used to symbolize backtraces and crash reports.
+///
+/// # Errors
+///
+/// The map can only be set once: afterwards, this function returns an error.
+pub fn set_symbol_map(map: SymbolMap) -> crate::Result<()> {
+    SYMBOLS
+        .set(map)
+        .map_err(|_| Error::Other(String::from("the symbol map was already set")))
+}
+
+/// Resolve `address` with the installed symbol map (see [`SymbolMap::resolve()`]).
+pub fn resolve(address: u32) -> Option<(&'static str, u32)> {
+    SYMBOLS.get()?.resolve(address)
+}
+
+/// A captured backtrace.
+///
+/// Its [`Display`](fmt::Display) implementation prints one frame per line, symbolized with the installed symbol map.
+#[derive(Clone, Debug)]
+pub struct Backtrace {
+    frames: Vec<u32>,
+}
+
+impl Backtrace {
+    /// Returns the return addresses of the captured frames, most recent first.
+    pub fn frames(&self) -> &[u32] {
+        &self.frames
+    }
+}
+
+/// Capture a backtrace of the current thread, using the unwind tables included in the executable.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// let backtrace = ctru::backtrace::capture();
+///
+/// println!("{backtrace}");
+/// ```
+#[doc(alias = "_Unwind_Backtrace")]
+pub fn capture() -> Backtrace {
+    extern "C" fn trace(context: *mut c_void, frames: *mut c_void) -> i32 {
+        // SAFETY: `frames` is the vector passed to `_Unwind_Backtrace()` below.
+        let frames = unsafe { &mut *frames.cast::<Vec<u32>>() };
+
+        let mut pc: u32 = 0;
+        // `_Unwind_GetIP()` is a macro on ARM: it reads the program counter (r15) of the frame's virtual register set.
+        unsafe {
+            _Unwind_VRS_Get(
+                context,
+                UVRSC_CORE,
+                15,
+                UVRSD_UINT32,
+                (&mut pc as *mut u32).cast(),
+            );
+        }
+
+        if pc == 0 || frames.len() == MAX_FRAMES {
+            return URC_END_OF_STACK;
+        }
+
+        frames.push(pc & !1);
+
+        URC_NO_REASON
+    }
+
+    let mut frames: Vec<u32> = Vec::new();
+
+    unsafe { _Unwind_Backtrace(trace, (&mut frames as *mut Vec<u32>).cast()) };
+
+    // Skip this function's own frame.
+    if !frames.is_empty() {
+        frames.remove(0);
+    }
+
+    Backtrace { frames }
+}
+
+/// Install a panic hook which prints a symbolized backtrace after the panic message.
+///
+/// The previously set panic hook is still called first.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        eprintln!("backtrace:\n{}", capture());
+    }));
+}
+
+/// Write `address` followed by its symbol, if known.
+pub(crate) fn write_address(f: &mut fmt::Formatter<'_>, address: u32) -> fmt::Result {
+    match resolve(address) {
+        Some((name, offset)) => write!(f, "{address:08X} {name}+{offset:#x}"),
+        None => write!(f, "{address:08X}"),
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, address) in self.frames.iter().enumerate() {
+            write!(f, "{index:>3}: ")?;
+            write_address(f, *address)?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+const UVRSC_CORE: i32 = 0;
+const UVRSD_UINT32: i32 = 0;
+const URC_NO_REASON: i32 = 0;
+const URC_END_OF_STACK: i32 = 5;
+
+extern "C" {
+    fn _Unwind_Backtrace(
+        trace: extern "C" fn(context: *mut c_void, arg: *mut c_void) -> i32,
+        arg: *mut c_void,
+    ) -> i32;
+
+    fn _Unwind_VRS_Get(
+        context: *mut c_void,
+        regclass: i32,
+        regno: u32,
+        representation: i32,
+        valuep: *mut c_void,
+    ) -> i32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_symbols() {
+        let map = SymbolMap::parse(
+            "00100000 T _start\n00100100 t my_app::main\n00100200 D some_data\n00100180 W weak_fn\n",
+        );
+        assert_eq!(map.len(), 3);
+
+        let map = SymbolMap::from_bytes(&map.to_bytes()).unwrap();
+        assert_eq!(map.resolve(0x0010_0104), Some(("my_app::main", 4)));
+        assert_eq!(map.resolve(0x0010_0181), Some(("weak_fn", 0)));
+        assert_eq!(map.resolve(0x000F_FFFF), None);
+
+        // Symbol counts too big for the address space are rejected.
+        assert!(SymbolMap::from_bytes(b"SYMS\xFF\xFF\xFF\xFF").is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! CPU cache maintenance.
+//!
+//! The ARM11 CPU has separate caches for data and instructions, which are not kept coherent with each other
+//! nor with other hardware (like the GPU and DSP) reading the main memory.
+//! Code generating machine code at runtime (e.g. dynamic recompilers and JIT compilers) must flush the data cache
+//! and invalidate the instruction cache before running the newly written instructions.
+//!
+//! # Notes
+//!
+//! Instruction cache maintenance and [`ExecutableMemory`] rely on kernel extensions provided by Luma3DS.
+//! They will fail (or crash the application) on consoles running without it.
+#![doc(alias = "jit")]
+#![doc(alias = "dynarec")]
+
+use std::alloc::{self, Layout};
+use std::ptr::NonNull;
+
+use crate::error::{Error, ResultCode};
+
+/// Size (in bytes) of a memory page.
+pub const PAGE_SIZE: usize = 0x1000;
+
+/// Write back the data cache for the given memory range, making the data visible to the main memory.
+#[doc(alias = "svcFlushProcessDataCache")]
+pub fn flush_range(ptr: *const u8, len: usize) -> crate::Result<()> {
+    ResultCode(unsafe {
+        ctru_sys::svcFlushProcessDataCache(ctru_sys::CUR_PROCESS_HANDLE, ptr as u32, len as u32)
+    })?;
+
+    Ok(())
+}
+
+/// Invalidate the instruction cache for the given memory range, so that newly written instructions are fetched from the main memory.
+///
+/// The data cache for the same range must be flushed first (see [`flush_range()`]).
+///
+/// # Notes
+///
+/// This function requires Luma3DS.
+#[doc(alias = "svcInvalidateInstructionCacheRange")]
+pub fn invalidate_instruction_range(ptr: *const u8, len: usize) -> crate::Result<()> {
+    ResultCode(unsafe {
+        ctru_sys::svcInvalidateInstructionCacheRange(ptr.cast_mut().cast(), len as u32)
+    })?;
+
+    Ok(())
+}
+
+/// Invalidate the whole instruction cache.
+///
+/// # Notes
+///
+/// This function requires Luma3DS.
+#[doc(alias = "svcInvalidateEntireInstructionCache")]
+pub fn invalidate_entire_instruction_cache() -> crate::Result<()> {
+    ResultCode(unsafe { ctru_sys::svcInvalidateEntireInstructionCache() })?;
+
+    Ok(())
+}
+
+/// Page-aligned memory buffer which is readable, writable and executable at the same time.
+///
+/// # Notes
+///
+/// Changing the permissions of the application's memory requires Luma3DS.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::cache::ExecutableMemory;
+///
+/// let mut memory = ExecutableMemory::new(0x1000)?;
+///
+/// // "bx lr"
+/// memory.as_mut_slice()[..4].copy_from_slice(&0xE12F_FF1Eu32.to_le_bytes());
+/// memory.sync()?;
+///
+/// let function: extern "C" fn() = unsafe { std::mem::transmute(memory.as_ptr()) };
+/// function();
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct ExecutableMemory {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl ExecutableMemory {
+    /// Allocate a new (zeroed) executable buffer of at least `len` bytes, rounded up to a multiple of [`PAGE_SIZE`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the memory couldn't be allocated or its permissions couldn't be changed.
+    #[doc(alias = "svcControlProcessMemory")]
+    pub fn new(len: usize) -> crate::Result<Self> {
+        let layout = Layout::from_size_align(len.max(1).next_multiple_of(PAGE_SIZE), PAGE_SIZE)
+            .map_err(|e| Error::Other(e.to_string()))?;
+
+        let ptr = NonNull::new(unsafe { alloc::alloc_zeroed(layout) })
+            .ok_or_else(|| Error::Other(String::from("couldn't allocate executable memory")))?;
+
+        let permissions =
+            ctru_sys::MEMPERM_READ | ctru_sys::MEMPERM_WRITE | ctru_sys::MEMPERM_EXECUTE;
+
+        if let Err(e) = set_permissions(ptr.as_ptr(), layout.size(), permissions) {
+            unsafe { alloc::dealloc(ptr.as_ptr(), layout) };
+            return Err(e);
+        }
+
+        Ok(Self { ptr, layout })
+    }
+
+    /// Returns the length of the buffer.
+    pub fn len(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// Returns `true` if the buffer is empty. Since buffers are always at least one page long, this is always `false`.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns a pointer to the start of the buffer.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Returns the buffer's contents.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len()) }
+    }
+
+    /// Make the buffer's contents visible to the CPU's instruction fetches.
+    ///
+    /// This function must be called after writing new instructions to the buffer and before running them.
+    pub fn sync(&self) -> crate::Result<()> {
+        flush_range(self.as_ptr(), self.len())?;
+        invalidate_instruction_range(self.as_ptr(), self.len())
+    }
+}
+
+impl Drop for ExecutableMemory {
+    fn drop(&mut self) {
+        // The pages must be returned to the allocator with their original permissions.
+        let _ = set_permissions(
+            self.ptr.as_ptr(),
+            self.len(),
+            ctru_sys::MEMPERM_READ | ctru_sys::MEMPERM_WRITE,
+        );
+
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+fn set_permissions(ptr: *mut u8, len: usize, permissions: u32) -> crate::Result<()> {
+    // `svcControlProcessMemory` doesn't accept the current process' pseudo-handle, so a real handle is needed.
+    let mut process = 0;
+    ResultCode(unsafe {
+        ctru_sys::svcDuplicateHandle(&mut process, ctru_sys::CUR_PROCESS_HANDLE)
+    })?;
+
+    let result = unsafe {
+        ctru_sys::svcControlProcessMemory(
+            process,
+            ptr as u32,
+            0,
+            len as u32,
+            ctru_sys::MEMOP_PROT,
+            permissions,
+        )
+    };
+
+    unsafe { ctru_sys::svcCloseHandle(process) };
+
+    ResultCode(result)?;
+
+    Ok(())
+}
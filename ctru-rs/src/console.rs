@@ -292,6 +292,14 @@ impl<'screen> Console<'screen> {
         self.set_window(0, 0, width, 30).unwrap();
     }
 
+    /// Returns the size (columns, rows) of the console's current window.
+    pub(crate) fn window_size(&self) -> (u8, u8) {
+        (
+            self.context.windowWidth as u8,
+            self.context.windowHeight as u8,
+        )
+    }
+
     /// Returns this [`Console`]'s maximum character width depending on the screen used.
     ///
     /// # Example
@@ -0,0 +1,782 @@
+//! Debugging utilities.
+//!
+//! This module contains tools meant to help during the development of an application, like the [`Overlay`],
+//! the [`Watchdog`] and helpers to smooth on-device debugging with the GDB stub integrated in Luma3DS.
+#![doc(alias = "overlay")]
+#![doc(alias = "gdb")]
+
+use std::fmt::{Display, Write};
+use std::os::horizon::thread::BuilderExt;
+use std::panic::Location;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::ResultCode;
+use crate::linear::LinearAllocator;
+use crate::os::MemRegion;
+use crate::services::gfx::Screen;
+use crate::services::gspgpu::FramebufferFormat;
+use crate::services::hid::{Hid, KeyPad};
+
+const GLYPH_SIZE: usize = 8;
+const BAR_WIDTH: usize = 12;
+const FPS_SAMPLE_TIME: Duration = Duration::from_secs(1);
+const MAX_NAMED_THREADS: usize = 32;
+const THREAD_NAME_LENGTH: usize = 32;
+
+/// Colour used for the overlay's text and bars.
+const FOREGROUND: (u8, u8, u8) = (0xFF, 0xFF, 0xFF);
+/// Colour used behind the overlay's lines, to keep them readable on top of anything else drawn on the screen.
+const BACKGROUND: (u8, u8, u8) = (0x00, 0x00, 0x00);
+
+/// Flag polled by [`wait_for_debugger()`]. It's set from the debugger with `set var CTRU_DEBUGGER_ATTACHED = 1`.
+#[no_mangle]
+static mut CTRU_DEBUGGER_ATTACHED: u8 = 0;
+
+/// Names of the threads registered with [`set_thread_name()`].
+///
+/// The table is kept in a plain C layout under an unmangled symbol, so it can be inspected from the debugger
+/// with `print CTRU_THREAD_NAMES`, since the kernel has no concept of thread names.
+#[no_mangle]
+static mut CTRU_THREAD_NAMES: [ThreadNameEntry; MAX_NAMED_THREADS] = [ThreadNameEntry {
+    id: 0,
+    name: [0; THREAD_NAME_LENGTH],
+}; MAX_NAMED_THREADS];
+
+static THREAD_NAMES_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct ThreadNameEntry {
+    id: u32,
+    name: [u8; THREAD_NAME_LENGTH],
+}
+
+/// Block the current thread until a debugger (e.g. Luma3DS' GDB stub) is attached.
+///
+/// # Notes
+///
+/// The kernel doesn't offer a way for a process to know whether it's being debugged, so the wait must be ended from the debugger
+/// once it's attached, by running `set var CTRU_DEBUGGER_ATTACHED = 1` and continuing the execution.
+/// Breakpoints can be placed before doing so, which is useful to debug code which runs right after the application starts.
+///
+/// # Example
+///
+/// ```no_run
+/// use ctru::debug;
+///
+/// debug::wait_for_debugger();
+///
+/// // The debugger is now attached.
+/// ```
+pub fn wait_for_debugger() {
+    output_debug_string("Waiting for debugger: `set var CTRU_DEBUGGER_ATTACHED = 1` to continue");
+
+    while unsafe { std::ptr::read_volatile(std::ptr::addr_of!(CTRU_DEBUGGER_ATTACHED)) } == 0 {
+        unsafe { ctru_sys::svcSleepThread(100_000_000) };
+    }
+}
+
+/// Send a message to the attached debugger.
+///
+/// With Luma3DS' GDB stub, the message is shown in the debugger's console. Nothing happens if no debugger is attached.
+#[doc(alias = "svcOutputDebugString")]
+pub fn output_debug_string(message: &str) {
+    unsafe {
+        ctru_sys::svcOutputDebugString(message.as_ptr().cast(), message.len() as i32);
+    }
+}
+
+/// Register a name for the current thread, to make it recognizable while debugging.
+///
+/// Names longer than 31 bytes are truncated on a character boundary. Setting a new name for the same thread replaces the old one.
+///
+/// # Notes
+///
+/// The kernel doesn't keep names for threads, so they are stored in a table readable from the debugger
+/// by running `print CTRU_THREAD_NAMES`, together with the ID of each thread (as shown by `info threads`).
+///
+/// # Errors
+///
+/// This function will return an error if the current thread's ID couldn't be obtained, or if too many threads were already registered.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::debug;
+///
+/// let worker = std::thread::spawn(|| {
+///     debug::set_thread_name("audio worker")?;
+///
+///     // ...
+///     # Ok::<(), ctru::Error>(())
+/// });
+/// #
+/// # worker.join().unwrap()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn set_thread_name(name: &str) -> crate::Result<()> {
+    let id = current_thread_id()?;
+
+    let mut entry = ThreadNameEntry {
+        id,
+        name: [0; THREAD_NAME_LENGTH],
+    };
+    let mut length = name.len().min(THREAD_NAME_LENGTH - 1);
+    while !name.is_char_boundary(length) {
+        length -= 1;
+    }
+    entry.name[..length].copy_from_slice(&name.as_bytes()[..length]);
+
+    let _lock = THREAD_NAMES_LOCK.lock().unwrap();
+    let table = unsafe { &mut *std::ptr::addr_of_mut!(CTRU_THREAD_NAMES) };
+
+    let slot = table
+        .iter_mut()
+        .find(|e| e.id == id)
+        .or_else(|| table.iter_mut().find(|e| e.id == 0))
+        .ok_or_else(|| crate::Error::Other(String::from("too many named threads")))?;
+
+    *slot = entry;
+
+    Ok(())
+}
+
+/// Returns the name registered for the thread with the given ID, if any.
+///
+/// The ID of a thread can be obtained with [`current_thread_id()`].
+pub fn thread_name(id: u32) -> Option<String> {
+    let _lock = THREAD_NAMES_LOCK.lock().unwrap();
+    let table = unsafe { &*std::ptr::addr_of!(CTRU_THREAD_NAMES) };
+
+    table.iter().find(|e| e.id == id && id != 0).map(|e| {
+        let length = e.name.iter().position(|b| *b == 0).unwrap_or(e.name.len());
+        String::from_utf8_lossy(&e.name[..length]).into_owned()
+    })
+}
+
+/// Remove the name registered for the current thread.
+///
+/// Thread IDs may be reused by the kernel, so threads should clear their name before exiting.
+pub fn clear_thread_name() -> crate::Result<()> {
+    let id = current_thread_id()?;
+
+    let _lock = THREAD_NAMES_LOCK.lock().unwrap();
+    let table = unsafe { &mut *std::ptr::addr_of_mut!(CTRU_THREAD_NAMES) };
+
+    if let Some(entry) = table.iter_mut().find(|e| e.id == id) {
+        entry.id = 0;
+        entry.name = [0; THREAD_NAME_LENGTH];
+    }
+
+    Ok(())
+}
+
+/// Returns the kernel ID of the current thread.
+#[doc(alias = "svcGetThreadId")]
+pub fn current_thread_id() -> crate::Result<u32> {
+    let mut id = 0;
+
+    ResultCode(unsafe { ctru_sys::svcGetThreadId(&mut id, ctru_sys::CUR_THREAD_HANDLE) })?;
+
+    Ok(id)
+}
+
+/// Information about a service opened by this crate, as returned by [`list_open_services()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceInfo {
+    /// Name of the service (e.g. `hid:USER`).
+    pub name: &'static str,
+    /// Session handle of the service, if known.
+    ///
+    /// Only services whose session is opened by this crate itself (such as `ir:USER`) report it:
+    /// `libctru` keeps the handles of the services it manages private.
+    pub handle: Option<u32>,
+}
+
+impl Display for ServiceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        if let Some(handle) = self.handle {
+            write!(f, " (handle {handle:#010X})")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the services currently opened by this crate's wrappers, in initialization order.
+///
+/// Each service is listed once, even if several handles (such as multiple [`Apt`](crate::services::apt::Apt) instances) reference it.
+///
+/// Services report the "already in use" error when a second handle is created while one is still alive
+/// somewhere else in the application (see [`Error::ServiceAlreadyActive`](crate::Error::ServiceAlreadyActive)):
+/// this list helps finding out which ones are still open.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::debug::{self, Overlay};
+/// use ctru::services::hid::Hid;
+///
+/// let hid = Hid::new()?;
+/// let mut overlay = Overlay::new();
+///
+/// for service in debug::list_open_services() {
+///     overlay.watch("service", service);
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub fn list_open_services() -> Vec<ServiceInfo> {
+    let mut services: Vec<ServiceInfo> = Vec::new();
+
+    for (name, handle) in crate::shutdown::registered_services() {
+        let handle = handle.and_then(|getter| getter());
+
+        match services.iter_mut().find(|service| service.name == name) {
+            Some(service) => service.handle = service.handle.or(handle),
+            None => services.push(ServiceInfo { name, handle }),
+        }
+    }
+
+    services
+}
+
+/// Maximum amount of threads listed in a [`Watchdog`] report.
+const MAX_REPORTED_THREADS: usize = 64;
+
+/// Timer detecting hangs of the application's main loop.
+///
+/// The watchdog runs a thread on the system core (core 1), which expects [`Watchdog::feed()`] to be called regularly
+/// (e.g. once per frame). When no feed happens for longer than the timeout, it writes a report listing
+/// the location of the last feed and the state of the application's threads, and optionally reboots the console.
+/// This is mostly useful for unattended, long-running applications which should recover from hangs on their own.
+///
+/// The watchdog fires only once per hang: it's armed again by the next feed.
+///
+/// # Notes
+///
+/// Application threads only run on the system core after [`Apt::set_app_cpu_time_limit()`](crate::services::apt::Apt::set_app_cpu_time_limit)
+/// has been called with a non-zero limit. Watching from another core ensures reports are written even if the hung thread
+/// never yields its own core.
+///
+/// The kernel doesn't let a process read the registers of its own threads, so reports can't contain the stack of the
+/// hung thread: the location of the last call to [`Watchdog::feed()`] is reported instead.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use std::time::Duration;
+///
+/// use ctru::debug::Watchdog;
+/// use ctru::services::apt::Apt;
+///
+/// let mut apt = Apt::new()?;
+/// apt.set_app_cpu_time_limit(5)?;
+///
+/// let watchdog = Watchdog::new(Duration::from_secs(5))?;
+/// watchdog.set_report_directory("sdmc:/3ds/my-app/hangs");
+/// watchdog.set_reboot(true);
+///
+/// while apt.main_loop() {
+///     watchdog.feed();
+///
+///     // ...
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct Watchdog {
+    shared: Arc<WatchdogShared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct WatchdogShared {
+    timeout: Duration,
+    state: Mutex<WatchdogState>,
+    condvar: Condvar,
+}
+
+struct WatchdogState {
+    last_feed: Instant,
+    location: &'static Location<'static>,
+    armed: bool,
+    stopped: bool,
+    directory: Option<PathBuf>,
+    reboot: bool,
+}
+
+impl Watchdog {
+    /// Start a watchdog expiring after `timeout` without being fed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the watchdog thread couldn't be created on the system core.
+    #[track_caller]
+    pub fn new(timeout: Duration) -> crate::Result<Self> {
+        let shared = Arc::new(WatchdogShared {
+            timeout,
+            state: Mutex::new(WatchdogState {
+                last_feed: Instant::now(),
+                location: Location::caller(),
+                armed: true,
+                stopped: false,
+                directory: None,
+                reboot: false,
+            }),
+            condvar: Condvar::new(),
+        });
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+
+            std::thread::Builder::new()
+                .name(String::from("watchdog"))
+                .processor_id(1)
+                .spawn(move || shared.run())
+                .map_err(|e| {
+                    crate::Error::Other(format!("couldn't start the watchdog thread: {e}"))
+                })?
+        };
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Reset the timer, recording the caller's location as the last point the application reached.
+    #[track_caller]
+    pub fn feed(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        state.last_feed = Instant::now();
+        state.location = Location::caller();
+        state.armed = true;
+    }
+
+    /// Returns the timeout of the watchdog.
+    pub fn timeout(&self) -> Duration {
+        self.shared.timeout
+    }
+
+    /// Set the directory where reports are written (e.g. `sdmc:/3ds/my-app/hangs`).
+    ///
+    /// Reports are always sent to the debugger with [`output_debug_string()`]. They're only written to the SD card
+    /// once a directory is set, which is created if needed.
+    pub fn set_report_directory(&self, directory: impl Into<PathBuf>) {
+        self.shared.state.lock().unwrap().directory = Some(directory.into());
+    }
+
+    /// Choose whether the console is rebooted after writing a report.
+    ///
+    /// Rebooting goes through APT, so an [`Apt`](crate::services::apt::Apt) handle must be alive when the watchdog expires.
+    pub fn set_reboot(&self, reboot: bool) {
+        self.shared.state.lock().unwrap().reboot = reboot;
+    }
+}
+
+impl WatchdogShared {
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        while !state.stopped {
+            let elapsed = state.last_feed.elapsed();
+
+            if !state.armed || elapsed < self.timeout {
+                let wait = if state.armed {
+                    self.timeout - elapsed
+                } else {
+                    self.timeout
+                };
+
+                state = self.condvar.wait_timeout(state, wait).unwrap().0;
+                continue;
+            }
+
+            state.armed = false;
+
+            let report = hang_report(elapsed, state.location);
+            let directory = state.directory.clone();
+            let reboot = state.reboot;
+
+            // Don't block feeds (which would mean the application recovered) while writing the report.
+            drop(state);
+
+            output_debug_string(&report);
+
+            if let Some(directory) = directory {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.as_secs())
+                    .unwrap_or_default();
+
+                let _ = std::fs::create_dir_all(&directory).and_then(|_| {
+                    std::fs::write(directory.join(format!("hang-{timestamp}.txt")), &report)
+                });
+            }
+
+            if reboot {
+                unsafe { ctru_sys::APT_HardwareResetAsync() };
+            }
+
+            state = self.state.lock().unwrap();
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stopped = true;
+        self.shared.condvar.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Describe a hang and the threads of the current process.
+fn hang_report(elapsed: Duration, location: &Location<'_>) -> String {
+    let mut report = String::new();
+
+    let _ = writeln!(
+        report,
+        "Watchdog expired: not fed for {} ms",
+        elapsed.as_millis()
+    );
+    let _ = writeln!(report, "Last fed at: {location}");
+    let _ = writeln!(report, "\nThreads:");
+
+    let mut ids = [0u32; MAX_REPORTED_THREADS];
+    let mut count = 0;
+    let result = unsafe {
+        ctru_sys::svcGetThreadList(
+            &mut count,
+            ids.as_mut_ptr(),
+            MAX_REPORTED_THREADS as i32,
+            ctru_sys::CUR_PROCESS_HANDLE,
+        )
+    };
+
+    if result < 0 {
+        let _ = writeln!(report, "  unavailable ({})", crate::Error::from(result));
+        return report;
+    }
+
+    for &id in &ids[..count as usize] {
+        let _ = write!(report, "  {id}");
+
+        if let Some(name) = thread_name(id) {
+            let _ = write!(report, " ({name})");
+        }
+
+        let mut handle = 0;
+        if unsafe { ctru_sys::svcOpenThread(&mut handle, ctru_sys::CUR_PROCESS_HANDLE, id) } >= 0 {
+            let mut priority = 0;
+            let mut processor = 0;
+
+            unsafe {
+                if ctru_sys::svcGetThreadPriority(&mut priority, handle) >= 0 {
+                    let _ = write!(report, " priority {priority:#X}");
+                }
+                if ctru_sys::svcGetThreadIdealProcessor(&mut processor, handle) >= 0 {
+                    let _ = write!(report, " core {processor}");
+                }
+
+                ctru_sys::svcCloseHandle(handle);
+            }
+        }
+
+        let _ = writeln!(report);
+    }
+
+    report
+}
+
+/// Immediate-mode debug overlay.
+///
+/// Every frame, the overlay renders the current framerate, memory usage statistics and any values registered
+/// with [`Overlay::watch()`] or [`Overlay::bar()`] directly onto a screen's framebuffer.
+/// It can be shown or hidden at runtime by pressing its toggle key combination (`L + R + SELECT` by default).
+///
+/// # Notes
+///
+/// The overlay doesn't use `stdout` nor `libctru`'s console system, so a [`Console`](crate::console::Console)
+/// on the other screen keeps working as usual. Since it writes to the framebuffer directly, [`Overlay::draw()`]
+/// should be called after everything else has been drawn for the frame, before flushing and swapping the screen's buffers.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::debug::Overlay;
+/// use ctru::services::gfx::{Flush, Gfx, Swap};
+/// use ctru::services::hid::Hid;
+///
+/// let gfx = Gfx::new()?;
+/// let mut hid = Hid::new()?;
+/// let mut overlay = Overlay::new();
+///
+/// let mut bottom_screen = gfx.bottom_screen.borrow_mut();
+/// let player_x = 42;
+///
+/// hid.scan_input();
+/// overlay.update(&hid);
+///
+/// overlay.watch("player x", player_x);
+/// overlay.bar("loading", 0.5);
+/// overlay.draw(&mut *bottom_screen);
+///
+/// bottom_screen.flush_buffers();
+/// bottom_screen.swap_buffers();
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct Overlay {
+    toggle: KeyPad,
+    visible: bool,
+    lines: Vec<Line>,
+    frames: u32,
+    sample_start: Instant,
+    fps: f32,
+}
+
+enum Line {
+    Text(String),
+    Bar(String, f32),
+}
+
+impl Overlay {
+    /// Create a new (visible) overlay using the default `L + R + SELECT` toggle key combination.
+    pub fn new() -> Self {
+        Self::with_toggle(KeyPad::L | KeyPad::R | KeyPad::SELECT)
+    }
+
+    /// Create a new (visible) overlay toggled by the given key combination.
+    pub fn with_toggle(toggle: KeyPad) -> Self {
+        Self {
+            toggle,
+            visible: true,
+            lines: Vec::new(),
+            frames: 0,
+            sample_start: Instant::now(),
+            fps: 0.0,
+        }
+    }
+
+    /// Returns `true` if the overlay is currently visible.
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Show or hide the overlay.
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Returns the framerate measured by the overlay, updated once per second.
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    /// Update the overlay's state. This function should be called once per frame.
+    ///
+    /// The overlay is toggled if the key combination was completed during this frame.
+    pub fn update(&mut self, hid: &Hid) {
+        if hid.keys_held().contains(self.toggle) && hid.keys_down().intersects(self.toggle) {
+            self.visible = !self.visible;
+        }
+
+        self.frames += 1;
+
+        let elapsed = self.sample_start.elapsed();
+        if elapsed >= FPS_SAMPLE_TIME {
+            self.fps = self.frames as f32 / elapsed.as_secs_f32();
+            self.frames = 0;
+            self.sample_start = Instant::now();
+        }
+    }
+
+    /// Show a labelled value on the overlay for the current frame.
+    pub fn watch(&mut self, label: &str, value: impl Display) {
+        let mut line = String::new();
+        let _ = write!(line, "{label}: {value}");
+
+        self.lines.push(Line::Text(line));
+    }
+
+    /// Show a labelled progress bar on the overlay for the current frame.
+    ///
+    /// `fraction` is clamped between `0.0` (empty) and `1.0` (full).
+    pub fn bar(&mut self, label: &str, fraction: f32) {
+        self.lines
+            .push(Line::Bar(String::from(label), fraction.clamp(0.0, 1.0)));
+    }
+
+    /// Draw the overlay onto the given screen and clear the values registered during this frame.
+    ///
+    /// Nothing is drawn if the overlay is hidden.
+    ///
+    /// # Panics
+    ///
+    /// If the [`Gfx`](crate::services::gfx::Gfx) service was initialised via [`Gfx::with_formats_vram()`](crate::services::gfx::Gfx::with_formats_vram)
+    /// this function will crash the program with an ARM exception.
+    pub fn draw<S: Screen>(&mut self, screen: &mut S) {
+        let lines = std::mem::take(&mut self.lines);
+
+        if !self.visible {
+            return;
+        }
+
+        let format = screen.framebuffer_format();
+        let framebuffer = screen.raw_framebuffer();
+
+        let mut canvas = Canvas {
+            ptr: framebuffer.ptr,
+            // Framebuffers are rotated by 90 degrees, so their "width" corresponds to the screen's height.
+            width: framebuffer.height,
+            height: framebuffer.width,
+            format,
+        };
+
+        let app = MemRegion::Application;
+        let header = [
+            format!("FPS: {:.1}", self.fps),
+            format!("HEAP: {}/{} KiB", app.used() / 1024, app.size() / 1024),
+            format!("LINEAR free: {} KiB", LinearAllocator::free_space() / 1024),
+        ];
+
+        let mut y = 0;
+
+        for text in header.iter() {
+            canvas.text(0, y, text);
+            y += GLYPH_SIZE;
+        }
+
+        for line in lines.iter() {
+            match line {
+                Line::Text(text) => canvas.text(0, y, text),
+                Line::Bar(label, fraction) => {
+                    let filled = (fraction * BAR_WIDTH as f32).round() as usize;
+                    let bar: String = (0..BAR_WIDTH)
+                        .map(|i| if i < filled { '#' } else { '-' })
+                        .collect();
+
+                    canvas.text(0, y, &format!("{label}: [{bar}] {:.0}%", fraction * 100.0));
+                }
+            }
+
+            y += GLYPH_SIZE;
+        }
+    }
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Minimal software renderer working on a raw framebuffer.
+struct Canvas {
+    ptr: *mut u8,
+    width: usize,
+    height: usize,
+    format: FramebufferFormat,
+}
+
+impl Canvas {
+    fn text(&mut self, x: usize, y: usize, text: &str) {
+        if y + GLYPH_SIZE > self.height {
+            return;
+        }
+
+        // SAFETY: the default console is statically allocated by `libctru` and its font is never modified.
+        let font = unsafe { (*ctru_sys::consoleGetDefault()).font };
+
+        for (i, c) in text.chars().enumerate() {
+            let glyph_x = x + i * GLYPH_SIZE;
+
+            if glyph_x + GLYPH_SIZE > self.width {
+                break;
+            }
+
+            let index = (c as u32)
+                .checked_sub(font.asciiOffset.into())
+                .filter(|index| *index < font.numChars.into())
+                .unwrap_or((u32::from(b'?')).saturating_sub(font.asciiOffset.into()));
+
+            let glyph = unsafe {
+                std::slice::from_raw_parts(font.gfx.add(index as usize * GLYPH_SIZE), GLYPH_SIZE)
+            };
+
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..GLYPH_SIZE {
+                    let color = if bits & (0x80 >> column) != 0 {
+                        FOREGROUND
+                    } else {
+                        BACKGROUND
+                    };
+
+                    self.set_pixel(glyph_x + column, y + row, color);
+                }
+            }
+        }
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, (r, g, b): (u8, u8, u8)) {
+        let depth = self.format.pixel_depth_bytes();
+        // Pixels are stored in columns, starting from the bottom-left corner of the screen.
+        let offset = (x * self.height + (self.height - 1 - y)) * depth;
+
+        let (r, g, b) = (u32::from(r), u32::from(g), u32::from(b));
+        let packed = match self.format {
+            FramebufferFormat::Rgba8 => (r << 24) | (g << 16) | (b << 8) | 0xFF,
+            FramebufferFormat::Bgr8 => (r << 16) | (g << 8) | b,
+            FramebufferFormat::Rgb565 => ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3),
+            FramebufferFormat::Rgb5A1 => ((r >> 3) << 11) | ((g >> 3) << 6) | ((b >> 3) << 1) | 1,
+            FramebufferFormat::Rgba4 => ((r >> 4) << 12) | ((g >> 4) << 8) | ((b >> 4) << 4) | 0xF,
+        };
+        let bytes = packed.to_le_bytes();
+
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.add(offset), depth) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thread_name_truncation() {
+        // 16 two-byte characters don't fit in 31 bytes: the last one is dropped whole.
+        set_thread_name(&"é".repeat(16)).unwrap();
+        let name = thread_name(current_thread_id().unwrap());
+        clear_thread_name().unwrap();
+
+        assert_eq!(name, Some("é".repeat(15)));
+    }
+}
@@ -0,0 +1,471 @@
+//! Asset hot-reloading over the network.
+//!
+//! [`HotReload`] listens for files pushed from a computer while the application is running, and hands them to the code which
+//! registered interest in their path (see [`HotReload::watch()`]). Swapping a texture or an audio buffer then only takes
+//! saving it on the computer and pushing it to the console, without rebuilding or restarting the application.
+//!
+//! # Protocol
+//!
+//! Each connection pushes one or more files, each sent as a header line followed by the file's contents:
+//!
+//! ```text
+//! PUSH <size in bytes> <path>\n
+//! <contents>
+//! ```
+//!
+//! The server answers every file with `OK\n` once it has been decoded, or `ERR <message>\n` if it couldn't be used.
+//! The path must match the one passed to [`HotReload::watch()`] exactly (e.g. `romfs:/textures/player.t3x`).
+//! Any TCP client can push files, for example a shell one-liner:
+//!
+//! ```text
+//! (printf "PUSH %d %s\n" $(stat -c%s player.t3x) romfs:/textures/player.t3x; cat player.t3x) | nc 192.168.1.20 5010
+//! ```
+//!
+//! Assets loaded with an [`assets::Loader`](crate::assets::Loader) can be watched with [`HotReload::watch_asset()`],
+//! which uses the same decoding function for the initial load and for the reloads.
+//!
+//! Files can also be reloaded when they change on the console's own filesystem (e.g. on the SD card, or on the host computer
+//! through [`fs::host()`](crate::services::fs::host)), see [`HotReload::watch_files()`].
+//!
+//! # Notes
+//!
+//! The server accepts files from anyone. Only use it on trusted networks.
+#![doc(alias = "live reload")]
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::marker::PhantomData;
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::server::Server;
+use crate::assets::{LoadHandle, Loader, Priority};
+use crate::linear::LinearAllocator;
+use crate::services::fs::{FileEventKind, Watcher};
+use crate::services::soc::Soc;
+
+/// Interval at which idle connections check whether the server is shutting down.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Maximum length of a header line.
+const MAX_HEADER_LENGTH: usize = 1024;
+
+/// Largest file accepted by the server.
+const MAX_FILE_SIZE: u64 = 32 * 1024 * 1024;
+
+type Decoded = Box<dyn Any + Send>;
+type Decoder = Arc<dyn Fn(Vec<u8>) -> crate::Result<Decoded> + Send + Sync>;
+type Apply = Box<dyn FnMut(Decoded)>;
+
+/// Server receiving assets pushed over the network.
+///
+/// Files are received and decoded on background threads. The decoded assets are only handed over by [`HotReload::poll()`],
+/// on the thread calling it, so resources which can't be shared between threads (such as GPU textures) can be swapped safely.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::devtools::HotReload;
+/// use ctru::prelude::*;
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// let apt = Apt::new()?;
+/// let soc = Soc::new()?;
+///
+/// let level = Rc::new(RefCell::new(std::fs::read("romfs:/levels/1.bin")?));
+///
+/// let mut hot_reload = HotReload::bind(&soc, 5010)?;
+/// let reloaded_level = level.clone();
+/// hot_reload.watch("romfs:/levels/1.bin", move |data| {
+///     *reloaded_level.borrow_mut() = data;
+/// });
+///
+/// while apt.main_loop() {
+///     hot_reload.poll()?;
+///
+///     let level = level.borrow();
+///     // Use the level...
+/// #   break;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct HotReload<'soc> {
+    server: Server,
+    decoders: Arc<Mutex<HashMap<PathBuf, Decoder>>>,
+    appliers: HashMap<PathBuf, Apply>,
+    sender: Sender<(PathBuf, Decoded)>,
+    receiver: Receiver<(PathBuf, Decoded)>,
+    files: Option<Watcher>,
+    _soc: PhantomData<&'soc Soc>,
+}
+
+impl<'soc> HotReload<'soc> {
+    /// Start listening for pushed files on the given port.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the port is already in use.
+    pub fn bind(_soc: &'soc Soc, port: u16) -> crate::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+
+        Ok(Self {
+            server: Server::bind(port, "hot-reload", POLL_INTERVAL)?,
+            decoders: Arc::new(Mutex::new(HashMap::new())),
+            appliers: HashMap::new(),
+            sender,
+            receiver,
+            files: None,
+            _soc: PhantomData,
+        })
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> crate::Result<SocketAddr> {
+        self.server.local_addr()
+    }
+
+    /// Call `on_reload` with the contents of every file pushed to `path`.
+    ///
+    /// Watching a path again replaces the previous callback.
+    pub fn watch(&mut self, path: impl Into<PathBuf>, on_reload: impl FnMut(Vec<u8>) + 'static) {
+        self.watch_with(path, Ok, on_reload);
+    }
+
+    /// Call `on_reload` with the contents of every file pushed to `path`, copied into [LINEAR memory](crate::linear).
+    ///
+    /// This matches [`Loader::load_linear()`](crate::assets::Loader::load_linear), for assets used by the GPU or the DSP.
+    pub fn watch_linear(
+        &mut self,
+        path: impl Into<PathBuf>,
+        on_reload: impl FnMut(Box<[u8], LinearAllocator>) + 'static,
+    ) {
+        self.watch_with(
+            path,
+            |data| {
+                let mut buffer = Vec::with_capacity_in(data.len(), LinearAllocator);
+                buffer.extend_from_slice(&data);
+
+                Ok(buffer.into_boxed_slice())
+            },
+            on_reload,
+        );
+    }
+
+    /// Decode every file pushed to `path` with `decode`, then call `on_reload` with the result.
+    ///
+    /// `decode` runs on the thread receiving the file, like a job queued with [`Loader::load_with()`](crate::assets::Loader::load_with),
+    /// so the main loop only pays for swapping the asset. Errors returned by `decode` are reported to the client which pushed the file.
+    pub fn watch_with<T, D, F>(&mut self, path: impl Into<PathBuf>, decode: D, mut on_reload: F)
+    where
+        T: Send + 'static,
+        D: Fn(Vec<u8>) -> crate::Result<T> + Send + Sync + 'static,
+        F: FnMut(T) + 'static,
+    {
+        let path = path.into();
+
+        let decoder: Decoder = Arc::new(move |data| Ok(Box::new(decode(data)?) as Decoded));
+        let apply: Apply = Box::new(move |decoded| {
+            if let Ok(decoded) = decoded.downcast::<T>() {
+                on_reload(*decoded);
+            }
+        });
+
+        if let Some(files) = &self.files {
+            files.watch(path.clone());
+        }
+
+        self.decoders.lock().unwrap().insert(path.clone(), decoder);
+        self.appliers.insert(path, apply);
+    }
+
+    /// Queue the loading of `path` on `loader`, decoded with `decode`, then reload it as with [`HotReload::watch_with()`].
+    ///
+    /// The initial load runs on the loader's worker thread like any [`Loader::load_with()`] request, and reloads use the same
+    /// `decode` function, so the asset is built the same way whether it comes from the application's files or from a push.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::assets::{Loader, Priority};
+    /// use ctru::devtools::HotReload;
+    /// use ctru::prelude::*;
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    ///
+    /// let soc = Soc::new()?;
+    /// let loader = Loader::new()?;
+    /// let mut hot_reload = HotReload::bind(&soc, 5010)?;
+    ///
+    /// let parse = |data: Vec<u8>| Ok(String::from_utf8_lossy(&data).into_owned());
+    ///
+    /// let dialogue = Rc::new(RefCell::new(String::new()));
+    /// let reloaded = dialogue.clone();
+    /// let initial = hot_reload.watch_asset(&loader, Priority::High, "romfs:/dialogue.txt", parse, move |text| {
+    ///     *reloaded.borrow_mut() = text;
+    /// });
+    ///
+    /// *dialogue.borrow_mut() = initial.wait()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch_asset<T, D, F>(
+        &mut self,
+        loader: &Loader,
+        priority: Priority,
+        path: impl Into<PathBuf>,
+        decode: D,
+        on_reload: F,
+    ) -> LoadHandle<T>
+    where
+        T: Send + 'static,
+        D: Fn(Vec<u8>) -> crate::Result<T> + Send + Sync + 'static,
+        F: FnMut(T) + 'static,
+    {
+        let path = path.into();
+        let decode = Arc::new(decode);
+
+        let load_path = path.clone();
+        let load_decode = decode.clone();
+        let handle = loader.load_with(priority, move || load_decode(std::fs::read(load_path)?));
+
+        self.watch_with(path, move |data| decode(data), on_reload);
+
+        handle
+    }
+
+    /// Also reload the watched paths whenever their file changes on the filesystem, checking them every `interval`.
+    ///
+    /// The files are read and decoded on the [`Watcher`]'s thread, then handed over by [`HotReload::poll()`]
+    /// like pushed files. Files which can't be read or decoded are skipped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the watcher thread couldn't be spawned.
+    pub fn watch_files(&mut self, interval: Duration) -> crate::Result<()> {
+        let decoders = self.decoders.clone();
+        let sender = self.sender.clone();
+
+        let watcher = Watcher::with_interval(interval, move |event| {
+            if event.kind == FileEventKind::Removed {
+                return;
+            }
+
+            let Some(decoder) = decoders.lock().unwrap().get(&event.path).cloned() else {
+                return;
+            };
+
+            if let Ok(decoded) = std::fs::read(&event.path)
+                .map_err(crate::Error::from)
+                .and_then(|data| decoder(data))
+            {
+                let _ = sender.send((event.path.clone(), decoded));
+            }
+        })?;
+
+        for path in self.appliers.keys() {
+            watcher.watch(path.clone());
+        }
+
+        self.files = Some(watcher);
+
+        Ok(())
+    }
+
+    /// Stop watching `path`. Files pushed to it are then rejected.
+    pub fn unwatch(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+
+        if let Some(files) = &self.files {
+            files.unwatch(&path);
+        }
+
+        self.decoders.lock().unwrap().remove(&path);
+        self.appliers.remove(&path);
+    }
+
+    /// Accept pending connections and hand the assets decoded since the last call to their callbacks, without blocking.
+    ///
+    /// Returns the amount of assets reloaded.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if accepting connections or spawning a connection thread failed.
+    pub fn poll(&mut self) -> crate::Result<usize> {
+        self.server.accept(|| {
+            let decoders = self.decoders.clone();
+            let sender = self.sender.clone();
+
+            move |stream: TcpStream, stop: &AtomicBool| {
+                let _ = receive(stream, &decoders, &sender, stop);
+            }
+        })?;
+
+        let mut reloaded = 0;
+
+        while let Ok((path, decoded)) = self.receiver.try_recv() {
+            if let Some(apply) = self.appliers.get_mut(&path) {
+                apply(decoded);
+                reloaded += 1;
+            }
+        }
+
+        Ok(reloaded)
+    }
+}
+
+/// Receive the files pushed through a connection until the client disconnects or the server shuts down.
+fn receive(
+    stream: TcpStream,
+    decoders: &Mutex<HashMap<PathBuf, Decoder>>,
+    sender: &Sender<(PathBuf, Decoded)>,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        let read = match (&mut reader)
+            .take((MAX_HEADER_LENGTH - line.len()) as u64)
+            .read_until(b'\n', &mut line)
+        {
+            Ok(read) => read,
+            // Partially read headers are kept in `line` until the next attempt.
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        };
+
+        if line.last() != Some(&b'\n') {
+            if line.len() >= MAX_HEADER_LENGTH {
+                return writeln!(writer, "ERR the header is too long");
+            }
+
+            if read == 0 {
+                // The client disconnected.
+                return Ok(());
+            }
+
+            continue;
+        }
+
+        let header = std::mem::take(&mut line);
+        let (size, path) = match parse_header(&header) {
+            Ok(header) => header,
+            Err(message) => {
+                // The rest of the stream can't be interpreted anymore.
+                return writeln!(writer, "ERR {message}");
+            }
+        };
+
+        let data = read_contents(&mut reader, size, stop)?;
+
+        let decoder = decoders.lock().unwrap().get(&path).cloned();
+        let reply = match decoder {
+            Some(decoder) => match decoder(data) {
+                Ok(decoded) => {
+                    let _ = sender.send((path, decoded));
+                    String::from("OK")
+                }
+                Err(e) => format!("ERR {e}"),
+            },
+            None => format!("ERR {} is not watched", path.display()),
+        };
+
+        writeln!(writer, "{reply}")?;
+    }
+
+    Ok(())
+}
+
+/// Read exactly `size` bytes, retrying on read timeouts unless the server shuts down.
+fn read_contents(reader: &mut impl Read, size: u64, stop: &AtomicBool) -> io::Result<Vec<u8>> {
+    let mut data = vec![0; size as usize];
+    let mut filled = 0;
+
+    while filled < data.len() {
+        if stop.load(Ordering::Relaxed) {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+
+        match reader.read(&mut data[filled..]) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(read) => filled += read,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::Interrupted
+                ) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(data)
+}
+
+/// Parse a `PUSH <size> <path>` header line.
+fn parse_header(line: &[u8]) -> Result<(u64, PathBuf), String> {
+    let line = std::str::from_utf8(line).map_err(|_| String::from("the header is not UTF-8"))?;
+    let line = line.trim_end_matches(['\r', '\n']);
+
+    let mut parts = line.splitn(3, ' ');
+
+    if parts.next() != Some("PUSH") {
+        return Err(String::from("expected a PUSH header"));
+    }
+
+    let size: u64 = parts
+        .next()
+        .and_then(|size| size.parse().ok())
+        .ok_or_else(|| String::from("invalid file size"))?;
+
+    if size > MAX_FILE_SIZE {
+        return Err(format!("files can't be larger than {MAX_FILE_SIZE} bytes"));
+    }
+
+    match parts.next() {
+        Some(path) if !path.is_empty() => Ok((size, PathBuf::from(path))),
+        _ => Err(String::from("missing file path")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_parsing() {
+        assert_eq!(
+            parse_header(b"PUSH 42 romfs:/textures/my player.t3x\r\n"),
+            Ok((42, PathBuf::from("romfs:/textures/my player.t3x")))
+        );
+
+        assert!(parse_header(b"PULL 42 romfs:/a.bin\n").is_err());
+        assert!(parse_header(b"PUSH -1 romfs:/a.bin\n").is_err());
+        assert!(parse_header(b"PUSH 42\n").is_err());
+        assert!(parse_header(format!("PUSH {} a.bin\n", MAX_FILE_SIZE + 1).as_bytes()).is_err());
+    }
+}
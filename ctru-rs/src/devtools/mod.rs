@@ -0,0 +1,17 @@
+//! Development tools.
+//!
+//! Utilities meant to shorten iteration times while developing on hardware, such as pushing new assets to a running application
+//! and inspecting or recording it from a computer.
+//! They have no place in a release build: this module is only available with the `devtools` feature.
+//!
+//! Everything in this module requires the [`Soc`](crate::services::soc::Soc) service to be active.
+#![doc(alias = "debugging")]
+
+pub mod hot_reload;
+pub mod remote_console;
+pub mod screen_stream;
+mod server;
+
+pub use hot_reload::HotReload;
+pub use remote_console::{remote_console, LogSink, RemoteConsole};
+pub use screen_stream::ScreenStream;
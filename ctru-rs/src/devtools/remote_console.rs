@@ -0,0 +1,382 @@
+//! Remote console over TCP.
+//!
+//! [`RemoteConsole`] lets developers connect to a running application with any telnet client (e.g. `telnet` or `nc`), read its log
+//! stream and run commands registered by the application (see [`RemoteConsole::register()`]), without a console on screen.
+//!
+//! Log lines are written with [`LogSink::write_line()`]. With the `log` feature, the sink can be attached to the
+//! [`Logger`](crate::logger::Logger) to forward all log records:
+//!
+//! ```ignore
+//! let sink = console.log_sink();
+//! Logger::builder().sink(move |line| sink.write_line(line)).init()?;
+//! ```
+//!
+//! # Notes
+//!
+//! The console accepts connections from anyone. Only use it on trusted networks.
+#![doc(alias = "repl")]
+#![doc(alias = "telnet")]
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::server::Server;
+use crate::services::soc::Soc;
+
+/// Interval at which sessions forward new log lines and check whether the console is shutting down.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Amount of log lines kept to be sent to clients when they connect.
+const HISTORY_LINES: usize = 64;
+
+/// Telnet "Interpret As Command" byte, which starts option negotiations.
+const TELNET_IAC: u8 = 0xFF;
+
+type Command = Box<dyn FnMut(&[&str]) -> String>;
+
+/// Command line sent by a session, to be run by [`RemoteConsole::poll()`].
+struct Request {
+    line: String,
+    reply: Sender<String>,
+}
+
+#[derive(Default)]
+struct LogState {
+    history: VecDeque<String>,
+    subscribers: Vec<Sender<String>>,
+}
+
+/// Handle writing log lines to all clients of a [`RemoteConsole`].
+///
+/// The sink can be cloned and used from any thread.
+#[derive(Clone, Default)]
+pub struct LogSink {
+    state: Arc<Mutex<LogState>>,
+}
+
+/// Start a [`RemoteConsole`] listening on the given port.
+///
+/// This is a shorthand for [`RemoteConsole::bind()`].
+pub fn remote_console(soc: &Soc, port: u16) -> crate::Result<RemoteConsole<'_>> {
+    RemoteConsole::bind(soc, port)
+}
+
+/// Console serving the log stream and running registered commands for TCP clients.
+///
+/// Clients are served on background threads, but commands only run within [`RemoteConsole::poll()`], on the thread calling it.
+/// They can thus freely access the state of the main loop.
+///
+/// Besides the registered commands, clients can always use `help` to list the available commands and `quit` to disconnect.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::devtools::remote_console;
+/// use ctru::prelude::*;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let apt = Apt::new()?;
+/// let soc = Soc::new()?;
+///
+/// let lives = Rc::new(Cell::new(3));
+///
+/// let mut console = remote_console(&soc, 5020)?;
+/// let command_lives = lives.clone();
+/// console.register("lives", "lives [count]: show or set the lives left", move |args| {
+///     if let Some(Ok(count)) = args.first().map(|count| count.parse()) {
+///         command_lives.set(count);
+///     }
+///
+///     format!("{} lives left", command_lives.get())
+/// });
+///
+/// let log = console.log_sink();
+/// log.write_line(&format!("Connect with `telnet {} 5020`", soc.host_address()));
+///
+/// while apt.main_loop() {
+///     console.poll()?;
+/// #   break;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct RemoteConsole<'soc> {
+    server: Server,
+    commands: BTreeMap<String, (String, Command)>,
+    log: LogSink,
+    sender: Sender<Request>,
+    receiver: Receiver<Request>,
+    _soc: PhantomData<&'soc Soc>,
+}
+
+impl<'soc> RemoteConsole<'soc> {
+    /// Start listening for clients on the given port.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the port is already in use.
+    pub fn bind(_soc: &'soc Soc, port: u16) -> crate::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+
+        Ok(Self {
+            server: Server::bind(port, "remote-console", POLL_INTERVAL)?,
+            commands: BTreeMap::new(),
+            log: LogSink::default(),
+            sender,
+            receiver,
+            _soc: PhantomData,
+        })
+    }
+
+    /// Returns the address the console is listening on.
+    pub fn local_addr(&self) -> crate::Result<SocketAddr> {
+        self.server.local_addr()
+    }
+
+    /// Returns a handle to write lines to the log stream of the console.
+    pub fn log_sink(&self) -> LogSink {
+        self.log.clone()
+    }
+
+    /// Register a command called `name`, described by `help` in the output of the `help` command.
+    ///
+    /// `command` receives the whitespace-separated arguments following the command name, and returns the text sent back to the client.
+    /// Registering a name again replaces the previous command.
+    pub fn register(
+        &mut self,
+        name: &str,
+        help: &str,
+        command: impl FnMut(&[&str]) -> String + 'static,
+    ) {
+        self.commands
+            .insert(String::from(name), (String::from(help), Box::new(command)));
+    }
+
+    /// Remove the command called `name`.
+    pub fn unregister(&mut self, name: &str) {
+        self.commands.remove(name);
+    }
+
+    /// Accept pending connections and run the commands sent since the last call, without blocking.
+    ///
+    /// Returns the amount of commands run.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if accepting connections or spawning a session thread failed.
+    pub fn poll(&mut self) -> crate::Result<usize> {
+        self.server.accept(|| {
+            // Each session gets the log history when it connects, then the new lines.
+            let log = self.log.subscribe();
+            let sender = self.sender.clone();
+
+            move |stream: TcpStream, stop: &AtomicBool| {
+                let _ = run_session(stream, &log, &sender, stop);
+            }
+        })?;
+
+        let mut run = 0;
+
+        while let Ok(request) = self.receiver.try_recv() {
+            let output = self.run(&request.line);
+            let _ = request.reply.send(output);
+            run += 1;
+        }
+
+        Ok(run)
+    }
+
+    fn run(&mut self, line: &str) -> String {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return String::new();
+        };
+        let args: Vec<&str> = words.collect();
+
+        match self.commands.get_mut(name) {
+            Some((_, command)) => command(&args),
+            None if name == "help" => {
+                let mut help = String::from("help: list the available commands\nquit: disconnect");
+
+                for (name, (description, _)) in &self.commands {
+                    if description.is_empty() {
+                        help.push_str(&format!("\n{name}"));
+                    } else {
+                        help.push_str(&format!("\n{description}"));
+                    }
+                }
+
+                help
+            }
+            None => format!("unknown command `{name}`, try `help`"),
+        }
+    }
+}
+
+impl LogSink {
+    /// Send `line` to all connected clients, and keep it to be sent to the ones connecting later.
+    pub fn write_line(&self, line: &str) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.history.len() == HISTORY_LINES {
+            state.history.pop_front();
+        }
+        state.history.push_back(String::from(line));
+
+        // Sessions which ended are dropped from the list.
+        state
+            .subscribers
+            .retain(|subscriber| subscriber.send(String::from(line)).is_ok());
+    }
+
+    /// Returns a receiver of all new lines, preloaded with the lines kept in the history.
+    fn subscribe(&self) -> Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        let mut state = self.state.lock().unwrap();
+
+        for line in &state.history {
+            let _ = sender.send(line.clone());
+        }
+        state.subscribers.push(sender);
+
+        receiver
+    }
+}
+
+/// Serve a client until it disconnects or the console shuts down.
+fn run_session(
+    stream: TcpStream,
+    log: &Receiver<String>,
+    requests: &Sender<Request>,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+
+    writer.write_all(b"ctru-rs remote console, type `help` for the available commands.\r\n> ")?;
+
+    while !stop.load(Ordering::Relaxed) {
+        forward_log(&mut writer, log)?;
+
+        match reader.read_until(b'\n', &mut line) {
+            // Partially read lines are kept in `line` until the next attempt.
+            Ok(0) => return Ok(()),
+            Ok(_) if line.last() != Some(&b'\n') => continue,
+            Ok(_) => {}
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        }
+
+        let input = strip_telnet(&std::mem::take(&mut line));
+
+        match input.trim() {
+            "" => {}
+            "quit" | "exit" => return Ok(()),
+            command => {
+                let (reply, replied) = mpsc::channel();
+                let request = Request {
+                    line: String::from(command),
+                    reply,
+                };
+
+                if requests.send(request).is_err() {
+                    return Ok(());
+                }
+
+                // Keep the log flowing while the main loop gets to the command.
+                let output = loop {
+                    match replied.recv_timeout(POLL_INTERVAL) {
+                        Ok(output) => break output,
+                        Err(mpsc::RecvTimeoutError::Timeout) if !stop.load(Ordering::Relaxed) => {
+                            forward_log(&mut writer, log)?
+                        }
+                        Err(_) => return Ok(()),
+                    }
+                };
+
+                for output_line in output.lines() {
+                    write!(writer, "{output_line}\r\n")?;
+                }
+            }
+        }
+
+        writer.write_all(b"> ")?;
+    }
+
+    Ok(())
+}
+
+fn forward_log(writer: &mut impl Write, log: &Receiver<String>) -> io::Result<()> {
+    while let Ok(line) = log.try_recv() {
+        write!(writer, "{line}\r\n")?;
+    }
+
+    Ok(())
+}
+
+/// Decode a line sent by a telnet client, removing option negotiations.
+fn strip_telnet(line: &[u8]) -> String {
+    let mut text = Vec::with_capacity(line.len());
+    let mut bytes = line.iter().copied();
+
+    while let Some(byte) = bytes.next() {
+        if byte == TELNET_IAC {
+            // Negotiations are `IAC <command> <option>`. Other commands are ignored as well.
+            match bytes.next() {
+                Some(0xFB..=0xFE) => {
+                    bytes.next();
+                }
+                Some(TELNET_IAC) => text.push(TELNET_IAC),
+                _ => {}
+            }
+        } else {
+            text.push(byte);
+        }
+    }
+
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn telnet_and_history() {
+        assert_eq!(
+            strip_telnet(b"\xFF\xFB\x1F\xFF\xF1lives 5\r\n"),
+            "lives 5\r\n"
+        );
+
+        let sink = LogSink::default();
+        for n in 0..HISTORY_LINES + 2 {
+            sink.write_line(&format!("line {n}"));
+        }
+
+        let receiver = sink.subscribe();
+        assert_eq!(receiver.try_iter().count(), HISTORY_LINES);
+        assert_eq!(receiver.try_recv().ok(), None);
+
+        sink.write_line("new line");
+        assert_eq!(receiver.try_recv().ok().as_deref(), Some("new line"));
+    }
+}
@@ -0,0 +1,478 @@
+//! Framebuffer streaming over TCP.
+//!
+//! [`ScreenStream`] sends the contents of the screens to the computers connected to it, so footage can be recorded without a capture board.
+//! Frames are copied out of the framebuffers on the main loop (see [`ScreenStream::capture()`]), then compressed and sent by a background thread.
+//!
+//! # Protocol
+//!
+//! Clients just connect and read. Each frame starts with a 16 bytes header (all integers are little-endian):
+//!
+//! | Offset | Size | Contents                                                                        |
+//! |--------|------|---------------------------------------------------------------------------------|
+//! | 0      | 4    | Magic `3DSF`                                                                    |
+//! | 4      | 1    | Screen: 0 for the top screen (left side), 1 for its right side, 2 for the bottom screen |
+//! | 5      | 1    | [`FramebufferFormat`], as its `GSP_*_OES` value                                 |
+//! | 6      | 1    | [`Compression`]: 0 for none, 1 for RLE, 2 for JPEG                              |
+//! | 7      | 1    | Reserved (0)                                                                    |
+//! | 8      | 2    | Framebuffer width, in pixels                                                    |
+//! | 10     | 2    | Framebuffer height, in pixels                                                   |
+//! | 12     | 4    | Size of the payload following the header, in bytes                              |
+//!
+//! The payload holds the framebuffer as it is laid out in memory: the screens of the 3DS are rotated, so framebuffers are stored
+//! column by column, starting from the bottom-left corner of the screen (e.g. the top screen's framebuffer is 240 pixels wide and 400 pixels high).
+//!
+//! RLE payloads are a sequence of packets made of whole pixels. A control byte `c` is followed by either `c + 1` literal pixels
+//! (if `c < 0x80`), or by a single pixel to repeat `c - 0x7F` times.
+//!
+//! JPEG payloads are complete JPEG files of the screen the right way up (see [`jpeg::encode()`]), whatever the framebuffer format.
+//!
+//! # Notes
+//!
+//! The 3DS has no hardware image encoder (its MVD and Y2R units only decode and convert), so frames are compressed on the CPU
+//! by the streaming thread. Capturing costs a copy of the framebuffer per frame on the main loop, so keep the frame rate reasonable.
+#![doc(alias = "capture")]
+#![doc(alias = "video")]
+
+use std::io::Write;
+use std::marker::PhantomData;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::image::{jpeg, Image, PixelFormat};
+use crate::services::gfx::{Screen, Side};
+use crate::services::gspgpu::FramebufferFormat;
+use crate::services::soc::Soc;
+
+/// Interval at which the streaming thread accepts new clients while no frame is captured.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Magic bytes starting each frame.
+const FRAME_MAGIC: &[u8; 4] = b"3DSF";
+
+/// Longest run or literal sequence encoded by a single RLE packet.
+const MAX_PACKET_PIXELS: usize = 0x80;
+
+/// Compression applied to the streamed frames.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Frames are sent as they are in memory.
+    None,
+    /// Frames are run-length encoded, which works well on flat-coloured interfaces and pixel art.
+    #[default]
+    Rle,
+    /// Frames are encoded as JPEG files, which works well on 3D scenes and photos at the cost of more work for the streaming thread.
+    Jpeg {
+        /// Quality of the encoded frames, between 1 and 100 (see [`jpeg::encode()`]).
+        quality: u8,
+    },
+}
+
+impl Compression {
+    fn id(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Rle => 1,
+            Self::Jpeg { .. } => 2,
+        }
+    }
+}
+
+struct Frame {
+    screen: u8,
+    compression: Compression,
+    format: FramebufferFormat,
+    width: u16,
+    height: u16,
+    data: Vec<u8>,
+}
+
+/// Server streaming the contents of the screens to TCP clients.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::devtools::screen_stream::ScreenStream;
+/// use ctru::prelude::*;
+/// use ctru::services::gfx::{Flush, Swap};
+///
+/// let apt = Apt::new()?;
+/// let gfx = Gfx::new()?;
+/// let soc = Soc::new()?;
+///
+/// let mut stream = ScreenStream::bind(&soc, 5030)?;
+/// stream.set_frame_rate(20);
+///
+/// let mut top_screen = gfx.top_screen.borrow_mut();
+///
+/// while apt.main_loop() {
+///     // Draw the frame...
+///
+///     stream.capture(&mut *top_screen);
+///
+///     top_screen.flush_buffers();
+///     top_screen.swap_buffers();
+///     gfx.wait_for_vblank();
+/// #   break;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct ScreenStream<'soc> {
+    sender: Option<SyncSender<Frame>>,
+    local_addr: SocketAddr,
+    clients: Arc<AtomicUsize>,
+    compression: Compression,
+    interval: Duration,
+    last_capture: [Option<Instant>; 3],
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+    _soc: PhantomData<&'soc Soc>,
+}
+
+impl<'soc> ScreenStream<'soc> {
+    /// Default amount of frames captured per second, for each screen.
+    pub const DEFAULT_FRAME_RATE: u32 = 15;
+
+    /// Start listening for clients on the given port.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the port is already in use or if the streaming thread couldn't be spawned.
+    pub fn bind(_soc: &'soc Soc, port: u16) -> crate::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        // Frames captured while the previous one is still being sent are dropped.
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let clients = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let worker = {
+            let clients = clients.clone();
+            let stop = stop.clone();
+
+            thread::Builder::new()
+                .name(String::from("screen-stream"))
+                .stack_size(0x8000)
+                .spawn(move || stream_frames(listener, &receiver, &clients, &stop))?
+        };
+
+        Ok(Self {
+            sender: Some(sender),
+            local_addr,
+            clients,
+            compression: Compression::default(),
+            interval: Duration::from_secs(1) / Self::DEFAULT_FRAME_RATE,
+            last_capture: [None; 3],
+            stop,
+            worker: Some(worker),
+            _soc: PhantomData,
+        })
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the amount of clients currently connected.
+    pub fn clients(&self) -> usize {
+        self.clients.load(Ordering::Relaxed)
+    }
+
+    /// Set the maximum amount of frames captured per second, for each screen.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `frame_rate` is 0.
+    pub fn set_frame_rate(&mut self, frame_rate: u32) {
+        assert!(frame_rate > 0, "the frame rate must be positive");
+
+        self.interval = Duration::from_secs(1) / frame_rate;
+    }
+
+    /// Set the compression applied to the frames captured from now on. Frames are RLE compressed by default.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Capture the current framebuffer of `screen`, if a client is connected and the frame rate allows it.
+    ///
+    /// Call this after drawing a frame and before swapping the buffers. Returns `true` if the frame was captured.
+    pub fn capture(&mut self, screen: &mut impl Screen) -> bool {
+        if self.clients() == 0 {
+            return false;
+        }
+
+        let screen_id = match (screen.as_raw(), screen.side()) {
+            (ctru_sys::GFX_TOP, Side::Left) => 0,
+            (ctru_sys::GFX_TOP, Side::Right) => 1,
+            _ => 2,
+        };
+
+        let now = Instant::now();
+        let last_capture = &mut self.last_capture[screen_id as usize];
+        if last_capture.is_some_and(|last| now.duration_since(last) < self.interval) {
+            return false;
+        }
+
+        let format = screen.framebuffer_format();
+        let framebuffer = screen.raw_framebuffer();
+        let size = framebuffer.width * framebuffer.height * format.pixel_depth_bytes();
+        // SAFETY: the framebuffer holds `width * height` pixels of the screen's format.
+        let data = unsafe { std::slice::from_raw_parts(framebuffer.ptr, size) }.to_vec();
+
+        let frame = Frame {
+            screen: screen_id,
+            compression: self.compression,
+            format,
+            width: framebuffer.width as u16,
+            height: framebuffer.height as u16,
+            data,
+        };
+
+        let Some(sender) = &self.sender else {
+            return false;
+        };
+
+        match sender.try_send(frame) {
+            Ok(()) => {
+                *last_capture = Some(now);
+                true
+            }
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+impl Drop for ScreenStream<'_> {
+    fn drop(&mut self) {
+        // The streaming thread must end before the `Soc` service it relies on goes away.
+        self.stop.store(true, Ordering::Relaxed);
+        self.sender = None;
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn stream_frames(
+    listener: TcpListener,
+    frames: &Receiver<Frame>,
+    clients: &AtomicUsize,
+    stop: &AtomicBool,
+) {
+    let mut streams: Vec<TcpStream> = Vec::new();
+    let mut packet = Vec::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        let frame = match frames.recv_timeout(POLL_INTERVAL) {
+            Ok(frame) => Some(frame),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        };
+
+        while let Ok((stream, _)) = listener.accept() {
+            if stream.set_nonblocking(false).is_ok() {
+                let _ = stream.set_nodelay(true);
+                streams.push(stream);
+            }
+        }
+
+        if let Some(frame) = frame {
+            encode_frame(&frame, &mut packet);
+
+            // Clients which disconnected are dropped.
+            streams.retain_mut(|stream| stream.write_all(&packet).is_ok());
+        }
+
+        clients.store(streams.len(), Ordering::Relaxed);
+    }
+}
+
+/// Write the header and payload of `frame` to `packet`, replacing its contents.
+fn encode_frame(frame: &Frame, packet: &mut Vec<u8>) {
+    packet.clear();
+    packet.extend_from_slice(FRAME_MAGIC);
+    packet.extend_from_slice(&[frame.screen, frame.format as u8, frame.compression.id(), 0]);
+    packet.extend_from_slice(&frame.width.to_le_bytes());
+    packet.extend_from_slice(&frame.height.to_le_bytes());
+    // The payload size is filled in once the payload is written.
+    packet.extend_from_slice(&[0; 4]);
+
+    match frame.compression {
+        Compression::None => packet.extend_from_slice(&frame.data),
+        Compression::Rle => rle_encode(&frame.data, frame.format.pixel_depth_bytes(), packet),
+        Compression::Jpeg { quality } => {
+            // Screens are never empty nor larger than a JPEG file can be, so encoding can't fail.
+            if let Ok(file) = jpeg::encode(&to_image(frame), quality) {
+                packet.extend_from_slice(&file);
+            }
+        }
+    }
+
+    let payload_size = (packet.len() - 16) as u32;
+    packet[12..16].copy_from_slice(&payload_size.to_le_bytes());
+}
+
+/// Convert a framebuffer to an [`Image`] of the screen the right way up.
+fn to_image(frame: &Frame) -> Image {
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let pixel_size = frame.format.pixel_depth_bytes();
+    let mut data = Vec::with_capacity(width * height * 3);
+
+    // Framebuffers are rotated: each row of the framebuffer is a column of the screen, starting from the bottom.
+    for y in 0..width {
+        for x in 0..height {
+            let offset = (x * width + width - 1 - y) * pixel_size;
+            let pixel = &frame.data[offset..offset + pixel_size];
+            let halfword = || u16::from_le_bytes([pixel[0], pixel[1]]);
+            let scale = |value: u16, bits: u32| ((value as u32 * 255) / ((1 << bits) - 1)) as u8;
+
+            let rgb = match frame.format {
+                FramebufferFormat::Rgba8 => [pixel[3], pixel[2], pixel[1]],
+                FramebufferFormat::Bgr8 => [pixel[2], pixel[1], pixel[0]],
+                FramebufferFormat::Rgb565 => {
+                    let value = halfword();
+                    [
+                        scale(value >> 11, 5),
+                        scale((value >> 5) & 0x3F, 6),
+                        scale(value & 0x1F, 5),
+                    ]
+                }
+                FramebufferFormat::Rgb5A1 => {
+                    let value = halfword();
+                    [
+                        scale(value >> 11, 5),
+                        scale((value >> 6) & 0x1F, 5),
+                        scale((value >> 1) & 0x1F, 5),
+                    ]
+                }
+                FramebufferFormat::Rgba4 => {
+                    let value = halfword();
+                    [
+                        scale(value >> 12, 4),
+                        scale((value >> 8) & 0xF, 4),
+                        scale((value >> 4) & 0xF, 4),
+                    ]
+                }
+            };
+
+            data.extend_from_slice(&rgb);
+        }
+    }
+
+    // The size always matches the data.
+    Image::new(height, width, PixelFormat::Rgb8, data).unwrap()
+}
+
+/// Append the run-length encoding of `data`, made of pixels of `pixel_size` bytes, to `output`.
+fn rle_encode(data: &[u8], pixel_size: usize, output: &mut Vec<u8>) {
+    let pixels: Vec<&[u8]> = data.chunks_exact(pixel_size).collect();
+    let mut start = 0;
+
+    while start < pixels.len() {
+        let run = pixels[start..]
+            .iter()
+            .take(MAX_PACKET_PIXELS)
+            .take_while(|pixel| **pixel == pixels[start])
+            .count();
+
+        if run > 1 {
+            output.push(0x7F + run as u8);
+            output.extend_from_slice(pixels[start]);
+            start += run;
+            continue;
+        }
+
+        // Literal pixels extend until the next run of at least two identical pixels.
+        let mut end = start + 1;
+        while end < pixels.len()
+            && end - start < MAX_PACKET_PIXELS
+            && (end + 1 >= pixels.len() || pixels[end] != pixels[end + 1])
+        {
+            end += 1;
+        }
+
+        output.push((end - start - 1) as u8);
+        for pixel in &pixels[start..end] {
+            output.extend_from_slice(pixel);
+        }
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rle_decode(mut data: &[u8], pixel_size: usize) -> Vec<u8> {
+        let mut output = Vec::new();
+
+        while let [control, rest @ ..] = data {
+            if *control < 0x80 {
+                let length = (*control as usize + 1) * pixel_size;
+                output.extend_from_slice(&rest[..length]);
+                data = &rest[length..];
+            } else {
+                for _ in 0..*control - 0x7F {
+                    output.extend_from_slice(&rest[..pixel_size]);
+                }
+                data = &rest[pixel_size..];
+            }
+        }
+
+        output
+    }
+
+    #[test]
+    fn rle_round_trip() {
+        let mut data = vec![0x10; 3 * 300];
+        data.extend((0..200u32).flat_map(|n| [n as u8, 0, 0xFF]));
+        data.extend_from_slice(&[1, 2, 3, 1, 2, 3, 4, 5, 6]);
+
+        let mut encoded = Vec::new();
+        rle_encode(&data, 3, &mut encoded);
+
+        assert!(encoded.len() < data.len());
+        assert_eq!(rle_decode(&encoded, 3), data);
+
+        let mut frame = Frame {
+            screen: 2,
+            compression: Compression::None,
+            format: FramebufferFormat::Bgr8,
+            width: 240,
+            height: 320,
+            data: data.clone(),
+        };
+        let mut packet = Vec::new();
+        encode_frame(&frame, &mut packet);
+
+        assert_eq!(&packet[..4], FRAME_MAGIC);
+        assert_eq!(
+            u32::from_le_bytes(packet[12..16].try_into().unwrap()),
+            data.len() as u32
+        );
+        assert_eq!(&packet[16..], data);
+
+        // A 2x3 BGR8 framebuffer holds a 3x2 screen, column by column from the bottom.
+        frame.width = 2;
+        frame.height = 3;
+        frame.data = (0..6).flat_map(|n| [0, 0, n]).collect();
+        let image = to_image(&frame);
+        assert_eq!((image.width(), image.height()), (3, 2));
+        assert_eq!(image.pixel(0, 0), Some([1, 0, 0, 0xFF]));
+        assert_eq!(image.pixel(0, 1), Some([0, 0, 0, 0xFF]));
+        assert_eq!(image.pixel(2, 0), Some([5, 0, 0, 0xFF]));
+    }
+}
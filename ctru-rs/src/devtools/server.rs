@@ -0,0 +1,93 @@
+//! TCP server shared by the development tools serving clients on background threads.
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Stack size of the client threads.
+const CLIENT_STACK_SIZE: usize = 0x8000;
+
+/// Listener polled from the main loop, serving each accepted client on its own thread.
+///
+/// Dropping the server asks the client threads to stop and waits for them, since they must end before the
+/// [`Soc`](crate::services::soc::Soc) service goes away.
+pub(super) struct Server {
+    listener: TcpListener,
+    thread_name: &'static str,
+    read_timeout: Duration,
+    stop: Arc<AtomicBool>,
+    clients: Vec<JoinHandle<()>>,
+}
+
+impl Server {
+    /// Start listening on the given port. Client threads are called `thread_name`, and their reads time out after `read_timeout`
+    /// so that they can notice the server shutting down.
+    pub(super) fn bind(
+        port: u16,
+        thread_name: &'static str,
+        read_timeout: Duration,
+    ) -> crate::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            thread_name,
+            read_timeout,
+            stop: Arc::new(AtomicBool::new(false)),
+            clients: Vec::new(),
+        })
+    }
+
+    pub(super) fn local_addr(&self) -> crate::Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept the pending connections without blocking, serving each of them on a new thread with the function returned by `client`.
+    ///
+    /// The function receives the connection (in blocking mode, with the read timeout set) and the flag set when the server shuts down.
+    pub(super) fn accept<F>(&mut self, mut client: impl FnMut() -> F) -> crate::Result<()>
+    where
+        F: FnOnce(TcpStream, &AtomicBool) + Send + 'static,
+    {
+        self.clients.retain(|thread| !thread.is_finished());
+
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+
+            // Clients which can't be configured are dropped, without stopping the server.
+            if stream.set_nonblocking(false).is_err()
+                || stream.set_read_timeout(Some(self.read_timeout)).is_err()
+            {
+                continue;
+            }
+
+            let serve = client();
+            let stop = self.stop.clone();
+
+            let thread = thread::Builder::new()
+                .name(String::from(self.thread_name))
+                .stack_size(CLIENT_STACK_SIZE)
+                .spawn(move || serve(stream, &stop))?;
+
+            self.clients.push(thread);
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        for thread in self.clients.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Fast memory copies using the GPU's DMA engine.
+//!
+//! The GPU can copy data between [LINEAR memory](crate::linear) and VRAM on its own, which is significantly faster than a CPU copy
+//! for large buffers (e.g. framebuffer-sized transfers) and leaves the CPU free to do other work in the meantime.
+//!
+//! # Notes
+//!
+//! Only buffers allocated in LINEAR memory (see [`LinearAllocator`](crate::linear::LinearAllocator)) or VRAM can be used with this module.
+//! Additionally, the addresses of both buffers must be aligned to [`ADDRESS_ALIGNMENT`] bytes and their length must be a multiple of [`LENGTH_ALIGNMENT`] bytes.
+#![doc(alias = "TextureCopy")]
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::error::ResultCode;
+use crate::services::gfx::Gfx;
+use crate::services::gspgpu::{self, Event};
+
+/// Required alignment (in bytes) of the addresses of the buffers used in a DMA copy.
+pub const ADDRESS_ALIGNMENT: usize = 8;
+
+/// The length (in bytes) of the buffers used in a DMA copy must be a multiple of this value.
+pub const LENGTH_ALIGNMENT: usize = 16;
+
+/// Flag requesting a plain copy, without any of the tiling or format conversions supported by the transfer engine.
+const TRANSFER_RAW_COPY: u32 = 1 << 3;
+
+/// Error returned by an unsuccessful DMA copy.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Error {
+    /// One of the buffers is not located in LINEAR memory or VRAM.
+    InaccessibleMemory,
+    /// One of the buffers doesn't respect the alignment requirements (see [`ADDRESS_ALIGNMENT`] and [`LENGTH_ALIGNMENT`]).
+    Misaligned,
+    /// The source and destination buffers have different lengths.
+    LengthMismatch,
+    /// The GPU refused the copy.
+    Failed(crate::Error),
+}
+
+/// A running DMA copy, started with [`start()`].
+///
+/// The buffers involved in the copy stay borrowed until the transfer is over.
+/// Dropping the [`Transfer`] blocks until the copy is complete.
+#[must_use]
+pub struct Transfer<'a> {
+    dst: *mut u8,
+    len: usize,
+    _buffers: PhantomData<(&'a [u8], &'a mut [u8])>,
+    _gfx: PhantomData<&'a Gfx>,
+}
+
+/// Copy `src` into `dst` using the DMA engine, blocking until the copy is complete.
+///
+/// # Errors
+///
+/// This function will return an error if the buffers are not DMA-accessible, don't respect the alignment requirements or have different lengths.
+///
+/// # Example
+///
+/// ```
+/// # #![feature(allocator_api)]
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _runner = test_runner::GdbRunner::default();
+/// #
+/// use ctru::dma;
+/// use ctru::linear::LinearAllocator;
+/// use ctru::services::gfx::Gfx;
+///
+/// let gfx = Gfx::new()?;
+///
+/// let src = Box::new_in([0xAB; 0x1000], LinearAllocator);
+/// let mut dst = Box::new_in([0; 0x1000], LinearAllocator);
+///
+/// dma::copy(&gfx, &*src, &mut *dst)?;
+///
+/// assert_eq!(src, dst);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub fn copy(gfx: &Gfx, src: &[u8], dst: &mut [u8]) -> Result<(), Error> {
+    // SAFETY: the transfer is waited for before the buffers are released.
+    unsafe { start(gfx, src, dst) }?.wait();
+
+    Ok(())
+}
+
+/// Start copying `src` into `dst` using the DMA engine, without waiting for the copy to complete.
+///
+/// # Errors
+///
+/// See [`copy()`].
+///
+/// # Safety
+///
+/// The returned [`Transfer`] must be dropped (or [waited for](Transfer::wait)) before `src` and `dst` are used, moved or freed again.
+/// The borrows of the buffers end if the transfer is leaked (e.g. with [`std::mem::forget()`]), while the GPU keeps reading and writing them.
+#[doc(alias = "GX_TextureCopy")]
+pub unsafe fn start<'a>(
+    _gfx: &'a Gfx,
+    src: &'a [u8],
+    dst: &'a mut [u8],
+) -> Result<Transfer<'a>, Error> {
+    if src.len() != dst.len() {
+        return Err(Error::LengthMismatch);
+    }
+
+    check_buffer(src)?;
+    check_buffer(dst)?;
+
+    if src.len() % LENGTH_ALIGNMENT != 0 {
+        return Err(Error::Misaligned);
+    }
+
+    unsafe { submit(src, dst) }.map_err(Error::Failed)?;
+
+    Ok(Transfer {
+        dst: dst.as_mut_ptr(),
+        len: dst.len(),
+        _buffers: PhantomData,
+        _gfx: PhantomData,
+    })
+}
+
+unsafe fn submit(src: &[u8], dst: &mut [u8]) -> crate::Result<()> {
+    let len = src.len() as u32;
+
+    // The source data must reach the physical memory before the GPU reads it,
+    // and no dirty cache line must be left to overwrite the destination once the copy is done.
+    ResultCode(ctru_sys::GSPGPU_FlushDataCache(src.as_ptr().cast(), len))?;
+    ResultCode(ctru_sys::GSPGPU_FlushDataCache(dst.as_ptr().cast(), len))?;
+
+    ResultCode(ctru_sys::GX_TextureCopy(
+        src.as_ptr().cast_mut().cast(),
+        0,
+        dst.as_mut_ptr().cast(),
+        0,
+        len,
+        TRANSFER_RAW_COPY,
+    ))?;
+
+    Ok(())
+}
+
+impl Transfer<'_> {
+    /// Block until the copy is complete.
+    pub fn wait(self) {
+        // The actual wait happens on drop.
+    }
+}
+
+impl Drop for Transfer<'_> {
+    fn drop(&mut self) {
+        gspgpu::wait_for_event(Event::PPF, false);
+
+        // Make sure the CPU doesn't read stale data from its cache.
+        let _ = unsafe { ctru_sys::GSPGPU_InvalidateDataCache(self.dst.cast(), self.len as u32) };
+    }
+}
+
+/// Check that the whole buffer is located in a single region accessible by the GPU (LINEAR memory or VRAM),
+/// and that its address is aligned to [`ADDRESS_ALIGNMENT`] bytes.
+pub(crate) fn check_buffer(buffer: &[u8]) -> Result<(), Error> {
+    if !is_dma_accessible(buffer) {
+        return Err(Error::InaccessibleMemory);
+    }
+
+    if buffer.as_ptr() as usize % ADDRESS_ALIGNMENT != 0 {
+        return Err(Error::Misaligned);
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if the buffer is located in a region accessible by the GPU (LINEAR memory or VRAM).
+fn is_dma_accessible(buffer: &[u8]) -> bool {
+    let Some(last) = buffer.len().checked_sub(1) else {
+        return unsafe { ctru_sys::osConvertVirtToPhys(buffer.as_ptr().cast()) != 0 };
+    };
+
+    let (start, end) = unsafe {
+        (
+            ctru_sys::osConvertVirtToPhys(buffer.as_ptr().cast()),
+            ctru_sys::osConvertVirtToPhys(buffer.as_ptr().add(last).cast()),
+        )
+    };
+
+    // Both regions are physically contiguous, so a buffer within one of them ends exactly `last` bytes after its start.
+    start != 0 && end != 0 && end.checked_sub(start) == Some(last as u32)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InaccessibleMemory => {
+                write!(f, "buffer is not located in LINEAR memory or VRAM")
+            }
+            Self::Misaligned => write!(f, "buffer doesn't respect the DMA alignment requirements"),
+            Self::LengthMismatch => {
+                write!(f, "source and destination buffers have different lengths")
+            }
+            Self::Failed(e) => write!(f, "DMA copy failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
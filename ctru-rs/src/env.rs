@@ -0,0 +1,47 @@
+//! Launch environment.
+//!
+//! Applications can be started either as a 3DSX file by a homebrew loader (such as the Homebrew Launcher),
+//! or as a title installed from a CIA file. The two environments offer very different capabilities:
+//! 3DSX applications run with the loader's service access rights and memory layout, and can't rely on services
+//! like AM which installed titles typically declare in their exheader.
+//!
+//! [`launch_mode()`] lets applications detect the current environment and adapt to it.
+#![doc(alias = "envIsHomebrew")]
+
+/// How the application was launched.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LaunchMode {
+    /// The application runs as a 3DSX file, started by a homebrew loader providing the `hb:ldr` services.
+    Homebrew,
+    /// The application runs as an installed title (e.g. installed from a CIA file).
+    Title,
+}
+
+impl LaunchMode {
+    /// Returns `true` if the application runs as a 3DSX file.
+    pub fn is_homebrew(self) -> bool {
+        self == Self::Homebrew
+    }
+}
+
+/// Returns how the application was launched.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::env::{launch_mode, LaunchMode};
+///
+/// match launch_mode() {
+///     LaunchMode::Homebrew => println!("Running from the Homebrew Launcher"),
+///     LaunchMode::Title => println!("Running as an installed title"),
+/// }
+/// ```
+#[doc(alias = "envIsHomebrew")]
+pub fn launch_mode() -> LaunchMode {
+    if unsafe { ctru_sys::envIsHomebrew() } {
+        LaunchMode::Homebrew
+    } else {
+        LaunchMode::Title
+    }
+}
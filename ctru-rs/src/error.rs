@@ -83,6 +83,8 @@ pub enum Error {
     Os(ctru_sys::Result),
     /// Generic [`libc`] errors.
     Libc(String),
+    /// Errors returned by [`std::io`] operations on the console's filesystems.
+    Io(std::io::Error),
     /// Requested service is already active and cannot be activated again.
     ServiceAlreadyActive,
     /// `stdout` is already being redirected.
@@ -94,6 +96,10 @@ pub enum Error {
         /// Size of the requested data (in bytes).
         wanted: usize,
     },
+    /// The storage media holding the data (i.e. the SD card) was removed while in use.
+    MediaRemoved,
+    /// The operation was cancelled before it could complete (see [`Progress::is_cancelled()`](crate::progress::Progress::is_cancelled)).
+    Cancelled,
     /// An error that doesn't fit into the other categories.
     Other(String),
 }
@@ -137,6 +143,12 @@ impl From<ResultCode> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -149,6 +161,7 @@ impl fmt::Debug for Error {
                 .field("description", &result_code_description_str(err))
                 .finish(),
             Self::Libc(err) => f.debug_tuple("Libc").field(err).finish(),
+            Self::Io(err) => f.debug_tuple("Io").field(err).finish(),
             Self::ServiceAlreadyActive => f.debug_tuple("ServiceAlreadyActive").finish(),
             Self::OutputAlreadyRedirected => f.debug_tuple("OutputAlreadyRedirected").finish(),
             Self::BufferTooShort { provided, wanted } => f
@@ -156,6 +169,8 @@ impl fmt::Debug for Error {
                 .field("provided", provided)
                 .field("wanted", wanted)
                 .finish(),
+            Self::MediaRemoved => f.debug_tuple("MediaRemoved").finish(),
+            Self::Cancelled => f.debug_tuple("Cancelled").finish(),
             Self::Other(err) => f.debug_tuple("Other").field(err).finish(),
         }
     }
@@ -175,11 +190,14 @@ impl fmt::Display for Error {
                 result_code_description_str(err)
             ),
             Self::Libc(err) => write!(f, "{err}"),
+            Self::Io(err) => write!(f, "{err}"),
             Self::ServiceAlreadyActive => write!(f, "service already active"),
             Self::OutputAlreadyRedirected => {
                 write!(f, "output streams are already redirected to 3dslink")
             }
             Self::BufferTooShort{provided, wanted} => write!(f, "the provided buffer's length is too short (length = {provided}) to hold the wanted data (size = {wanted})"),
+            Self::MediaRemoved => write!(f, "the SD card was removed"),
+            Self::Cancelled => write!(f, "the operation was cancelled"),
             Self::Other(err) => write!(f, "{err}"),
         }
     }
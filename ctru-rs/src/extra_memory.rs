@@ -0,0 +1,207 @@
+//! Allocator for the extra memory of the New 3DS.
+//!
+//! Titles running on a New 3DS in extended memory mode get a bigger APPLICATION memory region than the 124 MiB
+//! of the New 3DS' default mode. [`ExtraMemAllocator`] maps pages of this region on demand, e.g. for big capture or scratch buffers,
+//! and gives them back to the system once freed.
+//!
+//! # Notes
+//!
+//! At startup, `libctru` hands all the free APPLICATION memory to the main heap. Applications using this allocator
+//! must leave some memory out by choosing a smaller heap size, which is done by defining the `__ctru_heap_size` symbol:
+//!
+//! ```no_run
+//! // 64 MiB for the main heap. The LINEAR heap keeps its default size, and the rest is left to `ExtraMemAllocator`.
+//! #[no_mangle]
+//! static __ctru_heap_size: u32 = 64 << 20;
+//! ```
+//!
+//! # Additional Resources
+//!
+//! - <https://www.3dbrew.org/wiki/Memory_layout>
+//! - <https://www.3dbrew.org/wiki/SVC#Memory_Mapping>
+
+use std::alloc::{AllocError, Allocator, Layout};
+use std::ops::Range;
+use std::ptr::NonNull;
+use std::sync::Mutex;
+
+use crate::os::MemRegion;
+
+/// Size in bytes of the pages mapped by the allocator.
+pub const PAGE_SIZE: usize = 0x1000;
+
+/// Size of the APPLICATION memory region in the New 3DS' default memory mode.
+const DEFAULT_APPLICATION_SIZE: usize = 124 << 20;
+
+/// End of the virtual address range of the heap.
+const HEAP_AREA_END: usize = 0x1000_0000;
+
+extern "C" {
+    static __ctru_heap: u32;
+    static __ctru_heap_size: u32;
+}
+
+/// Virtual address ranges currently mapped by the allocator, sorted by address.
+static MAPPINGS: Mutex<Vec<Range<usize>>> = Mutex::new(Vec::new());
+
+/// [`Allocator`] struct for the extra memory of the New 3DS.
+///
+/// To use this struct the main crate must activate the `allocator_api` unstable feature.
+///
+/// # Notes
+///
+/// Allocations are rounded up to whole pages of [`PAGE_SIZE`] bytes, so this allocator is meant for few big buffers:
+/// small values are better off on the main heap.
+/// Allocations fail unless [`ExtraMemAllocator::is_available()`] returns `true`.
+///
+/// # Example
+///
+/// ```
+/// # #![feature(allocator_api)]
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::extra_memory::ExtraMemAllocator;
+///
+/// if ExtraMemAllocator::is_available() {
+///     // Room for 60 frames of the top screen.
+///     let mut captures = Vec::with_capacity_in(60 * 400 * 240 * 3, ExtraMemAllocator);
+///     captures.resize(60 * 400 * 240 * 3, 0u8);
+/// }
+/// ```
+#[derive(Copy, Clone, Default, Debug)]
+pub struct ExtraMemAllocator;
+
+impl ExtraMemAllocator {
+    /// Returns whether the application runs on a New 3DS in extended memory mode.
+    #[doc(alias = "APT_CheckNew3DS")]
+    pub fn is_available() -> bool {
+        let mut is_new_3ds = false;
+        let _ = unsafe { ctru_sys::APT_CheckNew3DS(&mut is_new_3ds) };
+
+        is_new_3ds && MemRegion::Application.size() > DEFAULT_APPLICATION_SIZE
+    }
+
+    /// Returns the amount of memory (in bytes) left for the allocator, or 0 if it isn't [available](ExtraMemAllocator::is_available).
+    #[doc(alias = "osGetMemRegionFree")]
+    pub fn free_space() -> usize {
+        if Self::is_available() {
+            MemRegion::Application.free() & !(PAGE_SIZE - 1)
+        } else {
+            0
+        }
+    }
+}
+
+unsafe impl Allocator for ExtraMemAllocator {
+    #[doc(alias = "svcControlMemory", alias = "MEMOP_ALLOC")]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if !Self::is_available() {
+            return Err(AllocError);
+        }
+
+        let size = layout
+            .size()
+            .max(1)
+            .checked_next_multiple_of(PAGE_SIZE)
+            .ok_or(AllocError)?;
+        let align = layout.align().max(PAGE_SIZE);
+
+        let heap_end = unsafe { (__ctru_heap + __ctru_heap_size) as usize };
+
+        let mut mappings = MAPPINGS.lock().unwrap();
+        let address =
+            find_gap(&mappings, heap_end..HEAP_AREA_END, size, align).ok_or(AllocError)?;
+
+        let mut mapped = 0;
+        let result = unsafe {
+            ctru_sys::svcControlMemory(
+                &mut mapped,
+                address as u32,
+                0,
+                size as u32,
+                ctru_sys::MEMOP_ALLOC,
+                ctru_sys::MEMPERM_READ | ctru_sys::MEMPERM_WRITE,
+            )
+        };
+        if ctru_sys::R_FAILED(result) {
+            return Err(AllocError);
+        }
+
+        let index = mappings.partition_point(|range| range.start < address);
+        mappings.insert(index, address..address + size);
+
+        let ptr = NonNull::new(mapped as *mut u8).ok_or(AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, size))
+    }
+
+    #[doc(alias = "svcControlMemory", alias = "MEMOP_FREE")]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _layout: Layout) {
+        let address = ptr.as_ptr() as usize;
+
+        let mut mappings = MAPPINGS.lock().unwrap();
+        let Some(index) = mappings.iter().position(|range| range.start == address) else {
+            return;
+        };
+        let range = mappings[index].clone();
+
+        let mut unmapped = 0;
+        let result = ctru_sys::svcControlMemory(
+            &mut unmapped,
+            range.start as u32,
+            0,
+            range.len() as u32,
+            ctru_sys::MEMOP_FREE,
+            0,
+        );
+
+        // Pages which couldn't be freed are still mapped, so the range must not be handed out again.
+        if ctru_sys::R_SUCCEEDED(result) {
+            mappings.remove(index);
+        }
+    }
+}
+
+/// Returns the lowest address within `area` aligned to `align` where `size` bytes don't overlap any of the sorted `mappings`.
+fn find_gap(
+    mappings: &[Range<usize>],
+    area: Range<usize>,
+    size: usize,
+    align: usize,
+) -> Option<usize> {
+    let mut candidate = area.start.checked_next_multiple_of(align)?;
+
+    for range in mappings {
+        if candidate.checked_add(size)? <= range.start {
+            break;
+        }
+
+        candidate = candidate.max(range.end.checked_next_multiple_of(align)?);
+    }
+
+    (candidate.checked_add(size)? <= area.end).then_some(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps_between_mappings() {
+        let area = 0x1000..0x10000;
+        let mappings = [0x1000..0x3000, 0x4000..0x5000];
+
+        assert_eq!(find_gap(&[], area.clone(), 0x2000, 0x1000), Some(0x1000));
+        assert_eq!(
+            find_gap(&mappings, area.clone(), 0x1000, 0x1000),
+            Some(0x3000)
+        );
+        assert_eq!(
+            find_gap(&mappings, area.clone(), 0x2000, 0x1000),
+            Some(0x5000)
+        );
+        assert_eq!(
+            find_gap(&mappings, area.clone(), 0x1000, 0x4000),
+            Some(0x8000)
+        );
+        assert_eq!(find_gap(&mappings, area, 0x10000, 0x1000), None);
+    }
+}
@@ -0,0 +1,345 @@
+//! CPU fault capture.
+//!
+//! When a thread triggers a CPU exception (e.g. by dereferencing an invalid pointer), the kernel can run a user-mode handler
+//! registered in the thread's local storage instead of terminating the application straight away.
+//! [`install()`] registers such a handler, which writes a crash report to the SD card containing the exception type,
+//! the faulting thread, its registers and the code addresses found on its stack, and then hands the crash over
+//! to the system (or Luma3DS' crash screen).
+//! Addresses are annotated with function names when a symbol map is installed (see [`backtrace`](crate::backtrace)).
+//!
+//! The faulting thread may have been interrupted while holding the allocator's or any other lock, so the handler neither allocates
+//! nor locks anything: it formats the report into a static buffer and writes it with a single call to a file opened in advance
+//! by [`install()`]. That file is renamed after the time of the crash by the next call to [`install()`], usually at the next launch.
+//!
+//! # Notes
+//!
+//! Handlers are registered per thread: call [`install_current_thread()`] at the start of every thread that should be covered.
+//! Reports only contain the IDs of the faulting threads, since looking up their names requires a lock.
+//! Rust panics are not CPU exceptions and don't go through the handler (see [`install_panic_hook()`](crate::shutdown::install_panic_hook)
+//! to handle those instead).
+#![doc(alias = "exception")]
+#![doc(alias = "crash")]
+
+use std::fmt;
+use std::fs::File;
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Size (in bytes) of the stack used by the fault handler.
+const HANDLER_STACK_SIZE: usize = 0x8000;
+
+/// Amount of stack words inspected when looking for code addresses.
+const STACK_SCAN_WORDS: usize = 256;
+
+/// Maximum amount of code addresses included in a report.
+const MAX_STACK_ADDRESSES: usize = 32;
+
+/// Virtual address range where application code is mapped.
+const CODE_RANGE: std::ops::Range<u32> = 0x0010_0000..0x0800_0000;
+
+/// Maximum size (in bytes) of a crash report. Longer reports are truncated.
+const REPORT_SIZE: usize = 0x2000;
+
+/// Name of the file the handler writes its report to, until the next call to [`install()`] renames it.
+const PENDING_REPORT: &str = "crash-pending.txt";
+
+/// Directory where crash reports are written, set by the first call to [`install()`].
+static REPORT_DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+
+/// File opened by the first call to [`install()`], which the handler writes its report to.
+static REPORT_FILE: OnceLock<File> = OnceLock::new();
+
+/// Stack shared by all fault handlers. Faults on multiple threads at once are fatal anyway.
+#[repr(C, align(8))]
+struct HandlerStack([u8; HANDLER_STACK_SIZE]);
+
+static mut HANDLER_STACK: HandlerStack = HandlerStack([0; HANDLER_STACK_SIZE]);
+
+/// Buffer the handler formats its report into, truncating it if needed.
+struct ReportBuffer {
+    bytes: [u8; REPORT_SIZE],
+    len: usize,
+}
+
+impl fmt::Write for ReportBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let count = s.len().min(REPORT_SIZE - self.len);
+        self.bytes[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+
+        if count == s.len() {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
+static mut REPORT: ReportBuffer = ReportBuffer {
+    bytes: [0; REPORT_SIZE],
+    len: 0,
+};
+
+/// Type of CPU exception.
+#[doc(alias = "ERRF_ExceptionType")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FaultKind {
+    /// The CPU tried to execute code from an invalid address.
+    PrefetchAbort,
+    /// The CPU tried to read or write an invalid address.
+    DataAbort,
+    /// The CPU tried to execute an undefined instruction.
+    UndefinedInstruction,
+    /// A floating point operation raised an exception.
+    Vfp,
+    /// Any other exception type.
+    Other(u32),
+}
+
+/// State of the CPU when a fault occurred.
+#[derive(Clone, Debug)]
+pub struct FaultInfo {
+    /// Type of the exception.
+    pub kind: FaultKind,
+    /// Kernel ID of the faulting thread.
+    pub thread_id: Option<u32>,
+    /// General purpose registers `r0` to `r12`.
+    pub registers: [u32; 13],
+    /// Stack pointer.
+    pub sp: u32,
+    /// Link register.
+    pub lr: u32,
+    /// Program counter of the faulting instruction.
+    pub pc: u32,
+    /// Current program status register.
+    pub cpsr: u32,
+    /// Fault status register (data and prefetch aborts only).
+    pub fault_status: u32,
+    /// Faulting data address (data aborts only).
+    pub fault_address: u32,
+    stack: [u32; MAX_STACK_ADDRESSES],
+    stack_len: usize,
+}
+
+/// Register the fault handler for the current thread, writing crash reports to `directory` (e.g. `sdmc:/3ds/my-app/crashes`).
+///
+/// The first call also renames the report left by a previous crash (if any) to `crash-<timestamp>.txt`,
+/// and opens the file the handler writes its report to. Later calls only register the handler.
+///
+/// # Errors
+///
+/// This function will return an error if the directory doesn't exist and couldn't be created,
+/// or if the report file couldn't be renamed or opened.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// ctru::fault::install("sdmc:/3ds/my-app/crashes")?;
+///
+/// std::thread::spawn(|| {
+///     ctru::fault::install_current_thread();
+///
+///     // ...
+/// });
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "threadOnException")]
+pub fn install(directory: impl Into<PathBuf>) -> crate::Result<()> {
+    let directory = REPORT_DIRECTORY.get_or_init(|| directory.into());
+
+    if REPORT_FILE.get().is_none() {
+        std::fs::create_dir_all(directory)?;
+
+        let pending = directory.join(PENDING_REPORT);
+
+        if std::fs::metadata(&pending).is_ok_and(|metadata| metadata.len() > 0) {
+            // The file was last written by the handler, at the time of the crash.
+            let timestamp = crate::services::fs::modification_time(&pending).unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.as_secs())
+                    .unwrap_or_default()
+            });
+
+            std::fs::rename(&pending, directory.join(format!("crash-{timestamp}.txt")))?;
+        }
+
+        let _ = REPORT_FILE.set(File::create(&pending)?);
+    }
+
+    install_current_thread();
+
+    Ok(())
+}
+
+/// Register the fault handler for the current thread, using the directory set by [`install()`].
+///
+/// If [`install()`] was never called (or failed), crashes are still captured, but no report is written.
+#[doc(alias = "threadOnException")]
+pub fn install_current_thread() {
+    unsafe {
+        let stack = std::ptr::addr_of_mut!(HANDLER_STACK).cast::<u8>();
+
+        ctru_sys::threadOnException(
+            Some(handler),
+            stack.add(HANDLER_STACK_SIZE).cast(),
+            // Let the kernel write the exception data on the handler's stack.
+            std::ptr::null_mut(),
+        );
+    }
+}
+
+unsafe extern "C" fn handler(
+    info: *mut ctru_sys::ERRF_ExceptionInfo,
+    regs: *mut ctru_sys::CpuRegisters,
+) {
+    let info = &*info;
+    let regs = &*regs;
+
+    let kind = match info.type_ as u32 {
+        ctru_sys::ERRF_EXCEPTION_PREFETCH_ABORT => FaultKind::PrefetchAbort,
+        ctru_sys::ERRF_EXCEPTION_DATA_ABORT => FaultKind::DataAbort,
+        ctru_sys::ERRF_EXCEPTION_UNDEFINED => FaultKind::UndefinedInstruction,
+        ctru_sys::ERRF_EXCEPTION_VFP => FaultKind::Vfp,
+        other => FaultKind::Other(other),
+    };
+
+    let mut fault = FaultInfo {
+        kind,
+        thread_id: crate::debug::current_thread_id().ok(),
+        registers: regs.r,
+        sp: regs.sp,
+        lr: regs.lr,
+        pc: regs.pc,
+        cpsr: regs.cpsr,
+        fault_status: info.fsr,
+        fault_address: info.far,
+        stack: [0; MAX_STACK_ADDRESSES],
+        stack_len: 0,
+    };
+    fault.stack_len = scan_stack(regs.sp, &mut fault.stack);
+
+    if let Some(mut file) = REPORT_FILE.get() {
+        let report = &mut *std::ptr::addr_of_mut!(REPORT);
+        report.len = 0;
+
+        // A truncated report is still worth writing.
+        let _ = fmt::Write::write_fmt(report, format_args!("{fault}"));
+        let _ = file.write_all(&report.bytes[..report.len]);
+    }
+
+    // The handler can't resume the faulting thread: let the system handle the crash.
+    ctru_sys::svcBreak(ctru_sys::USERBREAK_PANIC);
+}
+
+/// Collect the stack words which look like code addresses into `addresses`, returning how many were found.
+///
+/// # Safety
+///
+/// `sp` must be the stack pointer of a thread whose stack is still mapped.
+unsafe fn scan_stack(sp: u32, addresses: &mut [u32; MAX_STACK_ADDRESSES]) -> usize {
+    // Don't read past the end of the stack's memory region, since the next page may be unmapped.
+    let Ok(region) = crate::services::svc::query_memory(sp) else {
+        return 0;
+    };
+    // A corrupted stack pointer may not even point into the queried region.
+    let Some(remaining) = region.end().checked_sub(sp) else {
+        return 0;
+    };
+
+    let words = ((remaining / 4) as usize).min(STACK_SCAN_WORDS);
+    let stack = sp as *const u32;
+    let mut count = 0;
+
+    for i in 0..words {
+        let value = stack.add(i).read_volatile();
+
+        // Return addresses point to ARM or Thumb code, right after a call instruction.
+        if CODE_RANGE.contains(&value) {
+            addresses[count] = value;
+            count += 1;
+
+            if count == MAX_STACK_ADDRESSES {
+                break;
+            }
+        }
+    }
+
+    count
+}
+
+impl FaultInfo {
+    /// Returns the values found on the stack which point to application code, most recent first.
+    ///
+    /// Without frame pointers, these are only likely return addresses: some of them may be stale values.
+    pub fn stack_addresses(&self) -> &[u32] {
+        &self.stack[..self.stack_len]
+    }
+}
+
+impl fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PrefetchAbort => write!(f, "prefetch abort"),
+            Self::DataAbort => write!(f, "data abort"),
+            Self::UndefinedInstruction => write!(f, "undefined instruction"),
+            Self::Vfp => write!(f, "VFP exception"),
+            Self::Other(kind) => write!(f, "unknown exception {kind}"),
+        }
+    }
+}
+
+impl fmt::Display for FaultInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Exception: {}", self.kind)?;
+
+        match self.thread_id {
+            Some(id) => writeln!(f, "Thread: {id}")?,
+            None => writeln!(f, "Thread: unknown")?,
+        }
+
+        if self.kind == FaultKind::DataAbort {
+            writeln!(f, "Address: {:08X}", self.fault_address)?;
+        }
+        if matches!(self.kind, FaultKind::DataAbort | FaultKind::PrefetchAbort) {
+            writeln!(f, "FSR: {:08X}", self.fault_status)?;
+        }
+
+        writeln!(f)?;
+
+        for (index, value) in self.registers.iter().enumerate() {
+            write!(f, "r{index:<2} {value:08X}")?;
+
+            if index % 4 == 3 {
+                writeln!(f)?;
+            } else {
+                write!(f, "  ")?;
+            }
+        }
+        writeln!(f, "sp  {:08X}  lr  {:08X}", self.sp, self.lr)?;
+        writeln!(f, "pc  {:08X}  cpsr {:08X}", self.pc, self.cpsr)?;
+
+        write!(f, "\npc: ")?;
+        crate::backtrace::write_address(f, self.pc)?;
+        write!(f, "\nlr: ")?;
+        crate::backtrace::write_address(f, self.lr)?;
+        writeln!(f)?;
+
+        writeln!(f, "\nStack:")?;
+        for address in self.stack_addresses() {
+            write!(f, "  ")?;
+            crate::backtrace::write_address(f, *address)?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+//! System shared font.
+//!
+//! Every console has a font shared by the system and the applications, which covers the characters of its system languages
+//! (see [`glyph`](crate::glyph) for its button icons). Text typed by the user (e.g. with the [software keyboard](crate::applets::swkbd))
+//! may contain characters missing from it, which applications drawing their text with the shared font can't display.
+#![doc(alias = "fontEnsureMapped")]
+
+/// Returns whether the system's shared font can draw `c`.
+///
+/// Control characters (such as the line breaks of multiline input) are never drawn, so they are always accepted.
+/// If the shared font can't be mapped, every character is accepted.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::font;
+///
+/// assert!(font::has_glyph('A'));
+/// assert!(font::has_glyph(ctru::glyph::A));
+/// ```
+#[doc(alias = "fontGlyphIndexFromCodePoint")]
+pub fn has_glyph(c: char) -> bool {
+    if c.is_control() {
+        return true;
+    }
+
+    unsafe {
+        if ctru_sys::R_FAILED(ctru_sys::fontEnsureMapped()) {
+            return true;
+        }
+
+        let font = ctru_sys::fontGetSystemFont();
+        if font.is_null() {
+            return true;
+        }
+
+        // Characters missing from the font are mapped to its replacement glyph,
+        // which is the question mark in the system fonts.
+        let replacement = (*ctru_sys::fontGetInfo(font)).alterCharIndex;
+        let index = ctru_sys::fontGlyphIndexFromCodePoint(font, c as u32);
+
+        index != i32::from(replacement) || c == '?'
+    }
+}
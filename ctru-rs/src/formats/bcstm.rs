@@ -0,0 +1,561 @@
+//! BCSTM streams.
+//!
+//! BCSTM is the format of the music tracks and long voice lines of most titles. Unlike [BCWAV](super::bcwav) sounds, streams
+//! are too big to be decoded at once: [`BcstmStream`] reads and decodes them block by block from any [`Read`] + [`Seek`] source
+//! (usually a file of the [RomFS](crate::services::romfs)), and jumps back to the loop start when reaching the end of looping tracks.
+//!
+//! The decoded 16-bit PCM samples are meant to refill a rotation of [`Wave`](crate::services::ndsp::wave::Wave)s as NDSP plays them,
+//! such as an [`AudioStream`](crate::services::ndsp::stream::AudioStream).
+//!
+//! # Additional Resources
+//!
+//! - <https://www.3dbrew.org/wiki/BCSTM>
+#![doc(alias = "cstm")]
+
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+pub use super::nw4c::Encoding;
+use super::nw4c::{self, AdpcmDecoder};
+use super::{expect_magic, Endian};
+use crate::services::ndsp::{AudioFormat, Channel};
+use crate::Error;
+
+/// Size of the file header, with room for its block references.
+const HEADER_SIZE: usize = 0x40;
+/// Type of the INFO block reference.
+const INFO_BLOCK: u16 = 0x4000;
+/// Type of the SEEK block reference.
+const SEEK_BLOCK: u16 = 0x4001;
+/// Type of the DATA block reference.
+const DATA_BLOCK: u16 = 0x4002;
+/// Type of the stream information reference.
+const STREAM_INFO: u16 = 0x4100;
+/// Type of the references to tables of references.
+const REFERENCE_TABLE: u16 = 0x0101;
+/// Type of the channel information references.
+const CHANNEL_INFO: u16 = 0x4102;
+/// Type of the sample data reference.
+const SAMPLE_DATA: u16 = 0x1F00;
+/// Type of the DSP-ADPCM information references.
+const DSP_ADPCM_INFO: u16 = 0x0300;
+
+/// Streaming decoder of a BCSTM file.
+///
+/// # Example
+///
+/// ```
+/// # #![feature(allocator_api)]
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use std::fs::File;
+///
+/// use ctru::formats::bcstm::BcstmStream;
+/// use ctru::linear::LinearAllocator;
+/// use ctru::services::ndsp::{wave::Wave, Ndsp};
+///
+/// let ndsp = Ndsp::new()?;
+/// let mut channel = ndsp.channel(0)?;
+///
+/// let mut stream = BcstmStream::new(File::open("romfs:/music.bcstm")?)?;
+/// stream.configure(&mut channel);
+///
+/// // Decode the first quarter of a second of music.
+/// let mut buffer = Box::new_in([0u8; 8192 * 4], LinearAllocator);
+/// let len = stream.read_bytes(&mut buffer[..])?;
+///
+/// let mut wave = Wave::new(buffer, stream.audio_format().unwrap(), false);
+/// channel.queue_wave(&mut wave)?;
+/// #
+/// # let _ = len;
+/// # Ok(())
+/// # }
+/// ```
+pub struct BcstmStream<R> {
+    reader: R,
+    /// Length of the source, which bounds every read made from the header's sizes.
+    stream_len: u64,
+    endian: Endian,
+    encoding: Encoding,
+    sample_rate: u32,
+    channel_count: usize,
+    loop_start: Option<usize>,
+    frame_count: usize,
+    /// Offset of the encoded samples in the file.
+    data_offset: usize,
+    block_size: usize,
+    block_frames: usize,
+    last_block_size: usize,
+    last_block_padded_size: usize,
+    /// History samples at the start of every block, for every channel (if the file has a SEEK block).
+    seek_table: Vec<u8>,
+    decoders: Vec<AdpcmDecoder>,
+    /// Index of the next block to decode.
+    next_block: usize,
+    /// Interleaved samples of the current block, and amount of them already read.
+    pending: Vec<i16>,
+    pending_read: usize,
+    /// Per-channel decoding buffers, kept to avoid reallocating them for every block.
+    scratch: Vec<Vec<i16>>,
+}
+
+impl<R: Read + Seek> BcstmStream<R> {
+    /// Read the header of the stream from `reader`, which must hold a BCSTM file starting at offset 0 (its current position doesn't matter).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `reader` doesn't hold a valid BCSTM file, if reading it fails,
+    /// or if the stream is encoded with IMA-ADPCM.
+    pub fn new(mut reader: R) -> crate::Result<Self> {
+        let stream_len = reader.seek(SeekFrom::End(0))?;
+        let header = read_at(&mut reader, stream_len, 0, HEADER_SIZE)?;
+        expect_magic(&header, 0, b"CSTM")?;
+        let endian = Endian::from_bom(&header, 4)?;
+
+        let (info_offset, info_size) = nw4c::block(endian, &header, INFO_BLOCK)?;
+        let (data_block, _) = nw4c::block(endian, &header, DATA_BLOCK)?;
+        let info = read_at(&mut reader, stream_len, info_offset, info_size)?;
+        expect_magic(&info, 0, b"INFO")?;
+
+        let stream = 8 + nw4c::expect_reference(endian, &info, 8, STREAM_INFO)?;
+        let byte = |offset| super::bytes(&info, stream + offset, 1).map(|byte| byte[0]);
+        let word = |offset| endian.u32(&info, stream + offset).map(|word| word as usize);
+
+        let encoding = Encoding::from_raw(byte(0)?)?;
+        encoding.check_supported()?;
+        let looping = byte(1)? != 0;
+        let channel_count = usize::from(byte(2)?);
+        let sample_rate = word(4)? as u32;
+        let loop_start = word(0x08)?;
+        let frame_count = word(0x0C)?;
+        let block_count = word(0x10)?;
+        let block_size = word(0x14)?;
+        let block_frames = word(0x18)?;
+        let last_block_size = word(0x1C)?;
+        let last_block_padded_size = word(0x24)?;
+        // Sample data is located relative to the contents of the DATA block.
+        let data_offset = nw4c::expect_reference(endian, &info, stream + 0x30, SAMPLE_DATA)?
+            .checked_add(data_block)
+            .and_then(|offset| offset.checked_add(8))
+            .ok_or_else(|| Error::Other(String::from("invalid BCSTM sample data offset")))?;
+
+        if channel_count == 0
+            || sample_rate == 0
+            || block_frames == 0
+            || loop_start > frame_count
+            || !block_count
+                .checked_mul(block_frames)
+                .is_some_and(|frames| frame_count <= frames)
+        {
+            return Err(Error::Other(String::from(
+                "invalid BCSTM stream information",
+            )));
+        }
+
+        let decoders = if encoding == Encoding::DspAdpcm {
+            // Offsets of the INFO block are relative to its contents.
+            let table = 8 + nw4c::expect_reference(endian, &info, 0x18, REFERENCE_TABLE)?;
+
+            (0..channel_count)
+                .map(|index| {
+                    let channel = table
+                        + nw4c::expect_reference(
+                            endian,
+                            &info,
+                            table + 4 + index * 8,
+                            CHANNEL_INFO,
+                        )?;
+
+                    AdpcmDecoder::parse(
+                        endian,
+                        &info,
+                        channel + nw4c::expect_reference(endian, &info, channel, DSP_ADPCM_INFO)?,
+                    )
+                })
+                .collect::<crate::Result<_>>()?
+        } else {
+            vec![AdpcmDecoder::default(); channel_count]
+        };
+
+        let seek_table = match nw4c::block(endian, &header, SEEK_BLOCK) {
+            Ok((offset, size)) if size > 8 => {
+                read_at(&mut reader, stream_len, offset.saturating_add(8), size - 8)?
+            }
+            _ => Vec::new(),
+        };
+
+        Ok(Self {
+            reader,
+            stream_len,
+            endian,
+            encoding,
+            sample_rate,
+            channel_count,
+            loop_start: looping.then_some(loop_start),
+            frame_count,
+            data_offset,
+            block_size,
+            block_frames,
+            last_block_size,
+            last_block_padded_size,
+            seek_table,
+            decoders,
+            next_block: 0,
+            pending: Vec::new(),
+            pending_read: 0,
+            scratch: vec![Vec::new(); channel_count],
+        })
+    }
+
+    /// Fill `samples` with the next interleaved 16-bit samples of the stream, returning how many were written.
+    ///
+    /// Only whole frames (a sample of every channel) are written. Looping streams never end: once the loop end is reached,
+    /// decoding continues from the loop start. Other streams return 0 once fully read.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading the source fails or the stream data is corrupted.
+    pub fn read_samples(&mut self, samples: &mut [i16]) -> crate::Result<usize> {
+        let wanted = samples.len() - samples.len() % self.channel_count;
+        let mut written = 0;
+
+        while written < wanted {
+            if self.pending_read == self.pending.len() && !self.next_chunk()? {
+                break;
+            }
+
+            let available = &self.pending[self.pending_read..];
+            let len = available.len().min(wanted - written);
+            samples[written..written + len].copy_from_slice(&available[..len]);
+
+            written += len;
+            self.pending_read += len;
+        }
+
+        Ok(written)
+    }
+
+    /// Fill `buffer` with the next samples of the stream as little-endian bytes, ready for a [`Wave`](crate::services::ndsp::wave::Wave)
+    /// of the stream's [`audio_format()`](Self::audio_format), returning how many bytes were written.
+    ///
+    /// See [`read_samples()`](Self::read_samples) for the looping behaviour.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading the source fails or the stream data is corrupted.
+    pub fn read_bytes(&mut self, buffer: &mut [u8]) -> crate::Result<usize> {
+        let mut samples = vec![0; buffer.len() / 2];
+        let len = self.read_samples(&mut samples)?;
+
+        for (bytes, sample) in buffer.chunks_exact_mut(2).zip(&samples[..len]) {
+            bytes.copy_from_slice(&sample.to_le_bytes());
+        }
+
+        Ok(len * 2)
+    }
+
+    /// Go back to the start of the stream.
+    pub fn rewind(&mut self) -> crate::Result<()> {
+        self.seek_to_frame(0)
+    }
+
+    /// Continue decoding from `frame`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `frame` is past the end of the stream, or if reading the source fails.
+    pub fn seek_to_frame(&mut self, frame: usize) -> crate::Result<()> {
+        if frame > self.frame_count {
+            return Err(Error::Other(String::from(
+                "frame past the end of the stream",
+            )));
+        }
+
+        let block = frame / self.block_frames;
+        if block * self.block_frames >= self.frame_count {
+            self.next_block = block;
+            self.pending.clear();
+            self.pending_read = 0;
+            return Ok(());
+        }
+
+        self.restore_history(block);
+        self.decode_block(block)?;
+        self.pending_read =
+            ((frame % self.block_frames) * self.channel_count).min(self.pending.len());
+
+        Ok(())
+    }
+
+    /// Decode the next block into `pending`, looping back if needed. Returns `false` at the end of non-looping streams.
+    fn next_chunk(&mut self) -> crate::Result<bool> {
+        if self.next_block * self.block_frames < self.frame_count {
+            self.decode_block(self.next_block)?;
+            self.pending_read = 0;
+            return Ok(true);
+        }
+
+        match self.loop_start {
+            Some(start) if start < self.frame_count => {
+                self.seek_to_frame(start)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Reset the DSP-ADPCM decoders to the history stored for the start of `block`.
+    fn restore_history(&mut self, block: usize) {
+        if self.encoding != Encoding::DspAdpcm {
+            return;
+        }
+
+        for (index, decoder) in self.decoders.iter_mut().enumerate() {
+            let offset = block
+                .checked_mul(self.channel_count)
+                .and_then(|entry| entry.checked_add(index)?.checked_mul(4));
+            let sample =
+                |at| offset.and_then(|offset| self.endian.u16(&self.seek_table, offset + at).ok());
+
+            // Block 0 starts from the initial context, which is already right until the first loop.
+            decoder.history = match (sample(0), sample(2)) {
+                (Some(first), Some(second)) => [first as i16, second as i16],
+                _ if block == 0 => decoder.history,
+                _ => decoder.loop_history,
+            };
+        }
+    }
+
+    fn decode_block(&mut self, block: usize) -> crate::Result<()> {
+        let first_frame = block * self.block_frames;
+        let frames = self
+            .block_frames
+            .min(self.frame_count.saturating_sub(first_frame));
+        let is_last = (first_frame + self.block_frames) >= self.frame_count;
+
+        // Blocks hold the data of every channel one after the other; the last one is shorter.
+        let (size, stride) = if is_last {
+            (self.last_block_size, self.last_block_padded_size)
+        } else {
+            (self.block_size, self.block_size)
+        };
+        let size = size.max(self.encoding.data_size(frames)?);
+
+        let offset = |index: usize| {
+            block
+                .checked_mul(self.block_size)
+                .and_then(|offset| offset.checked_mul(self.channel_count))
+                .and_then(|offset| offset.checked_add(index.checked_mul(stride)?))
+                .and_then(|offset| offset.checked_add(self.data_offset))
+                .ok_or_else(|| Error::Other(String::from("block past the end of the stream")))
+        };
+
+        for (index, output) in self.scratch.iter_mut().enumerate() {
+            let data = read_at(&mut self.reader, self.stream_len, offset(index)?, size)?;
+
+            output.clear();
+            nw4c::decode_channel(
+                self.encoding,
+                self.endian,
+                &data,
+                frames,
+                &mut self.decoders[index],
+                output,
+            )?;
+        }
+
+        self.pending.clear();
+        nw4c::interleave(&self.scratch, &mut self.pending);
+        self.next_block = block + 1;
+
+        Ok(())
+    }
+}
+
+impl<R> BcstmStream<R> {
+    /// Returns the encoding the stream is stored with.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Returns the sample rate of the stream, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels of the stream.
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// Returns the length of the stream, in frames (samples of every channel).
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Returns the frames of the loop section, or [`None`] if the stream doesn't loop.
+    pub fn loop_range(&self) -> Option<Range<usize>> {
+        self.loop_start.map(|start| start..self.frame_count)
+    }
+
+    /// Returns the [`AudioFormat`] of the decoded samples, or [`None`] if NDSP can't play them (more than 2 channels).
+    pub fn audio_format(&self) -> Option<AudioFormat> {
+        match self.channel_count {
+            1 => Some(AudioFormat::PCM16Mono),
+            2 => Some(AudioFormat::PCM16Stereo),
+            _ => None,
+        }
+    }
+
+    /// Set the format and sample rate of `channel` to play this stream.
+    ///
+    /// Streams with more than 2 channels leave the format of `channel` untouched.
+    pub fn configure(&self, channel: &mut Channel) {
+        if let Some(format) = self.audio_format() {
+            channel.set_format(format);
+        }
+        channel.set_sample_rate(self.sample_rate as f32);
+    }
+
+    /// Returns the source of the stream.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Read `len` bytes of `reader` at `offset`, checking beforehand that they are within the `stream_len` bytes of the source.
+fn read_at<R: Read + Seek>(
+    reader: &mut R,
+    stream_len: u64,
+    offset: usize,
+    len: usize,
+) -> crate::Result<Vec<u8>> {
+    let end = (offset as u64).checked_add(len as u64);
+    if !end.is_some_and(|end| end <= stream_len) {
+        return Err(Error::Other(String::from("unexpected end of file")));
+    }
+
+    let mut data = vec![0; len];
+
+    reader.seek(SeekFrom::Start(offset as u64))?;
+    reader.read_exact(&mut data)?;
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Build a stereo 16-bit PCM BCSTM file with blocks of 2 frames.
+    fn pcm16_bcstm(channels: [&[i16]; 2], loop_start: Option<u32>) -> Vec<u8> {
+        const BLOCK_FRAMES: usize = 2;
+        let frames = channels[0].len();
+        let block_count = frames.div_ceil(BLOCK_FRAMES);
+        let last_frames = frames - (block_count - 1) * BLOCK_FRAMES;
+
+        let mut data = Vec::new();
+        for block in 0..block_count {
+            let range = block * BLOCK_FRAMES..(block * BLOCK_FRAMES + BLOCK_FRAMES).min(frames);
+            for channel in channels {
+                data.extend(
+                    channel[range.clone()]
+                        .iter()
+                        .flat_map(|sample| sample.to_le_bytes()),
+                );
+            }
+        }
+
+        let info = HEADER_SIZE as u32;
+        let info_size = 0x20 + 0x38;
+        let data_block = info + info_size;
+
+        let mut file = b"CSTM\xFF\xFE\x40\x00".to_vec();
+        file.extend_from_slice(&0x0200_0000u32.to_le_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&2u16.to_le_bytes());
+        file.extend_from_slice(&[0; 2]);
+        for (kind, offset, size) in [(INFO_BLOCK, info, info_size), (DATA_BLOCK, data_block, 0)] {
+            file.extend_from_slice(&kind.to_le_bytes());
+            file.extend_from_slice(&[0; 2]);
+            file.extend_from_slice(&offset.to_le_bytes());
+            file.extend_from_slice(&size.to_le_bytes());
+        }
+        file.resize(HEADER_SIZE, 0);
+
+        file.extend_from_slice(b"INFO");
+        file.extend_from_slice(&info_size.to_le_bytes());
+        file.extend_from_slice(&STREAM_INFO.to_le_bytes());
+        file.extend_from_slice(&[0; 2]);
+        file.extend_from_slice(&0x18u32.to_le_bytes());
+        file.resize(file.len() + 0x10, 0);
+
+        file.extend_from_slice(&[1, loop_start.is_some() as u8, 2, 0]);
+        for word in [
+            32728,
+            loop_start.unwrap_or(0),
+            frames as u32,
+            block_count as u32,
+            (BLOCK_FRAMES * 2) as u32,
+            BLOCK_FRAMES as u32,
+            (last_frames * 2) as u32,
+            last_frames as u32,
+            (last_frames * 2) as u32,
+            0,
+            0,
+        ] {
+            file.extend_from_slice(&word.to_le_bytes());
+        }
+        file.extend_from_slice(&SAMPLE_DATA.to_le_bytes());
+        file.extend_from_slice(&[0; 2]);
+        file.extend_from_slice(&0x18u32.to_le_bytes());
+
+        file.extend_from_slice(b"DATA");
+        file.extend_from_slice(&[0; 0x1C]);
+        file.extend(data);
+
+        file
+    }
+
+    #[test]
+    fn looping_stream() {
+        let file = pcm16_bcstm([&[0, 1, 2, 3, 4], &[0, -1, -2, -3, -4]], Some(1));
+        let mut stream = BcstmStream::new(Cursor::new(file)).unwrap();
+
+        assert_eq!(stream.channel_count(), 2);
+        assert_eq!(stream.frame_count(), 5);
+        assert_eq!(stream.loop_range(), Some(1..5));
+
+        // Odd lengths are cut down to whole frames.
+        let mut samples = [0; 15];
+        assert_eq!(stream.read_samples(&mut samples).unwrap(), 14);
+        assert_eq!(
+            samples[..14],
+            [0, 0, 1, -1, 2, -2, 3, -3, 4, -4, 1, -1, 2, -2]
+        );
+
+        stream.seek_to_frame(4).unwrap();
+        let mut bytes = [0; 4];
+        assert_eq!(stream.read_bytes(&mut bytes).unwrap(), 4);
+        assert_eq!(bytes, [4, 0, 0xFC, 0xFF]);
+    }
+
+    #[test]
+    fn finite_stream() {
+        let file = pcm16_bcstm([&[5, 6, 7], &[8, 9, 10]], None);
+        let mut stream = BcstmStream::new(Cursor::new(file)).unwrap();
+
+        let mut samples = [0; 8];
+        assert_eq!(stream.read_samples(&mut samples).unwrap(), 6);
+        assert_eq!(samples[..6], [5, 8, 6, 9, 7, 10]);
+        assert_eq!(stream.read_samples(&mut samples).unwrap(), 0);
+
+        stream.rewind().unwrap();
+        assert_eq!(stream.read_samples(&mut samples[..2]).unwrap(), 2);
+        assert_eq!(samples[..2], [5, 8]);
+    }
+}
@@ -0,0 +1,317 @@
+//! BCWAV sounds.
+//!
+//! BCWAV is the format of short sounds (menu and game sound effects, voice clips) of most titles and of the HOME Menu.
+//! [`Bcwav`] decodes a whole sound held in memory to 16-bit PCM, ready to be played as [`Wave`]s through [NDSP](crate::services::ndsp).
+//! Longer music tracks use the streamed [BCSTM](super::bcstm) format instead.
+//!
+//! # Additional Resources
+//!
+//! - <https://www.3dbrew.org/wiki/BCWAV>
+#![doc(alias = "cwav")]
+
+use std::ops::Range;
+
+pub use super::nw4c::Encoding;
+use super::nw4c::{self, AdpcmDecoder};
+use super::{expect_magic, Endian};
+use crate::linear::LinearAllocator;
+use crate::services::ndsp::wave::Wave;
+use crate::services::ndsp::{AudioFormat, Channel};
+use crate::Error;
+
+/// Type of the INFO block reference.
+const INFO_BLOCK: u16 = 0x7000;
+/// Type of the DATA block reference.
+const DATA_BLOCK: u16 = 0x7001;
+/// Type of the channel information references.
+const CHANNEL_INFO: u16 = 0x7100;
+/// Type of the sample data references.
+const SAMPLE_DATA: u16 = 0x1F00;
+/// Type of the DSP-ADPCM information references.
+const DSP_ADPCM_INFO: u16 = 0x0300;
+
+/// Sound decoded from a BCWAV file.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::formats::bcwav::Bcwav;
+/// use ctru::services::ndsp::Ndsp;
+///
+/// let ndsp = Ndsp::new()?;
+/// let mut channel = ndsp.channel(0)?;
+///
+/// let sound = Bcwav::parse(&std::fs::read("romfs:/jump.bcwav")?)?;
+/// sound.configure(&mut channel);
+///
+/// // The intro is played once, then the loop section repeats (if the sound loops).
+/// let (mut intro, mut section) = sound.to_waves()?;
+/// if let Some(intro) = &mut intro {
+///     channel.queue_wave(intro)?;
+/// }
+/// channel.queue_wave(&mut section)?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Bcwav {
+    encoding: Encoding,
+    sample_rate: u32,
+    channel_count: usize,
+    loop_range: Option<Range<usize>>,
+    /// Interleaved samples of every channel.
+    samples: Vec<i16>,
+}
+
+impl Bcwav {
+    /// Parse and decode the sound in `data`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `data` isn't a valid BCWAV file, or if the sound is encoded with IMA-ADPCM.
+    pub fn parse(data: &[u8]) -> crate::Result<Self> {
+        expect_magic(data, 0, b"CWAV")?;
+        let endian = Endian::from_bom(data, 4)?;
+
+        let (info, _) = nw4c::block(endian, data, INFO_BLOCK)?;
+        let (data_block, _) = nw4c::block(endian, data, DATA_BLOCK)?;
+        expect_magic(data, info, b"INFO")?;
+        expect_magic(data, data_block, b"DATA")?;
+
+        let encoding = Encoding::from_raw(super::bytes(data, info + 8, 1)?[0])?;
+        encoding.check_supported()?;
+        let looping = super::bytes(data, info + 9, 1)?[0] != 0;
+        let sample_rate = endian.u32(data, info + 0x0C)?;
+        let loop_start = endian.u32(data, info + 0x10)? as usize;
+        // The loop end is also the length of the sound.
+        let frame_count = endian.u32(data, info + 0x14)? as usize;
+
+        if sample_rate == 0 || loop_start > frame_count {
+            return Err(Error::Other(String::from(
+                "invalid BCWAV sound information",
+            )));
+        }
+
+        let table = info + 0x1C;
+        let channel_count = endian.u32(data, table)? as usize;
+        if channel_count == 0 {
+            return Err(Error::Other(String::from("BCWAV sound without channels")));
+        }
+
+        // Every channel's samples are stored in the file: check the length before allocating them.
+        let sample_count = frame_count
+            .checked_mul(channel_count)
+            .filter(|_| {
+                encoding
+                    .data_size(frame_count)
+                    .is_ok_and(|size| size <= data.len())
+            })
+            .ok_or_else(|| {
+                Error::Other(String::from(
+                    "the sample data is too short for the sound's length",
+                ))
+            })?;
+
+        let channels = (0..channel_count)
+            .map(|index| {
+                let channel = table
+                    + nw4c::expect_reference(endian, data, table + 4 + index * 8, CHANNEL_INFO)?;
+
+                // Sample data is located relative to the contents of the DATA block.
+                let samples =
+                    data_block + 8 + nw4c::expect_reference(endian, data, channel, SAMPLE_DATA)?;
+
+                let mut decoder = match encoding {
+                    Encoding::DspAdpcm => AdpcmDecoder::parse(
+                        endian,
+                        data,
+                        channel
+                            + nw4c::expect_reference(endian, data, channel + 8, DSP_ADPCM_INFO)?,
+                    )?,
+                    _ => AdpcmDecoder::default(),
+                };
+
+                let mut output = Vec::with_capacity(frame_count);
+                nw4c::decode_channel(
+                    encoding,
+                    endian,
+                    data.get(samples..).unwrap_or_default(),
+                    frame_count,
+                    &mut decoder,
+                    &mut output,
+                )?;
+
+                Ok(output)
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        let mut samples = Vec::with_capacity(sample_count);
+        nw4c::interleave(&channels, &mut samples);
+
+        Ok(Self {
+            encoding,
+            sample_rate,
+            channel_count,
+            loop_range: looping.then_some(loop_start..frame_count),
+            samples,
+        })
+    }
+
+    /// Returns the encoding the sound was stored with.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Returns the sample rate of the sound, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels of the sound.
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// Returns the length of the sound, in frames (samples of every channel).
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.channel_count
+    }
+
+    /// Returns the frames of the loop section, or [`None`] if the sound doesn't loop.
+    pub fn loop_range(&self) -> Option<Range<usize>> {
+        self.loop_range.clone()
+    }
+
+    /// Returns the decoded samples, interleaved by channel.
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Returns the [`AudioFormat`] of the decoded sound, or [`None`] if NDSP can't play it (more than 2 channels).
+    pub fn audio_format(&self) -> Option<AudioFormat> {
+        match self.channel_count {
+            1 => Some(AudioFormat::PCM16Mono),
+            2 => Some(AudioFormat::PCM16Stereo),
+            _ => None,
+        }
+    }
+
+    /// Set the format and sample rate of `channel` to play this sound.
+    ///
+    /// Sounds with more than 2 channels leave the format of `channel` untouched.
+    pub fn configure(&self, channel: &mut Channel) {
+        if let Some(format) = self.audio_format() {
+            channel.set_format(format);
+        }
+        channel.set_sample_rate(self.sample_rate as f32);
+    }
+
+    /// Copy the sound to [LINEAR memory](crate::linear) as waves: an optional intro, played once, followed by the loop section,
+    /// which is set to loop.
+    ///
+    /// Sounds which don't loop are returned whole as the second wave, without an intro.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the sound has more than 2 channels.
+    pub fn to_waves(&self) -> crate::Result<(Option<Wave>, Wave)> {
+        let format = self
+            .audio_format()
+            .ok_or_else(|| Error::Other(String::from("NDSP can only play up to 2 channels")))?;
+
+        let frames = self.loop_range.clone().unwrap_or(0..self.frame_count());
+        let intro = (frames.start > 0).then(|| self.wave(0..frames.start, format, false));
+
+        Ok((intro, self.wave(frames, format, self.loop_range.is_some())))
+    }
+
+    fn wave(&self, frames: Range<usize>, format: AudioFormat, looping: bool) -> Wave {
+        let samples =
+            &self.samples[frames.start * self.channel_count..frames.end * self.channel_count];
+
+        let mut buffer = Vec::with_capacity_in(samples.len() * 2, LinearAllocator);
+        buffer.extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+
+        Wave::new(buffer.into_boxed_slice(), format, looping)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a BCWAV file holding 16-bit PCM channels.
+    fn pcm16_bcwav(channels: &[&[i16]], looping: Option<Range<u32>>) -> Vec<u8> {
+        let frames = channels[0].len() as u32;
+        let info = 0x40u32;
+        let table = info + 0x1C;
+        let channel_infos = table + 4 + channels.len() as u32 * 8;
+        let data_block = channel_infos + channels.len() as u32 * 0x14;
+
+        let mut file = b"CWAV\xFF\xFE\x40\x00".to_vec();
+        file.extend_from_slice(&0x0201_0000u32.to_le_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&2u16.to_le_bytes());
+        file.extend_from_slice(&[0; 2]);
+        for (kind, offset) in [(INFO_BLOCK, info), (DATA_BLOCK, data_block)] {
+            file.extend_from_slice(&kind.to_le_bytes());
+            file.extend_from_slice(&[0; 2]);
+            file.extend_from_slice(&offset.to_le_bytes());
+            file.extend_from_slice(&[0; 4]);
+        }
+        file.resize(info as usize, 0);
+
+        file.extend_from_slice(b"INFO");
+        file.extend_from_slice(&[0; 4]);
+        file.push(1);
+        file.push(looping.is_some() as u8);
+        file.extend_from_slice(&[0; 2]);
+        let (loop_start, loop_end) = looping.map_or((0, frames), |range| (range.start, range.end));
+        for word in [32728, loop_start, loop_end, 0] {
+            file.extend_from_slice(&word.to_le_bytes());
+        }
+
+        file.extend_from_slice(&(channels.len() as u32).to_le_bytes());
+        for index in 0..channels.len() as u32 {
+            file.extend_from_slice(&CHANNEL_INFO.to_le_bytes());
+            file.extend_from_slice(&[0; 2]);
+            file.extend_from_slice(&(channel_infos - table + index * 0x14).to_le_bytes());
+        }
+        for index in 0..channels.len() as u32 {
+            file.extend_from_slice(&SAMPLE_DATA.to_le_bytes());
+            file.extend_from_slice(&[0; 2]);
+            file.extend_from_slice(&(index * frames * 2).to_le_bytes());
+            file.extend_from_slice(&[0; 12]);
+        }
+
+        file.extend_from_slice(b"DATA");
+        file.extend_from_slice(&[0; 4]);
+        for channel in channels {
+            file.extend(channel.iter().flat_map(|sample| sample.to_le_bytes()));
+        }
+
+        file
+    }
+
+    #[test]
+    fn pcm16_channels() {
+        let sound = Bcwav::parse(&pcm16_bcwav(&[&[1, 2, 3], &[-1, -2, -3]], Some(1..3))).unwrap();
+
+        assert_eq!(sound.encoding(), Encoding::Pcm16);
+        assert_eq!(sound.sample_rate(), 32728);
+        assert_eq!(sound.channel_count(), 2);
+        assert_eq!(sound.frame_count(), 3);
+        assert_eq!(sound.loop_range(), Some(1..3));
+        assert_eq!(sound.samples(), [1, -1, 2, -2, 3, -3]);
+        assert_eq!(sound.audio_format(), Some(AudioFormat::PCM16Stereo));
+
+        let mut file = pcm16_bcwav(&[&[1, 2, 3]], None);
+        file.truncate(file.len() - 1);
+        assert!(Bcwav::parse(&file).is_err());
+    }
+}
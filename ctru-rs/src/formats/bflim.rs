@@ -0,0 +1,655 @@
+//! BCLIM and BFLIM textures.
+//!
+//! BCLIM (and its newer revision, BFLIM) is the texture format of layouts: HOME Menu themes, applets and most titles' menus
+//! store their images this way. The texture data is kept in the GPU's native, tiled layout, followed by a small footer.
+//! [`Bflim`] reads the footer of a texture held in memory and either decodes it into an [`Image`], or uploads the texture data as-is
+//! to [LINEAR memory](crate::linear) or VRAM (as a [`TextureBuffer`]) to be sampled by the GPU.
+//!
+//! # Additional Resources
+//!
+//! - <https://www.3dbrew.org/wiki/CLIM>
+//! - <https://www.3dbrew.org/wiki/GPU/Internal_Registers#Texture_format>
+#![doc(alias = "bclim", alias = "clim", alias = "flim", alias = "texture")]
+
+use std::alloc::{Allocator, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+use super::{bytes, expect_magic, Endian};
+use crate::dma;
+use crate::image::{Image, PixelFormat};
+use crate::linear::LinearAllocator;
+use crate::services::gfx::Gfx;
+use crate::vram::VramAllocator;
+use crate::Error;
+
+/// Size of the footer, from the file header to the end of the file.
+const FOOTER_SIZE: usize = 0x28;
+
+/// Alignment of the uploaded textures, as used by `citro3d`.
+const TEXTURE_ALIGNMENT: usize = 0x80;
+
+/// Modifiers of the ETC1 intensity tables.
+const ETC1_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+/// Pixel format of a texture, numbered as in CLIM files.
+///
+/// The GPU numbers its formats differently: see [`TextureFormat::gpu_format()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum TextureFormat {
+    /// 8-bit luminance.
+    L8 = 0,
+    /// 8-bit alpha.
+    A8 = 1,
+    /// 4-bit luminance and alpha.
+    La4 = 2,
+    /// 8-bit luminance and alpha.
+    La8 = 3,
+    /// 8-bit two-channel normal map components.
+    Hilo8 = 4,
+    /// 16-bit RGB (5 bits of red and blue, 6 bits of green).
+    Rgb565 = 5,
+    /// 8-bit RGB.
+    Rgb8 = 6,
+    /// 5-bit RGB and 1-bit alpha.
+    Rgba5551 = 7,
+    /// 4-bit RGBA.
+    Rgba4 = 8,
+    /// 8-bit RGBA.
+    Rgba8 = 9,
+    /// ETC1 compressed RGB.
+    Etc1 = 10,
+    /// ETC1 compressed RGB, with 4-bit alpha.
+    Etc1A4 = 11,
+    /// 4-bit luminance.
+    L4 = 12,
+    /// 4-bit alpha.
+    A4 = 13,
+}
+
+impl TextureFormat {
+    fn from_raw(raw: u32) -> crate::Result<Self> {
+        Ok(match raw {
+            0 => Self::L8,
+            1 => Self::A8,
+            2 => Self::La4,
+            3 => Self::La8,
+            4 => Self::Hilo8,
+            5 => Self::Rgb565,
+            6 => Self::Rgb8,
+            7 => Self::Rgba5551,
+            8 => Self::Rgba4,
+            9 => Self::Rgba8,
+            10 => Self::Etc1,
+            11 => Self::Etc1A4,
+            12 => Self::L4,
+            13 => Self::A4,
+            _ => return Err(Error::Other(format!("unknown texture format {raw}"))),
+        })
+    }
+
+    /// Returns the GPU's identifier of this format, as set in its texture registers.
+    #[doc(alias = "GPU_TEXCOLOR")]
+    pub fn gpu_format(&self) -> ctru_sys::GPU_TEXCOLOR {
+        match self {
+            Self::Rgba8 => ctru_sys::GPU_RGBA8,
+            Self::Rgb8 => ctru_sys::GPU_RGB8,
+            Self::Rgba5551 => ctru_sys::GPU_RGBA5551,
+            Self::Rgb565 => ctru_sys::GPU_RGB565,
+            Self::Rgba4 => ctru_sys::GPU_RGBA4,
+            Self::La8 => ctru_sys::GPU_LA8,
+            Self::Hilo8 => ctru_sys::GPU_HILO8,
+            Self::L8 => ctru_sys::GPU_L8,
+            Self::A8 => ctru_sys::GPU_A8,
+            Self::La4 => ctru_sys::GPU_LA4,
+            Self::L4 => ctru_sys::GPU_L4,
+            Self::A4 => ctru_sys::GPU_A4,
+            Self::Etc1 => ctru_sys::GPU_ETC1,
+            Self::Etc1A4 => ctru_sys::GPU_ETC1A4,
+        }
+    }
+
+    /// Returns the number of bits used by each texel of this format.
+    pub fn bits_per_pixel(&self) -> usize {
+        match self {
+            Self::L4 | Self::A4 | Self::Etc1 => 4,
+            Self::L8 | Self::A8 | Self::La4 | Self::Etc1A4 => 8,
+            Self::La8 | Self::Hilo8 | Self::Rgb565 | Self::Rgba5551 | Self::Rgba4 => 16,
+            Self::Rgb8 => 24,
+            Self::Rgba8 => 32,
+        }
+    }
+
+    /// Returns the [`PixelFormat`] able to hold the decoded texels without losing information.
+    fn decoded_format(&self) -> PixelFormat {
+        match self {
+            Self::L8 | Self::L4 => PixelFormat::L8,
+            Self::Hilo8 | Self::Rgb565 | Self::Rgb8 | Self::Etc1 => PixelFormat::Rgb8,
+            _ => PixelFormat::Rgba8,
+        }
+    }
+}
+
+/// How the image is laid out in the texture (only BFLIM textures may be stored rotated).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    /// The texture holds the image as-is.
+    #[default]
+    Normal,
+    /// The texture holds the image rotated by 90 degrees.
+    Rotate90,
+    /// The texture holds the image transposed (swapping its rows and columns).
+    Transpose,
+}
+
+/// Texture read from a BCLIM or BFLIM file, borrowing the file's bytes.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::formats::{bflim::Bflim, compress, darc::Darc};
+///
+/// let data = compress::decompress(&std::fs::read("romfs:/layout.arc.lz")?)?;
+/// let archive = Darc::parse(&data)?;
+///
+/// let texture = Bflim::parse(archive.get("timg/icon.bclim").ok_or("missing texture")?)?;
+/// let image = texture.decode()?;
+///
+/// println!("{}x{} {:?}", image.width(), image.height(), texture.format());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Bflim<'a> {
+    width: usize,
+    height: usize,
+    format: TextureFormat,
+    orientation: Orientation,
+    data: &'a [u8],
+}
+
+impl<'a> Bflim<'a> {
+    /// Parse the footer of the texture in `data`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `data` isn't a valid BCLIM or BFLIM file.
+    pub fn parse(data: &'a [u8]) -> crate::Result<Self> {
+        let footer = data
+            .len()
+            .checked_sub(FOOTER_SIZE)
+            .ok_or_else(|| Error::Other(String::from("unexpected end of file")))?;
+
+        let is_bflim = match bytes(data, footer, 4)? {
+            b"CLIM" => false,
+            b"FLIM" => true,
+            _ => return Err(Error::Other(String::from("missing `CLIM` or `FLIM` magic"))),
+        };
+        let endian = Endian::from_bom(data, footer + 4)?;
+
+        let imag = footer + usize::from(endian.u16(data, footer + 6)?);
+        expect_magic(data, imag, b"imag")?;
+
+        let width = usize::from(endian.u16(data, imag + 8)?);
+        let height = usize::from(endian.u16(data, imag + 10)?);
+
+        let (format, orientation, size) = if is_bflim {
+            let format = u32::from(bytes(data, imag + 14, 1)?[0]);
+            let orientation = match bytes(data, imag + 15, 1)?[0] {
+                0 => Orientation::Normal,
+                4 => Orientation::Rotate90,
+                8 => Orientation::Transpose,
+                swizzle => {
+                    return Err(Error::Other(format!(
+                        "unknown BFLIM swizzle mode {swizzle}"
+                    )))
+                }
+            };
+
+            (format, orientation, endian.u32(data, imag + 16)?)
+        } else {
+            (
+                endian.u32(data, imag + 12)?,
+                Orientation::Normal,
+                endian.u32(data, imag + 16)?,
+            )
+        };
+
+        let mut texture = Self {
+            width,
+            height,
+            format: TextureFormat::from_raw(format)?,
+            orientation,
+            data: &[],
+        };
+
+        let wanted = texture
+            .texture_width()
+            .checked_mul(texture.texture_height())
+            .and_then(|texels| texels.checked_mul(texture.format.bits_per_pixel()))
+            .map(|bits| bits / 8)
+            .ok_or_else(|| Error::Other(String::from("the texture is too large")))?;
+        if (size as usize) < wanted || footer < wanted {
+            return Err(Error::Other(String::from(
+                "the texture data is too short for its dimensions",
+            )));
+        }
+        texture.data = &data[..wanted];
+
+        Ok(texture)
+    }
+
+    /// Returns the width of the image, in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the image, in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the width of the stored texture, in texels.
+    ///
+    /// Textures are stored with power of two dimensions of at least 8 texels, which may be bigger than the image (and swapped, if it's rotated).
+    pub fn texture_width(&self) -> usize {
+        match self.orientation {
+            Orientation::Normal => texture_size(self.width),
+            Orientation::Rotate90 | Orientation::Transpose => texture_size(self.height),
+        }
+    }
+
+    /// Returns the height of the stored texture, in texels.
+    ///
+    /// See [`Bflim::texture_width()`].
+    pub fn texture_height(&self) -> usize {
+        match self.orientation {
+            Orientation::Normal => texture_size(self.height),
+            Orientation::Rotate90 | Orientation::Transpose => texture_size(self.width),
+        }
+    }
+
+    /// Returns the pixel format of the texture.
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Returns how the image is laid out in the texture.
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Returns the raw texture data, in the GPU's tiled layout.
+    pub fn texture_data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Decode the texture into an [`Image`] of [`Bflim::width()`]x[`Bflim::height()`] pixels.
+    ///
+    /// Luminance-only formats decode to [`PixelFormat::L8`], formats without alpha to [`PixelFormat::Rgb8`] and the others to [`PixelFormat::Rgba8`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the decoded pixels don't fill the image, which can't happen for textures
+    /// returned by [`Bflim::parse()`].
+    pub fn decode(&self) -> crate::Result<Image> {
+        let texels = self.decode_texels()?;
+        let texture_width = self.texture_width();
+        let format = self.format.decoded_format();
+
+        let mut data = Vec::with_capacity(self.width * self.height * format.bytes_per_pixel());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (u, v) = match self.orientation {
+                    Orientation::Normal => (x, y),
+                    Orientation::Rotate90 => (y, self.width - 1 - x),
+                    Orientation::Transpose => (y, x),
+                };
+                let [r, g, b, a] = texels[v * texture_width + u];
+
+                match format {
+                    PixelFormat::L8 => data.push(r),
+                    PixelFormat::Rgb8 => data.extend_from_slice(&[r, g, b]),
+                    PixelFormat::Rgba8 => data.extend_from_slice(&[r, g, b, a]),
+                }
+            }
+        }
+
+        Image::new(self.width, self.height, format, data)
+    }
+
+    /// Copy the texture data to [LINEAR memory](crate::linear), where the GPU can sample it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there isn't enough LINEAR memory left.
+    pub fn to_linear(&self) -> crate::Result<TextureBuffer<LinearAllocator>> {
+        let mut buffer = TextureBuffer::new_in(self.data.len(), LinearAllocator)?;
+        buffer.copy_from_slice(self.data);
+
+        Ok(buffer)
+    }
+
+    /// Copy the texture data to VRAM, using the GPU's DMA engine.
+    ///
+    /// VRAM is faster to sample from than LINEAR memory, and the DMA engine copies large textures faster than the CPU would.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there isn't enough LINEAR memory (used for the transfer) or VRAM left, or if the copy fails.
+    pub fn to_vram(&self, gfx: &Gfx) -> crate::Result<TextureBuffer<VramAllocator>> {
+        let linear = self.to_linear()?;
+        let mut buffer = TextureBuffer::new_in(self.data.len(), VramAllocator)?;
+
+        dma::copy(gfx, &linear, &mut buffer).map_err(|e| Error::Other(e.to_string()))?;
+
+        Ok(buffer)
+    }
+
+    /// Decode every texel of the texture to RGBA, in left-to-right and top-to-bottom order.
+    fn decode_texels(&self) -> crate::Result<Vec<[u8; 4]>> {
+        let (width, height) = (self.texture_width(), self.texture_height());
+        let mut texels = vec![[0; 4]; width * height];
+
+        let mut tiles = self
+            .data
+            .chunks_exact(64 * self.format.bits_per_pixel() / 8);
+
+        for tile_y in (0..height).step_by(8) {
+            for tile_x in (0..width).step_by(8) {
+                let tile = tiles.next().ok_or_else(|| {
+                    Error::Other(String::from(
+                        "the texture data is too short for its dimensions",
+                    ))
+                })?;
+                let mut put = |x: usize, y: usize, texel| {
+                    texels[(tile_y + y) * width + tile_x + x] = texel;
+                };
+
+                match self.format {
+                    TextureFormat::Etc1 | TextureFormat::Etc1A4 => {
+                        let block_size = tile.len() / 4;
+
+                        // The 4x4 blocks of a tile are also stored in Z-order.
+                        for (index, block) in tile.chunks_exact(block_size).enumerate() {
+                            let (block_x, block_y) = ((index & 1) * 4, (index >> 1) * 4);
+                            let (alpha, color) = block.split_at(block_size - 8);
+
+                            let alpha = (!alpha.is_empty())
+                                .then(|| u64::from_le_bytes(alpha.try_into().unwrap()));
+                            let color = u64::from_le_bytes(color.try_into().unwrap());
+
+                            for (x, y, texel) in decode_etc1(color, alpha) {
+                                put(block_x + x, block_y + y, texel);
+                            }
+                        }
+                    }
+                    format => {
+                        for k in 0..64 {
+                            put(unmorton_x(k), unmorton_y(k), decode_texel(format, tile, k));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(texels)
+    }
+}
+
+/// Returns the texture dimension holding `size` pixels.
+fn texture_size(size: usize) -> usize {
+    size.next_power_of_two().max(8)
+}
+
+/// Texture data uploaded by [`Bflim::to_linear()`] or [`Bflim::to_vram()`], aligned as the GPU expects.
+///
+/// The buffer dereferences to the texture's bytes.
+pub struct TextureBuffer<A: Allocator> {
+    memory: NonNull<u8>,
+    layout: Layout,
+    allocator: A,
+}
+
+impl<A: Allocator> TextureBuffer<A> {
+    /// Allocate a zeroed, suitably aligned texture buffer of `len` bytes.
+    fn new_in(len: usize, allocator: A) -> crate::Result<Self> {
+        // DMA copies work on multiples of 16 bytes, which textures always are (a tile is at least 32 bytes long).
+        let layout = Layout::from_size_align(len, TEXTURE_ALIGNMENT)
+            .map_err(|e| Error::Other(e.to_string()))?;
+        let memory = allocator
+            .allocate_zeroed(layout)
+            .map_err(|_| Error::Other(String::from("not enough memory for the texture")))?;
+
+        Ok(Self {
+            memory: memory.cast(),
+            layout,
+            allocator,
+        })
+    }
+
+    /// Returns the allocator holding the texture.
+    pub fn allocator(&self) -> &A {
+        &self.allocator
+    }
+}
+
+impl<A: Allocator> Deref for TextureBuffer<A> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the allocation holds at least `layout.size()` zeroed or written bytes,
+        // even if the allocator returned a larger block.
+        unsafe { std::slice::from_raw_parts(self.memory.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl<A: Allocator> DerefMut for TextureBuffer<A> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: see `deref()`.
+        unsafe { std::slice::from_raw_parts_mut(self.memory.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl<A: Allocator> Drop for TextureBuffer<A> {
+    fn drop(&mut self) {
+        // SAFETY: the memory was allocated by the same allocator, with the same layout.
+        unsafe { self.allocator.deallocate(self.memory, self.layout) };
+    }
+}
+
+impl<A: Allocator> std::fmt::Debug for TextureBuffer<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TextureBuffer")
+            .field("address", &self.memory)
+            .field("len", &self.layout.size())
+            .finish()
+    }
+}
+
+/// Returns the coordinates within a tile of its `k`-th texel, undoing the Z-order curve.
+fn unmorton_x(k: usize) -> usize {
+    (k & 1) | ((k >> 1) & 2) | ((k >> 2) & 4)
+}
+
+fn unmorton_y(k: usize) -> usize {
+    ((k >> 1) & 1) | ((k >> 2) & 2) | ((k >> 3) & 4)
+}
+
+/// Decode the `k`-th texel of a tile of an uncompressed format.
+fn decode_texel(format: TextureFormat, tile: &[u8], k: usize) -> [u8; 4] {
+    let size = format.bits_per_pixel() / 8;
+    let texel = &tile[k * size..k * size + size];
+    let word = || u16::from_le_bytes([texel[0], texel[1]]);
+    // 4-bit formats store the first texel in the low nibble.
+    let nibble = || ((tile[k / 2] >> ((k & 1) * 4)) & 0xF) * 0x11;
+
+    match format {
+        TextureFormat::L8 => [texel[0], texel[0], texel[0], 0xFF],
+        TextureFormat::A8 => [0, 0, 0, texel[0]],
+        TextureFormat::La4 => {
+            let l = (texel[0] >> 4) * 0x11;
+            [l, l, l, (texel[0] & 0xF) * 0x11]
+        }
+        TextureFormat::La8 => [texel[1], texel[1], texel[1], texel[0]],
+        TextureFormat::Hilo8 => [texel[1], texel[0], 0, 0xFF],
+        TextureFormat::Rgb565 => {
+            let pixel = word();
+            let (r, g, b) = (
+                (pixel >> 11) as u8,
+                (pixel >> 5) as u8 & 0x3F,
+                pixel as u8 & 0x1F,
+            );
+            [
+                (r << 3) | (r >> 2),
+                (g << 2) | (g >> 4),
+                (b << 3) | (b >> 2),
+                0xFF,
+            ]
+        }
+        TextureFormat::Rgb8 => [texel[2], texel[1], texel[0], 0xFF],
+        TextureFormat::Rgba5551 => {
+            let pixel = word();
+            let channel = |shift: u16| {
+                let value = (pixel >> shift) as u8 & 0x1F;
+                (value << 3) | (value >> 2)
+            };
+            [
+                channel(11),
+                channel(6),
+                channel(1),
+                if pixel & 1 != 0 { 0xFF } else { 0 },
+            ]
+        }
+        TextureFormat::Rgba4 => {
+            let pixel = word();
+            let channel = |shift: u16| ((pixel >> shift) as u8 & 0xF) * 0x11;
+            [channel(12), channel(8), channel(4), channel(0)]
+        }
+        TextureFormat::Rgba8 => [texel[3], texel[2], texel[1], texel[0]],
+        TextureFormat::L4 => {
+            let l = nibble();
+            [l, l, l, 0xFF]
+        }
+        TextureFormat::A4 => [0, 0, 0, nibble()],
+        TextureFormat::Etc1 | TextureFormat::Etc1A4 => unreachable!("ETC1 is decoded by blocks"),
+    }
+}
+
+/// Decode a 4x4 ETC1 block, with its optional 4-bit alpha values, returning the coordinates and colour of each texel.
+fn decode_etc1(block: u64, alpha: Option<u64>) -> impl Iterator<Item = (usize, usize, [u8; 4])> {
+    let bits = move |shift: u32, len: u32| ((block >> shift) & ((1 << len) - 1)) as i32;
+    let differential = bits(33, 1) != 0;
+    let flipped = bits(32, 1) != 0;
+
+    let base_colors = if differential {
+        let expand = |value: i32| (value << 3) | (value >> 2);
+        let first = [bits(59, 5), bits(51, 5), bits(43, 5)];
+        // The second colour is a signed 3-bit offset from the first one.
+        let delta = [bits(56, 3), bits(48, 3), bits(40, 3)].map(|delta| (delta << 29) >> 29);
+
+        [
+            first.map(expand),
+            [0, 1, 2].map(|channel| expand((first[channel] + delta[channel]) & 0x1F)),
+        ]
+    } else {
+        [
+            [bits(60, 4), bits(52, 4), bits(44, 4)].map(|value| value * 0x11),
+            [bits(56, 4), bits(48, 4), bits(40, 4)].map(|value| value * 0x11),
+        ]
+    };
+    let tables = [bits(37, 3) as usize, bits(34, 3) as usize];
+
+    (0..16).map(move |index| {
+        // Texels are indexed column by column.
+        let (x, y) = (index / 4, index % 4);
+        let subblock = usize::from(if flipped { y >= 2 } else { x >= 2 });
+
+        let modifier = ETC1_MODIFIERS[tables[subblock]];
+        let modifier = match (bits(index as u32 + 16, 1), bits(index as u32, 1)) {
+            (0, 0) => modifier[0],
+            (0, _) => modifier[1],
+            (_, 0) => -modifier[0],
+            _ => -modifier[1],
+        };
+
+        let [r, g, b] = base_colors[subblock].map(|value| (value + modifier).clamp(0, 255) as u8);
+        let a = alpha.map_or(0xFF, |alpha| ((alpha >> (index * 4)) & 0xF) as u8 * 0x11);
+
+        (x, y, [r, g, b, a])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a BFLIM file from raw texture data.
+    fn bflim(width: u16, height: u16, format: TextureFormat, texture: &[u8]) -> Vec<u8> {
+        let mut file = texture.to_vec();
+        file.extend_from_slice(b"FLIM\xFF\xFE\x14\x00");
+        file.extend_from_slice(&0x0202_0000u32.to_le_bytes());
+        file.extend_from_slice(&((texture.len() + FOOTER_SIZE) as u32).to_le_bytes());
+        file.extend_from_slice(&[1, 0, 0, 0]);
+        file.extend_from_slice(b"imag\x10\x00\x00\x00");
+        file.extend_from_slice(&width.to_le_bytes());
+        file.extend_from_slice(&height.to_le_bytes());
+        file.extend_from_slice(&[0x80, 0, format as u8, 0]);
+        file.extend_from_slice(&(texture.len() as u32).to_le_bytes());
+        file
+    }
+
+    #[test]
+    fn tiled_formats() {
+        // Each RGBA8 texel holds its index within the tile in its red channel (texels are stored as ABGR).
+        let texture: Vec<u8> = (0..64u8).flat_map(|k| [0xFF, 0, 0, k]).collect();
+        let file = bflim(5, 3, TextureFormat::Rgba8, &texture);
+
+        let texture = Bflim::parse(&file).unwrap();
+        assert_eq!((texture.texture_width(), texture.texture_height()), (8, 8));
+
+        let image = texture.decode().unwrap();
+        assert_eq!(image.format(), PixelFormat::Rgba8);
+        assert_eq!((image.width(), image.height()), (5, 3));
+        // (1, 1) is the 4th texel along the Z-order curve, and (4, 2) the 24th.
+        assert_eq!(image.pixel(1, 1), Some([3, 0, 0, 0xFF]));
+        assert_eq!(image.pixel(4, 2), Some([24, 0, 0, 0xFF]));
+
+        // 4-bit formats store two texels per byte, the first one in the low nibble.
+        let file = bflim(8, 8, TextureFormat::L4, &[0xF0; 32]);
+        let image = Bflim::parse(&file).unwrap().decode().unwrap();
+        assert_eq!(image.format(), PixelFormat::L8);
+        assert_eq!(&image.data()[..2], [0, 0xFF]);
+
+        assert!(Bflim::parse(&file[16..]).is_err());
+
+        // Dimensions are checked against the data, without overflowing.
+        let file = bflim(u16::MAX, u16::MAX, TextureFormat::Rgba8, &[0; 256]);
+        assert!(Bflim::parse(&file).is_err());
+    }
+
+    #[test]
+    fn etc1_blocks() {
+        // Individual mode, both colours (0x88, 0x44, 0x00), first table, and alternating modifier indices.
+        let block = (0x8844_0000_u64 << 32) | 0x0000_AAAA;
+        let texels: Vec<_> = decode_etc1(block, Some(0xF)).collect();
+
+        assert_eq!(texels[0], (0, 0, [0x8A, 0x46, 0x02, 0xFF]));
+        assert_eq!(texels[1], (0, 1, [0x90, 0x4C, 0x08, 0]));
+    }
+}
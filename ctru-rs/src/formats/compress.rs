@@ -0,0 +1,515 @@
+//! Decompression of the Nintendo compression formats.
+//!
+//! Most first-party assets (and the tools producing them) compress data with the LZ77 variants of the console's BIOS
+//! (LZ10 and LZ11), or more rarely with its Huffman and run-length encodings. Files ported from other consoles
+//! may also use Yaz0. Every format starts with a header giving the size of the decompressed data,
+//! which [`Decompressor`] reads to detect the format.
+//!
+//! # Additional Resources
+//!
+//! - <https://problemkaputt.de/gbatek.htm#biosdecompressionfunctions>
+//! - <https://www.3dbrew.org/wiki/LZ>
+#![doc(alias = "lz10")]
+#![doc(alias = "lz11")]
+#![doc(alias = "lz77")]
+#![doc(alias = "yaz0")]
+
+use crate::Error;
+
+use std::io::{self, Read};
+
+/// Size of the sliding window of the LZ formats, which limits how far back data can be repeated.
+const WINDOW_SIZE: usize = 0x1000;
+
+/// Compression ratio assumed by [`decompress()`] when reserving its output.
+const INITIAL_RATIO: usize = 8;
+
+/// Compression format of a stream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// LZ77 variant with repetitions of up to 18 bytes (type `0x10`).
+    Lz10,
+    /// LZ77 variant with repetitions of up to 65808 bytes (type `0x11`).
+    Lz11,
+    /// Huffman coding of 4-bit symbols (type `0x24`).
+    Huffman4,
+    /// Huffman coding of 8-bit symbols (type `0x28`).
+    Huffman8,
+    /// Run-length encoding (type `0x30`).
+    Rle,
+    /// Yaz0 encoding, identified by its `Yaz0` magic.
+    Yaz0,
+}
+
+impl Compression {
+    /// Returns the format of `data` from its header, or [`None`] if it isn't compressed with any of the supported formats.
+    ///
+    /// # Notes
+    ///
+    /// The headers of the BIOS formats are only a byte long: uncompressed data may happen to start like one.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"Yaz0") {
+            return Some(Self::Yaz0);
+        }
+
+        match data.first()? {
+            0x10 => Some(Self::Lz10),
+            0x11 => Some(Self::Lz11),
+            0x24 => Some(Self::Huffman4),
+            0x28 => Some(Self::Huffman8),
+            0x30 => Some(Self::Rle),
+            _ => None,
+        }
+    }
+}
+
+/// Decoding state specific to the format.
+enum State {
+    /// LZ10, LZ11 and Yaz0 streams, made of flag bytes announcing whether the next 8 tokens are literals or repetitions.
+    Lz { flags: u8, flag_count: u8 },
+    /// Run-length encoded stream.
+    Rle,
+    /// Huffman stream, read as little-endian 32-bit words from their most significant bit.
+    Huffman {
+        tree: Vec<u8>,
+        word: u32,
+        bit_count: u8,
+    },
+}
+
+/// Streaming decompressor, reading compressed data from `R` and implementing [`Read`] for the decompressed data.
+///
+/// The decompressor keeps a window of the last 4 KiB of decompressed data, so arbitrarily big streams can be decompressed
+/// in small chunks (e.g. straight into the buffers of a [`Loader`](crate::assets::Loader) job).
+/// Reading from `R` is done one byte at a time: wrap it in a [`BufReader`](std::io::BufReader) unless it reads from memory.
+///
+/// # Example
+///
+/// ```
+/// use ctru::formats::compress::{Compression, Decompressor};
+/// use std::io::Read;
+///
+/// // "ABC" repeated 3 times, compressed with LZ10.
+/// let compressed: &[u8] = &[0x10, 9, 0, 0, 0x10, b'A', b'B', b'C', 0x30, 0x02];
+///
+/// let mut decompressor = Decompressor::new(compressed)?;
+/// assert_eq!(decompressor.compression(), Compression::Lz10);
+/// assert_eq!(decompressor.decompressed_size(), 9);
+///
+/// let mut text = String::new();
+/// decompressor.read_to_string(&mut text)?;
+/// assert_eq!(text, "ABCABCABC");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Decompressor<R> {
+    reader: R,
+    compression: Compression,
+    size: usize,
+    /// Number of bytes left to decompress.
+    remaining: usize,
+    state: State,
+    window: Box<[u8]>,
+    window_pos: usize,
+    /// Decompressed bytes not read yet, starting at `pending_pos`.
+    pending: Vec<u8>,
+    pending_pos: usize,
+}
+
+impl<R: Read> Decompressor<R> {
+    /// Read the header of a compressed stream, detecting its format.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error of kind [`io::ErrorKind::InvalidData`] if the stream isn't compressed
+    /// with any of the supported formats, or the error of `reader` if the header couldn't be read.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+
+        let compression = Compression::detect(&header)
+            .ok_or_else(|| invalid_data("unknown compression format"))?;
+
+        let size = if compression == Compression::Yaz0 {
+            let mut header = [0; 12];
+            reader.read_exact(&mut header)?;
+
+            u32::from_be_bytes(header[..4].try_into().unwrap())
+        } else {
+            let size = u32::from_le_bytes(header) >> 8;
+
+            // A size of 0 announces an extended 32-bit size (for data larger than 16 MiB).
+            if size == 0 {
+                read_u32(&mut reader)?
+            } else {
+                size
+            }
+        };
+
+        let state = match compression {
+            Compression::Lz10 | Compression::Lz11 | Compression::Yaz0 => State::Lz {
+                flags: 0,
+                flag_count: 0,
+            },
+            Compression::Rle => State::Rle,
+            Compression::Huffman4 | Compression::Huffman8 => {
+                // The tree size counts the size byte itself, in half-words.
+                let tree_size = read_u8(&mut reader)?;
+                let mut tree = vec![tree_size; (usize::from(tree_size) + 1) * 2];
+                reader.read_exact(&mut tree[1..])?;
+
+                State::Huffman {
+                    tree,
+                    word: 0,
+                    bit_count: 0,
+                }
+            }
+        };
+
+        let size = size as usize;
+
+        Ok(Self {
+            reader,
+            compression,
+            size,
+            remaining: size,
+            state,
+            window: vec![0; WINDOW_SIZE].into_boxed_slice(),
+            window_pos: 0,
+            pending: Vec::new(),
+            pending_pos: 0,
+        })
+    }
+
+    /// Returns the compression format of the stream.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Returns the size (in bytes) of the decompressed data.
+    pub fn decompressed_size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the underlying reader.
+    ///
+    /// # Notes
+    ///
+    /// Compressed streams are often padded: the reader may not be at the end of the stream even if all data was decompressed.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Append a decompressed byte to the pending data, unless the whole stream was decompressed already.
+    fn push(&mut self, byte: u8) {
+        if self.remaining == 0 {
+            return;
+        }
+
+        self.remaining -= 1;
+        self.pending.push(byte);
+        self.window[self.window_pos] = byte;
+        self.window_pos = (self.window_pos + 1) % WINDOW_SIZE;
+    }
+
+    /// Repeat `len` bytes starting `distance` bytes back in the decompressed data.
+    fn repeat(&mut self, len: usize, distance: usize) -> io::Result<()> {
+        if distance > self.size - self.remaining || distance > WINDOW_SIZE {
+            return Err(invalid_data(
+                "repetition goes back before the start of the data",
+            ));
+        }
+
+        let mut source = (self.window_pos + WINDOW_SIZE - distance) % WINDOW_SIZE;
+        for _ in 0..len {
+            self.push(self.window[source]);
+            source = (source + 1) % WINDOW_SIZE;
+        }
+
+        Ok(())
+    }
+
+    /// Decompress the next token of the stream into the pending data.
+    fn fill(&mut self) -> io::Result<()> {
+        match &mut self.state {
+            State::Lz { flags, flag_count } => {
+                if *flag_count == 0 {
+                    *flags = read_u8(&mut self.reader)?;
+                    *flag_count = 8;
+                }
+
+                let bit = *flags & 0x80 != 0;
+                *flags <<= 1;
+                *flag_count -= 1;
+
+                // Yaz0 flags literals, the BIOS formats flag repetitions.
+                if bit == (self.compression == Compression::Yaz0) {
+                    let byte = read_u8(&mut self.reader)?;
+                    self.push(byte);
+                    return Ok(());
+                }
+
+                let (len, distance) = self.read_repetition()?;
+                self.repeat(len, distance)
+            }
+            State::Rle => {
+                let flag = read_u8(&mut self.reader)?;
+                let len = usize::from(flag & 0x7F);
+
+                if flag & 0x80 != 0 {
+                    let byte = read_u8(&mut self.reader)?;
+                    for _ in 0..len + 3 {
+                        self.push(byte);
+                    }
+                } else {
+                    for _ in 0..len + 1 {
+                        let byte = read_u8(&mut self.reader)?;
+                        self.push(byte);
+                    }
+                }
+
+                Ok(())
+            }
+            State::Huffman { .. } => {
+                let byte = if self.compression == Compression::Huffman4 {
+                    // The first symbol goes into the low nibble.
+                    let low = self.read_symbol()?;
+                    let high = self.read_symbol()?;
+                    (low & 0xF) | (high << 4)
+                } else {
+                    self.read_symbol()?
+                };
+
+                self.push(byte);
+                Ok(())
+            }
+        }
+    }
+
+    /// Read the length and distance of an LZ repetition.
+    fn read_repetition(&mut self) -> io::Result<(usize, usize)> {
+        let first = usize::from(read_u8(&mut self.reader)?);
+        let second = usize::from(read_u8(&mut self.reader)?);
+
+        let repetition = match self.compression {
+            Compression::Lz10 => ((first >> 4) + 3, ((first & 0xF) << 8 | second) + 1),
+            Compression::Lz11 => match first >> 4 {
+                0 => {
+                    let third = usize::from(read_u8(&mut self.reader)?);
+
+                    (
+                        ((first & 0xF) << 4 | second >> 4) + 0x11,
+                        ((second & 0xF) << 8 | third) + 1,
+                    )
+                }
+                1 => {
+                    let third = usize::from(read_u8(&mut self.reader)?);
+                    let fourth = usize::from(read_u8(&mut self.reader)?);
+
+                    (
+                        ((first & 0xF) << 12 | second << 4 | third >> 4) + 0x111,
+                        ((third & 0xF) << 8 | fourth) + 1,
+                    )
+                }
+                count => (count + 1, ((first & 0xF) << 8 | second) + 1),
+            },
+            _ => {
+                let distance = ((first & 0xF) << 8 | second) + 1;
+
+                match first >> 4 {
+                    0 => (usize::from(read_u8(&mut self.reader)?) + 0x12, distance),
+                    count => (count + 2, distance),
+                }
+            }
+        };
+
+        Ok(repetition)
+    }
+
+    /// Walk the Huffman tree from its root to a leaf, returning the symbol of the leaf.
+    fn read_symbol(&mut self) -> io::Result<u8> {
+        let State::Huffman {
+            tree,
+            word,
+            bit_count,
+        } = &mut self.state
+        else {
+            unreachable!()
+        };
+
+        // The root node directly follows the size byte.
+        let mut node = 1;
+
+        loop {
+            if *bit_count == 0 {
+                *word = read_u32(&mut self.reader)?;
+                *bit_count = 32;
+            }
+
+            let bit = *word >> 31;
+            *word <<= 1;
+            *bit_count -= 1;
+
+            let value = *tree
+                .get(node)
+                .ok_or_else(|| invalid_data("Huffman tree node out of bounds"))?;
+
+            // Children come in pairs, at an offset counted in half-words from the pair containing the node.
+            let child = (node & !1) + usize::from(value & 0x3F) * 2 + 2 + bit as usize;
+            let is_leaf = value & if bit == 0 { 0x80 } else { 0x40 } != 0;
+
+            if is_leaf {
+                return tree
+                    .get(child)
+                    .copied()
+                    .ok_or_else(|| invalid_data("Huffman tree leaf out of bounds"));
+            }
+
+            node = child;
+        }
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending_pos == self.pending.len() {
+            if self.remaining == 0 {
+                return Ok(0);
+            }
+
+            self.pending.clear();
+            self.pending_pos = 0;
+            self.fill()?;
+        }
+
+        let len = buf.len().min(self.pending.len() - self.pending_pos);
+        buf[..len].copy_from_slice(&self.pending[self.pending_pos..][..len]);
+        self.pending_pos += len;
+
+        Ok(len)
+    }
+}
+
+/// Decompress `data`, detecting its format.
+///
+/// # Errors
+///
+/// This function will return an error if `data` isn't compressed with one of the supported formats, or is truncated or corrupted.
+pub fn decompress(data: &[u8]) -> crate::Result<Vec<u8>> {
+    let mut decompressor = Decompressor::new(data)?;
+
+    // The header's size isn't trusted for the initial allocation: the vector grows as needed past this typical ratio.
+    let capacity = decompressor
+        .decompressed_size()
+        .min(data.len().saturating_mul(INITIAL_RATIO));
+    let mut output = Vec::with_capacity(capacity);
+    decompressor.read_to_end(&mut output)?;
+
+    check_complete(&decompressor, output.len())?;
+
+    Ok(output)
+}
+
+/// Decompress `data` into `buffer` (e.g. a [LINEAR](crate::linear) buffer), detecting its format.
+///
+/// Returns the size of the decompressed data, which can be checked beforehand with [`Decompressor::decompressed_size()`].
+///
+/// # Errors
+///
+/// This function will return [`Error::BufferTooShort`] if the decompressed data doesn't fit in `buffer`,
+/// or another error if `data` isn't compressed with one of the supported formats, or is truncated or corrupted.
+pub fn decompress_into(data: &[u8], buffer: &mut [u8]) -> crate::Result<usize> {
+    let mut decompressor = Decompressor::new(data)?;
+    let size = decompressor.decompressed_size();
+
+    if buffer.len() < size {
+        return Err(Error::BufferTooShort {
+            provided: buffer.len(),
+            wanted: size,
+        });
+    }
+
+    decompressor.read_exact(&mut buffer[..size])?;
+
+    Ok(size)
+}
+
+fn check_complete<R>(decompressor: &Decompressor<R>, len: usize) -> crate::Result<()> {
+    if len != decompressor.size {
+        return Err(Error::Other(format!(
+            "decompressed {len} bytes instead of {}",
+            decompressor.size
+        )));
+    }
+
+    Ok(())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+
+    Ok(byte[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut word = [0; 4];
+    reader.read_exact(&mut word)?;
+
+    Ok(u32::from_le_bytes(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_format() {
+        let lz10 = [0x10, 9, 0, 0, 0x10, b'A', b'B', b'C', 0x30, 0x02];
+        assert_eq!(decompress(&lz10).unwrap(), b"ABCABCABC");
+
+        let lz11 = [0x11, 9, 0, 0, 0x10, b'A', b'B', b'C', 0x50, 0x02];
+        assert_eq!(decompress(&lz11).unwrap(), b"ABCABCABC");
+
+        // A single literal repeated with the 3-byte form.
+        let lz11_long = [0x11, 0x21, 0, 0, 0x40, b'A', 0x00, 0xF0, 0x00];
+        assert_eq!(decompress(&lz11_long).unwrap(), [b'A'; 0x21]);
+
+        let rle = [0x30, 6, 0, 0, 0x82, b'A', 0x00, b'B'];
+        assert_eq!(decompress(&rle).unwrap(), b"AAAAAB");
+
+        let mut yaz0 = b"Yaz0\0\0\0\x09".to_vec();
+        yaz0.extend_from_slice(&[0; 8]);
+        yaz0.extend_from_slice(&[0xE0, b'A', b'B', b'C', 0x40, 0x02]);
+        assert_eq!(decompress(&yaz0).unwrap(), b"ABCABCABC");
+
+        let huffman8 = [0x28, 4, 0, 0, 0x01, 0xC0, b'A', b'B', 0, 0, 0, 0x50];
+        assert_eq!(decompress(&huffman8).unwrap(), b"ABAB");
+
+        let huffman4 = [0x24, 1, 0, 0, 0x01, 0xC0, 0x01, 0x02, 0, 0, 0, 0x40];
+        assert_eq!(decompress(&huffman4).unwrap(), [0x21]);
+    }
+
+    #[test]
+    fn corrupted_streams() {
+        // Repetition before the start of the data.
+        assert!(decompress(&[0x10, 3, 0, 0, 0x80, 0x00, 0x00]).is_err());
+        // Truncated stream.
+        assert!(decompress(&[0x10, 9, 0, 0, 0x10, b'A']).is_err());
+        assert!(decompress(b"not compressed").is_err());
+        // Huge decompressed size, without the data to back it up.
+        assert!(decompress(&[0x10, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]).is_err());
+
+        let mut buffer = [0; 4];
+        assert!(matches!(
+            decompress_into(&[0x30, 6, 0, 0, 0x82, b'A', 0x00, b'B'], &mut buffer),
+            Err(Error::BufferTooShort {
+                provided: 4,
+                wanted: 6
+            })
+        ));
+    }
+}
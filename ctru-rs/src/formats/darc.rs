@@ -0,0 +1,293 @@
+//! DARC archives.
+//!
+//! DARC is the archive format of the HOME Menu's layouts and of many applications' `.arc` files
+//! (often [compressed](super::compress) with LZ11). [`Darc`] reads the directory tree of an archive held in memory
+//! and gives access to its files without copying them.
+//!
+//! # Additional Resources
+//!
+//! - <https://www.3dbrew.org/wiki/DARC>
+#![doc(alias = "arc")]
+
+use super::{add_offset, bytes, expect_magic, Endian};
+use crate::Error;
+
+/// Size of the archive header.
+const HEADER_SIZE: usize = 0x1C;
+
+/// Size of an entry of the file table.
+const ENTRY_SIZE: usize = 12;
+
+/// Flag set in the name offset of directory entries.
+const DIRECTORY_FLAG: u32 = 0x0100_0000;
+
+/// File stored in a [`Darc`] archive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DarcFile<'a> {
+    path: &'a str,
+    data: &'a [u8],
+}
+
+impl<'a> DarcFile<'a> {
+    /// Returns the path of the file within the archive, with `/` separators and without a leading slash (e.g. `timg/icon.bclim`).
+    pub fn path(&self) -> &'a str {
+        self.path
+    }
+
+    /// Returns the contents of the file.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+/// Parsed DARC archive, borrowing the archive's bytes.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::formats::{compress, darc::Darc};
+///
+/// let data = compress::decompress(&std::fs::read("romfs:/layout.arc.lz")?)?;
+/// let archive = Darc::parse(&data)?;
+///
+/// for file in archive.files() {
+///     println!("{} ({} bytes)", file.path(), file.data().len());
+/// }
+///
+/// let mut buffer = vec![0; 0x10000];
+/// let len = archive.extract("timg/icon.bclim", &mut buffer)?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Darc<'a> {
+    // Paths are stored together, and files refer to them by range.
+    paths: String,
+    files: Vec<(std::ops::Range<usize>, &'a [u8])>,
+}
+
+impl<'a> Darc<'a> {
+    /// Parse the directory tree of the archive in `data`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `data` isn't a valid DARC archive.
+    pub fn parse(data: &'a [u8]) -> crate::Result<Self> {
+        expect_magic(data, 0, b"darc")?;
+        let endian = Endian::from_bom(data, 4)?;
+
+        let header_size = usize::from(endian.u16(data, 6)?);
+        if header_size < HEADER_SIZE {
+            return Err(Error::Other(String::from("invalid DARC header size")));
+        }
+
+        let table = endian.u32(data, 0x10)? as usize;
+
+        // The root directory's "next entry" index is the total amount of entries.
+        let count = endian.u32(data, add_offset(table, 8)?)? as usize;
+        let names = count
+            .checked_mul(ENTRY_SIZE)
+            .and_then(|size| table.checked_add(size))
+            .ok_or_else(|| Error::Other(String::from("invalid DARC entry count")))?;
+        bytes(data, table, names - table)?;
+
+        let mut paths = String::new();
+        let mut files = Vec::new();
+        // Directories being walked: index of the entry after their contents, and length of their path.
+        let mut directories: Vec<(usize, usize)> = Vec::new();
+        let mut prefix = String::new();
+
+        for index in 1..count {
+            // The whole table was checked to fit in `data`.
+            let entry = table + index * ENTRY_SIZE;
+            let name_and_flags = endian.u32(data, entry)?;
+            let name = read_name(
+                data,
+                endian,
+                add_offset(names, (name_and_flags & !0xFF00_0000) as usize)?,
+            )?;
+
+            while directories.last().is_some_and(|&(end, _)| index >= end) {
+                let (_, len) = directories.pop().unwrap();
+                prefix.truncate(len);
+            }
+
+            if name_and_flags & DIRECTORY_FLAG != 0 {
+                let end = endian.u32(data, entry + 8)? as usize;
+                directories.push((end, prefix.len()));
+
+                // Archives usually nest their contents in a `.` directory, which isn't part of the paths.
+                if !name.is_empty() && name != "." {
+                    prefix.push_str(&name);
+                    prefix.push('/');
+                }
+            } else {
+                let offset = endian.u32(data, entry + 4)? as usize;
+                let size = endian.u32(data, entry + 8)? as usize;
+
+                let start = paths.len();
+                paths.push_str(&prefix);
+                paths.push_str(&name);
+
+                files.push((start..paths.len(), bytes(data, offset, size)?));
+            }
+        }
+
+        Ok(Self { paths, files })
+    }
+
+    /// Returns an iterator over the files of the archive, in the order they are stored.
+    pub fn files(&self) -> impl ExactSizeIterator<Item = DarcFile<'_>> + '_ {
+        self.files.iter().map(|(path, data)| DarcFile {
+            path: &self.paths[path.clone()],
+            data,
+        })
+    }
+
+    /// Returns the number of files in the archive.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns `true` if the archive doesn't contain any file.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns the contents of the file at `path` (see [`DarcFile::path()`]), or [`None`] if there is no such file.
+    pub fn get(&self, path: &str) -> Option<&'a [u8]> {
+        let path = path.trim_start_matches('/');
+
+        self.files
+            .iter()
+            .find(|(name, _)| &self.paths[name.clone()] == path)
+            .map(|&(_, data)| data)
+    }
+
+    /// Copy the contents of the file at `path` into `buffer`, returning its size.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`Error::BufferTooShort`] if the file doesn't fit in `buffer`,
+    /// or another error if there is no such file.
+    pub fn extract(&self, path: &str, buffer: &mut [u8]) -> crate::Result<usize> {
+        let data = self
+            .get(path)
+            .ok_or_else(|| Error::Other(format!("no file `{path}` in the archive")))?;
+
+        super::extract(data, buffer)
+    }
+}
+
+/// Read a NUL-terminated UTF-16 name, in the archive's byte order, from the name table.
+fn read_name(data: &[u8], endian: Endian, offset: usize) -> crate::Result<String> {
+    let units: Vec<u16> = data
+        .get(offset..)
+        .ok_or_else(|| Error::Other(String::from("DARC name out of bounds")))?
+        .chunks_exact(2)
+        .map(|unit| endian.u16_from_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+
+    Ok(String::from_utf16_lossy(&units))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(name: &str) -> Vec<u8> {
+        name.encode_utf16()
+            .chain(std::iter::once(0))
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn directory_tree() {
+        // Root, ".", "a.bin", "dir", "dir/b.bin", "c.bin" (after "dir" ends).
+        let mut names = Vec::new();
+        let mut name_offsets = Vec::new();
+        for name in ["", ".", "a.bin", "dir", "b.bin", "c.bin"] {
+            name_offsets.push(names.len() as u32);
+            names.extend(utf16(name));
+        }
+
+        let table = HEADER_SIZE as u32;
+        let data_offset = table + 6 * ENTRY_SIZE as u32 + names.len() as u32;
+        let entries: [(u32, u32, u32); 6] = [
+            (name_offsets[0] | DIRECTORY_FLAG, 0, 6),
+            (name_offsets[1] | DIRECTORY_FLAG, 0, 6),
+            (name_offsets[2], data_offset, 2),
+            (name_offsets[3] | DIRECTORY_FLAG, 1, 5),
+            (name_offsets[4], data_offset + 2, 1),
+            (name_offsets[5], data_offset + 3, 1),
+        ];
+
+        let mut archive = b"darc\xFF\xFE\x1C\x00".to_vec();
+        archive.extend_from_slice(&0x0100_0000u32.to_le_bytes());
+        archive.extend_from_slice(&[0; 4]);
+        archive.extend_from_slice(&table.to_le_bytes());
+        archive.extend_from_slice(&[0; 8]);
+        for (name, offset, size) in entries {
+            for word in [name, offset, size] {
+                archive.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        archive.extend(names);
+        archive.extend_from_slice(b"AABC");
+
+        let darc = Darc::parse(&archive).unwrap();
+        let files: Vec<_> = darc
+            .files()
+            .map(|file| (file.path(), file.data()))
+            .collect();
+        assert_eq!(
+            files,
+            [("a.bin", &b"AA"[..]), ("dir/b.bin", b"B"), ("c.bin", b"C")]
+        );
+
+        assert_eq!(darc.get("/dir/b.bin"), Some(&b"B"[..]));
+
+        let mut buffer = [0; 1];
+        assert!(matches!(
+            darc.extract("a.bin", &mut buffer),
+            Err(Error::BufferTooShort {
+                provided: 1,
+                wanted: 2
+            })
+        ));
+        assert!(darc.extract("missing", &mut buffer).is_err());
+    }
+
+    #[test]
+    fn big_endian() {
+        // Root and "a.bin", with the names in big endian UTF-16.
+        let names: Vec<u8> = [0, 0, 0, b'a', 0, b'.', 0, b'b', 0, b'i', 0, b'n', 0, 0].to_vec();
+        let table = HEADER_SIZE as u32;
+        let data_offset = table + 2 * ENTRY_SIZE as u32 + names.len() as u32;
+
+        let mut archive = b"darc\xFE\xFF\x00\x1C".to_vec();
+        archive.extend_from_slice(&0x0100_0000u32.to_be_bytes());
+        archive.extend_from_slice(&[0; 4]);
+        archive.extend_from_slice(&table.to_be_bytes());
+        archive.extend_from_slice(&[0; 8]);
+        for word in [DIRECTORY_FLAG, 0, 2, 2, data_offset, 1] {
+            archive.extend_from_slice(&word.to_be_bytes());
+        }
+        archive.extend(names);
+        archive.push(b'A');
+
+        let darc = Darc::parse(&archive).unwrap();
+        assert_eq!(darc.get("a.bin"), Some(&b"A"[..]));
+
+        // Offsets overflowing the address space are rejected instead of wrapping around.
+        archive[0x10..0x14].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(Darc::parse(&archive).is_err());
+    }
+}
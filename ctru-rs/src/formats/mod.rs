@@ -0,0 +1,93 @@
+//! Parsers for the file formats used by the system and first-party assets.
+//!
+//! These modules only deal with the bytes of the files: they can be fed from [`std::fs`], the [RomFS](crate::services::romfs)
+//! or memory, and don't need any service to be running.
+#![doc(alias = "file formats")]
+
+pub mod bcstm;
+pub mod bcwav;
+pub mod bflim;
+pub mod compress;
+pub mod darc;
+mod nw4c;
+pub mod sarc;
+
+use crate::Error;
+
+/// Byte order of a file, given by its byte order mark.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    /// Returns the byte order of a file from the bytes of its byte order mark (`0xFEFF` in the file's own byte order).
+    pub(crate) fn from_bom(data: &[u8], offset: usize) -> crate::Result<Self> {
+        match bytes(data, offset, 2)? {
+            [0xFF, 0xFE] => Ok(Self::Little),
+            [0xFE, 0xFF] => Ok(Self::Big),
+            _ => Err(Error::Other(String::from("invalid byte order mark"))),
+        }
+    }
+
+    pub(crate) fn u16(self, data: &[u8], offset: usize) -> crate::Result<u16> {
+        Ok(self.u16_from_bytes(bytes(data, offset, 2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u16_from_bytes(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Self::Little => u16::from_le_bytes(bytes),
+            Self::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    pub(crate) fn u32(self, data: &[u8], offset: usize) -> crate::Result<u32> {
+        let bytes = bytes(data, offset, 4)?.try_into().unwrap();
+
+        Ok(match self {
+            Self::Little => u32::from_le_bytes(bytes),
+            Self::Big => u32::from_be_bytes(bytes),
+        })
+    }
+}
+
+/// Returns `len` bytes of `data` starting at `offset`, or an error if the file is too short.
+pub(crate) fn bytes(data: &[u8], offset: usize, len: usize) -> crate::Result<&[u8]> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(|| Error::Other(String::from("unexpected end of file")))
+}
+
+/// Returns `base + offset`, or an error if a corrupted offset makes the sum overflow.
+pub(crate) fn add_offset(base: usize, offset: usize) -> crate::Result<usize> {
+    base.checked_add(offset)
+        .ok_or_else(|| Error::Other(String::from("unexpected end of file")))
+}
+
+/// Check that `data` starts with `magic` at `offset`.
+pub(crate) fn expect_magic(data: &[u8], offset: usize, magic: &[u8]) -> crate::Result<()> {
+    if bytes(data, offset, magic.len())? != magic {
+        return Err(Error::Other(format!(
+            "missing `{}` magic",
+            String::from_utf8_lossy(magic)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Copy `data` to the start of `buffer`, returning its length.
+pub(crate) fn extract(data: &[u8], buffer: &mut [u8]) -> crate::Result<usize> {
+    if buffer.len() < data.len() {
+        return Err(Error::BufferTooShort {
+            provided: buffer.len(),
+            wanted: data.len(),
+        });
+    }
+
+    buffer[..data.len()].copy_from_slice(data);
+
+    Ok(data.len())
+}
@@ -0,0 +1,224 @@
+//! Structures shared by the NW4C audio formats (BCWAV and BCSTM).
+
+use super::{bytes, Endian};
+use crate::Error;
+
+/// Encoding of the samples of a BCWAV or BCSTM file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Signed 8-bit PCM.
+    Pcm8,
+    /// Signed 16-bit PCM.
+    Pcm16,
+    /// Nintendo's 4-bit DSP-ADPCM, decoded to 16-bit samples.
+    DspAdpcm,
+    /// IMA-ADPCM, which isn't supported.
+    ImaAdpcm,
+}
+
+impl Encoding {
+    pub(crate) fn from_raw(raw: u8) -> crate::Result<Self> {
+        match raw {
+            0 => Ok(Self::Pcm8),
+            1 => Ok(Self::Pcm16),
+            2 => Ok(Self::DspAdpcm),
+            3 => Ok(Self::ImaAdpcm),
+            _ => Err(Error::Other(format!("unknown sound encoding {raw}"))),
+        }
+    }
+
+    /// Returns an error for the encodings which can't be decoded.
+    pub(crate) fn check_supported(self) -> crate::Result<()> {
+        if self == Self::ImaAdpcm {
+            return Err(Error::Other(String::from(
+                "IMA-ADPCM sounds are not supported",
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the size in bytes of the encoded data of `samples` samples of a channel.
+    ///
+    /// Returns an error if the size doesn't fit in memory.
+    pub(crate) fn data_size(self, samples: usize) -> crate::Result<usize> {
+        match self {
+            Self::Pcm8 => Some(samples),
+            Self::Pcm16 => samples.checked_mul(2),
+            // Frames of 8 bytes hold 14 samples.
+            Self::DspAdpcm | Self::ImaAdpcm => samples.div_ceil(14).checked_mul(8),
+        }
+        .ok_or_else(|| Error::Other(String::from("the sample data is too large")))
+    }
+}
+
+/// Reads a reference (a type and an offset relative to a position given by the format) at `offset`.
+pub(crate) fn reference(endian: Endian, data: &[u8], offset: usize) -> crate::Result<(u16, usize)> {
+    Ok((
+        endian.u16(data, offset)?,
+        endian.u32(data, offset + 4)? as usize,
+    ))
+}
+
+/// Reads the reference at `offset`, checking its type.
+pub(crate) fn expect_reference(
+    endian: Endian,
+    data: &[u8],
+    offset: usize,
+    kind: u16,
+) -> crate::Result<usize> {
+    match reference(endian, data, offset)? {
+        (found, offset) if found == kind => Ok(offset),
+        (found, _) => Err(Error::Other(format!(
+            "expected a reference of type {kind:#06X}, found {found:#06X}"
+        ))),
+    }
+}
+
+/// Returns the offset and size of the block of type `kind`, from the block references following the file header.
+pub(crate) fn block(endian: Endian, data: &[u8], kind: u16) -> crate::Result<(usize, usize)> {
+    let count = usize::from(endian.u16(data, 0x10)?);
+
+    for index in 0..count {
+        let (found, offset) = reference(endian, data, 0x14 + index * 12)?;
+
+        if found == kind {
+            return Ok((offset, endian.u32(data, 0x14 + index * 12 + 8)? as usize));
+        }
+    }
+
+    Err(Error::Other(format!("missing block of type {kind:#06X}")))
+}
+
+/// DSP-ADPCM decoder state of a channel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct AdpcmDecoder {
+    coefficients: [i16; 16],
+    /// Last two decoded samples, most recent first.
+    pub(crate) history: [i16; 2],
+    /// History at the loop start.
+    pub(crate) loop_history: [i16; 2],
+}
+
+impl AdpcmDecoder {
+    /// Reads the DSP-ADPCM information of a channel: coefficients, then initial and loop contexts.
+    pub(crate) fn parse(endian: Endian, data: &[u8], offset: usize) -> crate::Result<Self> {
+        let mut coefficients = [0; 16];
+        for (index, coefficient) in coefficients.iter_mut().enumerate() {
+            *coefficient = endian.u16(data, offset + index * 2)? as i16;
+        }
+
+        let sample = |at| endian.u16(data, offset + at).map(|sample| sample as i16);
+
+        Ok(Self {
+            coefficients,
+            history: [sample(0x22)?, sample(0x24)?],
+            loop_history: [sample(0x28)?, sample(0x2A)?],
+        })
+    }
+
+    /// Decode `samples` samples from DSP-ADPCM frames, appending them to `output`.
+    pub(crate) fn decode(
+        &mut self,
+        data: &[u8],
+        samples: usize,
+        output: &mut Vec<i16>,
+    ) -> crate::Result<()> {
+        let frames = bytes(data, 0, samples.div_ceil(14) * 8)?.chunks_exact(8);
+        let mut left = samples;
+
+        for frame in frames {
+            let predictor = usize::from(frame[0] >> 4) & 7;
+            // Intermediate values are kept in 64 bits: the prediction alone can exceed the range of an `i32`.
+            let scale = 1i64 << (frame[0] & 0xF);
+            let coefficient1 = i64::from(self.coefficients[predictor * 2]);
+            let coefficient2 = i64::from(self.coefficients[predictor * 2 + 1]);
+
+            for &byte in &frame[1..] {
+                for nibble in [byte >> 4, byte & 0xF] {
+                    if left == 0 {
+                        return Ok(());
+                    }
+
+                    // Sign-extend the nibble.
+                    let nibble = i64::from((nibble << 4) as i8 >> 4);
+                    let prediction = coefficient1 * i64::from(self.history[0])
+                        + coefficient2 * i64::from(self.history[1]);
+                    let sample = ((((nibble * scale) << 11) + 1024 + prediction) >> 11)
+                        .clamp(i16::MIN.into(), i16::MAX.into())
+                        as i16;
+
+                    self.history = [sample, self.history[0]];
+                    output.push(sample);
+                    left -= 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Decode `samples` samples of a channel, appending them to `output` as 16-bit PCM.
+pub(crate) fn decode_channel(
+    encoding: Encoding,
+    endian: Endian,
+    data: &[u8],
+    samples: usize,
+    decoder: &mut AdpcmDecoder,
+    output: &mut Vec<i16>,
+) -> crate::Result<()> {
+    let data = bytes(data, 0, encoding.data_size(samples)?)?;
+
+    match encoding {
+        Encoding::Pcm8 => output.extend(data.iter().map(|&sample| i16::from(sample as i8) << 8)),
+        Encoding::Pcm16 => {
+            output.extend((0..samples).map(|index| endian.u16(data, index * 2).unwrap() as i16))
+        }
+        Encoding::DspAdpcm => decoder.decode(data, samples, output)?,
+        Encoding::ImaAdpcm => encoding.check_supported()?,
+    }
+
+    Ok(())
+}
+
+/// Interleave the samples of each channel.
+pub(crate) fn interleave(channels: &[Vec<i16>], output: &mut Vec<i16>) {
+    let frames = channels.iter().map(Vec::len).min().unwrap_or(0);
+
+    output.extend((0..frames).flat_map(|frame| channels.iter().map(move |channel| channel[frame])));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adpcm_decoding() {
+        let mut decoder = AdpcmDecoder::default();
+        let mut output = Vec::new();
+
+        // Predictor 0 without coefficients, scale 1: samples are the nibbles themselves.
+        let frame = [0x00, 0x12, 0xF7, 0, 0, 0, 0, 0];
+        decoder.decode(&frame, 4, &mut output).unwrap();
+        assert_eq!(output, [1, 2, -1, 7]);
+
+        // With a first coefficient of 1.0, each sample adds up to the previous one.
+        decoder.coefficients[0] = 2048;
+        decoder.history = [0, 0];
+        output.clear();
+        decoder
+            .decode(&[0x01, 0x11, 0, 0, 0, 0, 0, 0], 3, &mut output)
+            .unwrap();
+        assert_eq!(output, [2, 4, 4]);
+
+        // Extreme coefficients and history saturate instead of overflowing.
+        decoder.coefficients[..2].copy_from_slice(&[i16::MAX, i16::MAX]);
+        decoder.history = [i16::MAX, i16::MAX];
+        output.clear();
+        decoder
+            .decode(&[0x0F, 0x70, 0, 0, 0, 0, 0, 0], 1, &mut output)
+            .unwrap();
+        assert_eq!(output, [i16::MAX]);
+    }
+}
@@ -0,0 +1,254 @@
+//! SARC archives.
+//!
+//! SARC is the archive format of many first-party titles' assets (`.sarc`, and often `.szs` once [compressed](super::compress)
+//! with Yaz0). Files are indexed by the hash of their name, and names themselves are optional.
+//! [`Sarc`] reads the file table of an archive held in memory and gives access to its files without copying them.
+//!
+//! # Additional Resources
+//!
+//! - <https://www.3dbrew.org/wiki/SARC>
+#![doc(alias = "szs")]
+
+use super::{add_offset, bytes, expect_magic, Endian};
+use crate::Error;
+
+/// Size of an entry of the file table.
+const NODE_SIZE: usize = 0x10;
+
+/// Flag set in the attributes of files whose name is stored in the name table.
+const HAS_NAME: u32 = 0x0100_0000;
+
+/// File stored in a [`Sarc`] archive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SarcFile<'a> {
+    hash: u32,
+    name: Option<&'a str>,
+    data: &'a [u8],
+}
+
+impl<'a> SarcFile<'a> {
+    /// Returns the hash of the file's name (see [`Sarc::hash()`]).
+    pub fn hash(&self) -> u32 {
+        self.hash
+    }
+
+    /// Returns the name of the file, if the archive stores it.
+    pub fn name(&self) -> Option<&'a str> {
+        self.name
+    }
+
+    /// Returns the contents of the file.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+/// Parsed SARC archive, borrowing the archive's bytes.
+///
+/// Both little-endian (3DS) and big-endian (Wii U) archives are supported.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::formats::{compress, sarc::Sarc};
+///
+/// let data = compress::decompress(&std::fs::read("romfs:/layout.szs")?)?;
+/// let archive = Sarc::parse(&data)?;
+///
+/// for file in archive.files() {
+///     println!("{:08X} {:?}", file.hash(), file.name());
+/// }
+///
+/// let layout = archive.get("blyt/main.bclyt");
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sarc<'a> {
+    hash_key: u32,
+    /// Files sorted by hash, as stored in the archive.
+    files: Vec<SarcFile<'a>>,
+}
+
+impl<'a> Sarc<'a> {
+    /// Parse the file table of the archive in `data`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `data` isn't a valid SARC archive.
+    pub fn parse(data: &'a [u8]) -> crate::Result<Self> {
+        expect_magic(data, 0, b"SARC")?;
+        let endian = Endian::from_bom(data, 6)?;
+
+        let sfat = usize::from(endian.u16(data, 4)?);
+        let data_offset = endian.u32(data, 0x0C)? as usize;
+
+        expect_magic(data, sfat, b"SFAT")?;
+        let node_count = usize::from(endian.u16(data, sfat + 6)?);
+        let hash_key = endian.u32(data, sfat + 8)?;
+        let nodes = add_offset(sfat, usize::from(endian.u16(data, sfat + 4)?))?;
+
+        let sfnt = add_offset(nodes, node_count * NODE_SIZE)?;
+        expect_magic(data, sfnt, b"SFNT")?;
+        let names = sfnt + usize::from(endian.u16(data, sfnt + 4)?);
+
+        let files = (0..node_count)
+            .map(|index| {
+                let node = nodes + index * NODE_SIZE;
+
+                let hash = endian.u32(data, node)?;
+                let attributes = endian.u32(data, node + 4)?;
+                let start = endian.u32(data, node + 8)? as usize;
+                let end = endian.u32(data, node + 12)? as usize;
+
+                let name = if attributes & HAS_NAME != 0 {
+                    // Names are aligned to 4 bytes, and their offset is counted in words.
+                    Some(read_name(
+                        data,
+                        add_offset(names, (attributes & 0xFFFF) as usize * 4)?,
+                    )?)
+                } else {
+                    None
+                };
+
+                let len = end
+                    .checked_sub(start)
+                    .ok_or_else(|| Error::Other(String::from("invalid SARC file bounds")))?;
+
+                Ok(SarcFile {
+                    hash,
+                    name,
+                    data: bytes(data, add_offset(data_offset, start)?, len)?,
+                })
+            })
+            .collect::<crate::Result<_>>()?;
+
+        Ok(Self { hash_key, files })
+    }
+
+    /// Returns the hash of `name` used by this archive to index its files.
+    pub fn hash(&self, name: &str) -> u32 {
+        name_hash(name, self.hash_key)
+    }
+
+    /// Returns an iterator over the files of the archive, sorted by hash.
+    pub fn files(&self) -> impl ExactSizeIterator<Item = SarcFile<'a>> + '_ {
+        self.files.iter().copied()
+    }
+
+    /// Returns the number of files in the archive.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns `true` if the archive doesn't contain any file.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns the contents of the file called `name`, or [`None`] if there is no such file.
+    ///
+    /// The file is looked up by the hash of its name, so this also works for archives which don't store names.
+    pub fn get(&self, name: &str) -> Option<&'a [u8]> {
+        let hash = self.hash(name);
+
+        let index = self
+            .files
+            .binary_search_by_key(&hash, |file| file.hash)
+            .ok()?;
+
+        Some(self.files[index].data)
+    }
+
+    /// Copy the contents of the file called `name` into `buffer`, returning its size.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`Error::BufferTooShort`] if the file doesn't fit in `buffer`,
+    /// or another error if there is no such file.
+    pub fn extract(&self, name: &str, buffer: &mut [u8]) -> crate::Result<usize> {
+        let data = self
+            .get(name)
+            .ok_or_else(|| Error::Other(format!("no file `{name}` in the archive")))?;
+
+        super::extract(data, buffer)
+    }
+}
+
+/// Hash of a file name, with the multiplier given by the archive (usually `0x65`).
+fn name_hash(name: &str, key: u32) -> u32 {
+    name.bytes().fold(0, |hash, byte| {
+        // Names are hashed as signed characters.
+        hash.wrapping_mul(key).wrapping_add(byte as i8 as u32)
+    })
+}
+
+/// Read a NUL-terminated name from the name table.
+fn read_name(data: &[u8], offset: usize) -> crate::Result<&str> {
+    let name = data
+        .get(offset..)
+        .and_then(|rest| rest.split(|&byte| byte == 0).next())
+        .ok_or_else(|| Error::Other(String::from("SARC name out of bounds")))?;
+
+    std::str::from_utf8(name).map_err(|_| Error::Other(String::from("SARC name isn't UTF-8")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_table() {
+        let key = 0x65;
+        let mut files = [("b.bin", &b"BB"[..]), ("a.bin", b"A")];
+        files.sort_by_key(|(name, _)| name_hash(name, key));
+
+        let mut archive = b"SARC\x14\x00\xFF\xFE".to_vec();
+        archive.extend_from_slice(&[0; 4]);
+        // Data offset, patched below.
+        archive.extend_from_slice(&[0; 4]);
+        archive.extend_from_slice(&[0x00, 0x01, 0, 0]);
+
+        archive.extend_from_slice(b"SFAT\x0C\x00\x02\x00");
+        archive.extend_from_slice(&key.to_le_bytes());
+
+        let mut names = Vec::new();
+        let mut contents = Vec::new();
+        for (name, data) in files {
+            let attributes = HAS_NAME | (names.len() / 4) as u32;
+            names.extend_from_slice(name.as_bytes());
+            names.resize(names.len().next_multiple_of(4) + 4, 0);
+
+            for word in [
+                name_hash(name, key),
+                attributes,
+                contents.len() as u32,
+                (contents.len() + data.len()) as u32,
+            ] {
+                archive.extend_from_slice(&word.to_le_bytes());
+            }
+            contents.extend_from_slice(data);
+        }
+
+        archive.extend_from_slice(b"SFNT\x08\x00\x00\x00");
+        archive.extend(names);
+        let data_offset = archive.len() as u32;
+        archive[0x0C..0x10].copy_from_slice(&data_offset.to_le_bytes());
+        archive.extend(contents);
+
+        let sarc = Sarc::parse(&archive).unwrap();
+        assert_eq!(sarc.len(), 2);
+        assert_eq!(sarc.get("a.bin"), Some(&b"A"[..]));
+        assert_eq!(sarc.get("b.bin"), Some(&b"BB"[..]));
+        assert_eq!(sarc.get("c.bin"), None);
+        assert!(sarc.files().all(|file| file.name().is_some()));
+
+        let mut buffer = [0; 2];
+        assert_eq!(sarc.extract("b.bin", &mut buffer).unwrap(), 2);
+    }
+}
@@ -0,0 +1,157 @@
+//! Button glyphs of the system font.
+//!
+//! The console's shared font contains icons for the buttons of the console in the Unicode private use area.
+//! Text drawn with the shared font (e.g. by the HOME Menu, the [software keyboard](crate::applets::swkbd) or citro2d)
+//! can include these characters to show button prompts like "Press (A) to continue" with the actual button icon.
+//!
+//! Not every button has a glyph: [`label()`] and [`expand()`] fall back to the button's name for those (e.g. `START`).
+//!
+//! # Notes
+//!
+//! The glyphs only render correctly with the shared font. Other fonts (including the one used by [`Console`](crate::console::Console))
+//! usually show them as missing characters.
+#![doc(alias = "font")]
+#![doc(alias = "icon")]
+
+use crate::services::hid::KeyPad;
+
+/// A button.
+pub const A: char = '\u{E000}';
+/// B button.
+pub const B: char = '\u{E001}';
+/// X button.
+pub const X: char = '\u{E002}';
+/// Y button.
+pub const Y: char = '\u{E003}';
+/// L button.
+pub const L: char = '\u{E004}';
+/// R button.
+pub const R: char = '\u{E005}';
+/// D-Pad (all directions).
+pub const DPAD: char = '\u{E006}';
+/// Circle Pad.
+pub const CIRCLE_PAD: char = '\u{E077}';
+/// D-Pad Up.
+pub const DPAD_UP: char = '\u{E079}';
+/// D-Pad Down.
+pub const DPAD_DOWN: char = '\u{E07A}';
+/// D-Pad Left.
+pub const DPAD_LEFT: char = '\u{E07B}';
+/// D-Pad Right.
+pub const DPAD_RIGHT: char = '\u{E07C}';
+/// D-Pad Up and Down.
+pub const DPAD_VERTICAL: char = '\u{E07D}';
+/// D-Pad Left and Right.
+pub const DPAD_HORIZONTAL: char = '\u{E07E}';
+
+/// Returns the glyph of a single button, if the system font has one.
+///
+/// All Circle Pad directions map to the [`CIRCLE_PAD`] glyph. Combined flags (such as [`KeyPad::UP`]) return `None`.
+pub fn glyph(key: KeyPad) -> Option<char> {
+    let glyph = match key {
+        KeyPad::A => A,
+        KeyPad::B => B,
+        KeyPad::X => X,
+        KeyPad::Y => Y,
+        KeyPad::L => L,
+        KeyPad::R => R,
+        KeyPad::DPAD_UP => DPAD_UP,
+        KeyPad::DPAD_DOWN => DPAD_DOWN,
+        KeyPad::DPAD_LEFT => DPAD_LEFT,
+        KeyPad::DPAD_RIGHT => DPAD_RIGHT,
+        KeyPad::CPAD_UP | KeyPad::CPAD_DOWN | KeyPad::CPAD_LEFT | KeyPad::CPAD_RIGHT => CIRCLE_PAD,
+        _ => return None,
+    };
+
+    Some(glyph)
+}
+
+/// Returns a printable label of a set of buttons, using glyphs where available and button names otherwise.
+///
+/// Buttons are joined with `+`, in the order of the [`KeyPad`] flags.
+///
+/// # Example
+///
+/// ```
+/// use ctru::glyph;
+/// use ctru::services::hid::KeyPad;
+///
+/// assert_eq!(glyph::label(KeyPad::A), glyph::A.to_string());
+/// assert_eq!(glyph::label(KeyPad::SELECT | KeyPad::START), "SELECT+START");
+/// ```
+pub fn label(keys: KeyPad) -> String {
+    let mut label = String::new();
+
+    for (name, key) in keys.iter_names() {
+        if !label.is_empty() {
+            label.push('+');
+        }
+
+        match glyph(key) {
+            Some(glyph) => label.push(glyph),
+            None => label.push_str(name),
+        }
+    }
+
+    label
+}
+
+/// Replace button placeholders in `template` with their glyphs (or names).
+///
+/// Placeholders use the names of the [`KeyPad`] flags within braces, such as `{A}`, `{START}` or `{DPAD_UP}`.
+/// `{DPAD}` and `{CIRCLE_PAD}` are also supported. Unknown placeholders are left untouched.
+///
+/// # Example
+///
+/// ```
+/// use ctru::glyph;
+///
+/// let prompt = glyph::expand("Press {A} to continue, {START} to exit");
+/// assert_eq!(prompt, format!("Press {} to continue, START to exit", glyph::A));
+/// ```
+pub fn expand(template: &str) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+
+        let name = &rest[1..end];
+        let replacement = match name {
+            "DPAD" => Some(DPAD.to_string()),
+            "CIRCLE_PAD" => Some(CIRCLE_PAD.to_string()),
+            _ => KeyPad::from_name(name).map(label),
+        };
+
+        match replacement {
+            Some(replacement) => result.push_str(&replacement),
+            None => result.push_str(&rest[..=end]),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    result.push_str(rest);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_placeholders() {
+        assert_eq!(
+            expand("{B}: back, {L}+{R}: {unknown} {ZL}"),
+            format!("{B}: back, {L}+{R}: {{unknown}} ZL")
+        );
+        assert_eq!(expand("{A"), "{A");
+        assert_eq!(label(KeyPad::CPAD_UP), CIRCLE_PAD.to_string());
+    }
+}
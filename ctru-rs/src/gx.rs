@@ -0,0 +1,324 @@
+//! GPU transfer engine helpers.
+//!
+//! Other than plain copies (see [`dma`](crate::dma)), the GPU can convert images between pixel formats and memory layouts while copying them
+//! ([`display_transfer()`]) and fill buffers with a constant value ([`memory_fill()`]).
+//! These operations are needed to show offscreen renders on the screens and to clear framebuffers quickly.
+//!
+//! # Notes
+//!
+//! As with [`dma`](crate::dma), all buffers must be located in [LINEAR memory](crate::linear) or VRAM,
+//! with addresses aligned to [`ADDRESS_ALIGNMENT`](crate::dma::ADDRESS_ALIGNMENT) bytes.
+#![doc(alias = "GX")]
+
+use crate::dma::{self, LENGTH_ALIGNMENT};
+use crate::error::{Error, ResultCode};
+use crate::services::gfx::Gfx;
+use crate::services::gspgpu::{self, Event, FramebufferFormat};
+
+/// Pixel format of the images handled by [`display_transfer()`].
+#[doc(alias = "GX_TRANSFER_FORMAT")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransferFormat {
+    /// RGBA8. 4 bytes per pixel
+    Rgba8 = ctru_sys::GX_TRANSFER_FMT_RGBA8,
+    /// RGB8. 3 bytes per pixel
+    Rgb8 = ctru_sys::GX_TRANSFER_FMT_RGB8,
+    /// RGB565. 2 bytes per pixel
+    Rgb565 = ctru_sys::GX_TRANSFER_FMT_RGB565,
+    /// RGB5A1. 2 bytes per pixel
+    Rgb5A1 = ctru_sys::GX_TRANSFER_FMT_RGB5A1,
+    /// RGBA4. 2 bytes per pixel
+    Rgba4 = ctru_sys::GX_TRANSFER_FMT_RGBA4,
+}
+
+/// Downscaling (anti-aliasing) applied by [`display_transfer()`].
+#[doc(alias = "GX_TRANSFER_SCALE")]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Scaling {
+    /// No scaling.
+    #[default]
+    None = ctru_sys::GX_TRANSFER_SCALE_NO,
+    /// 2x1 downscaling: the output is half as wide as the input.
+    X = ctru_sys::GX_TRANSFER_SCALE_X,
+    /// 2x2 downscaling: the output is half as wide and half as tall as the input.
+    XY = ctru_sys::GX_TRANSFER_SCALE_XY,
+}
+
+/// Size of a buffer (in pixels) used with [`display_transfer()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Dimensions {
+    /// Width of the buffer.
+    pub width: u16,
+    /// Height of the buffer.
+    pub height: u16,
+}
+
+/// Configuration of a [`display_transfer()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TransferFlags {
+    /// Pixel format of the source image.
+    pub input_format: TransferFormat,
+    /// Pixel format of the destination image.
+    pub output_format: TransferFormat,
+    /// Flip the image vertically.
+    pub flip_vertical: bool,
+    /// Write the output in the GPU's tiled layout (instead of converting the tiled input to a linear layout).
+    pub tiled_output: bool,
+    /// Downscaling to apply.
+    pub scaling: Scaling,
+}
+
+/// Size of the values written by [`memory_fill()`].
+#[doc(alias = "GX_FILL_CONTROL")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FillWidth {
+    /// 16 bit values (e.g. RGB565 colours, 16 bit depth buffers).
+    Bits16 = ctru_sys::GX_FILL_16BIT_DEPTH,
+    /// 24 bit values (e.g. RGB8 colours, 24 bit depth buffers).
+    Bits24 = ctru_sys::GX_FILL_24BIT_DEPTH,
+    /// 32 bit values (e.g. RGBA8 colours, depth/stencil buffers).
+    Bits32 = ctru_sys::GX_FILL_32BIT_DEPTH,
+}
+
+impl TransferFormat {
+    /// Returns the number of bytes per pixel used by this format.
+    pub fn pixel_depth_bytes(&self) -> usize {
+        match self {
+            Self::Rgba8 => 4,
+            Self::Rgb8 => 3,
+            Self::Rgb565 | Self::Rgb5A1 | Self::Rgba4 => 2,
+        }
+    }
+}
+
+impl TransferFlags {
+    /// Returns the flags for a transfer between the given formats, with no flipping, scaling or tiled output.
+    pub fn new(input_format: TransferFormat, output_format: TransferFormat) -> Self {
+        Self {
+            input_format,
+            output_format,
+            flip_vertical: false,
+            tiled_output: false,
+            scaling: Scaling::None,
+        }
+    }
+
+    fn bits(&self) -> u32 {
+        u32::from(self.flip_vertical)
+            | (u32::from(self.tiled_output) << 1)
+            | ((self.input_format as u32) << 8)
+            | ((self.output_format as u32) << 12)
+            | ((self.scaling as u32) << 24)
+    }
+}
+
+impl Dimensions {
+    fn bits(&self) -> u32 {
+        (u32::from(self.height) << 16) | u32::from(self.width)
+    }
+
+    fn pixels(&self) -> usize {
+        usize::from(self.width) * usize::from(self.height)
+    }
+}
+
+/// Copy an image from `src` to `dst`, converting its format and layout as described by `flags`. Blocks until the transfer is complete.
+///
+/// This is most commonly used to copy a tiled render target (i.e. the output of the GPU) into a linear framebuffer to display it.
+///
+/// # Errors
+///
+/// This function will return an error if any of the buffers is shorter than described by its dimensions and format,
+/// if the buffers aren't located in LINEAR memory or VRAM or if the GPU refuses the transfer.
+///
+/// # Example
+///
+/// ```
+/// # #![feature(allocator_api)]
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _runner = test_runner::GdbRunner::default();
+/// #
+/// use ctru::gx::{self, Dimensions, TransferFlags, TransferFormat};
+/// use ctru::linear::LinearAllocator;
+/// use ctru::services::gfx::Gfx;
+///
+/// let gfx = Gfx::new()?;
+///
+/// let dimensions = Dimensions {
+///     width: 240,
+///     height: 400,
+/// };
+/// let render_target = Box::new_in([0u8; 240 * 400 * 4], LinearAllocator);
+/// let mut output = Box::new_in([0u8; 240 * 400 * 3], LinearAllocator);
+///
+/// gx::display_transfer(
+///     &gfx,
+///     &*render_target,
+///     dimensions,
+///     &mut *output,
+///     dimensions,
+///     TransferFlags::new(TransferFormat::Rgba8, TransferFormat::Rgb8),
+/// )?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "GX_DisplayTransfer")]
+pub fn display_transfer(
+    _gfx: &Gfx,
+    src: &[u8],
+    src_dimensions: Dimensions,
+    dst: &mut [u8],
+    dst_dimensions: Dimensions,
+    flags: TransferFlags,
+) -> crate::Result<()> {
+    check_length(
+        src,
+        src_dimensions.pixels() * flags.input_format.pixel_depth_bytes(),
+    )?;
+    check_length(
+        dst,
+        dst_dimensions.pixels() * flags.output_format.pixel_depth_bytes(),
+    )?;
+    check_buffer(src)?;
+    check_buffer(dst)?;
+
+    unsafe {
+        ResultCode(ctru_sys::GSPGPU_FlushDataCache(
+            src.as_ptr().cast(),
+            src.len() as u32,
+        ))?;
+        ResultCode(ctru_sys::GSPGPU_FlushDataCache(
+            dst.as_ptr().cast(),
+            dst.len() as u32,
+        ))?;
+
+        ResultCode(ctru_sys::GX_DisplayTransfer(
+            src.as_ptr().cast_mut().cast(),
+            src_dimensions.bits(),
+            dst.as_mut_ptr().cast(),
+            dst_dimensions.bits(),
+            flags.bits(),
+        ))?;
+    }
+
+    gspgpu::wait_for_event(Event::PPF, false);
+
+    unsafe {
+        ResultCode(ctru_sys::GSPGPU_InvalidateDataCache(
+            dst.as_ptr().cast(),
+            dst.len() as u32,
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Fill `buffer` with `value`, repeated every 2, 3 or 4 bytes depending on `width`. Blocks until the fill is complete.
+///
+/// This is the fastest way to clear a framebuffer or a depth buffer.
+///
+/// # Errors
+///
+/// This function will return an error if the buffer isn't located in LINEAR memory or VRAM,
+/// if its length isn't a multiple of [`LENGTH_ALIGNMENT`](crate::dma::LENGTH_ALIGNMENT) or if the GPU refuses the operation.
+///
+/// # Example
+///
+/// ```
+/// # #![feature(allocator_api)]
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _runner = test_runner::GdbRunner::default();
+/// #
+/// use ctru::gx::{self, FillWidth};
+/// use ctru::linear::LinearAllocator;
+/// use ctru::services::gfx::Gfx;
+///
+/// let gfx = Gfx::new()?;
+///
+/// let mut depth_buffer = Box::new_in([0u8; 240 * 400 * 4], LinearAllocator);
+///
+/// gx::memory_fill(&gfx, &mut *depth_buffer, 0xFFFFFFFF, FillWidth::Bits32)?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "GX_MemoryFill")]
+pub fn memory_fill(
+    _gfx: &Gfx,
+    buffer: &mut [u8],
+    value: u32,
+    width: FillWidth,
+) -> crate::Result<()> {
+    check_buffer(buffer)?;
+
+    if buffer.len() % LENGTH_ALIGNMENT != 0 {
+        return Err(Error::Other(format!(
+            "buffer length must be a multiple of {LENGTH_ALIGNMENT} bytes"
+        )));
+    }
+
+    let start = buffer.as_mut_ptr();
+
+    unsafe {
+        ResultCode(ctru_sys::GSPGPU_FlushDataCache(
+            start.cast(),
+            buffer.len() as u32,
+        ))?;
+
+        ResultCode(ctru_sys::GX_MemoryFill(
+            start.cast(),
+            value,
+            start.add(buffer.len()).cast(),
+            (ctru_sys::GX_FILL_TRIGGER | width as u32) as u16,
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            0,
+        ))?;
+    }
+
+    gspgpu::wait_for_event(Event::Psc0, false);
+
+    unsafe {
+        ResultCode(ctru_sys::GSPGPU_InvalidateDataCache(
+            start.cast(),
+            buffer.len() as u32,
+        ))?;
+    }
+
+    Ok(())
+}
+
+fn check_length(buffer: &[u8], wanted: usize) -> crate::Result<()> {
+    if buffer.len() < wanted {
+        return Err(Error::BufferTooShort {
+            provided: buffer.len(),
+            wanted,
+        });
+    }
+
+    Ok(())
+}
+
+fn check_buffer(buffer: &[u8]) -> crate::Result<()> {
+    dma::check_buffer(buffer).map_err(|e| Error::Other(e.to_string()))
+}
+
+impl From<FramebufferFormat> for TransferFormat {
+    fn from(format: FramebufferFormat) -> Self {
+        match format {
+            FramebufferFormat::Rgba8 => Self::Rgba8,
+            FramebufferFormat::Bgr8 => Self::Rgb8,
+            FramebufferFormat::Rgb565 => Self::Rgb565,
+            FramebufferFormat::Rgb5A1 => Self::Rgb5A1,
+            FramebufferFormat::Rgba4 => Self::Rgba4,
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Localization helpers.
+//!
+//! Applications usually ship their translations as one [`Bundle`] of strings per [`Language`], and pick the one matching the
+//! console's system language (see [`Cfgu::language()`]). [`Localizer`] does exactly that: it looks strings up in the bundle
+//! of the current language, falling back to other languages (and ultimately English) for strings which aren't translated yet.
+//!
+//! Bundles can be embedded in the executable with [`include_bundle!`](crate::include_bundle), which reads a simple text format:
+//!
+//! ```text
+//! # Comments start with a hash sign.
+//! title = My Application
+//! greeting = Hello!\nPress START to exit.
+//! ```
+//!
+//! Each line contains a key and its translation, separated by the first `=` sign. Surrounding whitespace is ignored,
+//! and `\n`, `\t` and `\\` escape sequences are supported within translations.
+#![doc(alias = "localization")]
+#![doc(alias = "translation")]
+
+use crate::services::cfgu::{Cfgu, Language};
+
+use std::collections::HashMap;
+
+/// Translated strings of a single language.
+#[derive(Clone, Debug)]
+pub struct Bundle {
+    language: Language,
+    strings: HashMap<String, String>,
+}
+
+impl Bundle {
+    /// Create an empty bundle for the given language.
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            strings: HashMap::new(),
+        }
+    }
+
+    /// Parse a bundle for the given language from the text format described in the [module documentation](self).
+    ///
+    /// Lines without a `=` sign (other than comments and empty lines) are ignored.
+    pub fn parse(language: Language, source: &str) -> Self {
+        let mut bundle = Self::new(language);
+
+        for line in source.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                bundle.insert(key.trim(), unescape(value.trim()));
+            }
+        }
+
+        bundle
+    }
+
+    /// Returns the language of this bundle.
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Add or replace a translation.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.strings.insert(key.into(), value.into());
+    }
+
+    /// Returns the translation of `key`, if this bundle contains it.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.strings.get(key).map(String::as_str)
+    }
+
+    /// Returns the number of translations in this bundle.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Returns `true` if this bundle doesn't contain any translation.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+/// String lookup across multiple language bundles.
+///
+/// Strings are searched in the bundle of the current language first, then in the bundles of the fallback languages
+/// (in order) and finally in the English bundle. If no bundle contains the string, the key itself is returned.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::i18n::{Bundle, Localizer};
+/// use ctru::services::cfgu::Language;
+///
+/// let mut localizer = Localizer::new(Language::Portuguese);
+/// localizer.add_bundle(Bundle::parse(Language::English, "title = Settings\nback = Back"));
+/// localizer.add_bundle(Bundle::parse(Language::Spanish, "title = Ajustes"));
+/// localizer.set_fallbacks(&[Language::Spanish]);
+///
+/// // Not translated in Portuguese, so the Spanish string is used.
+/// assert_eq!(localizer.get("title"), "Ajustes");
+/// // Only available in English.
+/// assert_eq!(localizer.get("back"), "Back");
+/// // Missing everywhere.
+/// assert_eq!(localizer.get("missing"), "missing");
+///
+/// // Languages can be switched at any time.
+/// localizer.set_language(Language::English);
+/// assert_eq!(localizer.get("title"), "Settings");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Localizer {
+    language: Language,
+    fallbacks: Vec<Language>,
+    bundles: HashMap<Language, Bundle>,
+}
+
+impl Localizer {
+    /// Create a localizer without any bundle, using the given language.
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            fallbacks: Vec::new(),
+            bundles: HashMap::new(),
+        }
+    }
+
+    /// Create a localizer without any bundle, using the console's system language.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the system language couldn't be read.
+    #[doc(alias = "CFGU_GetSystemLanguage")]
+    pub fn from_system(cfgu: &Cfgu) -> crate::Result<Self> {
+        Ok(Self::new(cfgu.language()?))
+    }
+
+    /// Add a bundle, replacing any previous bundle for the same language.
+    pub fn add_bundle(&mut self, bundle: Bundle) {
+        self.bundles.insert(bundle.language(), bundle);
+    }
+
+    /// Returns the current language.
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Switch to a different language.
+    pub fn set_language(&mut self, language: Language) {
+        self.language = language;
+    }
+
+    /// Set the languages searched (in order) for strings missing in the current language, before English.
+    pub fn set_fallbacks(&mut self, fallbacks: &[Language]) {
+        self.fallbacks = fallbacks.to_vec();
+    }
+
+    /// Returns `true` if there is a bundle for the current language.
+    ///
+    /// Useful to let the user pick a different language when the system one isn't supported.
+    pub fn is_supported(&self) -> bool {
+        self.bundles.contains_key(&self.language)
+    }
+
+    /// Returns the translation of `key`, if any bundle in the fallback chain contains it.
+    pub fn try_get(&self, key: &str) -> Option<&str> {
+        std::iter::once(self.language)
+            .chain(self.fallbacks.iter().copied())
+            .chain(std::iter::once(Language::English))
+            .filter_map(|language| self.bundles.get(&language))
+            .find_map(|bundle| bundle.get(key))
+    }
+
+    /// Returns the translation of `key`, or the key itself if it isn't translated in any bundle.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.try_get(key).unwrap_or(key)
+    }
+}
+
+/// Embed a translation file in the executable and parse it as a [`Bundle`](crate::i18n::Bundle).
+///
+/// The path is resolved relative to the current file, like [`include_str!`].
+///
+/// # Example
+///
+/// ```ignore
+/// use ctru::i18n::Localizer;
+/// use ctru::include_bundle;
+/// use ctru::services::cfgu::Language;
+///
+/// let mut localizer = Localizer::new(Language::English);
+/// localizer.add_bundle(include_bundle!(Language::English, "../lang/en.txt"));
+/// localizer.add_bundle(include_bundle!(Language::French, "../lang/fr.txt"));
+/// ```
+#[macro_export]
+macro_rules! include_bundle {
+    ($language:expr, $path:literal $(,)?) => {
+        $crate::i18n::Bundle::parse($language, include_str!($path))
+    };
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_chain() {
+        let mut localizer = Localizer::new(Language::TraditionalChinese);
+        localizer.add_bundle(Bundle::parse(
+            Language::English,
+            "# Comment\nhello = Hello\\nWorld\nbye = Bye\n",
+        ));
+        localizer.add_bundle(Bundle::parse(Language::SimplifiedChinese, "hello = 你好"));
+
+        assert!(!localizer.is_supported());
+        assert_eq!(localizer.get("hello"), "Hello\nWorld");
+
+        localizer.set_fallbacks(&[Language::SimplifiedChinese]);
+        assert_eq!(localizer.get("hello"), "你好");
+        assert_eq!(localizer.get("bye"), "Bye");
+        assert_eq!(localizer.try_get("unknown"), None);
+    }
+}
@@ -0,0 +1,1205 @@
+//! JPEG encoding and decoding.
+//!
+//! The 3DS has no hardware JPEG codec: the camera service delivers raw YUV or RGB frames, and the Y2R unit only converts
+//! YUV images to RGB. Its ARM11 cores don't support NEON either, so this module implements the codec in software,
+//! using separable floating point DCTs (which run on the VFP unit) and table-driven Huffman decoding.
+//!
+//! The colour conversion of decoded YCbCr images runs on the Y2R unit when it supports the image's layout
+//! (4:2:0 or 4:2:2 chroma subsampling, up to 1024x1024 pixels), which includes the photos taken by the console's camera.
+//! Other images, or conversions failing on the Y2R unit, fall back to the software conversion.
+//!
+//! Only baseline (sequential, Huffman coded, 8-bit) JPEG files are supported, which covers photos taken by the console's camera
+//! application and most files produced by other encoders. Progressive and arithmetic coded files are rejected.
+#![doc(alias = "jpg")]
+
+use std::f32::consts::PI;
+
+use super::y2r::{self, Subsampling};
+use super::{Image, PixelFormat};
+use crate::Error;
+
+/// Index in an 8x8 block (in row-major order) of each coefficient, in the zig-zag order used by JPEG files.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// Example tables from Annex K of the JPEG specification, which most encoders use.
+
+const LUMINANCE_QUANTIZATION: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+const CHROMINANCE_QUANTIZATION: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+const DC_LUMINANCE_BITS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const DC_CHROMINANCE_BITS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+const AC_LUMINANCE_BITS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7D];
+const AC_LUMINANCE_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xA1, 0x08, 0x23, 0x42, 0xB1, 0xC1, 0x15, 0x52, 0xD1, 0xF0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0A, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2A, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7,
+    0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3, 0xC4, 0xC5,
+    0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xE1, 0xE2,
+    0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+
+const AC_CHROMINANCE_BITS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const AC_CHROMINANCE_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xA1, 0xB1, 0xC1, 0x09, 0x23, 0x33, 0x52, 0xF0,
+    0x15, 0x62, 0x72, 0xD1, 0x0A, 0x16, 0x24, 0x34, 0xE1, 0x25, 0xF1, 0x17, 0x18, 0x19, 0x1A, 0x26,
+    0x27, 0x28, 0x29, 0x2A, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5,
+    0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3,
+    0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA,
+    0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+
+/// Amount of bits looked up at once when decoding Huffman codes.
+const LOOKUP_BITS: u32 = 9;
+
+/// Marker codes used by the codec.
+mod marker {
+    pub const SOF0: u8 = 0xC0;
+    pub const SOF1: u8 = 0xC1;
+    pub const DHT: u8 = 0xC4;
+    pub const RST0: u8 = 0xD0;
+    pub const RST7: u8 = 0xD7;
+    pub const SOI: u8 = 0xD8;
+    pub const EOI: u8 = 0xD9;
+    pub const SOS: u8 = 0xDA;
+    pub const DQT: u8 = 0xDB;
+    pub const DRI: u8 = 0xDD;
+    pub const APP0: u8 = 0xE0;
+}
+
+/// Returns the 8-point DCT basis: `table[x][u]` is the weight of frequency `u` at sample `x`.
+///
+/// The basis is orthonormal, so the same table computes both the forward and the inverse transforms.
+fn dct_table() -> [[f32; 8]; 8] {
+    let mut table = [[0.0; 8]; 8];
+
+    for (x, row) in table.iter_mut().enumerate() {
+        for (u, weight) in row.iter_mut().enumerate() {
+            let scale = if u == 0 { 0.5 / 2f32.sqrt() } else { 0.5 };
+            *weight = scale * (((2 * x + 1) * u) as f32 * PI / 16.0).cos();
+        }
+    }
+
+    table
+}
+
+fn invalid(message: &str) -> Error {
+    Error::Other(format!("invalid JPEG data: {message}"))
+}
+
+fn unsupported(message: &str) -> Error {
+    Error::Other(format!("unsupported JPEG file: {message}"))
+}
+
+/// Decode a baseline JPEG file.
+///
+/// Grayscale files are decoded to [`PixelFormat::L8`] images, and colour (YCbCr) files to [`PixelFormat::Rgb8`] images.
+///
+/// # Errors
+///
+/// This function will return an error if the data isn't a valid JPEG file, or if it uses features other than
+/// the baseline ones (e.g. progressive or arithmetic coding, or CMYK colours).
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::image::{jpeg, Image, PixelFormat};
+///
+/// let image = Image::new(16, 16, PixelFormat::Rgb8, vec![0x80; 16 * 16 * 3])?;
+/// let file = jpeg::encode(&image, 90)?;
+///
+/// let decoded = jpeg::decode(&file)?;
+/// assert_eq!((decoded.width(), decoded.height()), (16, 16));
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode(data: &[u8]) -> crate::Result<Image> {
+    Decoder::new(data).decode()
+}
+
+/// Encode `image` as a baseline JPEG file, with a `quality` between 1 (smallest file) and 100 (best quality).
+///
+/// [`PixelFormat::L8`] images are encoded in grayscale. Colour images are encoded in YCbCr with 4:2:0 chroma subsampling,
+/// dropping the alpha channel (if any).
+///
+/// # Errors
+///
+/// This function will return an error if the image is empty or larger than 65535 pixels in either dimension.
+pub fn encode(image: &Image, quality: u8) -> crate::Result<Vec<u8>> {
+    let (width, height) = (image.width(), image.height());
+
+    if width == 0 || height == 0 || width > 0xFFFF || height > 0xFFFF {
+        return Err(Error::Other(format!(
+            "a {width}x{height} image can't be encoded as JPEG"
+        )));
+    }
+
+    let grayscale = image.format() == PixelFormat::L8;
+    let quality = quality.clamp(1, 100) as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+    let scale_table = |table: &[u8; 64]| -> [u8; 64] {
+        let mut scaled = [0; 64];
+        for (k, value) in scaled.iter_mut().enumerate() {
+            *value = ((table[ZIGZAG[k]] as u32 * scale + 50) / 100).clamp(1, 255) as u8;
+        }
+        scaled
+    };
+    let quantization = [
+        scale_table(&LUMINANCE_QUANTIZATION),
+        scale_table(&CHROMINANCE_QUANTIZATION),
+    ];
+
+    let mut output = Vec::new();
+    let segment = |output: &mut Vec<u8>, marker: u8, contents: &[u8]| {
+        output.extend_from_slice(&[0xFF, marker]);
+        output.extend_from_slice(&(contents.len() as u16 + 2).to_be_bytes());
+        output.extend_from_slice(contents);
+    };
+
+    output.extend_from_slice(&[0xFF, marker::SOI]);
+    segment(
+        &mut output,
+        marker::APP0,
+        &[b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0],
+    );
+
+    let tables = if grayscale { 1 } else { 2 };
+    for (id, table) in quantization.iter().take(tables).enumerate() {
+        let mut contents = vec![id as u8];
+        contents.extend_from_slice(table);
+        segment(&mut output, marker::DQT, &contents);
+    }
+
+    // Components are (id, sampling factors, quantization and Huffman tables).
+    let components: &[(u8, u8, u8)] = if grayscale {
+        &[(1, 0x11, 0)]
+    } else {
+        &[(1, 0x22, 0), (2, 0x11, 1), (3, 0x11, 1)]
+    };
+
+    let mut frame = vec![8];
+    frame.extend_from_slice(&(height as u16).to_be_bytes());
+    frame.extend_from_slice(&(width as u16).to_be_bytes());
+    frame.push(components.len() as u8);
+    for &(id, sampling, table) in components {
+        frame.extend_from_slice(&[id, sampling, table]);
+    }
+    segment(&mut output, marker::SOF0, &frame);
+
+    let huffman_tables: [(u8, &[u8; 16], &[u8]); 4] = [
+        (0x00, &DC_LUMINANCE_BITS, &DC_VALUES),
+        (0x10, &AC_LUMINANCE_BITS, &AC_LUMINANCE_VALUES),
+        (0x01, &DC_CHROMINANCE_BITS, &DC_VALUES),
+        (0x11, &AC_CHROMINANCE_BITS, &AC_CHROMINANCE_VALUES),
+    ];
+    for (class, bits, values) in huffman_tables.iter().take(tables * 2) {
+        let mut contents = vec![*class];
+        contents.extend_from_slice(*bits);
+        contents.extend_from_slice(values);
+        segment(&mut output, marker::DHT, &contents);
+    }
+
+    let mut scan = vec![components.len() as u8];
+    for &(id, _, table) in components {
+        scan.extend_from_slice(&[id, (table << 4) | table]);
+    }
+    scan.extend_from_slice(&[0, 63, 0]);
+    segment(&mut output, marker::SOS, &scan);
+
+    let codes = [
+        (
+            HuffmanCodes::new(&DC_LUMINANCE_BITS, &DC_VALUES),
+            HuffmanCodes::new(&AC_LUMINANCE_BITS, &AC_LUMINANCE_VALUES),
+        ),
+        (
+            HuffmanCodes::new(&DC_CHROMINANCE_BITS, &DC_VALUES),
+            HuffmanCodes::new(&AC_CHROMINANCE_BITS, &AC_CHROMINANCE_VALUES),
+        ),
+    ];
+
+    let planes = to_ycbcr(image);
+    let table = dct_table();
+    let mut writer = BitWriter::new(&mut output);
+    let mut predictions = [0; 3];
+    let mut block = [0.0; 64];
+
+    let mcu_size = if grayscale { 8 } else { 16 };
+    for mcu_y in (0..height).step_by(mcu_size) {
+        for mcu_x in (0..width).step_by(mcu_size) {
+            // Luminance blocks, at full resolution.
+            for block_y in (0..mcu_size).step_by(8) {
+                for block_x in (0..mcu_size).step_by(8) {
+                    for (k, sample) in block.iter_mut().enumerate() {
+                        let x = (mcu_x + block_x + k % 8).min(width - 1);
+                        let y = (mcu_y + block_y + k / 8).min(height - 1);
+                        *sample = planes[0][y * width + x];
+                    }
+
+                    let coefficients = forward_dct(&block, &table, &quantization[0]);
+                    writer.write_block(&coefficients, &mut predictions[0], &codes[0]);
+                }
+            }
+
+            // Chrominance blocks, averaging 2x2 pixels.
+            for component in 1..components.len() {
+                for (k, sample) in block.iter_mut().enumerate() {
+                    let mut sum = 0.0;
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let x = (mcu_x + (k % 8) * 2 + dx).min(width - 1);
+                        let y = (mcu_y + (k / 8) * 2 + dy).min(height - 1);
+                        sum += planes[component][y * width + x];
+                    }
+                    *sample = sum / 4.0;
+                }
+
+                let coefficients = forward_dct(&block, &table, &quantization[1]);
+                writer.write_block(&coefficients, &mut predictions[component], &codes[1]);
+            }
+        }
+    }
+
+    writer.finish();
+    output.extend_from_slice(&[0xFF, marker::EOI]);
+
+    Ok(output)
+}
+
+/// Split `image` into Y, Cb and Cr planes, with samples centered around 0.
+fn to_ycbcr(image: &Image) -> Vec<Vec<f32>> {
+    let size = image.format().bytes_per_pixel();
+    let pixels = image.data().chunks_exact(size);
+
+    if image.format() == PixelFormat::L8 {
+        return vec![pixels.map(|pixel| pixel[0] as f32 - 128.0).collect()];
+    }
+
+    let mut planes: Vec<Vec<f32>> = (0..3)
+        .map(|_| Vec::with_capacity(image.width() * image.height()))
+        .collect();
+    for pixel in pixels {
+        let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+
+        planes[0].push(0.299 * r + 0.587 * g + 0.114 * b - 128.0);
+        planes[1].push(-0.168_736 * r - 0.331_264 * g + 0.5 * b);
+        planes[2].push(0.5 * r - 0.418_688 * g - 0.081_312 * b);
+    }
+
+    planes
+}
+
+/// Transform and quantize a block of samples, returning the coefficients in zig-zag order.
+fn forward_dct(block: &[f32; 64], table: &[[f32; 8]; 8], quantization: &[u8; 64]) -> [i32; 64] {
+    let mut rows = [0.0; 64];
+    for y in 0..8 {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..8).map(|x| table[x][u] * block[y * 8 + x]).sum();
+        }
+    }
+
+    let mut coefficients = [0; 64];
+    for (k, coefficient) in coefficients.iter_mut().enumerate() {
+        let (u, v) = (ZIGZAG[k] % 8, ZIGZAG[k] / 8);
+        let value: f32 = (0..8).map(|y| table[y][v] * rows[y * 8 + u]).sum();
+        *coefficient = (value / quantization[k] as f32).round() as i32;
+    }
+
+    coefficients
+}
+
+/// Inverse of [`forward_dct()`], for dequantized coefficients in row-major order. Returns samples between 0 and 255.
+fn inverse_dct(coefficients: &[i32; 64], table: &[[f32; 8]; 8]) -> [u8; 64] {
+    let mut columns = [0.0; 64];
+    for u in 0..8 {
+        for y in 0..8 {
+            columns[y * 8 + u] = (0..8)
+                .map(|v| table[y][v] * coefficients[v * 8 + u] as f32)
+                .sum();
+        }
+    }
+
+    let mut samples = [0; 64];
+    for y in 0..8 {
+        for x in 0..8 {
+            let value: f32 = (0..8).map(|u| table[x][u] * columns[y * 8 + u]).sum();
+            samples[y * 8 + x] = (value + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    samples
+}
+
+/// Number of bits needed to represent the magnitude of `value`.
+fn bit_length(value: i32) -> u32 {
+    32 - value.unsigned_abs().leading_zeros()
+}
+
+/// Huffman codes (and their lengths) for each symbol, used by the encoder.
+struct HuffmanCodes {
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanCodes {
+    fn new(bits: &[u8; 16], values: &[u8]) -> Self {
+        let mut codes = [(0, 0); 256];
+        let mut code = 0u16;
+        let mut values = values.iter();
+
+        for (length, &count) in bits.iter().enumerate() {
+            for _ in 0..count {
+                if let Some(&value) = values.next() {
+                    codes[value as usize] = (code, length as u8 + 1);
+                }
+                code += 1;
+            }
+            code <<= 1;
+        }
+
+        Self { codes }
+    }
+}
+
+struct BitWriter<'a> {
+    output: &'a mut Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(output: &'a mut Vec<u8>) -> Self {
+        Self {
+            output,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    fn write(&mut self, value: u32, length: u32) {
+        self.bits = (self.bits << length) | (value & ((1 << length) - 1));
+        self.count += length;
+
+        while self.count >= 8 {
+            self.count -= 8;
+            let byte = (self.bits >> self.count) as u8;
+            self.output.push(byte);
+
+            // 0xFF bytes in the entropy coded data must be followed by a 0x00 byte, so they aren't mistaken for markers.
+            if byte == 0xFF {
+                self.output.push(0);
+            }
+        }
+    }
+
+    fn write_code(&mut self, codes: &HuffmanCodes, symbol: u8) {
+        let (code, length) = codes.codes[symbol as usize];
+        self.write(code as u32, length as u32);
+    }
+
+    fn write_value(&mut self, value: i32, length: u32) {
+        // Negative values are stored as their one's complement.
+        let bits = if value < 0 { value - 1 } else { value };
+        self.write(bits as u32, length);
+    }
+
+    fn write_block(
+        &mut self,
+        coefficients: &[i32; 64],
+        prediction: &mut i32,
+        (dc, ac): &(HuffmanCodes, HuffmanCodes),
+    ) {
+        let difference = coefficients[0] - *prediction;
+        *prediction = coefficients[0];
+
+        let length = bit_length(difference);
+        self.write_code(dc, length as u8);
+        self.write_value(difference, length);
+
+        let mut run = 0;
+        for &coefficient in &coefficients[1..] {
+            if coefficient == 0 {
+                run += 1;
+                continue;
+            }
+
+            while run > 15 {
+                // Run of 16 zeros.
+                self.write_code(ac, 0xF0);
+                run -= 16;
+            }
+
+            let length = bit_length(coefficient);
+            self.write_code(ac, (run << 4) | length as u8);
+            self.write_value(coefficient, length);
+            run = 0;
+        }
+
+        if run > 0 {
+            // End of block.
+            self.write_code(ac, 0x00);
+        }
+    }
+
+    fn finish(mut self) {
+        // Pad the last byte with ones.
+        let padding = (8 - self.count % 8) % 8;
+        self.write(0xFF, padding);
+    }
+}
+
+/// Huffman table used by the decoder.
+#[derive(Clone)]
+struct HuffmanTable {
+    /// Symbol and code length for each possible value of the next [`LOOKUP_BITS`] bits, if the code is that short.
+    lookup: Vec<(u8, u8)>,
+    /// Largest code of each length (+1), or -1 if there are no codes of that length.
+    max_code: [i32; 17],
+    /// Difference between the index in `values` and the code, for the codes of each length.
+    offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl HuffmanTable {
+    fn new(bits: &[u8; 16], values: Vec<u8>) -> crate::Result<Self> {
+        let mut lookup = vec![(0, 0); 1 << LOOKUP_BITS];
+        let mut max_code = [-1; 17];
+        let mut offset = [0; 17];
+        let mut code = 0i32;
+        let mut index = 0i32;
+
+        for length in 1..=16 {
+            let count = bits[length - 1] as i32;
+
+            offset[length] = index - code;
+
+            for _ in 0..count {
+                let value = *values
+                    .get(index as usize)
+                    .ok_or_else(|| invalid("Huffman table is too short"))?;
+
+                if length as u32 <= LOOKUP_BITS {
+                    let shift = LOOKUP_BITS - length as u32;
+                    let start = (code as usize) << shift;
+                    for entry in &mut lookup[start..start + (1 << shift)] {
+                        *entry = (value, length as u8);
+                    }
+                }
+
+                code += 1;
+                index += 1;
+            }
+
+            if count > 0 {
+                max_code[length] = code - 1;
+            }
+
+            if code > 1 << length {
+                return Err(invalid("Huffman table has too many codes"));
+            }
+            code <<= 1;
+        }
+
+        Ok(Self {
+            lookup,
+            max_code,
+            offset,
+            values,
+        })
+    }
+}
+
+/// Reader of entropy coded data, which stops at the next marker.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bits: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        Self {
+            data,
+            position,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.count <= 24 {
+            let byte = match self.data.get(self.position) {
+                Some(0xFF) => match self.data.get(self.position + 1) {
+                    Some(0x00) => {
+                        self.position += 2;
+                        0xFF
+                    }
+                    // A marker was reached: feed zeros from now on.
+                    _ => 0,
+                },
+                Some(&byte) => {
+                    self.position += 1;
+                    byte
+                }
+                None => 0,
+            };
+
+            self.bits |= (byte as u32) << (24 - self.count);
+            self.count += 8;
+        }
+    }
+
+    fn peek(&mut self, length: u32) -> u32 {
+        self.fill();
+        self.bits >> (32 - length)
+    }
+
+    fn consume(&mut self, length: u32) {
+        self.bits <<= length;
+        self.count -= length;
+    }
+
+    fn read(&mut self, length: u32) -> u32 {
+        if length == 0 {
+            return 0;
+        }
+
+        let value = self.peek(length);
+        self.consume(length);
+        value
+    }
+
+    /// Read a value of `length` bits, stored as in [`BitWriter::write_value()`].
+    fn read_value(&mut self, length: u32) -> i32 {
+        let value = self.read(length) as i32;
+
+        if length > 0 && value < 1 << (length - 1) {
+            value - (1 << length) + 1
+        } else {
+            value
+        }
+    }
+
+    fn decode(&mut self, table: &HuffmanTable) -> crate::Result<u8> {
+        let (value, length) = table.lookup[self.peek(LOOKUP_BITS) as usize];
+        if length > 0 {
+            self.consume(length as u32);
+            return Ok(value);
+        }
+
+        let code = self.peek(16) as i32;
+        for length in LOOKUP_BITS as usize + 1..=16 {
+            let prefix = code >> (16 - length);
+
+            if prefix <= table.max_code[length] {
+                self.consume(length as u32);
+                return table
+                    .values
+                    .get((prefix + table.offset[length]) as usize)
+                    .copied()
+                    .ok_or_else(|| invalid("corrupted Huffman code"));
+            }
+        }
+
+        Err(invalid("corrupted Huffman code"))
+    }
+
+    /// Skip to the restart marker following the current position, and return the position after it.
+    fn restart(self) -> crate::Result<usize> {
+        let mut position = self.position;
+
+        while position + 1 < self.data.len() {
+            if self.data[position] == 0xFF {
+                match self.data[position + 1] {
+                    marker::RST0..=marker::RST7 => return Ok(position + 2),
+                    // Stuffed byte or fill byte.
+                    0x00 | 0xFF => {}
+                    _ => break,
+                }
+            }
+            position += 1;
+        }
+
+        Err(invalid("missing restart marker"))
+    }
+}
+
+struct Component {
+    id: u8,
+    horizontal: usize,
+    vertical: usize,
+    quantization: usize,
+    dc_table: usize,
+    ac_table: usize,
+    prediction: i32,
+    /// Width of the plane, in blocks (padded to a whole amount of MCUs).
+    blocks_wide: usize,
+    /// Decoded samples, padded to a whole amount of MCUs.
+    samples: Vec<u8>,
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+    quantization: [[u16; 64]; 4],
+    dc_tables: [Option<HuffmanTable>; 4],
+    ac_tables: [Option<HuffmanTable>; 4],
+    restart_interval: usize,
+    table: [[f32; 8]; 8],
+}
+
+impl<'a> Decoder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            width: 0,
+            height: 0,
+            components: Vec::new(),
+            quantization: [[1; 64]; 4],
+            dc_tables: [None, None, None, None],
+            ac_tables: [None, None, None, None],
+            restart_interval: 0,
+            table: dct_table(),
+        }
+    }
+
+    fn decode(mut self) -> crate::Result<Image> {
+        self.read()?;
+        self.into_image()
+    }
+
+    /// Read the whole file, decoding the samples of each component.
+    fn read(&mut self) -> crate::Result<()> {
+        if self.data.get(..2) != Some(&[0xFF, marker::SOI]) {
+            return Err(invalid("missing start of image marker"));
+        }
+        self.position = 2;
+
+        loop {
+            let marker = self.next_marker()?;
+
+            if marker == marker::EOI {
+                break;
+            }
+
+            let length = self.u16_at(self.position)? as usize;
+            if length < 2 || self.position + length > self.data.len() {
+                return Err(invalid("truncated segment"));
+            }
+            let segment = &self.data[self.position + 2..self.position + length];
+            self.position += length;
+
+            match marker {
+                marker::SOF0 | marker::SOF1 => self.read_frame(segment)?,
+                // Other frame types (progressive, lossless, arithmetic coded...) and arithmetic coding conditioning.
+                0xC2 | 0xC3 | 0xC5..=0xC7 | 0xC9..=0xCF => {
+                    return Err(unsupported("only baseline JPEG files can be decoded"))
+                }
+                marker::DHT => self.read_huffman_tables(segment)?,
+                marker::DQT => self.read_quantization_tables(segment)?,
+                marker::DRI => {
+                    if segment.len() < 2 {
+                        return Err(invalid("truncated restart interval"));
+                    }
+                    self.restart_interval = u16::from_be_bytes([segment[0], segment[1]]) as usize;
+                }
+                marker::SOS => self.read_scan(segment)?,
+                // Application data and comments.
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn u16_at(&self, position: usize) -> crate::Result<u16> {
+        match self.data.get(position..position + 2) {
+            Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]])),
+            None => Err(invalid("truncated data")),
+        }
+    }
+
+    fn next_marker(&mut self) -> crate::Result<u8> {
+        match self.data.get(self.position) {
+            Some(0xFF) => {}
+            Some(_) => return Err(invalid("expected a marker")),
+            None => return Err(invalid("missing end of image marker")),
+        }
+
+        // Markers may be preceded by any amount of 0xFF fill bytes.
+        while self.data.get(self.position) == Some(&0xFF) {
+            self.position += 1;
+        }
+
+        match self.data.get(self.position) {
+            Some(&marker) => {
+                self.position += 1;
+                Ok(marker)
+            }
+            None => Err(invalid("missing end of image marker")),
+        }
+    }
+
+    fn read_frame(&mut self, segment: &[u8]) -> crate::Result<()> {
+        if !self.components.is_empty() {
+            return Err(invalid("multiple frames"));
+        }
+
+        if segment.len() < 6 {
+            return Err(invalid("truncated frame header"));
+        }
+
+        if segment[0] != 8 {
+            return Err(unsupported("only 8-bit samples can be decoded"));
+        }
+
+        self.height = u16::from_be_bytes([segment[1], segment[2]]) as usize;
+        self.width = u16::from_be_bytes([segment[3], segment[4]]) as usize;
+        let count = segment[5] as usize;
+
+        if self.width == 0 || self.height == 0 {
+            return Err(unsupported(
+                "the image size must be set in the frame header",
+            ));
+        }
+
+        if count != 1 && count != 3 {
+            return Err(unsupported(
+                "only grayscale and YCbCr images can be decoded",
+            ));
+        }
+
+        if segment.len() < 6 + count * 3 {
+            return Err(invalid("truncated frame header"));
+        }
+
+        for component in segment[6..6 + count * 3].chunks_exact(3) {
+            let (horizontal, vertical) =
+                ((component[1] >> 4) as usize, (component[1] & 0xF) as usize);
+
+            if !(1..=4).contains(&horizontal) || !(1..=4).contains(&vertical) || component[2] > 3 {
+                return Err(invalid("invalid component"));
+            }
+
+            self.components.push(Component {
+                id: component[0],
+                horizontal,
+                vertical,
+                quantization: component[2] as usize,
+                dc_table: 0,
+                ac_table: 0,
+                prediction: 0,
+                blocks_wide: 0,
+                samples: Vec::new(),
+            });
+        }
+
+        let (max_horizontal, max_vertical) = self.max_sampling();
+        let mcus_wide = self.width.div_ceil(8 * max_horizontal);
+        let mcus_high = self.height.div_ceil(8 * max_vertical);
+
+        for component in &mut self.components {
+            component.blocks_wide = mcus_wide * component.horizontal;
+            component.samples =
+                vec![0; component.blocks_wide * 64 * mcus_high * component.vertical];
+        }
+
+        Ok(())
+    }
+
+    fn max_sampling(&self) -> (usize, usize) {
+        self.components.iter().fold((1, 1), |(h, v), component| {
+            (h.max(component.horizontal), v.max(component.vertical))
+        })
+    }
+
+    fn read_quantization_tables(&mut self, mut segment: &[u8]) -> crate::Result<()> {
+        while let [info, rest @ ..] = segment {
+            let precision = info >> 4;
+            let id = (info & 0xF) as usize;
+            let size = if precision == 0 { 64 } else { 128 };
+
+            if id > 3 || rest.len() < size {
+                return Err(invalid("invalid quantization table"));
+            }
+
+            for k in 0..64 {
+                self.quantization[id][k] = if precision == 0 {
+                    rest[k] as u16
+                } else {
+                    u16::from_be_bytes([rest[k * 2], rest[k * 2 + 1]])
+                };
+            }
+
+            segment = &rest[size..];
+        }
+
+        Ok(())
+    }
+
+    fn read_huffman_tables(&mut self, mut segment: &[u8]) -> crate::Result<()> {
+        while segment.len() >= 17 {
+            let class = segment[0] >> 4;
+            let id = (segment[0] & 0xF) as usize;
+            let bits: [u8; 16] = segment[1..17].try_into().unwrap();
+            let count: usize = bits.iter().map(|&count| count as usize).sum();
+
+            if class > 1 || id > 3 || segment.len() < 17 + count {
+                return Err(invalid("invalid Huffman table"));
+            }
+
+            let table = HuffmanTable::new(&bits, segment[17..17 + count].to_vec())?;
+            if class == 0 {
+                self.dc_tables[id] = Some(table);
+            } else {
+                self.ac_tables[id] = Some(table);
+            }
+
+            segment = &segment[17 + count..];
+        }
+
+        Ok(())
+    }
+
+    fn read_scan(&mut self, segment: &[u8]) -> crate::Result<()> {
+        if self.components.is_empty() {
+            return Err(invalid("scan before the frame header"));
+        }
+
+        let count = *segment
+            .first()
+            .ok_or_else(|| invalid("truncated scan header"))? as usize;
+        if count == 0 || segment.len() < 1 + count * 2 + 3 {
+            return Err(invalid("truncated scan header"));
+        }
+
+        let mut scan = Vec::with_capacity(count);
+        for selector in segment[1..1 + count * 2].chunks_exact(2) {
+            let index = self
+                .components
+                .iter()
+                .position(|component| component.id == selector[0])
+                .ok_or_else(|| invalid("scan of an unknown component"))?;
+
+            let component = &mut self.components[index];
+            component.dc_table = (selector[1] >> 4) as usize & 3;
+            component.ac_table = (selector[1] & 0xF) as usize & 3;
+            component.prediction = 0;
+
+            if self.dc_tables[component.dc_table].is_none()
+                || self.ac_tables[component.ac_table].is_none()
+            {
+                return Err(invalid("scan uses a missing Huffman table"));
+            }
+
+            scan.push(index);
+        }
+
+        let (max_horizontal, max_vertical) = self.max_sampling();
+
+        // Interleaved scans are made of MCUs holding the blocks of all their components.
+        // Scans of a single component are made of its blocks, only covering the image itself.
+        let (mcus_wide, mcus_high) = if scan.len() == 1 {
+            let component = &self.components[scan[0]];
+            (
+                (self.width * component.horizontal).div_ceil(8 * max_horizontal),
+                (self.height * component.vertical).div_ceil(8 * max_vertical),
+            )
+        } else {
+            (
+                self.width.div_ceil(8 * max_horizontal),
+                self.height.div_ceil(8 * max_vertical),
+            )
+        };
+
+        let mut reader = BitReader::new(self.data, self.position);
+        let mut coefficients = [0; 64];
+
+        for mcu in 0..mcus_wide * mcus_high {
+            if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval == 0 {
+                let position = reader.restart()?;
+                reader = BitReader::new(self.data, position);
+
+                for &index in &scan {
+                    self.components[index].prediction = 0;
+                }
+            }
+
+            let (mcu_x, mcu_y) = (mcu % mcus_wide, mcu / mcus_wide);
+
+            for &index in &scan {
+                let (blocks_wide, blocks_high) = if scan.len() == 1 {
+                    (1, 1)
+                } else {
+                    let component = &self.components[index];
+                    (component.horizontal, component.vertical)
+                };
+
+                for block in 0..blocks_wide * blocks_high {
+                    let block_x = mcu_x * blocks_wide + block % blocks_wide;
+                    let block_y = mcu_y * blocks_high + block / blocks_wide;
+
+                    self.decode_block(&mut reader, index, &mut coefficients)?;
+
+                    let component = &mut self.components[index];
+                    let samples = inverse_dct(&coefficients, &self.table);
+                    let stride = component.blocks_wide * 8;
+
+                    for (row, samples) in samples.chunks_exact(8).enumerate() {
+                        let start = (block_y * 8 + row) * stride + block_x * 8;
+                        component.samples[start..start + 8].copy_from_slice(samples);
+                    }
+                }
+            }
+        }
+
+        // Resume parsing at the marker following the entropy coded data.
+        let mut position = reader.position.min(self.data.len());
+        while position + 1 < self.data.len()
+            && !(self.data[position] == 0xFF
+                && self.data[position + 1] != 0x00
+                && !(marker::RST0..=marker::RST7).contains(&self.data[position + 1]))
+        {
+            position += 1;
+        }
+        self.position = position;
+
+        Ok(())
+    }
+
+    fn decode_block(
+        &mut self,
+        reader: &mut BitReader,
+        index: usize,
+        coefficients: &mut [i32; 64],
+    ) -> crate::Result<()> {
+        let component = &mut self.components[index];
+        let quantization = &self.quantization[component.quantization];
+        let dc = self.dc_tables[component.dc_table].as_ref().unwrap();
+        let ac = self.ac_tables[component.ac_table].as_ref().unwrap();
+
+        coefficients.fill(0);
+
+        let length = reader.decode(dc)? as u32;
+        if length > 11 {
+            return Err(invalid("corrupted DC coefficient"));
+        }
+        component.prediction += reader.read_value(length);
+        coefficients[0] = component.prediction * quantization[0] as i32;
+
+        let mut k = 1;
+        while k < 64 {
+            let symbol = reader.decode(ac)?;
+            let (run, length) = ((symbol >> 4) as usize, (symbol & 0xF) as u32);
+
+            if length == 0 {
+                if run == 15 {
+                    // Run of 16 zeros.
+                    k += 16;
+                    continue;
+                }
+
+                // End of block.
+                break;
+            }
+
+            k += run;
+            if k > 63 {
+                return Err(invalid("corrupted AC coefficients"));
+            }
+
+            coefficients[ZIGZAG[k]] = reader.read_value(length) * quantization[k] as i32;
+            k += 1;
+        }
+
+        Ok(())
+    }
+
+    fn into_image(self) -> crate::Result<Image> {
+        if self.components.is_empty() {
+            return Err(invalid("missing frame header"));
+        }
+
+        let (width, height) = (self.width, self.height);
+
+        if let [luminance] = self.components.as_slice() {
+            let mut data = Vec::with_capacity(width * height);
+            for y in 0..height {
+                for x in 0..width {
+                    data.push(self.sample(luminance, x, y));
+                }
+            }
+
+            return Image::new(width, height, PixelFormat::L8, data);
+        }
+
+        let data = self.hardware_rgb().unwrap_or_else(|| self.software_rgb());
+
+        Image::new(width, height, PixelFormat::Rgb8, data)
+    }
+
+    /// Returns the sample of `component` at pixel (`x`, `y`), with nearest neighbour upsampling of subsampled components.
+    fn sample(&self, component: &Component, x: usize, y: usize) -> u8 {
+        let (max_horizontal, max_vertical) = self.max_sampling();
+        let x = x * component.horizontal / max_horizontal;
+        let y = y * component.vertical / max_vertical;
+        component.samples[y * component.blocks_wide * 8 + x]
+    }
+
+    /// Convert the YCbCr components to RGB8 pixels in software.
+    fn software_rgb(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.width * self.height * 3);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let luminance = self.sample(&self.components[0], x, y) as f32;
+                let blue = self.sample(&self.components[1], x, y) as f32 - 128.0;
+                let red = self.sample(&self.components[2], x, y) as f32 - 128.0;
+
+                let to_u8 = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+                data.push(to_u8(luminance + 1.402 * red));
+                data.push(to_u8(luminance - 0.344_136 * blue - 0.714_136 * red));
+                data.push(to_u8(luminance + 1.772 * blue));
+            }
+        }
+
+        data
+    }
+
+    /// Convert the YCbCr components to RGB8 pixels with the Y2R unit, if it supports their layout.
+    fn hardware_rgb(&self) -> Option<Vec<u8>> {
+        let [luminance, blue, red] = self.components.as_slice() else {
+            return None;
+        };
+
+        let chroma = |component: &Component| component.horizontal == 1 && component.vertical == 1;
+        if !chroma(blue) || !chroma(red) {
+            return None;
+        }
+
+        let subsampling = match (luminance.horizontal, luminance.vertical) {
+            (2, 2) => Subsampling::Yuv420,
+            (2, 1) => Subsampling::Yuv422,
+            _ => return None,
+        };
+
+        // The planes are converted with their padding, which is cropped afterwards.
+        let stride = luminance.blocks_wide * 8;
+        let lines = luminance.samples.len() / stride;
+        let converted = y2r::ycbcr_to_rgb(
+            &luminance.samples,
+            &blue.samples,
+            &red.samples,
+            stride,
+            lines,
+            subsampling,
+        )
+        .ok()?;
+
+        let mut data = Vec::with_capacity(self.width * self.height * 3);
+        for row in converted.chunks_exact(stride * 3).take(self.height) {
+            data.extend_from_slice(&row[..self.width * 3]);
+        }
+
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: usize, height: usize) -> Image {
+        let data = (0..width * height)
+            .flat_map(|index| {
+                let (x, y) = (index % width, index / width);
+                [(x * 255 / width) as u8, (y * 255 / height) as u8, 0x80]
+            })
+            .collect();
+
+        Image::new(width, height, PixelFormat::Rgb8, data).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        // Sizes which aren't multiples of the MCU size exercise the padding.
+        let image = gradient(45, 30);
+        let file = encode(&image, 95).unwrap();
+        let decoded = decode(&file).unwrap();
+
+        assert_eq!(decoded.format(), PixelFormat::Rgb8);
+        assert_eq!((decoded.width(), decoded.height()), (45, 30));
+
+        let error = image
+            .data()
+            .iter()
+            .zip(decoded.data())
+            .map(|(&a, &b)| (a as i32 - b as i32).abs())
+            .max()
+            .unwrap();
+        assert!(error < 24, "maximum error of {error}");
+
+        let grayscale = image.convert(PixelFormat::L8);
+        let decoded = decode(&encode(&grayscale, 50).unwrap()).unwrap();
+        assert_eq!(decoded.format(), PixelFormat::L8);
+        assert_eq!((decoded.width(), decoded.height()), (45, 30));
+
+        assert!(decode(&file[..file.len() / 2]).is_err());
+        assert!(decode(b"not a JPEG file").is_err());
+    }
+
+    #[test]
+    fn hardware_conversion() {
+        // The encoder uses 4:2:0 subsampling, which the Y2R unit supports.
+        let file = encode(&gradient(45, 30), 95).unwrap();
+        let mut decoder = Decoder::new(&file);
+        decoder.read().unwrap();
+
+        let hardware = decoder.hardware_rgb().unwrap();
+        let software = decoder.software_rgb();
+        assert_eq!(hardware.len(), 45 * 30 * 3);
+
+        let error = hardware
+            .iter()
+            .zip(&software)
+            .map(|(&a, &b)| (a as i32 - b as i32).abs())
+            .max()
+            .unwrap();
+        assert!(error < 8, "maximum error of {error}");
+    }
+}
@@ -0,0 +1,187 @@
+//! Decoded images and image codecs.
+//!
+//! [`Image`] holds the pixels of a decoded image in a linear, left-to-right and top-to-bottom order, unlike the GPU's textures
+//! (which are tiled) and the screens' framebuffers (which are rotated). Codecs such as [`jpeg`] decode into and encode from it.
+#![doc(alias = "picture")]
+
+pub mod jpeg;
+mod y2r;
+
+use crate::Error;
+
+/// Layout of the pixels of an [`Image`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// 8-bit luminance (grayscale), 1 byte per pixel.
+    L8,
+    /// 8-bit red, green and blue channels, 3 bytes per pixel.
+    Rgb8,
+    /// 8-bit red, green, blue and alpha channels, 4 bytes per pixel.
+    Rgba8,
+}
+
+impl PixelFormat {
+    /// Returns the number of bytes per pixel used by this format.
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            Self::L8 => 1,
+            Self::Rgb8 => 3,
+            Self::Rgba8 => 4,
+        }
+    }
+}
+
+/// Decoded image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    data: Vec<u8>,
+}
+
+impl Image {
+    /// Create an image of `width`x`height` pixels from their raw bytes, in left-to-right and top-to-bottom order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `data` doesn't hold exactly `width * height` pixels of the given format.
+    pub fn new(
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+        data: Vec<u8>,
+    ) -> crate::Result<Self> {
+        let wanted = width * height * format.bytes_per_pixel();
+
+        if data.len() < wanted {
+            return Err(Error::BufferTooShort {
+                provided: data.len(),
+                wanted,
+            });
+        }
+
+        if data.len() > wanted {
+            return Err(Error::Other(format!(
+                "the image data is {} bytes long, but {width}x{height} pixels only take {wanted} bytes",
+                data.len()
+            )));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            format,
+            data,
+        })
+    }
+
+    /// Create an [`PixelFormat::Rgb8`] image of `width`x`height` pixels from RGB565 pixels (such as the icons of an [`Smdh`](crate::smdh::Smdh)).
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `pixels` holds less than `width * height` pixels.
+    pub fn from_rgb565(width: usize, height: usize, pixels: &[u16]) -> Self {
+        let data = pixels[..width * height]
+            .iter()
+            .flat_map(|&pixel| {
+                // Replicate the high bits into the low ones, so that full intensity maps to 255.
+                let r = (pixel >> 11) as u8 & 0x1F;
+                let g = (pixel >> 5) as u8 & 0x3F;
+                let b = pixel as u8 & 0x1F;
+
+                [
+                    (r << 3) | (r >> 2),
+                    (g << 2) | (g >> 4),
+                    (b << 3) | (b >> 2),
+                ]
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            format: PixelFormat::Rgb8,
+            data,
+        }
+    }
+
+    /// Returns the width of the image, in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the image, in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the format of the pixels.
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Returns the raw bytes of the pixels, in left-to-right and top-to-bottom order.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the raw bytes of the pixels, consuming the image.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns the pixel at (`x`, `y`) as red, green, blue and alpha channels, or [`None`] if it's out of bounds.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let size = self.format.bytes_per_pixel();
+        let offset = (y * self.width + x) * size;
+
+        Some(to_rgba(self.format, &self.data[offset..offset + size]))
+    }
+
+    /// Returns a copy of the image with its pixels converted to another format.
+    ///
+    /// Converting to [`PixelFormat::L8`] weights the colour channels by their perceived luminance, and
+    /// converting to [`PixelFormat::Rgba8`] makes all pixels opaque unless they already had an alpha channel.
+    pub fn convert(&self, format: PixelFormat) -> Self {
+        if format == self.format {
+            return self.clone();
+        }
+
+        let data = self
+            .data
+            .chunks_exact(self.format.bytes_per_pixel())
+            .flat_map(|pixel| {
+                let [r, g, b, a] = to_rgba(self.format, pixel);
+
+                match format {
+                    PixelFormat::L8 => {
+                        let luminance = (r as u32 * 77 + g as u32 * 150 + b as u32 * 29) >> 8;
+                        vec![luminance as u8]
+                    }
+                    PixelFormat::Rgb8 => vec![r, g, b],
+                    PixelFormat::Rgba8 => vec![r, g, b, a],
+                }
+            })
+            .collect();
+
+        Self {
+            width: self.width,
+            height: self.height,
+            format,
+            data,
+        }
+    }
+}
+
+fn to_rgba(format: PixelFormat, pixel: &[u8]) -> [u8; 4] {
+    match format {
+        PixelFormat::L8 => [pixel[0], pixel[0], pixel[0], 0xFF],
+        PixelFormat::Rgb8 => [pixel[0], pixel[1], pixel[2], 0xFF],
+        PixelFormat::Rgba8 => [pixel[0], pixel[1], pixel[2], pixel[3]],
+    }
+}
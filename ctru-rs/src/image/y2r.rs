@@ -0,0 +1,152 @@
+//! Colour conversion with the Y2R unit.
+//!
+//! The Y2R unit converts planar YUV images to RGB, moving the data in and out of the unit with DMA transfers.
+//! It's used by the [`jpeg`](super::jpeg) decoder, which falls back to its software conversion whenever the unit can't be used.
+#![doc(alias = "y2r")]
+
+use crate::error::ResultCode;
+use crate::Error;
+
+use std::time::Duration;
+
+/// Maximum width and height (in pixels) of the images converted by the Y2R unit.
+const MAX_SIZE: usize = 1024;
+
+/// Time after which a conversion is considered stuck.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Chroma subsampling of the planes converted by [`ycbcr_to_rgb()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum Subsampling {
+    /// Chroma planes have half the width and half the height of the luminance plane.
+    Yuv420,
+    /// Chroma planes have half the width of the luminance plane.
+    Yuv422,
+}
+
+/// Handle to the Y2R service, closed when dropped.
+struct Y2r(());
+
+impl Y2r {
+    #[doc(alias = "y2rInit")]
+    fn new() -> crate::Result<Self> {
+        ResultCode(unsafe { ctru_sys::y2rInit() })?;
+
+        Ok(Self(()))
+    }
+}
+
+impl Drop for Y2r {
+    #[doc(alias = "y2rExit")]
+    fn drop(&mut self) {
+        unsafe { ctru_sys::y2rExit() };
+    }
+}
+
+/// Convert full-range BT.601 YCbCr planes (as stored in JPEG files) of `width`x`lines` pixels to RGB8 pixels.
+///
+/// # Errors
+///
+/// This function will return an error if the Y2R unit doesn't support the image size (its width and number of lines must be
+/// multiples of 8, up to 1024), if the planes' lengths don't match the size, or if the conversion failed.
+#[doc(alias = "Y2RU_StartConversion")]
+pub(super) fn ycbcr_to_rgb(
+    luminance: &[u8],
+    blue: &[u8],
+    red: &[u8],
+    width: usize,
+    lines: usize,
+    subsampling: Subsampling,
+) -> crate::Result<Vec<u8>> {
+    let supported = |size: usize| size % 8 == 0 && (8..=MAX_SIZE).contains(&size);
+    if !supported(width) || !supported(lines) {
+        return Err(Error::Other(format!(
+            "the Y2R unit can't convert a {width}x{lines} image"
+        )));
+    }
+
+    let (chroma_width, chroma_lines, input_format) = match subsampling {
+        Subsampling::Yuv420 => (width / 2, lines / 2, ctru_sys::INPUT_YUV420_INDIV_8),
+        Subsampling::Yuv422 => (width / 2, lines, ctru_sys::INPUT_YUV422_INDIV_8),
+    };
+
+    if luminance.len() != width * lines
+        || blue.len() != chroma_width * chroma_lines
+        || red.len() != chroma_width * chroma_lines
+    {
+        return Err(Error::Other(String::from(
+            "the YCbCr planes don't match the image size",
+        )));
+    }
+
+    let _y2r = Y2r::new()?;
+    let mut output = vec![0; width * lines * 3];
+
+    unsafe {
+        ResultCode(ctru_sys::Y2RU_SetInputFormat(input_format))?;
+        ResultCode(ctru_sys::Y2RU_SetOutputFormat(ctru_sys::OUTPUT_RGB_24))?;
+        ResultCode(ctru_sys::Y2RU_SetRotation(ctru_sys::ROTATION_NONE))?;
+        ResultCode(ctru_sys::Y2RU_SetBlockAlignment(ctru_sys::BLOCK_LINE))?;
+        ResultCode(ctru_sys::Y2RU_SetInputLineWidth(width as u16))?;
+        ResultCode(ctru_sys::Y2RU_SetInputLines(lines as u16))?;
+        // The non-scaling coefficients use the full 0-255 range for all components, like JPEG files.
+        ResultCode(ctru_sys::Y2RU_SetStandardCoefficient(
+            ctru_sys::COEFFICIENT_ITU_R_BT_601,
+        ))?;
+        ResultCode(ctru_sys::Y2RU_SetTransferEndInterrupt(true))?;
+
+        // Each transfer moves one line of input, and eight lines of output (the unit converts blocks of 8 lines).
+        ResultCode(ctru_sys::Y2RU_SetSendingY(
+            luminance.as_ptr().cast(),
+            luminance.len() as u32,
+            width as i16,
+            0,
+        ))?;
+        ResultCode(ctru_sys::Y2RU_SetSendingU(
+            blue.as_ptr().cast(),
+            blue.len() as u32,
+            chroma_width as i16,
+            0,
+        ))?;
+        ResultCode(ctru_sys::Y2RU_SetSendingV(
+            red.as_ptr().cast(),
+            red.len() as u32,
+            chroma_width as i16,
+            0,
+        ))?;
+        ResultCode(ctru_sys::Y2RU_SetReceiving(
+            output.as_mut_ptr().cast(),
+            output.len() as u32,
+            (width * 8 * 3) as i16,
+            0,
+        ))?;
+
+        let mut end_event = 0;
+        ResultCode(ctru_sys::Y2RU_GetTransferEndEvent(&mut end_event))?;
+
+        let mut result = ctru_sys::Y2RU_StartConversion();
+        if ctru_sys::R_SUCCEEDED(result) {
+            result = ctru_sys::svcWaitSynchronization(end_event, TIMEOUT.as_nanos() as i64);
+        }
+
+        // Close the event before checking for errors, so that it doesn't leak.
+        let _ = ctru_sys::svcCloseHandle(end_event);
+
+        // A timed out wait doesn't fail, but leaves the conversion running.
+        let mut busy = false;
+        let _ = ctru_sys::Y2RU_IsBusyConversion(&mut busy);
+        if busy {
+            let _ = ctru_sys::Y2RU_StopConversion();
+            return Err(Error::Other(String::from("the Y2R conversion timed out")));
+        }
+
+        ResultCode(result)?;
+    }
+
+    // The unit writes 24-bit pixels in BGR order, like the framebuffers.
+    for pixel in output.chunks_exact_mut(3) {
+        pixel.swap(0, 2);
+    }
+
+    Ok(output)
+}
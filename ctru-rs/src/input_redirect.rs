@@ -0,0 +1,426 @@
+//! Input redirection over the network.
+//!
+//! This module implements the UDP protocol used by Luma3DS' InputRedirection feature and its clients (such as `InputRedirectionClient-Qt`),
+//! so that the console can be controlled from another device:
+//! - [`InputServer`] receives input packets, which can be merged with the console's own input read via [`Hid`].
+//! - [`InputClient`] sends the console's input to another device, e.g. to use a 3DS as a controller.
+//!
+//! # Packet format
+//!
+//! Each packet holds 5 little-endian `u32` values:
+//! 1. The state of the 12 main buttons (A to Y), with pressed buttons' bits *cleared*.
+//! 2. The touch screen state: `1 << 24 | y << 12 | x` (with 12 bit coordinates) while touching, `0x2000000` otherwise.
+//!    Pixel coordinates are scaled up rounding down, and packet coordinates are scaled down to the nearest pixel,
+//!    so that every pixel survives a round trip.
+//! 3. The circle pad position: `y << 12 | x`, with 12 bit coordinates centered on `0x800`.
+//! 4. The C-stick and ZL/ZR state: `y << 24 | x << 16 | zlzr << 8 | 0x81`, with 8 bit coordinates centered on `0x80`.
+//! 5. The special buttons: bit 0 is HOME, bit 1 is POWER and bit 2 is a long press of POWER.
+#![doc(alias = "InputRedirection")]
+
+use std::io;
+use std::marker::PhantomData;
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::services::hid::{Hid, KeyPad};
+use crate::services::soc::Soc;
+
+/// Default port used by the protocol.
+pub const DEFAULT_PORT: u16 = 4950;
+
+/// Size (in bytes) of an input packet.
+pub const PACKET_SIZE: usize = 20;
+
+const MAIN_BUTTONS: u32 = 0xFFF;
+const NO_TOUCH: u32 = 0x0200_0000;
+const TOUCH_BIT: u32 = 1 << 24;
+const CPAD_CENTER: i32 = 0x800;
+const CPAD_BOUND: i32 = 0x5D0;
+/// Maximum deflection of the circle pad, as reported by [`Hid::circlepad_position()`].
+const CPAD_RANGE: i32 = 156;
+const CSTICK_CENTER: i32 = 0x80;
+const ZR_BIT: u32 = 1 << 1;
+const ZL_BIT: u32 = 1 << 2;
+const SCREEN_WIDTH: u32 = 320;
+const SCREEN_HEIGHT: u32 = 240;
+
+/// Snapshot of the console's input, as transmitted by the protocol.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputState {
+    /// Buttons held down. The C-stick and circle pad directions are ignored (see `circle_pad` and `c_stick`).
+    pub keys: KeyPad,
+    /// Touch screen position in pixels, if the screen is being touched.
+    pub touch: Option<(u16, u16)>,
+    /// Circle pad position, in the same range as [`Hid::circlepad_position()`].
+    pub circle_pad: (i16, i16),
+    /// C-stick position, ranging from -127 to 127 on each axis.
+    pub c_stick: (i16, i16),
+    /// The HOME button is pressed.
+    pub home: bool,
+    /// The POWER button is pressed.
+    pub power: bool,
+    /// The POWER button is held down.
+    pub power_long: bool,
+}
+
+/// How remote input is combined with local input by [`InputServer::merge()`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Buttons pressed on either side are held. The touch screen and sticks use whichever side is active, preferring the remote one.
+    #[default]
+    Combine,
+    /// Remote input completely replaces local input while a client is sending packets.
+    PreferRemote,
+    /// Local input is used, except for the parts (buttons, touch screen, sticks) left idle locally.
+    PreferLocal,
+}
+
+/// Receiver of input packets.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::input_redirect::{InputServer, DEFAULT_PORT};
+/// use ctru::prelude::*;
+///
+/// let apt = Apt::new()?;
+/// let mut hid = Hid::new()?;
+/// let soc = Soc::new()?;
+///
+/// let mut server = InputServer::bind(&soc, DEFAULT_PORT)?;
+///
+/// while apt.main_loop() {
+///     hid.scan_input();
+///     server.poll()?;
+///
+///     let input = server.merge(&hid);
+///     if input.keys.contains(KeyPad::START) {
+///         break;
+///     }
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct InputServer<'soc> {
+    socket: UdpSocket,
+    policy: MergePolicy,
+    timeout: Duration,
+    remote: Option<(InputState, Instant)>,
+    _soc: PhantomData<&'soc Soc>,
+}
+
+/// Sender of input packets.
+pub struct InputClient<'soc> {
+    socket: UdpSocket,
+    _soc: PhantomData<&'soc Soc>,
+}
+
+impl InputState {
+    /// Returns the state with no input at all.
+    pub fn idle() -> Self {
+        Self {
+            keys: KeyPad::empty(),
+            touch: None,
+            circle_pad: (0, 0),
+            c_stick: (0, 0),
+            home: false,
+            power: false,
+            power_long: false,
+        }
+    }
+
+    /// Read the current local input (as of the last call to [`Hid::scan_input()`]).
+    ///
+    /// The C-stick and the HOME/POWER buttons aren't readable through [`Hid`], and are left idle.
+    pub fn from_hid(hid: &Hid) -> Self {
+        let keys = hid.keys_held();
+
+        Self {
+            keys,
+            touch: keys.contains(KeyPad::TOUCH).then(|| hid.touch_position()),
+            circle_pad: hid.circlepad_position(),
+            ..Self::idle()
+        }
+    }
+
+    /// Returns `true` if no input is active.
+    pub fn is_idle(&self) -> bool {
+        *self == Self::idle()
+    }
+
+    /// Serialize the state into a packet.
+    pub fn to_packet(&self) -> [u8; PACKET_SIZE] {
+        let buttons = MAIN_BUTTONS & !self.keys.bits();
+
+        let touch = match self.touch {
+            Some((x, y)) => {
+                let x = u32::from(x).min(SCREEN_WIDTH - 1) * 0xFFF / (SCREEN_WIDTH - 1);
+                let y = u32::from(y).min(SCREEN_HEIGHT - 1) * 0xFFF / (SCREEN_HEIGHT - 1);
+                TOUCH_BIT | y << 12 | x
+            }
+            None => NO_TOUCH,
+        };
+
+        let to_cpad = |value: i16| {
+            (CPAD_CENTER + i32::from(value) * CPAD_BOUND / CPAD_RANGE).clamp(0, 0xFFF) as u32
+        };
+        let circle_pad = to_cpad(self.circle_pad.1) << 12 | to_cpad(self.circle_pad.0);
+
+        let to_cstick = |value: i16| (CSTICK_CENTER + i32::from(value)).clamp(0, 0xFF) as u32;
+        let mut zlzr = 0;
+        if self.keys.contains(KeyPad::ZR) {
+            zlzr |= ZR_BIT;
+        }
+        if self.keys.contains(KeyPad::ZL) {
+            zlzr |= ZL_BIT;
+        }
+        let c_stick =
+            to_cstick(self.c_stick.1) << 24 | to_cstick(self.c_stick.0) << 16 | zlzr << 8 | 0x81;
+
+        let special =
+            u32::from(self.home) | u32::from(self.power) << 1 | u32::from(self.power_long) << 2;
+
+        let mut packet = [0; PACKET_SIZE];
+        for (chunk, value) in packet
+            .chunks_exact_mut(4)
+            .zip([buttons, touch, circle_pad, c_stick, special])
+        {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+
+        packet
+    }
+
+    /// Deserialize a packet.
+    pub fn from_packet(packet: &[u8; PACKET_SIZE]) -> Self {
+        let word = |i: usize| u32::from_le_bytes(packet[i * 4..i * 4 + 4].try_into().unwrap());
+        let (buttons, touch, circle_pad, c_stick, special) =
+            (word(0), word(1), word(2), word(3), word(4));
+
+        let mut keys = KeyPad::from_bits_truncate(!buttons & MAIN_BUTTONS);
+
+        let zlzr = (c_stick >> 8) & 0xFF;
+        if zlzr & ZR_BIT != 0 {
+            keys |= KeyPad::ZR;
+        }
+        if zlzr & ZL_BIT != 0 {
+            keys |= KeyPad::ZL;
+        }
+
+        let touch = (touch & TOUCH_BIT != 0).then(|| {
+            // Rounding to the nearest pixel undoes the scaling of `to_packet`, which rounds down by less than a pixel.
+            let scale = |raw: u32, size: u32| (raw * (size - 1) + 0xFFF / 2) / 0xFFF;
+            let x = scale(touch & 0xFFF, SCREEN_WIDTH);
+            let y = scale((touch >> 12) & 0xFFF, SCREEN_HEIGHT);
+            (x as u16, y as u16)
+        });
+        if touch.is_some() {
+            keys |= KeyPad::TOUCH;
+        }
+
+        let from_cpad = |raw: u32| {
+            ((raw as i32 - CPAD_CENTER) * CPAD_RANGE / CPAD_BOUND).clamp(-CPAD_RANGE, CPAD_RANGE)
+                as i16
+        };
+        let from_cstick = |raw: u32| (raw as i32 - CSTICK_CENTER).clamp(-127, 127) as i16;
+
+        Self {
+            keys,
+            touch,
+            circle_pad: (
+                from_cpad(circle_pad & 0xFFF),
+                from_cpad((circle_pad >> 12) & 0xFFF),
+            ),
+            c_stick: (
+                from_cstick((c_stick >> 16) & 0xFF),
+                from_cstick((c_stick >> 24) & 0xFF),
+            ),
+            home: special & 1 != 0,
+            power: special & 2 != 0,
+            power_long: special & 4 != 0,
+        }
+    }
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::idle()
+    }
+}
+
+impl<'soc> InputServer<'soc> {
+    /// Default time after which a silent client is considered disconnected, and its input released.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Start listening for input packets on the given port (usually [`DEFAULT_PORT`]).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the port is already in use.
+    pub fn bind(_soc: &'soc Soc, port: u16) -> crate::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            policy: MergePolicy::default(),
+            timeout: Self::DEFAULT_TIMEOUT,
+            remote: None,
+            _soc: PhantomData,
+        })
+    }
+
+    /// Set how remote input is combined with local input by [`InputServer::merge()`].
+    pub fn set_merge_policy(&mut self, policy: MergePolicy) {
+        self.policy = policy;
+    }
+
+    /// Set the time after which a silent client is considered disconnected.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Receive all pending packets, keeping the most recent input. Should be called once per frame.
+    ///
+    /// Returns `true` if any packet was received.
+    pub fn poll(&mut self) -> crate::Result<bool> {
+        let mut buffer = [0; PACKET_SIZE];
+        let mut received = false;
+
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(PACKET_SIZE) => {
+                    self.remote = Some((InputState::from_packet(&buffer), Instant::now()));
+                    received = true;
+                }
+                // Malformed packets are ignored.
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(received),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Returns the most recent remote input, or [`None`] if no client sent packets recently.
+    pub fn remote(&self) -> Option<&InputState> {
+        self.remote
+            .as_ref()
+            .filter(|(_, received)| received.elapsed() < self.timeout)
+            .map(|(state, _)| state)
+    }
+
+    /// Combine the remote input with the local one read from `hid`, according to the [`MergePolicy`].
+    pub fn merge(&self, hid: &Hid) -> InputState {
+        let local = InputState::from_hid(hid);
+
+        let Some(&remote) = self.remote() else {
+            return local;
+        };
+
+        let (primary, secondary) = match self.policy {
+            MergePolicy::PreferRemote => return remote,
+            MergePolicy::Combine => (remote, local),
+            MergePolicy::PreferLocal => (local, remote),
+        };
+
+        let stick = |primary: (i16, i16), secondary: (i16, i16)| {
+            if primary != (0, 0) {
+                primary
+            } else {
+                secondary
+            }
+        };
+
+        let keys = match self.policy {
+            MergePolicy::PreferLocal if !local.keys.is_empty() => local.keys,
+            _ => local.keys | remote.keys,
+        };
+
+        InputState {
+            keys,
+            touch: primary.touch.or(secondary.touch),
+            circle_pad: stick(primary.circle_pad, secondary.circle_pad),
+            c_stick: stick(primary.c_stick, secondary.c_stick),
+            home: primary.home || secondary.home,
+            power: primary.power || secondary.power,
+            power_long: primary.power_long || secondary.power_long,
+        }
+    }
+}
+
+impl<'soc> InputClient<'soc> {
+    /// Create a client sending packets to `target` (e.g. `"192.168.1.20:4950"`).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `target` couldn't be resolved or the socket couldn't be created.
+    pub fn connect(_soc: &'soc Soc, target: impl ToSocketAddrs) -> crate::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.connect(target)?;
+
+        Ok(Self {
+            socket,
+            _soc: PhantomData,
+        })
+    }
+
+    /// Returns the address packets are sent to.
+    pub fn target(&self) -> crate::Result<SocketAddr> {
+        Ok(self.socket.peer_addr()?)
+    }
+
+    /// Send an input state.
+    pub fn send(&self, state: &InputState) -> crate::Result<()> {
+        self.socket.send(&state.to_packet())?;
+
+        Ok(())
+    }
+
+    /// Send the current local input (see [`InputState::from_hid()`]). Should be called once per frame.
+    pub fn send_hid(&self, hid: &Hid) -> crate::Result<()> {
+        self.send(&InputState::from_hid(hid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_round_trip() {
+        let state = InputState {
+            keys: KeyPad::A | KeyPad::START | KeyPad::ZL | KeyPad::TOUCH,
+            touch: Some((319, 0)),
+            circle_pad: (156, -156),
+            c_stick: (-127, 40),
+            home: true,
+            ..InputState::idle()
+        };
+
+        assert_eq!(InputState::from_packet(&state.to_packet()), state);
+
+        // Every touch position survives the scaling to 12 bit coordinates.
+        for x in 0..SCREEN_WIDTH as u16 {
+            let y = x % SCREEN_HEIGHT as u16;
+            let state = InputState {
+                keys: KeyPad::TOUCH,
+                touch: Some((x, y)),
+                ..InputState::idle()
+            };
+            assert_eq!(
+                InputState::from_packet(&state.to_packet()).touch,
+                state.touch
+            );
+        }
+
+        assert_eq!(
+            InputState::idle().to_packet()[..8],
+            [0xFF, 0x0F, 0, 0, 0, 0, 0, 0x02]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! IPC helpers.
+//!
+//! Services reply to requests with data larger than the command buffer by writing it to "static buffers":
+//! receive buffers registered by the client in its thread local storage, right after the command buffer.
+//! `libctru` sets these descriptors itself before many of its own requests, and doesn't restore them afterwards.
+//!
+//! Code sending its own requests (e.g. with [`HandleExt::send_service_request()`](crate::services::svc::HandleExt::send_service_request))
+//! should set them within [`StaticBuffers::scope()`]: the descriptors are restored when the scope ends,
+//! so that custom requests and `libctru`'s own requests can't overwrite each other's receive buffers.
+//!
+//! See also <https://www.3dbrew.org/wiki/IPC#Static_Buffer_Descriptor>
+#![doc(alias = "getThreadStaticBuffers")]
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+/// Amount of static buffer descriptors available to each thread.
+pub const STATIC_BUFFER_COUNT: usize = 16;
+
+/// Largest size (in bytes) of a single static buffer.
+pub const MAX_STATIC_BUFFER_SIZE: usize = 0x3FFFF;
+
+/// Each descriptor is a pair of words: the descriptor itself and the buffer address.
+const DESCRIPTOR_WORDS: usize = 2 * STATIC_BUFFER_COUNT;
+
+/// Scope over the current thread's static buffer descriptors, created by [`StaticBuffers::scope()`].
+///
+/// The descriptors in use when the scope starts are saved, and written back when it ends.
+/// Buffers set with [`StaticBuffers::set()`] are borrowed for the whole scope, so they can't be freed
+/// while a service may still write to them.
+///
+/// The scope only lends out shared references to this type, and can't be sent to other threads,
+/// since the descriptors live in the creating thread's local storage.
+pub struct StaticBuffers<'buf> {
+    saved: [u32; DESCRIPTOR_WORDS],
+    // Invariant, so that a scope can't be shortened to accept buffers which don't live until its end.
+    _buffers: PhantomData<Cell<&'buf mut [u8]>>,
+    // Thread local storage is only valid on the creating thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'buf> StaticBuffers<'buf> {
+    /// Run `f` with the current thread's static buffer descriptors saved, restoring them once it returns (or panics).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ctru::ipc::StaticBuffers;
+    ///
+    /// let mut reply = vec![0u8; 0x200];
+    ///
+    /// StaticBuffers::scope(|buffers| {
+    ///     buffers.set(0, &mut reply);
+    ///
+    ///     // Send a request whose reply is written to static buffer 0...
+    /// });
+    ///
+    /// // The previous descriptors are restored, and `reply` contains the data.
+    /// ```
+    #[doc(alias = "getThreadStaticBuffers")]
+    pub fn scope<R>(f: impl FnOnce(&StaticBuffers<'buf>) -> R) -> R {
+        // Dropping the saved descriptors writes them back, even while unwinding.
+        let buffers = Self::save();
+
+        f(&buffers)
+    }
+
+    /// Save the current thread's static buffer descriptors.
+    ///
+    /// This is private, since leaking the returned value would leave the descriptors pointing to borrowed buffers.
+    fn save() -> Self {
+        let mut saved = [0; DESCRIPTOR_WORDS];
+
+        // SAFETY: the static buffer descriptors are always mapped within the thread local storage.
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                ctru_sys::getThreadStaticBuffers(),
+                saved.as_mut_ptr(),
+                DESCRIPTOR_WORDS,
+            )
+        };
+
+        Self {
+            saved,
+            _buffers: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Register `buffer` as the static buffer with the given index, until the end of the scope.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `index` is not lower than [`STATIC_BUFFER_COUNT`],
+    /// or if the buffer is larger than [`MAX_STATIC_BUFFER_SIZE`].
+    #[doc(alias = "IPC_Desc_StaticBuffer")]
+    pub fn set(&self, index: usize, buffer: &'buf mut [u8]) {
+        assert!(index < STATIC_BUFFER_COUNT, "invalid static buffer index");
+        assert!(
+            buffer.len() <= MAX_STATIC_BUFFER_SIZE,
+            "static buffers can't be larger than {MAX_STATIC_BUFFER_SIZE:#X} bytes"
+        );
+
+        // SAFETY: the index is within the descriptor table, and the buffer outlives the scope.
+        unsafe {
+            let descriptors = ctru_sys::getThreadStaticBuffers().add(2 * index);
+
+            descriptors.write(ctru_sys::IPC_Desc_StaticBuffer(buffer.len(), index as _));
+            descriptors.add(1).write(buffer.as_mut_ptr() as u32);
+        }
+    }
+
+    /// Returns the descriptors which were in use when the scope started, as (descriptor, address) pairs.
+    pub fn saved(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.saved.chunks_exact(2).map(|pair| (pair[0], pair[1]))
+    }
+}
+
+impl Drop for StaticBuffers<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the scope was started on this thread, whose local storage is still mapped.
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                self.saved.as_ptr(),
+                ctru_sys::getThreadStaticBuffers(),
+                DESCRIPTOR_WORDS,
+            )
+        };
+    }
+}
@@ -57,14 +57,61 @@ macro_rules! from_impl {
     };
 }
 
+pub mod activity_log;
 pub mod applets;
+pub mod assets;
+pub mod audio;
+pub mod backtrace;
+pub mod cache;
 pub mod console;
+pub mod debug;
+#[cfg(feature = "devtools")]
+pub mod devtools;
+pub mod dma;
+pub mod env;
 pub mod error;
+pub mod extra_memory;
+pub mod fault;
+pub mod font;
+pub mod formats;
+pub mod glyph;
+pub mod gx;
+pub mod i18n;
+pub mod image;
+pub mod input_redirect;
+pub mod ipc;
 pub mod linear;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod mem;
 pub mod mii;
+pub mod net;
 pub mod os;
+pub mod perf;
+pub mod playcoins;
+pub mod power;
 pub mod prelude;
+pub mod progress;
+#[cfg(feature = "render2d")]
+pub mod render2d;
+pub mod render_thread;
+pub mod runtime;
+pub mod savetool;
 mod sealed;
+pub mod seeddb;
 pub mod services;
+pub mod shader;
+pub mod shutdown;
+pub mod smdh;
+pub mod stereo;
+pub mod sync;
+pub mod themes;
+pub mod thread;
+pub mod time;
+pub mod timer;
+#[cfg(feature = "ui")]
+pub mod ui;
+pub mod util;
+pub mod vram;
 
 pub use crate::error::{Error, Result};
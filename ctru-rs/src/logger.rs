@@ -0,0 +1,294 @@
+//! Logging backend for the [`log`] crate.
+//!
+//! The [`Logger`] can send the same log records to multiple outputs (sinks) at once:
+//! - The standard output, which will be shown by a selected [`Console`](crate::console::Console) (or redirected via [`Soc::redirect_to_3dslink()`](crate::services::soc::Soc::redirect_to_3dslink)).
+//! - A file (e.g. on the SD card), which is rotated once it grows past a configurable size.
+//! - The debug output of the attached debugger (see [`output_debug_string()`](crate::debug::output_debug_string)).
+//! - Custom sinks (see [`LoggerBuilder::sink()`]), such as a remote console.
+//!
+//! Levels can be filtered both globally and per-module.
+//!
+//! This module is only available if the `log` feature is enabled.
+#![doc(alias = "log")]
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Builder to configure and install a [`Logger`].
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::logger::Logger;
+/// use log::LevelFilter;
+///
+/// Logger::builder()
+///     .level(LevelFilter::Info)
+///     .module_level("my_app::network", LevelFilter::Trace)
+///     .console()
+///     .debug_output()
+///     .file("sdmc:/my_app/log.txt", 64 * 1024, 3)
+///     .init()?;
+///
+/// log::info!("Logger ready!");
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[must_use]
+pub struct LoggerBuilder {
+    level: LevelFilter,
+    module_levels: Vec<(String, LevelFilter)>,
+    console: bool,
+    debug_output: bool,
+    file: Option<FileSinkConfig>,
+    sinks: Vec<Sink>,
+}
+
+/// [`Log`] implementation sending records to multiple sinks.
+///
+/// Use [`Logger::builder()`] to configure and install it.
+pub struct Logger {
+    level: LevelFilter,
+    module_levels: Vec<(String, LevelFilter)>,
+    console: bool,
+    debug_output: bool,
+    file: Option<Mutex<FileSink>>,
+    sinks: Vec<Sink>,
+}
+
+type Sink = Box<dyn Fn(&str) + Send + Sync>;
+
+struct FileSinkConfig {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+}
+
+struct FileSink {
+    config: FileSinkConfig,
+    file: Option<File>,
+    size: u64,
+}
+
+impl Logger {
+    /// Returns a new [`LoggerBuilder`] with no sinks, logging everything at [`LevelFilter::Info`] level or above.
+    pub fn builder() -> LoggerBuilder {
+        LoggerBuilder {
+            level: LevelFilter::Info,
+            module_levels: Vec::new(),
+            console: false,
+            debug_output: false,
+            file: None,
+            sinks: Vec::new(),
+        }
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        // Module levels are sorted from the most specific path, so the first match wins.
+        self.module_levels
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || (target.starts_with(module.as_str())
+                        && target[module.len()..].starts_with("::"))
+            })
+            .map_or(self.level, |(_, level)| *level)
+    }
+}
+
+impl LoggerBuilder {
+    /// Set the level used for all modules without a specific filter.
+    pub fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set the level used for the given module (and its submodules), e.g. `"my_app::network"`.
+    pub fn module_level(mut self, module: &str, level: LevelFilter) -> Self {
+        self.module_levels.retain(|(m, _)| m != module);
+        self.module_levels.push((String::from(module), level));
+        self
+    }
+
+    /// Send log records to the standard output.
+    pub fn console(mut self) -> Self {
+        self.console = true;
+        self
+    }
+
+    /// Send log records to the attached debugger.
+    pub fn debug_output(mut self) -> Self {
+        self.debug_output = true;
+        self
+    }
+
+    /// Append log records to the file at `path`.
+    ///
+    /// Once the file grows past `max_size` bytes, it's renamed to `<path>.1` (shifting older files to `<path>.2` and so on)
+    /// and a new file is started. At most `max_files` old files are kept. If `max_files` is `0`, the file is simply truncated.
+    ///
+    /// # Notes
+    ///
+    /// The parent directory of the file must already exist.
+    pub fn file(mut self, path: impl Into<PathBuf>, max_size: u64, max_files: usize) -> Self {
+        self.file = Some(FileSinkConfig {
+            path: path.into(),
+            max_size,
+            max_files,
+        });
+        self
+    }
+
+    /// Send log records to `sink`, which receives each record formatted as a single line (without line terminator).
+    ///
+    /// The sink may be called from any thread logging a record.
+    pub fn sink(mut self, sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Build the [`Logger`] without installing it.
+    pub fn build(mut self) -> Logger {
+        self.module_levels
+            .sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+
+        Logger {
+            level: self.level,
+            module_levels: self.module_levels,
+            console: self.console,
+            debug_output: self.debug_output,
+            file: self.file.map(|config| {
+                Mutex::new(FileSink {
+                    config,
+                    file: None,
+                    size: 0,
+                })
+            }),
+            sinks: self.sinks,
+        }
+    }
+
+    /// Build the [`Logger`] and install it as the global logger for the [`log`] crate.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a global logger was already installed.
+    pub fn init(self) -> crate::Result<()> {
+        let logger = self.build();
+
+        let max_level = logger
+            .module_levels
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(logger.level, LevelFilter::max);
+
+        log::set_boxed_logger(Box::new(logger)).map_err(|e| crate::Error::Other(e.to_string()))?;
+        log::set_max_level(max_level);
+
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!("[{} {}] {}", record.level(), record.target(), record.args());
+
+        if self.console {
+            println!("{line}");
+        }
+
+        if self.debug_output {
+            crate::debug::output_debug_string(&line);
+        }
+
+        if let Some(file) = &self.file {
+            // A failing log sink has nowhere to report its errors, so they are ignored.
+            let _ = file.lock().unwrap().write_line(&line);
+        }
+
+        for sink in &self.sinks {
+            sink(&line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Some(file) = &mut file.lock().unwrap().file {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+impl FileSink {
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let length = line.len() as u64 + 1;
+
+        if self.file.is_none() {
+            self.open()?;
+        }
+
+        // This also applies to a file which was already too large when opened (e.g. by a previous run of the application).
+        // Lines longer than the maximum size are still written whole to an empty file.
+        if self.size > 0 && self.size + length > self.config.max_size {
+            self.file = None;
+            self.rotate()?;
+            self.open()?;
+        }
+
+        let file = self.file.as_mut().unwrap();
+        writeln!(file, "{line}")?;
+        self.size += length;
+
+        Ok(())
+    }
+
+    fn open(&mut self) -> std::io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.path)?;
+        self.size = file.metadata()?.len();
+        self.file = Some(file);
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let path = &self.config.path;
+        let numbered = |n: usize| {
+            let mut name = path.clone().into_os_string();
+            name.push(format!(".{n}"));
+            PathBuf::from(name)
+        };
+
+        if self.config.max_files == 0 {
+            return fs::remove_file(path);
+        }
+
+        let _ = fs::remove_file(numbered(self.config.max_files));
+
+        for n in (1..self.config.max_files).rev() {
+            let _ = fs::rename(numbered(n), numbered(n + 1));
+        }
+
+        fs::rename(path, numbered(1))
+    }
+}
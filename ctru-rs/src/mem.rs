@@ -0,0 +1,386 @@
+//! Memory copy and fill routines tuned for the ARM11 CPU.
+//!
+//! [`fast_copy()`] and [`fast_set()`] move data in aligned 32 byte blocks (one cache line), which the compiler turns into
+//! multiple-register `ldm`/`stm` transfers, and prefetch the source data a few cache lines ahead with `pld`.
+//! This noticeably speeds up large transfers such as framebuffer updates and audio buffer mixing.
+//!
+//! Small or mutually misaligned buffers gain nothing from this, so they fall back to [`slice::copy_from_slice()`] and [`slice::fill()`].
+//!
+//! This module also provides [`LinearPool`], a fixed-size block allocator for LINEAR memory.
+//!
+//! # Notes
+//!
+//! The console's ARM11 MPCore has no NEON unit, so these routines only use general purpose registers.
+//! Remember to [flush the data cache](crate::cache) afterwards if the destination is read by the GPU or DSP.
+#![doc(alias = "memcpy")]
+#![doc(alias = "memset")]
+
+use crate::sealed::Sealed;
+use crate::Error;
+
+use std::alloc::{AllocError, Allocator, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::Mutex;
+
+/// Size of a data cache line, which is also the size of the blocks copied by the fast paths.
+pub const CACHE_LINE_SIZE: usize = 32;
+
+/// Buffers shorter than this (in bytes) always use the standard library's routines.
+const FAST_PATH_THRESHOLD: usize = 256;
+
+/// How far ahead of the current position (in bytes) the source data is prefetched.
+const PREFETCH_DISTANCE: usize = 4 * CACHE_LINE_SIZE;
+
+/// Alignment (in bytes) of the blocks handed out by a [`LinearPool`]. This satisfies the requirements of both GPU textures and DSP buffers.
+pub const POOL_BLOCK_ALIGN: usize = 0x80;
+
+/// Plain data types which can be copied byte by byte (integers and floating point numbers).
+///
+/// This trait is sealed and can't be implemented outside of this crate.
+pub trait Plain: Sealed + Copy {}
+
+impl Plain for u8 {}
+impl Plain for i8 {}
+impl Plain for u16 {}
+impl Plain for i16 {}
+impl Plain for u32 {}
+impl Plain for i32 {}
+impl Plain for u64 {}
+impl Plain for i64 {}
+impl Plain for f32 {}
+impl Plain for f64 {}
+
+/// A single cache line worth of data.
+#[derive(Clone, Copy)]
+#[repr(C, align(4))]
+struct Block([u32; CACHE_LINE_SIZE / 4]);
+
+/// Copy all elements from `src` into `dst`, like [`slice::copy_from_slice()`].
+///
+/// # Panics
+///
+/// This function will panic if the two slices have different lengths.
+///
+/// # Example
+///
+/// ```
+/// use ctru::mem::fast_copy;
+///
+/// let samples = vec![0x1234i16; 4096];
+/// let mut buffer = vec![0i16; 4096];
+///
+/// fast_copy(&mut buffer, &samples);
+/// assert_eq!(buffer, samples);
+/// ```
+pub fn fast_copy<T: Plain>(dst: &mut [T], src: &[T]) {
+    assert_eq!(
+        dst.len(),
+        src.len(),
+        "source and destination slices have different lengths"
+    );
+
+    let len = std::mem::size_of_val(src);
+
+    // SAFETY: `Plain` types have no padding and every bit pattern is valid, so they can be viewed as bytes.
+    let dst = unsafe { std::slice::from_raw_parts_mut(dst.as_mut_ptr().cast::<u8>(), len) };
+    let src = unsafe { std::slice::from_raw_parts(src.as_ptr().cast::<u8>(), len) };
+
+    copy_bytes(dst, src);
+}
+
+/// Set all bytes of `dst` to `value`, like [`slice::fill()`].
+///
+/// # Example
+///
+/// ```
+/// use ctru::mem::fast_set;
+///
+/// // Clear a 400x240 RGB8 framebuffer to white.
+/// let mut framebuffer = vec![0u8; 400 * 240 * 3];
+/// fast_set(&mut framebuffer, 0xFF);
+/// assert!(framebuffer.iter().all(|&b| b == 0xFF));
+/// ```
+pub fn fast_set(dst: &mut [u8], value: u8) {
+    if dst.len() < FAST_PATH_THRESHOLD {
+        dst.fill(value);
+        return;
+    }
+
+    let head = dst.as_ptr().align_offset(CACHE_LINE_SIZE).min(dst.len());
+    let (head, rest) = dst.split_at_mut(head);
+    head.fill(value);
+
+    let blocks = rest.len() / CACHE_LINE_SIZE;
+    let (body, tail) = rest.split_at_mut(blocks * CACHE_LINE_SIZE);
+
+    let block = Block([u32::from_ne_bytes([value; 4]); CACHE_LINE_SIZE / 4]);
+    let ptr = body.as_mut_ptr().cast::<Block>();
+
+    for i in 0..blocks {
+        // SAFETY: `body` is aligned to a cache line and contains exactly `blocks` blocks.
+        unsafe { ptr.add(i).write(block) };
+    }
+
+    tail.fill(value);
+}
+
+fn copy_bytes(dst: &mut [u8], src: &[u8]) {
+    // Block transfers need both pointers to be word-aligned at the same time.
+    let misaligned = (dst.as_ptr() as usize ^ src.as_ptr() as usize) % 4 != 0;
+
+    if dst.len() < FAST_PATH_THRESHOLD || misaligned {
+        dst.copy_from_slice(src);
+        return;
+    }
+
+    let head = dst.as_ptr().align_offset(CACHE_LINE_SIZE).min(dst.len());
+    dst[..head].copy_from_slice(&src[..head]);
+
+    let (dst, src) = (&mut dst[head..], &src[head..]);
+    let blocks = dst.len() / CACHE_LINE_SIZE;
+    let body = blocks * CACHE_LINE_SIZE;
+
+    let dst_ptr = dst.as_mut_ptr().cast::<Block>();
+    let src_ptr = src.as_ptr();
+
+    for i in 0..blocks {
+        let offset = i * CACHE_LINE_SIZE;
+
+        if offset + PREFETCH_DISTANCE < src.len() {
+            prefetch(src_ptr.wrapping_add(offset + PREFETCH_DISTANCE));
+        }
+
+        // SAFETY: both ranges are within the slices. `dst` is aligned to a cache line,
+        // while `src` is only word-aligned, which is all `Block` (and `ldm`) requires.
+        unsafe {
+            let block = src_ptr.add(offset).cast::<Block>().read();
+            dst_ptr.add(i).write(block);
+        }
+    }
+
+    dst[body..].copy_from_slice(&src[body..]);
+}
+
+/// Hint the CPU to start loading the cache line containing `ptr`.
+#[inline(always)]
+fn prefetch(ptr: *const u8) {
+    #[cfg(target_arch = "arm")]
+    // SAFETY: `pld` never faults, even on invalid addresses.
+    unsafe {
+        std::arch::asm!("pld [{0}]", in(reg) ptr, options(nostack, readonly, preserves_flags));
+    }
+
+    #[cfg(not(target_arch = "arm"))]
+    let _ = ptr;
+}
+
+/// Pool of fixed-size blocks of LINEAR memory.
+///
+/// The pool reserves all of its memory with a single LINEAR allocation when created, then hands out blocks in constant time.
+/// Since blocks are never split or merged, allocating and freeing them in any order (e.g. audio buffers during a long
+/// streaming session) doesn't fragment the LINEAR heap.
+///
+/// Blocks can be allocated either as [`PoolBlock`] handles, or through the [`Allocator`] implementation of `&LinearPool`
+/// (for allocations up to [`LinearPool::block_size()`]).
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::mem::LinearPool;
+///
+/// // 8 buffers of 4096 bytes each.
+/// let pool = LinearPool::new(4096, 8)?;
+///
+/// let mut buffer = pool.alloc().expect("pool is empty");
+/// buffer.fill(0);
+/// assert_eq!(pool.free_blocks(), 7);
+///
+/// // The block goes back to the pool once dropped.
+/// drop(buffer);
+/// assert_eq!(pool.free_blocks(), 8);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct LinearPool {
+    memory: NonNull<u8>,
+    block_size: usize,
+    block_count: usize,
+    /// Indices of the free blocks, used as a stack.
+    free: Mutex<Vec<usize>>,
+}
+
+// SAFETY: the pool owns its memory, and the free list is protected by a mutex.
+unsafe impl Send for LinearPool {}
+unsafe impl Sync for LinearPool {}
+
+impl LinearPool {
+    /// Reserve a pool of `block_count` blocks of at least `block_size` bytes each.
+    ///
+    /// The block size is rounded up to a multiple of [`POOL_BLOCK_ALIGN`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there isn't enough free LINEAR memory for the whole pool.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `block_size` or `block_count` is 0.
+    #[doc(alias = "linearMemAlign")]
+    pub fn new(block_size: usize, block_count: usize) -> crate::Result<Self> {
+        assert!(
+            block_size > 0 && block_count > 0,
+            "pools must contain at least one non-empty block"
+        );
+
+        let block_size = block_size
+            .checked_next_multiple_of(POOL_BLOCK_ALIGN)
+            .and_then(|size| size.checked_mul(block_count).map(|_| size))
+            .ok_or_else(|| Error::Other(String::from("pool size overflows")))?;
+
+        let memory =
+            unsafe { ctru_sys::linearMemAlign(block_size * block_count, POOL_BLOCK_ALIGN) };
+        let memory = NonNull::new(memory.cast())
+            .ok_or_else(|| Error::Other(String::from("not enough LINEAR memory for the pool")))?;
+
+        // Blocks are exposed as initialized byte slices.
+        unsafe { memory.as_ptr().write_bytes(0, block_size * block_count) };
+
+        Ok(Self {
+            memory,
+            block_size,
+            block_count,
+            // Pop the lowest blocks first.
+            free: Mutex::new((0..block_count).rev().collect()),
+        })
+    }
+
+    /// Returns the size (in bytes) of each block.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Returns the total amount of blocks in the pool.
+    pub fn block_count(&self) -> usize {
+        self.block_count
+    }
+
+    /// Returns the amount of blocks which are currently free.
+    pub fn free_blocks(&self) -> usize {
+        self.free.lock().unwrap().len()
+    }
+
+    /// Take a free block from the pool, or return `None` if all blocks are in use.
+    ///
+    /// The block's contents are left as they were when it was last freed (or zeroed, if it was never used).
+    pub fn alloc(&self) -> Option<PoolBlock<'_>> {
+        let index = self.free.lock().unwrap().pop()?;
+
+        Some(PoolBlock { pool: self, index })
+    }
+
+    fn block_ptr(&self, index: usize) -> NonNull<u8> {
+        // SAFETY: `index` is within the pool, so the offset stays within the allocation.
+        unsafe { NonNull::new_unchecked(self.memory.as_ptr().add(index * self.block_size)) }
+    }
+
+    fn release(&self, index: usize) {
+        self.free.lock().unwrap().push(index);
+    }
+}
+
+impl Drop for LinearPool {
+    #[doc(alias = "linearFree")]
+    fn drop(&mut self) {
+        unsafe { ctru_sys::linearFree(self.memory.as_ptr().cast()) };
+    }
+}
+
+unsafe impl Allocator for &LinearPool {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() > self.block_size || layout.align() > POOL_BLOCK_ALIGN {
+            return Err(AllocError);
+        }
+
+        let index = self.free.lock().unwrap().pop().ok_or(AllocError)?;
+
+        Ok(NonNull::slice_from_raw_parts(
+            self.block_ptr(index),
+            self.block_size,
+        ))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _layout: Layout) {
+        let offset = ptr.as_ptr() as usize - self.memory.as_ptr() as usize;
+
+        self.release(offset / self.block_size);
+    }
+}
+
+/// A block allocated from a [`LinearPool`]. The block returns to the pool when dropped.
+pub struct PoolBlock<'pool> {
+    pool: &'pool LinearPool,
+    index: usize,
+}
+
+impl PoolBlock<'_> {
+    /// Returns a raw pointer to the start of the block.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.pool.block_ptr(self.index).as_ptr()
+    }
+
+    /// Returns a mutable raw pointer to the start of the block.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.pool.block_ptr(self.index).as_ptr()
+    }
+}
+
+impl Deref for PoolBlock<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the block is exclusively owned by this handle and lives as long as the pool.
+        unsafe { std::slice::from_raw_parts(self.as_ptr(), self.pool.block_size) }
+    }
+}
+
+impl DerefMut for PoolBlock<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let len = self.pool.block_size;
+
+        // SAFETY: the block is exclusively owned by this handle and lives as long as the pool.
+        unsafe { std::slice::from_raw_parts_mut(self.as_mut_ptr(), len) }
+    }
+}
+
+impl Drop for PoolBlock<'_> {
+    fn drop(&mut self) {
+        self.pool.release(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_std() {
+        let src: Vec<u8> = (0..2048u32).map(|i| (i * 7) as u8).collect();
+
+        for offset in 0..8 {
+            for len in [0, 31, 300, 1000, 2000] {
+                let mut dst = vec![0u8; 2048];
+                fast_copy(&mut dst[offset..][..len], &src[3..][..len]);
+                assert_eq!(&dst[offset..][..len], &src[3..][..len]);
+
+                fast_set(&mut dst[offset..][..len], 0xA5);
+                assert!(dst[offset..][..len].iter().all(|&b| b == 0xA5));
+            }
+        }
+    }
+}
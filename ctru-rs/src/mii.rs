@@ -3,9 +3,17 @@
 //! This module contains the structs that represent all the data of a Mii.
 //!
 //! Have a look at the [`MiiSelector`](crate::applets::mii_selector::MiiSelector) applet to learn how to ask the user for a specific Mii.
+//!
+//! Miis can also be shared as QR codes, which the Mii Maker scans: see [`to_qr_bytes()`] and [`from_qr_bytes()`].
+
+use crate::error::ResultCode;
+use crate::services::apt::Apt;
+use crate::util::str16;
+use crate::Error;
 
 /// Region lock of the Mii.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RegionLock {
     /// No region-lock.
     None,
@@ -19,6 +27,7 @@ pub enum RegionLock {
 
 /// Charset of the Mii.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Charset {
     /// Japan-USA-Europe unified charset.
     JapanUSAEurope,
@@ -32,6 +41,7 @@ pub enum Charset {
 
 /// Generic options of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Options {
     /// Whether it is allowed to copy the Mii.
     pub is_copying_allowed: bool,
@@ -45,6 +55,7 @@ pub struct Options {
 
 /// Positional Index that the Mii has on the [`MiiSelector`](crate::applets::mii_selector::MiiSelector) window.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SelectorPosition {
     /// Index of the page where the Mii is found.
     pub page_index: u8,
@@ -54,6 +65,7 @@ pub struct SelectorPosition {
 
 /// Console model from which the Mii originated.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OriginConsole {
     /// Nintendo Wii.
     Wii,
@@ -69,6 +81,7 @@ pub enum OriginConsole {
 
 /// Identity of the origin console.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConsoleIdentity {
     /// From which console the Mii originated from.
     pub origin_console: OriginConsole,
@@ -76,6 +89,7 @@ pub struct ConsoleIdentity {
 
 /// Sex of the Mii.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Sex {
     /// Male sex.
     Male,
@@ -85,6 +99,7 @@ pub enum Sex {
 
 /// Generic details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Details {
     /// Sex of the Mii.
     pub sex: Sex,
@@ -102,6 +117,7 @@ pub struct Details {
 
 /// Face style of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FaceStyle {
     /// Face shape.
     pub shape: u8,
@@ -111,6 +127,7 @@ pub struct FaceStyle {
 
 /// Face details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FaceDetails {
     /// Face style.
     pub style: FaceStyle,
@@ -122,6 +139,7 @@ pub struct FaceDetails {
 
 /// Hair details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HairDetails {
     /// Hair style.
     pub style: u8,
@@ -133,6 +151,7 @@ pub struct HairDetails {
 
 /// Eye details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EyeDetails {
     /// Eye style.
     pub style: u8,
@@ -152,6 +171,7 @@ pub struct EyeDetails {
 
 /// Eyebrow details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EyebrowDetails {
     /// Eyebrow style.
     pub style: u8,
@@ -171,6 +191,7 @@ pub struct EyebrowDetails {
 
 /// Nose details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NoseDetails {
     /// Nose style.
     pub style: u8,
@@ -182,6 +203,7 @@ pub struct NoseDetails {
 
 /// Mouth details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MouthDetails {
     /// Mouth style.
     pub style: u8,
@@ -197,6 +219,7 @@ pub struct MouthDetails {
 
 /// Mustache details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MustacheDetails {
     /// Mustache style.
     pub mustache_style: u8,
@@ -204,6 +227,7 @@ pub struct MustacheDetails {
 
 /// Beard details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BeardDetails {
     /// Beard style
     pub style: u8,
@@ -217,6 +241,7 @@ pub struct BeardDetails {
 
 /// Glasses details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlassesDetails {
     /// Glasses style.
     pub style: u8,
@@ -230,6 +255,7 @@ pub struct GlassesDetails {
 
 /// Mole details of the Mii.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MoleDetails {
     /// Whether the Mii has a mole.
     pub is_enabled: bool,
@@ -247,6 +273,7 @@ pub struct MoleDetails {
 ///
 /// This struct can be retrieved by [`MiiSelector::launch()`](crate::applets::mii_selector::MiiSelector::launch).
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mii {
     /// Mii options.
     pub options: Options,
@@ -323,7 +350,7 @@ impl From<ctru_sys::MiiData> for Mii {
         let raw_details: [bool; 16] = get_and_concat_vec_bit(&raw_mii_data, &[0x18, 0x19])
             .try_into()
             .unwrap();
-        let raw_utf16_name = &raw_mii_data[0x1A..0x2D];
+        let raw_utf16_name = &raw_mii_data[0x1A..0x2E];
         let height = raw_mii_data[0x2E];
         let width = raw_mii_data[0x2F];
         let raw_face_style = vec_bit(raw_mii_data[0x30]);
@@ -357,8 +384,8 @@ impl From<ctru_sys::MiiData> for Mii {
             .unwrap();
         let raw_utf16_author = &raw_mii_data[0x48..0x5C];
 
-        let name = utf16_byte_pairs_to_string(raw_utf16_name);
-        let author_name = utf16_byte_pairs_to_string(raw_utf16_author);
+        let name = str16::from_le_bytes(raw_utf16_name);
+        let author_name = str16::from_le_bytes(raw_utf16_author);
 
         let options = Options {
             is_copying_allowed: raw_options[0],
@@ -512,6 +539,123 @@ impl From<ctru_sys::MiiData> for Mii {
     }
 }
 
+/// Size (in bytes) of the data stored in a Mii QR code.
+pub const QR_DATA_SIZE: usize = 0x70;
+
+/// Size (in bytes) of a Mii with its checksum, as stored in QR codes once decrypted.
+const STORE_DATA_SIZE: usize = 0x60;
+
+/// Position and size of the bytes of the Mii moved in front of the QR data, where they are used as the encryption nonce.
+const QR_NONCE_OFFSET: u32 = 0xC;
+const QR_NONCE_SIZE: u32 = 8;
+
+/// Encode a Mii as the data of a QR code the Mii Maker can scan.
+///
+/// The Mii is encrypted with the system's AES-CCM key for Mii QR codes (see `APT:Wrap`), so the result only needs
+/// to be encoded in a QR code (in byte mode) by the application.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::applets::mii_selector::MiiSelector;
+/// use ctru::prelude::*;
+///
+/// let apt = Apt::new()?;
+/// let gfx = Gfx::new()?;
+///
+/// let selection = MiiSelector::new().launch(&apt, &gfx)?;
+/// let qr_data = ctru::mii::to_qr_bytes(&apt, &selection.raw_mii_data)?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "APT_Wrap")]
+pub fn to_qr_bytes(_apt: &Apt, mii: &ctru_sys::MiiData) -> crate::Result<[u8; QR_DATA_SIZE]> {
+    let mut store_data = [0; STORE_DATA_SIZE];
+    let raw = &mii._bindgen_opaque_blob;
+    store_data[..raw.len()].copy_from_slice(raw);
+
+    // The checksum covers the Mii and 2 bytes of padding, and is stored big-endian.
+    let checksum = crc16(&store_data[..STORE_DATA_SIZE - 2]);
+    store_data[STORE_DATA_SIZE - 2..].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut qr_data = [0; QR_DATA_SIZE];
+
+    unsafe {
+        ResultCode(ctru_sys::APT_Wrap(
+            QR_DATA_SIZE as u32,
+            qr_data.as_mut_ptr().cast(),
+            STORE_DATA_SIZE as u32,
+            store_data.as_ptr().cast(),
+            QR_NONCE_OFFSET,
+            QR_NONCE_SIZE,
+        ))?;
+    }
+
+    Ok(qr_data)
+}
+
+/// Decode the data of a Mii QR code (e.g. made by the Mii Maker or by [`to_qr_bytes()`]).
+///
+/// The decoded data can be read with [`Mii::from()`].
+///
+/// # Errors
+///
+/// This function will return an error if `data` is shorter than [`QR_DATA_SIZE`], if it couldn't be decrypted
+/// (e.g. it isn't from a Mii QR code, or it was damaged), or if the checksum of the decrypted Mii is wrong.
+#[doc(alias = "APT_Unwrap")]
+pub fn from_qr_bytes(_apt: &Apt, data: &[u8]) -> crate::Result<ctru_sys::MiiData> {
+    if data.len() < QR_DATA_SIZE {
+        return Err(Error::BufferTooShort {
+            provided: data.len(),
+            wanted: QR_DATA_SIZE,
+        });
+    }
+
+    let mut store_data = [0; STORE_DATA_SIZE];
+
+    unsafe {
+        ResultCode(ctru_sys::APT_Unwrap(
+            STORE_DATA_SIZE as u32,
+            store_data.as_mut_ptr().cast(),
+            QR_DATA_SIZE as u32,
+            data.as_ptr().cast(),
+            QR_NONCE_OFFSET,
+            QR_NONCE_SIZE,
+        ))?;
+    }
+
+    let (content, checksum) = store_data.split_at(STORE_DATA_SIZE - 2);
+    if crc16(content).to_be_bytes() != checksum {
+        return Err(Error::Other(String::from(
+            "the Mii read from the QR code has an invalid checksum",
+        )));
+    }
+
+    let mut mii = ctru_sys::MiiData::default();
+    let raw = &mut mii._bindgen_opaque_blob;
+    let len = raw.len();
+    raw.copy_from_slice(&content[..len]);
+
+    Ok(mii)
+}
+
+/// CRC-16/CCITT (polynomial `0x1021`, initial value 0), used as the checksum of Mii data.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
 // Methods to handle "_bits_", ``bitvec`` cannot compile to 32-bit targets, so I had to create a few
 // helper methods
 
@@ -538,17 +682,17 @@ fn partial_u8_bits_to_u8(data: &[bool]) -> u8 {
     vec_bit_to_u8([data, &leading_zeroes].concat().try_into().unwrap())
 }
 
-/// UTF-16 Strings are give in pairs of bytes (u8), this converts them into an _actual_ string
-fn utf16_byte_pairs_to_string(data: &[u8]) -> String {
-    let raw_utf16_composed = data
-        .chunks_exact(2)
-        .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
-        .collect::<Vec<u16>>();
-
-    String::from_utf16_lossy(raw_utf16_composed.as_slice()).replace('\0', "")
-}
-
 /// Gets the values from the slice and concatenates them
 fn get_and_concat_vec_bit(data: &[u8], get_values: &[usize]) -> Vec<bool> {
     get_values.iter().flat_map(|v| vec_bit(data[*v])).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+    }
+}
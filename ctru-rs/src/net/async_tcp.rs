@@ -0,0 +1,213 @@
+//! Non-blocking TCP sockets for the [`runtime`](crate::runtime).
+//!
+//! [`AsyncTcpStream`] implements [`futures_io::AsyncRead`] and [`futures_io::AsyncWrite`], so it works with the
+//! extension traits of the `futures` ecosystem. While a socket isn't ready, the task awaiting it sleeps and the runtime
+//! polls the socket (the SOC service can't signal kernel events for sockets) along with its other work.
+
+use std::future::{poll_fn, Future};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::os::fd::AsRawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use crate::runtime::{self, WaitSocket};
+
+/// Run `operation` until it doesn't return [`io::ErrorKind::WouldBlock`], waiting for the socket to be ready in between.
+fn poll_io<T>(
+    wait: &mut Option<WaitSocket>,
+    fd: libc::c_int,
+    events: libc::c_short,
+    cx: &mut Context<'_>,
+    mut operation: impl FnMut() -> io::Result<T>,
+) -> Poll<io::Result<T>> {
+    loop {
+        match operation() {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                let socket = wait.get_or_insert_with(|| runtime::wait_socket(fd, events));
+
+                match Pin::new(socket).poll(cx) {
+                    Poll::Ready(()) => *wait = None,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            result => {
+                *wait = None;
+                return Poll::Ready(result);
+            }
+        }
+    }
+}
+
+/// TCP stream usable from futures run by a [`Runtime`](crate::runtime::Runtime).
+///
+/// As with [`std::net`], the [`Soc`](crate::services::soc::Soc) service must be active while the stream is used.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::net::AsyncTcpStream;
+/// use ctru::runtime;
+/// use ctru::services::soc::Soc;
+/// use futures::{AsyncReadExt, AsyncWriteExt};
+///
+/// let soc = Soc::new()?;
+///
+/// let response = runtime::block_on(async {
+///     let mut stream = AsyncTcpStream::connect("example.com:80").await?;
+///     stream
+///         .write_all(b"GET / HTTP/1.0\r\nHost: example.com\r\n\r\n")
+///         .await?;
+///
+///     let mut response = Vec::new();
+///     stream.read_to_end(&mut response).await?;
+///
+///     Ok::<_, std::io::Error>(response)
+/// })?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncTcpStream {
+    inner: TcpStream,
+    read_wait: Option<WaitSocket>,
+    write_wait: Option<WaitSocket>,
+}
+
+impl AsyncTcpStream {
+    /// Open a connection to `addr`.
+    ///
+    /// The SOC service only connects sockets synchronously, so the connection is established by a thread spawned with
+    /// [`runtime::spawn_blocking()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the connection couldn't be established, or if the thread couldn't be spawned.
+    pub async fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+
+        let stream = runtime::spawn_blocking(move || TcpStream::connect(addrs.as_slice()))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
+            .await
+            .map_err(|_| {
+                io::Error::new(io::ErrorKind::Other, "the connection thread panicked")
+            })??;
+
+        Self::from_std(stream)
+    }
+
+    /// Wrap a connected [`TcpStream`], switching it to non-blocking mode.
+    pub fn from_std(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+
+        Ok(Self {
+            inner: stream,
+            read_wait: None,
+            write_wait: None,
+        })
+    }
+
+    /// Returns the underlying [`TcpStream`], which is in non-blocking mode.
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.inner
+    }
+
+    /// Returns the underlying [`TcpStream`], switched back to blocking mode.
+    pub fn into_std(self) -> io::Result<TcpStream> {
+        self.inner.set_nonblocking(false)?;
+        Ok(self.inner)
+    }
+
+    /// Returns the address of the remote peer.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    /// Returns the local address of the stream.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+impl AsyncRead for AsyncTcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let fd = this.inner.as_raw_fd();
+        let inner = &mut this.inner;
+
+        poll_io(&mut this.read_wait, fd, libc::POLLIN, cx, || {
+            inner.read(buf)
+        })
+    }
+}
+
+impl AsyncWrite for AsyncTcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let fd = this.inner.as_raw_fd();
+        let inner = &mut this.inner;
+
+        poll_io(&mut this.write_wait, fd, libc::POLLOUT, cx, || {
+            inner.write(buf)
+        })
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // TCP streams aren't buffered.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.inner.shutdown(Shutdown::Write))
+    }
+}
+
+/// TCP listener usable from futures run by a [`Runtime`](crate::runtime::Runtime).
+///
+/// As with [`std::net`], the [`Soc`](crate::services::soc::Soc) service must be active while the listener is used.
+pub struct AsyncTcpListener {
+    inner: TcpListener,
+}
+
+impl AsyncTcpListener {
+    /// Create a listener bound to `addr`.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::from_std(TcpListener::bind(addr)?)
+    }
+
+    /// Wrap a bound [`TcpListener`], switching it to non-blocking mode.
+    pub fn from_std(listener: TcpListener) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+
+        Ok(Self { inner: listener })
+    }
+
+    /// Returns the local address of the listener.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Wait for a new connection.
+    pub async fn accept(&self) -> io::Result<(AsyncTcpStream, SocketAddr)> {
+        let fd = self.inner.as_raw_fd();
+        let mut wait = None;
+
+        let (stream, addr) =
+            poll_fn(|cx| poll_io(&mut wait, fd, libc::POLLIN, cx, || self.inner.accept())).await?;
+
+        Ok((AsyncTcpStream::from_std(stream)?, addr))
+    }
+}
@@ -0,0 +1,510 @@
+//! FTP server.
+//!
+//! [`FtpServer`] exposes a directory (usually the SD card) to FTP clients on the local network, using passive mode transfers.
+//! It implements the subset of the protocol needed by common clients (e.g. FileZilla, WinSCP or `curl`) to browse, download,
+//! upload, rename and delete files, so file transfer functionality can be embedded in any application.
+//!
+//! # Notes
+//!
+//! The server accepts any user name and password. Only use it on trusted networks.
+#![doc(alias = "ftpd")]
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::marker::PhantomData;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::services::soc::Soc;
+
+/// Interval at which idle sessions check whether the server is shutting down.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time given to a client to connect to a passive data socket.
+const DATA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Size of the buffer used for file transfers.
+const TRANSFER_BUFFER_SIZE: usize = 0x8000;
+
+/// FTP server exposing a directory to FTP clients.
+///
+/// New connections are accepted by [`FtpServer::poll()`], which should be called regularly (e.g. once per frame).
+/// Each session then runs in its own thread until the client disconnects or the server is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::net::ftp::FtpServer;
+/// use ctru::prelude::*;
+///
+/// let apt = Apt::new()?;
+/// let soc = Soc::new()?;
+///
+/// let mut server = FtpServer::bind(&soc, 5000, "sdmc:/")?;
+/// println!("Connect to ftp://{}:5000/", soc.host_address());
+///
+/// while apt.main_loop() {
+///     server.poll()?;
+/// #   break;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct FtpServer<'soc> {
+    listener: TcpListener,
+    root: Arc<PathBuf>,
+    max_sessions: usize,
+    active_sessions: Arc<AtomicUsize>,
+    stop: Arc<AtomicBool>,
+    sessions: Vec<JoinHandle<()>>,
+    _soc: PhantomData<&'soc Soc>,
+}
+
+impl<'soc> FtpServer<'soc> {
+    /// Default maximum amount of concurrent sessions.
+    pub const DEFAULT_MAX_SESSIONS: usize = 4;
+
+    /// Start listening for FTP clients on the given port, serving the contents of `root`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the port is already in use.
+    pub fn bind(_soc: &'soc Soc, port: u16, root: impl Into<PathBuf>) -> crate::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            root: Arc::new(root.into()),
+            max_sessions: Self::DEFAULT_MAX_SESSIONS,
+            active_sessions: Arc::new(AtomicUsize::new(0)),
+            stop: Arc::new(AtomicBool::new(false)),
+            sessions: Vec::new(),
+            _soc: PhantomData,
+        })
+    }
+
+    /// Set the maximum amount of concurrent sessions. Further clients are turned away until a session ends.
+    pub fn set_max_sessions(&mut self, max_sessions: usize) {
+        self.max_sessions = max_sessions;
+    }
+
+    /// Returns the amount of sessions currently open.
+    pub fn active_sessions(&self) -> usize {
+        self.active_sessions.load(Ordering::Relaxed)
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> crate::Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept all pending connections, without blocking if there are none.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if accepting connections or spawning a session thread failed.
+    pub fn poll(&mut self) -> crate::Result<()> {
+        self.sessions.retain(|session| !session.is_finished());
+
+        loop {
+            let mut stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+
+            if self.active_sessions() >= self.max_sessions {
+                let _ = stream.write_all(b"421 Too many connections, try again later.\r\n");
+                continue;
+            }
+
+            self.active_sessions.fetch_add(1, Ordering::Relaxed);
+
+            let root = self.root.clone();
+            let stop = self.stop.clone();
+            let active_sessions = self.active_sessions.clone();
+
+            let session = thread::Builder::new()
+                .name(String::from("ftp-session"))
+                .stack_size(0x8000)
+                .spawn(move || {
+                    let _ = Session::new(stream, &root, &stop).and_then(|mut s| s.run());
+                    active_sessions.fetch_sub(1, Ordering::Relaxed);
+                });
+
+            match session {
+                Ok(session) => self.sessions.push(session),
+                Err(e) => {
+                    self.active_sessions.fetch_sub(1, Ordering::Relaxed);
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for FtpServer<'_> {
+    fn drop(&mut self) {
+        // The sessions must end before the `Soc` service they rely on goes away.
+        self.stop.store(true, Ordering::Relaxed);
+
+        for session in self.sessions.drain(..) {
+            let _ = session.join();
+        }
+    }
+}
+
+struct Session<'a> {
+    control: BufReader<TcpStream>,
+    root: &'a Path,
+    stop: &'a AtomicBool,
+    /// Current directory, as an absolute virtual path (always starting with `/`).
+    cwd: String,
+    passive: Option<TcpListener>,
+    rename_from: Option<PathBuf>,
+}
+
+impl<'a> Session<'a> {
+    fn new(stream: TcpStream, root: &'a Path, stop: &'a AtomicBool) -> io::Result<Self> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        Ok(Self {
+            control: BufReader::new(stream),
+            root,
+            stop,
+            cwd: String::from("/"),
+            passive: None,
+            rename_from: None,
+        })
+    }
+
+    fn run(&mut self) -> io::Result<()> {
+        self.reply(220, "ctru-rs FTP server ready.")?;
+
+        let mut line = String::new();
+
+        while !self.stop.load(Ordering::Relaxed) {
+            match self.control.read_line(&mut line) {
+                Ok(0) => return Ok(()),
+                Ok(_) => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e),
+            }
+
+            // Partial lines (interrupted by a timeout) are kept until the rest arrives.
+            if !line.ends_with('\n') {
+                continue;
+            }
+
+            let command = line.trim_end().to_owned();
+            line.clear();
+
+            let (verb, argument) = command.split_once(' ').unwrap_or((command.as_str(), ""));
+
+            if !self.handle(&verb.to_ascii_uppercase(), argument)? {
+                return Ok(());
+            }
+        }
+
+        self.reply(421, "Server shutting down.")
+    }
+
+    /// Handle a single command. Returns `false` once the session should end.
+    fn handle(&mut self, verb: &str, argument: &str) -> io::Result<bool> {
+        // The rename source only applies to the command right after `RNFR`.
+        if verb != "RNFR" && verb != "RNTO" {
+            self.rename_from = None;
+        }
+
+        match verb {
+            "USER" => self.reply(331, "Any password will do.")?,
+            "PASS" => self.reply(230, "Logged in.")?,
+            "SYST" => self.reply(215, "UNIX Type: L8")?,
+            "FEAT" => {
+                self.reply_multiline(211, &["Features:", " PASV", " SIZE", " UTF8", "End"])?
+            }
+            "OPTS" | "TYPE" | "MODE" | "STRU" | "NOOP" | "ALLO" => self.reply(200, "OK.")?,
+            "PWD" | "XPWD" => {
+                let cwd = self.cwd.replace('"', "\"\"");
+                self.reply(257, &format!("\"{cwd}\" is the current directory."))?
+            }
+            "CWD" | "XCWD" => self.change_directory(argument)?,
+            "CDUP" | "XCUP" => self.change_directory("..")?,
+            "PASV" => self.enter_passive_mode()?,
+            "LIST" | "NLST" => self.list(argument, verb == "NLST")?,
+            "RETR" => self.retrieve(argument)?,
+            "STOR" => self.store(argument, false)?,
+            "APPE" => self.store(argument, true)?,
+            "SIZE" => match fs::metadata(self.resolve(argument)) {
+                Ok(metadata) if metadata.is_file() => {
+                    self.reply(213, &metadata.len().to_string())?
+                }
+                _ => self.reply(550, "No such file.")?,
+            },
+            "DELE" => {
+                let result = fs::remove_file(self.resolve(argument));
+                self.reply_result(result, 250, "File deleted.")?
+            }
+            "MKD" | "XMKD" => {
+                let result = fs::create_dir(self.resolve(argument));
+                self.reply_result(result, 257, "Directory created.")?
+            }
+            "RMD" | "XRMD" => {
+                let result = fs::remove_dir(self.resolve(argument));
+                self.reply_result(result, 250, "Directory removed.")?
+            }
+            "RNFR" => {
+                let path = self.resolve(argument);
+
+                if path.exists() {
+                    self.rename_from = Some(path);
+                    self.reply(350, "Ready for destination name.")?
+                } else {
+                    self.reply(550, "No such file or directory.")?
+                }
+            }
+            "RNTO" => match self.rename_from.take() {
+                Some(from) => {
+                    let result = fs::rename(from, self.resolve(argument));
+                    self.reply_result(result, 250, "Renamed.")?
+                }
+                None => self.reply(503, "RNFR required first.")?,
+            },
+            "QUIT" => {
+                self.reply(221, "Goodbye.")?;
+                return Ok(false);
+            }
+            _ => self.reply(502, "Command not implemented.")?,
+        }
+
+        Ok(true)
+    }
+
+    fn change_directory(&mut self, argument: &str) -> io::Result<()> {
+        let virtual_path = self.virtual_path(argument);
+
+        if self.to_real(&virtual_path).is_dir() {
+            self.cwd = virtual_path;
+            self.reply(250, "Directory changed.")
+        } else {
+            self.reply(550, "No such directory.")
+        }
+    }
+
+    fn enter_passive_mode(&mut self) -> io::Result<()> {
+        let ip = match self.control.get_ref().local_addr()? {
+            SocketAddr::V4(addr) => *addr.ip(),
+            SocketAddr::V6(_) => return self.reply(425, "IPv6 is not supported."),
+        };
+
+        let listener = TcpListener::bind((ip, 0))?;
+        let port = listener.local_addr()?.port();
+        self.passive = Some(listener);
+
+        let [a, b, c, d] = ip.octets();
+        let [p1, p2] = port.to_be_bytes();
+
+        self.reply(
+            227,
+            &format!("Entering Passive Mode ({a},{b},{c},{d},{p1},{p2})."),
+        )
+    }
+
+    fn list(&mut self, argument: &str, names_only: bool) -> io::Result<()> {
+        // Clients commonly pass `ls` flags (e.g. `LIST -a`), which aren't supported and are ignored.
+        let argument = if argument.starts_with('-') {
+            ""
+        } else {
+            argument
+        };
+
+        let entries = match fs::read_dir(self.resolve(argument)) {
+            Ok(entries) => entries,
+            Err(_) => return self.reply(550, "No such directory."),
+        };
+
+        let mut listing = String::new();
+
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if names_only {
+                listing.push_str(&format!("{name}\r\n"));
+                continue;
+            }
+
+            let (kind, size) = match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => ('d', 0),
+                Ok(metadata) => ('-', metadata.len()),
+                Err(_) => continue,
+            };
+
+            listing.push_str(&format!(
+                "{kind}rwxrwxrwx 1 3ds 3ds {size} Jan  1  2000 {name}\r\n"
+            ));
+        }
+
+        self.transfer(|data| data.write_all(listing.as_bytes()))
+    }
+
+    fn retrieve(&mut self, argument: &str) -> io::Result<()> {
+        let mut file = match fs::File::open(self.resolve(argument)) {
+            Ok(file) => file,
+            Err(_) => return self.reply(550, "No such file."),
+        };
+
+        self.transfer(|data| copy(&mut file, data))
+    }
+
+    fn store(&mut self, argument: &str, append: bool) -> io::Result<()> {
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(self.resolve(argument));
+
+        let mut file = match file {
+            Ok(file) => file,
+            Err(_) => return self.reply(550, "Couldn't open the file for writing."),
+        };
+
+        self.transfer(|data| copy(data, &mut file))
+    }
+
+    /// Open the data connection and run `operation` on it, replying to the client accordingly.
+    fn transfer(
+        &mut self,
+        operation: impl FnOnce(&mut TcpStream) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let Some(listener) = self.passive.take() else {
+            return self.reply(425, "Use PASV first.");
+        };
+
+        self.reply(150, "Opening data connection.")?;
+
+        let result = accept_with_timeout(&listener).and_then(|mut data| {
+            operation(&mut data)?;
+            data.flush()
+        });
+
+        match result {
+            Ok(()) => self.reply(226, "Transfer complete."),
+            Err(_) => self.reply(426, "Transfer aborted."),
+        }
+    }
+
+    /// Combine `argument` with the current directory, returning a normalized virtual path.
+    fn virtual_path(&self, argument: &str) -> String {
+        let mut components: Vec<&str> = if argument.starts_with('/') {
+            Vec::new()
+        } else {
+            self.cwd.split('/').filter(|c| !c.is_empty()).collect()
+        };
+
+        for component in argument.split('/') {
+            match component {
+                "" | "." => {}
+                // Going above the root just stays at the root.
+                ".." => {
+                    components.pop();
+                }
+                component => components.push(component),
+            }
+        }
+
+        format!("/{}", components.join("/"))
+    }
+
+    fn to_real(&self, virtual_path: &str) -> PathBuf {
+        let relative = virtual_path.trim_start_matches('/');
+
+        if relative.is_empty() {
+            self.root.to_path_buf()
+        } else {
+            self.root.join(relative)
+        }
+    }
+
+    fn resolve(&self, argument: &str) -> PathBuf {
+        self.to_real(&self.virtual_path(argument))
+    }
+
+    fn reply(&mut self, code: u16, message: &str) -> io::Result<()> {
+        self.control
+            .get_mut()
+            .write_all(format!("{code} {message}\r\n").as_bytes())
+    }
+
+    fn reply_multiline(&mut self, code: u16, lines: &[&str]) -> io::Result<()> {
+        let mut reply = String::new();
+
+        for (i, line) in lines.iter().enumerate() {
+            if i == 0 {
+                reply.push_str(&format!("{code}-{line}\r\n"));
+            } else if i == lines.len() - 1 {
+                reply.push_str(&format!("{code} {line}\r\n"));
+            } else {
+                reply.push_str(&format!("{line}\r\n"));
+            }
+        }
+
+        self.control.get_mut().write_all(reply.as_bytes())
+    }
+
+    fn reply_result(&mut self, result: io::Result<()>, code: u16, message: &str) -> io::Result<()> {
+        match result {
+            Ok(()) => self.reply(code, message),
+            Err(e) => self.reply(550, &e.to_string()),
+        }
+    }
+}
+
+fn accept_with_timeout(listener: &TcpListener) -> io::Result<TcpStream> {
+    listener.set_nonblocking(true)?;
+
+    let mut waited = Duration::ZERO;
+
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                return Ok(stream);
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock && waited < DATA_TIMEOUT => {
+                thread::sleep(Duration::from_millis(10));
+                waited += Duration::from_millis(10);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn copy(reader: &mut impl Read, writer: &mut impl Write) -> io::Result<()> {
+    // `io::copy` uses a small stack buffer, which is much slower on the console's network stack.
+    let mut buffer = vec![0; TRANSFER_BUFFER_SIZE];
+
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => return Ok(()),
+            len => writer.write_all(&buffer[..len])?,
+        }
+    }
+}
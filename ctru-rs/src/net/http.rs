@@ -0,0 +1,608 @@
+//! Minimal HTTP/1.1 server.
+//!
+//! [`HttpServer`] is meant to serve small configuration pages to browsers on the same local network (e.g. a phone or a PC),
+//! which is often more comfortable than editing settings with the console's buttons.
+//! It reads requests from the main loop without ever waiting for slow clients, and doesn't support TLS, keep-alive or chunked transfers.
+#![doc(alias = "web")]
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::marker::PhantomData;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::services::soc::Soc;
+
+/// Maximum size (in bytes) of the request line and headers of a request.
+pub const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/// Maximum size (in bytes) of the body of a request.
+pub const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Time given to a client to send its request before the connection is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time given to a client to receive the response, once its request was read.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// HTTP request method.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// `GET`
+    Get,
+    /// `HEAD`
+    Head,
+    /// `POST`
+    Post,
+    /// `PUT`
+    Put,
+    /// `DELETE`
+    Delete,
+    /// `PATCH`
+    Patch,
+    /// `OPTIONS`
+    Options,
+    /// Any other method.
+    Other(String),
+}
+
+/// HTTP request received by a [`HttpServer`].
+#[derive(Clone, Debug)]
+pub struct Request {
+    method: Method,
+    path: String,
+    query: Option<String>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    peer: Option<SocketAddr>,
+}
+
+/// HTTP response sent by a [`HttpServer`] route.
+#[derive(Clone, Debug)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+type Handler = Box<dyn FnMut(&Request) -> Response>;
+
+struct Route {
+    method: Method,
+    path: String,
+    handler: Handler,
+}
+
+/// Connection whose request is still being received.
+struct Connection {
+    stream: TcpStream,
+    peer: SocketAddr,
+    buffer: Vec<u8>,
+    accepted: Instant,
+}
+
+/// HTTP server with a list of routes.
+///
+/// The server doesn't spawn any thread: pending connections are served by calling [`HttpServer::poll()`],
+/// usually once per frame from the application's main loop. Requests are received over as many calls as needed,
+/// so a slow client never blocks the main loop.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::net::http::{Method, Response};
+/// use ctru::net::HttpServer;
+/// use ctru::prelude::*;
+///
+/// let apt = Apt::new()?;
+/// let soc = Soc::new()?;
+///
+/// let mut server = HttpServer::bind(&soc, 8080)?;
+/// println!("Open http://{}:8080/ in your browser", soc.host_address());
+///
+/// server.route(Method::Get, "/", |_| {
+///     Response::html("<form method=\"post\"><input name=\"nickname\"><button>Save</button></form>")
+/// });
+/// server.route(Method::Post, "/", |request| {
+///     let nickname = request.form_param("nickname").unwrap_or_default();
+///     Response::text(format!("Hello, {nickname}!"))
+/// });
+///
+/// while apt.main_loop() {
+///     server.poll()?;
+/// #   break;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct HttpServer<'soc> {
+    listener: TcpListener,
+    routes: Vec<Route>,
+    connections: Vec<Connection>,
+    _soc: PhantomData<&'soc Soc>,
+}
+
+impl<'soc> HttpServer<'soc> {
+    /// Start listening for connections on the given port, on all network interfaces.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the port is already in use.
+    pub fn bind(_soc: &'soc Soc, port: u16) -> crate::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            routes: Vec::new(),
+            connections: Vec::new(),
+            _soc: PhantomData,
+        })
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> crate::Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Register a route, calling `handler` for requests with the given method and path.
+    ///
+    /// If `path` ends with `*`, the route matches every path starting with the rest of it (e.g. `/static/*`).
+    /// Routes are matched in the order they were registered. Requests without a matching route get a `404 Not Found` response.
+    pub fn route(
+        &mut self,
+        method: Method,
+        path: impl Into<String>,
+        handler: impl FnMut(&Request) -> Response + 'static,
+    ) -> &mut Self {
+        self.routes.push(Route {
+            method,
+            path: path.into(),
+            handler: Box::new(handler),
+        });
+
+        self
+    }
+
+    /// Accept new connections and serve the requests received in full, without waiting for the rest.
+    ///
+    /// Returns the number of requests served.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if accepting connections failed. Errors of single connections (e.g. a client
+    /// disconnecting early) aren't reported. Clients which don't send their whole request within 2 seconds are disconnected.
+    pub fn poll(&mut self) -> crate::Result<usize> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    // Connections which can't be made non-blocking are dropped, without stopping the server.
+                    if stream.set_nonblocking(true).is_ok() {
+                        self.connections.push(Connection {
+                            stream,
+                            peer,
+                            buffer: Vec::new(),
+                            accepted: Instant::now(),
+                        });
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut served = 0;
+        let mut connections = std::mem::take(&mut self.connections);
+
+        connections.retain_mut(|connection| {
+            let response = match connection.receive() {
+                Ok(None) => return connection.accepted.elapsed() < READ_TIMEOUT,
+                Ok(Some(Ok(mut request))) => {
+                    request.peer = Some(connection.peer);
+                    self.dispatch(&request)
+                }
+                Ok(Some(Err(e))) if e.kind() == io::ErrorKind::OutOfMemory => Response::new(413),
+                Ok(Some(Err(_))) => Response::new(400),
+                Err(_) => return false,
+            };
+
+            if connection.respond(&response).is_ok() {
+                served += 1;
+            }
+
+            false
+        });
+
+        // Route handlers can't access the server, so no connection was accepted in the meantime.
+        self.connections = connections;
+
+        Ok(served)
+    }
+
+    fn dispatch(&mut self, request: &Request) -> Response {
+        let route = self.routes.iter_mut().find(|route| {
+            // `HEAD` requests are answered like `GET` requests, without the body.
+            (route.method == request.method
+                || (route.method == Method::Get && request.method == Method::Head))
+                && match route.path.strip_suffix('*') {
+                    Some(prefix) => request.path.starts_with(prefix),
+                    None => route.path == request.path,
+                }
+        });
+
+        let Some(route) = route else {
+            return Response::new(404);
+        };
+
+        let mut response = (route.handler)(request);
+
+        if request.method == Method::Head {
+            let len = response.body.len();
+            response.body.clear();
+            response = response.header("Content-Length", len.to_string());
+        }
+
+        response
+    }
+}
+
+impl Connection {
+    /// Read the data received so far, returning the request once it's complete.
+    fn receive(&mut self) -> io::Result<Option<io::Result<Request>>> {
+        let mut chunk = [0; 1024];
+
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(len) => self.buffer.extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+
+            if self.buffer.len() > MAX_HEADER_SIZE + MAX_BODY_SIZE {
+                return Ok(Some(Err(io::ErrorKind::OutOfMemory.into())));
+            }
+        }
+
+        Ok(parse_buffered(&self.buffer))
+    }
+
+    fn respond(&mut self, response: &Response) -> io::Result<()> {
+        self.stream.set_nonblocking(false)?;
+        self.stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+        response.write_to(&mut self.stream)
+    }
+}
+
+/// Parse the request in `buffer`, or return `None` if more data is needed to complete it.
+fn parse_buffered(buffer: &[u8]) -> Option<io::Result<Request>> {
+    let has_headers =
+        buffer.windows(4).any(|w| w == b"\r\n\r\n") || buffer.windows(2).any(|w| w == b"\n\n");
+
+    if !has_headers && buffer.len() < MAX_HEADER_SIZE {
+        return None;
+    }
+
+    match Request::read(&mut &buffer[..]) {
+        // The headers are complete, so only the body can be missing data.
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+        result => Some(result),
+    }
+}
+
+impl Request {
+    /// Returns the request method.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the (percent-decoded) path of the request, without the query string.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the raw query string (the part of the URL after `?`), if any.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// Returns the decoded value of the query string parameter called `name`, if present.
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        find_param(self.query.as_deref()?, name)
+    }
+
+    /// Returns the decoded value of the form field called `name`, for requests with an `application/x-www-form-urlencoded` body
+    /// (the default encoding of HTML forms).
+    pub fn form_param(&self, name: &str) -> Option<String> {
+        find_param(std::str::from_utf8(&self.body).ok()?, name)
+    }
+
+    /// Returns the value of the header called `name` (case insensitive), if present.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns all headers of the request.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns the body of the request.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the address of the client which sent the request.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+
+    fn read(reader: &mut impl BufRead) -> io::Result<Self> {
+        let mut header_size = 0;
+        let mut next_line = |reader: &mut dyn BufRead| -> io::Result<String> {
+            let mut line = String::new();
+            reader
+                .take((MAX_HEADER_SIZE - header_size) as u64)
+                .read_line(&mut line)?;
+            header_size += line.len();
+
+            match line.strip_suffix('\n') {
+                Some(line) => Ok(line.strip_suffix('\r').unwrap_or(line).to_owned()),
+                None => Err(invalid_data()),
+            }
+        };
+
+        let request_line = next_line(reader)?;
+        let mut parts = request_line.split(' ');
+        let (Some(method), Some(target), Some(version)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid_data());
+        };
+
+        if !version.starts_with("HTTP/1.") {
+            return Err(invalid_data());
+        }
+
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_owned())),
+            None => (target, None),
+        };
+
+        let mut headers = Vec::new();
+        loop {
+            let line = next_line(reader)?;
+            if line.is_empty() {
+                break;
+            }
+
+            let (key, value) = line.split_once(':').ok_or_else(invalid_data)?;
+            headers.push((key.trim().to_owned(), value.trim().to_owned()));
+        }
+
+        let mut request = Self {
+            method: Method::from(method),
+            path: percent_decode(path, false),
+            query,
+            headers,
+            body: Vec::new(),
+            peer: None,
+        };
+
+        if let Some(len) = request.header("Content-Length") {
+            let len: usize = len.parse().map_err(|_| invalid_data())?;
+            if len > MAX_BODY_SIZE {
+                return Err(io::ErrorKind::OutOfMemory.into());
+            }
+
+            request.body = vec![0; len];
+            reader.read_exact(&mut request.body)?;
+        }
+
+        Ok(request)
+    }
+}
+
+impl Response {
+    /// Create an empty response with the given status code (e.g. `200` or `404`).
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Create a `200 OK` response with an HTML body.
+    pub fn html(body: impl Into<String>) -> Self {
+        Self::new(200)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .body(body.into())
+    }
+
+    /// Create a `200 OK` response with a plain text body.
+    pub fn text(body: impl Into<String>) -> Self {
+        Self::new(200)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(body.into())
+    }
+
+    /// Create a `303 See Other` response, redirecting the browser to `location`.
+    ///
+    /// This is the usual response to a form submission, so that reloading the page doesn't submit the form again.
+    pub fn redirect(location: impl Into<String>) -> Self {
+        Self::new(303).header("Location", location)
+    }
+
+    /// Add a header to the response.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the body of the response.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Returns the status code of the response.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+
+        if !self
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+        {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+
+        head.push_str("Connection: close\r\n\r\n");
+
+        writer.write_all(head.as_bytes())?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+impl From<&str> for Method {
+    fn from(method: &str) -> Self {
+        match method {
+            "GET" => Self::Get,
+            "HEAD" => Self::Head,
+            "POST" => Self::Post,
+            "PUT" => Self::Put,
+            "DELETE" => Self::Delete,
+            "PATCH" => Self::Patch,
+            "OPTIONS" => Self::Options,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Patch => "PATCH",
+            Self::Options => "OPTIONS",
+            Self::Other(method) => method,
+        })
+    }
+}
+
+fn find_param(params: &str, name: &str) -> Option<String> {
+    params.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (percent_decode(key, true) == name).then(|| percent_decode(value, true))
+    })
+}
+
+/// Decode `%XX` escapes (and `+` as a space, for form data).
+fn percent_decode(input: &str, plus_as_space: bool) -> String {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let digit = |b: u8| char::from(b).to_digit(16);
+
+                match (digit(bytes[i + 1]), digit(bytes[i + 2])) {
+                    (Some(high), Some(low)) => {
+                        output.push((high * 16 + low) as u8);
+                        i += 3;
+                        continue;
+                    }
+                    _ => output.push(b'%'),
+                }
+            }
+            b'+' if plus_as_space => output.push(b' '),
+            byte => output.push(byte),
+        }
+
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+fn invalid_data() -> io::Error {
+    io::ErrorKind::InvalidData.into()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_request() {
+        let raw = b"POST /settings%20page?tab=audio HTTP/1.1\r\nHost: 3ds\r\nContent-Length: 23\r\n\r\nname=Ash+K&volume=80%25";
+        let request = Request::read(&mut &raw[..]).unwrap();
+
+        assert_eq!(request.method(), &Method::Post);
+        assert_eq!(request.path(), "/settings page");
+        assert_eq!(request.query_param("tab").as_deref(), Some("audio"));
+        assert_eq!(request.header("host"), Some("3ds"));
+        assert_eq!(request.form_param("name").as_deref(), Some("Ash K"));
+        assert_eq!(request.body().len(), 23);
+    }
+
+    #[test]
+    fn partial_request() {
+        let raw = b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nbody";
+
+        // Requests are parsed only once their headers and body are complete.
+        for len in [0, 10, raw.len() - 5, raw.len() - 4, raw.len() - 1] {
+            assert!(parse_buffered(&raw[..len]).is_none());
+        }
+        assert!(matches!(parse_buffered(raw), Some(Ok(_))));
+
+        let garbage = b"not http\r\n\r\n";
+        assert!(matches!(parse_buffered(garbage), Some(Err(_))));
+    }
+}
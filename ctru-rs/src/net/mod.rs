@@ -0,0 +1,11 @@
+//! Networking utilities built on top of [`std::net`].
+//!
+//! Everything in this module requires the [`Soc`](crate::services::soc::Soc) service to be active.
+#![doc(alias = "network")]
+
+pub mod async_tcp;
+pub mod ftp;
+pub mod http;
+
+pub use async_tcp::{AsyncTcpListener, AsyncTcpStream};
+pub use http::HttpServer;
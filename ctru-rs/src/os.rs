@@ -1,5 +1,10 @@
 //! Utilities to get information about the operating system and hardware state.
 
+use std::sync::{Mutex, Once, PoisonError};
+use std::time::Duration;
+
+use crate::services::apt::AptHook;
+
 /// System version information. This struct is used for both kernel and firmware versions.
 ///
 /// # Example
@@ -50,6 +55,203 @@ pub fn kernel_version() -> Version {
     Version(unsafe { ctru_sys::osGetKernelVersion() })
 }
 
+/// Get the version of the Luma3DS custom firmware, or `None` if the console isn't running Luma3DS.
+///
+/// The returned [`Version`] uses the build number as its revision.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// if let Some(luma) = ctru::os::luma_version() {
+///     println!("Luma3DS v{}.{}", luma.major(), luma.minor());
+/// }
+/// ```
+#[doc(alias = "svcGetSystemInfo")]
+pub fn luma_version() -> Option<Version> {
+    // System info type added by Luma3DS. Stock firmware rejects it with an error.
+    const LUMA_SYSTEM_INFO: u32 = 0x10000;
+
+    let mut version = 0;
+    let result = unsafe { ctru_sys::svcGetSystemInfo(&mut version, LUMA_SYSTEM_INFO, 0) };
+
+    (result >= 0).then_some(Version(version as u32))
+}
+
+/// Whether or not the application is running in the Citra emulator.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// if ctru::os::is_citra() {
+///     println!("Running in Citra");
+/// }
+/// ```
+#[doc(alias = "svcGetSystemInfo")]
+#[doc(alias = "emulator")]
+pub fn is_citra() -> bool {
+    // System info type added by Citra, whose first parameter reports whether the emulator is Citra.
+    // The console's kernel rejects it with an error.
+    const CITRA_SYSTEM_INFO: u32 = 0x20000;
+    const IS_CITRA: i32 = 0;
+
+    let mut is_citra = 0;
+    let result = unsafe { ctru_sys::svcGetSystemInfo(&mut is_citra, CITRA_SYSTEM_INFO, IS_CITRA) };
+
+    result >= 0 && is_citra == 1
+}
+
+/// Whether or not the Luma3DS 3GX plugin loader (`plg:ldr`) is available.
+///
+/// # Notes
+///
+/// Game plugins run alongside the application and may intercept system calls,
+/// which is known to disturb timing-sensitive code such as [`Ndsp`](crate::services::ndsp::Ndsp) audio streaming.
+/// Applications may use this to warn the user about possible incompatibilities.
+#[doc(alias = "srvIsServiceRegistered")]
+pub fn is_plugin_loader_available() -> bool {
+    let name = std::ffi::CStr::from_bytes_with_nul(b"plg:ldr\0").unwrap();
+
+    let mut registered = false;
+    let result = unsafe { ctru_sys::srvIsServiceRegistered(&mut registered, name.as_ptr()) };
+
+    result >= 0 && registered
+}
+
+/// Frequency (in Hz) of the system tick counter returned by [`system_tick()`].
+///
+/// The counter runs at the same rate on all models, regardless of the New 3DS' higher CPU clock.
+#[doc(alias = "SYSCLOCK_ARM11")]
+pub const SYSTEM_TICKS_PER_SECOND: u64 = 268_111_856;
+
+/// Returns the number of ticks elapsed since the console was started.
+///
+/// See [`SYSTEM_TICKS_PER_SECOND`] to convert the value to a time unit.
+#[doc(alias = "svcGetSystemTick")]
+pub fn system_tick() -> u64 {
+    unsafe { ctru_sys::svcGetSystemTick() }
+}
+
+/// Convert an amount of system ticks (see [`system_tick()`]) to a [`Duration`].
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    let nanos = u128::from(ticks) * 1_000_000_000 / u128::from(SYSTEM_TICKS_PER_SECOND);
+
+    Duration::from_nanos(nanos as u64)
+}
+
+/// Block the current thread for (at least) the given duration.
+///
+/// Unlike [`std::thread::sleep()`], durations too long for the kernel are clamped rather than rejected.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use std::time::Duration;
+///
+/// ctru::os::sleep(Duration::from_millis(10));
+/// ```
+#[doc(alias = "svcSleepThread")]
+pub fn sleep(duration: Duration) {
+    let nanos = i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX);
+
+    unsafe { ctru_sys::svcSleepThread(nanos) };
+}
+
+/// Block the current thread until the application ran in the foreground for (at least) the given duration.
+///
+/// Time spent suspended (in the HOME Menu, an applet or Sleep mode) doesn't count, so a wait started before the HOME Menu was
+/// opened doesn't end as soon as the application is back.
+///
+/// # Notes
+///
+/// Suspensions are tracked from the first call to this function or to [`suspended_time()`]: earlier ones are not accounted for.
+pub fn sleep_active(duration: Duration) {
+    track_suspensions();
+
+    let start = system_tick();
+    let start_suspended = suspended_ticks();
+
+    loop {
+        let suspended = suspended_ticks() - start_suspended;
+        let active = ticks_to_duration((system_tick() - start).saturating_sub(suspended));
+
+        match duration.checked_sub(active) {
+            Some(remaining) if !remaining.is_zero() => sleep(remaining),
+            _ => break,
+        }
+    }
+}
+
+/// Returns the total time the application spent suspended (in the HOME Menu, an applet or Sleep mode),
+/// including the ongoing suspension if any.
+///
+/// Subtracting it from the elapsed time gives the time the application actually ran in the foreground.
+///
+/// # Notes
+///
+/// Suspensions are tracked from the first call to this function or to [`sleep_active()`]: earlier ones are not accounted for.
+#[doc(alias = "aptHook")]
+pub fn suspended_time() -> Duration {
+    track_suspensions();
+
+    ticks_to_duration(suspended_ticks())
+}
+
+/// Suspensions seen by the APT hook registered by [`track_suspensions()`].
+struct Suspensions {
+    /// Number of ongoing suspensions (e.g. the console can go to sleep while the HOME Menu is open).
+    depth: u32,
+    /// System tick at which the outermost ongoing suspension started.
+    start: u64,
+    /// Total ticks spent in finished suspensions.
+    total: u64,
+}
+
+static SUSPENSIONS: Mutex<Suspensions> = Mutex::new(Suspensions {
+    depth: 0,
+    start: 0,
+    total: 0,
+});
+
+fn track_suspensions() {
+    static REGISTER: Once = Once::new();
+
+    REGISTER.call_once(|| AptHook::register_forever(on_apt_hook));
+}
+
+fn on_apt_hook(hook: ctru_sys::APT_HookType) {
+    // The counters stay consistent even if a panic poisoned the lock.
+    let mut suspensions = SUSPENSIONS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    match hook {
+        ctru_sys::APTHOOK_ONSUSPEND | ctru_sys::APTHOOK_ONSLEEP => {
+            if suspensions.depth == 0 {
+                suspensions.start = system_tick();
+            }
+            suspensions.depth += 1;
+        }
+        ctru_sys::APTHOOK_ONRESTORE | ctru_sys::APTHOOK_ONWAKEUP if suspensions.depth > 0 => {
+            suspensions.depth -= 1;
+            if suspensions.depth == 0 {
+                suspensions.total += system_tick() - suspensions.start;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn suspended_ticks() -> u64 {
+    let suspensions = SUSPENSIONS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if suspensions.depth > 0 {
+        suspensions.total + (system_tick() - suspensions.start)
+    } else {
+        suspensions.total
+    }
+}
+
 // TODO: I can't seem to find good documentation on it, but we could probably
 // define enums for firmware type (NATIVE_FIRM, SAFE_FIRM etc.) as well as
 // application memory layout. Leaving those as future enhancements for now
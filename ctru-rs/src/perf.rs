@@ -0,0 +1,159 @@
+//! Benchmarking helpers.
+//!
+//! Measurements are taken with the system tick counter, which runs at [`SYSTEM_TICKS_PER_SECOND`](crate::os::SYSTEM_TICKS_PER_SECOND)
+//! on every model. Unlike CPU cycle counts, tick counts (and the nanoseconds derived from them) are therefore comparable between
+//! the Old 3DS and the New 3DS, and between the New 3DS' normal and high clock modes.
+//!
+//! # Notes
+//!
+//! The New 3DS' higher CPU clock (804 MHz instead of 268 MHz) and L2 cache are only enabled once requested by the application
+//! (see [`set_speedup()`]). Make sure to compare benchmarks taken with the same setting.
+#![doc(alias = "benchmark")]
+
+use std::fmt;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::os;
+
+/// Last value given to [`set_speedup()`], since `libctru` doesn't report it.
+static SPEEDUP: AtomicBool = AtomicBool::new(false);
+
+/// Number of measured iterations run by [`bench()`].
+pub const DEFAULT_ITERATIONS: u32 = 100;
+
+/// Results of a benchmark run by [`bench()`] or [`bench_iterations()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BenchResult {
+    /// Number of measured iterations.
+    pub iterations: u32,
+    /// Total amount of ticks spent in the measured iterations.
+    pub total_ticks: u64,
+    /// Amount of ticks spent in the fastest iteration.
+    pub min_ticks: u64,
+    /// Amount of ticks spent in the slowest iteration.
+    pub max_ticks: u64,
+    /// Whether the benchmark ran on a New 3DS.
+    pub new_3ds: bool,
+}
+
+/// Measure the time taken by `f`, running it [`DEFAULT_ITERATIONS`] times.
+///
+/// See [`bench_iterations()`] for more details.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::perf;
+///
+/// let result = perf::bench(|| (0..1000u32).sum::<u32>());
+///
+/// println!("{result}");
+/// assert!(result.min_ticks <= result.max_ticks);
+/// ```
+pub fn bench<R>(f: impl FnMut() -> R) -> BenchResult {
+    bench_iterations(DEFAULT_ITERATIONS, f)
+}
+
+/// Measure the time taken by `f`, running it `iterations` times.
+///
+/// `f` is run once more before the measurements start, to warm up the caches. Its return value is passed through [`black_box`],
+/// so the computation isn't optimized away.
+///
+/// # Panics
+///
+/// This function will panic if `iterations` is 0.
+pub fn bench_iterations<R>(iterations: u32, mut f: impl FnMut() -> R) -> BenchResult {
+    assert!(iterations > 0, "a benchmark needs at least one iteration");
+
+    black_box(f());
+
+    let mut total_ticks = 0;
+    let mut min_ticks = u64::MAX;
+    let mut max_ticks = 0;
+
+    for _ in 0..iterations {
+        let start = os::system_tick();
+        black_box(f());
+        let ticks = os::system_tick() - start;
+
+        total_ticks += ticks;
+        min_ticks = min_ticks.min(ticks);
+        max_ticks = max_ticks.max(ticks);
+    }
+
+    BenchResult {
+        iterations,
+        total_ticks,
+        min_ticks,
+        max_ticks,
+        new_3ds: is_new_3ds(),
+    }
+}
+
+/// Enable or disable the New 3DS' higher CPU clock and L2 cache. Does nothing on the Old 3DS.
+#[doc(alias = "osSetSpeedupEnable")]
+pub fn set_speedup(enable: bool) {
+    unsafe { ctru_sys::osSetSpeedupEnable(enable) };
+    SPEEDUP.store(enable, Ordering::Relaxed);
+}
+
+/// Returns `true` if the New 3DS' higher CPU clock was requested with [`set_speedup()`].
+///
+/// This function always returns the requested setting, even on the Old 3DS.
+///
+/// # Notes
+///
+/// Only the calls made through this crate are tracked: calling `osSetSpeedupEnable` directly (e.g. from C code linked
+/// with the application) isn't reflected, since the system offers no way to read the setting back.
+pub fn is_speedup_enabled() -> bool {
+    SPEEDUP.load(Ordering::Relaxed)
+}
+
+impl BenchResult {
+    /// Returns the average amount of ticks spent in an iteration.
+    pub fn mean_ticks(&self) -> u64 {
+        self.total_ticks / u64::from(self.iterations)
+    }
+
+    /// Returns the average time spent in an iteration.
+    pub fn mean(&self) -> Duration {
+        os::ticks_to_duration(self.mean_ticks())
+    }
+
+    /// Returns the time spent in the fastest iteration.
+    pub fn min(&self) -> Duration {
+        os::ticks_to_duration(self.min_ticks)
+    }
+
+    /// Returns the time spent in the slowest iteration.
+    pub fn max(&self) -> Duration {
+        os::ticks_to_duration(self.max_ticks)
+    }
+}
+
+fn is_new_3ds() -> bool {
+    let mut is_new_3ds = false;
+    let _ = unsafe { ctru_sys::APT_CheckNew3DS(&mut is_new_3ds) };
+
+    is_new_3ds
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} iterations on {}: mean {} ticks ({} ns), min {} ticks ({} ns), max {} ticks ({} ns)",
+            self.iterations,
+            if self.new_3ds { "New 3DS" } else { "Old 3DS" },
+            self.mean_ticks(),
+            self.mean().as_nanos(),
+            self.min_ticks,
+            self.min().as_nanos(),
+            self.max_ticks,
+            self.max().as_nanos(),
+        )
+    }
+}
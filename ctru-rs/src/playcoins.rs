@@ -0,0 +1,163 @@
+//! Play Coins.
+//!
+//! Play Coins are earned by walking around while carrying the console and are spent in various system applications and games.
+//! The system keeps track of them in the `gamecoin.dat` file, stored within the shared extdata archive `0xF000000B`.
+//!
+//! This module provides safe read and write access to that file, making sure that the written values stay within the limits enforced by the system.
+//!
+//! # Notes
+//!
+//! Unlike other system files, `gamecoin.dat` is not protected by a checksum, so no CRC needs to be updated after modifying it.
+//! Since the data is stored in shared extdata, the application needs the appropriate access rights to open it.
+//!
+//! See also <https://www.3dbrew.org/wiki/Extdata#Shared_Extdata>
+#![doc(alias = "gamecoin")]
+
+use crate::services::fs::{MountedArchive, SharedArchive};
+use crate::Error;
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+/// Maximum amount of Play Coins a console can hold.
+pub const MAX_PLAY_COINS: u16 = 300;
+
+/// Maximum amount of Play Coins that can be obtained in a single day.
+pub const MAX_DAILY_PLAY_COINS: u16 = 10;
+
+const GAMECOIN_MAGIC: u32 = 0x4F00;
+const GAMECOIN_FILE_SIZE: usize = 0x14;
+const MOUNT_NAME: &str = "gamecoin";
+const FILE_PATH: &str = "gamecoin:/gamecoin.dat";
+
+/// Date of the last update of the Play Coins data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Date {
+    /// Year.
+    pub year: u16,
+    /// Month (starting from 1).
+    pub month: u8,
+    /// Day of the month (starting from 1).
+    pub day: u8,
+}
+
+/// Handle to the console's Play Coins data.
+///
+/// Changes to the data are only saved to the console's storage once [`PlayCoins::save()`] is called.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::playcoins::PlayCoins;
+///
+/// let mut coins = PlayCoins::open()?;
+///
+/// println!("You have {} Play Coins!", coins.count());
+///
+/// // Values above the maximum are clamped to 300.
+/// coins.set_count(1000);
+/// coins.save()?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct PlayCoins {
+    raw: [u8; GAMECOIN_FILE_SIZE],
+    _archive: MountedArchive,
+}
+
+impl PlayCoins {
+    /// Open and read the console's Play Coins data.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the shared extdata archive couldn't be mounted (e.g. because of insufficient access rights),
+    /// if the file couldn't be read, or if its contents aren't valid Play Coins data.
+    pub fn open() -> crate::Result<Self> {
+        let _archive = MountedArchive::shared(SharedArchive::PlayData, MOUNT_NAME)?;
+
+        let data = fs::read(FILE_PATH)?;
+
+        let raw: [u8; GAMECOIN_FILE_SIZE] = data
+            .get(..GAMECOIN_FILE_SIZE)
+            .and_then(|d| d.try_into().ok())
+            .ok_or_else(|| Error::Other(String::from("gamecoin.dat is too short")))?;
+
+        if u32::from_le_bytes(raw[0x0..0x4].try_into().unwrap()) != GAMECOIN_MAGIC {
+            return Err(Error::Other(String::from(
+                "gamecoin.dat has an invalid magic number",
+            )));
+        }
+
+        Ok(Self { raw, _archive })
+    }
+
+    /// Returns the amount of Play Coins currently held.
+    pub fn count(&self) -> u16 {
+        self.read_u16(0x4)
+    }
+
+    /// Set the amount of Play Coins held.
+    ///
+    /// Values higher than [`MAX_PLAY_COINS`] are clamped.
+    pub fn set_count(&mut self, count: u16) {
+        self.write_u16(0x4, count.min(MAX_PLAY_COINS));
+    }
+
+    /// Returns the amount of Play Coins obtained on the day returned by [`PlayCoins::last_update()`].
+    pub fn obtained_today(&self) -> u16 {
+        self.read_u16(0x6)
+    }
+
+    /// Set the amount of Play Coins obtained on the day returned by [`PlayCoins::last_update()`].
+    ///
+    /// Values higher than [`MAX_DAILY_PLAY_COINS`] are clamped.
+    /// Once this value reaches the maximum, no more Play Coins can be obtained until the date changes.
+    pub fn set_obtained_today(&mut self, count: u16) {
+        self.write_u16(0x6, count.min(MAX_DAILY_PLAY_COINS));
+    }
+
+    /// Returns the total step count registered the last time a Play Coin was obtained.
+    pub fn total_step_count(&self) -> u32 {
+        self.read_u32(0x8)
+    }
+
+    /// Returns the step count of the day the last Play Coin was obtained.
+    pub fn daily_step_count(&self) -> u32 {
+        self.read_u32(0xC)
+    }
+
+    /// Returns the date of the last update of the Play Coins data.
+    pub fn last_update(&self) -> Date {
+        Date {
+            year: self.read_u16(0x10),
+            month: self.raw[0x12],
+            day: self.raw[0x13],
+        }
+    }
+
+    /// Write the (possibly modified) Play Coins data back to the console's storage.
+    pub fn save(&self) -> crate::Result<()> {
+        // Extdata files cannot be resized, so we write the data in-place.
+        let mut file = OpenOptions::new().write(true).open(FILE_PATH)?;
+        file.write_all(&self.raw)?;
+
+        Ok(())
+    }
+
+    fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.raw[offset], self.raw[offset + 1]])
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        u32::from_le_bytes(self.raw[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn write_u16(&mut self, offset: usize, value: u16) {
+        self.raw[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+}
@@ -0,0 +1,415 @@
+//! Power saving for idle applications.
+//!
+//! Menus and other mostly static screens don't need to redraw at full speed, keep the backlight at full brightness
+//! or run the New 3DS' CPU at its higher clock while nobody is using the console. [`enter_low_power_idle()`] applies these savings
+//! until the returned [`LowPower`] guard is dropped, and [`IdleMonitor`] does it automatically after some time without any input.
+//!
+//! Applications which don't want to drive a monitor themselves can install a crate-wide policy with [`set_idle_policy()`]:
+//! [`Hid::scan_input()`] then reports the input to it, and [`Apt::main_loop()`] enters or leaves the idle mode.
+//!
+//! [`Apt::main_loop()`]: crate::services::apt::Apt::main_loop
+#![doc(alias = "battery")]
+#![doc(alias = "idle")]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::ResultCode;
+use crate::perf;
+use crate::services::gfx::Gfx;
+use crate::services::hid::Hid;
+
+/// Settings of the low power idle mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IdlePolicy {
+    /// Time without input before [`IdleMonitor`] enters the idle mode.
+    pub timeout: Duration,
+    /// Amount of VBlanks per frame while idle, as waited by [`IdleMonitor::wait_for_frame()`] (e.g. 4 for about 15 FPS).
+    pub frame_divider: u32,
+    /// Backlight brightness level while idle, from 1 (darkest) to 5, or `None` to keep the current brightness.
+    pub brightness: Option<u32>,
+    /// Whether to disable the New 3DS' higher CPU clock while idle (see [`perf::set_speedup()`]).
+    pub downclock: bool,
+}
+
+impl Default for IdlePolicy {
+    /// Returns a policy entering the idle mode after 30 seconds, running at about 15 FPS with the darkest backlight and the normal CPU clock.
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            frame_divider: 4,
+            brightness: Some(1),
+            downclock: true,
+        }
+    }
+}
+
+/// Guard of the low power idle mode, returned by [`enter_low_power_idle()`].
+///
+/// The backlight brightness and the CPU clock are restored when the guard is dropped.
+pub struct LowPower {
+    /// Raw brightness of the top and bottom screens before dimming them.
+    brightness: Option<[u32; 2]>,
+    speedup: bool,
+    frame_divider: u32,
+}
+
+/// Enter the low power idle mode, until the returned guard is dropped.
+///
+/// # Errors
+///
+/// This function will return an error if the backlight couldn't be dimmed (e.g. because the "gsp::Lcd" service couldn't be accessed).
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::power::{self, IdlePolicy};
+///
+/// // Save battery while waiting for a download.
+/// let low_power = power::enter_low_power_idle(&IdlePolicy::default())?;
+///
+/// // ...
+///
+/// drop(low_power);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "GSPLCD_SetBrightness")]
+pub fn enter_low_power_idle(policy: &IdlePolicy) -> crate::Result<LowPower> {
+    let brightness = match policy.brightness {
+        Some(level) => Some(dim_backlight(level)?),
+        None => None,
+    };
+
+    let speedup = perf::is_speedup_enabled();
+    if policy.downclock && speedup {
+        perf::set_speedup(false);
+    }
+
+    Ok(LowPower {
+        brightness,
+        speedup,
+        frame_divider: policy.frame_divider.max(1),
+    })
+}
+
+/// Dim both screens to `level`, returning their previous raw brightness.
+fn dim_backlight(level: u32) -> crate::Result<[u32; 2]> {
+    ResultCode(unsafe { ctru_sys::gspLcdInit() })?;
+
+    let mut brightness = [0; 2];
+    let result = unsafe {
+        let screens = [ctru_sys::GSPLCD_SCREEN_TOP, ctru_sys::GSPLCD_SCREEN_BOTTOM];
+        let mut result = 0;
+
+        for (screen, brightness) in screens.into_iter().zip(&mut brightness) {
+            result = ctru_sys::GSPLCD_GetBrightness(screen, brightness);
+            if ctru_sys::R_FAILED(result) {
+                break;
+            }
+        }
+
+        if ctru_sys::R_SUCCEEDED(result) {
+            result =
+                ctru_sys::GSPLCD_SetBrightness(ctru_sys::GSPLCD_SCREEN_BOTH, level.clamp(1, 5));
+        }
+
+        result
+    };
+
+    if ctru_sys::R_FAILED(result) {
+        unsafe { ctru_sys::gspLcdExit() };
+        return Err(crate::Error::Os(result));
+    }
+
+    Ok(brightness)
+}
+
+impl LowPower {
+    /// Returns the amount of VBlanks per frame while idle.
+    pub fn frame_divider(&self) -> u32 {
+        self.frame_divider
+    }
+}
+
+impl Drop for LowPower {
+    fn drop(&mut self) {
+        if let Some([top, bottom]) = self.brightness {
+            unsafe {
+                let _ = ctru_sys::GSPLCD_SetBrightnessRaw(ctru_sys::GSPLCD_SCREEN_TOP, top);
+                let _ = ctru_sys::GSPLCD_SetBrightnessRaw(ctru_sys::GSPLCD_SCREEN_BOTTOM, bottom);
+                ctru_sys::gspLcdExit();
+            }
+        }
+
+        if self.speedup && !perf::is_speedup_enabled() {
+            perf::set_speedup(true);
+        }
+    }
+}
+
+/// Distance from the circle pad's center under which its position is considered noise rather than input.
+const CIRCLEPAD_DEADZONE: u16 = 16;
+
+/// Duration of a frame at the screens' refresh rate.
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Returns whether `hid` registered any input during the last scan.
+fn has_input(hid: &Hid) -> bool {
+    let (x, y) = hid.circlepad_position();
+
+    !hid.keys_held().is_empty()
+        || !hid.keys_up().is_empty()
+        || x.unsigned_abs() > CIRCLEPAD_DEADZONE
+        || y.unsigned_abs() > CIRCLEPAD_DEADZONE
+}
+
+/// Inactivity tracking of an [`IdleMonitor`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct InactivityTimer {
+    timeout: Duration,
+    last_input: Instant,
+}
+
+impl InactivityTimer {
+    /// Register the input state at `now`, returning whether the timeout elapsed since the last input.
+    fn update(&mut self, input: bool, now: Instant) -> bool {
+        if input {
+            self.last_input = now;
+        }
+
+        now.duration_since(self.last_input) >= self.timeout
+    }
+}
+
+/// Automatic low power idle mode, driven by the application's main loop.
+///
+/// The monitor enters the idle mode once no input was registered for the [policy's timeout](IdlePolicy::timeout),
+/// and leaves it as soon as any button is pressed, the touch screen is touched or the circle pad is moved.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::power::{IdleMonitor, IdlePolicy};
+/// use ctru::services::apt::Apt;
+/// use ctru::services::gfx::Gfx;
+/// use ctru::services::hid::Hid;
+///
+/// let apt = Apt::new()?;
+/// let mut hid = Hid::new()?;
+/// let gfx = Gfx::new()?;
+///
+/// let mut idle = IdleMonitor::new(IdlePolicy::default());
+///
+/// while apt.main_loop() {
+///     hid.scan_input();
+///     idle.update(&hid)?;
+///
+///     // Draw the menu...
+///
+///     // Runs at full speed, or at a quarter of it while idle.
+///     idle.wait_for_frame(&gfx);
+/// #   break;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct IdleMonitor {
+    policy: IdlePolicy,
+    timer: InactivityTimer,
+    low_power: Option<LowPower>,
+}
+
+impl IdleMonitor {
+    /// Create a monitor for `policy`, starting the inactivity timer now.
+    pub fn new(policy: IdlePolicy) -> Self {
+        Self {
+            timer: InactivityTimer {
+                timeout: policy.timeout,
+                last_input: Instant::now(),
+            },
+            policy,
+            low_power: None,
+        }
+    }
+
+    /// Returns the policy applied by the monitor.
+    pub fn policy(&self) -> &IdlePolicy {
+        &self.policy
+    }
+
+    /// Register the input scanned by `hid`, entering or leaving the idle mode if needed. Returns whether the idle mode is active.
+    ///
+    /// Call this once per frame, after [`Hid::scan_input()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the idle mode couldn't be entered (see [`enter_low_power_idle()`]).
+    /// The monitor tries again at the next call.
+    pub fn update(&mut self, hid: &Hid) -> crate::Result<bool> {
+        self.update_input(has_input(hid))
+    }
+
+    fn update_input(&mut self, input: bool) -> crate::Result<bool> {
+        if self.timer.update(input, Instant::now()) {
+            if self.low_power.is_none() {
+                self.low_power = Some(enter_low_power_idle(&self.policy)?);
+            }
+        } else {
+            // Dropping the guard restores the previous settings.
+            self.low_power = None;
+        }
+
+        Ok(self.is_idle())
+    }
+
+    /// Returns `true` if the idle mode is active.
+    pub fn is_idle(&self) -> bool {
+        self.low_power.is_some()
+    }
+
+    /// Leave the idle mode (if active) and restart the inactivity timer, e.g. when the application shows something new on its own.
+    pub fn wake(&mut self) {
+        self.low_power = None;
+        self.timer.last_input = Instant::now();
+    }
+
+    /// Wait for the next frame: the next VBlank, or the [policy's amount](IdlePolicy::frame_divider) of them while idle.
+    pub fn wait_for_frame(&self, gfx: &Gfx) {
+        let vblanks = self.low_power.as_ref().map_or(1, LowPower::frame_divider);
+
+        for _ in 0..vblanks {
+            gfx.wait_for_vblank();
+        }
+    }
+}
+
+/// Monitor installed with [`set_idle_policy()`].
+static GLOBAL_MONITOR: Mutex<Option<IdleMonitor>> = Mutex::new(None);
+
+/// Whether any input was scanned since the last iteration of the main loop, for the [`GLOBAL_MONITOR`].
+static PENDING_INPUT: AtomicBool = AtomicBool::new(false);
+
+/// Set the crate-wide idle policy, or remove it with `None`.
+///
+/// While a policy is set, every [`Hid::scan_input()`] registers the scanned input, and every [`Apt::main_loop()`] enters or leaves
+/// the idle mode like [`IdleMonitor::update()`]. While idle, [`Apt::main_loop()`] also sleeps for the additional frames
+/// of the [policy's frame divider](IdlePolicy::frame_divider), so that the application runs at the reduced frame rate
+/// without waiting for the VBlanks itself.
+///
+/// Removing the policy leaves the idle mode immediately.
+///
+/// # Notes
+///
+/// Failures to enter the idle mode aren't reported, and are tried again at the next iteration of the main loop.
+/// Use an [`IdleMonitor`] instead to handle them.
+///
+/// [`Apt::main_loop()`]: crate::services::apt::Apt::main_loop
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::power::{self, IdlePolicy};
+/// use ctru::services::apt::Apt;
+/// use ctru::services::hid::Hid;
+///
+/// let apt = Apt::new()?;
+/// let mut hid = Hid::new()?;
+///
+/// power::set_idle_policy(Some(IdlePolicy::default()));
+///
+/// while apt.main_loop() {
+///     hid.scan_input();
+///
+///     // Draw the menu...
+/// #   break;
+/// }
+///
+/// power::set_idle_policy(None);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub fn set_idle_policy(policy: Option<IdlePolicy>) {
+    PENDING_INPUT.store(false, Ordering::Relaxed);
+
+    *GLOBAL_MONITOR
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = policy.map(IdleMonitor::new);
+}
+
+/// Returns the crate-wide idle policy set with [`set_idle_policy()`], if any.
+pub fn idle_policy() -> Option<IdlePolicy> {
+    GLOBAL_MONITOR
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .map(|monitor| monitor.policy)
+}
+
+/// Register the input scanned by `hid` for the crate-wide idle policy. Called by [`Hid::scan_input()`].
+pub(crate) fn register_input(hid: &Hid) {
+    if has_input(hid) {
+        PENDING_INPUT.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Update the crate-wide idle policy at an iteration of the main loop. Called by `Apt::main_loop()`.
+pub(crate) fn drive_main_loop() {
+    let vblanks = {
+        let mut monitor = GLOBAL_MONITOR
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(monitor) = monitor.as_mut() else {
+            return;
+        };
+
+        let _ = monitor.update_input(PENDING_INPUT.swap(false, Ordering::Relaxed));
+        monitor
+            .low_power
+            .as_ref()
+            .map_or(1, LowPower::frame_divider)
+    };
+
+    if vblanks > 1 {
+        thread::sleep(FRAME_DURATION * (vblanks - 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inactivity_timeout() {
+        let start = Instant::now();
+        let mut timer = InactivityTimer {
+            timeout: Duration::from_secs(30),
+            last_input: start,
+        };
+
+        assert!(!timer.update(false, start + Duration::from_secs(29)));
+        assert!(timer.update(false, start + Duration::from_secs(30)));
+
+        // Any input restarts the timer.
+        assert!(!timer.update(true, start + Duration::from_secs(31)));
+        assert!(!timer.update(false, start + Duration::from_secs(60)));
+        assert!(timer.update(false, start + Duration::from_secs(61)));
+    }
+}
@@ -0,0 +1,235 @@
+//! Progress reporting for long operations.
+//!
+//! Operations which can take several seconds (copying files, downloading, installing titles, exporting save data...) accept a [`Progress`] implementation,
+//! which they periodically inform about the amount of bytes processed and the current stage of the operation.
+//! The same implementation can also ask the operation to stop early, in which case it returns a `Cancelled` error
+//! (e.g. [`Error::Cancelled`](crate::Error::Cancelled)).
+//!
+//! These operations usually run on a worker thread while the main thread keeps drawing the UI: [`SharedProgress`] can be
+//! cloned into the worker thread and read from the main thread to drive a single progress bar for any of them.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! #
+//! use ctru::progress::SharedProgress;
+//! use ctru::services::fs;
+//!
+//! let progress = SharedProgress::new();
+//!
+//! let mut worker_progress = progress.clone();
+//! let worker = std::thread::spawn(move || {
+//!     fs::copy_with_progress("sdmc:/video.avi", "sdmc:/backup/video.avi", &mut worker_progress)
+//! });
+//!
+//! while !worker.is_finished() {
+//!     let (done, total) = progress.position();
+//!     println!("{}: {done}/{} bytes", progress.stage(), total.unwrap_or(0));
+//!
+//!     std::thread::sleep(std::time::Duration::from_millis(100));
+//! }
+//!
+//! worker.join().unwrap()?;
+//! #
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Size of the buffer used by [`copy()`].
+const COPY_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Receiver of the progress of a long operation.
+///
+/// `()` implements this trait by ignoring all updates, as do closures taking the amount of bytes `done` and the `total`.
+pub trait Progress {
+    /// Called when the operation moves on to a new stage (e.g. "Reading", then "Writing").
+    ///
+    /// The amount of bytes done is reset by the following call to [`Progress::update()`].
+    fn set_stage(&mut self, label: &str) {
+        let _ = label;
+    }
+
+    /// Called with the amount of bytes processed so far in the current stage, out of `total` if it's known.
+    fn update(&mut self, done: u64, total: Option<u64>);
+
+    /// Returns `true` if the operation should stop as soon as possible.
+    ///
+    /// Operations check this between chunks of work, so they may keep running for a short while after the request.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+impl Progress for () {
+    fn update(&mut self, _done: u64, _total: Option<u64>) {}
+}
+
+impl<F: FnMut(u64, Option<u64>)> Progress for F {
+    fn update(&mut self, done: u64, total: Option<u64>) {
+        self(done, total)
+    }
+}
+
+#[derive(Default)]
+struct SharedState {
+    stage: String,
+    done: u64,
+    total: Option<u64>,
+}
+
+/// [`Progress`] implementation which can be shared between threads.
+///
+/// All clones report to and read from the same state: give one clone to the operation and keep another one to read its progress.
+#[derive(Clone, Default)]
+pub struct SharedProgress {
+    state: Arc<Mutex<SharedState>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl SharedProgress {
+    /// Create a new progress state, with no stage and no bytes done.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the label of the current stage, or an empty string if the operation didn't report one yet.
+    pub fn stage(&self) -> String {
+        self.state.lock().unwrap().stage.clone()
+    }
+
+    /// Returns the amount of bytes done in the current stage, and the total if it's known.
+    pub fn position(&self) -> (u64, Option<u64>) {
+        let state = self.state.lock().unwrap();
+        (state.done, state.total)
+    }
+
+    /// Returns the completion of the current stage, between 0 and 1, or [`None`] if its total is unknown.
+    pub fn fraction(&self) -> Option<f32> {
+        match self.position() {
+            (_, Some(0)) => Some(1.0),
+            (done, Some(total)) => Some((done as f32 / total as f32).min(1.0)),
+            (_, None) => None,
+        }
+    }
+
+    /// Ask the operation to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Reset the state, to report the progress of another operation.
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = SharedState::default();
+        self.cancelled.store(false, Ordering::Release);
+    }
+}
+
+impl Progress for SharedProgress {
+    fn set_stage(&mut self, label: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.stage = String::from(label);
+        state.done = 0;
+        state.total = None;
+    }
+
+    fn update(&mut self, done: u64, total: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        state.done = done;
+        state.total = total;
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
+
+/// Copy the contents of `reader` into `writer`, reporting the amount of bytes copied out of `total` to `progress`.
+///
+/// Returns the amount of bytes copied.
+///
+/// # Errors
+///
+/// This function will return [`Error::Cancelled`](crate::Error::Cancelled) if `progress` asked to stop,
+/// or an [`Error::Io`](crate::Error::Io) if reading or writing failed.
+pub fn copy(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    total: Option<u64>,
+    progress: &mut impl Progress,
+) -> crate::Result<u64> {
+    let mut buffer = vec![0; COPY_CHUNK_SIZE];
+    let mut done = 0;
+
+    progress.update(0, total);
+
+    loop {
+        if progress.is_cancelled() {
+            return Err(crate::Error::Cancelled);
+        }
+
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        writer.write_all(&buffer[..read])?;
+
+        done += read as u64;
+        progress.update(done, total);
+    }
+
+    writer.flush()?;
+
+    Ok(done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_reports_and_cancels() {
+        let data = vec![0x5A; COPY_CHUNK_SIZE * 2 + 10];
+
+        let mut updates = Vec::new();
+        let mut output = Vec::new();
+        let copied = copy(
+            data.as_slice(),
+            &mut output,
+            Some(data.len() as u64),
+            &mut |done: u64, _: Option<u64>| updates.push(done),
+        )
+        .unwrap();
+
+        assert_eq!(copied, data.len() as u64);
+        assert_eq!(output, data);
+        assert_eq!(updates.last(), Some(&(data.len() as u64)));
+
+        let mut progress = SharedProgress::new();
+        progress.set_stage("Copying");
+        assert_eq!(progress.stage(), "Copying");
+        assert_eq!(progress.fraction(), None);
+
+        progress.cancel();
+        let result = copy(data.as_slice(), io::sink(), None, &mut progress.clone());
+        assert!(matches!(result, Err(crate::Error::Cancelled)));
+
+        progress.reset();
+        copy(
+            data.as_slice(),
+            io::sink(),
+            Some(data.len() as u64),
+            &mut progress.clone(),
+        )
+        .unwrap();
+        assert_eq!(progress.fraction(), Some(1.0));
+    }
+}
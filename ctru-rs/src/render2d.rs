@@ -0,0 +1,1002 @@
+//! Immediate-mode software 2D renderer.
+//!
+//! This module draws filled rectangles and quads, textured sprites (one by one or in [`SpriteBatch`]es) and text onto a [`RenderTarget`],
+//! with alpha blending and an optional scissor rectangle, and then presents the result on one of the screens.
+//! It covers the needs of menus and other UI-heavy applications without writing any shader.
+//!
+//! # Notes
+//!
+//! Drawing isn't hardware accelerated: the [`SoftwareRenderer`] rasterizes every primitive on the CPU, since the crate doesn't drive
+//! the GPU's 3D pipeline. Applications drawing many large sprites per frame should use the GPU through `citro3d` instead.
+//!
+//! Render targets use the GPU's tiled layout, so the GPU's transfer engine still handles the bulk operations on its own:
+//! it clears them ([`RenderTarget::clear()`]) and converts them to the screens' framebuffer format ([`RenderTarget::present()`]).
+//! Targets can also be kept in VRAM (see [`RenderTarget::new_in_vram()`]), which the GPU reads faster when presenting them.
+//!
+//! Coordinates are expressed in pixels, with the origin in the top-left corner of the screen. Use [`TOP_SCREEN_SIZE`] and
+//! [`BOTTOM_SCREEN_SIZE`] to create render targets matching the screens.
+//!
+//! # Example
+//!
+//! ```
+//! # let _runner = test_runner::GdbRunner::default();
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! #
+//! use ctru::render2d::{Color, Rect, RenderTarget, SoftwareRenderer, BOTTOM_SCREEN_SIZE};
+//! use ctru::services::gfx::{Flush, Gfx, Swap};
+//!
+//! let gfx = Gfx::new()?;
+//! let mut target = RenderTarget::new(BOTTOM_SCREEN_SIZE.0, BOTTOM_SCREEN_SIZE.1)?;
+//!
+//! target.clear(&gfx, Color::rgb(0x20, 0x20, 0x40))?;
+//!
+//! let mut renderer = SoftwareRenderer::new(&mut target);
+//! renderer.fill_rect(Rect::new(10, 10, 300, 40), Color::new(0xFF, 0xFF, 0xFF, 0x80));
+//! renderer.draw_text(20, 26, "Settings", Color::BLACK, 1);
+//!
+//! let mut bottom_screen = gfx.bottom_screen.borrow_mut();
+//! target.present(&gfx, &mut *bottom_screen)?;
+//! bottom_screen.flush_buffers();
+//! bottom_screen.swap_buffers();
+//! #
+//! # Ok(())
+//! # }
+//! ```
+#![doc(alias = "citro2d")]
+#![doc(alias = "2d")]
+
+use crate::dma::{self, ADDRESS_ALIGNMENT};
+use crate::gx::{self, Dimensions, FillWidth, TransferFlags, TransferFormat};
+use crate::image::{Image, PixelFormat};
+use crate::linear::LinearAllocator;
+use crate::services::gfx::{Gfx, Screen};
+use crate::vram::VramAllocator;
+use crate::Error;
+
+use std::alloc::{Allocator, Layout};
+
+/// Size (width, height) of the top screen, in pixels.
+pub const TOP_SCREEN_SIZE: (u16, u16) = (400, 240);
+
+/// Size (width, height) of the bottom screen, in pixels.
+pub const BOTTOM_SCREEN_SIZE: (u16, u16) = (320, 240);
+
+/// Render targets are made of square tiles of this many pixels per side.
+const TILE_SIZE: u16 = 8;
+
+/// Size of the glyphs of the built-in font, in pixels.
+const GLYPH_SIZE: u32 = 8;
+
+/// RGBA colour with 8 bits per channel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    /// Red channel.
+    pub r: u8,
+    /// Green channel.
+    pub g: u8,
+    /// Blue channel.
+    pub b: u8,
+    /// Alpha channel. `0` is fully transparent and `0xFF` fully opaque.
+    pub a: u8,
+}
+
+impl Color {
+    /// Opaque white.
+    pub const WHITE: Self = Self::rgb(0xFF, 0xFF, 0xFF);
+    /// Opaque black.
+    pub const BLACK: Self = Self::rgb(0, 0, 0);
+    /// Fully transparent black.
+    pub const TRANSPARENT: Self = Self::new(0, 0, 0, 0);
+
+    /// Create a colour from its channels.
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Create an opaque colour.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::new(r, g, b, 0xFF)
+    }
+
+    /// Multiply each channel by the matching channel of `other`.
+    pub fn modulate(self, other: Self) -> Self {
+        let mul = |a: u8, b: u8| ((u16::from(a) * u16::from(b) + 0x7F) / 0xFF) as u8;
+
+        Self::new(
+            mul(self.r, other.r),
+            mul(self.g, other.g),
+            mul(self.b, other.b),
+            mul(self.a, other.a),
+        )
+    }
+
+    /// Composite this colour over `dst`, using this colour's alpha channel.
+    pub fn blend_over(self, dst: Self) -> Self {
+        match self.a {
+            0xFF => return self,
+            0 => return dst,
+            _ => {}
+        }
+
+        let alpha = u32::from(self.a);
+        let inverse = 0xFF - alpha;
+        let mix = |src: u8, dst: u8| {
+            ((u32::from(src) * alpha + u32::from(dst) * inverse + 0x7F) / 0xFF) as u8
+        };
+
+        Self::new(
+            mix(self.r, dst.r),
+            mix(self.g, dst.g),
+            mix(self.b, dst.b),
+            (alpha + (u32::from(dst.a) * inverse + 0x7F) / 0xFF) as u8,
+        )
+    }
+
+    /// Returns the colour packed as the GPU's RGBA8 format.
+    fn to_rgba8(self) -> u32 {
+        u32::from_be_bytes([self.r, self.g, self.b, self.a])
+    }
+
+    fn from_rgba8(value: u32) -> Self {
+        let [r, g, b, a] = value.to_be_bytes();
+        Self::new(r, g, b, a)
+    }
+}
+
+/// Axis-aligned rectangle, in pixels.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    /// Horizontal position of the left edge.
+    pub x: i32,
+    /// Vertical position of the top edge.
+    pub y: i32,
+    /// Width of the rectangle.
+    pub width: u32,
+    /// Height of the rectangle.
+    pub height: u32,
+}
+
+impl Rect {
+    /// Create a rectangle from its top-left corner and size.
+    pub const fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the horizontal position right after the right edge.
+    pub fn right(&self) -> i32 {
+        self.x.saturating_add(self.width as i32)
+    }
+
+    /// Returns the vertical position right after the bottom edge.
+    pub fn bottom(&self) -> i32 {
+        self.y.saturating_add(self.height as i32)
+    }
+
+    /// Returns `true` if the point lies within the rectangle.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x..self.right()).contains(&x) && (self.y..self.bottom()).contains(&y)
+    }
+
+    /// Returns the area covered by both rectangles, if they overlap.
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+
+        (x < right && y < bottom).then(|| Self::new(x, y, (right - x) as u32, (bottom - y) as u32))
+    }
+}
+
+/// Image which can be drawn by the [`SoftwareRenderer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Texture {
+    width: u16,
+    height: u16,
+    pixels: Vec<Color>,
+}
+
+impl Texture {
+    /// Create a texture from RGBA8 pixel data (4 bytes per pixel, in `r, g, b, a` order), stored row by row from the top-left corner.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `data` is shorter than needed for the given size.
+    pub fn from_rgba8(width: u16, height: u16, data: &[u8]) -> crate::Result<Self> {
+        let wanted = usize::from(width) * usize::from(height) * 4;
+
+        if data.len() < wanted {
+            return Err(Error::BufferTooShort {
+                provided: data.len(),
+                wanted,
+            });
+        }
+
+        let pixels = data[..wanted]
+            .chunks_exact(4)
+            .map(|p| Color::new(p[0], p[1], p[2], p[3]))
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Create a texture from a decoded [`Image`], such as the icon of an [`Smdh`](crate::smdh::Smdh).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the image is wider or taller than 65535 pixels.
+    pub fn from_image(image: &Image) -> crate::Result<Self> {
+        let (Ok(width), Ok(height)) = (u16::try_from(image.width()), u16::try_from(image.height()))
+        else {
+            return Err(Error::Other(format!(
+                "a {}x{} image is too large for a texture",
+                image.width(),
+                image.height()
+            )));
+        };
+
+        Self::from_rgba8(width, height, image.convert(PixelFormat::Rgba8).data())
+    }
+
+    /// Returns the width of the texture, in pixels.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Returns the height of the texture, in pixels.
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Returns the area covered by the whole texture.
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width.into(), self.height.into())
+    }
+
+    /// Returns the colour of a pixel, clamping the coordinates to the texture's edges.
+    pub fn pixel(&self, x: i32, y: i32) -> Color {
+        if self.pixels.is_empty() {
+            return Color::TRANSPARENT;
+        }
+
+        let x = x.clamp(0, i32::from(self.width) - 1) as usize;
+        let y = y.clamp(0, i32::from(self.height) - 1) as usize;
+
+        self.pixels[y * usize::from(self.width) + x]
+    }
+}
+
+/// Sprites sharing the same [`Texture`], drawn together by [`SoftwareRenderer::draw_batch()`].
+#[derive(Clone, Debug)]
+pub struct SpriteBatch<'tex> {
+    texture: &'tex Texture,
+    sprites: Vec<Sprite>,
+}
+
+/// Single entry of a [`SpriteBatch`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Sprite {
+    source: Rect,
+    destination: Rect,
+    tint: Color,
+}
+
+impl<'tex> SpriteBatch<'tex> {
+    /// Create an empty batch drawing from `texture` (e.g. a sprite sheet or a font atlas).
+    pub fn new(texture: &'tex Texture) -> Self {
+        Self {
+            texture,
+            sprites: Vec::new(),
+        }
+    }
+
+    /// Add a sprite copying the `source` area of the texture onto the `destination` area, scaled to fit and tinted by `tint`.
+    pub fn push(&mut self, source: Rect, destination: Rect, tint: Color) {
+        self.sprites.push(Sprite {
+            source,
+            destination,
+            tint,
+        });
+    }
+
+    /// Remove all sprites, keeping the allocated memory for the next frame.
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    /// Returns the number of sprites in the batch.
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    /// Returns `true` if the batch doesn't contain any sprite.
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+}
+
+/// Image which the [`SoftwareRenderer`] draws onto, presented on a screen with [`RenderTarget::present()`].
+///
+/// Targets created with [`RenderTarget::new()`] live in LINEAR memory and use the RGBA8 format.
+/// Targets created with [`RenderTarget::new_in_vram()`] live in VRAM instead, in any format: they can be drawn onto as well,
+/// or filled from other targets with [`RenderTarget::copy_from()`] and copied back out with [`RenderTarget::read_back_to_linear()`],
+/// making them suitable to keep composed scenes or pre-rendered UI elements around without using up LINEAR memory.
+pub struct RenderTarget {
+    width: u16,
+    height: u16,
+    format: TransferFormat,
+    storage: Storage,
+}
+
+/// Memory holding the pixels of a [`RenderTarget`].
+enum Storage {
+    Linear(Box<[u8], LinearAllocator>),
+    Vram(Box<[u8], VramAllocator>),
+}
+
+impl RenderTarget {
+    /// Create a render target of the given size (e.g. [`TOP_SCREEN_SIZE`]) in LINEAR memory, initially transparent.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the width or height aren't non-zero multiples of 8 pixels,
+    /// or if there isn't enough LINEAR memory left.
+    pub fn new(width: u16, height: u16) -> crate::Result<Self> {
+        let layout = target_layout(width, height, TransferFormat::Rgba8)?;
+
+        Ok(Self {
+            width,
+            height,
+            format: TransferFormat::Rgba8,
+            storage: Storage::Linear(linear_buffer(layout)?),
+        })
+    }
+
+    /// Create a render target of the given size and pixel format in VRAM.
+    ///
+    /// The target is initially zeroed (i.e. black, and transparent for formats with an alpha channel):
+    /// clear it with [`RenderTarget::clear()`] or fill it with [`RenderTarget::copy_from()`] before presenting it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the width or height aren't non-zero multiples of 8 pixels,
+    /// or if there isn't enough VRAM left.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::gx::TransferFormat;
+    /// use ctru::render2d::{Color, Rect, RenderTarget, SoftwareRenderer, TOP_SCREEN_SIZE};
+    /// use ctru::services::gfx::Gfx;
+    ///
+    /// let gfx = Gfx::new()?;
+    /// let (width, height) = TOP_SCREEN_SIZE;
+    ///
+    /// // Compose the scene once in LINEAR memory...
+    /// let mut scratch = RenderTarget::new(width, height)?;
+    /// scratch.clear(&gfx, Color::BLACK)?;
+    /// SoftwareRenderer::new(&mut scratch).fill_rect(Rect::new(0, 0, 400, 20), Color::WHITE);
+    ///
+    /// // ...and keep it in VRAM, to present it every frame.
+    /// let mut scene = RenderTarget::new_in_vram(width, height, TransferFormat::Rgb565)?;
+    /// scene.copy_from(&gfx, &scratch)?;
+    ///
+    /// // Later, read it back (e.g. to save a screenshot).
+    /// let screenshot = scene.read_back_to_linear(&gfx)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "vramAlloc")]
+    pub fn new_in_vram(width: u16, height: u16, format: TransferFormat) -> crate::Result<Self> {
+        let layout = target_layout(width, height, format)?;
+        let memory = VramAllocator
+            .allocate_zeroed(layout)
+            .map_err(|_| Error::Other(String::from("not enough VRAM")))?;
+
+        Ok(Self {
+            width,
+            height,
+            format,
+            // SAFETY: the memory was allocated (and zeroed) by the same allocator.
+            storage: Storage::Vram(unsafe { Box::from_raw_in(memory.as_ptr(), VramAllocator) }),
+        })
+    }
+
+    /// Returns the width of the target, in pixels.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Returns the height of the target, in pixels.
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Returns the pixel format of the target.
+    ///
+    /// Targets in LINEAR memory always use [`TransferFormat::Rgba8`].
+    pub fn format(&self) -> TransferFormat {
+        self.format
+    }
+
+    /// Returns `true` if the target is located in VRAM.
+    pub fn is_in_vram(&self) -> bool {
+        matches!(self.storage, Storage::Vram(_))
+    }
+
+    /// Returns the area covered by the whole target.
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width.into(), self.height.into())
+    }
+
+    /// Returns the raw pixel data, in the GPU's tiled layout and the target's [format](RenderTarget::format()).
+    pub fn as_bytes(&self) -> &[u8] {
+        self.storage.bytes()
+    }
+
+    /// Returns the colour of a pixel, or [`None`] if it's out of the target's bounds.
+    ///
+    /// Formats without an alpha channel always return opaque colours.
+    pub fn pixel(&self, x: u16, y: u16) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let offset = self.offset(x, y);
+        let depth = self.format.pixel_depth_bytes();
+
+        Some(decode_pixel(
+            &self.storage.bytes()[offset..offset + depth],
+            self.format,
+        ))
+    }
+
+    /// Fill the whole target with `color`, using the GPU.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the GPU refuses the operation.
+    #[doc(alias = "GX_MemoryFill")]
+    pub fn clear(&mut self, gfx: &Gfx, color: Color) -> crate::Result<()> {
+        let (value, width) = fill_value(color, self.format);
+
+        gx::memory_fill(gfx, self.storage.bytes_mut(), value, width)
+    }
+
+    /// Copy the contents of `source` into this target, converting them to this target's format.
+    ///
+    /// This is how images drawn by the [`SoftwareRenderer`] onto a LINEAR target end up in a VRAM target.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the targets have different sizes, if there isn't enough LINEAR memory left
+    /// for the temporary buffer needed to convert between formats, or if the GPU refuses the transfer.
+    #[doc(alias = "GX_TextureCopy", alias = "GX_DisplayTransfer")]
+    pub fn copy_from(&mut self, gfx: &Gfx, source: &RenderTarget) -> crate::Result<()> {
+        if (self.width, self.height) != (source.width, source.height) {
+            return Err(Error::Other(format!(
+                "cannot copy a {}x{} render target into a {}x{} one",
+                source.width, source.height, self.width, self.height
+            )));
+        }
+
+        let src = source.storage.bytes();
+
+        if self.format == source.format {
+            return dma::copy(gfx, src, self.storage.bytes_mut())
+                .map_err(|e| Error::Other(e.to_string()));
+        }
+
+        // The transfer engine can't convert between two tiled images directly:
+        // untile the source while converting it, and then tile it again.
+        let dimensions = self.transfer_dimensions();
+        let layout = target_layout(self.width, self.height, self.format)?;
+        let mut untiled = linear_buffer(layout)?;
+
+        gx::display_transfer(
+            gfx,
+            src,
+            dimensions,
+            &mut untiled,
+            dimensions,
+            TransferFlags::new(source.format, self.format),
+        )?;
+
+        gx::display_transfer(
+            gfx,
+            &untiled,
+            dimensions,
+            self.storage.bytes_mut(),
+            dimensions,
+            TransferFlags {
+                tiled_output: true,
+                ..TransferFlags::new(self.format, self.format)
+            },
+        )
+    }
+
+    /// Copy the target into a new RGBA8 target in LINEAR memory (e.g. to save a screenshot, or to encode it as an [`Image`]).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there isn't enough LINEAR memory left, or if the GPU refuses the transfer.
+    pub fn read_back_to_linear(&self, gfx: &Gfx) -> crate::Result<RenderTarget> {
+        let mut target = RenderTarget::new(self.width, self.height)?;
+        target.copy_from(gfx, self)?;
+
+        Ok(target)
+    }
+
+    /// Copy the target to the screen's current framebuffer, converting it to the framebuffer's format.
+    ///
+    /// As with any other drawing operation, the screen's buffers must then be flushed and swapped to show the result.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the target's size doesn't match the screen's size, or if the GPU refuses the transfer.
+    #[doc(alias = "GX_DisplayTransfer")]
+    pub fn present<S: Screen>(&self, gfx: &Gfx, screen: &mut S) -> crate::Result<()> {
+        let format = screen.framebuffer_format();
+        let framebuffer = screen.raw_framebuffer();
+
+        // Framebuffers are stored rotated: their "width" is the screen's height.
+        if framebuffer.width != usize::from(self.height)
+            || framebuffer.height != usize::from(self.width)
+        {
+            return Err(Error::Other(format!(
+                "render target is {}x{}, but the screen is {}x{}",
+                self.width, self.height, framebuffer.height, framebuffer.width
+            )));
+        }
+
+        let dimensions = self.transfer_dimensions();
+        // SAFETY: the framebuffer is valid for the current frame, and its size matches the target's size.
+        let output = unsafe {
+            std::slice::from_raw_parts_mut(
+                framebuffer.ptr,
+                framebuffer.width * framebuffer.height * format.pixel_depth_bytes(),
+            )
+        };
+
+        gx::display_transfer(
+            gfx,
+            self.storage.bytes(),
+            dimensions,
+            output,
+            dimensions,
+            TransferFlags::new(self.format, format.into()),
+        )
+    }
+
+    /// Dimensions of the (rotated) image handled by the transfer engine.
+    pub(crate) fn transfer_dimensions(&self) -> Dimensions {
+        Dimensions {
+            width: self.height,
+            height: self.width,
+        }
+    }
+
+    /// Returns the byte offset of a pixel within the buffer.
+    ///
+    /// The buffer is stored like the screens' framebuffers (column by column, starting from the bottom of the screen),
+    /// but split in 8x8 tiles whose pixels are ordered along a Z-order curve, as the transfer engine expects.
+    fn offset(&self, x: u16, y: u16) -> usize {
+        let column = usize::from(self.height - 1 - y);
+        let row = usize::from(x);
+        let tiles_per_row = usize::from(self.height / TILE_SIZE);
+
+        let tile = (row / 8) * tiles_per_row + column / 8;
+
+        (tile * 64 + morton(column % 8, row % 8)) * self.format.pixel_depth_bytes()
+    }
+
+    fn blend(&mut self, x: u16, y: u16, color: Color) {
+        let offset = self.offset(x, y);
+        let depth = self.format.pixel_depth_bytes();
+        let format = self.format;
+        let pixel = &mut self.storage.bytes_mut()[offset..offset + depth];
+
+        let dst = decode_pixel(pixel, format);
+        let (value, _) = fill_value(color.blend_over(dst), format);
+        pixel.copy_from_slice(&value.to_le_bytes()[..depth]);
+    }
+}
+
+impl Storage {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Self::Linear(buffer) => buffer,
+            Self::Vram(buffer) => buffer,
+        }
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        match self {
+            Self::Linear(buffer) => buffer,
+            Self::Vram(buffer) => buffer,
+        }
+    }
+}
+
+/// Returns the memory layout of a render target, checking its dimensions.
+fn target_layout(width: u16, height: u16, format: TransferFormat) -> crate::Result<Layout> {
+    if width == 0 || height == 0 || width % TILE_SIZE != 0 || height % TILE_SIZE != 0 {
+        return Err(Error::Other(String::from(
+            "render target dimensions must be non-zero multiples of 8",
+        )));
+    }
+
+    let length = usize::from(width) * usize::from(height) * format.pixel_depth_bytes();
+
+    Layout::from_size_align(length, ADDRESS_ALIGNMENT).map_err(|e| Error::Other(e.to_string()))
+}
+
+/// Allocate a zeroed, suitably aligned buffer in LINEAR memory.
+fn linear_buffer(layout: Layout) -> crate::Result<Box<[u8], LinearAllocator>> {
+    let memory = LinearAllocator
+        .allocate_zeroed(layout)
+        .map_err(|_| Error::Other(String::from("not enough LINEAR memory")))?;
+
+    // SAFETY: the memory was allocated (and zeroed) by the same allocator.
+    Ok(unsafe { Box::from_raw_in(memory.as_ptr(), LinearAllocator) })
+}
+
+/// Returns the value and width used by the transfer engine to fill a buffer of the given format with `color`.
+fn fill_value(color: Color, format: TransferFormat) -> (u32, FillWidth) {
+    let Color { r, g, b, a } = color;
+    let (r, g, b, a) = (u32::from(r), u32::from(g), u32::from(b), u32::from(a));
+
+    match format {
+        TransferFormat::Rgba8 => (color.to_rgba8(), FillWidth::Bits32),
+        TransferFormat::Rgb8 => ((r << 16) | (g << 8) | b, FillWidth::Bits24),
+        TransferFormat::Rgb565 => (
+            ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3),
+            FillWidth::Bits16,
+        ),
+        TransferFormat::Rgb5A1 => (
+            ((r >> 3) << 11) | ((g >> 3) << 6) | ((b >> 3) << 1) | (a >> 7),
+            FillWidth::Bits16,
+        ),
+        TransferFormat::Rgba4 => (
+            ((r >> 4) << 12) | ((g >> 4) << 8) | ((b >> 4) << 4) | (a >> 4),
+            FillWidth::Bits16,
+        ),
+    }
+}
+
+/// Returns the colour of a pixel stored in the given format, as packed by [`fill_value()`].
+fn decode_pixel(pixel: &[u8], format: TransferFormat) -> Color {
+    let expand5 = |value: u16| ((value << 3) | (value >> 2)) as u8;
+    let expand6 = |value: u16| ((value << 2) | (value >> 4)) as u8;
+    let expand4 = |value: u16| (value * 0x11) as u8;
+
+    match format {
+        TransferFormat::Rgba8 => {
+            Color::from_rgba8(u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]))
+        }
+        TransferFormat::Rgb8 => Color::rgb(pixel[2], pixel[1], pixel[0]),
+        TransferFormat::Rgb565 => {
+            let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+            Color::rgb(
+                expand5(value >> 11),
+                expand6((value >> 5) & 0x3F),
+                expand5(value & 0x1F),
+            )
+        }
+        TransferFormat::Rgb5A1 => {
+            let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+            Color::new(
+                expand5(value >> 11),
+                expand5((value >> 6) & 0x1F),
+                expand5((value >> 1) & 0x1F),
+                if value & 1 != 0 { 0xFF } else { 0 },
+            )
+        }
+        TransferFormat::Rgba4 => {
+            let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+            Color::new(
+                expand4(value >> 12),
+                expand4((value >> 8) & 0xF),
+                expand4((value >> 4) & 0xF),
+                expand4(value & 0xF),
+            )
+        }
+    }
+}
+
+/// Interleave the bits of the coordinates within a tile.
+fn morton(x: usize, y: usize) -> usize {
+    let spread = |v: usize| (v & 1) | ((v & 2) << 1) | ((v & 4) << 2);
+
+    spread(x) | (spread(y) << 1)
+}
+
+/// Software drawing context of a [`RenderTarget`], rasterizing on the CPU.
+///
+/// All drawing operations are clipped to the target's bounds and to the scissor rectangle, if set.
+pub struct SoftwareRenderer<'target> {
+    target: &'target mut RenderTarget,
+    scissor: Option<Rect>,
+}
+
+impl<'target> SoftwareRenderer<'target> {
+    /// Start drawing onto `target`, which may be located in LINEAR memory or VRAM.
+    pub fn new(target: &'target mut RenderTarget) -> Self {
+        Self {
+            target,
+            scissor: None,
+        }
+    }
+
+    /// Restrict drawing to the given area, or remove the restriction with [`None`].
+    pub fn set_scissor(&mut self, scissor: Option<Rect>) {
+        self.scissor = scissor;
+    }
+
+    /// Returns the current scissor rectangle.
+    pub fn scissor(&self) -> Option<Rect> {
+        self.scissor
+    }
+
+    /// Returns the area actually affected by drawing within `rect`.
+    fn clip(&self, rect: Rect) -> Option<Rect> {
+        let visible = match self.scissor {
+            Some(scissor) => scissor.intersection(&self.target.bounds())?,
+            None => self.target.bounds(),
+        };
+
+        rect.intersection(&visible)
+    }
+
+    /// Fill a rectangle with `color`.
+    pub fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let Some(area) = self.clip(rect) else {
+            return;
+        };
+
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                self.target.blend(x as u16, y as u16, color);
+            }
+        }
+    }
+
+    /// Fill a convex quad (such as a rotated rectangle) with `color`. The corners must be given in order, in either direction.
+    pub fn fill_quad(&mut self, corners: [(f32, f32); 4], color: Color) {
+        self.fill_triangle([corners[0], corners[1], corners[2]], color);
+        self.fill_triangle([corners[0], corners[2], corners[3]], color);
+    }
+
+    /// Fill a triangle with `color`.
+    ///
+    /// Pixels are covered when their center lies within the triangle. Edges shared by two triangles are drawn only once.
+    pub fn fill_triangle(&mut self, [a, b, c]: [(f32, f32); 3], color: Color) {
+        let edge = |(x0, y0): (f32, f32), (x1, y1): (f32, f32), (x, y): (f32, f32)| {
+            (x1 - x0) * (y - y0) - (y1 - y0) * (x - x0)
+        };
+
+        let area = edge(a, b, c);
+        if area == 0.0 {
+            return;
+        }
+        // Use the same winding for every triangle.
+        let (b, c) = if area < 0.0 { (c, b) } else { (b, c) };
+
+        // Top-left fill rule, in screen coordinates (y grows downwards).
+        let is_top_left =
+            |(x0, y0): (f32, f32), (x1, y1): (f32, f32)| (y0 == y1 && x1 < x0) || y1 > y0;
+        let biases = [is_top_left(b, c), is_top_left(c, a), is_top_left(a, b)];
+
+        let min_x = a.0.min(b.0).min(c.0).floor() as i32;
+        let min_y = a.1.min(b.1).min(c.1).floor() as i32;
+        let max_x = a.0.max(b.0).max(c.0).ceil() as i32;
+        let max_y = a.1.max(b.1).max(c.1).ceil() as i32;
+
+        let bounds = Rect::new(
+            min_x,
+            min_y,
+            (max_x - min_x).max(0) as u32,
+            (max_y - min_y).max(0) as u32,
+        );
+        let Some(area) = self.clip(bounds) else {
+            return;
+        };
+
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                let center = (x as f32 + 0.5, y as f32 + 0.5);
+                let weights = [edge(b, c, center), edge(c, a, center), edge(a, b, center)];
+
+                let inside = weights
+                    .iter()
+                    .zip(biases)
+                    .all(|(w, top_left)| *w > 0.0 || (*w == 0.0 && top_left));
+
+                if inside {
+                    self.target.blend(x as u16, y as u16, color);
+                }
+            }
+        }
+    }
+
+    /// Draw the `source` area of `texture` onto the `destination` area, scaled to fit and tinted by `tint`
+    /// ([`Color::WHITE`] keeps the original colours).
+    pub fn draw_sprite(&mut self, texture: &Texture, source: Rect, destination: Rect, tint: Color) {
+        let Some(area) = self.clip(destination) else {
+            return;
+        };
+
+        for y in area.y..area.bottom() {
+            // Nearest neighbour sampling, at the center of each destination pixel.
+            let v = source.y
+                + ((i64::from(y - destination.y) * 2 + 1) * i64::from(source.height)
+                    / (i64::from(destination.height) * 2)) as i32;
+
+            for x in area.x..area.right() {
+                let u = source.x
+                    + ((i64::from(x - destination.x) * 2 + 1) * i64::from(source.width)
+                        / (i64::from(destination.width) * 2)) as i32;
+
+                let color = texture.pixel(u, v).modulate(tint);
+                self.target.blend(x as u16, y as u16, color);
+            }
+        }
+    }
+
+    /// Draw all sprites of a batch, in the order they were added.
+    pub fn draw_batch(&mut self, batch: &SpriteBatch<'_>) {
+        for sprite in &batch.sprites {
+            self.draw_sprite(
+                batch.texture,
+                sprite.source,
+                sprite.destination,
+                sprite.tint,
+            );
+        }
+    }
+
+    /// Draw `text` with the built-in 8x8 font (the same used by the [`Console`](crate::console::Console)), with each glyph scaled by `scale`.
+    ///
+    /// `(x, y)` is the top-left corner of the first glyph. Line feeds start a new line below the first glyph.
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str, color: Color, scale: u32) {
+        let size = GLYPH_SIZE * scale;
+
+        // SAFETY: the default console is statically allocated by `libctru` and its font is never modified.
+        let font = unsafe { (*ctru_sys::consoleGetDefault()).font };
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line_y = y + (line_index as u32 * size) as i32;
+
+            for (i, c) in line.chars().enumerate() {
+                let glyph_x = x + (i as u32 * size) as i32;
+
+                if self.clip(Rect::new(glyph_x, line_y, size, size)).is_none() {
+                    continue;
+                }
+
+                let index = (c as u32)
+                    .checked_sub(font.asciiOffset.into())
+                    .filter(|index| *index < font.numChars.into())
+                    .unwrap_or((u32::from(b'?')).saturating_sub(font.asciiOffset.into()));
+
+                let glyph = unsafe {
+                    std::slice::from_raw_parts(
+                        font.gfx.add(index as usize * GLYPH_SIZE as usize),
+                        GLYPH_SIZE as usize,
+                    )
+                };
+
+                for (row, bits) in glyph.iter().enumerate() {
+                    for column in 0..GLYPH_SIZE {
+                        if bits & (0x80 >> column) != 0 {
+                            self.fill_rect(
+                                Rect::new(
+                                    glyph_x + (column * scale) as i32,
+                                    line_y + (row as u32 * scale) as i32,
+                                    scale,
+                                    scale,
+                                ),
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the size (width, height) of `text` drawn by [`SoftwareRenderer::draw_text()`] with the given scale.
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let size = GLYPH_SIZE * scale;
+    let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+
+    (columns as u32 * size, text.lines().count() as u32 * size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blending_and_clipping() {
+        let half_red = Color::new(0xFF, 0, 0, 0x80);
+        assert_eq!(
+            half_red.blend_over(Color::BLACK),
+            Color::new(0x80, 0, 0, 0xFF)
+        );
+        assert_eq!(Color::TRANSPARENT.blend_over(Color::WHITE), Color::WHITE);
+
+        let a = Rect::new(-10, -10, 20, 20);
+        let b = Rect::new(5, 0, 100, 100);
+        assert_eq!(a.intersection(&b), Some(Rect::new(5, 0, 5, 10)));
+        assert_eq!(a.intersection(&Rect::new(10, 10, 5, 5)), None);
+
+        assert_eq!(morton(7, 7), 63);
+        assert_eq!(morton(1, 0), 1);
+        assert_eq!(morton(0, 1), 2);
+        assert_eq!(text_size("ab\nc", 2), (32, 32));
+
+        assert_eq!(
+            fill_value(Color::WHITE, TransferFormat::Rgb565),
+            (0xFFFF, FillWidth::Bits16)
+        );
+        assert_eq!(
+            fill_value(Color::new(0xFF, 0, 0, 0x80), TransferFormat::Rgba4),
+            (0xF008, FillWidth::Bits16)
+        );
+    }
+
+    #[test]
+    fn pixel_formats() {
+        let color = Color::new(0xFF, 0x80, 0, 0xFF);
+
+        for format in [
+            TransferFormat::Rgba8,
+            TransferFormat::Rgb8,
+            TransferFormat::Rgb565,
+            TransferFormat::Rgb5A1,
+            TransferFormat::Rgba4,
+        ] {
+            let (value, _) = fill_value(color, format);
+            let decoded = decode_pixel(&value.to_le_bytes(), format);
+
+            assert_eq!((decoded.r, decoded.b, decoded.a), (0xFF, 0, 0xFF));
+            assert!((0x80..=0x88).contains(&decoded.g), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn rgb565_read_back() {
+        let gfx = Gfx::new().unwrap();
+
+        // The VRAM target is filled by the GPU, read by the CPU, then converted to RGBA8 by the transfer engine.
+        let mut scene = RenderTarget::new_in_vram(8, 8, TransferFormat::Rgb565).unwrap();
+        scene.clear(&gfx, Color::rgb(0xFF, 0x80, 0)).unwrap();
+        assert_eq!(scene.pixel(3, 5), Some(Color::rgb(0xFF, 0x82, 0)));
+
+        let copy = scene.read_back_to_linear(&gfx).unwrap();
+        assert_eq!(copy.format(), TransferFormat::Rgba8);
+
+        let pixel = copy.pixel(3, 5).unwrap();
+        assert_eq!((pixel.r, pixel.b, pixel.a), (0xFF, 0, 0xFF));
+        assert!((0x80..=0x83).contains(&pixel.g));
+    }
+}
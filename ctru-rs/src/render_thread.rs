@@ -0,0 +1,360 @@
+//! Rendering on a dedicated thread.
+//!
+//! Heavy rendering slows down the whole main loop when it runs on the main thread, and with it input handling.
+//! This module moves rendering to its own [`RenderThread`], which waits for the screens' V-blank on its own,
+//! while the main thread only polls input and runs the APT loop. The main thread hands the latest application state to the
+//! renderer through a [`frame_queue()`]: a triple buffer, so that neither side ever waits for the other and the renderer
+//! always draws the most recent state.
+//!
+//! # GPU access
+//!
+//! The application gives up the GPU when the HOME Menu or an applet takes over the screens, and gets it back afterwards.
+//! `libctru` does so from the thread running [`Apt::main_loop()`](crate::services::apt::Apt::main_loop): a [`RenderThread`]
+//! hooks into these transitions and pauses the render thread at its next [`RenderContext::next_frame()`] call
+//! before the GPU is released, resuming it once the GPU is available again.
+//! The render thread must therefore only use the GPU between two [`RenderContext::next_frame()`] calls.
+//!
+//! [`Gfx`](crate::services::gfx::Gfx) should be initialized on the render thread itself, since its screens can't be shared between threads.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! #
+//! use ctru::prelude::*;
+//! use ctru::render_thread::{frame_queue, RenderThread};
+//! use ctru::services::gfx::{Flush, Swap};
+//!
+//! let apt = Apt::new()?;
+//! let mut hid = Hid::new()?;
+//!
+//! let (mut sender, mut receiver) = frame_queue(KeyPad::empty());
+//!
+//! let render_thread = RenderThread::spawn(move |context| {
+//!     let gfx = Gfx::new().unwrap();
+//!
+//!     while context.next_frame() {
+//!         let keys = receiver.latest();
+//!
+//!         // Draw the frame using `keys`...
+//!
+//!         let mut top_screen = gfx.top_screen.borrow_mut();
+//!         top_screen.flush_buffers();
+//!         top_screen.swap_buffers();
+//!         drop(top_screen);
+//!
+//!         gfx.wait_for_vblank();
+//!     }
+//! })?;
+//!
+//! while apt.main_loop() {
+//!     hid.scan_input();
+//!
+//!     if hid.keys_down().contains(KeyPad::START) {
+//!         break;
+//!     }
+//!
+//!     sender.send(hid.keys_held());
+//!
+//!     // Poll input at about 240 Hz.
+//!     std::thread::sleep(std::time::Duration::from_millis(4));
+//! }
+//!
+//! // Dropping the handle stops the render thread and waits for it to finish.
+//! drop(render_thread);
+//! #
+//! # Ok(())
+//! # }
+//! ```
+#![doc(alias = "triple buffering")]
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{Builder, JoinHandle};
+
+use crate::services::apt::AptHook;
+
+/// Flag set in [`Slots::middle`] when the middle slot holds a value the receiver hasn't seen yet.
+const FRESH: u8 = 0b100;
+const INDEX_MASK: u8 = 0b011;
+
+/// The three buffers of a frame queue.
+struct Slots<T> {
+    buffers: [UnsafeCell<T>; 3],
+    /// Index of the buffer exchanged between the sender and the receiver, along with the [`FRESH`] flag.
+    middle: AtomicU8,
+}
+
+// SAFETY: each buffer is only accessed by the side which currently owns its index, and ownership moves through `middle`.
+unsafe impl<T: Send> Sync for Slots<T> {}
+
+/// Sending half of a [`frame_queue()`].
+pub struct FrameSender<T> {
+    slots: Arc<Slots<T>>,
+    back: u8,
+}
+
+/// Receiving half of a [`frame_queue()`].
+pub struct FrameReceiver<T> {
+    slots: Arc<Slots<T>>,
+    front: u8,
+}
+
+/// Create a triple-buffered queue, holding `initial` until the first value is sent.
+///
+/// Sending never blocks and overwrites values the receiver didn't get to see: the receiver always gets the latest value.
+pub fn frame_queue<T: Clone + Send>(initial: T) -> (FrameSender<T>, FrameReceiver<T>) {
+    let slots = Arc::new(Slots {
+        buffers: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial),
+        ],
+        middle: AtomicU8::new(1),
+    });
+
+    (
+        FrameSender {
+            slots: Arc::clone(&slots),
+            back: 0,
+        },
+        FrameReceiver { slots, front: 2 },
+    )
+}
+
+impl<T> FrameSender<T> {
+    /// Returns the value which will be sent by the next call to [`FrameSender::publish()`].
+    ///
+    /// The buffer still contains an older value, which can be updated in place to avoid reallocations.
+    pub fn back_mut(&mut self) -> &mut T {
+        // SAFETY: the back buffer is owned by the sender.
+        unsafe { &mut *self.slots.buffers[usize::from(self.back)].get() }
+    }
+
+    /// Make the value written with [`FrameSender::back_mut()`] available to the receiver.
+    pub fn publish(&mut self) {
+        let previous = self.slots.middle.swap(self.back | FRESH, Ordering::AcqRel);
+        self.back = previous & INDEX_MASK;
+    }
+
+    /// Send `value` to the receiver.
+    pub fn send(&mut self, value: T) {
+        *self.back_mut() = value;
+        self.publish();
+    }
+}
+
+impl<T> FrameReceiver<T> {
+    /// Returns `true` if a value was sent since the last call to [`FrameReceiver::latest()`].
+    pub fn has_new(&self) -> bool {
+        self.slots.middle.load(Ordering::Acquire) & FRESH != 0
+    }
+
+    /// Returns the latest value sent, or the same value as the previous call if nothing was sent in the meantime.
+    pub fn latest(&mut self) -> &T {
+        if self.has_new() {
+            let previous = self.slots.middle.swap(self.front, Ordering::AcqRel);
+            self.front = previous & INDEX_MASK;
+        }
+
+        // SAFETY: the front buffer is owned by the receiver.
+        unsafe { &*self.slots.buffers[usize::from(self.front)].get() }
+    }
+}
+
+// SAFETY: each half only accesses the buffers it owns.
+unsafe impl<T: Send> Send for FrameSender<T> {}
+unsafe impl<T: Send> Send for FrameReceiver<T> {}
+
+/// State of the render thread, shared with the APT hook and the [`RenderThread`] handle.
+#[derive(Default)]
+struct ControlState {
+    pause_requested: bool,
+    paused: bool,
+    stop_requested: bool,
+    finished: bool,
+}
+
+#[derive(Default)]
+struct Control {
+    state: Mutex<ControlState>,
+    changed: Condvar,
+}
+
+impl Control {
+    /// Ask the render thread to pause, and wait until it's paused (or finished).
+    fn pause(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.pause_requested = true;
+
+        let _state = self
+            .changed
+            .wait_while(state, |state| !state.paused && !state.finished)
+            .unwrap();
+    }
+
+    fn resume(&self) {
+        self.state.lock().unwrap().pause_requested = false;
+        self.changed.notify_all();
+    }
+
+    fn update(&self, update: impl FnOnce(&mut ControlState)) {
+        update(&mut self.state.lock().unwrap());
+        self.changed.notify_all();
+    }
+}
+
+/// Handle passed to the render thread's closure, used to synchronize with the main thread between frames.
+pub struct RenderContext {
+    control: Arc<Control>,
+}
+
+impl RenderContext {
+    /// Wait until the render thread may start a new frame.
+    ///
+    /// This function blocks while the GPU is unavailable (see the [module documentation](self)),
+    /// and returns `false` once the render thread should stop.
+    pub fn next_frame(&self) -> bool {
+        let mut state = self.control.state.lock().unwrap();
+
+        if state.pause_requested && !state.stop_requested {
+            state.paused = true;
+            self.control.changed.notify_all();
+
+            state = self
+                .control
+                .changed
+                .wait_while(state, |state| {
+                    state.pause_requested && !state.stop_requested
+                })
+                .unwrap();
+
+            state.paused = false;
+        }
+
+        !state.stop_requested
+    }
+
+    /// Returns `true` if the render thread was asked to stop.
+    pub fn should_stop(&self) -> bool {
+        self.control.state.lock().unwrap().stop_requested
+    }
+}
+
+/// Register the APT hook pausing the render thread while the application is suspended.
+fn pause_hook(control: Arc<Control>) -> AptHook {
+    AptHook::register(move |hook| match hook {
+        // Runs before `libctru` releases the GPU.
+        ctru_sys::APTHOOK_ONSUSPEND | ctru_sys::APTHOOK_ONSLEEP => control.pause(),
+        ctru_sys::APTHOOK_ONRESTORE | ctru_sys::APTHOOK_ONWAKEUP => control.resume(),
+        _ => {}
+    })
+}
+
+/// Handle to a thread dedicated to rendering.
+///
+/// Dropping the handle asks the thread to stop (see [`RenderContext::next_frame()`]) and waits for it to finish.
+pub struct RenderThread {
+    control: Arc<Control>,
+    thread: Option<JoinHandle<()>>,
+    _hook: AptHook,
+}
+
+impl RenderThread {
+    /// Spawn a thread named `render` running `render`.
+    ///
+    /// The thread runs on the same core as the calling thread: use [`RenderThread::spawn_with()`] to choose its core or stack size.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the thread couldn't be created.
+    pub fn spawn<F>(render: F) -> crate::Result<Self>
+    where
+        F: FnOnce(RenderContext) + Send + 'static,
+    {
+        Self::spawn_with(Builder::new().name(String::from("render")), render)
+    }
+
+    /// Spawn the render thread with the given thread builder
+    /// (e.g. on the system core, with `std::os::horizon::thread::BuilderExt::processor_id()`).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the thread couldn't be created.
+    pub fn spawn_with<F>(builder: Builder, render: F) -> crate::Result<Self>
+    where
+        F: FnOnce(RenderContext) + Send + 'static,
+    {
+        let control = Arc::new(Control::default());
+        let context = RenderContext {
+            control: Arc::clone(&control),
+        };
+
+        let thread = builder.spawn(move || {
+            let control = Arc::clone(&context.control);
+
+            // Make sure a pending pause request doesn't wait forever, even if the closure panics.
+            struct Finish(Arc<Control>);
+
+            impl Drop for Finish {
+                fn drop(&mut self) {
+                    self.0.update(|state| state.finished = true);
+                }
+            }
+
+            let _finish = Finish(control);
+            render(context);
+        })?;
+
+        Ok(Self {
+            _hook: pause_hook(Arc::clone(&control)),
+            control,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns `true` if the render thread is currently paused, waiting for the GPU to be available again.
+    pub fn is_paused(&self) -> bool {
+        self.control.state.lock().unwrap().paused
+    }
+
+    /// Returns `true` if the render thread's closure returned (or panicked).
+    pub fn is_finished(&self) -> bool {
+        self.control.state.lock().unwrap().finished
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        self.control.update(|state| state.stop_requested = true);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_queue_keeps_latest() {
+        let (mut sender, mut receiver) = frame_queue(0);
+        assert!(!receiver.has_new());
+        assert_eq!(*receiver.latest(), 0);
+
+        sender.send(1);
+        sender.send(2);
+        assert!(receiver.has_new());
+        assert_eq!(*receiver.latest(), 2);
+        assert_eq!(*receiver.latest(), 2);
+
+        *sender.back_mut() = 3;
+        sender.publish();
+        sender.send(4);
+        assert_eq!(*receiver.latest(), 4);
+    }
+}
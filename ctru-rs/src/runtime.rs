@@ -0,0 +1,814 @@
+//! Minimal async runtime.
+//!
+//! [`Runtime`] is a single-threaded executor meant for the console's limited resources: tasks are polled on the thread which
+//! calls [`Runtime::block_on()`], and the thread sleeps in the kernel while no task can make progress.
+//! Instead of a dedicated I/O driver, it waits on kernel objects directly: any handle which gets signalled
+//! (events, timers, thread handles...) can be awaited with [`wait_handle()`], and [`sleep()`] uses the wait's timeout.
+//! Sockets can't be waited on by the kernel: while tasks await them (see [`AsyncTcpStream`](crate::net::AsyncTcpStream)),
+//! the runtime wakes up every few milliseconds to poll them.
+//!
+//! Work which can't be made asynchronous (e.g. blocking library calls or file system accesses) can be moved to another thread
+//! with [`spawn_blocking()`], or to the other core with [`spawn_blocking_with()`], and awaited from the runtime.
+//!
+//! # Example
+//!
+//! ```
+//! # let _runner = test_runner::GdbRunner::default();
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! #
+//! use ctru::runtime::{self, Runtime};
+//! use std::time::Duration;
+//!
+//! let runtime = Runtime::new()?;
+//!
+//! let sum = runtime.block_on(async {
+//!     let first = runtime::spawn(async {
+//!         runtime::sleep(Duration::from_millis(10)).await;
+//!         1
+//!     });
+//!     let second = runtime::spawn(async { 2 });
+//!
+//!     first.await + second.await
+//! });
+//!
+//! assert_eq!(sum, 3);
+//! #
+//! # Ok(())
+//! # }
+//! ```
+#![doc(alias = "async")]
+#![doc(alias = "executor")]
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::{pin, Pin};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Builder};
+use std::time::{Duration, Instant};
+
+use crate::error::ResultCode;
+
+/// Longest wait while sockets are awaited: unlike kernel objects, their readiness can only be polled.
+const SOCKET_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Maximum number of handles waited on at once, including the runtime's own wake event.
+///
+/// Handles awaited beyond this amount are only waited on once earlier ones are signalled.
+const MAX_WAIT_HANDLES: usize = 64;
+
+/// ID of the future passed to [`Runtime::block_on()`].
+const MAIN_TASK: usize = usize::MAX;
+
+type LocalTask = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Tasks woken since the runtime last checked, shared with their wakers (which may be used from other threads).
+struct WakeQueue {
+    woken: Mutex<Vec<usize>>,
+    /// Signalled when a task is woken, to interrupt the runtime's wait.
+    event: ctru_sys::Handle,
+}
+
+impl WakeQueue {
+    fn push(&self, id: usize) {
+        self.woken.lock().unwrap().push(id);
+        let _ = unsafe { ctru_sys::svcSignalEvent(self.event) };
+    }
+
+    fn take(&self) -> Vec<usize> {
+        std::mem::take(&mut *self.woken.lock().unwrap())
+    }
+}
+
+impl Drop for WakeQueue {
+    fn drop(&mut self) {
+        let _ = unsafe { ctru_sys::svcCloseHandle(self.event) };
+    }
+}
+
+struct TaskWaker {
+    id: usize,
+    queue: Arc<WakeQueue>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.queue.push(self.id);
+    }
+}
+
+/// Waker of a pending [`Sleep`] or [`WaitHandle`], updated on every poll.
+type WakerSlot = Rc<RefCell<Option<Waker>>>;
+
+fn wake_slot(slot: &WakerSlot) {
+    if let Some(waker) = slot.borrow_mut().take() {
+        waker.wake();
+    }
+}
+
+fn update_slot(slot: &WakerSlot, waker: &Waker) {
+    let mut slot = slot.borrow_mut();
+
+    if !slot
+        .as_ref()
+        .is_some_and(|current| current.will_wake(waker))
+    {
+        *slot = Some(waker.clone());
+    }
+}
+
+struct TimerEntry {
+    deadline: Instant,
+    slot: WakerSlot,
+}
+
+struct HandleEntry {
+    handle: ctru_sys::Handle,
+    slot: WakerSlot,
+    /// Result of the wait, once the handle is signalled (or the wait failed).
+    result: Rc<Cell<Option<ctru_sys::Result>>>,
+}
+
+struct SocketEntry {
+    fd: libc::c_int,
+    events: libc::c_short,
+    slot: WakerSlot,
+}
+
+/// Timers, kernel handles and sockets the runtime waits on while idle.
+#[derive(Default)]
+struct Reactor {
+    timers: Vec<TimerEntry>,
+    handles: Vec<HandleEntry>,
+    sockets: Vec<SocketEntry>,
+}
+
+impl Reactor {
+    /// Wake the expired timers, returning `true` if there were any.
+    fn fire_timers(&mut self, now: Instant) -> bool {
+        let before = self.timers.len();
+
+        self.timers.retain(|timer| {
+            let expired = timer.deadline <= now;
+            if expired {
+                wake_slot(&timer.slot);
+            }
+            !expired
+        });
+
+        self.timers.len() != before
+    }
+
+    /// Wake the tasks waiting on sockets which became ready, returning `true` if there were any.
+    #[doc(alias = "socPoll")]
+    fn poll_sockets(&mut self) -> bool {
+        if self.sockets.is_empty() {
+            return false;
+        }
+
+        let mut fds: Vec<libc::pollfd> = self
+            .sockets
+            .iter()
+            .map(|socket| libc::pollfd {
+                fd: socket.fd,
+                events: socket.events,
+                revents: 0,
+            })
+            .collect();
+
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, 0) } <= 0 {
+            return false;
+        }
+
+        // Errors and hang-ups are reported in `revents` as well: wake those tasks too, so that their next operation reports it.
+        let mut fds = fds.iter();
+        self.sockets.retain(|socket| {
+            let ready = fds.next().is_some_and(|fd| fd.revents != 0);
+            if ready {
+                wake_slot(&socket.slot);
+            }
+            !ready
+        });
+
+        true
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        let deadline = self.timers.iter().map(|timer| timer.deadline).min();
+
+        if self.sockets.is_empty() {
+            deadline
+        } else {
+            let poll = Instant::now() + SOCKET_POLL_INTERVAL;
+            Some(deadline.map_or(poll, |deadline| deadline.min(poll)))
+        }
+    }
+
+    /// Wait until a task is woken, a handle is signalled, a socket is ready or the next timer expires.
+    fn park(&mut self, wake_event: ctru_sys::Handle) {
+        if self.fire_timers(Instant::now()) | self.poll_sockets() {
+            return;
+        }
+
+        let timeout = match self.next_deadline() {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                i64::try_from(timeout.as_nanos()).unwrap_or(i64::MAX)
+            }
+            None => -1,
+        };
+
+        let handles: Vec<ctru_sys::Handle> = std::iter::once(wake_event)
+            .chain(self.handles.iter().map(|entry| entry.handle))
+            .take(MAX_WAIT_HANDLES)
+            .collect();
+        let mut index = 0;
+
+        let result = unsafe {
+            ctru_sys::svcWaitSynchronizationN(
+                &mut index,
+                handles.as_ptr(),
+                handles.len() as i32,
+                false,
+                timeout,
+            )
+        };
+
+        if crate::Error::Os(result).is_timeout() {
+            // Handled by `fire_timers()` below.
+        } else if ctru_sys::R_FAILED(result) {
+            // One of the handles is invalid, but the kernel doesn't say which: fail all waits rather than spinning.
+            for entry in self.handles.drain(..) {
+                entry.result.set(Some(result));
+                wake_slot(&entry.slot);
+            }
+        } else if index > 0 {
+            let entry = self.handles.remove(index as usize - 1);
+            entry.result.set(Some(0));
+            wake_slot(&entry.slot);
+        }
+
+        self.fire_timers(Instant::now());
+        self.poll_sockets();
+    }
+}
+
+struct Shared {
+    queue: Arc<WakeQueue>,
+    tasks: RefCell<Vec<Option<LocalTask>>>,
+    free: RefCell<Vec<usize>>,
+    reactor: RefCell<Reactor>,
+}
+
+impl Shared {
+    fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let state = Rc::new(RefCell::new(JoinState {
+            output: None,
+            waker: None,
+        }));
+
+        let task_state = Rc::clone(&state);
+        let task: LocalTask = Box::pin(async move {
+            let output = future.await;
+
+            let mut state = task_state.borrow_mut();
+            state.output = Some(output);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+
+        let mut tasks = self.tasks.borrow_mut();
+        let id = match self.free.borrow_mut().pop() {
+            Some(id) => {
+                tasks[id] = Some(task);
+                id
+            }
+            None => {
+                tasks.push(Some(task));
+                tasks.len() - 1
+            }
+        };
+
+        self.queue.push(id);
+
+        JoinHandle { state }
+    }
+
+    fn poll_task(&self, id: usize) {
+        // Take the task out while polling it, so that it can spawn other tasks.
+        let Some(mut task) = self.tasks.borrow_mut().get_mut(id).and_then(Option::take) else {
+            return;
+        };
+
+        let waker = Waker::from(Arc::new(TaskWaker {
+            id,
+            queue: Arc::clone(&self.queue),
+        }));
+
+        match task.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(()) => self.free.borrow_mut().push(id),
+            Poll::Pending => self.tasks.borrow_mut()[id] = Some(task),
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<Shared>>> = RefCell::new(None);
+}
+
+fn current() -> Option<Rc<Shared>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+fn expect_current() -> Rc<Shared> {
+    current().expect("this function must be called from a future run by a `Runtime`")
+}
+
+/// Single-threaded async executor.
+///
+/// Tasks spawned on the runtime only run while [`Runtime::block_on()`] is being called.
+pub struct Runtime {
+    shared: Rc<Shared>,
+}
+
+impl Runtime {
+    /// Create a new runtime.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the runtime's wake event couldn't be created.
+    #[doc(alias = "svcCreateEvent")]
+    pub fn new() -> crate::Result<Self> {
+        let mut event = 0;
+        ResultCode(unsafe { ctru_sys::svcCreateEvent(&mut event, ctru_sys::RESET_ONESHOT) })?;
+
+        Ok(Self {
+            shared: Rc::new(Shared {
+                queue: Arc::new(WakeQueue {
+                    woken: Mutex::new(Vec::new()),
+                    event,
+                }),
+                tasks: RefCell::new(Vec::new()),
+                free: RefCell::new(Vec::new()),
+                reactor: RefCell::new(Reactor::default()),
+            }),
+        })
+    }
+
+    /// Spawn a task on the runtime.
+    ///
+    /// The task starts running at the next call to [`Runtime::block_on()`]. Dropping the returned [`JoinHandle`] detaches the task.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        self.shared.spawn(future)
+    }
+
+    /// Run `future` to completion, along with the tasks spawned on the runtime, and return its output.
+    ///
+    /// The calling thread sleeps in the kernel while no task can make progress.
+    /// Tasks which are still pending once `future` completes stay on the runtime.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        struct Restore(Option<Rc<Shared>>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+            }
+        }
+
+        let previous = CURRENT.with(|current| current.replace(Some(Rc::clone(&self.shared))));
+        let _restore = Restore(previous);
+
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(TaskWaker {
+            id: MAIN_TASK,
+            queue: Arc::clone(&self.shared.queue),
+        }));
+        let mut main_woken = true;
+
+        loop {
+            if main_woken {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker))
+                {
+                    return output;
+                }
+                main_woken = false;
+            }
+
+            let woken = self.shared.queue.take();
+
+            if woken.is_empty() {
+                self.shared
+                    .reactor
+                    .borrow_mut()
+                    .park(self.shared.queue.event);
+                continue;
+            }
+
+            for id in woken {
+                if id == MAIN_TASK {
+                    main_woken = true;
+                } else {
+                    self.shared.poll_task(id);
+                }
+            }
+        }
+    }
+}
+
+/// Run `future` to completion on a new [`Runtime`].
+///
+/// # Panics
+///
+/// This function will panic if the runtime couldn't be created.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    Runtime::new()
+        .expect("couldn't create the runtime")
+        .block_on(future)
+}
+
+/// Spawn a task on the runtime running the current future.
+///
+/// # Panics
+///
+/// This function will panic if it's not called from a future run by a [`Runtime`].
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    expect_current().spawn(future)
+}
+
+struct JoinState<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Handle to a task spawned on a [`Runtime`], resolving to the task's output.
+pub struct JoinHandle<T> {
+    state: Rc<RefCell<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Returns `true` if the task completed and its output wasn't taken yet.
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().output.is_some()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.borrow_mut();
+
+        match state.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Future returned by [`sleep()`].
+pub struct Sleep {
+    deadline: Instant,
+    slot: Option<WakerSlot>,
+}
+
+/// Wait until `duration` has elapsed.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+/// Wait until `deadline` is reached.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        slot: None,
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        match &self.slot {
+            Some(slot) => update_slot(slot, cx.waker()),
+            None => {
+                let slot = Rc::new(RefCell::new(Some(cx.waker().clone())));
+
+                expect_current()
+                    .reactor
+                    .borrow_mut()
+                    .timers
+                    .push(TimerEntry {
+                        deadline: self.deadline,
+                        slot: Rc::clone(&slot),
+                    });
+
+                self.slot = Some(slot);
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let (Some(slot), Some(shared)) = (&self.slot, current()) {
+            shared
+                .reactor
+                .borrow_mut()
+                .timers
+                .retain(|timer| !Rc::ptr_eq(&timer.slot, slot));
+        }
+    }
+}
+
+/// Future returned by [`wait_handle()`].
+pub struct WaitHandle {
+    handle: ctru_sys::Handle,
+    slot: Option<WakerSlot>,
+    result: Rc<Cell<Option<ctru_sys::Result>>>,
+}
+
+/// Wait until the kernel object behind `handle` (an event, a timer, a thread...) is signalled.
+///
+/// Waiting on a one-shot event or timer resets it, as with a blocking wait.
+/// `handle` must stay open until the future completes or is dropped.
+///
+/// The future resolves to an error if `handle` isn't a valid kernel object.
+#[doc(alias = "svcWaitSynchronizationN")]
+pub fn wait_handle(handle: ctru_sys::Handle) -> WaitHandle {
+    WaitHandle {
+        handle,
+        slot: None,
+        result: Rc::new(Cell::new(None)),
+    }
+}
+
+impl Future for WaitHandle {
+    type Output = crate::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(result) = self.result.get() {
+            return Poll::Ready(check(result));
+        }
+
+        match &self.slot {
+            Some(slot) => update_slot(slot, cx.waker()),
+            None => {
+                let slot = Rc::new(RefCell::new(Some(cx.waker().clone())));
+
+                expect_current()
+                    .reactor
+                    .borrow_mut()
+                    .handles
+                    .push(HandleEntry {
+                        handle: self.handle,
+                        slot: Rc::clone(&slot),
+                        result: Rc::clone(&self.result),
+                    });
+
+                self.slot = Some(slot);
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+fn check(result: ctru_sys::Result) -> crate::Result<()> {
+    ResultCode(result)?;
+    Ok(())
+}
+
+impl Drop for WaitHandle {
+    fn drop(&mut self) {
+        if let (Some(slot), Some(shared)) = (&self.slot, current()) {
+            shared
+                .reactor
+                .borrow_mut()
+                .handles
+                .retain(|entry| !Rc::ptr_eq(&entry.slot, slot));
+        }
+    }
+}
+
+/// Future returned by [`wait_socket()`].
+pub(crate) struct WaitSocket {
+    fd: libc::c_int,
+    events: libc::c_short,
+    slot: Option<WakerSlot>,
+}
+
+/// Wait until the socket `fd` is ready for one of `events` (`POLLIN`, `POLLOUT`...), or reports an error.
+pub(crate) fn wait_socket(fd: libc::c_int, events: libc::c_short) -> WaitSocket {
+    WaitSocket {
+        fd,
+        events,
+        slot: None,
+    }
+}
+
+impl Future for WaitSocket {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match &self.slot {
+            // The reactor takes the waker out of the slot when the socket is ready.
+            Some(slot) if slot.borrow().is_none() => return Poll::Ready(()),
+            Some(slot) => update_slot(slot, cx.waker()),
+            None => {
+                let slot = Rc::new(RefCell::new(Some(cx.waker().clone())));
+
+                expect_current()
+                    .reactor
+                    .borrow_mut()
+                    .sockets
+                    .push(SocketEntry {
+                        fd: self.fd,
+                        events: self.events,
+                        slot: Rc::clone(&slot),
+                    });
+
+                self.slot = Some(slot);
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for WaitSocket {
+    fn drop(&mut self) {
+        if let (Some(slot), Some(shared)) = (&self.slot, current()) {
+            shared
+                .reactor
+                .borrow_mut()
+                .sockets
+                .retain(|entry| !Rc::ptr_eq(&entry.slot, slot));
+        }
+    }
+}
+
+/// Future returned by [`yield_now()`].
+pub struct YieldNow {
+    yielded: bool,
+}
+
+/// Let the other tasks of the runtime run before the current one continues.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+struct BlockingState<T> {
+    output: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Handle to a closure run by [`spawn_blocking()`], resolving to its result (or to the panic payload if it panicked).
+pub struct BlockingHandle<T> {
+    state: Arc<Mutex<BlockingState<T>>>,
+}
+
+/// Run `f` on a new thread, on the same core as the calling thread, and await its result from the runtime.
+///
+/// # Notes
+///
+/// Threads on the same core aren't preempted: the closure only runs while the runtime's thread is waiting
+/// (which it does whenever no task can make progress). Use [`spawn_blocking_with()`] to run the closure on another core.
+///
+/// # Errors
+///
+/// This function will return an error if the thread couldn't be created.
+pub fn spawn_blocking<F, T>(f: F) -> crate::Result<BlockingHandle<T>>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    spawn_blocking_with(Builder::new(), f)
+}
+
+/// Run `f` on a new thread created with the given builder
+/// (e.g. on the system core, with `std::os::horizon::thread::BuilderExt::processor_id()`) and await its result from the runtime.
+///
+/// # Errors
+///
+/// This function will return an error if the thread couldn't be created.
+pub fn spawn_blocking_with<F, T>(builder: Builder, f: F) -> crate::Result<BlockingHandle<T>>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let state = Arc::new(Mutex::new(BlockingState {
+        output: None,
+        waker: None,
+    }));
+
+    let thread_state = Arc::clone(&state);
+    builder.spawn(move || {
+        let output = panic::catch_unwind(AssertUnwindSafe(f));
+
+        let mut state = thread_state.lock().unwrap();
+        state.output = Some(output);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    })?;
+
+    Ok(BlockingHandle { state })
+}
+
+impl<T> Future for BlockingHandle<T> {
+    type Output = thread::Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tasks_timers_and_blocking() {
+        let runtime = Runtime::new().unwrap();
+        let order = Rc::new(RefCell::new(Vec::new()));
+
+        let late = {
+            let order = Rc::clone(&order);
+            runtime.spawn(async move {
+                sleep(Duration::from_millis(20)).await;
+                order.borrow_mut().push("late");
+            })
+        };
+
+        let output = runtime.block_on(async {
+            let early = {
+                let order = Rc::clone(&order);
+                spawn(async move {
+                    yield_now().await;
+                    order.borrow_mut().push("early");
+                })
+            };
+
+            let blocking = spawn_blocking(|| 40 + 2).unwrap().await.unwrap();
+
+            early.await;
+            late.await;
+
+            blocking
+        });
+
+        assert_eq!(output, 42);
+        assert_eq!(*order.borrow(), ["early", "late"]);
+    }
+}
@@ -0,0 +1,474 @@
+//! Save data backup and restore.
+//!
+//! This module can export the save data of any installed title (see [`Am::title_list()`](crate::services::am::Am::title_list))
+//! into a single self-contained archive, and import it back later, which is all that's needed to write a save manager.
+//!
+//! # Archive format
+//!
+//! All integers are stored in little-endian byte order.
+//!
+//! | Field        | Size     | Description                                      |
+//! |--------------|----------|--------------------------------------------------|
+//! | Magic        | 8 bytes  | `CTRSAVE\0`                                      |
+//! | Version      | 4 bytes  | Format version, currently [`FORMAT_VERSION`].    |
+//! | Title ID     | 8 bytes  | ID of the title the save data belongs to.       |
+//! | Entry count  | 4 bytes  | Number of entries following the header.          |
+//!
+//! Each entry then starts with:
+//!
+//! | Field        | Size     | Description                                      |
+//! |--------------|----------|--------------------------------------------------|
+//! | Kind         | 1 byte   | `0` for a directory, `1` for a file.             |
+//! | Path length  | 2 bytes  | Length of the path, in bytes.                    |
+//! | Path         | variable | UTF-8 path relative to the save root, using `/` as separator. |
+//!
+//! File entries are followed by:
+//!
+//! | Field        | Size     | Description                                      |
+//! |--------------|----------|--------------------------------------------------|
+//! | Size         | 8 bytes  | Size of the file contents, in bytes.             |
+//! | Hash         | 4 bytes  | CRC-32 (IEEE) of the file contents.              |
+//! | Contents     | variable | The file contents.                               |
+//!
+//! Directories are always listed before the entries they contain.
+//!
+//! # Notes
+//!
+//! Accessing the save data of other titles requires the application to have the appropriate access rights.
+#![doc(alias = "backup")]
+#![doc(alias = "savedata")]
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::progress::Progress;
+use crate::services::am::Title;
+use crate::services::fs::{ArchiveID, MountedArchive, PathType};
+
+/// Magic bytes identifying a save archive.
+pub const MAGIC: [u8; 8] = *b"CTRSAVE\0";
+
+/// Version of the archive format written by [`export_save()`].
+pub const FORMAT_VERSION: u32 = 1;
+
+const KIND_DIRECTORY: u8 = 0;
+const KIND_FILE: u8 = 1;
+
+/// Error returned by an unsuccessful export or import.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Error {
+    /// The data doesn't start with [`MAGIC`] or is otherwise malformed.
+    InvalidFormat,
+    /// The archive was written with an unsupported version of the format.
+    UnsupportedVersion(u32),
+    /// The archive belongs to a different title.
+    TitleMismatch {
+        /// ID of the title the save data was being imported to.
+        expected: u64,
+        /// ID of the title stored in the archive.
+        found: u64,
+    },
+    /// The contents of a file don't match their hash.
+    HashMismatch {
+        /// Path of the corrupted file.
+        path: String,
+    },
+    /// The operation was cancelled (see [`Progress::is_cancelled()`]).
+    Cancelled,
+    /// The save data couldn't be accessed.
+    Failed(crate::Error),
+}
+
+/// Write the save data of `title` to `writer`.
+///
+/// # Errors
+///
+/// This function will return an error if the save data couldn't be mounted (e.g. because of insufficient access rights)
+/// or if reading the save data or writing to `writer` failed.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::savetool;
+/// use ctru::services::am::Am;
+/// use ctru::services::fs::MediaType;
+///
+/// let app_manager = Am::new()?;
+///
+/// for title in app_manager.title_list(MediaType::Sd)? {
+///     let file = std::fs::File::create(format!("sdmc:/saves/{:016X}.bin", title.id()))?;
+///     savetool::export_save(&title, std::io::BufWriter::new(file))?;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub fn export_save(title: &Title, writer: impl Write) -> Result<(), Error> {
+    export_save_with_progress(title, writer, &mut ())
+}
+
+/// Write the save data of `title` to `writer`, reporting the progress of the export to `progress`.
+///
+/// The export goes through two stages: `"Scanning"` while the save data is being listed, and `"Exporting"`
+/// while the files are written, where the amount of bytes done is the size of the files written so far.
+///
+/// # Errors
+///
+/// This function will return [`Error::Cancelled`] if `progress` asked to stop,
+/// or any of the errors returned by [`export_save()`].
+pub fn export_save_with_progress(
+    title: &Title,
+    mut writer: impl Write,
+    progress: &mut impl Progress,
+) -> Result<(), Error> {
+    let archive = mount(title)?;
+    let root = format!("{}:/", archive.name());
+
+    progress.set_stage("Scanning");
+
+    let mut entries = Vec::new();
+    collect_entries(Path::new(&root), "", &mut entries).map_err(failed)?;
+
+    let mut total = 0;
+    for (path, is_dir) in &entries {
+        if !is_dir {
+            total += fs::metadata(format!("{root}{path}")).map_err(failed)?.len();
+        }
+    }
+
+    writer.write_all(&MAGIC).map_err(failed)?;
+    writer
+        .write_all(&FORMAT_VERSION.to_le_bytes())
+        .map_err(failed)?;
+    writer
+        .write_all(&title.id().to_le_bytes())
+        .map_err(failed)?;
+    writer
+        .write_all(&(entries.len() as u32).to_le_bytes())
+        .map_err(failed)?;
+
+    progress.set_stage("Exporting");
+    progress.update(0, Some(total));
+
+    let mut done = 0;
+    for (path, is_dir) in entries {
+        if progress.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        done += write_entry(&mut writer, &root, &path, is_dir).map_err(failed)?;
+        progress.update(done, Some(total));
+    }
+
+    writer.flush().map_err(failed)
+}
+
+/// Replace the save data of `title` with the contents of an archive produced by [`export_save()`].
+///
+/// # Notes
+///
+/// The whole archive is read and verified in memory before the existing save data is replaced:
+/// if the archive is malformed or corrupted, the existing save data is left untouched.
+///
+/// # Errors
+///
+/// This function will return an error if the archive is malformed, belongs to a different title or contains corrupted files,
+/// or if the save data couldn't be mounted or written.
+pub fn import_save(title: &Title, reader: impl Read) -> Result<(), Error> {
+    import_save_with_progress(title, reader, &mut ())
+}
+
+/// Replace the save data of `title` with the contents of an archive produced by [`export_save()`],
+/// reporting the progress of the import to `progress`.
+///
+/// The import goes through a single `"Importing"` stage, where the amount of bytes done is the size of the files read and verified so far.
+/// The total isn't known in advance.
+///
+/// Cancellation is only checked while the archive is being read, before the existing save data is replaced:
+/// once the save data starts being written, the import runs to completion.
+///
+/// # Errors
+///
+/// This function will return [`Error::Cancelled`] if `progress` asked to stop (leaving the existing save data untouched),
+/// or any of the errors returned by [`import_save()`].
+pub fn import_save_with_progress(
+    title: &Title,
+    mut reader: impl Read,
+    progress: &mut impl Progress,
+) -> Result<(), Error> {
+    progress.set_stage("Importing");
+
+    let mut magic = [0; 8];
+    read_exact(&mut reader, &mut magic)?;
+    if magic != MAGIC {
+        return Err(Error::InvalidFormat);
+    }
+
+    let version = u32::from_le_bytes(read_array(&mut reader)?);
+    if version != FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    let found = u64::from_le_bytes(read_array(&mut reader)?);
+    if found != title.id() {
+        return Err(Error::TitleMismatch {
+            expected: title.id(),
+            found,
+        });
+    }
+
+    let count = u32::from_le_bytes(read_array(&mut reader)?);
+
+    // Nothing is written before every entry has been read and verified.
+    let mut entries = Vec::new();
+    let mut done = 0;
+    for _ in 0..count {
+        if progress.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        let entry = read_entry(&mut reader)?;
+
+        if let Some(contents) = &entry.contents {
+            done += contents.len() as u64;
+            progress.update(done, None);
+        }
+
+        entries.push(entry);
+    }
+
+    let archive = mount(title)?;
+    let root = format!("{}:/", archive.name());
+
+    clear_directory(Path::new(&root)).map_err(failed)?;
+
+    for entry in entries {
+        let full_path = format!("{root}{}", entry.path);
+
+        match entry.contents {
+            None => fs::create_dir(&full_path).map_err(failed)?,
+            Some(contents) => fs::write(&full_path, contents).map_err(failed)?,
+        }
+    }
+
+    archive.commit().map_err(Error::Failed)
+}
+
+/// Entry read from an archive, with the contents of files.
+struct Entry {
+    path: String,
+    contents: Option<Vec<u8>>,
+}
+
+/// Read and verify the next entry of an archive.
+fn read_entry(reader: &mut impl Read) -> Result<Entry, Error> {
+    let [kind] = read_array(reader)?;
+    let path_len = u16::from_le_bytes(read_array(reader)?);
+
+    let mut path = vec![0; usize::from(path_len)];
+    read_exact(reader, &mut path)?;
+    let path = String::from_utf8(path).map_err(|_| Error::InvalidFormat)?;
+
+    // Don't let malformed archives escape the save root.
+    if path.is_empty()
+        || path
+            .split('/')
+            .any(|c| c.is_empty() || c == "." || c == "..")
+    {
+        return Err(Error::InvalidFormat);
+    }
+
+    let contents = match kind {
+        KIND_DIRECTORY => None,
+        KIND_FILE => {
+            let size = u64::from_le_bytes(read_array(reader)?);
+            let hash = u32::from_le_bytes(read_array(reader)?);
+
+            let mut contents = Vec::new();
+            reader
+                .by_ref()
+                .take(size)
+                .read_to_end(&mut contents)
+                .map_err(failed)?;
+
+            if contents.len() as u64 != size {
+                return Err(Error::InvalidFormat);
+            }
+
+            if crc32(&contents) != hash {
+                return Err(Error::HashMismatch { path });
+            }
+
+            Some(contents)
+        }
+        _ => return Err(Error::InvalidFormat),
+    };
+
+    Ok(Entry { path, contents })
+}
+
+fn mount(title: &Title) -> Result<MountedArchive, Error> {
+    // Every mount needs a unique device name, in case saves are being moved between titles concurrently.
+    static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+    let name = format!("save{}", NEXT_ID.fetch_add(1, Ordering::Relaxed));
+
+    // Lowpath of the user save data archive: media type and title ID (low, high).
+    let lowpath: Vec<u8> = [
+        title.media_type() as u32,
+        (title.id() & 0xFFFF_FFFF) as u32,
+        (title.id() >> 32) as u32,
+    ]
+    .into_iter()
+    .flat_map(u32::to_le_bytes)
+    .collect();
+
+    MountedArchive::new(ArchiveID::UserSavedata, PathType::Binary, &lowpath, &name)
+        .map_err(Error::Failed)
+}
+
+fn collect_entries(
+    directory: &Path,
+    prefix: &str,
+    entries: &mut Vec<(String, bool)>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = format!("{prefix}{name}");
+
+        if entry.file_type()?.is_dir() {
+            entries.push((path.clone(), true));
+            collect_entries(&entry.path(), &format!("{path}/"), entries)?;
+        } else {
+            entries.push((path, false));
+        }
+    }
+
+    Ok(())
+}
+
+/// Write an entry of the archive, returning the size of the file contents written.
+fn write_entry(writer: &mut impl Write, root: &str, path: &str, is_dir: bool) -> io::Result<u64> {
+    let path_len = u16::try_from(path.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path is too long"))?;
+
+    if is_dir {
+        writer.write_all(&[KIND_DIRECTORY])?;
+        writer.write_all(&path_len.to_le_bytes())?;
+        writer.write_all(path.as_bytes())?;
+
+        Ok(0)
+    } else {
+        let contents = fs::read(format!("{root}{path}"))?;
+
+        writer.write_all(&[KIND_FILE])?;
+        writer.write_all(&path_len.to_le_bytes())?;
+        writer.write_all(path.as_bytes())?;
+        writer.write_all(&(contents.len() as u64).to_le_bytes())?;
+        writer.write_all(&crc32(&contents).to_le_bytes())?;
+        writer.write_all(&contents)?;
+
+        Ok(contents.len() as u64)
+    }
+}
+
+fn clear_directory(directory: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_exact(reader: &mut impl Read, buffer: &mut [u8]) -> Result<(), Error> {
+    reader.read_exact(buffer).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => Error::InvalidFormat,
+        _ => failed(e),
+    })
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], Error> {
+    let mut buffer = [0; N];
+    read_exact(reader, &mut buffer)?;
+
+    Ok(buffer)
+}
+
+fn failed(e: io::Error) -> Error {
+    Error::Failed(e.into())
+}
+
+/// CRC-32 (IEEE 802.3) checksum, as used by zlib and PNG.
+fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+
+            while bit < 8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+
+            table[i] = crc;
+            i += 1;
+        }
+
+        table
+    };
+
+    !data.iter().fold(!0, |crc, &byte| {
+        TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidFormat => write!(f, "malformed save archive"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported save archive version {version}")
+            }
+            Self::TitleMismatch { expected, found } => write!(
+                f,
+                "save archive belongs to title {found:016X}, not {expected:016X}"
+            ),
+            Self::HashMismatch { path } => write!(f, "corrupted file in save archive: {path}"),
+            Self::Cancelled => write!(f, "the save data transfer was cancelled"),
+            Self::Failed(e) => write!(f, "couldn't access save data: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
@@ -13,3 +13,21 @@ impl Sealed for TopScreenLeft {}
 impl Sealed for TopScreenRight {}
 impl Sealed for BottomScreen {}
 impl Sealed for Console<'_> {}
+
+// Plain data types accepted by `crate::mem`.
+impl Sealed for u8 {}
+impl Sealed for i8 {}
+impl Sealed for u16 {}
+impl Sealed for i16 {}
+impl Sealed for u32 {}
+impl Sealed for i32 {}
+impl Sealed for u64 {}
+impl Sealed for i64 {}
+impl Sealed for f32 {}
+impl Sealed for f64 {}
+
+// Uniform values accepted by `crate::shader`.
+impl Sealed for [f32; 4] {}
+impl<const N: usize> Sealed for [[f32; 4]; N] {}
+impl Sealed for [u8; 4] {}
+impl Sealed for bool {}
@@ -0,0 +1,181 @@
+//! Seed database.
+//!
+//! Titles released after system version 9.6.0 may have their contents encrypted with an additional per-title "seed",
+//! which the console obtains from the eShop servers and keeps in its own seed database.
+//! Content can only be decrypted once the corresponding seed is known.
+//!
+//! The system's database is stored in FS' own system save data, which applications can't access.
+//! This module instead reads the `seeddb.bin` format used by title managers and emulators to share seeds,
+//! and provides [`ncch_uses_seed()`] to check whether a title's content needs one.
+//!
+//! # Format
+//!
+//! | Offset | Size          | Description                               |
+//! |--------|---------------|-------------------------------------------|
+//! | 0x0    | 0x4           | Number of entries (little endian)         |
+//! | 0x4    | 0xC           | Padding                                   |
+//! | 0x10   | 0x20 × count  | Entries                                   |
+//!
+//! Each entry contains the title ID (8 bytes, little endian), the seed (16 bytes) and 8 bytes of padding.
+//!
+//! See also <https://www.3dbrew.org/wiki/Seed_Database>
+#![doc(alias = "seed")]
+
+use crate::Error;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Size (in bytes) of a title's seed.
+pub const SEED_SIZE: usize = 0x10;
+
+/// A title's content seed.
+pub type Seed = [u8; SEED_SIZE];
+
+const HEADER_SIZE: usize = 0x10;
+const ENTRY_SIZE: usize = 0x20;
+
+/// Minimum size of an NCCH header, as needed by [`ncch_uses_seed()`].
+const NCCH_HEADER_SIZE: usize = 0x200;
+const NCCH_MAGIC_OFFSET: usize = 0x100;
+const NCCH_FLAGS_OFFSET: usize = 0x188;
+/// Bit of the 8th NCCH flag marking seed encrypted content.
+const NCCH_SEED_FLAG: u8 = 0x20;
+
+/// Read-only seed database.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::seeddb::SeedDb;
+///
+/// let seeds = SeedDb::open("sdmc:/seeddb.bin")?;
+///
+/// if seeds.contains(0x0004_0000_0017_6F00) {
+///     println!("The seed of this title is known");
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SeedDb {
+    seeds: HashMap<u64, Seed>,
+}
+
+impl SeedDb {
+    /// Parse a seed database from its raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the data is shorter than the amount of entries in its header requires.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<Self> {
+        if data.len() < HEADER_SIZE {
+            return Err(Error::BufferTooShort {
+                provided: data.len(),
+                wanted: HEADER_SIZE,
+            });
+        }
+
+        let count = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+        let wanted = count
+            .checked_mul(ENTRY_SIZE)
+            .and_then(|size| size.checked_add(HEADER_SIZE))
+            .ok_or_else(|| Error::Other(String::from("seed database is too large")))?;
+
+        if data.len() < wanted {
+            return Err(Error::BufferTooShort {
+                provided: data.len(),
+                wanted,
+            });
+        }
+
+        let seeds = data[HEADER_SIZE..wanted]
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| {
+                let title_id = u64::from_le_bytes(entry[0..8].try_into().unwrap());
+                let seed: Seed = entry[8..8 + SEED_SIZE].try_into().unwrap();
+
+                (title_id, seed)
+            })
+            .collect();
+
+        Ok(Self { seeds })
+    }
+
+    /// Read and parse the seed database file at the given path.
+    pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Returns the seed of the given title, if it is in the database.
+    pub fn seed(&self, title_id: u64) -> Option<&Seed> {
+        self.seeds.get(&title_id)
+    }
+
+    /// Returns `true` if the database contains the seed of the given title.
+    pub fn contains(&self, title_id: u64) -> bool {
+        self.seeds.contains_key(&title_id)
+    }
+
+    /// Returns the number of seeds in the database.
+    pub fn len(&self) -> usize {
+        self.seeds.len()
+    }
+
+    /// Returns `true` if the database doesn't contain any seed.
+    pub fn is_empty(&self) -> bool {
+        self.seeds.is_empty()
+    }
+
+    /// Returns an iterator over the title IDs and seeds in the database, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &Seed)> {
+        self.seeds.iter().map(|(id, seed)| (*id, seed))
+    }
+}
+
+/// Returns `true` if the NCCH content with the given header is encrypted with a seed.
+///
+/// # Errors
+///
+/// This function will return an error if the header is too short or doesn't have a valid `NCCH` magic number.
+pub fn ncch_uses_seed(header: &[u8]) -> crate::Result<bool> {
+    if header.len() < NCCH_HEADER_SIZE {
+        return Err(Error::BufferTooShort {
+            provided: header.len(),
+            wanted: NCCH_HEADER_SIZE,
+        });
+    }
+
+    if &header[NCCH_MAGIC_OFFSET..NCCH_MAGIC_OFFSET + 4] != b"NCCH" {
+        return Err(Error::Other(String::from(
+            "data doesn't have a valid NCCH magic number",
+        )));
+    }
+
+    Ok(header[NCCH_FLAGS_OFFSET + 7] & NCCH_SEED_FLAG != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_database() {
+        let mut data = vec![0; HEADER_SIZE + 2 * ENTRY_SIZE];
+        data[0] = 2;
+        data[HEADER_SIZE..HEADER_SIZE + 8].copy_from_slice(&0x0004_0000_0017_6F00u64.to_le_bytes());
+        data[HEADER_SIZE + 8..HEADER_SIZE + 24].fill(0xAB);
+
+        let seeds = SeedDb::from_bytes(&data).unwrap();
+        assert_eq!(seeds.len(), 2);
+        assert_eq!(seeds.seed(0x0004_0000_0017_6F00), Some(&[0xAB; SEED_SIZE]));
+        assert!(!seeds.contains(0x0004_0000_0000_0000));
+
+        assert!(SeedDb::from_bytes(&data[..HEADER_SIZE + ENTRY_SIZE]).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! Account service.
+//!
+//! The ACT service manages the Nintendo Network ID (NNID) accounts linked to the console.
+//! This module reads the information of the current account, e.g. to personalize an application's behavior per account.
+//!
+//! # Notes
+//!
+//! The "act:u" service is only accessible to applications which list it in their exheader's service access control.
+//! Consoles without a linked NNID only provide some of the information: requests for the rest return an error.
+//!
+//! See also <https://www.3dbrew.org/wiki/ACT_Services>
+#![doc(alias = "nnid")]
+#![doc(alias = "account")]
+
+use crate::error::ResultCode;
+use crate::services::frd::FriendCode;
+use crate::services::svc::{make_ipc_header, HandleExt};
+use crate::services::ServiceReference;
+use crate::Error;
+use ctru_sys::Handle;
+use std::ffi::CString;
+use std::sync::Mutex;
+
+static ACT_ACTIVE: Mutex<()> = Mutex::new(());
+static ACT_HANDLE: Mutex<Option<Handle>> = Mutex::new(None);
+
+// act:u command headers
+const INITIALIZE_COMMAND_HEADER: u32 = make_ipc_header(1, 2, 4);
+const GET_ACCOUNT_DATA_BLOCK_COMMAND_HEADER: u32 = make_ipc_header(6, 3, 2);
+
+/// SDK version reported when initializing the service.
+const SDK_VERSION: u32 = 0xB0502C8;
+/// Account slot of the account currently in use.
+const CURRENT_ACCOUNT: u32 = 0xFE;
+
+/// Information blocks of an account.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+enum DataBlock {
+    PersistentId = 0x05,
+    AccountId = 0x08,
+    Country = 0x0B,
+    PrincipalId = 0x0C,
+}
+
+/// Handle to the "act:u" service.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::act::Act;
+///
+/// let act = Act::new()?;
+///
+/// match act.account_id() {
+///     Ok(nnid) => println!("Welcome back, {nnid}!"),
+///     Err(_) => println!("No Nintendo Network ID is linked to this console."),
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct Act {
+    _service_reference: ServiceReference,
+}
+
+impl Act {
+    /// Initialize a new service handle.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the service was unable to be initialized,
+    /// e.g. because the application isn't allowed to access "act:u", or if it is already in use elsewhere.
+    pub fn new() -> crate::Result<Self> {
+        let service_reference = ServiceReference::new(
+            "act:u",
+            &ACT_ACTIVE,
+            || unsafe {
+                let mut handle = Handle::default();
+                let service_name = CString::new("act:u").unwrap();
+                ResultCode(ctru_sys::srvGetServiceHandle(
+                    &mut handle,
+                    service_name.as_ptr(),
+                ))?;
+
+                // The service doesn't need any shared memory to read account information.
+                let request = vec![INITIALIZE_COMMAND_HEADER, SDK_VERSION, 0, 0x20, 0, 0, 0];
+                if let Err(e) = handle.send_service_request(request, 2) {
+                    ctru_sys::svcCloseHandle(handle);
+                    return Err(e);
+                }
+
+                *ACT_HANDLE
+                    .lock()
+                    .map_err(|e| Error::Other(format!("Failed to write to ACT_HANDLE: {e}")))? =
+                    Some(handle);
+
+                Ok(())
+            },
+            || {
+                let Some(handle) = ACT_HANDLE
+                    .lock()
+                    .expect("Failed to write to ACT_HANDLE")
+                    .take()
+                else {
+                    return;
+                };
+
+                unsafe {
+                    ctru_sys::svcCloseHandle(handle);
+                }
+            },
+        )?;
+
+        service_reference.set_handle_getter(|| *ACT_HANDLE.try_lock().ok()?);
+
+        Ok(Self {
+            _service_reference: service_reference,
+        })
+    }
+
+    /// Returns the Nintendo Network ID (the user name chosen when creating the account) of the current account.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no NNID is linked to the console.
+    #[doc(alias = "nnid")]
+    pub fn account_id(&self) -> crate::Result<String> {
+        let mut block = [0; 17];
+        self.account_data_block(DataBlock::AccountId, &mut block)?;
+
+        string_from_block(&block)
+    }
+
+    /// Returns the country of the current account, as a two-letter ISO 3166-1 code (e.g. `"US"`).
+    pub fn country(&self) -> crate::Result<String> {
+        let mut block = [0; 3];
+        self.account_data_block(DataBlock::Country, &mut block)?;
+
+        string_from_block(&block)
+    }
+
+    /// Returns the persistent ID of the current account.
+    ///
+    /// Unlike the NNID, this ID identifies the account slot on the console, and is available without a linked NNID.
+    pub fn persistent_id(&self) -> crate::Result<u32> {
+        let mut block = [0; 4];
+        self.account_data_block(DataBlock::PersistentId, &mut block)?;
+
+        Ok(u32::from_le_bytes(block))
+    }
+
+    /// Returns the principal ID of the current account, which identifies it on Nintendo Network.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no NNID is linked to the console.
+    pub fn principal_id(&self) -> crate::Result<u32> {
+        let mut block = [0; 4];
+        self.account_data_block(DataBlock::PrincipalId, &mut block)?;
+
+        Ok(u32::from_le_bytes(block))
+    }
+
+    /// Returns the friend code of the current account.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no NNID is linked to the console.
+    pub fn friend_code(&self) -> crate::Result<FriendCode> {
+        self.principal_id().map(FriendCode::from_principal_id)
+    }
+
+    fn account_data_block(&self, block: DataBlock, output: &mut [u8]) -> crate::Result<()> {
+        let handle = (*ACT_HANDLE
+            .lock()
+            .map_err(|e| Error::Other(format!("Failed to read ACT_HANDLE: {e}")))?)
+        .ok_or_else(|| Error::Other(String::from("the act:u service isn't initialized")))?;
+
+        let request = vec![
+            GET_ACCOUNT_DATA_BLOCK_COMMAND_HEADER,
+            CURRENT_ACCOUNT,
+            output.len() as u32,
+            block as u32,
+            // Mapped buffer descriptor, writable by the service.
+            ((output.len() as u32) << 4) | 0xC,
+            output.as_mut_ptr() as u32,
+        ];
+
+        unsafe {
+            handle.send_service_request(request, 2)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Read a NUL-terminated string returned by the service.
+fn string_from_block(block: &[u8]) -> crate::Result<String> {
+    let len = block
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(block.len());
+
+    String::from_utf8(block[..len].to_vec())
+        .map_err(|_| Error::Other(String::from("invalid account information string")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_strings() {
+        let mut block = [0; 17];
+        block[..6].copy_from_slice(b"Player");
+        assert_eq!(string_from_block(&block).unwrap(), "Player");
+
+        assert_eq!(string_from_block(b"US\0").unwrap(), "US");
+        assert_eq!(string_from_block(b"JP").unwrap(), "JP");
+        assert!(string_from_block(&[0xFF, 0]).is_err());
+    }
+}
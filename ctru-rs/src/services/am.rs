@@ -2,23 +2,36 @@
 //!
 //! As the name implies, the AM service manages installed applications. It can:
 //! - Read the installed applications on the console and their information (depending on the install location).
-//! - Install compatible applications to the console.
+//! - Install compatible applications to the console, from CIA files (see [`Am::install_cia()`]).
 //!
-//! TODO: [`ctru-rs`](crate) doesn't support installing or uninstalling titles yet.
+//! TODO: [`ctru-rs`](crate) doesn't support uninstalling titles yet.
 #![doc(alias = "app")]
 #![doc(alias = "manager")]
 
 use crate::error::ResultCode;
-use crate::services::fs::MediaType;
+use crate::image::Image;
+use crate::progress::{self, Progress};
+use crate::services::cfgu::{Language, Region};
+use crate::services::fs::{ArchiveID, MediaType, PathType};
+use crate::smdh::{Smdh, TitleNames, SMDH_SIZE};
+use std::fs;
+use std::io::{self, Read, Write};
 use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::vec;
 
 /// General information about a specific title entry.
+///
+/// With the `serde` feature, titles can be serialized (e.g. to keep a list of installed titles),
+/// but not deserialized, since they're only valid while the [`Am`] service is active.
 #[doc(alias = "AM_TitleEntry")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Title<'a> {
     id: u64,
     mediatype: MediaType,
     size: u64,
     version: u16,
+    #[cfg_attr(feature = "serde", serde(skip))]
     _am: PhantomData<&'a Am>,
 }
 
@@ -51,8 +64,375 @@ impl<'a> Title<'a> {
     pub fn version(&self) -> u16 {
         self.version
     }
+
+    /// Returns the install location of this title.
+    pub fn media_type(&self) -> MediaType {
+        self.mediatype
+    }
+
+    /// Read the [`Smdh`] (which contains the title's names and icons) from this title's ExeFS.
+    ///
+    /// # Notes
+    ///
+    /// The whole file is read with a single FS request, yet opening each title's content is still noticeably slow.
+    /// Have a look at [`IconCache`] if you need to read the SMDH of many titles repeatedly (e.g. when making a launcher).
+    #[doc(alias = "FSUSER_OpenFileDirectly")]
+    pub fn smdh(&self) -> crate::Result<Smdh> {
+        // Lowpath of the title's content archive: title ID (low, high), media type and a reserved word.
+        let archive_path: [u32; 4] = [
+            (self.id & 0xFFFF_FFFF) as u32,
+            (self.id >> 32) as u32,
+            self.mediatype as u32,
+            0,
+        ];
+        // Lowpath of the "icon" file within the ExeFS.
+        let file_path: [u32; 5] = [0, 0, 2, u32::from_le_bytes(*b"icon"), 0];
+
+        let raw_archive_path = ctru_sys::FS_Path {
+            type_: PathType::Binary.into(),
+            size: std::mem::size_of_val(&archive_path) as u32,
+            data: archive_path.as_ptr().cast(),
+        };
+        let raw_file_path = ctru_sys::FS_Path {
+            type_: PathType::Binary.into(),
+            size: std::mem::size_of_val(&file_path) as u32,
+            data: file_path.as_ptr().cast(),
+        };
+
+        let mut buffer = vec![0u8; SMDH_SIZE];
+
+        unsafe {
+            let mut handle = 0;
+            ResultCode(ctru_sys::FSUSER_OpenFileDirectly(
+                &mut handle,
+                ArchiveID::SaveDataAndContent.into(),
+                raw_archive_path,
+                raw_file_path,
+                ctru_sys::FS_OPEN_READ,
+                0,
+            ))?;
+
+            let mut read_amount = 0;
+            let read_result = ResultCode(ctru_sys::FSFILE_Read(
+                handle,
+                &mut read_amount,
+                0,
+                buffer.as_mut_ptr().cast(),
+                SMDH_SIZE as u32,
+            ));
+
+            // Always close the file handle, even if the read failed.
+            let _ = ctru_sys::FSFILE_Close(handle);
+
+            read_result?;
+            buffer.truncate(read_amount as usize);
+        }
+
+        Smdh::from_bytes(&buffer)
+    }
+
+    /// Returns information about the contents of this title.
+    ///
+    /// # Notes
+    ///
+    /// The AM service only lists the contents of add-on content (DLC) titles this way. Other titles return an error.
+    #[doc(
+        alias = "AMAPP_ListDLCContentInfos",
+        alias = "AMAPP_GetDLCContentInfoCount"
+    )]
+    pub fn content_infos(&self) -> crate::Result<Vec<ContentInfo>> {
+        let mut count = 0;
+        ResultCode(unsafe {
+            ctru_sys::AMAPP_GetDLCContentInfoCount(&mut count, self.mediatype.into(), self.id)
+        })?;
+
+        let mut infos: Vec<ctru_sys::AM_ContentInfo> = Vec::with_capacity(count as usize);
+        let mut read_amount = 0;
+
+        unsafe {
+            ResultCode(ctru_sys::AMAPP_ListDLCContentInfos(
+                &mut read_amount,
+                self.mediatype.into(),
+                self.id,
+                count,
+                0,
+                infos.as_mut_ptr(),
+            ))?;
+
+            infos.set_len(read_amount.min(count) as usize);
+        }
+
+        Ok(infos
+            .into_iter()
+            .map(|info| ContentInfo {
+                index: info.index,
+                content_type: info.type_,
+                id: info.contentId,
+                size: info.size,
+                flags: info.flags,
+            })
+            .collect())
+    }
 }
 
+/// Information about a single content of a title.
+#[doc(alias = "AM_ContentInfo")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContentInfo {
+    index: u16,
+    content_type: u16,
+    id: u32,
+    size: u64,
+    flags: u8,
+}
+
+impl ContentInfo {
+    /// Returns the index of this content within the title.
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the ID of this content.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the size of this content in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns `true` if the content is encrypted with the title key.
+    ///
+    /// Whether it is additionally encrypted with a seed is stored in its NCCH header instead,
+    /// see [`ncch_uses_seed()`](crate::seeddb::ncch_uses_seed).
+    pub fn is_encrypted(&self) -> bool {
+        self.content_type & 0x1 != 0
+    }
+
+    /// Returns `true` if the content is optional.
+    pub fn is_optional(&self) -> bool {
+        self.content_type & 0x4000 != 0
+    }
+
+    /// Returns `true` if the content has been downloaded.
+    #[doc(alias = "AM_CONTENT_DOWNLOADED")]
+    pub fn is_downloaded(&self) -> bool {
+        u32::from(self.flags) & ctru_sys::AM_CONTENT_DOWNLOADED != 0
+    }
+
+    /// Returns `true` if the console owns the rights to this content.
+    #[doc(alias = "AM_CONTENT_OWNED")]
+    pub fn is_owned(&self) -> bool {
+        u32::from(self.flags) & ctru_sys::AM_CONTENT_OWNED != 0
+    }
+}
+
+/// Cache of the [`Smdh`] data of installed titles.
+///
+/// Reading the SMDH of many titles directly from their content takes several seconds.
+/// This cache stores each read SMDH file in a user-supplied directory (e.g. on the SD card),
+/// so that successive reads (even across different runs of the application) only need a single small file read.
+///
+/// Cached entries are keyed by title ID and version, so updated titles are read again automatically.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::am::{Am, IconCache};
+/// use ctru::services::fs::MediaType;
+/// let app_manager = Am::new()?;
+///
+/// let cache = IconCache::new(&app_manager, "sdmc:/3ds/my-launcher/icons")?;
+///
+/// let titles = app_manager.title_list(MediaType::Sd)?;
+/// let icons = cache.smdh_list(&titles);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct IconCache<'a> {
+    directory: PathBuf,
+    _am: PhantomData<&'a Am>,
+}
+
+impl<'a> IconCache<'a> {
+    /// Create a new cache saving its data in the given directory.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the directory doesn't exist and couldn't be created.
+    pub fn new(_am: &'a Am, directory: impl Into<PathBuf>) -> crate::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+
+        Ok(Self {
+            directory,
+            _am: PhantomData,
+        })
+    }
+
+    /// Returns the [`Smdh`] of the given title, reading it from the cache if available.
+    ///
+    /// # Notes
+    ///
+    /// If the cache cannot be written to, the SMDH is still returned, but it will be read again from the title's content next time.
+    pub fn smdh(&self, title: &Title) -> crate::Result<Smdh> {
+        let path = self
+            .directory
+            .join(format!("{:016X}-{}.smdh", title.id(), title.version()));
+
+        if let Ok(smdh) = fs::read(&path)
+            .map_err(Into::into)
+            .and_then(|d| Smdh::from_bytes(&d))
+        {
+            return Ok(smdh);
+        }
+
+        let smdh = title.smdh()?;
+        let _ = fs::write(&path, smdh.as_bytes());
+
+        Ok(smdh)
+    }
+
+    /// Returns the [`Smdh`] of each of the given titles, in the same order.
+    pub fn smdh_list(&self, titles: &[Title]) -> Vec<crate::Result<Smdh>> {
+        titles.iter().map(|title| self.smdh(title)).collect()
+    }
+
+    /// Remove all cached entries.
+    pub fn clear(&self) -> crate::Result<()> {
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+
+            if path.extension().is_some_and(|ext| ext == "smdh") {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Installed title with the data shown for it by a launcher, returned by a [`TitleBrowser`].
+pub struct TitleInfo<'a> {
+    /// The title itself.
+    pub title: Title<'a>,
+    /// Regions the title runs in.
+    pub regions: Vec<Region>,
+    /// Names of the title in the language chosen for the browser.
+    pub names: TitleNames,
+    /// Large ([`LARGE_ICON_SIZE`](crate::smdh::LARGE_ICON_SIZE)) icon of the title.
+    pub icon: Image,
+}
+
+/// Iterator over the installed titles and their names, regions and icons, as needed by a launcher.
+///
+/// The [`Smdh`] data of the titles is read through an [`IconCache`], so only the first run of the application
+/// (or the first time a title is seen) needs to read it from the titles' content.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::am::{Am, TitleBrowser};
+/// use ctru::services::cfgu::Language;
+/// use ctru::services::fs::MediaType;
+/// let app_manager = Am::new()?;
+///
+/// let browser = TitleBrowser::new(
+///     &app_manager,
+///     &[MediaType::Sd, MediaType::Nand],
+///     "sdmc:/3ds/my-launcher/icons",
+///     Language::English,
+/// )?;
+///
+/// for info in browser {
+///     let info = info?;
+///     println!("{:016X}: {}", info.title.id(), info.names.short_description);
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct TitleBrowser<'a> {
+    cache: IconCache<'a>,
+    language: Language,
+    titles: vec::IntoIter<Title<'a>>,
+}
+
+impl<'a> TitleBrowser<'a> {
+    /// List the titles installed on the given media types, caching their SMDH data in `cache_directory`
+    /// and returning their names in `language`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the titles couldn't be listed or if the cache directory couldn't be created.
+    pub fn new(
+        am: &'a Am,
+        media_types: &[MediaType],
+        cache_directory: impl Into<PathBuf>,
+        language: Language,
+    ) -> crate::Result<Self> {
+        let mut titles = Vec::new();
+
+        for &media_type in media_types {
+            titles.extend(am.title_list(media_type)?);
+        }
+
+        Ok(Self {
+            cache: IconCache::new(am, cache_directory)?,
+            language,
+            titles: titles.into_iter(),
+        })
+    }
+
+    /// Returns the cache used to read the SMDH data of the titles.
+    pub fn cache(&self) -> &IconCache<'a> {
+        &self.cache
+    }
+}
+
+impl<'a> Iterator for TitleBrowser<'a> {
+    type Item = crate::Result<TitleInfo<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let title = self.titles.next()?;
+
+        let smdh = match self.cache.smdh(&title) {
+            Ok(smdh) => smdh,
+            Err(e) => return Some(Err(e)),
+        };
+
+        // Many titles only include names for some languages, English is the most common one.
+        let mut names = smdh.names(self.language);
+        if names.short_description.is_empty() {
+            names = smdh.names(Language::English);
+        }
+
+        Some(Ok(TitleInfo {
+            regions: smdh.regions(),
+            names,
+            icon: smdh.large_icon_image(),
+            title,
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.titles.size_hint()
+    }
+}
+
+impl ExactSizeIterator for TitleBrowser<'_> {}
+
 /// Handle to the Application Manager service.
 pub struct Am(());
 
@@ -173,6 +553,108 @@ impl Am {
             })
             .collect())
     }
+
+    /// Install the CIA file read from `reader` to `mediatype`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading the CIA file fails, or if AM refuses to install it
+    /// (e.g. because the file is corrupted or the application lacks access to the `am:net` service).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::am::Am;
+    /// use ctru::services::fs::MediaType;
+    ///
+    /// let app_manager = Am::new()?;
+    ///
+    /// let file = std::fs::File::open("sdmc:/cias/homebrew.cia")?;
+    /// app_manager.install_cia(MediaType::Sd, file)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "AM_StartCiaInstall", alias = "AM_FinishCiaInstall")]
+    pub fn install_cia(&self, mediatype: MediaType, reader: impl Read) -> crate::Result<()> {
+        self.install_cia_with_progress(mediatype, reader, None, &mut ())
+    }
+
+    /// Install the CIA file read from `reader` to `mediatype`, reporting the amount of bytes installed out of `size` to `progress`.
+    ///
+    /// The install goes through a single `"Installing"` stage. If it's cancelled or fails, AM discards the partially installed title.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`Error::Cancelled`](crate::Error::Cancelled) if `progress` asked to stop,
+    /// or any of the errors returned by [`Am::install_cia()`].
+    #[doc(alias = "AM_StartCiaInstall", alias = "AM_FinishCiaInstall")]
+    pub fn install_cia_with_progress(
+        &self,
+        mediatype: MediaType,
+        reader: impl Read,
+        size: Option<u64>,
+        progress: &mut impl Progress,
+    ) -> crate::Result<()> {
+        let mut handle = 0;
+        ResultCode(unsafe { ctru_sys::AM_StartCiaInstall(mediatype.into(), &mut handle) })?;
+
+        let mut install = CiaInstall { handle, offset: 0 };
+
+        progress.set_stage("Installing");
+        let copied = progress::copy(reader, &mut install, size, progress);
+
+        // Finishing or cancelling the install also closes the handle.
+        unsafe {
+            match copied {
+                Ok(_) => ResultCode(ctru_sys::AM_FinishCiaInstall(handle))?,
+                Err(e) => {
+                    let _ = ctru_sys::AM_CancelCIAInstall(handle);
+                    return Err(e);
+                }
+            };
+        }
+
+        Ok(())
+    }
+}
+
+/// Writer of the CIA file being installed through a handle returned by `AM_StartCiaInstall`.
+struct CiaInstall {
+    handle: ctru_sys::Handle,
+    offset: u64,
+}
+
+impl Write for CiaInstall {
+    #[doc(alias = "FSFILE_Write")]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(u32::MAX as usize) as u32;
+        let mut written = 0;
+
+        let result = unsafe {
+            ctru_sys::FSFILE_Write(
+                self.handle,
+                &mut written,
+                self.offset,
+                buf.as_ptr().cast(),
+                len,
+                0,
+            )
+        };
+        if ctru_sys::R_FAILED(result) {
+            return Err(io::Error::other(crate::Error::Os(result)));
+        }
+
+        self.offset += u64::from(written);
+        Ok(written as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 impl Drop for Am {
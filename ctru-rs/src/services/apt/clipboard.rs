@@ -0,0 +1,76 @@
+//! Application clipboard.
+//!
+//! The system doesn't provide a clipboard, so text editing homebrew can't copy and paste text between inputs (or keep it while
+//! the HOME Menu or an applet is shown) on its own. This module keeps a single UTF-8 text buffer in the application's memory,
+//! which isn't affected by applets and the HOME Menu since they only suspend the application.
+//!
+//! The [Software Keyboard](crate::applets::swkbd) can start with the clipboard's contents
+//! with [`SoftwareKeyboard::paste_initial_text()`](crate::applets::swkbd::SoftwareKeyboard::paste_initial_text).
+//!
+//! # Example
+//!
+//! ```
+//! # let _runner = test_runner::GdbRunner::default();
+//! use ctru::services::apt::clipboard;
+//!
+//! clipboard::copy("Hello!");
+//!
+//! assert_eq!(clipboard::paste(), "Hello!");
+//! ```
+#![doc(alias = "copy")]
+#![doc(alias = "paste")]
+
+use std::sync::Mutex;
+
+/// Maximum length (in bytes) of the clipboard's contents.
+pub const MAX_LEN: usize = 0x1000;
+
+static CLIPBOARD: Mutex<String> = Mutex::new(String::new());
+
+/// Replace the clipboard's contents with `text`, truncated to [`MAX_LEN`] bytes on a character boundary.
+pub fn copy(text: &str) {
+    let mut end = text.len().min(MAX_LEN);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let mut clipboard = CLIPBOARD.lock().unwrap();
+    clipboard.clear();
+    clipboard.push_str(&text[..end]);
+}
+
+/// Returns a copy of the clipboard's contents.
+pub fn paste() -> String {
+    CLIPBOARD.lock().unwrap().clone()
+}
+
+/// Returns `true` if the clipboard is empty.
+pub fn is_empty() -> bool {
+    CLIPBOARD.lock().unwrap().is_empty()
+}
+
+/// Empty the clipboard.
+pub fn clear() {
+    CLIPBOARD.lock().unwrap().clear();
+}
+
+/// Remove the clipboard's contents and return them.
+pub fn take() -> String {
+    std::mem::take(&mut *CLIPBOARD.lock().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncation() {
+        // 'é' takes 2 bytes, so after the leading 'a' the limit falls in the middle of one.
+        let text = String::from("a") + &"é".repeat(MAX_LEN / 2);
+        copy(&text);
+
+        let pasted = take();
+        assert_eq!(pasted.len(), MAX_LEN - 1);
+        assert!(is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+//! System events delivered to the main loop.
+//!
+//! [`Apt::main_loop()`] handles the HOME button, Sleep mode and orders to close on its own: the application only notices them
+//! through the loop blocking (while the HOME Menu is shown) or ending (when the system asks it to close).
+//! [`AptEvents`] runs the same main loop, but reports these notifications as [`AptEvent`]s
+//! and lets the application take over the default behaviour of the HOME button and of orders to close,
+//! e.g. to ask for confirmation or to save its progress first.
+#![doc(alias = "aptHook")]
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::{Apt, AptHook};
+
+/// Notification from the system, returned by [`AptEvents::poll_event()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AptEvent {
+    /// The HOME button was pressed.
+    ///
+    /// Unless the HOME button is [intercepted](AptEvents::intercept_home), the HOME Menu was shown right after.
+    HomePressed,
+    /// The power button was pressed, and the system showed its power menu.
+    ///
+    /// # Notes
+    ///
+    /// `libctru` doesn't report power button presses: this event is inferred from [`AptEvents::main_loop()`] suspending
+    /// the application without the HOME button being pressed, which only the power menu does.
+    /// Suspensions caused by the application itself (such as [applets](crate::applets)) happen outside the main loop and are never reported as such.
+    PowerPressed,
+    /// The application lost control of the screens and the GPU (e.g. to the HOME Menu or an applet).
+    Suspended,
+    /// The application got back control of the screens and the GPU.
+    Restored,
+    /// The console went to Sleep mode.
+    Sleeping,
+    /// The console woke up from Sleep mode.
+    WokeUp,
+    /// The system asked the application to close (e.g. the console is being turned off or the user closed it from the HOME Menu).
+    ///
+    /// Unless orders to close are [intercepted](AptEvents::intercept_close), [`AptEvents::main_loop()`] returns `false` at the same time.
+    CloseRequested,
+}
+
+/// APT hook recording the application's transitions.
+struct EventHook {
+    hooks: Arc<Mutex<Vec<ctru_sys::APT_HookType>>>,
+    _hook: AptHook,
+}
+
+impl EventHook {
+    fn register() -> Self {
+        let hooks = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&hooks);
+
+        Self {
+            hooks,
+            _hook: AptHook::register(move |hook| {
+                recorded
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(hook)
+            }),
+        }
+    }
+
+    fn take(&self) -> Vec<ctru_sys::APT_HookType> {
+        std::mem::take(&mut self.hooks.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Main loop reporting system notifications as [`AptEvent`]s.
+///
+/// By default, the HOME button and orders to close behave as with [`Apt::main_loop()`], and the events only report them.
+/// Applications can instead handle them on their own with [`AptEvents::intercept_home()`] and [`AptEvents::intercept_close()`].
+///
+/// # Notes
+///
+/// Pressing the power button always shows the system's power menu, which can't be prevented.
+/// [`AptEvent::PowerPressed`] is reported once the application regains control, like the other events caused by a suspension.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::prelude::*;
+/// use ctru::services::apt::events::{AptEvent, AptEvents};
+///
+/// let apt = Apt::new()?;
+/// let mut events = AptEvents::new(&apt);
+///
+/// // Ask before leaving to the HOME Menu, and save before closing.
+/// events.intercept_home(true);
+/// events.intercept_close(true);
+///
+/// while events.main_loop() {
+///     while let Some(event) = events.poll_event() {
+///         match event {
+///             AptEvent::HomePressed => {
+///                 // Show a confirmation dialog, then leave if confirmed.
+///                 events.jump_to_home_menu();
+///             }
+///             AptEvent::CloseRequested => {
+///                 // Save the progress, then let the application close.
+///                 events.close();
+///             }
+///             _ => {}
+///         }
+///     }
+/// #   break;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct AptEvents<'apt> {
+    apt: &'apt Apt,
+    hook: EventHook,
+    events: VecDeque<AptEvent>,
+    intercept_close: bool,
+    close_requested: bool,
+    closed: bool,
+    // The HOME button setting is global to `libctru`, and hooks run on the thread of the main loop.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'apt> AptEvents<'apt> {
+    /// Start reporting the system notifications received by the main loop.
+    pub fn new(apt: &'apt Apt) -> Self {
+        Self {
+            apt,
+            hook: EventHook::register(),
+            events: VecDeque::new(),
+            intercept_close: false,
+            close_requested: false,
+            closed: false,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Choose whether the HOME button is handled by the application.
+    ///
+    /// When intercepted, pressing the HOME button only reports [`AptEvent::HomePressed`], and the system shows a message
+    /// telling the user the HOME Menu can't be opened. Call [`AptEvents::jump_to_home_menu()`] to open it anyway.
+    #[doc(alias = "aptSetHomeAllowed")]
+    pub fn intercept_home(&mut self, intercept: bool) {
+        unsafe { ctru_sys::aptSetHomeAllowed(!intercept) };
+    }
+
+    /// Returns whether the HOME button is handled by the application.
+    #[doc(alias = "aptIsHomeAllowed")]
+    pub fn is_home_intercepted(&self) -> bool {
+        unsafe { !ctru_sys::aptIsHomeAllowed() }
+    }
+
+    /// Choose whether orders to close are handled by the application.
+    ///
+    /// When intercepted, [`AptEvents::main_loop()`] keeps returning `true` after reporting [`AptEvent::CloseRequested`],
+    /// until [`AptEvents::close()`] is called.
+    ///
+    /// # Notes
+    ///
+    /// Orders to close can't be refused, only delayed: the application should close as soon as it saved its state,
+    /// without waiting for user input.
+    pub fn intercept_close(&mut self, intercept: bool) {
+        self.intercept_close = intercept;
+    }
+
+    /// Run the main loop of the application (see [`Apt::main_loop()`]), recording the notifications received meanwhile.
+    ///
+    /// Returns `false` once the application should close.
+    pub fn main_loop(&mut self) -> bool {
+        if self.close_requested {
+            // `libctru` considers the application closed already, its main loop can't run again.
+            return !self.closed;
+        }
+
+        self.record_hooks(false);
+
+        let home_pressed =
+            unsafe { ctru_sys::aptCheckHomePressRejected() || ctru_sys::aptShouldJumpToHome() };
+        if home_pressed {
+            self.events.push_back(AptEvent::HomePressed);
+        }
+
+        let running = self.apt.main_loop();
+
+        // Without a HOME button press, the main loop only suspends the application for the power menu.
+        self.record_hooks(!home_pressed);
+
+        if running {
+            return true;
+        }
+
+        self.close_requested = true;
+        self.closed = !self.intercept_close;
+
+        if unsafe { ctru_sys::aptShouldClose() } {
+            self.events.push_back(AptEvent::CloseRequested);
+        } else {
+            // The application left for another reason (e.g. it is being replaced by another title): it can't be delayed.
+            self.closed = true;
+        }
+
+        !self.closed
+    }
+
+    /// Returns the next notification received by the main loop, or [`None`] if all of them were handled.
+    pub fn poll_event(&mut self) -> Option<AptEvent> {
+        self.events.pop_front()
+    }
+
+    /// Open the HOME Menu, as the HOME button does when it isn't [intercepted](AptEvents::intercept_home).
+    ///
+    /// This function blocks until the application is back in the foreground.
+    #[doc(alias = "aptJumpToHomeMenu")]
+    pub fn jump_to_home_menu(&mut self) {
+        self.record_hooks(false);
+        unsafe { ctru_sys::aptJumpToHomeMenu() };
+        self.record_hooks(false);
+    }
+
+    /// Let the application close after an [intercepted](AptEvents::intercept_close) [`AptEvent::CloseRequested`]:
+    /// the next call to [`AptEvents::main_loop()`] returns `false`.
+    ///
+    /// This function does nothing if the system didn't ask the application to close.
+    pub fn close(&mut self) {
+        if self.close_requested {
+            self.closed = true;
+        }
+    }
+
+    fn record_hooks(&mut self, suspend_is_power: bool) {
+        for hook in self.hook.take() {
+            let event = match hook {
+                ctru_sys::APTHOOK_ONSUSPEND => {
+                    if suspend_is_power {
+                        self.events.push_back(AptEvent::PowerPressed);
+                    }
+                    AptEvent::Suspended
+                }
+                ctru_sys::APTHOOK_ONRESTORE => AptEvent::Restored,
+                ctru_sys::APTHOOK_ONSLEEP => AptEvent::Sleeping,
+                ctru_sys::APTHOOK_ONWAKEUP => AptEvent::WokeUp,
+                _ => continue,
+            };
+
+            self.events.push_back(event);
+        }
+    }
+}
+
+impl Drop for AptEvents<'_> {
+    fn drop(&mut self) {
+        unsafe { ctru_sys::aptSetHomeAllowed(true) };
+    }
+}
@@ -0,0 +1,57 @@
+//! Registration of APT hooks.
+//!
+//! `libctru` calls its registered hooks on the thread running the main loop, whenever the application is suspended, restored,
+//! put to sleep or woken up. [`AptHook`] registers a Rust closure as such a hook, for every part of the crate following these transitions.
+
+use std::panic::{self, AssertUnwindSafe};
+
+type Callback = Box<dyn Fn(ctru_sys::APT_HookType) + Send + Sync>;
+
+struct HookData {
+    cookie: ctru_sys::aptHookCookie,
+    callback: Callback,
+}
+
+/// Registration of a closure in the APT hook list, removed when dropped.
+pub(crate) struct AptHook(Box<HookData>);
+
+// SAFETY: the cookie is only used by `libctru` while the hook is registered, which is tied to the lifetime of the box.
+unsafe impl Send for AptHook {}
+
+impl AptHook {
+    /// Register `callback` to be called with the type of every transition of the application.
+    pub(crate) fn register(
+        callback: impl Fn(ctru_sys::APT_HookType) + Send + Sync + 'static,
+    ) -> Self {
+        let mut data = Box::new(HookData {
+            cookie: ctru_sys::aptHookCookie::default(),
+            callback: Box::new(callback),
+        });
+        let param: *mut HookData = data.as_mut();
+
+        unsafe { ctru_sys::aptHook(&mut data.cookie, Some(dispatch), param.cast()) };
+
+        Self(data)
+    }
+
+    /// Register `callback` for the rest of the application's life.
+    pub(crate) fn register_forever(
+        callback: impl Fn(ctru_sys::APT_HookType) + Send + Sync + 'static,
+    ) {
+        // Leaking the registration keeps the hook (and its cookie) alive without ever unregistering it.
+        std::mem::forget(Self::register(callback));
+    }
+}
+
+impl Drop for AptHook {
+    fn drop(&mut self) {
+        unsafe { ctru_sys::aptUnhook(&mut self.0.cookie) };
+    }
+}
+
+unsafe extern "C" fn dispatch(hook: ctru_sys::APT_HookType, param: *mut libc::c_void) {
+    let data = &*param.cast::<HookData>();
+
+    // Panics can't unwind into `libctru`: a panicking callback only misses this transition.
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| (data.callback)(hook)));
+}
@@ -0,0 +1,238 @@
+//! Applet service.
+//!
+//! The APT service handles integration with other applications,
+//! including high-level OS features such as Sleep mode, the Home Menu and application switching.
+//!
+//! It also handles running applets, small programs made available by the OS to streamline specific functionality.
+//! Those are implemented in the [`applets`](crate::applets) module.
+//! The web browser and the Nintendo eShop can also be opened from an application, with [`open_browser()`] and [`open_eshop()`].
+
+pub mod clipboard;
+pub mod events;
+mod hook;
+
+pub(crate) use hook::AptHook;
+
+use crate::error::ResultCode;
+use crate::services::cfgu::Region;
+use crate::shutdown::{self, Registration, Stage};
+use crate::Error;
+
+use std::ffi::CString;
+
+/// Largest parameter accepted by [`ctru_sys::APT_DoApplicationJump()`].
+const MAX_JUMP_PARAMETER_SIZE: usize = 0x300;
+
+/// Largest URL accepted by the web browser, including the NUL terminator.
+const MAX_URL_SIZE: usize = 0x400;
+
+/// Handle to the Applet service.
+pub struct Apt(Registration);
+
+impl Apt {
+    /// Initialize a new service handle.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::apt::Apt;
+    ///
+    /// let apt = Apt::new()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "aptInit")]
+    pub fn new() -> crate::Result<Apt> {
+        unsafe {
+            ResultCode(ctru_sys::aptInit())?;
+            Ok(Apt(shutdown::register(Stage::System, "APT:U", || unsafe {
+                ctru_sys::aptExit();
+            })))
+        }
+    }
+
+    /// Returns `true` if the application is running in the foreground as normal.
+    ///
+    /// # Notes
+    ///
+    /// This function is called as such since it automatically handles all checks for Home Menu switching, Sleep mode and other events that could take away control from the application.
+    /// For this reason, its main use is as the condition of a while loop that controls the main logic for your program.
+    ///
+    /// Use [`AptEvents`](events::AptEvents) instead to be notified of these events, or to handle the HOME button
+    /// and orders to close on your own.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// use std::error::Error;
+    /// use ctru::services::apt::Apt;
+    ///
+    /// // In a simple `main` function, the structure should be the following.
+    /// fn main() -> Result<(), Box<dyn Error>> {
+    ///
+    /// let apt = Apt::new()?;
+    ///
+    /// while apt.main_loop() {
+    ///     // Main program logic should be written here.
+    /// }
+    ///
+    /// // Optional clean-ups after running the application should be written after the main loop.
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "aptMainLoop")]
+    pub fn main_loop(&self) -> bool {
+        let running = unsafe { ctru_sys::aptMainLoop() };
+
+        if running {
+            crate::power::drive_main_loop();
+        }
+
+        running
+    }
+
+    /// Set (in percentage) the amount of time to lend to the application thread spawned on the syscore (core #1).
+    ///
+    /// # Notes
+    ///
+    /// It is necessary to set a time limit before spawning threads on the syscore.
+    /// The percentage value must be withing 5% and 89%, though it is suggested to use lower values (around 30-45%) to avoid slowing down the OS processes.
+    #[doc(alias = "APT_SetAppCpuTimeLimit")]
+    pub fn set_app_cpu_time_limit(&mut self, percent: u32) -> crate::Result<()> {
+        unsafe {
+            ResultCode(ctru_sys::APT_SetAppCpuTimeLimit(percent))?;
+            Ok(())
+        }
+    }
+}
+
+/// Open the Internet Browser on `url`, e.g. to show an application's online documentation.
+///
+/// This function blocks until the user closes the browser.
+///
+/// # Errors
+///
+/// This function will return an error if `url` contains NUL bytes, if it is longer than the browser accepts (1023 bytes),
+/// or if the browser couldn't be launched.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::apt::{self, Apt};
+///
+/// let apt = Apt::new()?;
+///
+/// apt::open_browser(&apt, "https://github.com/rust3ds/ctru-rs")?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "aptLaunchSystemApplet")]
+#[doc(alias = "APPID_WEB")]
+pub fn open_browser(_apt: &Apt, url: &str) -> crate::Result<()> {
+    let url =
+        CString::new(url).map_err(|_| Error::Other(String::from("URL contains NUL bytes")))?;
+    let url = url.as_bytes_with_nul();
+
+    if url.len() > MAX_URL_SIZE {
+        return Err(Error::Other(format!(
+            "URL is longer than {} bytes",
+            MAX_URL_SIZE - 1
+        )));
+    }
+
+    ResultCode(unsafe {
+        ctru_sys::aptLaunchSystemApplet(
+            ctru_sys::APPID_WEB,
+            url.as_ptr().cast_mut().cast(),
+            url.len(),
+            0,
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Close the application and open the Nintendo eShop on the page of the title `title_id`, e.g. to link to a game's DLC or demo.
+///
+/// `region` selects the eShop to open, and should be the console's region (see [`Cfgu::region()`](crate::services::cfgu::Cfgu::region)).
+///
+/// # Notes
+///
+/// Unlike the web browser, the eShop can't return to the application: once this function succeeds,
+/// [`Apt::main_loop()`] returns `false` and the application should exit as soon as possible, after which the eShop is started.
+///
+/// # Errors
+///
+/// This function will return an error if there is no eShop for `region`, or if the jump couldn't be prepared.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::apt::{self, Apt};
+/// use ctru::services::cfgu::Cfgu;
+///
+/// let apt = Apt::new()?;
+/// let region = Cfgu::new()?.region()?;
+///
+/// apt::open_eshop(&apt, region, 0x0004_0000_0003_0800)?;
+///
+/// while apt.main_loop() {}
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "APT_PrepareToDoApplicationJump")]
+#[doc(alias = "APT_DoApplicationJump")]
+pub fn open_eshop(_apt: &Apt, region: Region, title_id: u64) -> crate::Result<()> {
+    let eshop_id = eshop_title_id(region)
+        .ok_or_else(|| Error::Other(format!("no Nintendo eShop for region {region:?}")))?;
+
+    // The eShop reads the title to show from the jump parameter.
+    let mut parameter = [0u8; MAX_JUMP_PARAMETER_SIZE];
+    let link = format!("tiuid={title_id:016X}");
+    parameter[..link.len()].copy_from_slice(link.as_bytes());
+
+    // Only system titles can sign jump parameters: applications pass an empty HMAC.
+    let hmac = [0u8; 0x20];
+
+    unsafe {
+        ResultCode(ctru_sys::APT_PrepareToDoApplicationJump(
+            0,
+            eshop_id,
+            ctru_sys::MEDIATYPE_NAND as u8,
+        ))?;
+        ResultCode(ctru_sys::APT_DoApplicationJump(
+            parameter.as_ptr().cast(),
+            parameter.len(),
+            hmac.as_ptr().cast(),
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Returns the title ID of the Nintendo eShop for `region`.
+fn eshop_title_id(region: Region) -> Option<u64> {
+    match region {
+        Region::Japan => Some(0x0004_0010_0002_0900),
+        Region::USA => Some(0x0004_0010_0002_1900),
+        Region::Europe | Region::Australia => Some(0x0004_0010_0002_2900),
+        Region::Korea => Some(0x0004_0010_0002_7900),
+        Region::Taiwan => Some(0x0004_0010_0002_8900),
+        Region::China => None,
+    }
+}
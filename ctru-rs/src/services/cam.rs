@@ -2,6 +2,7 @@
 //!
 //! The CAM service provides access to the built-in cameras. [`Camera`]s can return images
 //! in the form of byte vectors which can be displayed to the screen or used in other ways.
+//! Pictures can be taken one at a time with [`Camera::take_picture()`], or streamed continuously with [`Camera::start_capture()`].
 #![doc(alias = "camera")]
 
 use crate::error::{Error, ResultCode};
@@ -10,6 +11,7 @@ use crate::services::ServiceReference;
 use ctru_sys::Handle;
 use private::Configuration;
 
+use std::marker::PhantomData;
 use std::sync::Mutex;
 use std::time::Duration;
 
@@ -1095,6 +1097,305 @@ pub trait Camera: private::ConfigurableCamera {
 
         Ok(())
     }
+
+    /// Start capturing frames continuously, e.g. to display a live camera feed.
+    ///
+    /// Unlike [`Camera::take_picture()`], the camera keeps running between frames: each call to [`Capture::read_frame()`]
+    /// receives the next frame into a buffer, without waiting for the camera to start again.
+    /// The camera is stopped when the returned [`Capture`] is dropped.
+    ///
+    /// # Notes
+    ///
+    /// The camera's configuration can't be changed while capturing, since the [`Capture`] borrows it.
+    /// Query [`Capture::frame_size()`] for the size of the buffers to provide.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::cam::{Cam, Camera, OutputFormat, ViewSize};
+    /// use std::time::Duration;
+    /// let mut cam = Cam::new()?;
+    ///
+    /// let camera = &mut cam.outer_right_cam;
+    /// camera.set_view_size(ViewSize::TopLCD)?;
+    /// camera.set_output_format(OutputFormat::Rgb565)?;
+    ///
+    /// let mut capture = camera.start_capture()?;
+    /// let mut buffer = vec![0; capture.frame_size()];
+    ///
+    /// for _ in 0..10 {
+    ///     capture.read_frame(&mut buffer, Duration::from_millis(300))?;
+    ///
+    ///     // Display the frame...
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "CAMU_StartCapture")]
+    fn start_capture(&mut self) -> crate::Result<Capture<'_>> {
+        let final_view = self.final_view_size();
+
+        let transfer_unit = unsafe {
+            let mut transfer_unit = 0;
+
+            ResultCode(ctru_sys::CAMU_GetMaxBytes(
+                &mut transfer_unit,
+                final_view.0,
+                final_view.1,
+            ))?;
+
+            transfer_unit
+        };
+
+        unsafe {
+            ResultCode(ctru_sys::CAMU_SetTransferBytes(
+                self.port_as_raw(),
+                transfer_unit,
+                final_view.0,
+                final_view.1,
+            ))?;
+        };
+
+        // From now on, dropping the capture cleans up the camera state if anything fails.
+        let mut capture = Capture {
+            port: self.port_as_raw(),
+            frame_size: self.final_byte_length(),
+            transfer_unit,
+            running: false,
+            _camera: PhantomData,
+        };
+
+        unsafe {
+            ResultCode(ctru_sys::CAMU_Activate(self.camera_as_raw()))?;
+
+            if capture.port == ctru_sys::PORT_BOTH {
+                ResultCode(ctru_sys::CAMU_SynchronizeVsyncTiming(
+                    ctru_sys::SELECT_OUT1,
+                    ctru_sys::SELECT_OUT2,
+                ))?;
+            }
+        };
+
+        capture.start()?;
+
+        Ok(capture)
+    }
+}
+
+/// Continuous capture from a [`Camera`], started with [`Camera::start_capture()`].
+///
+/// Frames can be received either by blocking until they are complete ([`Capture::read_frame()`]),
+/// or by starting their transfer and waiting for its completion events along with other work ([`Capture::receive()`]).
+pub struct Capture<'cam> {
+    port: u32,
+    frame_size: usize,
+    transfer_unit: u32,
+    running: bool,
+    _camera: PhantomData<&'cam mut ()>,
+}
+
+/// Frame being received by a [`Capture`], into the buffer given to [`Capture::receive()`].
+///
+/// Dropping the frame before it is complete stops the capture (to stop the transfer into the buffer),
+/// which starts again with the next frame received.
+pub struct PendingFrame<'capture> {
+    port: u32,
+    running: &'capture mut bool,
+    events: [Handle; 2],
+    ports: &'static [u32],
+    complete: bool,
+    _buffer: PhantomData<&'capture mut [u8]>,
+}
+
+impl Capture<'_> {
+    /// Returns the amount of bytes of each frame, which is the minimum size of the buffers receiving them.
+    ///
+    /// See [`Camera::final_byte_length()`] for more details.
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    /// Start receiving the next frame into `buffer`, and return immediately.
+    ///
+    /// The frame is complete once [`PendingFrame::wait()`] returns, or once all the [events](PendingFrame::events) of the frame are signalled
+    /// (e.g. when awaited with [`runtime::wait_handle()`](crate::runtime::wait_handle)). `buffer` stays borrowed until then.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `buffer` is smaller than [`Capture::frame_size()`],
+    /// or if the transfer couldn't be started.
+    ///
+    /// # Safety
+    ///
+    /// The returned [`PendingFrame`] must be dropped (or [waited for](PendingFrame::wait)) before `buffer` is used, moved or freed again.
+    /// The borrow of the buffer ends if the frame is leaked (e.g. with [`std::mem::forget()`]), while the camera keeps writing to it.
+    /// Use [`Capture::read_frame()`] to receive frames without this requirement.
+    #[doc(alias = "CAMU_SetReceiving")]
+    pub unsafe fn receive<'capture>(
+        &'capture mut self,
+        buffer: &'capture mut [u8],
+    ) -> crate::Result<PendingFrame<'capture>> {
+        if buffer.len() < self.frame_size {
+            return Err(Error::BufferTooShort {
+                provided: buffer.len(),
+                wanted: self.frame_size,
+            });
+        }
+
+        if !self.running {
+            self.start()?;
+        }
+
+        // Both outward cameras send their images through a port each, to each half of the buffer.
+        let ports: &'static [u32] = match self.port {
+            ctru_sys::PORT_BOTH => &[ctru_sys::PORT_CAM1, ctru_sys::PORT_CAM2],
+            ctru_sys::PORT_CAM2 => &[ctru_sys::PORT_CAM2],
+            _ => &[ctru_sys::PORT_CAM1],
+        };
+        let part_size = self.frame_size / ports.len();
+        let transfer_unit = self.transfer_unit;
+
+        let mut frame = PendingFrame {
+            port: self.port,
+            running: &mut self.running,
+            events: [0; 2],
+            ports: &[],
+            complete: false,
+            _buffer: PhantomData,
+        };
+
+        for (index, &port) in ports.iter().enumerate() {
+            unsafe {
+                ResultCode(ctru_sys::CAMU_SetReceiving(
+                    &mut frame.events[index],
+                    buffer[index * part_size..].as_mut_ptr().cast(),
+                    port,
+                    part_size as u32,
+                    transfer_unit.try_into().unwrap(),
+                ))?;
+            }
+
+            // Only the ports whose transfer started need to be waited for (and cleaned up).
+            frame.ports = &ports[..=index];
+        }
+
+        Ok(frame)
+    }
+
+    /// Receive the next frame into `buffer`, blocking until it is complete or until `timeout` elapses.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `buffer` is smaller than [`Capture::frame_size()`],
+    /// or if the frame couldn't be received in time (see [`Error::is_timeout()`]).
+    pub fn read_frame(&mut self, buffer: &mut [u8], timeout: Duration) -> crate::Result<()> {
+        // SAFETY: the frame is waited for (or dropped, stopping the capture) before the buffer is released.
+        unsafe { self.receive(buffer) }?.wait(timeout)
+    }
+
+    fn start(&mut self) -> crate::Result<()> {
+        unsafe {
+            ResultCode(ctru_sys::CAMU_ClearBuffer(self.port))?;
+            ResultCode(ctru_sys::CAMU_StartCapture(self.port))?;
+        }
+        self.running = true;
+
+        Ok(())
+    }
+}
+
+impl Drop for Capture<'_> {
+    fn drop(&mut self) {
+        stop_capture(self.port);
+
+        unsafe {
+            let _ = ctru_sys::CAMU_Activate(ctru_sys::SELECT_NONE);
+        }
+    }
+}
+
+impl PendingFrame<'_> {
+    /// Returns the events signalled when the transfer of each part of the frame completes (one per camera port).
+    ///
+    /// The events are owned by the frame, and are closed when it is dropped.
+    pub fn events(&self) -> &[Handle] {
+        &self.events[..self.ports.len()]
+    }
+
+    /// Returns `true` if the frame was fully received.
+    #[doc(alias = "CAMU_IsFinishedReceiving")]
+    pub fn is_complete(&mut self) -> crate::Result<bool> {
+        if !self.complete {
+            let mut finished = true;
+
+            for &port in self.ports {
+                let mut port_finished = false;
+                ResultCode(unsafe {
+                    ctru_sys::CAMU_IsFinishedReceiving(&mut port_finished, port)
+                })?;
+                finished &= port_finished;
+            }
+
+            self.complete = finished;
+        }
+
+        Ok(self.complete)
+    }
+
+    /// Block until the frame is fully received, or until `timeout` elapses.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the frame wasn't received in time (see [`Error::is_timeout()`]).
+    /// The capture is stopped in that case, and starts again with the next frame received.
+    #[doc(alias = "svcWaitSynchronizationN")]
+    pub fn wait(mut self, timeout: Duration) -> crate::Result<()> {
+        let events = self.events();
+        let mut index = 0;
+
+        // All the ports are waited for at once, so that the timeout applies to the whole frame.
+        ResultCode(unsafe {
+            ctru_sys::svcWaitSynchronizationN(
+                &mut index,
+                events.as_ptr(),
+                events.len() as i32,
+                true,
+                timeout.as_nanos().try_into().unwrap_or(i64::MAX),
+            )
+        })?;
+
+        self.complete = true;
+
+        Ok(())
+    }
+}
+
+impl Drop for PendingFrame<'_> {
+    fn drop(&mut self) {
+        // The buffer must not be written to after the frame releases it.
+        if !self.complete {
+            stop_capture(self.port);
+            *self.running = false;
+        }
+
+        for &event in self.events() {
+            let _ = unsafe { ctru_sys::svcCloseHandle(event) };
+        }
+    }
+}
+
+/// Stop capturing on `port`, discarding the data not received yet.
+fn stop_capture(port: u32) {
+    // Errors are ignored, since there is nothing left to do if stopping fails.
+    unsafe {
+        let _ = ctru_sys::CAMU_StopCapture(port);
+        let _ = ctru_sys::CAMU_ClearBuffer(port);
+    }
 }
 
 impl Trimming {
@@ -1153,6 +1454,7 @@ impl Cam {
     #[doc(alias = "camInit")]
     pub fn new() -> crate::Result<Cam> {
         let _service_handler = ServiceReference::new(
+            "cam:u",
             &CAM_ACTIVE,
             || {
                 ResultCode(unsafe { ctru_sys::camInit() })?;
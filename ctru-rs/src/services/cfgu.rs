@@ -9,6 +9,7 @@ use crate::error::ResultCode;
 #[doc(alias = "CFG_Region")]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Region {
     /// Japan.
     Japan = ctru_sys::CFG_REGION_JPN,
@@ -28,8 +29,9 @@ pub enum Region {
 
 /// Language set for the console's OS.
 #[doc(alias = "CFG_Language")]
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Language {
     /// Japanese.
     Japanese = ctru_sys::CFG_LANGUAGE_JP,
@@ -61,6 +63,7 @@ pub enum Language {
 #[doc(alias = "CFG_SystemModel")]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SystemModel {
     /// Old Nintendo 3DS.
     Old3DS = ctru_sys::CFG_MODEL_3DS,
@@ -0,0 +1,231 @@
+//! Friend service.
+//!
+//! Every Nintendo Network account is identified by a principal ID, which users share as a 12 digit friend code.
+//! The friend code embeds a checksum of the principal ID, so typos can be detected offline with [`FriendCode`],
+//! while [`Frd`] reads the console's own friend code and its friend list from the "frd:u" service.
+//!
+//! See also <https://www.3dbrew.org/wiki/Friend_Services>
+#![doc(alias = "friend")]
+
+use crate::error::ResultCode;
+use crate::Error;
+
+use sha1_smol::Sha1;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Largest value representable by a 12 digit friend code.
+const MAX_FRIEND_CODE: u64 = 999_999_999_999;
+
+/// Maximum amount of friends in a friend list.
+const MAX_FRIENDS: usize = 100;
+
+/// Handle to the "frd:u" service.
+pub struct Frd(());
+
+impl Frd {
+    /// Initialize a new service handle.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::frd::Frd;
+    ///
+    /// let frd = Frd::new()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "frdInit")]
+    pub fn new() -> crate::Result<Self> {
+        ResultCode(unsafe { ctru_sys::frdInit() })?;
+        Ok(Self(()))
+    }
+
+    /// Returns the friend code of the console's user.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::frd::Frd;
+    ///
+    /// let frd = Frd::new()?;
+    ///
+    /// println!("My friend code: {}", frd.my_friend_code()?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "FRD_GetMyFriendKey")]
+    pub fn my_friend_code(&self) -> crate::Result<FriendCode> {
+        let mut key = ctru_sys::FriendKey::default();
+        ResultCode(unsafe { ctru_sys::FRD_GetMyFriendKey(&mut key) })?;
+
+        Ok(FriendCode::from_principal_id(key.principalId))
+    }
+
+    /// Returns the friend codes of the user's friends, in the order of the friend list.
+    #[doc(alias = "FRD_GetFriendKeyList")]
+    pub fn friend_codes(&self) -> crate::Result<Vec<FriendCode>> {
+        let mut keys = [ctru_sys::FriendKey::default(); MAX_FRIENDS];
+        let mut count = 0;
+
+        ResultCode(unsafe {
+            ctru_sys::FRD_GetFriendKeyList(keys.as_mut_ptr(), &mut count, 0, MAX_FRIENDS as u32)
+        })?;
+
+        Ok(keys[..(count as usize).min(MAX_FRIENDS)]
+            .iter()
+            .map(|key| FriendCode::from_principal_id(key.principalId))
+            .collect())
+    }
+}
+
+impl Drop for Frd {
+    #[doc(alias = "frdExit")]
+    fn drop(&mut self) {
+        unsafe { ctru_sys::frdExit() };
+    }
+}
+
+/// A friend code, as shown in the HOME Menu's friend list.
+///
+/// The lower 32 bits of a friend code are the account's principal ID,
+/// while the upper bits hold a 7 bit checksum of it (the first byte of its SHA-1 hash, shifted right by one).
+///
+/// # Example
+///
+/// ```
+/// use ctru::services::frd::FriendCode;
+///
+/// let code = FriendCode::from_principal_id(0x1234_5678);
+/// assert!(code.is_valid());
+/// assert_eq!(code.principal_id(), 0x1234_5678);
+///
+/// // Friend codes can be parsed with or without dashes.
+/// let parsed: FriendCode = code.to_string().parse()?;
+/// assert_eq!(parsed, code);
+/// # Ok::<(), ctru::Error>(())
+/// ```
+#[doc(alias = "FriendKey")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FriendCode(u64);
+
+impl FriendCode {
+    /// Build the friend code of the given principal ID.
+    #[doc(alias = "FRD_PrincipalIdToFriendCode")]
+    pub fn from_principal_id(principal_id: u32) -> Self {
+        Self(u64::from(checksum(principal_id)) << 32 | u64::from(principal_id))
+    }
+
+    /// Wrap a raw friend code value, without validating it.
+    ///
+    /// Use [`FriendCode::is_valid()`] to check the value afterwards.
+    pub fn from_raw(raw: u64) -> Self {
+        Self(raw)
+    }
+
+    /// Returns the raw value of the friend code.
+    pub fn raw(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the principal ID stored in the friend code.
+    pub fn principal_id(self) -> u32 {
+        self.0 as u32
+    }
+
+    /// Returns `true` if the friend code's checksum matches its principal ID.
+    ///
+    /// A principal ID of 0 is never valid.
+    #[doc(alias = "FRD_IsValidFriendCode")]
+    pub fn is_valid(self) -> bool {
+        self.0 <= MAX_FRIEND_CODE
+            && self.principal_id() != 0
+            && self.0 >> 32 == u64::from(checksum(self.principal_id()))
+    }
+}
+
+impl fmt::Display for FriendCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = self.0 % (MAX_FRIEND_CODE + 1);
+
+        write!(
+            f,
+            "{:04}-{:04}-{:04}",
+            code / 100_000_000,
+            code / 10_000 % 10_000,
+            code % 10_000
+        )
+    }
+}
+
+impl FromStr for FriendCode {
+    type Err = Error;
+
+    /// Parse a friend code made of 12 digits, optionally separated by dashes or spaces.
+    ///
+    /// Parsing only checks the format: use [`FriendCode::is_valid()`] to verify the checksum.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut value = 0u64;
+        let mut digits = 0;
+
+        for c in s.trim().chars() {
+            match c {
+                '0'..='9' => {
+                    value = value * 10 + u64::from(c as u8 - b'0');
+                    digits += 1;
+
+                    if digits > 12 {
+                        break;
+                    }
+                }
+                '-' | ' ' => (),
+                _ => {
+                    return Err(Error::Other(format!(
+                        "invalid character {c:?} in friend code"
+                    )))
+                }
+            }
+        }
+
+        if digits != 12 {
+            return Err(Error::Other(String::from(
+                "friend codes must contain exactly 12 digits",
+            )));
+        }
+
+        Ok(Self(value))
+    }
+}
+
+/// Checksum of a principal ID, as stored in the upper bits of its friend code.
+fn checksum(principal_id: u32) -> u8 {
+    Sha1::from(principal_id.to_le_bytes()).digest().bytes()[0] >> 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn friend_code_round_trip() {
+        let code = FriendCode::from_principal_id(0xDEAD_BEEF);
+        assert!(code.is_valid());
+        assert_eq!(code.to_string().parse::<FriendCode>().unwrap(), code);
+
+        let typo = FriendCode::from_raw(code.raw() ^ (1 << 32));
+        assert!(!typo.is_valid());
+
+        assert!("1234-5678".parse::<FriendCode>().is_err());
+    }
+}
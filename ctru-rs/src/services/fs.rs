@@ -1,9 +1,35 @@
 //! FileSystem service.
 //!
-//! Currently, this module contains only datatypes to easily operate with unsafe [`ctru_sys`] code regarding the file-system functionality.
+//! This module contains datatypes to easily operate with unsafe [`ctru_sys`] code regarding the file-system functionality,
+//! as well as [`MountedArchive`], which makes the contents of system archives (such as extdata) accessible via [`std::fs`].
+//!
+//! The SD card can be removed while the application is running. [`SdWatcher`] notifies the application when that happens,
+//! and the filesystem operations of this crate return [`Error::MediaRemoved`] for operations which failed because of it,
+//! so that the user can be prompted to reinsert the card.
+//!
+//! All threads share a single session with the FS service by default, so filesystem requests from different threads
+//! (e.g. the [asset loader](crate::assets) and the main thread) wait for each other. Threads doing a lot of IO can open
+//! their own session with [`ThreadSession`].
+//!
+//! [`Watcher`] polls files for changes, e.g. to reload a configuration file when it's edited.
 #![doc(alias = "filesystem")]
 
+use crate::error::ResultCode;
+use crate::progress::{self, Progress};
+use crate::Error;
+
 use bitflags::bitflags;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 bitflags! {
     #[derive(Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
@@ -32,6 +58,7 @@ bitflags! {
 #[doc(alias = "FS_MediaType")]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MediaType {
     /// Internal NAND memory.
     Nand = ctru_sys::MEDIATYPE_NAND,
@@ -111,6 +138,1014 @@ pub enum ArchiveID {
     DemoSavedata = ctru_sys::ARCHIVE_DEMO_SAVEDATA,
 }
 
+/// Path within an archive (or the lowpath identifying an archive), encoded as expected by the FS service.
+///
+/// Each [`PathType`] has its own encoding: ASCII paths are NUL-terminated bytes, UTF-16 paths are NUL-terminated
+/// little-endian code units, and empty paths still consist of a single NUL byte.
+/// The constructors of this type take care of these details, which are easy to get wrong when building [`ctru_sys::FS_Path`]s by hand.
+///
+/// # Example
+///
+/// ```
+/// use ctru::services::fs::{ArchivePath, PathType};
+/// use std::path::Path;
+///
+/// let path = ArchivePath::try_from(Path::new("sdmc:/3ds/app.3dsx"))?;
+/// assert_eq!(path, ArchivePath::utf16("/3ds/app.3dsx")?);
+/// assert_eq!(path.path_type(), PathType::UTF16);
+///
+/// // Extdata archives are identified by binary lowpaths.
+/// let lowpath = ArchivePath::binary_words(&[1, 0x0000_1234, 0]);
+/// assert_eq!(lowpath.as_bytes().len(), 12);
+/// # Ok::<(), ctru::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchivePath {
+    path_type: PathType,
+    data: Cow<'static, [u8]>,
+}
+
+impl ArchivePath {
+    /// Empty path, used to open archives which don't need a lowpath (such as the SD card).
+    pub const EMPTY: Self = Self {
+        path_type: PathType::Empty,
+        data: Cow::Borrowed(&[0]),
+    };
+
+    /// Create a binary path from raw bytes, whose meaning depends on the archive.
+    pub fn binary(data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            path_type: PathType::Binary,
+            data: Cow::Owned(data.into()),
+        }
+    }
+
+    /// Create a binary path from 32-bit words, such as the media type and IDs making up most archive lowpaths.
+    pub fn binary_words(words: &[u32]) -> Self {
+        Self::binary(
+            words
+                .iter()
+                .copied()
+                .flat_map(u32::to_le_bytes)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Create a binary path from bytes known at compile time.
+    pub const fn binary_static(data: &'static [u8]) -> Self {
+        Self {
+            path_type: PathType::Binary,
+            data: Cow::Borrowed(data),
+        }
+    }
+
+    /// Create an ASCII path.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `path` contains non-ASCII characters or NUL bytes.
+    pub fn ascii(path: &str) -> crate::Result<Self> {
+        if !path.is_ascii() {
+            return Err(Error::Other(format!(
+                "path `{path}` contains non-ASCII characters"
+            )));
+        }
+
+        let path = CString::new(path)
+            .map_err(|_| Error::Other(String::from("path contains NUL bytes")))?;
+
+        Ok(Self {
+            path_type: PathType::ASCII,
+            data: Cow::Owned(path.into_bytes_with_nul()),
+        })
+    }
+
+    /// Create an ASCII path known at compile time, e.g. `ArchivePath::ascii_static(c"/data.bin")`.
+    ///
+    /// # Notes
+    ///
+    /// Unlike [`ArchivePath::ascii()`], this function can't check that the path only contains ASCII characters.
+    pub const fn ascii_static(path: &'static CStr) -> Self {
+        Self {
+            path_type: PathType::ASCII,
+            data: Cow::Borrowed(path.to_bytes_with_nul()),
+        }
+    }
+
+    /// Create a UTF-16 path, the encoding used by most archives (including the SD card) for file paths.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `path` contains NUL characters.
+    pub fn utf16(path: &str) -> crate::Result<Self> {
+        if path.contains('\0') {
+            return Err(Error::Other(String::from("path contains NUL characters")));
+        }
+
+        let data = path
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .flat_map(u16::to_le_bytes)
+            .collect();
+
+        Ok(Self {
+            path_type: PathType::UTF16,
+            data: Cow::Owned(data),
+        })
+    }
+
+    /// Returns the kind of the path.
+    pub fn path_type(&self) -> PathType {
+        self.path_type
+    }
+
+    /// Returns the encoded path, including its NUL terminator (if any).
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the raw `libctru` path, which borrows the data of this path.
+    #[doc(alias = "fsMakePath")]
+    pub fn as_raw(&self) -> ctru_sys::FS_Path {
+        ctru_sys::FS_Path {
+            type_: self.path_type.into(),
+            size: self.data.len() as u32,
+            data: self.data.as_ptr().cast(),
+        }
+    }
+}
+
+/// Converts a path as used with [`std::fs`] into a UTF-16 path within its archive,
+/// without the device prefix (e.g. `sdmc:/3ds/app.3dsx` becomes `/3ds/app.3dsx`).
+impl TryFrom<&Path> for ArchivePath {
+    type Error = Error;
+
+    fn try_from(path: &Path) -> crate::Result<Self> {
+        let path = path
+            .to_str()
+            .ok_or_else(|| Error::Other(String::from("path is not valid UTF-8")))?;
+
+        // Device prefixes can't contain slashes.
+        let path = match path.split_once(':') {
+            Some((device, rest)) if !device.contains('/') => rest,
+            _ => path,
+        };
+
+        if path.starts_with('/') {
+            Self::utf16(path)
+        } else {
+            Self::utf16(&format!("/{path}"))
+        }
+    }
+}
+
 from_impl!(MediaType, ctru_sys::FS_MediaType);
 from_impl!(PathType, ctru_sys::FS_PathType);
 from_impl!(ArchiveID, ctru_sys::FS_ArchiveID);
+
+/// Change in the SD card's presence, reported by [`SdWatcher`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SdEvent {
+    /// The SD card was removed.
+    Removed,
+    /// The SD card was inserted.
+    Inserted,
+}
+
+/// Background watcher calling a function whenever the SD card is removed or inserted.
+///
+/// The SD card's state is polled from a separate thread, which also runs the callback.
+/// The watcher stops when dropped.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// use ctru::services::fs::{SdEvent, SdWatcher};
+///
+/// let removed = Arc::new(AtomicBool::new(false));
+///
+/// let flag = removed.clone();
+/// let _watcher = SdWatcher::new(move |event| {
+///     flag.store(event == SdEvent::Removed, Ordering::Relaxed);
+/// })?;
+///
+/// // In the main loop...
+/// if removed.load(Ordering::Relaxed) {
+///     println!("Please reinsert the SD card.");
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct SdWatcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SdWatcher {
+    /// Default interval between two checks of the SD card's state.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Start watching the SD card, checking its state every [`SdWatcher::DEFAULT_INTERVAL`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the watcher thread couldn't be spawned.
+    pub fn new(callback: impl FnMut(SdEvent) + Send + 'static) -> crate::Result<Self> {
+        Self::with_interval(Self::DEFAULT_INTERVAL, callback)
+    }
+
+    /// Start watching the SD card, checking its state every `interval`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the watcher thread couldn't be spawned.
+    pub fn with_interval(
+        interval: Duration,
+        mut callback: impl FnMut(SdEvent) + Send + 'static,
+    ) -> crate::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut inserted = is_sd_inserted().unwrap_or(true);
+
+        let thread = thread::Builder::new()
+            .name(String::from("sd-watcher"))
+            .stack_size(0x2000)
+            .spawn({
+                let stop = stop.clone();
+
+                move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        thread::park_timeout(interval);
+
+                        let Ok(now_inserted) = is_sd_inserted() else {
+                            continue;
+                        };
+
+                        if now_inserted != inserted {
+                            inserted = now_inserted;
+                            callback(if inserted {
+                                SdEvent::Inserted
+                            } else {
+                                SdEvent::Removed
+                            });
+                        }
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for SdWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+thread_local! {
+    /// Whether the current thread uses a [`ThreadSession`].
+    static HAS_THREAD_SESSION: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Session with the FS service dedicated to the current thread.
+///
+/// While it is alive, the filesystem operations of the thread which opened it (through [`std::fs`] or this crate)
+/// go through their own session instead of the one shared by the whole application, so they don't wait for the requests
+/// of the other threads. Operations on files which are already open are unaffected, since each open file has its own session.
+///
+/// # Notes
+///
+/// The FS service only accepts a limited number of sessions: only open one for threads doing a lot of IO.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::fs::ThreadSession;
+///
+/// let loader = std::thread::spawn(|| -> ctru::Result<Vec<u8>> {
+///     let _session = ThreadSession::open()?;
+///
+///     Ok(std::fs::read("romfs:/level1.bin")?)
+/// });
+/// #
+/// # let _ = loader.join();
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "fsUseSession")]
+pub struct ThreadSession {
+    handle: ctru_sys::Handle,
+    // The session is bound to the thread which opened it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ThreadSession {
+    /// Open a new session with the FS service, used by the current thread until the returned value is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the current thread already has its own session,
+    /// or if the FS service refused a new session.
+    #[doc(alias = "fsUseSession", alias = "FSUSER_Initialize")]
+    pub fn open() -> crate::Result<Self> {
+        if HAS_THREAD_SESSION.get() {
+            return Err(Error::Other(String::from(
+                "the current thread already has its own FS session",
+            )));
+        }
+
+        let mut handle = ctru_sys::Handle::default();
+        let service_name = CString::new("fs:USER").unwrap();
+        ResultCode(unsafe { ctru_sys::srvGetServiceHandle(&mut handle, service_name.as_ptr()) })?;
+
+        if let Err(e) = into_result(ResultCode(unsafe { ctru_sys::FSUSER_Initialize(handle) })) {
+            let _ = unsafe { ctru_sys::svcCloseHandle(handle) };
+            return Err(e);
+        }
+
+        unsafe { ctru_sys::fsUseSession(handle) };
+        HAS_THREAD_SESSION.set(true);
+
+        Ok(Self {
+            handle,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Returns the handle of the session.
+    pub fn handle(&self) -> ctru_sys::Handle {
+        self.handle
+    }
+}
+
+impl Drop for ThreadSession {
+    #[doc(alias = "fsEndUseSession")]
+    fn drop(&mut self) {
+        unsafe {
+            ctru_sys::fsEndUseSession();
+            let _ = ctru_sys::svcCloseHandle(self.handle);
+        }
+
+        HAS_THREAD_SESSION.set(false);
+    }
+}
+
+/// Kind of change of a file, reported by [`Watcher`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileEventKind {
+    /// The file didn't exist, and was created.
+    Created,
+    /// The size or the modification time of the file changed.
+    Modified,
+    /// The file was removed.
+    Removed,
+}
+
+/// Change of a watched file, reported by [`Watcher`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileEvent {
+    /// Path of the file, as passed to [`Watcher::watch()`].
+    pub path: PathBuf,
+    /// What changed.
+    pub kind: FileEventKind,
+}
+
+/// Largest file whose contents are hashed to detect changes, when its archive doesn't keep modification times.
+const MAX_HASHED_SIZE: u64 = 1024 * 1024;
+
+/// Metadata of a watched file used to detect changes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Stamp {
+    len: u64,
+    /// Modification time kept by the archive, if it supports them.
+    modified: Option<u64>,
+    /// Hash of the contents, for small files without a modification time.
+    contents: Option<u64>,
+}
+
+impl Stamp {
+    /// Returns the current stamp of the file at `path`, or [`None`] if it doesn't exist (or can't be read).
+    fn of(path: &Path) -> Option<Self> {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+
+        // The devoptab `stat` implementation doesn't fill in the modification time, so it's queried separately.
+        let len = std::fs::metadata(path).ok()?.len();
+        let modified = modification_time(path);
+
+        let contents = match modified {
+            None if len <= MAX_HASHED_SIZE => std::fs::read(path).ok().map(|data| {
+                let mut hasher = DefaultHasher::new();
+                data.hash(&mut hasher);
+                hasher.finish()
+            }),
+            _ => None,
+        };
+
+        Some(Self {
+            len,
+            modified,
+            contents,
+        })
+    }
+}
+
+/// Returns the modification time of the file at `path`, as stored by its archive (e.g. the SD card's FAT timestamps).
+#[doc(alias = "archive_getmtime")]
+pub(crate) fn modification_time(path: &Path) -> Option<u64> {
+    let path = CString::new(path.to_str()?).ok()?;
+    let mut mtime = 0;
+
+    let result = unsafe { ctru_sys::archive_getmtime(path.as_ptr(), &mut mtime) };
+
+    ctru_sys::R_SUCCEEDED(result).then_some(mtime)
+}
+
+/// Returns the change between two stamps of the same file, if any.
+fn change(old: Option<Stamp>, new: Option<Stamp>) -> Option<FileEventKind> {
+    match (old, new) {
+        (None, Some(_)) => Some(FileEventKind::Created),
+        (Some(_), None) => Some(FileEventKind::Removed),
+        (Some(old), Some(new)) if old != new => Some(FileEventKind::Modified),
+        _ => None,
+    }
+}
+
+/// Background watcher calling a function whenever a watched file is created, modified or removed.
+///
+/// Like [`SdWatcher`], the files are polled from a separate thread, which also runs the callback. Changes are detected from their size
+/// and the modification time kept by their archive, or from a hash of their contents for small files on archives without modification times (e.g. the RomFS).
+/// Changes happening between two checks are reported as a single event. The watcher stops when dropped.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// use ctru::services::fs::{FileEventKind, Watcher};
+///
+/// let changed = Arc::new(AtomicBool::new(false));
+///
+/// let flag = changed.clone();
+/// let watcher = Watcher::new(move |event| {
+///     if event.kind != FileEventKind::Removed {
+///         flag.store(true, Ordering::Relaxed);
+///     }
+/// })?;
+/// watcher.watch("sdmc:/my_app/config.toml");
+///
+/// // In the main loop...
+/// if changed.swap(false, Ordering::Relaxed) {
+///     let config = std::fs::read_to_string("sdmc:/my_app/config.toml");
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct Watcher {
+    files: Arc<Mutex<HashMap<PathBuf, Option<Stamp>>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    /// Default interval between two checks of the watched files.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Start a watcher checking its files every [`Watcher::DEFAULT_INTERVAL`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the watcher thread couldn't be spawned.
+    pub fn new(callback: impl FnMut(&FileEvent) + Send + 'static) -> crate::Result<Self> {
+        Self::with_interval(Self::DEFAULT_INTERVAL, callback)
+    }
+
+    /// Start a watcher checking its files every `interval`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the watcher thread couldn't be spawned.
+    pub fn with_interval(
+        interval: Duration,
+        mut callback: impl FnMut(&FileEvent) + Send + 'static,
+    ) -> crate::Result<Self> {
+        let files: Arc<Mutex<HashMap<PathBuf, Option<Stamp>>>> = Arc::default();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = thread::Builder::new()
+            .name(String::from("fs-watcher"))
+            .stack_size(0x4000)
+            .spawn({
+                let files = files.clone();
+                let stop = stop.clone();
+
+                move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        thread::park_timeout(interval);
+
+                        // Don't hold the lock while reading the metadata, so watching files never waits for the filesystem.
+                        let paths: Vec<PathBuf> = files.lock().unwrap().keys().cloned().collect();
+                        let stamps: Vec<_> = paths.iter().map(|path| Stamp::of(path)).collect();
+
+                        let mut events = Vec::new();
+                        {
+                            let mut files = files.lock().unwrap();
+
+                            for (path, new) in paths.into_iter().zip(stamps) {
+                                // The file may have been unwatched in the meantime.
+                                let Some(old) = files.get_mut(&path) else {
+                                    continue;
+                                };
+
+                                if let Some(kind) = change(std::mem::replace(old, new), new) {
+                                    events.push(FileEvent { path, kind });
+                                }
+                            }
+                        }
+
+                        for event in &events {
+                            callback(event);
+                        }
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            files,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Start watching the file at `path` (which doesn't have to exist yet).
+    ///
+    /// Only the changes happening after this call are reported. Watching a path again does nothing.
+    pub fn watch(&self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let stamp = Stamp::of(&path);
+
+        self.files.lock().unwrap().entry(path).or_insert(stamp);
+    }
+
+    /// Stop watching the file at `path`.
+    pub fn unwatch(&self, path: impl AsRef<Path>) {
+        self.files.lock().unwrap().remove(path.as_ref());
+    }
+
+    /// Returns the paths of the watched files.
+    pub fn watched(&self) -> Vec<PathBuf> {
+        self.files.lock().unwrap().keys().cloned().collect()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Returns `true` if an SD card is inserted in the console.
+#[doc(alias = "FSUSER_IsSdmcDetected")]
+pub fn is_sd_inserted() -> crate::Result<bool> {
+    let mut detected = false;
+    ResultCode(unsafe { ctru_sys::FSUSER_IsSdmcDetected(&mut detected) })?;
+
+    Ok(detected)
+}
+
+/// Returns [`Error::MediaRemoved`] if the SD card isn't inserted anymore, or `err` otherwise.
+///
+/// This is useful to tell apart operations on the SD card (e.g. through [`std::fs`] with the `sdmc:/` prefix)
+/// which failed because the card was removed.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::services::fs;
+/// use ctru::Error;
+///
+/// match std::fs::read("sdmc:/my_app/settings.txt").map_err(fs::sd_error) {
+///     Ok(settings) => println!("Read {} bytes", settings.len()),
+///     Err(Error::MediaRemoved) => println!("Please reinsert the SD card."),
+///     Err(e) => println!("Couldn't read the settings: {e}"),
+/// }
+/// ```
+pub fn sd_error(err: impl Into<Error>) -> Error {
+    match is_sd_inserted() {
+        Ok(false) => Error::MediaRemoved,
+        _ => err.into(),
+    }
+}
+
+/// Name of the device through which host-IO bridges expose the files of the host computer.
+const HOST_DEVICE: &str = "host0:/";
+
+/// Returns the root of a directory of the host computer reachable with [`std::fs`], if there is one.
+///
+/// Files of the host computer make integration tests and asset hot-reloading possible without copying anything to the console.
+/// They are available when a host-IO bridge registered the `host0:` device, whose root is returned.
+///
+/// Returns [`None`] when no host-IO bridge is running.
+///
+/// # Notes
+///
+/// Citra's emulated SD card is a directory of the host computer (`sdmc` in Citra's user directory), but it isn't reported
+/// by this function: it is the application's regular `sdmc:/`, not a separate channel to the host.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::services::fs;
+///
+/// if let Some(host) = fs::host() {
+///     let config = std::fs::read_to_string(host.join("my_app/config.toml"));
+/// }
+/// ```
+pub fn host() -> Option<PathBuf> {
+    std::fs::metadata(HOST_DEVICE)
+        .is_ok()
+        .then(|| PathBuf::from(HOST_DEVICE))
+}
+
+/// Copy the file at `from` to `to`, reporting the amount of bytes copied to `progress`.
+///
+/// Like [`std::fs::copy()`], this function overwrites `to` if it already exists, and returns the amount of bytes copied.
+/// If the copy is cancelled or fails, the incomplete destination file is removed.
+/// Pass a [`CancelToken`](crate::sync::CancelToken) as `progress` to make the copy cancellable without tracking its progress.
+///
+/// # Errors
+///
+/// This function will return [`Error::Cancelled`] if `progress` asked to stop, [`Error::MediaRemoved`] if the SD card was removed,
+/// or another error if `from` couldn't be read or `to` couldn't be written.
+pub fn copy_with_progress(
+    from: impl AsRef<Path>,
+    to: impl AsRef<Path>,
+    progress: &mut impl Progress,
+) -> crate::Result<u64> {
+    let source = std::fs::File::open(from).map_err(sd_error)?;
+    let total = source.metadata().map_err(sd_error)?.len();
+
+    let to = to.as_ref();
+    let destination = std::fs::File::create(to).map_err(sd_error)?;
+
+    progress::copy(
+        io::BufReader::new(source),
+        destination,
+        Some(total),
+        progress,
+    )
+    .map_err(|e| {
+        let _ = std::fs::remove_file(to);
+
+        match e {
+            Error::Io(e) => sd_error(e),
+            e => e,
+        }
+    })
+}
+
+/// Well-known system archives, identified by their archive ID and lowpath.
+///
+/// Use [`MountedArchive::shared()`] to mount one of them without spelling out its lowpath.
+/// All of these archives are stored in the internal NAND memory and require the appropriate access rights.
+///
+/// # Notes
+///
+/// The system's shared font is not listed here: it is stored in a system data title rather than in a mountable archive,
+/// and APT already maps it in shared memory for every application.
+///
+/// See also <https://www.3dbrew.org/wiki/Extdata#Shared_Extdata> and <https://www.3dbrew.org/wiki/System_SaveData>
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SharedArchive {
+    /// Shared extdata `0xF0000001`, containing the Nintendo 3DS Camera's NAND photos and videos.
+    CameraData,
+    /// Shared extdata `0xF0000002`, containing the Nintendo 3DS Sound recordings stored in NAND.
+    SoundData,
+    /// Shared extdata `0xF000000B`, containing the Play Coins file and the Activity Log's icon cache.
+    PlayData,
+    /// System save data of the config service (`0x00010017`), containing the system settings.
+    ConfigSavedata,
+    /// System save data of the PTM service (`0x00010022`), containing the Activity Log records.
+    PtmSavedata,
+    /// System save data of the friends service (`0x00010032`), containing the friend list.
+    FriendsSavedata,
+    /// System save data of the account service (`0x00010038`), containing the Nintendo Network ID data.
+    AccountSavedata,
+}
+
+impl SharedArchive {
+    /// Returns the ID of the archive type.
+    pub fn archive_id(self) -> ArchiveID {
+        match self {
+            Self::CameraData | Self::SoundData | Self::PlayData => ArchiveID::SharedExtdata,
+            _ => ArchiveID::SystemSavedata,
+        }
+    }
+
+    /// Returns the extdata or system save data ID within the archive type.
+    pub fn id(self) -> u32 {
+        match self {
+            Self::CameraData => 0xF000_0001,
+            Self::SoundData => 0xF000_0002,
+            Self::PlayData => 0xF000_000B,
+            Self::ConfigSavedata => 0x0001_0017,
+            Self::PtmSavedata => 0x0001_0022,
+            Self::FriendsSavedata => 0x0001_0032,
+            Self::AccountSavedata => 0x0001_0038,
+        }
+    }
+
+    /// Returns the binary lowpath used to open the archive.
+    pub fn lowpath(self) -> Vec<u8> {
+        self.path().as_bytes().to_vec()
+    }
+
+    /// Returns the binary lowpath used to open the archive, as an [`ArchivePath`].
+    pub fn path(self) -> ArchivePath {
+        match self.archive_id() {
+            ArchiveID::SharedExtdata => {
+                ArchivePath::binary_words(&[MediaType::Nand as u32, self.id(), 0x0004_8000])
+            }
+            _ => ArchivePath::binary_words(&[MediaType::Nand as u32, self.id()]),
+        }
+    }
+}
+
+/// An archive mounted as a virtual device.
+///
+/// As long as this handle is alive, the contents of the archive are accessible via [`std::fs`]
+/// by using the device name as a path prefix (e.g. `name:/file.txt`), exactly like [`RomFS`](crate::services::romfs).
+///
+/// # Notes
+///
+/// Most system archives (such as shared extdata) require the application to have the appropriate access rights.
+/// Opening them without those rights will return an error.
+#[doc(alias = "archiveMount")]
+pub struct MountedArchive {
+    name: CString,
+    on_sd: bool,
+}
+
+impl MountedArchive {
+    /// Mount an archive with the given ID and lowpath as a virtual device called `name`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the name contains NUL bytes, if a device with the same name is already mounted
+    /// or if the archive could not be opened. If the archive is stored on the SD card and the card is not inserted,
+    /// the error is [`Error::MediaRemoved`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::fs::{ArchiveID, MountedArchive, PathType};
+    ///
+    /// // The SD card archive uses an empty lowpath.
+    /// let sd = MountedArchive::new(ArchiveID::Sdmc, PathType::Empty, &[0], "card")?;
+    ///
+    /// let entries = std::fs::read_dir("card:/")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "archiveMount")]
+    pub fn new(id: ArchiveID, path_type: PathType, path: &[u8], name: &str) -> crate::Result<Self> {
+        let name = CString::new(name)
+            .map_err(|_| Error::Other(String::from("archive name contains NUL bytes")))?;
+
+        let raw_path = ctru_sys::FS_Path {
+            type_: path_type.into(),
+            size: path.len() as u32,
+            data: path.as_ptr().cast(),
+        };
+
+        let on_sd = match id {
+            ArchiveID::Sdmc
+            | ArchiveID::SdmcWriteOnly
+            | ArchiveID::Extdata
+            | ArchiveID::BossExtdata
+            | ArchiveID::ExtDataAndBossExtdata => true,
+            // The lowpath of these archives starts with the media type.
+            ArchiveID::UserSavedata | ArchiveID::SharedExtdata => {
+                path.get(..4) == Some(&(MediaType::Sd as u32).to_le_bytes())
+            }
+            _ => false,
+        };
+
+        check(
+            ResultCode(unsafe { ctru_sys::archiveMount(id.into(), raw_path, name.as_ptr()) }),
+            on_sd,
+        )?;
+
+        Ok(Self { name, on_sd })
+    }
+
+    /// Mount an archive with the given ID and lowpath as a virtual device called `name`.
+    ///
+    /// This is the same as [`MountedArchive::new()`], with a lowpath encoded by [`ArchivePath`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::fs::{ArchiveID, ArchivePath, MountedArchive};
+    ///
+    /// let sd = MountedArchive::with_path(ArchiveID::Sdmc, &ArchivePath::EMPTY, "card")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "archiveMount")]
+    pub fn with_path(id: ArchiveID, path: &ArchivePath, name: &str) -> crate::Result<Self> {
+        Self::new(id, path.path_type(), path.as_bytes(), name)
+    }
+
+    /// Mount the shared extdata archive with the given ID (e.g. `0xF000000B`) as a virtual device called `name`.
+    ///
+    /// Shared extdata is always stored in the internal NAND memory.
+    #[doc(alias = "archiveMount")]
+    pub fn shared_extdata(extdata_id: u32, name: &str) -> crate::Result<Self> {
+        let lowpath = ArchivePath::binary_words(&[MediaType::Nand as u32, extdata_id, 0x0004_8000]);
+
+        Self::with_path(ArchiveID::SharedExtdata, &lowpath, name)
+    }
+
+    /// Mount one of the well-known system archives as a virtual device called `name`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::fs::{MountedArchive, SharedArchive};
+    ///
+    /// let play_data = MountedArchive::shared(SharedArchive::PlayData, "playdata")?;
+    ///
+    /// let coins = std::fs::read("playdata:/gamecoin.dat")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "archiveMount")]
+    pub fn shared(archive: SharedArchive, name: &str) -> crate::Result<Self> {
+        Self::with_path(archive.archive_id(), &archive.path(), name)
+    }
+
+    /// Returns the name of the virtual device.
+    pub fn name(&self) -> &str {
+        // The name was created from a valid `&str`.
+        self.name.to_str().unwrap()
+    }
+
+    /// Commit any pending changes to the archive.
+    ///
+    /// # Notes
+    ///
+    /// This is only needed by save data archives, where file changes aren't persisted until committed.
+    #[doc(alias = "archiveCommitSaveData")]
+    pub fn commit(&self) -> crate::Result<()> {
+        check(
+            ResultCode(unsafe { ctru_sys::archiveCommitSaveData(self.name.as_ptr()) }),
+            self.on_sd,
+        )
+    }
+
+    /// Returns `true` if the archive is stored on the SD card.
+    pub fn is_on_sd(&self) -> bool {
+        self.on_sd
+    }
+
+    /// Convert the error of a failed operation on this archive, returning [`Error::MediaRemoved`] if the archive
+    /// is stored on the SD card and the card was removed.
+    ///
+    /// This is useful to handle errors of [`std::fs`] operations on the archive's contents.
+    pub fn media_error(&self, err: impl Into<Error>) -> Error {
+        if self.on_sd {
+            sd_error(err)
+        } else {
+            err.into()
+        }
+    }
+}
+
+fn check(result: ResultCode, on_sd: bool) -> crate::Result<()> {
+    if let Err(e) = into_result(result) {
+        return Err(if on_sd { sd_error(e) } else { e });
+    }
+
+    Ok(())
+}
+
+fn into_result(result: ResultCode) -> crate::Result<()> {
+    result?;
+
+    Ok(())
+}
+
+impl Drop for MountedArchive {
+    #[doc(alias = "archiveUnmount")]
+    fn drop(&mut self) {
+        unsafe {
+            let _ = ctru_sys::archiveUnmount(self.name.as_ptr());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_changes() {
+        let stamp = Stamp {
+            len: 10,
+            modified: Some(1),
+            contents: None,
+        };
+        let grown = Stamp { len: 12, ..stamp };
+        let touched = Stamp {
+            modified: Some(2),
+            ..stamp
+        };
+
+        assert_eq!(change(None, Some(stamp)), Some(FileEventKind::Created));
+        assert_eq!(change(Some(stamp), None), Some(FileEventKind::Removed));
+        assert_eq!(
+            change(Some(stamp), Some(grown)),
+            Some(FileEventKind::Modified)
+        );
+        assert_eq!(
+            change(Some(stamp), Some(touched)),
+            Some(FileEventKind::Modified)
+        );
+        assert_eq!(change(Some(stamp), Some(stamp)), None);
+        assert_eq!(change(None, None), None);
+    }
+
+    #[test]
+    fn archive_path_encoding() {
+        assert_eq!(ArchivePath::EMPTY.as_raw().size, 1);
+
+        let utf16 = ArchivePath::utf16("/é").unwrap();
+        assert_eq!(utf16.as_bytes(), &[b'/', 0, 0xE9, 0, 0, 0]);
+
+        let ascii = ArchivePath::ascii("/a").unwrap();
+        assert_eq!(ascii.as_bytes(), b"/a\0");
+        assert_eq!(ascii, ArchivePath::ascii_static(c"/a"));
+        assert!(ArchivePath::ascii("/é").is_err());
+
+        for path in ["sdmc:/a/b", "/a/b", "a/b"] {
+            assert_eq!(
+                ArchivePath::try_from(Path::new(path)).unwrap(),
+                ArchivePath::utf16("/a/b").unwrap()
+            );
+        }
+
+        assert_eq!(
+            SharedArchive::PlayData.lowpath(),
+            [0x00, 0x00, 0x00, 0x00, 0x0B, 0x00, 0x00, 0xF0, 0x00, 0x80, 0x04, 0x00]
+        );
+    }
+}
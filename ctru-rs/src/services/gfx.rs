@@ -4,14 +4,17 @@
 //! The screens are subordinate to the GFX service handle and can be used by only one borrower at a time.
 #![doc(alias = "graphics")]
 
-use std::cell::{Ref, RefCell, RefMut};
+use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::marker::PhantomData;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, TryLockError};
 
 use crate::error::Result;
 use crate::sealed::Sealed;
+use crate::services::apt::AptHook;
 use crate::services::gspgpu::{self, FramebufferFormat};
 use crate::services::ServiceReference;
+use crate::shutdown::Stage;
 
 /// Trait to handle common functionality for all screens.
 ///
@@ -227,6 +230,66 @@ pub enum Side {
     Right = ctru_sys::GFX_RIGHT,
 }
 
+/// Framebuffer settings of both screens, as reported by [`FramebufferEvent::Reconfigured`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FramebufferConfig {
+    /// Format of the top screen's framebuffers.
+    pub top_format: FramebufferFormat,
+    /// Format of the bottom screen's framebuffers.
+    pub bottom_format: FramebufferFormat,
+    /// Whether the top screen is in wide mode.
+    pub wide_mode: bool,
+}
+
+impl FramebufferConfig {
+    /// Returns the current framebuffer settings.
+    #[doc(alias = "gfxGetScreenFormat")]
+    fn current() -> Self {
+        unsafe {
+            Self {
+                top_format: ctru_sys::gfxGetScreenFormat(ctru_sys::GFX_TOP).into(),
+                bottom_format: ctru_sys::gfxGetScreenFormat(ctru_sys::GFX_BOTTOM).into(),
+                wide_mode: ctru_sys::gfxIsWide(),
+            }
+        }
+    }
+}
+
+/// Change to the screens' framebuffers, as returned by [`Gfx::framebuffer_event()`].
+///
+/// Renderers caching framebuffer addresses, strides or formats must query them again after any of these events.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FramebufferEvent {
+    /// The application got back the control of the screens (e.g. after an applet or the HOME Menu closed),
+    /// and the framebuffers were registered again with GSP.
+    Restored,
+    /// The format of a screen or the top screen's wide mode changed.
+    Reconfigured(FramebufferConfig),
+}
+
+/// APT hook watching for the application regaining the screens.
+struct RestoreHook {
+    restored: Arc<AtomicBool>,
+    _hook: AptHook,
+}
+
+impl RestoreHook {
+    fn register() -> Self {
+        let restored = Arc::new(AtomicBool::new(false));
+        let hook_restored = Arc::clone(&restored);
+
+        Self {
+            restored,
+            _hook: AptHook::register(move |hook| {
+                if hook == ctru_sys::APTHOOK_ONRESTORE {
+                    hook_restored.store(true, Ordering::Release);
+                }
+            }),
+        }
+    }
+}
+
 /// Handle to the GFX service.
 ///
 /// This service is a wrapper around the lower-level [GSPGPU](crate::services::gspgpu) service that
@@ -236,6 +299,8 @@ pub struct Gfx {
     pub top_screen: RefCell<TopScreen>,
     /// Bottom screen representation.
     pub bottom_screen: RefCell<BottomScreen>,
+    restore_hook: RestoreHook,
+    last_config: Cell<FramebufferConfig>,
     _service_handler: ServiceReference,
 }
 
@@ -356,8 +421,10 @@ impl Gfx {
         bottom_fb_fmt: FramebufferFormat,
         vram_buffer: bool,
     ) -> Result<Self> {
-        let handler = ServiceReference::new(
+        let handler = ServiceReference::with_stage(
+            "gsp::Gpu",
             &GFX_ACTIVE,
+            Stage::Graphics,
             || unsafe {
                 ctru_sys::gfxInit(top_fb_fmt.into(), bottom_fb_fmt.into(), vram_buffer);
 
@@ -369,6 +436,8 @@ impl Gfx {
         Ok(Self {
             top_screen: RefCell::new(TopScreen::new()),
             bottom_screen: RefCell::new(BottomScreen),
+            restore_hook: RestoreHook::register(),
+            last_config: Cell::new(FramebufferConfig::current()),
             _service_handler: handler,
         })
     }
@@ -404,6 +473,223 @@ impl Gfx {
     pub fn wait_for_vblank(&self) {
         gspgpu::wait_for_event(gspgpu::Event::VBlank0, true);
     }
+
+    /// Returns the next change to the screens' framebuffers since the last call, if any.
+    ///
+    /// Call this once per frame (e.g. right after [`Apt::main_loop()`](crate::services::apt::Apt::main_loop)) and re-query any cached
+    /// framebuffer information when it returns an event: the framebuffers may have been reallocated or registered again
+    /// while an applet or the HOME Menu had control of the screens.
+    /// Framebuffer format and wide mode changes, done with [`Screen::set_framebuffer_format()`] and [`TopScreen::set_wide_mode()`]
+    /// (or directly through `libctru`), are reported as well.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::apt::Apt;
+    /// use ctru::services::gfx::{FramebufferEvent, Gfx, Screen};
+    /// use ctru::services::gspgpu::FramebufferFormat;
+    ///
+    /// let apt = Apt::new()?;
+    /// let gfx = Gfx::new()?;
+    ///
+    /// gfx.top_screen.borrow_mut().set_framebuffer_format(FramebufferFormat::Rgb565);
+    ///
+    /// match gfx.framebuffer_event() {
+    ///     Some(FramebufferEvent::Reconfigured(config)) => {
+    ///         assert_eq!(config.top_format, FramebufferFormat::Rgb565);
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "APTHOOK_ONRESTORE")]
+    pub fn framebuffer_event(&self) -> Option<FramebufferEvent> {
+        if self.restore_hook.restored.swap(false, Ordering::Acquire) {
+            // The configuration is kept across the restore, but must be queried again by the renderer anyway.
+            self.last_config.set(FramebufferConfig::current());
+
+            return Some(FramebufferEvent::Restored);
+        }
+
+        let config = FramebufferConfig::current();
+        if config != self.last_config.replace(config) {
+            return Some(FramebufferEvent::Reconfigured(config));
+        }
+
+        None
+    }
+
+    /// Returns `true` if a [`Gfx`] handle is currently alive.
+    pub(crate) fn is_active() -> bool {
+        matches!(GFX_ACTIVE.try_lock(), Err(TryLockError::WouldBlock))
+    }
+}
+
+/// Screens due for a new frame, as returned by [`FramePacer::wait()`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DueScreens {
+    /// Whether a new frame should be presented on the top screen.
+    pub top: bool,
+    /// Whether a new frame should be presented on the bottom screen.
+    pub bottom: bool,
+}
+
+/// Frame pacing of a single screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Cadence {
+    refresh_rate: f32,
+    frame_rate: f32,
+    /// Fraction of the next frame elapsed.
+    progress: f32,
+}
+
+impl Cadence {
+    fn new(refresh_rate: f32) -> Self {
+        Self {
+            refresh_rate,
+            frame_rate: refresh_rate,
+            progress: 0.,
+        }
+    }
+
+    /// Advance by one refresh of the pacer's clock screen, returning whether a new frame is due.
+    fn advance(&mut self, clock_rate: f32) -> bool {
+        // Frames can't be presented faster than the screen refreshes.
+        self.progress += self.frame_rate.min(self.refresh_rate) / clock_rate;
+
+        // Rates equal to the clock's must be due at every refresh, despite rounding.
+        if self.progress < 1. - 1e-4 {
+            return false;
+        }
+
+        self.progress = (self.progress - 1.).max(0.);
+        true
+    }
+}
+
+/// Synchronizer of the application's main loop with the refresh of both screens, for screens updated at different cadences.
+///
+/// [`Gfx::wait_for_vblank()`] waits for the top screen only, which is enough as long as both screens refresh together
+/// and are redrawn at every refresh. The pacer instead ticks at every refresh of the faster screen, and reports which screens
+/// are due for a new frame given their own [frame rate](FramePacer::set_frame_rates), e.g. to play a 24 FPS video
+/// on the bottom screen while the top screen runs at 60 FPS. Screens reprogrammed to a slower refresh rate
+/// (see [`gspgpu::refresh_rate()`]) are never reported as due more often than they refresh.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::apt::Apt;
+/// use ctru::services::gfx::{FramePacer, Gfx};
+/// let apt = Apt::new()?;
+/// let gfx = Gfx::new()?;
+///
+/// let mut pacer = FramePacer::new(&gfx)?;
+///
+/// // Play a video on the bottom screen, and redraw the top screen at every refresh.
+/// let (top_rate, _) = pacer.refresh_rates();
+/// pacer.set_frame_rates(top_rate, 24.);
+///
+/// while apt.main_loop() {
+///     let due = pacer.wait();
+///
+///     if due.top {
+///         // Draw the next frame of the user interface.
+///     }
+///     if due.bottom {
+///         // Decode and draw the next frame of the video.
+///     }
+/// #   break;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FramePacer {
+    top: Cadence,
+    bottom: Cadence,
+}
+
+impl FramePacer {
+    /// Create a pacer for the current refresh rates of the screens, presenting a new frame on both at every refresh.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the LCD configuration couldn't be read.
+    pub fn new(_gfx: &Gfx) -> Result<Self> {
+        Ok(Self {
+            top: Cadence::new(gspgpu::lcd_refresh_rate(ctru_sys::GFX_TOP)?),
+            bottom: Cadence::new(gspgpu::lcd_refresh_rate(ctru_sys::GFX_BOTTOM)?),
+        })
+    }
+
+    /// Returns the refresh rates of the top and bottom screens in hertz, as read when creating the pacer
+    /// or at the last call to [`FramePacer::update_refresh_rates()`].
+    pub fn refresh_rates(&self) -> (f32, f32) {
+        (self.top.refresh_rate, self.bottom.refresh_rate)
+    }
+
+    /// Read the refresh rates of the screens again, e.g. after reprogramming the LCD timings.
+    ///
+    /// The frame rates aren't changed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the LCD configuration couldn't be read.
+    pub fn update_refresh_rates(&mut self) -> Result<()> {
+        self.top.refresh_rate = gspgpu::lcd_refresh_rate(ctru_sys::GFX_TOP)?;
+        self.bottom.refresh_rate = gspgpu::lcd_refresh_rate(ctru_sys::GFX_BOTTOM)?;
+
+        Ok(())
+    }
+
+    /// Returns the frame rates of the top and bottom screens, in frames per second.
+    pub fn frame_rates(&self) -> (f32, f32) {
+        (self.top.frame_rate, self.bottom.frame_rate)
+    }
+
+    /// Set the amount of frames per second to present on the top and bottom screens.
+    ///
+    /// Frame rates higher than a screen's refresh rate are capped to it. Frame rates which don't divide the refresh rate
+    /// are approximated by spacing frames unevenly (e.g. 24 FPS on a 60 Hz screen alternates between 2 and 3 refreshes per frame).
+    pub fn set_frame_rates(&mut self, top: f32, bottom: f32) {
+        self.top.frame_rate = top;
+        self.bottom.frame_rate = bottom;
+    }
+
+    /// Wait for the next refresh of the faster screen, and return which screens are due for a new frame.
+    ///
+    /// A screen can be due for a new frame while it's still refreshing, if it is slower than the other one:
+    /// its new frame is presented at its next refresh, once its buffers are [swapped](Swap::swap_buffers).
+    #[doc(alias = "gspWaitForEvent")]
+    pub fn wait(&mut self) -> DueScreens {
+        let (event, clock_rate) = if self.bottom.refresh_rate > self.top.refresh_rate {
+            (gspgpu::Event::VBlank1, self.bottom.refresh_rate)
+        } else {
+            (gspgpu::Event::VBlank0, self.top.refresh_rate)
+        };
+
+        gspgpu::wait_for_event(event, true);
+
+        self.advance(clock_rate)
+    }
+
+    fn advance(&mut self, clock_rate: f32) -> DueScreens {
+        DueScreens {
+            top: self.top.advance(clock_rate),
+            bottom: self.bottom.advance(clock_rate),
+        }
+    }
 }
 
 impl TopScreen3D<'_> {
@@ -554,4 +840,25 @@ mod tests {
 
         assert!(matches!(Gfx::new(), Err(Error::ServiceAlreadyActive)));
     }
+
+    #[test]
+    fn paced_cadences() {
+        let mut pacer = FramePacer {
+            top: Cadence::new(59.831),
+            bottom: Cadence::new(24.),
+        };
+
+        // The top screen is due at every refresh, the bottom one follows its own refresh rate.
+        let due: Vec<_> = (0..600).map(|_| pacer.advance(59.831)).collect();
+        assert!(due.iter().all(|due| due.top));
+        let bottom = due.iter().filter(|due| due.bottom).count();
+        assert!((240..=241).contains(&bottom));
+
+        // 24 FPS on a 60 Hz screen alternates between 2 and 3 refreshes per frame.
+        pacer.bottom = Cadence::new(60.);
+        pacer.set_frame_rates(30., 24.);
+        let due: Vec<_> = (0..10).map(|_| pacer.advance(60.)).collect();
+        assert_eq!(due.iter().filter(|due| due.top).count(), 5);
+        assert_eq!(due.iter().filter(|due| due.bottom).count(), 4);
+    }
 }
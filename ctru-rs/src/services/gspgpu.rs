@@ -1,5 +1,23 @@
 //! GSPGPU service
 
+use crate::error::ResultCode;
+use crate::services::gfx::{Screen, Side};
+
+/// Offset of the top screen's framebuffer registers (`0x1EF00468`), relative to the start of the registers accessible through GSP.
+const TOP_FRAMEBUFFER_REGISTERS: u32 = 0x400468;
+/// Offset of the bottom screen's framebuffer registers (`0x1EF00568`).
+const BOTTOM_FRAMEBUFFER_REGISTERS: u32 = 0x400568;
+/// Amount of framebuffer registers read, from the left framebuffer addresses to the right framebuffer addresses.
+const FRAMEBUFFER_REGISTER_COUNT: usize = 13;
+/// Offset of the top screen's timing registers (`0x1EF00400`).
+const TOP_TIMING_REGISTERS: u32 = 0x400400;
+/// Offset of the bottom screen's timing registers (`0x1EF00500`).
+const BOTTOM_TIMING_REGISTERS: u32 = 0x400500;
+/// Amount of timing registers read, from the horizontal total to the vertical total.
+const TIMING_REGISTER_COUNT: usize = 10;
+/// Pixel clock of the LCD controller, in hertz.
+const PIXEL_CLOCK: f32 = 268_111_856. / 24.;
+
 /// GSPGPU events that can be awaited.
 #[doc(alias = "GSPGPU_Event")]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -25,6 +43,7 @@ pub enum Event {
 /// Framebuffer formats supported by the 3DS' screens.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FramebufferFormat {
     /// RGBA8. 4 bytes per pixel
     Rgba8 = ctru_sys::GSP_RGBA8_OES,
@@ -52,6 +71,189 @@ impl FramebufferFormat {
     }
 }
 
+/// Snapshot of the framebuffer configuration of a screen, as currently used by the LCD controller.
+///
+/// GSP programs the LCD registers from the framebuffer descriptors written by [`Gfx`](crate::services::gfx::Gfx) to its shared memory,
+/// at the next VBlank after each [swap](crate::services::gfx::Swap::swap_buffers). Comparing snapshots with the buffers being drawn to
+/// shows which buffer is actually displayed, e.g. to debug tearing in custom presentation code.
+///
+/// Addresses are physical addresses, as used by the hardware (see [`FramebufferInfo::active_framebuffer_ptr()`]).
+#[doc(alias = "GSPGPU_FramebufferInfo")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FramebufferInfo {
+    active: usize,
+    left: [u32; 2],
+    right: [u32; 2],
+    stride: u32,
+    raw_format: u32,
+}
+
+impl FramebufferInfo {
+    /// Returns the index (0 or 1) of the framebuffer being displayed.
+    pub fn active_framebuffer(&self) -> usize {
+        self.active
+    }
+
+    /// Returns the physical addresses of both framebuffers of the given side of the screen
+    /// (only the top screen's framebuffers have a right side, used in 3D mode).
+    pub fn addresses(&self, side: Side) -> [u32; 2] {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+        }
+    }
+
+    /// Returns the physical address of the framebuffer being displayed on the given side of the screen.
+    pub fn active_address(&self, side: Side) -> u32 {
+        self.addresses(side)[self.active]
+    }
+
+    /// Returns a pointer to the framebuffer being displayed on the given side of the screen, in the application's address space,
+    /// or a null pointer if the framebuffer isn't mapped in it.
+    #[doc(alias = "osConvertPhysToVirt")]
+    pub fn active_framebuffer_ptr(&self, side: Side) -> *const u8 {
+        unsafe { ctru_sys::osConvertPhysToVirt(self.active_address(side)) }.cast()
+    }
+
+    /// Returns the amount of bytes between the start of two lines of the framebuffer.
+    ///
+    /// Since the screens are rotated, a line of the framebuffer is a column of pixels on the screen.
+    pub fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    /// Returns the format of the framebuffer, or [`None`] if the register holds an invalid format.
+    pub fn format(&self) -> Option<FramebufferFormat> {
+        match self.raw_format & 0b111 {
+            ctru_sys::GSP_RGBA8_OES => Some(FramebufferFormat::Rgba8),
+            ctru_sys::GSP_BGR8_OES => Some(FramebufferFormat::Bgr8),
+            ctru_sys::GSP_RGB565_OES => Some(FramebufferFormat::Rgb565),
+            ctru_sys::GSP_RGB5_A1_OES => Some(FramebufferFormat::Rgb5A1),
+            ctru_sys::GSP_RGBA4_OES => Some(FramebufferFormat::Rgba4),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw value of the format register, which also holds the screen's display mode flags.
+    pub fn raw_format(&self) -> u32 {
+        self.raw_format
+    }
+}
+
+/// Read the framebuffer configuration currently used by the LCD controller for `screen`.
+///
+/// All the values are read at once, so the snapshot is consistent even if the buffers are swapped while reading it.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::gfx::{Gfx, Side};
+/// use ctru::services::gspgpu;
+///
+/// let gfx = Gfx::new()?;
+///
+/// let info = gspgpu::framebuffer_info(&*gfx.bottom_screen.borrow())?;
+/// println!(
+///     "Displaying framebuffer {} at {:#X}",
+///     info.active_framebuffer(),
+///     info.active_address(Side::Left)
+/// );
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "GSPGPU_ReadHWRegs")]
+pub fn framebuffer_info(screen: &impl Screen) -> crate::Result<FramebufferInfo> {
+    let address = match screen.as_raw() {
+        ctru_sys::GFX_TOP => TOP_FRAMEBUFFER_REGISTERS,
+        _ => BOTTOM_FRAMEBUFFER_REGISTERS,
+    };
+
+    let mut registers = [0u32; FRAMEBUFFER_REGISTER_COUNT];
+    read_registers(address, &mut registers)?;
+
+    // Registers are 4 bytes apart, starting from the first left framebuffer address (0x68).
+    let register = |offset: usize| registers[(offset - 0x68) / 4];
+
+    Ok(FramebufferInfo {
+        active: (register(0x78) & 1) as usize,
+        left: [register(0x68), register(0x6C)],
+        right: [register(0x94), register(0x98)],
+        stride: register(0x90),
+        raw_format: register(0x70),
+    })
+}
+
+/// Returns the refresh rate of `screen` in hertz, as currently configured in the LCD controller.
+///
+/// Both screens refresh at about 59.83 Hz by default, but their timings can be reprogrammed independently,
+/// e.g. by video players running the bottom screen at 24 Hz: see [`FramePacer`](crate::services::gfx::FramePacer)
+/// to update the screens at different cadences.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::gfx::Gfx;
+/// use ctru::services::gspgpu;
+///
+/// let gfx = Gfx::new()?;
+///
+/// let rate = gspgpu::refresh_rate(&*gfx.bottom_screen.borrow())?;
+/// println!("The bottom screen refreshes at {rate:.2} Hz");
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "GSPGPU_ReadHWRegs")]
+pub fn refresh_rate(screen: &impl Screen) -> crate::Result<f32> {
+    lcd_refresh_rate(screen.as_raw())
+}
+
+/// Returns the refresh rate of the screen `screen` in hertz, without borrowing it.
+pub(crate) fn lcd_refresh_rate(screen: ctru_sys::gfxScreen_t) -> crate::Result<f32> {
+    let address = match screen {
+        ctru_sys::GFX_TOP => TOP_TIMING_REGISTERS,
+        _ => BOTTOM_TIMING_REGISTERS,
+    };
+
+    let mut registers = [0u32; TIMING_REGISTER_COUNT];
+    read_registers(address, &mut registers)?;
+
+    // The totals are the amount of pixel clocks per line (0x00) and of lines per frame (0x24), minus one.
+    let h_total = (registers[0] & 0xFFF) + 1;
+    let v_total = (registers[9] & 0xFFF) + 1;
+
+    Ok(PIXEL_CLOCK / (h_total * v_total) as f32)
+}
+
+/// Read consecutive hardware registers, starting from `address` (relative to `0x1EB00000`).
+fn read_registers(address: u32, registers: &mut [u32]) -> crate::Result<()> {
+    ResultCode(unsafe {
+        ctru_sys::GSPGPU_ReadHWRegs(
+            address,
+            registers.as_mut_ptr(),
+            std::mem::size_of_val(registers) as u8,
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Returns `true` if framebuffer descriptors were written to GSP's shared memory for `screen`,
+/// but weren't applied to the LCD controller yet (which happens at the next VBlank).
+#[doc(alias = "gspIsPresentPending")]
+pub fn is_present_pending(screen: &impl Screen) -> bool {
+    unsafe { ctru_sys::gspIsPresentPending(screen.as_raw()) }
+}
+
 /// Waits for a GSPGPU event to occur.
 ///
 /// `discard_current` determines whether to discard the current event and wait for the next event
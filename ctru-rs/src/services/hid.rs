@@ -3,11 +3,13 @@
 //! The HID service provides read access to user input such as [button presses](Hid::keys_down), [touch screen presses](Hid::touch_position),
 //! and [circle pad information](Hid::circlepad_position). It also provides information from the [volume slider](Hid::volume_slider()),
 //! the [accelerometer](Hid::accelerometer_vector()), and the [gyroscope](Hid::gyroscope_rate()).
+//! [`ShakeDetector`] builds on the accelerometer to detect the console being shaken.
 #![doc(alias = "input")]
 #![doc(alias = "controller")]
 #![doc(alias = "gamepad")]
 
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use crate::error::ResultCode;
 use crate::services::ServiceReference;
@@ -19,6 +21,7 @@ static HID_ACTIVE: Mutex<()> = Mutex::new(());
 bitflags! {
     /// A set of flags corresponding to the button and directional pad inputs present on the 3DS.
     #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct KeyPad: u32 {
         /// A button.
         const A             = ctru_sys::KEY_A;
@@ -95,6 +98,7 @@ pub enum Error {
 /// Have a look at [`Hid::set_accelerometer()`] for more information.
 #[allow(missing_docs)]
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Acceleration {
     x: i16,
     y: i16,
@@ -106,6 +110,7 @@ pub struct Acceleration {
 /// Have a look at [`Hid::set_gyroscope()`] for more information.
 #[allow(missing_docs)]
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AngularRate {
     roll: i16,
     pitch: i16,
@@ -144,6 +149,7 @@ impl Hid {
     #[doc(alias = "hidInit")]
     pub fn new() -> crate::Result<Hid> {
         let handler = ServiceReference::new(
+            "hid:USER",
             &HID_ACTIVE,
             || {
                 ResultCode(unsafe { ctru_sys::hidInit() })?;
@@ -188,6 +194,8 @@ impl Hid {
     #[doc(alias = "hidScanInput")]
     pub fn scan_input(&mut self) {
         unsafe { ctru_sys::hidScanInput() };
+
+        crate::power::register_input(self);
     }
 
     /// Returns a bitflag struct representing which buttons have just been pressed
@@ -541,6 +549,214 @@ impl Hid {
     }
 }
 
+/// Approximate accelerometer reading for an acceleration of 1 g.
+const ACCELERATION_PER_G: f32 = 512.0;
+
+/// Weight of each new reading in the estimate of the gravity.
+const GRAVITY_SMOOTHING: f32 = 0.1;
+
+/// Time within which several jolts must happen to be considered a shake.
+const SHAKE_WINDOW: Duration = Duration::from_millis(400);
+
+/// Jolts (back and forth movements count as two) needed within [`SHAKE_WINDOW`] to detect a shake.
+const SHAKE_JOLTS: u8 = 3;
+
+/// Acceleration (in g, gravity excluded) needed to register a jolt, at the lowest and highest sensitivity.
+const LEAST_SENSITIVE_THRESHOLD: f32 = 2.5;
+const MOST_SENSITIVE_THRESHOLD: f32 = 0.5;
+
+/// Shake of the console, detected by a [`ShakeDetector`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Shake {
+    /// Strongest acceleration of the shake (gravity excluded), in g.
+    pub strength: f32,
+}
+
+/// Detector of the console being shaken, from the readings of the accelerometer.
+///
+/// A shake is a few strong jolts in quick succession: moving the console once, or tilting it, isn't enough.
+/// After a shake is detected, the detector waits for a cooldown before detecting the next one,
+/// so that a single long shake isn't reported several times.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::hid::{Hid, ShakeDetector};
+/// let mut hid = Hid::new()?;
+/// hid.set_accelerometer(true)?;
+///
+/// let mut detector = ShakeDetector::new();
+/// detector.set_sensitivity(0.8);
+///
+/// hid.scan_input();
+/// if let Some(shake) = detector.update(&hid)? {
+///     println!("Shaken with a strength of {:.1} g", shake.strength);
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ShakeDetector {
+    sensitivity: f32,
+    cooldown: Duration,
+    gravity: Option<[f32; 3]>,
+    /// Direction of the ongoing jolt while the acceleration is above the threshold, so a single jolt is only counted once.
+    jolt_direction: Option<[f32; 3]>,
+    /// Time of the first jolt of the ongoing shake, amount of jolts and strongest acceleration so far.
+    jolts: Option<(Instant, u8, f32)>,
+    cooldown_end: Option<Instant>,
+}
+
+impl ShakeDetector {
+    /// Create a detector with a sensitivity of 0.5 and a cooldown of half a second.
+    pub fn new() -> Self {
+        Self {
+            sensitivity: 0.5,
+            cooldown: Duration::from_millis(500),
+            gravity: None,
+            jolt_direction: None,
+            jolts: None,
+            cooldown_end: None,
+        }
+    }
+
+    /// Returns the sensitivity of the detector, between 0 and 1.
+    pub fn sensitivity(&self) -> f32 {
+        self.sensitivity
+    }
+
+    /// Set the sensitivity of the detector, clamped between 0 (only violent shakes) and 1 (gentle shakes).
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = if sensitivity.is_nan() {
+            0.5
+        } else {
+            sensitivity.clamp(0.0, 1.0)
+        };
+    }
+
+    /// Returns the time waited after a shake before detecting the next one.
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Set the time waited after a shake before detecting the next one.
+    pub fn set_cooldown(&mut self, cooldown: Duration) {
+        self.cooldown = cooldown;
+    }
+
+    /// Forget the readings seen so far (e.g. after the accelerometer was disabled for a while).
+    pub fn reset(&mut self) {
+        self.gravity = None;
+        self.jolt_direction = None;
+        self.jolts = None;
+        self.cooldown_end = None;
+    }
+
+    /// Process the latest reading of the accelerometer, returning the shake it completed if any.
+    ///
+    /// Call this function once per frame, after [`Hid::scan_input()`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the accelerometer isn't enabled (see [`Hid::set_accelerometer()`]).
+    pub fn update(&mut self, hid: &Hid) -> Result<Option<Shake>, Error> {
+        let acceleration = hid.accelerometer_vector()?;
+
+        Ok(self.update_with(acceleration, Instant::now()))
+    }
+
+    /// Process an accelerometer reading taken at `time`, returning the shake it completed if any.
+    ///
+    /// This is what [`ShakeDetector::update()`] does with the current reading, and can be used to feed recorded readings.
+    pub fn update_with(&mut self, acceleration: Acceleration, time: Instant) -> Option<Shake> {
+        let reading = [acceleration.x, acceleration.y, acceleration.z]
+            .map(|value| f32::from(value) / ACCELERATION_PER_G);
+
+        // Gravity (and slow tilting) is separated from the movement with a low-pass filter.
+        let gravity = self.gravity.get_or_insert(reading);
+        for (gravity, reading) in gravity.iter_mut().zip(reading) {
+            *gravity += (reading - *gravity) * GRAVITY_SMOOTHING;
+        }
+
+        let movement: [f32; 3] = std::array::from_fn(|i| reading[i] - gravity[i]);
+        let magnitude = movement
+            .iter()
+            .map(|value| value * value)
+            .sum::<f32>()
+            .sqrt();
+
+        if self.cooldown_end.is_some_and(|end| time < end) {
+            return None;
+        }
+
+        if self
+            .jolts
+            .is_some_and(|(start, _, _)| time.duration_since(start) > SHAKE_WINDOW)
+        {
+            self.jolts = None;
+        }
+
+        let threshold = LEAST_SENSITIVE_THRESHOLD
+            + (MOST_SENSITIVE_THRESHOLD - LEAST_SENSITIVE_THRESHOLD) * self.sensitivity;
+
+        if magnitude <= threshold {
+            self.jolt_direction = None;
+            return None;
+        }
+
+        // A new jolt starts when the acceleration goes above the threshold, or when it reverses while staying above it.
+        let new_jolt = match self.jolt_direction.replace(movement) {
+            Some(direction) => {
+                direction
+                    .iter()
+                    .zip(movement)
+                    .map(|(a, b)| a * b)
+                    .sum::<f32>()
+                    < 0.0
+            }
+            None => true,
+        };
+
+        let (_, count, strength) = self.jolts.get_or_insert((time, 0, 0.0));
+        *strength = strength.max(magnitude);
+        if new_jolt {
+            *count += 1;
+        }
+
+        if *count < SHAKE_JOLTS {
+            return None;
+        }
+
+        let shake = Shake {
+            strength: *strength,
+        };
+
+        self.jolts = None;
+        self.cooldown_end = Some(time + self.cooldown);
+
+        Some(shake)
+    }
+}
+
+impl Default for ShakeDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Acceleration {
+    /// Create an acceleration vector from raw accelerometer values, e.g. to feed recorded readings
+    /// to [`ShakeDetector::update_with()`].
+    pub const fn new(x: i16, y: i16, z: i16) -> Self {
+        Self { x, y, z }
+    }
+}
+
 impl From<Acceleration> for (i16, i16, i16) {
     fn from(value: Acceleration) -> (i16, i16, i16) {
         (value.x, value.y, value.z)
@@ -563,3 +779,40 @@ impl std::fmt::Display for Error {
 }
 
 impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shake_detection() {
+        let mut detector = ShakeDetector::new();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let reading = |x| Acceleration::new(x, -512, 0);
+
+        // Resting and tilting slowly isn't a shake.
+        for frame in 0..60u16 {
+            assert_eq!(
+                detector.update_with(reading(frame as i16 * 4), at(u64::from(frame) * 16)),
+                None
+            );
+        }
+
+        // Jolts back and forth in quick succession are.
+        let mut shakes = 0;
+        for (i, x) in [2000, -2000, 2000, -2000, 2000, -2000]
+            .into_iter()
+            .enumerate()
+        {
+            shakes += usize::from(
+                detector
+                    .update_with(reading(x), at(1000 + i as u64 * 16))
+                    .is_some(),
+            );
+        }
+
+        // The cooldown prevents reporting the same shake twice.
+        assert_eq!(shakes, 1);
+    }
+}
@@ -0,0 +1,421 @@
+//! HTTP client (HTTPC) service.
+//!
+//! The system's HTTP module handles the connection (including TLS) on its own: the application only opens a [`Request`],
+//! sends it and reads the [`Response`]. Requests can be driven with blocking calls, or awaited from a [`Runtime`](crate::runtime::Runtime),
+//! which lets a UI keep running (and run several downloads at once) while the module works.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! #
+//! use ctru::runtime;
+//! use ctru::services::httpc::{Httpc, Method, Request};
+//!
+//! let httpc = Httpc::new()?;
+//!
+//! let body = runtime::block_on(async {
+//!     let mut request = Request::new(&httpc, Method::Get, "http://example.com/")?;
+//!     request.add_header("User-Agent", "ctru-rs")?;
+//!
+//!     let response = request.send().await?;
+//!     println!("Status: {}", response.status());
+//!
+//!     response.bytes().await
+//! })?;
+//! #
+//! # Ok(())
+//! # }
+//! ```
+#![doc(alias = "http")]
+#![doc(alias = "download")]
+
+use std::ffi::CString;
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::error::ResultCode;
+use crate::progress::Progress;
+use crate::runtime;
+use crate::services::ServiceReference;
+use crate::Error;
+
+/// Delay between two checks of a pending request from async code.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Size of the chunks read by [`Response::bytes()`] and [`Response::download()`].
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// Capacity of the buffer receiving the value of a response header.
+const HEADER_VALUE_SIZE: usize = 1024;
+
+static HTTPC_ACTIVE: Mutex<()> = Mutex::new(());
+
+/// Handle to the HTTPC service.
+pub struct Httpc {
+    _service_handler: ServiceReference,
+}
+
+/// HTTP request method.
+#[doc(alias = "HTTPC_RequestMethod")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Method {
+    /// `GET`
+    Get = ctru_sys::HTTPC_METHOD_GET,
+    /// `POST`
+    Post = ctru_sys::HTTPC_METHOD_POST,
+    /// `HEAD`
+    Head = ctru_sys::HTTPC_METHOD_HEAD,
+    /// `PUT`
+    Put = ctru_sys::HTTPC_METHOD_PUT,
+    /// `DELETE`
+    Delete = ctru_sys::HTTPC_METHOD_DELETE,
+}
+
+/// HTTPC context, closed when dropped.
+struct Context(ctru_sys::httpcContext);
+
+impl Drop for Context {
+    #[doc(alias = "httpcCloseContext")]
+    fn drop(&mut self) {
+        let _ = unsafe { ctru_sys::httpcCloseContext(&mut self.0) };
+    }
+}
+
+/// HTTP request, not sent yet.
+pub struct Request {
+    context: Context,
+}
+
+/// Response to a [`Request`], whose body can be read incrementally.
+///
+/// [`Response`] implements [`Read`] to read the body with blocking calls.
+pub struct Response {
+    context: Context,
+    status: u32,
+}
+
+impl Httpc {
+    /// Initialize a new service handle.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the service is already active.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::httpc::Httpc;
+    ///
+    /// let httpc = Httpc::new()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "httpcInit")]
+    pub fn new() -> crate::Result<Self> {
+        let _service_handler = ServiceReference::new(
+            "http:C",
+            &HTTPC_ACTIVE,
+            || {
+                ResultCode(unsafe { ctru_sys::httpcInit(0) })?;
+                Ok(())
+            },
+            || unsafe { ctru_sys::httpcExit() },
+        )?;
+
+        Ok(Self { _service_handler })
+    }
+}
+
+impl Request {
+    /// Open a request to `url`, using the system's default proxy settings.
+    ///
+    /// Each request uses its own session with the HTTP module, so it can be sent (and awaited) independently of the others.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `url` contains NUL bytes or if the HTTP module refused the request
+    /// (e.g. because of a malformed URL, or because too many requests are open).
+    #[doc(alias = "httpcOpenContext")]
+    pub fn new(_httpc: &Httpc, method: Method, url: &str) -> crate::Result<Self> {
+        let url =
+            CString::new(url).map_err(|_| Error::Other(String::from("URL contains NUL bytes")))?;
+        let mut context = ctru_sys::httpcContext::default();
+
+        ResultCode(unsafe {
+            ctru_sys::httpcOpenContext(&mut context, method as u32, url.as_ptr(), 1)
+        })?;
+
+        Ok(Self {
+            context: Context(context),
+        })
+    }
+
+    /// Add a header field to the request.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` or `value` contain NUL bytes.
+    #[doc(alias = "httpcAddRequestHeaderField")]
+    pub fn add_header(&mut self, name: &str, value: &str) -> crate::Result<()> {
+        let name = CString::new(name)
+            .map_err(|_| Error::Other(String::from("header name contains NUL bytes")))?;
+        let value = CString::new(value)
+            .map_err(|_| Error::Other(String::from("header value contains NUL bytes")))?;
+
+        ResultCode(unsafe {
+            ctru_sys::httpcAddRequestHeaderField(&mut self.context.0, name.as_ptr(), value.as_ptr())
+        })?;
+
+        Ok(())
+    }
+
+    /// Set the body of the request (e.g. for [`Method::Post`]).
+    #[doc(alias = "httpcAddPostDataRaw")]
+    pub fn set_body(&mut self, body: &[u8]) -> crate::Result<()> {
+        let len = u32::try_from(body.len())
+            .map_err(|_| Error::Other(String::from("request body is too large")))?;
+
+        ResultCode(unsafe {
+            ctru_sys::httpcAddPostDataRaw(&mut self.context.0, body.as_ptr().cast(), len)
+        })?;
+
+        Ok(())
+    }
+
+    /// Don't verify the server's certificate for `https` requests.
+    ///
+    /// # Notes
+    ///
+    /// The system's root certificates are outdated, so many servers can only be reached this way.
+    /// It makes the connection vulnerable to man-in-the-middle attacks.
+    #[doc(alias = "httpcSetSSLOpt")]
+    pub fn disable_certificate_verification(&mut self) -> crate::Result<()> {
+        ResultCode(unsafe {
+            ctru_sys::httpcSetSSLOpt(&mut self.context.0, ctru_sys::SSLCOPT_DisableVerify)
+        })?;
+
+        Ok(())
+    }
+
+    /// Send the request and wait for the response's status code and headers, blocking the thread.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request couldn't be sent or if no response was received.
+    #[doc(alias = "httpcBeginRequest")]
+    #[doc(alias = "httpcGetResponseStatusCode")]
+    pub fn send_blocking(mut self) -> crate::Result<Response> {
+        ResultCode(unsafe { ctru_sys::httpcBeginRequest(&mut self.context.0) })?;
+
+        let mut status = 0;
+        ResultCode(unsafe {
+            ctru_sys::httpcGetResponseStatusCode(&mut self.context.0, &mut status)
+        })?;
+
+        Ok(Response {
+            context: self.context,
+            status,
+        })
+    }
+
+    /// Send the request and wait for the response's status code and headers.
+    ///
+    /// This future must be run by a [`Runtime`](crate::runtime::Runtime), which checks the request every few milliseconds.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request couldn't be sent or if no response was received.
+    #[doc(alias = "httpcBeginRequest")]
+    #[doc(alias = "httpcGetResponseStatusCodeTimeout")]
+    pub async fn send(mut self) -> crate::Result<Response> {
+        ResultCode(unsafe { ctru_sys::httpcBeginRequest(&mut self.context.0) })?;
+
+        let mut status = 0;
+
+        loop {
+            let result = unsafe {
+                ctru_sys::httpcGetResponseStatusCodeTimeout(&mut self.context.0, &mut status, 0)
+            };
+
+            if result as u32 != ctru_sys::HTTPC_RESULTCODE_TIMEDOUT {
+                ResultCode(result)?;
+                break;
+            }
+
+            runtime::sleep(POLL_INTERVAL).await;
+        }
+
+        Ok(Response {
+            context: self.context,
+            status,
+        })
+    }
+}
+
+impl Response {
+    /// Returns the status code of the response (e.g. 200).
+    pub fn status(&self) -> u32 {
+        self.status
+    }
+
+    /// Returns the value of the header field `name`, or [`None`] if the response doesn't contain it.
+    #[doc(alias = "httpcGetResponseHeader")]
+    pub fn header(&mut self, name: &str) -> Option<String> {
+        let name = CString::new(name).ok()?;
+        let mut value = vec![0u8; HEADER_VALUE_SIZE];
+
+        let result = unsafe {
+            ctru_sys::httpcGetResponseHeader(
+                &mut self.context.0,
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len() as u32,
+            )
+        };
+
+        if ctru_sys::R_FAILED(result) {
+            return None;
+        }
+
+        let len = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+        value.truncate(len);
+
+        Some(String::from_utf8_lossy(&value).into_owned())
+    }
+
+    /// Returns the size of the body, if the server announced it.
+    #[doc(alias = "httpcGetDownloadSizeState")]
+    pub fn content_length(&mut self) -> Option<u64> {
+        let (_, total) = self.download_state().ok()?;
+        (total != 0).then_some(u64::from(total))
+    }
+
+    /// Read part of the body into `buffer` without blocking the runtime, returning the amount of bytes read.
+    ///
+    /// Returns `0` once the whole body was read.
+    #[doc(alias = "httpcReceiveDataTimeout")]
+    pub async fn read_chunk(&mut self, buffer: &mut [u8]) -> crate::Result<usize> {
+        loop {
+            let read = self.receive(buffer, Some(0))?;
+
+            match read {
+                Some(read) => return Ok(read),
+                None => runtime::sleep(POLL_INTERVAL).await,
+            }
+        }
+    }
+
+    /// Read the whole body.
+    pub async fn bytes(mut self) -> crate::Result<Vec<u8>> {
+        let mut body = Vec::new();
+        self.download(&mut body, &mut ()).await?;
+
+        Ok(body)
+    }
+
+    /// Write the whole body to `writer`, reporting the amount of bytes downloaded to `progress`, and return the size of the body.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`Error::Cancelled`] if `progress` asked to stop, or another error if receiving the body
+    /// or writing to `writer` failed.
+    pub async fn download(
+        &mut self,
+        mut writer: impl Write,
+        progress: &mut impl Progress,
+    ) -> crate::Result<u64> {
+        let total = self.content_length();
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut done = 0;
+
+        progress.update(0, total);
+
+        loop {
+            if progress.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+
+            let read = self.read_chunk(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+
+            writer.write_all(&buffer[..read])?;
+
+            done += read as u64;
+            progress.update(done, total);
+        }
+
+        writer.flush()?;
+
+        Ok(done)
+    }
+
+    fn download_state(&mut self) -> crate::Result<(u32, u32)> {
+        let mut downloaded = 0;
+        let mut total = 0;
+
+        ResultCode(unsafe {
+            ctru_sys::httpcGetDownloadSizeState(&mut self.context.0, &mut downloaded, &mut total)
+        })?;
+
+        Ok((downloaded, total))
+    }
+
+    /// Receive data into `buffer`, waiting up to `timeout` nanoseconds (or indefinitely if [`None`]).
+    ///
+    /// Returns [`None`] if the timeout expired before any data was received.
+    fn receive(&mut self, buffer: &mut [u8], timeout: Option<u64>) -> crate::Result<Option<usize>> {
+        if buffer.is_empty() {
+            return Ok(Some(0));
+        }
+
+        let (before, _) = self.download_state()?;
+        let size = u32::try_from(buffer.len()).unwrap_or(u32::MAX);
+
+        let result = unsafe {
+            match timeout {
+                Some(timeout) => ctru_sys::httpcReceiveDataTimeout(
+                    &mut self.context.0,
+                    buffer.as_mut_ptr(),
+                    size,
+                    timeout,
+                ),
+                None => ctru_sys::httpcReceiveData(&mut self.context.0, buffer.as_mut_ptr(), size),
+            }
+        };
+
+        // The module reports partial reads through the download state rather than through the result.
+        let (after, _) = self.download_state()?;
+        let read = (after - before) as usize;
+
+        match result as u32 {
+            // The buffer is full, but there is more to download.
+            ctru_sys::HTTPC_RESULTCODE_DOWNLOADPENDING => Ok(Some(read)),
+            ctru_sys::HTTPC_RESULTCODE_TIMEDOUT => Ok((read != 0).then_some(read)),
+            _ => {
+                ResultCode(result)?;
+                Ok(Some(read))
+            }
+        }
+    }
+}
+
+impl Read for Response {
+    #[doc(alias = "httpcReceiveData")]
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self.receive(buffer, None) {
+            Ok(read) => Ok(read.unwrap_or(0)),
+            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e.to_string())),
+        }
+    }
+}
@@ -67,6 +67,7 @@ impl IrUser {
         send_packet_count: usize,
     ) -> crate::Result<Self> {
         let service_reference = ServiceReference::new(
+            "ir:USER",
             &IR_USER_ACTIVE,
             || unsafe {
                 // Get the ir:USER service handle
@@ -156,6 +157,11 @@ impl IrUser {
             },
         )?;
 
+        service_reference.set_handle_getter(|| {
+            let state = IR_USER_STATE.try_lock().ok()?;
+            state.as_ref().map(|state| state.service_handle)
+        });
+
         Ok(IrUser {
             _service_reference: service_reference,
         })
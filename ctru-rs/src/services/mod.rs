@@ -11,21 +11,26 @@
 //!
 //! In [`ctru-rs`](crate) some services only allow a single handle to be created at a time, to ensure a safe and controlled environment.
 
+pub mod act;
 pub mod am;
 pub mod apt;
 pub mod cam;
 pub mod cfgu;
+pub mod frd;
 pub mod fs;
 pub mod gfx;
 pub mod gspgpu;
 pub mod hid;
+pub mod httpc;
 pub mod ir_user;
 pub mod ndsp;
+pub mod nim;
 pub mod ps;
 mod reference;
 pub mod soc;
 pub mod sslc;
 pub mod svc;
+pub mod uds;
 
 cfg_if::cfg_if! {
     if #[cfg(all(feature = "romfs", romfs_exists))] {
@@ -0,0 +1,383 @@
+//! Audio latency estimation and measurement.
+//!
+//! Rhythm games need to know how long it takes between queueing a sound and the player hearing it, to line up their
+//! timing windows with the music. [`Ndsp::latency_info()`] estimates it from the DSP's pipeline, and [`measure_round_trip()`]
+//! measures it on the console by playing clicks on the speakers and listening for them with the microphone.
+#![doc(alias = "calibration")]
+
+use std::alloc::{self, Layout};
+use std::time::{Duration, Instant};
+
+use super::wave::{Status, Wave};
+use super::{AudioFormat, Channel, Ndsp, OutputMode, FRAME_DURATION};
+use crate::error::ResultCode;
+use crate::linear::LinearAllocator;
+use crate::Error;
+
+/// Frames between a wave being queued and its first samples leaving the DSP: the frame being prepared by `libctru`
+/// at each DSP interrupt, the frame being mixed by the DSP and the frame being played.
+const PIPELINE_FRAMES: u32 = 3;
+
+/// Extra frames of filtering applied by the DSP to produce virtual surround sound.
+const SURROUND_FRAMES: u32 = 1;
+
+/// Sample rate of the DSP's output.
+const DSP_SAMPLE_RATE: f32 = 32728.498;
+
+/// Sample rate used to record the microphone.
+const MIC_SAMPLE_RATE: f32 = 32728.498;
+
+/// Size of the microphone's shared buffer, about a second and a half of samples.
+const MIC_BUFFER_SIZE: usize = 0x18000;
+
+/// Length of each click, in samples.
+const CLICK_SAMPLES: usize = 328;
+
+/// Longest wait for a click to be heard before giving up.
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time spent listening to the ambient noise before each click.
+const NOISE_DURATION: Duration = Duration::from_millis(50);
+
+/// Lowest amplitude considered to be a click, whatever the ambient noise.
+const MIN_THRESHOLD: i16 = 2000;
+
+/// Estimated output latency of the DSP, returned by [`Ndsp::latency_info()`].
+///
+/// # Notes
+///
+/// The estimate doesn't account for the speakers, audio jack or headphones (Bluetooth headphones can't be used with the console,
+/// but some audio jack adapters add their own delay). Use [`measure_round_trip()`] to calibrate from an actual measurement.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LatencyInfo {
+    /// Duration of an audio frame, the granularity at which the DSP starts playing waves.
+    pub frame: Duration,
+    /// Time spent by a frame in the DSP's pipeline before being output.
+    pub pipeline: Duration,
+    /// Time added by the processing of the [`OutputMode`].
+    pub output_mode: Duration,
+    /// Time needed to play the samples queued before the next wave (see [`LatencyInfo::with_queued()`]).
+    pub queued: Duration,
+}
+
+impl LatencyInfo {
+    /// Returns the estimated time between queueing a wave and its first sample being output, in the worst case
+    /// (the wave being queued right after the start of a frame).
+    pub fn total(&self) -> Duration {
+        self.frame + self.pipeline + self.output_mode + self.queued
+    }
+
+    /// Account for `samples` samples at `sample_rate` Hz queued on the channel before the next wave, which must play first.
+    pub fn with_queued(mut self, samples: usize, sample_rate: f32) -> Self {
+        if sample_rate > 0.0 {
+            self.queued += Duration::from_secs_f32(samples as f32 / sample_rate);
+        }
+
+        self
+    }
+
+    /// Account for the given waves queued on a channel playing at `sample_rate` Hz before the next wave.
+    ///
+    /// Only waves which are queued or playing are counted. As the playback position of the playing wave isn't known,
+    /// it's counted whole, which overestimates the latency by up to the duration of that wave.
+    pub fn with_queued_waves(self, waves: &[&Wave], sample_rate: f32) -> Self {
+        let samples = waves
+            .iter()
+            .filter(|wave| matches!(wave.status(), Status::Queued | Status::Playing))
+            .map(|wave| wave.sample_count())
+            .sum();
+
+        self.with_queued(samples, sample_rate)
+    }
+}
+
+impl Ndsp {
+    /// Returns an estimate of the output latency of the DSP with the current [`OutputMode`], without any queued waves.
+    ///
+    /// See [`LatencyInfo::with_queued()`] to add the waves already queued on a channel.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::ndsp::Ndsp;
+    /// let ndsp = Ndsp::new()?;
+    ///
+    /// let latency = ndsp.latency_info().with_queued(4096, 44100.);
+    /// println!("Sounds are heard about {:?} after being queued", latency.total());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn latency_info(&self) -> LatencyInfo {
+        let output_frames = match self.output_mode {
+            OutputMode::Surround => SURROUND_FRAMES,
+            OutputMode::Mono | OutputMode::Stereo => 0,
+        };
+
+        LatencyInfo {
+            frame: FRAME_DURATION,
+            pipeline: FRAME_DURATION * PIPELINE_FRAMES,
+            output_mode: FRAME_DURATION * output_frames,
+            queued: Duration::ZERO,
+        }
+    }
+}
+
+/// Results of [`measure_round_trip()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RoundTrip {
+    /// Shortest measured latency.
+    pub min: Duration,
+    /// Median of the measured latencies, the most reliable value to calibrate with.
+    pub median: Duration,
+    /// Longest measured latency.
+    pub max: Duration,
+}
+
+/// Measure the round-trip latency from queueing a sound to hearing it, by playing `attempts` clicks on `channel`
+/// and detecting them with the microphone.
+///
+/// The result includes the output latency (see [`Ndsp::latency_info()`]) as well as the input latency of the microphone,
+/// which is close to what a player tapping along to the music experiences.
+///
+/// # Notes
+///
+/// The measurement needs a quiet room, the volume slider turned up and no headphones plugged in, so the microphone can hear
+/// the speakers. The channel is reset before returning.
+///
+/// # Errors
+///
+/// This function will return an error if the microphone couldn't be used (e.g. it's already in use by the application),
+/// or if a click wasn't heard within a second.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::ndsp::latency::measure_round_trip;
+/// use ctru::services::ndsp::Ndsp;
+/// let ndsp = Ndsp::new()?;
+/// let mut channel = ndsp.channel(0)?;
+///
+/// let round_trip = measure_round_trip(&ndsp, &mut channel, 5)?;
+/// println!("Round-trip latency: {:?}", round_trip.median);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "micInit")]
+pub fn measure_round_trip(
+    _ndsp: &Ndsp,
+    channel: &mut Channel,
+    attempts: usize,
+) -> crate::Result<RoundTrip> {
+    let mic = Microphone::new()?;
+
+    channel.reset();
+    channel.set_format(AudioFormat::PCM16Mono);
+    channel.set_sample_rate(DSP_SAMPLE_RATE);
+
+    let mut click = Box::new_in([0u8; CLICK_SAMPLES * 2], LinearAllocator);
+    for (i, sample) in click.chunks_exact_mut(2).enumerate() {
+        // Square wave at about 2 kHz, which the microphone picks up well.
+        let value: i16 = if (i / 8) % 2 == 0 {
+            i16::MAX
+        } else {
+            -i16::MAX
+        };
+        sample.copy_from_slice(&value.to_le_bytes());
+    }
+    let mut wave = Wave::new(click, AudioFormat::PCM16Mono, false);
+
+    let mut latencies = Vec::with_capacity(attempts);
+
+    let result = (|| {
+        for _ in 0..attempts {
+            // Measure the ambient noise to set the detection threshold above it.
+            let noise_start = mic.position();
+            std::thread::sleep(NOISE_DURATION);
+            let noise = mic.peak(noise_start, mic.position());
+            let threshold = noise.saturating_mul(4).max(MIN_THRESHOLD);
+
+            let start = mic.position();
+            channel
+                .queue_wave(&mut wave)
+                .map_err(|e| Error::Other(e.to_string()))?;
+
+            let heard = mic.listen(start, threshold)?;
+            latencies.push(Duration::from_secs_f32(heard as f32 / MIC_SAMPLE_RATE));
+
+            // Let the click and its echoes fade away before the next one.
+            while channel.is_playing() {
+                std::thread::sleep(FRAME_DURATION);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        Ok(())
+    })();
+
+    channel.reset();
+    result?;
+
+    Ok(summarize(latencies).unwrap_or(RoundTrip {
+        min: Duration::ZERO,
+        median: Duration::ZERO,
+        max: Duration::ZERO,
+    }))
+}
+
+fn summarize(mut latencies: Vec<Duration>) -> Option<RoundTrip> {
+    latencies.sort();
+
+    Some(RoundTrip {
+        min: *latencies.first()?,
+        median: latencies[latencies.len() / 2],
+        max: *latencies.last()?,
+    })
+}
+
+/// Microphone sampling into a shared ring buffer, stopped when dropped.
+struct Microphone {
+    buffer: *mut u8,
+    /// Size of the sample data in the buffer, in bytes (the end of the buffer holds the position of the last sample).
+    data_size: usize,
+}
+
+impl Microphone {
+    fn new() -> crate::Result<Self> {
+        let layout = Self::layout();
+
+        // The microphone's buffer is shared with the service, which needs it page-aligned.
+        let buffer = unsafe { alloc::alloc_zeroed(layout) };
+        if buffer.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+
+        if let Err(e) = ResultCode(unsafe { ctru_sys::micInit(buffer, MIC_BUFFER_SIZE as u32) }) {
+            unsafe { alloc::dealloc(buffer, layout) };
+            return Err(e.into());
+        }
+
+        let mic = Self {
+            buffer,
+            data_size: unsafe { ctru_sys::micGetSampleDataSize() } as usize,
+        };
+
+        ResultCode(unsafe {
+            ctru_sys::MICU_StartSampling(
+                ctru_sys::MICU_ENCODING_PCM16_SIGNED,
+                ctru_sys::MICU_SAMPLE_RATE_32730,
+                0,
+                mic.data_size as u32,
+                true,
+            )
+        })?;
+
+        Ok(mic)
+    }
+
+    fn layout() -> Layout {
+        Layout::from_size_align(MIC_BUFFER_SIZE, 0x1000).unwrap()
+    }
+
+    /// Returns the byte offset at which the microphone will write its next sample.
+    fn position(&self) -> usize {
+        unsafe { ctru_sys::micGetLastSampleOffset() as usize }
+    }
+
+    fn sample(&self, offset: usize) -> i16 {
+        let offset = offset % self.data_size;
+        let bytes = unsafe { [*self.buffer.add(offset), *self.buffer.add(offset + 1)] };
+
+        i16::from_le_bytes(bytes)
+    }
+
+    /// Returns the highest amplitude recorded between two positions.
+    fn peak(&self, start: usize, end: usize) -> i16 {
+        let length = (end + self.data_size - start) % self.data_size;
+
+        (0..length / 2)
+            .map(|i| self.sample(start + i * 2).saturating_abs())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Wait for a sample louder than `threshold` to be recorded after `start`, returning the amount of samples before it.
+    fn listen(&self, start: usize, threshold: i16) -> crate::Result<usize> {
+        let deadline = Instant::now() + LISTEN_TIMEOUT;
+        let mut scanned = 0;
+
+        while Instant::now() < deadline {
+            let recorded = (self.position() + self.data_size - start) % self.data_size / 2;
+
+            if let Some(index) = first_above(
+                (scanned..recorded).map(|i| self.sample(start + i * 2)),
+                threshold,
+            ) {
+                return Ok(scanned + index);
+            }
+
+            scanned = recorded;
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        Err(Error::Other(String::from(
+            "the click wasn't heard by the microphone",
+        )))
+    }
+}
+
+impl Drop for Microphone {
+    fn drop(&mut self) {
+        unsafe {
+            ctru_sys::MICU_StopSampling();
+            ctru_sys::micExit();
+            alloc::dealloc(self.buffer, Self::layout());
+        }
+    }
+}
+
+/// Returns the index of the first sample with an amplitude above `threshold`.
+fn first_above(samples: impl Iterator<Item = i16>, threshold: i16) -> Option<usize> {
+    samples
+        .enumerate()
+        .find(|(_, sample)| sample.saturating_abs() > threshold)
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn onset_and_summary() {
+        let samples = [10, -40, 30, -2500, 3000, 12];
+        assert_eq!(first_above(samples.into_iter(), MIN_THRESHOLD), Some(3));
+        assert_eq!(first_above(samples.into_iter(), i16::MAX), None);
+
+        let ms = Duration::from_millis;
+        assert_eq!(
+            summarize(vec![ms(40), ms(20), ms(30)]),
+            Some(RoundTrip {
+                min: ms(20),
+                median: ms(30),
+                max: ms(40),
+            })
+        );
+        assert_eq!(summarize(Vec::new()), None);
+
+        let info = LatencyInfo {
+            frame: ms(5),
+            pipeline: ms(15),
+            output_mode: Duration::ZERO,
+            queued: Duration::ZERO,
+        };
+        assert_eq!(info.with_queued(16000, 32000.).total(), ms(520));
+    }
+}
@@ -14,20 +14,28 @@
 // this module are `no_run`, since Citra doesn't provide a stub for the DSP firmware:
 // https://github.com/citra-emu/citra/issues/6111
 
+pub mod latency;
+pub mod stream;
 pub mod wave;
 use wave::{Status, Wave};
 
 use crate::error::ResultCode;
 use crate::services::ServiceReference;
+use crate::shutdown::Stage;
 
 use std::cell::{RefCell, RefMut};
 use std::default::Default;
 use std::error;
 use std::fmt;
-use std::sync::Mutex;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
 
 const NUMBER_OF_CHANNELS: u8 = 24;
 
+/// Approximate duration of an NDSP audio frame (160 samples at 32728 Hz).
+const FRAME_DURATION: Duration = Duration::from_micros(4889);
+
 /// Audio output mode.
 #[doc(alias = "ndspOutputMode")]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -41,9 +49,10 @@ pub enum OutputMode {
     Surround = ctru_sys::NDSP_OUTPUT_SURROUND,
 }
 
-/// PCM formats supported by the audio engine.
+/// Audio formats supported by the audio engine.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AudioFormat {
     /// PCM 8bit single-channel.
     PCM8Mono = ctru_sys::NDSP_FORMAT_MONO_PCM8,
@@ -53,6 +62,11 @@ pub enum AudioFormat {
     PCM8Stereo = ctru_sys::NDSP_FORMAT_STEREO_PCM8,
     /// PCM 16bit interleaved dual-channel.
     PCM16Stereo = ctru_sys::NDSP_FORMAT_STEREO_PCM16,
+    /// DSP-ADPCM single-channel.
+    ///
+    /// The DSP only decodes single-channel ADPCM: stereo sounds must be played on two channels.
+    /// Waves in this format are usually built with [`Wave::with_adpcm()`], to provide the decoding parameters.
+    ADPCMMono = ctru_sys::NDSP_FORMAT_MONO_ADPCM,
 }
 
 /// Representation of the volume mix for a channel.
@@ -96,6 +110,39 @@ pub enum Error {
     WaveBusy(u8),
     /// The sample amount requested was larger than the maximum.
     SampleCountOutOfBounds(usize, usize),
+    /// The channel with the specified ID still has waves queued.
+    ChannelBusy(u8),
+}
+
+/// Wave waiting in [`SCHEDULE`] to be queued on a channel at a given frame.
+struct ScheduledWave {
+    channel: u8,
+    frame: u32,
+    wave: *mut ctru_sys::ndspWaveBuf,
+}
+
+// SAFETY: the wave is only accessed while holding the lock of `SCHEDULE`, and `Wave` removes itself from it when dropped.
+unsafe impl Send for ScheduledWave {}
+
+/// Waves scheduled with [`Channel::queue_at()`], queued by [`frame_callback()`] on the NDSP thread.
+///
+/// The callback can't unwind, so the lock's poisoning is ignored everywhere: the schedule is never left half-updated.
+static SCHEDULE: Mutex<Vec<ScheduledWave>> = Mutex::new(Vec::new());
+
+/// How [`Channel::reconfigure()`] handles waves still queued on the channel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum QueuePolicy {
+    /// Return [`Error::ChannelBusy`] if any wave is queued.
+    #[default]
+    Fail,
+    /// Wait for all queued waves to finish playing.
+    ///
+    /// # Notes
+    ///
+    /// A looping wave never finishes playing, so it must not be queued when using this policy.
+    Drain,
+    /// Stop playback and clear the queue.
+    Clear,
 }
 
 /// NDSP Channel representation.
@@ -115,7 +162,28 @@ pub enum Error {
 /// The handle to a channel can be retrieved with [`Ndsp::channel()`]
 pub struct Channel<'ndsp> {
     id: u8,
-    _rf: RefMut<'ndsp, ()>, // we don't need to hold any data
+    config: RefMut<'ndsp, ChannelConfig>,
+}
+
+/// Parameters last set on a channel, since the DSP can't be queried for them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct ChannelConfig {
+    format: AudioFormat,
+    sample_rate: f32,
+    interpolation: InterpolationType,
+    mix: AudioMix,
+}
+
+impl Default for ChannelConfig {
+    // Values set by `ndspChnReset()` and `ndspChnInitParams()`.
+    fn default() -> Self {
+        Self {
+            format: AudioFormat::PCM16Mono,
+            sample_rate: 1.,
+            interpolation: InterpolationType::Polyphase,
+            mix: AudioMix::default(),
+        }
+    }
 }
 
 static NDSP_ACTIVE: Mutex<()> = Mutex::new(());
@@ -125,7 +193,8 @@ static NDSP_ACTIVE: Mutex<()> = Mutex::new(());
 /// Only one handle for this service can exist at a time.
 pub struct Ndsp {
     _service_handler: ServiceReference,
-    channel_flags: [RefCell<()>; NUMBER_OF_CHANNELS as usize],
+    channel_flags: [RefCell<ChannelConfig>; NUMBER_OF_CHANNELS as usize],
+    output_mode: OutputMode,
 }
 
 impl Ndsp {
@@ -152,10 +221,13 @@ impl Ndsp {
     /// ```
     #[doc(alias = "ndspInit")]
     pub fn new() -> crate::Result<Self> {
-        let _service_handler = ServiceReference::new(
+        let _service_handler = ServiceReference::with_stage(
+            "dsp::DSP",
             &NDSP_ACTIVE,
+            Stage::Audio,
             || {
                 ResultCode(unsafe { ctru_sys::ndspInit() })?;
+                unsafe { ctru_sys::ndspSetCallback(Some(frame_callback), std::ptr::null_mut()) };
 
                 Ok(())
             },
@@ -167,6 +239,7 @@ impl Ndsp {
         Ok(Self {
             _service_handler,
             channel_flags: Default::default(),
+            output_mode: OutputMode::Stereo,
         })
     }
 
@@ -197,7 +270,7 @@ impl Ndsp {
             Some(ref_cell) => {
                 let flag = ref_cell.try_borrow_mut();
                 match flag {
-                    Ok(_rf) => Ok(Channel { id, _rf }),
+                    Ok(config) => Ok(Channel { id, config }),
                     Err(_) => Err(Error::ChannelAlreadyInUse(id)),
                 }
             }
@@ -225,7 +298,106 @@ impl Ndsp {
     #[doc(alias = "ndspSetOutputMode")]
     pub fn set_output_mode(&mut self, mode: OutputMode) {
         unsafe { ctru_sys::ndspSetOutputMode(mode.into()) };
+        self.output_mode = mode;
+    }
+
+    /// Set the master volume of the audio output, between 0 (muted) and 1 (full volume). Defaults to 1.
+    #[doc(alias = "ndspSetMasterVol")]
+    pub fn set_master_volume(&mut self, volume: f32) {
+        unsafe { ctru_sys::ndspSetMasterVol(volume) };
+    }
+
+    /// Enable or disable an auxiliary output device.
+    ///
+    /// Auxiliary devices mix the volumes sent to them by each channel (see [`AudioMix::set_aux_front()`]) into a separate bus,
+    /// e.g. to process them with a reverb or echo effect before they are added to the output.
+    #[doc(alias = "ndspAuxSetEnable")]
+    pub fn set_aux_enabled(&mut self, id: AuxDevice, enable: bool) {
+        unsafe { ctru_sys::ndspAuxSetEnable(id as _, enable) };
+    }
+
+    /// Set the volume of an auxiliary output device in the final mix, between 0 and 1.
+    #[doc(alias = "ndspAuxSetVolume")]
+    pub fn set_aux_volume(&mut self, id: AuxDevice, volume: f32) {
+        unsafe { ctru_sys::ndspAuxSetVolume(id as _, volume) };
+    }
+
+    /// Set whether the front volumes of an auxiliary output device bypass the surround sound processing.
+    #[doc(alias = "ndspAuxSetFrontBypass")]
+    pub fn set_aux_front_bypass(&mut self, id: AuxDevice, bypass: bool) {
+        unsafe { ctru_sys::ndspAuxSetFrontBypass(id as _, bypass) };
+    }
+
+    /// Returns the number of audio frames processed by the DSP since the service was initialized.
+    ///
+    /// The counter increases by one every frame (160 samples at the DSP's rate of 32728 Hz, about 4.9 ms) and wraps around.
+    /// See [`Channel::queue_at()`] to start waves on a given frame.
+    #[doc(alias = "ndspGetFrameCount")]
+    pub fn frame_count(&self) -> u32 {
+        unsafe { ctru_sys::ndspGetFrameCount() }
+    }
+
+    /// Returns the first frame (see [`Ndsp::frame_count()`]) starting at least `delay` from now.
+    pub fn frame_after(&self, delay: Duration) -> u32 {
+        let frames = delay.as_nanos().div_ceil(FRAME_DURATION.as_nanos());
+
+        self.frame_count().wrapping_add(frames as u32)
+    }
+}
+
+/// Called by `libctru` on the NDSP thread at every audio frame, to queue the waves scheduled for it and refill the driven streams.
+unsafe extern "C" fn frame_callback(_data: *mut libc::c_void) {
+    let frame = ctru_sys::ndspGetFrameCount();
+    let mut schedule = SCHEDULE.lock().unwrap_or_else(PoisonError::into_inner);
+
+    schedule.retain(|scheduled| {
+        if !frame_reached(frame, scheduled.frame) {
+            return true;
+        }
+
+        ctru_sys::ndspChnWaveBufAdd(scheduled.channel.into(), scheduled.wave);
+        false
+    });
+    drop(schedule);
+
+    stream::drive_streams();
+}
+
+/// Returns whether the (wrapping) frame counter reached `target`, considering frames up to half the counter's range ahead as future ones.
+fn frame_reached(frame: u32, target: u32) -> bool {
+    frame.wrapping_sub(target) < 1 << 31
+}
+
+/// Remove a wave from the schedule, returning whether it was waiting in it.
+///
+/// Waves removed from the schedule are marked as [`Done`](Status::Done), since they are never going to play.
+pub(crate) fn unschedule(wave: *mut ctru_sys::ndspWaveBuf) -> bool {
+    let mut schedule = SCHEDULE.lock().unwrap_or_else(PoisonError::into_inner);
+    let length = schedule.len();
+
+    schedule.retain(|scheduled| scheduled.wave != wave);
+
+    if schedule.len() == length {
+        return false;
     }
+
+    unsafe { (*wave).status = ctru_sys::NDSP_WBUF_DONE as u8 };
+    true
+}
+
+/// Remove all the waves scheduled on a channel from the schedule, marking them as [`Done`](Status::Done).
+fn unschedule_channel(id: u8) {
+    SCHEDULE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|scheduled| {
+            if scheduled.channel != id {
+                return true;
+            }
+
+            unsafe { (*scheduled.wave).status = ctru_sys::NDSP_WBUF_DONE as u8 };
+            false
+        });
 }
 
 impl Channel<'_> {
@@ -248,7 +420,9 @@ impl Channel<'_> {
     /// ```
     #[doc(alias = "ndspChnReset")]
     pub fn reset(&mut self) {
+        unschedule_channel(self.id);
         unsafe { ctru_sys::ndspChnReset(self.id.into()) };
+        *self.config = ChannelConfig::default();
     }
 
     /// Initialize the channel's parameters with default values.
@@ -271,6 +445,7 @@ impl Channel<'_> {
     #[doc(alias = "ndspChnInitParams")]
     pub fn init_parameters(&mut self) {
         unsafe { ctru_sys::ndspChnInitParams(self.id.into()) };
+        *self.config = ChannelConfig::default();
     }
 
     /// Returns whether the channel is playing any audio.
@@ -349,7 +524,9 @@ impl Channel<'_> {
         (unsafe { ctru_sys::ndspChnGetSamplePos(self.id.into()) }) as usize
     }
 
-    /// Returns the channel's current wave sequence's id.
+    /// Returns the sequence ID of the wave currently playing on the channel.
+    ///
+    /// See [`Wave::sequence_id()`] for more details about sequence IDs.
     #[doc(alias = "ndspChnGetWaveBufSeq")]
     pub fn wave_sequence_id(&self) -> u16 {
         unsafe { ctru_sys::ndspChnGetWaveBufSeq(self.id.into()) }
@@ -404,6 +581,12 @@ impl Channel<'_> {
     #[doc(alias = "ndspChnSetFormat")]
     pub fn set_format(&mut self, format: AudioFormat) {
         unsafe { ctru_sys::ndspChnSetFormat(self.id.into(), format.into()) };
+        self.config.format = format;
+    }
+
+    /// Returns the channel's output format, as last set with [`Channel::set_format()`].
+    pub fn format(&self) -> AudioFormat {
+        self.config.format
     }
 
     /// Set the channel's interpolation mode.
@@ -427,6 +610,12 @@ impl Channel<'_> {
     #[doc(alias = "ndspChnSetInterp")]
     pub fn set_interpolation(&mut self, interp_type: InterpolationType) {
         unsafe { ctru_sys::ndspChnSetInterp(self.id.into(), interp_type.into()) };
+        self.config.interpolation = interp_type;
+    }
+
+    /// Returns the channel's interpolation mode, as last set with [`Channel::set_interpolation()`].
+    pub fn interpolation(&self) -> InterpolationType {
+        self.config.interpolation
     }
 
     /// Set the channel's volume mix.
@@ -452,7 +641,32 @@ impl Channel<'_> {
     /// ```
     #[doc(alias = "ndspChnSetMix")]
     pub fn set_mix(&mut self, mix: &AudioMix) {
-        unsafe { ctru_sys::ndspChnSetMix(self.id.into(), mix.as_raw().as_ptr().cast_mut()) }
+        unsafe { ctru_sys::ndspChnSetMix(self.id.into(), mix.as_raw().as_ptr().cast_mut()) };
+        self.config.mix = *mix;
+    }
+
+    /// Returns the channel's volume mix, as last set with [`Channel::set_mix()`].
+    ///
+    /// Use it to change a single volume of the mix, e.g. an [auxiliary send](AudioMix::set_aux_front):
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::ndsp::{AuxDevice, Ndsp};
+    /// let ndsp = Ndsp::new()?;
+    /// let mut channel_0 = ndsp.channel(0)?;
+    ///
+    /// // Send half of the channel's volume to the reverb on auxiliary device 0.
+    /// let mut mix = channel_0.mix();
+    /// mix.set_aux_front(0.5, 0.5, AuxDevice::Zero);
+    /// channel_0.set_mix(&mix);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mix(&self) -> AudioMix {
+        self.config.mix
     }
 
     /// Set the channel's rate of sampling in hertz.
@@ -476,9 +690,88 @@ impl Channel<'_> {
     #[doc(alias = "ndspChnSetRate")]
     pub fn set_sample_rate(&mut self, rate: f32) {
         unsafe { ctru_sys::ndspChnSetRate(self.id.into(), rate) };
+        self.config.sample_rate = rate;
+    }
+
+    /// Returns the channel's rate of sampling in hertz, as last set with [`Channel::set_sample_rate()`].
+    pub fn sample_rate(&self) -> f32 {
+        self.config.sample_rate
+    }
+
+    /// Change the channel's format, sample rate and interpolation type at once.
+    ///
+    /// Changing the format of a channel while waves are queued on it makes the DSP interpret their data incorrectly,
+    /// so the channel must be idle first: `policy` decides what to do with any queued wave.
+    /// The new settings are applied while the channel is paused, so that the DSP never plays a frame with only some of them applied.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`Error::ChannelBusy`] if waves are queued and `policy` is [`QueuePolicy::Fail`],
+    /// or if `policy` is [`QueuePolicy::Drain`] and the channel is paused (since its queue would never drain).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::ndsp::{AudioFormat, InterpolationType, Ndsp, QueuePolicy};
+    /// let ndsp = Ndsp::new()?;
+    /// let mut channel_0 = ndsp.channel(0)?;
+    ///
+    /// // Switch to stereo music, after the sound effects currently queued finish playing.
+    /// channel_0.reconfigure(
+    ///     AudioFormat::PCM16Stereo,
+    ///     44100.,
+    ///     InterpolationType::Polyphase,
+    ///     QueuePolicy::Drain,
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reconfigure(
+        &mut self,
+        format: AudioFormat,
+        rate: f32,
+        interpolation: InterpolationType,
+        policy: QueuePolicy,
+    ) -> std::result::Result<(), Error> {
+        if self.is_playing() {
+            match policy {
+                QueuePolicy::Fail => return Err(Error::ChannelBusy(self.id)),
+                QueuePolicy::Drain if self.is_paused() => return Err(Error::ChannelBusy(self.id)),
+                QueuePolicy::Drain => {
+                    while self.is_playing() {
+                        thread::sleep(FRAME_DURATION);
+                    }
+                }
+                QueuePolicy::Clear => self.clear_queue(),
+            }
+        }
+
+        let was_paused = self.is_paused();
+        self.set_paused(true);
+
+        self.set_format(format);
+        self.set_sample_rate(rate);
+        self.set_interpolation(interpolation);
+
+        self.set_paused(was_paused);
+
+        Ok(())
     }
 
-    // TODO: wrap ADPCM format helpers.
+    /// Set the coefficients used by the channel to decode [`AudioFormat::ADPCMMono`] audio.
+    ///
+    /// Queuing a wave built with [`Wave::with_adpcm()`] sets its coefficients automatically:
+    /// this function is only needed for ADPCM waves built with [`Wave::new()`].
+    #[doc(alias = "ndspChnSetAdpcmCoefs")]
+    pub fn set_adpcm_coefficients(&mut self, coefficients: &[i16; 16]) {
+        let mut coefficients = coefficients.map(|coefficient| coefficient as u16);
+
+        unsafe { ctru_sys::ndspChnSetAdpcmCoefs(self.id.into(), coefficients.as_mut_ptr()) };
+    }
 
     /// Clear the wave buffer queue and stop playback.
     ///
@@ -500,12 +793,18 @@ impl Channel<'_> {
     /// ```
     #[doc(alias = "ndspChnWaveBufClear")]
     pub fn clear_queue(&mut self) {
+        unschedule_channel(self.id);
         unsafe { ctru_sys::ndspChnWaveBufClear(self.id.into()) };
     }
 
     /// Add a wave buffer to the channel's queue.
     /// If there are no other buffers in queue, playback for this buffer will start.
     ///
+    /// # Notes
+    ///
+    /// Queued waves always finish playing in the order they were queued, and each of them gets the next (wrapping) sequence ID
+    /// of the channel (see [`Wave::sequence_id()`]). Once a wave is [`Done`](Status::Done), all waves queued before it are done too.
+    ///
     /// # Warning
     ///
     /// `libctru` expects the user to manually keep the info data (in this case [`Wave`]) alive during playback.
@@ -544,12 +843,74 @@ impl Channel<'_> {
             _ => (),
         }
 
+        if let Some(adpcm) = wave.adpcm_data() {
+            self.set_adpcm_coefficients(adpcm.coefficients());
+        }
+
         wave.set_channel(self.id);
 
         unsafe { ctru_sys::ndspChnWaveBufAdd(self.id.into(), &mut wave.raw_data) };
 
         Ok(())
     }
+
+    /// Add a wave buffer to the channel's queue once the DSP reaches the given frame (see [`Ndsp::frame_count()`]).
+    ///
+    /// The wave is queued by the NDSP thread at the start of that frame, so waves scheduled on consecutive beats
+    /// start exactly the same amount of frames apart, whatever the application's main loop is doing in the meantime.
+    /// If the frame already passed, the wave is queued at the next frame.
+    ///
+    /// # Notes
+    ///
+    /// The wave starts playing immediately only if the channel's queue is empty by then: otherwise, it plays after the waves before it
+    /// (see [`Channel::queue_wave()`]). Until the frame is reached, the wave is reported as [`Queued`](Status::Queued).
+    /// Clearing the queue or dropping the wave cancels it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #![feature(allocator_api)]
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// # use ctru::linear::LinearAllocator;
+    /// use ctru::services::ndsp::wave::Wave;
+    /// use ctru::services::ndsp::{AudioFormat, Ndsp};
+    /// use std::time::Duration;
+    /// let ndsp = Ndsp::new()?;
+    /// let mut channel_0 = ndsp.channel(0)?;
+    ///
+    /// # let audio_data = Box::new_in([0u8; 96], LinearAllocator);
+    /// let mut tick = Wave::new(audio_data, AudioFormat::PCM16Mono, false);
+    ///
+    /// // Metronome tick in exactly half a second.
+    /// let frame = ndsp.frame_after(Duration::from_millis(500));
+    /// channel_0.queue_at(&mut tick, frame)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "ndspSetCallback")]
+    pub fn queue_at(&mut self, wave: &mut Wave, frame: u32) -> std::result::Result<(), Error> {
+        match wave.status() {
+            Status::Playing | Status::Queued => return Err(Error::WaveBusy(self.id)),
+            _ => (),
+        }
+
+        wave.set_channel(self.id);
+        wave.raw_data.status = ctru_sys::NDSP_WBUF_QUEUED as u8;
+
+        SCHEDULE
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(ScheduledWave {
+                channel: self.id,
+                frame,
+                wave: &mut wave.raw_data,
+            });
+
+        Ok(())
+    }
 }
 
 /// Functions to handle audio filtering.
@@ -646,11 +1007,26 @@ impl AudioFormat {
     ///
     /// - 8 bit mono formats return 1 (byte)
     /// - 16 bit stereo (dual-channel) formats return 4 (bytes)
+    ///
+    /// # Notes
+    ///
+    /// ADPCM samples take 4 bits, and are stored in frames of 8 bytes holding 14 samples:
+    /// [`AudioFormat::ADPCMMono`] returns the size of a frame. Use [`AudioFormat::sample_count()`] to get the amount of samples in a buffer.
     pub const fn size(self) -> usize {
         match self {
             Self::PCM8Mono => 1,
             Self::PCM16Mono | Self::PCM8Stereo => 2,
             Self::PCM16Stereo => 4,
+            Self::ADPCMMono => 8,
+        }
+    }
+
+    /// Returns the amount of samples (of every channel) stored in `bytes` bytes of audio data.
+    pub const fn sample_count(self, bytes: usize) -> usize {
+        match self {
+            // Each frame starts with a header byte, followed by 2 samples per byte.
+            Self::ADPCMMono => bytes / 8 * 14 + (bytes % 8).saturating_sub(1) * 2,
+            _ => bytes / self.size(),
         }
     }
 }
@@ -661,6 +1037,18 @@ impl AudioMix {
         Self { raw: [0.; 12] }
     }
 
+    /// Creates a new [`AudioMix`] playing at `volume` on the front speakers, panned between the left (-1) and right (1) speaker.
+    ///
+    /// The volume is split with a constant power law, so that the sound is equally loud wherever it is panned.
+    pub fn panned(volume: f32, pan: f32) -> Self {
+        let angle = (pan.clamp(-1., 1.) + 1.) * std::f32::consts::FRAC_PI_4;
+
+        let mut mix = Self::zeroed();
+        mix.set_front(volume * angle.cos(), volume * angle.sin());
+
+        mix
+    }
+
     /// Returns a reference to the raw data.
     pub fn as_raw(&self) -> &[f32; 12] {
         &self.raw
@@ -767,6 +1155,7 @@ impl fmt::Display for Error {
             Self::ChannelAlreadyInUse(id) => write!(f, "audio Channel with ID {id} is already being used. Drop the other instance if you want to use it here"),
             Self::WaveBusy(id) => write!(f, "the selected Wave is busy playing on channel {id}"),
             Self::SampleCountOutOfBounds(samples_requested, max_samples) => write!(f, "the sample count requested is too big (requested = {samples_requested}, maximum = {max_samples})"),
+            Self::ChannelBusy(id) => write!(f, "audio Channel with ID {id} still has waves queued"),
         }
     }
 }
@@ -785,3 +1174,31 @@ impl Drop for Ndsp {
 from_impl!(InterpolationType, ctru_sys::ndspInterpType);
 from_impl!(OutputMode, ctru_sys::ndspOutputMode);
 from_impl!(AudioFormat, u16);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_reached_wraps() {
+        assert!(frame_reached(10, 10));
+        assert!(frame_reached(11, 10));
+        assert!(!frame_reached(9, 10));
+        assert!(frame_reached(3, u32::MAX - 2));
+        assert!(!frame_reached(u32::MAX - 2, 3));
+    }
+
+    #[test]
+    fn panned_mix() {
+        let (left, right) = AudioMix::panned(1., 0.).front();
+        assert!((left - right).abs() < 1e-6);
+        assert!((left * left + right * right - 1.).abs() < 1e-6);
+
+        let (left, right) = AudioMix::panned(0.5, -1.).front();
+        assert!((left - 0.5).abs() < 1e-6 && right.abs() < 1e-6);
+
+        // Out of range pans stick to a single speaker.
+        assert_eq!(AudioMix::panned(1., 3.), AudioMix::panned(1., 1.));
+        assert_eq!(AudioMix::panned(1., 1.).back(), (0., 0.));
+    }
+}
@@ -0,0 +1,367 @@
+//! Streaming audio playback.
+//!
+//! Music and other long sounds are too big to fit in memory at once, so they are played through a few small [`Wave`]s,
+//! each refilled with the next samples as soon as it finishes playing. [`AudioStream`] takes care of this rotation:
+//! it owns the waves and a [`Channel`], and requeues the waves (always in the same order) whenever new samples are provided.
+//!
+//! Streams can be refilled from the application's main loop with [`AudioStream::fill()`], or from the NDSP thread at every audio frame
+//! with [`AudioStream::drive()`], which keeps the music playing even while the main loop is busy (e.g. loading a level).
+#![doc(alias = "streaming")]
+#![doc(alias = "music")]
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+
+use super::wave::{Status, Wave};
+use super::{AudioFormat, Channel};
+use crate::linear::LinearAllocator;
+
+/// Source of the samples of a driven stream, called on the NDSP thread.
+type Source = Box<dyn FnMut(&mut [u8]) -> usize + Send>;
+
+/// Stream refilled by [`drive_streams()`] at every audio frame.
+struct Driver {
+    buffers: Arc<Mutex<Buffers>>,
+    source: Source,
+}
+
+/// Streams registered with [`AudioStream::drive()`].
+static DRIVERS: Mutex<Vec<Driver>> = Mutex::new(Vec::new());
+
+/// Lock `mutex`, ignoring its poisoning.
+///
+/// The stream's state is also used by the NDSP frame callback, which can't unwind, and stays consistent when a source panics:
+/// at worst, the wave being refilled is left out of the queue.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Rotation of waves played by an [`AudioStream`].
+struct Buffers {
+    channel: u8,
+    format: AudioFormat,
+    waves: Vec<Wave>,
+    /// Index of the next wave to refill. Waves are refilled in rotation order, so they always play in that order.
+    next: usize,
+    /// Whether any wave was queued since the stream was created or stopped.
+    started: bool,
+    /// Whether the source had no samples to provide at the last refill.
+    exhausted: bool,
+    underruns: usize,
+}
+
+// SAFETY: the waves are only accessed while holding the lock of their `Buffers`, whichever thread holds it.
+unsafe impl Send for Buffers {}
+
+/// Continuous audio playback on a [`Channel`], through a rotation of waves refilled with new samples as they finish playing.
+///
+/// # Notes
+///
+/// The stream doesn't rely on waves finishing in the order they were queued: it only ever refills the oldest queued wave,
+/// and stops refilling until that one finishes, even if a later wave finished first. Waves therefore always play in rotation order.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::formats::bcstm::BcstmStream;
+/// use ctru::services::ndsp::stream::AudioStream;
+/// use ctru::services::ndsp::{AudioFormat, Ndsp};
+///
+/// let ndsp = Ndsp::new()?;
+/// let mut music = BcstmStream::new(std::fs::File::open("romfs:/music.bcstm")?)?;
+///
+/// let mut channel = ndsp.channel(0)?;
+/// music.configure(&mut channel);
+///
+/// // Three buffers of 4096 stereo frames each.
+/// let mut stream = AudioStream::new(channel, AudioFormat::PCM16Stereo, 3, 4096 * 4);
+///
+/// loop {
+///     // Refill the buffers which finished playing, once per frame of the main loop.
+///     stream.fill(|buffer| music.read_bytes(buffer).unwrap_or(0));
+///
+///     // ...
+/// #   break;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct AudioStream<'ndsp> {
+    channel: Channel<'ndsp>,
+    buffers: Arc<Mutex<Buffers>>,
+    driven: bool,
+}
+
+impl<'ndsp> AudioStream<'ndsp> {
+    /// Create a stream playing on `channel`, with `buffer_count` buffers of `buffer_size` bytes each, allocated on [LINEAR memory](crate::linear).
+    ///
+    /// `channel` must be [configured](Channel::set_format) for `format`. Each buffer holds the samples of a single wave: more (or bigger) buffers
+    /// give more time to refill them, at the cost of memory and of latency when the stream starts.
+    ///
+    /// # Notes
+    ///
+    /// The stream doesn't set coefficients for [`AudioFormat::ADPCMMono`] data: use [`Channel::set_adpcm_coefficients()`] on the channel beforehand.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `buffer_count` is lower than 2, since a single buffer can't be refilled while it plays.
+    pub fn new(
+        channel: Channel<'ndsp>,
+        format: AudioFormat,
+        buffer_count: usize,
+        buffer_size: usize,
+    ) -> Self {
+        assert!(buffer_count >= 2, "audio streams need at least 2 buffers");
+
+        let waves = (0..buffer_count)
+            .map(|_| {
+                let mut buffer = Vec::with_capacity_in(buffer_size, LinearAllocator);
+                buffer.resize(buffer_size, 0);
+
+                Wave::new(buffer.into_boxed_slice(), format, false)
+            })
+            .collect();
+
+        Self {
+            buffers: Arc::new(Mutex::new(Buffers {
+                channel: channel.id,
+                format,
+                waves,
+                next: 0,
+                started: false,
+                exhausted: false,
+                underruns: 0,
+            })),
+            channel,
+            driven: false,
+        }
+    }
+
+    /// Refill the buffers which finished playing and queue them again, returning the amount of buffers queued.
+    ///
+    /// `source` is called with each buffer to refill, and returns the amount of bytes it wrote at the start of it.
+    /// Returning less than the size of the buffer queues a shorter wave, while returning 0 (e.g. at the end of the sound) stops refilling
+    /// until the next call. Call this function regularly (e.g. once per frame of the main loop) to keep the stream playing.
+    ///
+    /// This function does nothing if the stream is [driven](AudioStream::drive) by the NDSP thread.
+    pub fn fill(&mut self, source: impl FnMut(&mut [u8]) -> usize) -> usize {
+        if self.driven {
+            return 0;
+        }
+
+        lock(&self.buffers).refill(source)
+    }
+
+    /// Refill the buffers which finished playing with the 16-bit samples of `samples`, and queue them again.
+    ///
+    /// The samples not needed to refill the buffers are left in the iterator for the next call. See [`AudioStream::fill()`] for more details.
+    pub fn fill_samples(&mut self, samples: &mut impl Iterator<Item = i16>) -> usize {
+        self.fill(|buffer| {
+            let mut written = 0;
+
+            for (bytes, sample) in buffer.chunks_exact_mut(2).zip(&mut *samples) {
+                bytes.copy_from_slice(&sample.to_le_bytes());
+                written += 2;
+            }
+
+            written
+        })
+    }
+
+    /// Refill the stream from the NDSP thread at every audio frame (about every 5 ms) with `source`, instead of with [`AudioStream::fill()`].
+    ///
+    /// `source` works as in [`AudioStream::fill()`], but runs on the NDSP thread: it must return quickly, or it will delay the audio
+    /// of every channel. Returning 0 when no samples are ready yet is fine, since the stream is refilled again at the next frame.
+    /// Calling this function again replaces the previous source.
+    ///
+    /// If `source` panics, it is dropped and the stream stops being refilled.
+    ///
+    /// # Example
+    ///
+    /// Decoding is usually too slow to run on the NDSP thread: decode on another thread instead,
+    /// and only copy the decoded samples in `source`.
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use std::sync::mpsc;
+    ///
+    /// use ctru::formats::bcstm::BcstmStream;
+    /// use ctru::services::ndsp::stream::AudioStream;
+    /// use ctru::services::ndsp::{AudioFormat, Ndsp};
+    ///
+    /// const BUFFER_SIZE: usize = 4096 * 4;
+    ///
+    /// let ndsp = Ndsp::new()?;
+    /// let mut music = BcstmStream::new(std::fs::File::open("romfs:/music.bcstm")?)?;
+    ///
+    /// let mut channel = ndsp.channel(0)?;
+    /// music.configure(&mut channel);
+    ///
+    /// // Keep a couple of decoded buffers ahead of the playback.
+    /// let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(2);
+    ///
+    /// std::thread::spawn(move || loop {
+    ///     let mut block = vec![0; BUFFER_SIZE];
+    ///     let len = music.read_bytes(&mut block).unwrap_or(0);
+    ///     block.truncate(len);
+    ///
+    ///     // Stop at the end of the music, or once the stream is dropped.
+    ///     if len == 0 || sender.send(block).is_err() {
+    ///         break;
+    ///     }
+    /// });
+    ///
+    /// let mut stream = AudioStream::new(channel, AudioFormat::PCM16Stereo, 3, BUFFER_SIZE);
+    ///
+    /// stream.drive(move |buffer| match receiver.try_recv() {
+    ///     Ok(block) => {
+    ///         buffer[..block.len()].copy_from_slice(&block);
+    ///         block.len()
+    ///     }
+    ///     // Nothing decoded yet.
+    ///     Err(_) => 0,
+    /// });
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn drive(&mut self, source: impl FnMut(&mut [u8]) -> usize + Send + 'static) {
+        self.stop_driving();
+
+        lock(&DRIVERS).push(Driver {
+            buffers: self.buffers.clone(),
+            source: Box::new(source),
+        });
+        self.driven = true;
+    }
+
+    /// Stop refilling the stream from the NDSP thread, dropping the source given to [`AudioStream::drive()`].
+    ///
+    /// The buffers already queued keep playing.
+    pub fn stop_driving(&mut self) {
+        if self.driven {
+            lock(&DRIVERS).retain(|driver| !Arc::ptr_eq(&driver.buffers, &self.buffers));
+            self.driven = false;
+        }
+    }
+
+    /// Returns `true` if the stream is refilled from the NDSP thread (see [`AudioStream::drive()`]).
+    pub fn is_driven(&self) -> bool {
+        self.driven
+    }
+
+    /// Stop playback, clearing the queued buffers.
+    ///
+    /// The following refill starts the stream again from its first buffer.
+    #[doc(alias = "ndspChnWaveBufClear")]
+    pub fn stop(&mut self) {
+        let mut buffers = lock(&self.buffers);
+
+        self.channel.clear_queue();
+        buffers.next = 0;
+        buffers.started = false;
+    }
+
+    /// Returns the amount of buffers queued or playing.
+    pub fn queued(&self) -> usize {
+        lock(&self.buffers).queued()
+    }
+
+    /// Returns `true` if the stream started playing and all its buffers finished playing.
+    ///
+    /// This happens when the sound ended, or when the buffers weren't refilled in time (see [`AudioStream::underruns()`]).
+    pub fn is_finished(&self) -> bool {
+        let buffers = lock(&self.buffers);
+
+        buffers.started && buffers.queued() == 0
+    }
+
+    /// Returns how many times all the buffers finished playing while the source still had samples to provide,
+    /// which causes audible gaps. More or bigger buffers, or refilling the stream more often, avoid them.
+    pub fn underruns(&self) -> usize {
+        lock(&self.buffers).underruns
+    }
+
+    /// Returns the channel the stream plays on.
+    pub fn channel(&self) -> &Channel<'ndsp> {
+        &self.channel
+    }
+
+    /// Returns the channel the stream plays on, e.g. to change its volume mix.
+    pub fn channel_mut(&mut self) -> &mut Channel<'ndsp> {
+        &mut self.channel
+    }
+}
+
+impl Drop for AudioStream<'_> {
+    fn drop(&mut self) {
+        self.stop_driving();
+        self.stop();
+    }
+}
+
+impl Buffers {
+    fn queued(&self) -> usize {
+        self.waves
+            .iter()
+            .filter(|wave| matches!(wave.status(), Status::Queued | Status::Playing))
+            .count()
+    }
+
+    fn refill(&mut self, mut source: impl FnMut(&mut [u8]) -> usize) -> usize {
+        if self.started && !self.exhausted && self.queued() == 0 {
+            self.underruns += 1;
+        }
+
+        let mut queued = 0;
+
+        loop {
+            let wave = &mut self.waves[self.next];
+
+            // This is the oldest queued wave: the following ones are only refilled after it, to keep the rotation order.
+            let Ok(buffer) = wave.get_buffer_mut() else {
+                break;
+            };
+
+            let written = source(buffer).min(buffer.len());
+            self.exhausted = written == 0;
+            if self.exhausted {
+                break;
+            }
+
+            // The sample count can't exceed the buffer's size, and the wave isn't busy.
+            let _ = wave.set_sample_count(self.format.sample_count(written));
+            wave.set_channel(self.channel);
+
+            unsafe { ctru_sys::ndspChnWaveBufAdd(self.channel.into(), &mut wave.raw_data) };
+
+            self.next = (self.next + 1) % self.waves.len();
+            self.started = true;
+            queued += 1;
+        }
+
+        queued
+    }
+}
+
+/// Refill the driven streams, called by the NDSP frame callback.
+///
+/// Sources which panic are dropped, since the panic can't unwind out of the callback: their streams stop being refilled.
+pub(super) fn drive_streams() {
+    lock(&DRIVERS).retain_mut(|driver| {
+        let mut buffers = match driver.buffers.try_lock() {
+            Ok(buffers) => buffers,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            // Streams being stopped or read from another thread are refilled at the next frame.
+            Err(TryLockError::WouldBlock) => return true,
+        };
+
+        panic::catch_unwind(AssertUnwindSafe(|| buffers.refill(&mut driver.source))).is_ok()
+    });
+}
@@ -12,11 +12,23 @@ pub struct Wave {
     /// Data block of the audio wave (and its format information).
     buffer: Box<[u8], LinearAllocator>,
     audio_format: AudioFormat,
+    // Boxed, since `raw_data` points to its context.
+    adpcm_data: Option<Box<AdpcmData>>,
     // Holding the data with the raw format is necessary since `libctru` will access it.
     pub(crate) raw_data: ctru_sys::ndspWaveBuf,
     played_on_channel: Option<u8>,
 }
 
+/// Decoding parameters of [DSP-ADPCM](AudioFormat::ADPCMMono) audio.
+///
+/// These are usually found in the header of the audio file, next to the ADPCM data (e.g. in BCWAV and DSP files).
+#[doc(alias = "ndspAdpcmData")]
+#[derive(Copy, Clone, Debug)]
+pub struct AdpcmData {
+    coefficients: [i16; 16],
+    context: ctru_sys::ndspAdpcmData,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
 /// Playback status of a [`Wave`].
@@ -55,7 +67,7 @@ impl Wave {
         audio_format: AudioFormat,
         looping: bool,
     ) -> Self {
-        let sample_count = buffer.len() / audio_format.size();
+        let sample_count = audio_format.sample_count(buffer.len());
 
         // Signal to the DSP processor the buffer's RAM sector.
         // This step may seem delicate, but testing reports failure most of the time, while still having no repercussions on the resulting audio.
@@ -82,11 +94,50 @@ impl Wave {
         Self {
             buffer,
             audio_format,
+            adpcm_data: None,
             raw_data,
             played_on_channel: None,
         }
     }
 
+    /// Build a new playable wave object from [DSP-ADPCM](AudioFormat::ADPCMMono) data on [LINEAR memory](`crate::linear`)
+    /// and its decoding parameters.
+    ///
+    /// The decoder is reset to the context in `adpcm_data` when the wave starts playing, and the coefficients are set on the channel
+    /// when the wave is queued (see [`Channel::queue_wave()`](super::Channel::queue_wave)).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #![feature(allocator_api)]
+    /// # fn main() {
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// #
+    /// use ctru::linear::LinearAllocator;
+    /// use ctru::services::ndsp::wave::{AdpcmData, Wave};
+    ///
+    /// // Provide your own audio data and parameters.
+    /// let audio_data = Box::new_in([0u8; 96], LinearAllocator);
+    /// let adpcm_data = AdpcmData::new([0; 16], audio_data[0], [0, 0]);
+    ///
+    /// let wave = Wave::with_adpcm(audio_data, adpcm_data, false);
+    /// assert_eq!(wave.sample_count(), 168);
+    /// # }
+    /// ```
+    pub fn with_adpcm(
+        buffer: Box<[u8], LinearAllocator>,
+        adpcm_data: AdpcmData,
+        looping: bool,
+    ) -> Self {
+        let mut wave = Self::new(buffer, AudioFormat::ADPCMMono, looping);
+
+        let mut adpcm_data = Box::new(adpcm_data);
+        wave.raw_data.adpcm_data = &mut adpcm_data.context;
+        wave.adpcm_data = Some(adpcm_data);
+
+        wave
+    }
+
     /// Returns a slice to the audio data (on the LINEAR memory).
     pub fn get_buffer(&self) -> &[u8] {
         &self.buffer
@@ -132,6 +183,19 @@ impl Wave {
         self.raw_data.status.try_into().unwrap()
     }
 
+    /// Returns the sequence ID assigned to this wave when it was last queued, or [`None`] if it was never queued.
+    ///
+    /// Each channel numbers the waves queued on it with consecutive (wrapping) sequence IDs, and plays them in that order.
+    /// Comparing this value with [`Channel::wave_sequence_id()`](super::Channel::wave_sequence_id) tells whether the channel
+    /// is still playing this wave, or has already moved on to the following ones.
+    /// Ring-buffer players can use it to detect buffers which finished without being refilled in time (e.g. while the console was asleep).
+    pub fn sequence_id(&self) -> Option<u16> {
+        match self.status() {
+            Status::Free => None,
+            _ => Some(self.raw_data.sequence_id),
+        }
+    }
+
     /// Returns the amount of samples *read* by the NDSP process.
     ///
     /// # Notes
@@ -146,6 +210,11 @@ impl Wave {
         self.audio_format
     }
 
+    /// Returns the ADPCM decoding parameters of the wave, if it was built with [`Wave::with_adpcm()`].
+    pub fn adpcm_data(&self) -> Option<&AdpcmData> {
+        self.adpcm_data.as_deref()
+    }
+
     // Set the internal flag for the id of the channel playing this wave.
     //
     // Internal Use Only.
@@ -173,7 +242,7 @@ impl Wave {
             _ => (),
         }
 
-        let max_count = self.buffer.len() / self.audio_format.size();
+        let max_count = self.audio_format.sample_count(self.buffer.len());
 
         if sample_count > max_count {
             return Err(Error::SampleCountOutOfBounds(sample_count, max_count));
@@ -185,6 +254,37 @@ impl Wave {
     }
 }
 
+impl AdpcmData {
+    /// Create the decoding parameters of ADPCM audio from its 8 pairs of coefficients,
+    /// the header byte (predictor and scale) of its first frame and the last 2 samples decoded before it, from the newest
+    /// to the oldest (both 0 at the start of a sound).
+    pub fn new(coefficients: [i16; 16], predictor_scale: u8, history: [i16; 2]) -> Self {
+        Self {
+            coefficients,
+            context: ctru_sys::ndspAdpcmData {
+                index: predictor_scale.into(),
+                history0: history[0],
+                history1: history[1],
+            },
+        }
+    }
+
+    /// Returns the coefficients used to decode the audio.
+    pub fn coefficients(&self) -> &[i16; 16] {
+        &self.coefficients
+    }
+
+    /// Returns the header byte (predictor and scale) of the first frame.
+    pub fn predictor_scale(&self) -> u8 {
+        self.context.index as u8
+    }
+
+    /// Returns the last 2 samples decoded before the first frame, from the newest to the oldest.
+    pub fn history(&self) -> [i16; 2] {
+        [self.context.history0, self.context.history1]
+    }
+}
+
 impl TryFrom<u8> for Status {
     type Error = &'static str;
 
@@ -205,6 +305,8 @@ impl Drop for Wave {
         // A panic was considered, but it would cause issues with drop order against `Ndsp`.
         match self.status() {
             Status::Free | Status::Done => (),
+            // Waves scheduled with `Channel::queue_at()` aren't known to `libctru` yet.
+            _ if super::unschedule(&mut self.raw_data) => (),
             // If the status flag is "unfinished"
             _ => {
                 // The unwrap is safe, since it must have a value in the case the status is "unfinished".
@@ -0,0 +1,229 @@
+//! Network Installation Manager (NIM) service.
+//!
+//! NIM handles the system's background downloads, such as system updates and title updates from the eShop.
+//! This module exposes what update-aware applications need: whether a system update is pending,
+//! and the latest version of a title available for download.
+//!
+//! # Notes
+//!
+//! The "nim:s" service is only accessible to applications which list it in their exheader's service access control.
+//! [`latest_title_version()`] doesn't need it, since it only contacts Nintendo's content servers through [HTTPC](crate::services::httpc).
+//!
+//! See also <https://www.3dbrew.org/wiki/NIM_Services>
+#![doc(alias = "update")]
+#![doc(alias = "eshop")]
+
+use crate::error::ResultCode;
+use crate::formats::Endian;
+use crate::services::httpc::{Httpc, Method, Request};
+use crate::services::ServiceReference;
+use crate::Error;
+use std::alloc::Layout;
+use std::io::Read;
+use std::sync::Mutex;
+
+static NIM_ACTIVE: Mutex<()> = Mutex::new(());
+static NIM_BUFFER: Mutex<Option<&'static mut [u8]>> = Mutex::new(None);
+
+/// Size of the buffer used internally by the service.
+const BUFFER_SIZE: usize = 0x20000;
+const PAGE_SIZE: usize = 0x1000;
+
+/// Server holding the title metadata of every title on the eShop.
+const CONTENT_SERVER: &str = "http://nus.cdn.c.shop.nintendowifi.net/ccs/download";
+
+/// Handle to the "nim:s" service.
+pub struct Nim {
+    _service_reference: ServiceReference,
+}
+
+impl Nim {
+    /// Initialize a new service handle.
+    ///
+    /// # Notes
+    ///
+    /// The service contacts Nintendo's servers while initializing, so this function blocks until it is done.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the service was unable to be initialized,
+    /// e.g. because the application isn't allowed to access "nim:s", or if it is already in use elsewhere.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let _runner = test_runner::GdbRunner::default();
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #
+    /// use ctru::services::nim::Nim;
+    ///
+    /// let nim = Nim::new()?;
+    ///
+    /// if nim.is_system_update_pending()? {
+    ///     println!("Please update your console before going online.");
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "nimsInit")]
+    pub fn new() -> crate::Result<Self> {
+        let service_reference = ServiceReference::new(
+            "nim:s",
+            &NIM_ACTIVE,
+            || unsafe {
+                let layout = buffer_layout();
+                let buffer = std::alloc::alloc_zeroed(layout);
+                if buffer.is_null() {
+                    std::alloc::handle_alloc_error(layout);
+                }
+
+                let result = ctru_sys::nimsInit(buffer.cast(), BUFFER_SIZE as _);
+                if ctru_sys::R_FAILED(result) {
+                    std::alloc::dealloc(buffer, layout);
+                    return Err(Error::Os(result));
+                }
+
+                // The service keeps using the buffer until it is closed.
+                *NIM_BUFFER
+                    .lock()
+                    .map_err(|e| Error::Other(format!("Failed to write to NIM_BUFFER: {e}")))? =
+                    Some(std::slice::from_raw_parts_mut(buffer, BUFFER_SIZE));
+
+                Ok(())
+            },
+            || unsafe {
+                ctru_sys::nimsExit();
+
+                if let Some(buffer) = NIM_BUFFER
+                    .lock()
+                    .expect("Failed to write to NIM_BUFFER")
+                    .take()
+                {
+                    std::alloc::dealloc(buffer.as_mut_ptr(), buffer_layout());
+                }
+            },
+        )?;
+
+        Ok(Self {
+            _service_reference: service_reference,
+        })
+    }
+
+    /// Returns `true` if a system update is available, and must be installed before the console can go online.
+    #[doc(alias = "NIMS_WantUpdate")]
+    pub fn is_system_update_pending(&self) -> crate::Result<bool> {
+        let mut pending = false;
+        ResultCode(unsafe { ctru_sys::NIMS_WantUpdate(&mut pending) })?;
+
+        Ok(pending)
+    }
+}
+
+/// Returns the latest version of the title `title_id` available for download, from the title's metadata on Nintendo's content servers.
+///
+/// Compare it with the installed version (e.g. [`Title::version()`](crate::services::am::Title::version)) to check
+/// whether an update is available. Pass the ID of a title's update (e.g. `0x0004000E...`) to check for updates of a game.
+///
+/// # Errors
+///
+/// This function will return an error if the request failed (e.g. because the console isn't connected to the internet),
+/// if the servers don't know the title, or if they returned invalid metadata.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::httpc::Httpc;
+/// use ctru::services::nim;
+///
+/// let httpc = Httpc::new()?;
+///
+/// let installed = 1040;
+/// let latest = nim::latest_title_version(&httpc, 0x0004_000E_0003_0800)?;
+/// if latest > installed {
+///     println!("Version {latest} is available!");
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub fn latest_title_version(httpc: &Httpc, title_id: u64) -> crate::Result<u16> {
+    let url = format!("{CONTENT_SERVER}/{title_id:016x}/tmd");
+    let mut response = Request::new(httpc, Method::Get, &url)?.send_blocking()?;
+
+    match response.status() {
+        200 => (),
+        404 => {
+            return Err(Error::Other(format!(
+                "title {title_id:016X} isn't available for download"
+            )))
+        }
+        status => {
+            return Err(Error::Other(format!(
+                "unexpected HTTP status {status} from the content server"
+            )))
+        }
+    }
+
+    let mut tmd = Vec::new();
+    response.read_to_end(&mut tmd)?;
+
+    tmd_title_version(&tmd, title_id)
+}
+
+fn buffer_layout() -> Layout {
+    Layout::from_size_align(BUFFER_SIZE, PAGE_SIZE).unwrap()
+}
+
+/// Read the version of the title `title_id` from its title metadata (TMD).
+///
+/// See also <https://www.3dbrew.org/wiki/Title_metadata>
+fn tmd_title_version(tmd: &[u8], title_id: u64) -> crate::Result<u16> {
+    // The header follows the signature, whose size (including its padding) depends on its type.
+    let header = 4 + match Endian::Big.u32(tmd, 0)? {
+        0x10000 | 0x10003 => 0x23C,
+        0x10001 | 0x10004 => 0x13C,
+        0x10002 | 0x10005 => 0x7C,
+        kind => {
+            return Err(Error::Other(format!(
+                "unknown TMD signature type {kind:#X}"
+            )))
+        }
+    };
+
+    let id = u64::from(Endian::Big.u32(tmd, header + 0x4C)?) << 32
+        | u64::from(Endian::Big.u32(tmd, header + 0x50)?);
+    if id != title_id {
+        return Err(Error::Other(format!(
+            "TMD is for title {id:016X} instead of {title_id:016X}"
+        )));
+    }
+
+    Endian::Big.u16(tmd, header + 0x9C)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tmd_versions() {
+        let title_id = 0x0004_000E_0003_0800;
+
+        let mut tmd = vec![0; 0x140 + 0xC4];
+        tmd[..4].copy_from_slice(&0x10004u32.to_be_bytes());
+        tmd[0x140 + 0x4C..0x140 + 0x54].copy_from_slice(&title_id.to_be_bytes());
+        tmd[0x140 + 0x9C..0x140 + 0x9E].copy_from_slice(&1040u16.to_be_bytes());
+
+        assert_eq!(tmd_title_version(&tmd, title_id).unwrap(), 1040);
+        assert!(tmd_title_version(&tmd, title_id + 1).is_err());
+        assert!(tmd_title_version(&tmd[..0x150], title_id).is_err());
+
+        tmd[..4].copy_from_slice(&0x20000u32.to_be_bytes());
+        assert!(tmd_title_version(&tmd, title_id).is_err());
+    }
+}
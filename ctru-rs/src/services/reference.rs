@@ -1,13 +1,34 @@
+use crate::shutdown::{self, Registration, Stage};
 use crate::Error;
 use std::sync::{Mutex, MutexGuard, TryLockError};
 
 pub(crate) struct ServiceReference {
+    // The registration must be dropped (closing the service) before the guard is released.
+    _registration: Registration,
     _guard: MutexGuard<'static, ()>,
-    close: Box<dyn Fn() + Send + Sync>,
 }
 
 impl ServiceReference {
-    pub fn new<S, E>(counter: &'static Mutex<()>, start: S, close: E) -> crate::Result<Self>
+    pub fn new<S, E>(
+        name: &'static str,
+        counter: &'static Mutex<()>,
+        start: S,
+        close: E,
+    ) -> crate::Result<Self>
+    where
+        S: FnOnce() -> crate::Result<()>,
+        E: Fn() + Send + Sync + 'static,
+    {
+        Self::with_stage(name, counter, Stage::Default, start, close)
+    }
+
+    pub fn with_stage<S, E>(
+        name: &'static str,
+        counter: &'static Mutex<()>,
+        stage: Stage,
+        start: S,
+        close: E,
+    ) -> crate::Result<Self>
     where
         S: FnOnce() -> crate::Result<()>,
         E: Fn() + Send + Sync + 'static,
@@ -31,14 +52,13 @@ impl ServiceReference {
         start()?;
 
         Ok(Self {
+            _registration: shutdown::register(stage, name, close),
             _guard,
-            close: Box::new(close),
         })
     }
-}
 
-impl Drop for ServiceReference {
-    fn drop(&mut self) {
-        (self.close)();
+    /// Report the service's session handle in [`list_open_services()`](crate::debug::list_open_services).
+    pub fn set_handle_getter(&self, handle: fn() -> Option<u32>) {
+        self._registration.set_handle_getter(handle);
     }
 }
@@ -63,6 +63,7 @@ impl RomFS {
     #[doc(alias = "romfsMountSelf")]
     pub fn new() -> crate::Result<Self> {
         let _service_handler = ServiceReference::new(
+            "romfs",
             &ROMFS_ACTIVE,
             || {
                 let mount_name = CStr::from_bytes_with_nul(b"romfs\0").unwrap();
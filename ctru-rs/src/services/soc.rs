@@ -72,6 +72,7 @@ impl Soc {
     #[doc(alias = "socInit")]
     pub fn init_with_buffer_size(num_bytes: usize) -> crate::Result<Self> {
         let _service_handler = ServiceReference::new(
+            "soc:U",
             &SOC_ACTIVE,
             || {
                 let soc_mem = unsafe { memalign(0x1000, num_bytes) } as *mut u32;
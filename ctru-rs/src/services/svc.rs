@@ -4,6 +4,7 @@
 //! Most APIs should be used directly from `ctru-sys`.
 
 use crate::error::ResultCode;
+use bitflags::bitflags;
 use ctru_sys::Handle;
 use std::time::Duration;
 
@@ -74,3 +75,165 @@ pub const fn make_ipc_header(command_id: u16, normal_params: u8, translate_param
         | (((normal_params as u32) & 0x3F) << 6)
         | ((translate_params as u32) & 0x3F)
 }
+
+bitflags! {
+    /// Access permissions of a memory region.
+    #[doc(alias = "MemPerm")]
+    #[derive(Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+    pub struct MemoryPermission: u32 {
+        /// The region can be read.
+        const READ    = ctru_sys::MEMPERM_READ;
+        /// The region can be written.
+        const WRITE   = ctru_sys::MEMPERM_WRITE;
+        /// The region can be executed.
+        const EXECUTE = ctru_sys::MEMPERM_EXECUTE;
+    }
+}
+
+/// Kernel state of a memory region, describing what the region is used for.
+#[doc(alias = "MemState")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MemoryState {
+    /// Unmapped memory.
+    Free,
+    /// Reserved memory.
+    Reserved,
+    /// I/O memory.
+    Io,
+    /// Static memory.
+    Static,
+    /// Code of the process.
+    Code,
+    /// Private memory, such as the heap and thread stacks.
+    Private,
+    /// Memory shared with other processes.
+    Shared,
+    /// Continuous memory, such as the linear heap.
+    Continuous,
+    /// Memory aliased elsewhere (e.g. the source of a mirrored mapping).
+    Aliased,
+    /// Alias of another region.
+    Alias,
+    /// Alias of a code region.
+    AliasCode,
+    /// Locked memory.
+    Locked,
+    /// Any other state.
+    Other(u32),
+}
+
+impl From<u32> for MemoryState {
+    fn from(value: u32) -> Self {
+        match value {
+            ctru_sys::MEMSTATE_FREE => Self::Free,
+            ctru_sys::MEMSTATE_RESERVED => Self::Reserved,
+            ctru_sys::MEMSTATE_IO => Self::Io,
+            ctru_sys::MEMSTATE_STATIC => Self::Static,
+            ctru_sys::MEMSTATE_CODE => Self::Code,
+            ctru_sys::MEMSTATE_PRIVATE => Self::Private,
+            ctru_sys::MEMSTATE_SHARED => Self::Shared,
+            ctru_sys::MEMSTATE_CONTINUOUS => Self::Continuous,
+            ctru_sys::MEMSTATE_ALIASED => Self::Aliased,
+            ctru_sys::MEMSTATE_ALIAS => Self::Alias,
+            ctru_sys::MEMSTATE_ALIASCODE => Self::AliasCode,
+            ctru_sys::MEMSTATE_LOCKED => Self::Locked,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// A contiguous range of the current process' address space sharing the same permissions and state.
+#[doc(alias = "MemInfo")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemoryRegion {
+    /// Start address of the region.
+    pub base: u32,
+    /// Size of the region, in bytes.
+    pub size: u32,
+    /// Access permissions of the region.
+    pub permission: MemoryPermission,
+    /// Kernel state of the region.
+    pub state: MemoryState,
+}
+
+impl MemoryRegion {
+    /// Returns the address right after the end of the region.
+    pub fn end(&self) -> u32 {
+        self.base.wrapping_add(self.size)
+    }
+
+    /// Returns `true` if `address` lies within the region.
+    pub fn contains(&self, address: u32) -> bool {
+        address.wrapping_sub(self.base) < self.size
+    }
+
+    /// Returns `true` if the region is mapped (i.e. its state isn't [`MemoryState::Free`]).
+    pub fn is_mapped(&self) -> bool {
+        self.state != MemoryState::Free
+    }
+}
+
+/// Returns the memory region of the current process containing `address`.
+///
+/// # Errors
+///
+/// This function will return an error if the address lies outside of the process' address space.
+#[doc(alias = "svcQueryMemory")]
+pub fn query_memory(address: u32) -> crate::Result<MemoryRegion> {
+    let mut mem_info = ctru_sys::MemInfo::default();
+    let mut page_info = ctru_sys::PageInfo::default();
+
+    ResultCode(unsafe { ctru_sys::svcQueryMemory(&mut mem_info, &mut page_info, address) })?;
+
+    Ok(MemoryRegion {
+        base: mem_info.base_addr,
+        size: mem_info.size,
+        permission: MemoryPermission::from_bits_truncate(mem_info.perm),
+        state: MemoryState::from(mem_info.state),
+    })
+}
+
+/// Iterator over the memory regions of the current process, as returned by [`memory_regions()`].
+#[derive(Clone, Debug)]
+pub struct MemoryRegions {
+    next: Option<u32>,
+}
+
+impl Iterator for MemoryRegions {
+    type Item = MemoryRegion;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let region = query_memory(self.next?).ok()?;
+
+        // Stop after the last region, which ends at the top of the address space.
+        self.next = region
+            .base
+            .checked_add(region.size)
+            .filter(|_| region.size != 0);
+
+        Some(region)
+    }
+}
+
+/// Returns an iterator over all memory regions of the current process, in address order.
+///
+/// Unmapped ranges are included as [`MemoryState::Free`] regions, so the regions cover the whole address space.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::services::svc::{memory_regions, MemoryPermission};
+///
+/// let executable: u32 = memory_regions()
+///     .filter(|region| region.permission.contains(MemoryPermission::EXECUTE))
+///     .map(|region| region.size)
+///     .sum();
+///
+/// println!("{executable:#X} bytes of code are mapped");
+/// ```
+#[doc(alias = "svcQueryMemory")]
+pub fn memory_regions() -> MemoryRegions {
+    MemoryRegions { next: Some(0) }
+}
@@ -0,0 +1,541 @@
+//! Local wireless (UDS) service.
+//!
+//! UDS lets consoles in the same room communicate directly, without an access point.
+//! This module currently focuses on one-to-many broadcasts for spectator modes: a host [`Broadcaster`] sends small state packets
+//! every frame, and any number of [`Spectator`]s receive them. Spectators don't take part in the network's connection handshake
+//! nor occupy one of its node slots, so they can come and go freely.
+//!
+//! [`PacketWriter`] and [`PacketReader`] help serializing the fixed-size state packets (see [`StatePacket`]).
+#![doc(alias = "local wireless")]
+#![doc(alias = "spectator")]
+
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use crate::error::ResultCode;
+use crate::services::ServiceReference;
+use crate::sync::CancelToken;
+use crate::Error;
+
+/// Maximum size (in bytes) of a single packet, including the sequence number added by [`Broadcaster::send_state()`].
+#[doc(alias = "UDS_DATAFRAME_MAXSIZE")]
+pub const MAX_PACKET_SIZE: usize = 0x5C6;
+
+const SHAREDMEM_SIZE: usize = 0x3000;
+const RECV_BUFFER_SIZE: u32 = 0x2E30;
+const DATA_CHANNEL: u8 = 1;
+const BROADCAST_NODE_ID: u16 = 0xFFFF;
+const HOST_NODE_ID: u16 = 1;
+const SEQUENCE_SIZE: usize = 4;
+
+static UDS_ACTIVE: Mutex<()> = Mutex::new(());
+
+/// Handle to the UDS service.
+pub struct Uds {
+    _service_handler: ServiceReference,
+}
+
+/// UDS network found by [`Uds::scan()`].
+#[derive(Clone)]
+pub struct Network {
+    raw: ctru_sys::udsNetworkScanInfo,
+}
+
+/// Host side of a spectator broadcast.
+///
+/// The network is destroyed when this struct is dropped.
+pub struct Broadcaster<'uds> {
+    bind: ctru_sys::udsBindContext,
+    sequence: u32,
+    _uds: PhantomData<&'uds mut Uds>,
+}
+
+/// Receiving side of a spectator broadcast.
+///
+/// The spectator leaves the network when this struct is dropped.
+pub struct Spectator<'uds> {
+    bind: ctru_sys::udsBindContext,
+    last_sequence: Option<u32>,
+    _uds: PhantomData<&'uds mut Uds>,
+}
+
+/// Small, fixed-size state (e.g. player positions and scores) broadcast every frame.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::services::uds::{PacketReader, PacketWriter, StatePacket};
+///
+/// struct Ball {
+///     x: f32,
+///     y: f32,
+///     score: [u8; 2],
+/// }
+///
+/// impl StatePacket for Ball {
+///     const SIZE: usize = 10;
+///
+///     fn write(&self, packet: &mut PacketWriter) {
+///         packet.put_f32(self.x);
+///         packet.put_f32(self.y);
+///         packet.put_bytes(&self.score);
+///     }
+///
+///     fn read(packet: &mut PacketReader) -> Option<Self> {
+///         Some(Self {
+///             x: packet.get_f32()?,
+///             y: packet.get_f32()?,
+///             score: packet.get_bytes()?,
+///         })
+///     }
+/// }
+/// ```
+pub trait StatePacket: Sized {
+    /// Size (in bytes) of the serialized state. Must be at most [`MAX_PACKET_SIZE`] minus 4 bytes.
+    const SIZE: usize;
+
+    /// Serialize the state.
+    fn write(&self, packet: &mut PacketWriter);
+
+    /// Deserialize the state, returning [`None`] if the packet is malformed.
+    fn read(packet: &mut PacketReader) -> Option<Self>;
+}
+
+/// Little-endian serializer for [`StatePacket`]s.
+pub struct PacketWriter {
+    buffer: Vec<u8>,
+}
+
+/// Little-endian deserializer for [`StatePacket`]s.
+pub struct PacketReader<'a> {
+    buffer: &'a [u8],
+}
+
+impl Uds {
+    /// Initialize a new service handle.
+    ///
+    /// `username` is the name shown to other consoles. If [`None`], the console's user name is used.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the service is already active, if `username` contains NUL bytes
+    /// or if the wireless communication is disabled.
+    #[doc(alias = "udsInit")]
+    pub fn new(username: Option<&str>) -> crate::Result<Self> {
+        let username = username
+            .map(CString::new)
+            .transpose()
+            .map_err(|_| Error::Other(String::from("username contains NUL bytes")))?;
+
+        let _service_handler = ServiceReference::new(
+            "nwm::UDS",
+            &UDS_ACTIVE,
+            || {
+                let username = username.as_ref().map_or(std::ptr::null(), |u| u.as_ptr());
+                ResultCode(unsafe { ctru_sys::udsInit(SHAREDMEM_SIZE, username) })?;
+
+                Ok(())
+            },
+            || unsafe { ctru_sys::udsExit() },
+        )?;
+
+        Ok(Self { _service_handler })
+    }
+
+    /// Create a network and start broadcasting to spectators.
+    ///
+    /// `comm_id` identifies the application (it's usually derived from the title ID), while `id8` tells apart
+    /// different kinds of networks of the same application. Spectators need the same values and `passphrase` to find and join the network.
+    #[doc(alias = "udsCreateNetwork")]
+    pub fn broadcast(
+        &mut self,
+        comm_id: u32,
+        id8: u8,
+        passphrase: &[u8],
+    ) -> crate::Result<Broadcaster<'_>> {
+        let mut network = unsafe { std::mem::zeroed() };
+        // Spectators don't count towards the node limit, so a single node (the host) is enough.
+        unsafe { ctru_sys::udsGenerateDefaultNetworkStruct(&mut network, comm_id, id8, 1) };
+
+        let mut bind = unsafe { std::mem::zeroed() };
+        ResultCode(unsafe {
+            ctru_sys::udsCreateNetwork(
+                &network,
+                passphrase.as_ptr().cast(),
+                passphrase.len(),
+                &mut bind,
+                DATA_CHANNEL,
+                RECV_BUFFER_SIZE,
+            )
+        })?;
+
+        Ok(Broadcaster {
+            bind,
+            sequence: 0,
+            _uds: PhantomData,
+        })
+    }
+
+    /// Scan for networks with the given communication ID and `id8` (see [`Uds::broadcast()`]).
+    #[doc(alias = "udsScanBeacons")]
+    pub fn scan(&mut self, comm_id: u32, id8: u8) -> crate::Result<Vec<Network>> {
+        let mut buffer = vec![0u8; 0x4000];
+        let mut networks = std::ptr::null_mut();
+        let mut count = 0;
+
+        ResultCode(unsafe {
+            ctru_sys::udsScanBeacons(
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                &mut networks,
+                &mut count,
+                comm_id,
+                id8,
+                std::ptr::null(),
+                false,
+            )
+        })?;
+
+        if networks.is_null() {
+            return Ok(Vec::new());
+        }
+
+        // `udsScanBeacons` allocates the list with `malloc`.
+        let list = unsafe {
+            let list = std::slice::from_raw_parts(networks, count)
+                .iter()
+                .map(|raw| Network { raw: *raw })
+                .collect();
+            libc::free(networks.cast());
+
+            list
+        };
+
+        Ok(list)
+    }
+
+    /// Join a network found by [`Uds::scan()`] as a spectator.
+    #[doc(alias = "udsConnectNetwork")]
+    pub fn spectate(
+        &mut self,
+        network: &Network,
+        passphrase: &[u8],
+    ) -> crate::Result<Spectator<'_>> {
+        let mut bind = unsafe { std::mem::zeroed() };
+
+        ResultCode(unsafe {
+            ctru_sys::udsConnectNetwork(
+                &network.raw.network,
+                passphrase.as_ptr().cast(),
+                passphrase.len(),
+                &mut bind,
+                HOST_NODE_ID,
+                ctru_sys::UDSCONTYPE_Spectator,
+                DATA_CHANNEL,
+                RECV_BUFFER_SIZE,
+            )
+        })?;
+
+        Ok(Spectator {
+            bind,
+            last_sequence: None,
+            _uds: PhantomData,
+        })
+    }
+}
+
+impl Network {
+    /// Returns the amount of nodes connected to the network (spectators excluded).
+    pub fn node_count(&self) -> u8 {
+        self.raw.network.total_nodes
+    }
+
+    /// Returns the underlying `libctru` scan information.
+    pub fn as_raw(&self) -> &ctru_sys::udsNetworkScanInfo {
+        &self.raw
+    }
+}
+
+impl Broadcaster<'_> {
+    /// Send raw data to all spectators.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `data` is longer than [`MAX_PACKET_SIZE`] or if sending failed.
+    #[doc(alias = "udsSendTo")]
+    pub fn send(&self, data: &[u8]) -> crate::Result<()> {
+        if data.len() > MAX_PACKET_SIZE {
+            return Err(Error::Other(format!(
+                "packets can't be longer than {MAX_PACKET_SIZE} bytes"
+            )));
+        }
+
+        ResultCode(unsafe {
+            ctru_sys::udsSendTo(
+                BROADCAST_NODE_ID,
+                DATA_CHANNEL,
+                ctru_sys::UDS_SENDFLAG_Default as u8 | ctru_sys::UDS_SENDFLAG_Broadcast as u8,
+                data.as_ptr().cast(),
+                data.len(),
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Serialize and send `state` to all spectators, prefixed by an increasing sequence number.
+    ///
+    /// Spectators use the sequence number to drop late packets (see [`Spectator::recv_state()`]).
+    pub fn send_state<P: StatePacket>(&mut self, state: &P) -> crate::Result<()> {
+        let mut packet = PacketWriter::with_capacity(SEQUENCE_SIZE + P::SIZE);
+        packet.put_u32(self.sequence);
+        state.write(&mut packet);
+
+        debug_assert_eq!(
+            packet.len(),
+            SEQUENCE_SIZE + P::SIZE,
+            "wrong StatePacket::SIZE"
+        );
+
+        self.send(packet.as_bytes())?;
+        self.sequence = self.sequence.wrapping_add(1);
+
+        Ok(())
+    }
+}
+
+impl Drop for Broadcaster<'_> {
+    #[doc(alias = "udsDestroyNetwork")]
+    fn drop(&mut self) {
+        unsafe {
+            ctru_sys::udsUnbind(&mut self.bind);
+            let _ = ctru_sys::udsDestroyNetwork();
+        }
+    }
+}
+
+impl Spectator<'_> {
+    /// Receive a raw packet into `buffer`, without blocking.
+    ///
+    /// Returns the size of the packet, or [`None`] if no packet is pending.
+    #[doc(alias = "udsPullPacket")]
+    pub fn recv(&mut self, buffer: &mut [u8]) -> crate::Result<Option<usize>> {
+        let mut size = 0;
+        let mut source = 0;
+
+        ResultCode(unsafe {
+            ctru_sys::udsPullPacket(
+                &self.bind,
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                &mut size,
+                &mut source,
+            )
+        })?;
+
+        Ok((size != 0).then_some(size))
+    }
+
+    /// Receive a raw packet into `buffer`, waiting until one arrives.
+    ///
+    /// Returns the size of the packet.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`Error::Cancelled`] as soon as `cancel` is cancelled, or another error if receiving failed.
+    pub fn recv_blocking(
+        &mut self,
+        buffer: &mut [u8],
+        cancel: &CancelToken,
+    ) -> crate::Result<usize> {
+        loop {
+            if let Some(size) = self.recv(buffer)? {
+                return Ok(size);
+            }
+
+            // The bind event is signalled whenever new data reaches the receive buffer.
+            cancel.wait_for(self.bind.event, None)?;
+        }
+    }
+
+    /// Receive all pending state packets sent by [`Broadcaster::send_state()`], returning the most recent one.
+    ///
+    /// Returns [`None`] if no new state was received since the last call. Malformed and out-of-order packets are dropped.
+    pub fn recv_state<P: StatePacket>(&mut self) -> crate::Result<Option<P>> {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        let mut latest = None;
+
+        while let Some(size) = self.recv(&mut buffer)? {
+            let mut packet = PacketReader::new(&buffer[..size]);
+
+            let Some(sequence) = packet.get_u32() else {
+                continue;
+            };
+
+            // Sequence numbers wrap around, so compare their distance instead of their values.
+            if let Some(last) = self.last_sequence {
+                if sequence.wrapping_sub(last) as i32 <= 0 {
+                    continue;
+                }
+            }
+
+            if let Some(state) = P::read(&mut packet) {
+                self.last_sequence = Some(sequence);
+                latest = Some(state);
+            }
+        }
+
+        Ok(latest)
+    }
+}
+
+impl Drop for Spectator<'_> {
+    #[doc(alias = "udsDisconnectNetwork")]
+    fn drop(&mut self) {
+        unsafe {
+            ctru_sys::udsUnbind(&mut self.bind);
+            let _ = ctru_sys::udsDisconnectNetwork();
+        }
+    }
+}
+
+impl PacketWriter {
+    /// Create an empty writer.
+    pub fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    /// Create an empty writer with space for `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buffer: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the amount of bytes written.
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns `true` if nothing was written yet.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Returns the written bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Write raw bytes.
+    pub fn put_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Write a `u8`.
+    pub fn put_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    /// Write a `u16`.
+    pub fn put_u16(&mut self, value: u16) {
+        self.put_bytes(&value.to_le_bytes());
+    }
+
+    /// Write a `u32`.
+    pub fn put_u32(&mut self, value: u32) {
+        self.put_bytes(&value.to_le_bytes());
+    }
+
+    /// Write an `i16`.
+    pub fn put_i16(&mut self, value: i16) {
+        self.put_bytes(&value.to_le_bytes());
+    }
+
+    /// Write an `i32`.
+    pub fn put_i32(&mut self, value: i32) {
+        self.put_bytes(&value.to_le_bytes());
+    }
+
+    /// Write an `f32`.
+    pub fn put_f32(&mut self, value: f32) {
+        self.put_bytes(&value.to_le_bytes());
+    }
+}
+
+impl Default for PacketWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> PacketReader<'a> {
+    /// Create a reader over the given packet.
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer }
+    }
+
+    /// Returns the amount of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Read `N` raw bytes.
+    pub fn get_bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.buffer.split_first_chunk::<N>()?;
+        self.buffer = rest;
+
+        Some(*bytes)
+    }
+
+    /// Read a `u8`.
+    pub fn get_u8(&mut self) -> Option<u8> {
+        self.get_bytes().map(u8::from_le_bytes)
+    }
+
+    /// Read a `u16`.
+    pub fn get_u16(&mut self) -> Option<u16> {
+        self.get_bytes().map(u16::from_le_bytes)
+    }
+
+    /// Read a `u32`.
+    pub fn get_u32(&mut self) -> Option<u32> {
+        self.get_bytes().map(u32::from_le_bytes)
+    }
+
+    /// Read an `i16`.
+    pub fn get_i16(&mut self) -> Option<i16> {
+        self.get_bytes().map(i16::from_le_bytes)
+    }
+
+    /// Read an `i32`.
+    pub fn get_i32(&mut self) -> Option<i32> {
+        self.get_bytes().map(i32::from_le_bytes)
+    }
+
+    /// Read an `f32`.
+    pub fn get_f32(&mut self) -> Option<f32> {
+        self.get_bytes().map(f32::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_round_trip() {
+        let mut writer = PacketWriter::new();
+        writer.put_u16(0xBEEF);
+        writer.put_f32(1.5);
+        writer.put_i16(-2);
+
+        let mut reader = PacketReader::new(writer.as_bytes());
+        assert_eq!(reader.get_u16(), Some(0xBEEF));
+        assert_eq!(reader.get_f32(), Some(1.5));
+        assert_eq!(reader.get_i16(), Some(-2));
+        assert_eq!(reader.get_u8(), None);
+    }
+}
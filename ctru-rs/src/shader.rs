@@ -0,0 +1,628 @@
+//! PICA200 shader binaries.
+//!
+//! Shaders for the GPU are assembled (e.g. with `picasso`) into SHBIN files: a DVLB container holding the shared program code (DVLP)
+//! and one entry point description (DVLE) per shader program. Each DVLE lists the program's uniforms, which are bound to
+//! constant registers chosen by the assembler.
+//!
+//! [`Shader`] parses these files, so that uniforms can be looked up by name and set through [`Uniforms`],
+//! which checks the type and size of the values instead of relying on hardcoded register indices.
+//! Binaries can be embedded in the executable with [`include_shader!`](crate::include_shader).
+//!
+//! # Notes
+//!
+//! This module doesn't upload anything to the GPU: [`Shader::code()`], [`Shader::operand_descriptors()`] and the register values
+//! collected in [`Uniforms`] are meant to be written to the GPU by a rendering library.
+//!
+//! See also <https://www.3dbrew.org/wiki/SHBIN>
+#![doc(alias = "SHBIN")]
+#![doc(alias = "DVLB")]
+#![doc(alias = "PICA")]
+
+use crate::sealed::Sealed;
+use crate::Error;
+
+use std::fmt;
+
+/// Amount of floating point vector registers (`c0` to `c95`).
+pub const FLOAT_REGISTERS: usize = 96;
+
+/// Amount of integer vector registers (`i0` to `i3`).
+pub const INTEGER_REGISTERS: usize = 4;
+
+/// Amount of boolean registers (`b0` to `b15`).
+pub const BOOL_REGISTERS: usize = 16;
+
+const FLOAT_BASE: u16 = 0x10;
+const INTEGER_BASE: u16 = 0x70;
+const BOOL_BASE: u16 = 0x78;
+
+const DVLE_UNIFORM_ENTRY_SIZE: usize = 8;
+
+/// Pipeline stage a shader program runs in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShaderKind {
+    /// Vertex shader.
+    Vertex,
+    /// Geometry shader.
+    Geometry,
+}
+
+/// Type of the registers a uniform is bound to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UniformKind {
+    /// Floating point vectors (`.fvec`), set with `[f32; 4]` values or arrays of them (e.g. a 4x4 matrix).
+    Float,
+    /// Integer vectors (`.ivec`), set with `[u8; 4]` values.
+    Integer,
+    /// Booleans (`.bool`), set with `bool` values.
+    Bool,
+}
+
+/// Uniform declared by a shader program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Uniform {
+    /// Name of the uniform in the shader's source.
+    pub name: String,
+    /// Type of the uniform's registers.
+    pub kind: UniformKind,
+    /// Index of the first register (e.g. `4` for `c4`).
+    pub register: u8,
+    /// Amount of consecutive registers used by the uniform.
+    pub count: u8,
+}
+
+/// Shader program described by a DVLE.
+#[derive(Clone, Debug)]
+pub struct Program {
+    kind: ShaderKind,
+    entry_point: u32,
+    end: u32,
+    uniforms: Vec<Uniform>,
+}
+
+/// Parsed SHBIN file.
+#[derive(Clone, Debug)]
+pub struct Shader {
+    code: Vec<u32>,
+    operand_descriptors: Vec<u32>,
+    programs: Vec<Program>,
+}
+
+/// Error returned when setting a uniform through [`Uniforms::set()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UniformError {
+    /// The program doesn't declare a uniform with this name.
+    Unknown {
+        /// Name of the uniform.
+        name: String,
+    },
+    /// The value doesn't match the type of the uniform.
+    KindMismatch {
+        /// Name of the uniform.
+        name: String,
+        /// Type of the uniform.
+        expected: UniformKind,
+        /// Type of the value.
+        found: UniformKind,
+    },
+    /// The value doesn't fit in the uniform's registers.
+    TooLarge {
+        /// Name of the uniform.
+        name: String,
+        /// Amount of registers used by the uniform.
+        registers: usize,
+        /// Amount of registers needed by the value.
+        provided: usize,
+    },
+}
+
+impl Shader {
+    /// Parse a SHBIN file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the data doesn't start with the `DVLB` magic number, or if any of its sections is truncated.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<Self> {
+        let reader = Reader(data);
+
+        if reader.bytes(0, 4)? != b"DVLB" {
+            return Err(invalid());
+        }
+
+        let program_count = reader.u32(0x4)? as usize;
+        let dvlp = 0x8 + 4 * program_count;
+
+        if reader.bytes(dvlp, 4)? != b"DVLP" {
+            return Err(invalid());
+        }
+
+        let code = reader.words(
+            dvlp + reader.u32(dvlp + 0x8)? as usize,
+            reader.u32(dvlp + 0xC)? as usize,
+        )?;
+
+        // Operand descriptors are 8 bytes long, but only their first word is used.
+        let operand_descriptors = reader
+            .words(
+                dvlp + reader.u32(dvlp + 0x10)? as usize,
+                2 * reader.u32(dvlp + 0x14)? as usize,
+            )?
+            .chunks_exact(2)
+            .map(|descriptor| descriptor[0])
+            .collect();
+
+        let programs = (0..program_count)
+            .map(|index| Program::parse(&reader, reader.u32(0x8 + 4 * index)? as usize))
+            .collect::<crate::Result<_>>()?;
+
+        Ok(Self {
+            code,
+            operand_descriptors,
+            programs,
+        })
+    }
+
+    /// Returns the program code shared by all the programs in the file.
+    pub fn code(&self) -> &[u32] {
+        &self.code
+    }
+
+    /// Returns the operand descriptors used by the program code.
+    pub fn operand_descriptors(&self) -> &[u32] {
+        &self.operand_descriptors
+    }
+
+    /// Returns the programs contained in the file, in order.
+    pub fn programs(&self) -> &[Program] {
+        &self.programs
+    }
+
+    /// Returns the program at the given index, if it exists.
+    pub fn program(&self, index: usize) -> Option<&Program> {
+        self.programs.get(index)
+    }
+}
+
+impl Program {
+    fn parse(reader: &Reader, dvle: usize) -> crate::Result<Self> {
+        if reader.bytes(dvle, 4)? != b"DVLE" {
+            return Err(invalid());
+        }
+
+        let kind = match reader.bytes(dvle + 0x6, 1)?[0] {
+            0 => ShaderKind::Vertex,
+            _ => ShaderKind::Geometry,
+        };
+
+        let uniform_table = dvle + reader.u32(dvle + 0x30)? as usize;
+        let uniform_count = reader.u32(dvle + 0x34)? as usize;
+        let symbols = reader.bytes(
+            dvle + reader.u32(dvle + 0x38)? as usize,
+            reader.u32(dvle + 0x3C)? as usize,
+        )?;
+
+        let mut uniforms = Vec::with_capacity(uniform_count);
+
+        for index in 0..uniform_count {
+            let entry = uniform_table + index * DVLE_UNIFORM_ENTRY_SIZE;
+            let name = symbols
+                .get(reader.u32(entry)? as usize..)
+                .ok_or_else(invalid)?;
+            let name = &name[..name.iter().position(|&b| b == 0).ok_or_else(invalid)?];
+
+            let start = reader.u16(entry + 0x4)?;
+            let end = reader.u16(entry + 0x6)?;
+
+            let (kind, base) = match start {
+                // Input registers are listed too, but they aren't uniforms.
+                0x00..FLOAT_BASE => continue,
+                FLOAT_BASE..INTEGER_BASE => (UniformKind::Float, FLOAT_BASE),
+                INTEGER_BASE..BOOL_BASE => (UniformKind::Integer, INTEGER_BASE),
+                _ => (UniformKind::Bool, BOOL_BASE),
+            };
+
+            // Registers past the end of their bank (such as 0x74 to 0x77) don't exist.
+            let register = usize::from(start - base);
+            let last = end.checked_sub(base).map(usize::from);
+            if !last.is_some_and(|last| register <= last && last < kind.register_count()) {
+                return Err(invalid());
+            }
+
+            uniforms.push(Uniform {
+                name: String::from_utf8_lossy(name).into_owned(),
+                kind,
+                register: (start - base) as u8,
+                count: (end - start + 1) as u8,
+            });
+        }
+
+        Ok(Self {
+            kind,
+            entry_point: reader.u32(dvle + 0x8)?,
+            end: reader.u32(dvle + 0xC)?,
+            uniforms,
+        })
+    }
+
+    /// Returns the pipeline stage the program runs in.
+    pub fn kind(&self) -> ShaderKind {
+        self.kind
+    }
+
+    /// Returns the offset (in words) of the program's `main` procedure within [`Shader::code()`].
+    pub fn entry_point(&self) -> u32 {
+        self.entry_point
+    }
+
+    /// Returns the offset (in words) of the end of the program's `main` procedure within [`Shader::code()`].
+    pub fn end(&self) -> u32 {
+        self.end
+    }
+
+    /// Returns the uniforms declared by the program.
+    pub fn uniforms(&self) -> &[Uniform] {
+        &self.uniforms
+    }
+
+    /// Returns the uniform with the given name, if the program declares it.
+    pub fn uniform(&self, name: &str) -> Option<&Uniform> {
+        self.uniforms.iter().find(|uniform| uniform.name == name)
+    }
+}
+
+impl UniformKind {
+    /// Returns the amount of registers of this type.
+    pub fn register_count(&self) -> usize {
+        match self {
+            Self::Float => FLOAT_REGISTERS,
+            Self::Integer => INTEGER_REGISTERS,
+            Self::Bool => BOOL_REGISTERS,
+        }
+    }
+}
+
+/// Value which can be stored in a uniform by [`Uniforms::set()`].
+///
+/// This trait is implemented for `[f32; 4]` and arrays of it (floating point uniforms), `[u8; 4]` (integer uniforms) and `bool`.
+pub trait UniformValue: Sealed {
+    /// Type of the registers the value is stored in.
+    const KIND: UniformKind;
+
+    /// Returns the amount of registers needed by the value.
+    fn registers(&self) -> usize;
+
+    #[doc(hidden)]
+    fn store(&self, uniforms: &mut Uniforms<'_>, register: usize);
+}
+
+impl UniformValue for [f32; 4] {
+    const KIND: UniformKind = UniformKind::Float;
+
+    fn registers(&self) -> usize {
+        1
+    }
+
+    fn store(&self, uniforms: &mut Uniforms<'_>, register: usize) {
+        uniforms.floats[register] = *self;
+    }
+}
+
+impl<const N: usize> UniformValue for [[f32; 4]; N] {
+    const KIND: UniformKind = UniformKind::Float;
+
+    fn registers(&self) -> usize {
+        N
+    }
+
+    fn store(&self, uniforms: &mut Uniforms<'_>, register: usize) {
+        uniforms.floats[register..register + N].copy_from_slice(self);
+    }
+}
+
+impl UniformValue for [u8; 4] {
+    const KIND: UniformKind = UniformKind::Integer;
+
+    fn registers(&self) -> usize {
+        1
+    }
+
+    fn store(&self, uniforms: &mut Uniforms<'_>, register: usize) {
+        uniforms.integers[register] = *self;
+    }
+}
+
+impl UniformValue for bool {
+    const KIND: UniformKind = UniformKind::Bool;
+
+    fn registers(&self) -> usize {
+        1
+    }
+
+    fn store(&self, uniforms: &mut Uniforms<'_>, register: usize) {
+        uniforms.bools &= !(1 << register);
+        uniforms.bools |= u16::from(*self) << register;
+    }
+}
+
+/// Register values of a program's uniforms.
+///
+/// # Example
+///
+/// ```
+/// # use std::error::Error;
+/// # fn example(shbin: &[u8]) -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::shader::{Shader, Uniforms};
+///
+/// let shader = Shader::from_bytes(shbin)?;
+/// let program = shader.program(0).unwrap();
+///
+/// let mut uniforms = Uniforms::new(program);
+/// uniforms.set("projection", [[0.0f32; 4]; 4])?;
+///
+/// // The value has the wrong type.
+/// assert!(uniforms.set("projection", true).is_err());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Uniforms<'program> {
+    program: &'program Program,
+    floats: [[f32; 4]; FLOAT_REGISTERS],
+    integers: [[u8; 4]; INTEGER_REGISTERS],
+    bools: u16,
+}
+
+impl<'program> Uniforms<'program> {
+    /// Create a set of uniform values for `program`, with all registers set to zero.
+    pub fn new(program: &'program Program) -> Self {
+        Self {
+            program,
+            floats: [[0.0; 4]; FLOAT_REGISTERS],
+            integers: [[0; 4]; INTEGER_REGISTERS],
+            bools: 0,
+        }
+    }
+
+    /// Store `value` in the registers of the uniform with the given name.
+    ///
+    /// A value smaller than the uniform only overwrites its first registers.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the program doesn't declare the uniform,
+    /// if the value is of a different type or if it doesn't fit in the uniform's registers.
+    pub fn set<V: UniformValue>(&mut self, name: &str, value: V) -> Result<(), UniformError> {
+        let uniform = self
+            .program
+            .uniform(name)
+            .ok_or_else(|| UniformError::Unknown {
+                name: String::from(name),
+            })?;
+
+        if uniform.kind != V::KIND {
+            return Err(UniformError::KindMismatch {
+                name: String::from(name),
+                expected: uniform.kind,
+                found: V::KIND,
+            });
+        }
+
+        if value.registers() > usize::from(uniform.count) {
+            return Err(UniformError::TooLarge {
+                name: String::from(name),
+                registers: uniform.count.into(),
+                provided: value.registers(),
+            });
+        }
+
+        value.store(self, uniform.register.into());
+
+        Ok(())
+    }
+
+    /// Returns the program the values belong to.
+    pub fn program(&self) -> &'program Program {
+        self.program
+    }
+
+    /// Returns the values of the floating point registers (`c0` to `c95`), with their components in `x, y, z, w` order.
+    pub fn floats(&self) -> &[[f32; 4]; FLOAT_REGISTERS] {
+        &self.floats
+    }
+
+    /// Returns the values of the integer registers (`i0` to `i3`), with their components in `x, y, z, w` order.
+    pub fn integers(&self) -> &[[u8; 4]; INTEGER_REGISTERS] {
+        &self.integers
+    }
+
+    /// Returns the values of the boolean registers, with `b0` in the lowest bit.
+    pub fn bools(&self) -> u16 {
+        self.bools
+    }
+}
+
+/// Embed a SHBIN file in the executable and parse it as a [`Shader`](crate::shader::Shader).
+///
+/// The path is resolved relative to the current file, like [`include_bytes!`].
+/// The macro evaluates to a [`Result`](crate::Result), which is an error if the file isn't a valid shader binary.
+///
+/// # Example
+///
+/// ```ignore
+/// use ctru::include_shader;
+///
+/// let shader = include_shader!("../shaders/sprite.shbin")?;
+/// ```
+#[macro_export]
+macro_rules! include_shader {
+    ($path:literal $(,)?) => {
+        $crate::shader::Shader::from_bytes(include_bytes!($path))
+    };
+}
+
+/// Bounds-checked little endian reads within a shader binary.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> crate::Result<&'a [u8]> {
+        self.0
+            .get(offset..offset.checked_add(len).ok_or_else(invalid)?)
+            .ok_or_else(invalid)
+    }
+
+    fn u16(&self, offset: usize) -> crate::Result<u16> {
+        Ok(u16::from_le_bytes(
+            self.bytes(offset, 2)?.try_into().unwrap(),
+        ))
+    }
+
+    fn u32(&self, offset: usize) -> crate::Result<u32> {
+        Ok(u32::from_le_bytes(
+            self.bytes(offset, 4)?.try_into().unwrap(),
+        ))
+    }
+
+    fn words(&self, offset: usize, count: usize) -> crate::Result<Vec<u32>> {
+        Ok(self
+            .bytes(offset, count.checked_mul(4).ok_or_else(invalid)?)?
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect())
+    }
+}
+
+fn invalid() -> Error {
+    Error::Other(String::from("invalid shader binary"))
+}
+
+impl fmt::Display for UniformKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Float => write!(f, "float"),
+            Self::Integer => write!(f, "integer"),
+            Self::Bool => write!(f, "bool"),
+        }
+    }
+}
+
+impl fmt::Display for UniformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown { name } => write!(f, "unknown uniform \"{name}\""),
+            Self::KindMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "uniform \"{name}\" is a {expected} uniform, but a {found} value was provided"
+            ),
+            Self::TooLarge {
+                name,
+                registers,
+                provided,
+            } => write!(
+                f,
+                "uniform \"{name}\" uses {registers} registers, but the value needs {provided}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UniformError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_u32(data: &mut Vec<u8>, value: u32) {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Build a SHBIN with one vertex program and two uniforms: `projection` (`c0-c3`) and `flag` (`b2`).
+    fn shbin() -> Vec<u8> {
+        let mut data = Vec::new();
+
+        // DVLB
+        data.extend_from_slice(b"DVLB");
+        push_u32(&mut data, 1);
+        push_u32(&mut data, 0x44);
+
+        // DVLP, with two words of code and one operand descriptor.
+        data.extend_from_slice(b"DVLP");
+        for value in [0, 0x28, 2, 0x30, 1, 0, 0, 0, 0] {
+            push_u32(&mut data, value);
+        }
+        for value in [0x1234, 0x88000000, 0x0000036F, 0] {
+            push_u32(&mut data, value);
+        }
+
+        // DVLE
+        data.extend_from_slice(b"DVLE");
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        push_u32(&mut data, 0);
+        push_u32(&mut data, 1);
+        data.resize(0x44 + 0x30, 0);
+        for value in [0x40, 2, 0x50, 16] {
+            push_u32(&mut data, value);
+        }
+
+        // Uniform table and symbols.
+        push_u32(&mut data, 0);
+        data.extend_from_slice(&[0x10, 0, 0x13, 0]);
+        push_u32(&mut data, 11);
+        data.extend_from_slice(&[0x7A, 0, 0x7A, 0]);
+        data.extend_from_slice(b"projection\0flag\0");
+
+        data
+    }
+
+    #[test]
+    fn uniform_reflection() {
+        let shader = Shader::from_bytes(&shbin()).unwrap();
+        assert_eq!(shader.code(), &[0x1234, 0x88000000]);
+        assert_eq!(shader.operand_descriptors(), &[0x0000036F]);
+
+        let program = shader.program(0).unwrap();
+        assert_eq!(program.kind(), ShaderKind::Vertex);
+        assert_eq!(program.end(), 1);
+        assert_eq!(
+            program.uniform("flag"),
+            Some(&Uniform {
+                name: String::from("flag"),
+                kind: UniformKind::Bool,
+                register: 2,
+                count: 1,
+            })
+        );
+
+        let mut uniforms = Uniforms::new(program);
+        uniforms.set("projection", [[1.0; 4]; 4]).unwrap();
+        uniforms.set("flag", true).unwrap();
+        assert_eq!(uniforms.floats()[3], [1.0; 4]);
+        assert_eq!(uniforms.bools(), 0b100);
+
+        assert!(matches!(
+            uniforms.set("flag", [0u8; 4]),
+            Err(UniformError::KindMismatch { .. })
+        ));
+        assert!(matches!(
+            uniforms.set("projection", [[0.0; 4]; 5]),
+            Err(UniformError::TooLarge { .. })
+        ));
+        assert!(matches!(
+            uniforms.set("missing", true),
+            Err(UniformError::Unknown { .. })
+        ));
+
+        assert!(Shader::from_bytes(&shbin()[..0x50]).is_err());
+
+        // `flag` moved to the nonexistent integer register 0x74.
+        let mut data = shbin();
+        data[0x90..0x94].copy_from_slice(&[0x74, 0, 0x74, 0]);
+        assert!(Shader::from_bytes(&data).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Service shutdown ordering.
+//!
+//! Every service opened by this crate registers itself in a crate-wide shutdown registry.
+//! Services are normally closed when their handles are dropped, but the registry makes it possible to close all of them at once
+//! in a safe order, regardless of the order in which the handles would be dropped:
+//! audio (NDSP) first, then all other services in reverse initialization order, then APT and finally GFX.
+//!
+//! This is mostly useful when a panic occurs: leaving the DSP running while the application exits is known to hang the console.
+//! See [`install_panic_hook()`] to run the shutdown sequence automatically.
+#![doc(alias = "exit")]
+
+use std::sync::{Mutex, MutexGuard};
+
+/// Shutdown stage of a registered service. Stages are closed in declaration order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Stage {
+    /// Services which must be stopped before anything else (e.g. NDSP).
+    Audio,
+    /// Most services.
+    Default,
+    /// APT.
+    System,
+    /// GFX, closed last so that any output stays visible as long as possible.
+    Graphics,
+}
+
+/// Handle to a service registered for shutdown.
+///
+/// Dropping the handle closes the service, unless it was already closed by [`shutdown_all()`].
+pub(crate) struct Registration {
+    id: u64,
+}
+
+struct Entry {
+    id: u64,
+    stage: Stage,
+    name: &'static str,
+    handle: Option<fn() -> Option<u32>>,
+    close: Box<dyn Fn() + Send + Sync>,
+}
+
+struct Registry {
+    entries: Vec<Entry>,
+    next_id: u64,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    entries: Vec::new(),
+    next_id: 0,
+});
+
+/// Register a service's `close` function to be called on shutdown.
+pub(crate) fn register<E>(stage: Stage, name: &'static str, close: E) -> Registration
+where
+    E: Fn() + Send + Sync + 'static,
+{
+    let mut registry = lock_registry();
+
+    let id = registry.next_id;
+    registry.next_id += 1;
+    registry.entries.push(Entry {
+        id,
+        stage,
+        name,
+        handle: None,
+        close: Box::new(close),
+    });
+
+    Registration { id }
+}
+
+/// Close all currently active services in a safe order.
+///
+/// Services are closed stage by stage (audio first, then all other services, then APT and lastly GFX),
+/// in reverse initialization order within each stage.
+///
+/// # Notes
+///
+/// The handles of the closed services remain valid objects, but they must not be used anymore:
+/// this function should only be called when the application is about to exit.
+/// Dropping the handles afterwards doesn't close the services a second time.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::ndsp::Ndsp;
+///
+/// let ndsp = Ndsp::new()?;
+///
+/// // ...
+///
+/// ctru::shutdown::shutdown_all();
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub fn shutdown_all() {
+    let mut entries = std::mem::take(&mut lock_registry().entries);
+
+    entries.sort_by(|a, b| a.stage.cmp(&b.stage).then(b.id.cmp(&a.id)));
+
+    for entry in entries {
+        (entry.close)();
+    }
+}
+
+/// Install a panic hook that closes all active services (see [`shutdown_all()`]) after the panic message has been reported.
+///
+/// The previously set panic hook (by default, the one printing the panic message) is still called first.
+///
+/// # Notes
+///
+/// Once the hook runs, all services are closed: applications which recover from panics (e.g. using [`std::panic::catch_unwind()`])
+/// should not install it.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        shutdown_all();
+    }));
+}
+
+impl Registration {
+    /// Set the function returning the service's session handle, if it is known.
+    pub(crate) fn set_handle_getter(&self, handle: fn() -> Option<u32>) {
+        if let Some(entry) = lock_registry().entries.iter_mut().find(|e| e.id == self.id) {
+            entry.handle = Some(handle);
+        }
+    }
+}
+
+/// Returns the name and handle getter of each registered service, in initialization order.
+pub(crate) fn registered_services() -> Vec<(&'static str, Option<fn() -> Option<u32>>)> {
+    lock_registry()
+        .entries
+        .iter()
+        .map(|entry| (entry.name, entry.handle))
+        .collect()
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let entry = {
+            let mut registry = lock_registry();
+            let index = registry.entries.iter().position(|e| e.id == self.id);
+
+            index.map(|index| registry.entries.remove(index))
+        };
+
+        // The service is closed outside the lock, since closing it may drop other registered services.
+        if let Some(entry) = entry {
+            (entry.close)();
+        }
+    }
+}
+
+fn lock_registry() -> MutexGuard<'static, Registry> {
+    // A panic while holding the lock cannot leave the registry in an inconsistent state, so poisoning is ignored.
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
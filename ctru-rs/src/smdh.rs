@@ -0,0 +1,229 @@
+//! SMDH (title metadata) data.
+//!
+//! Every title installed on the console includes an SMDH file, which holds its localized names, publisher, region lockout and icons.
+//! This is the data shown by the HOME Menu for each title.
+//!
+//! Have a look at [`Title::smdh()`](crate::services::am::Title::smdh) to read the SMDH of an installed title.
+//!
+//! See also <https://www.3dbrew.org/wiki/SMDH>
+#![doc(alias = "icon")]
+
+use crate::image::Image;
+use crate::services::cfgu::{Language, Region};
+use crate::util::str16;
+use crate::Error;
+
+/// Size (in bytes) of an SMDH file.
+pub const SMDH_SIZE: usize = 0x36C0;
+
+/// Width and height (in pixels) of the small icon.
+pub const SMALL_ICON_SIZE: usize = 24;
+
+/// Width and height (in pixels) of the large icon.
+pub const LARGE_ICON_SIZE: usize = 48;
+
+const SMDH_MAGIC: &[u8; 4] = b"SMDH";
+const TITLES_OFFSET: usize = 0x8;
+const TITLE_ENTRY_SIZE: usize = 0x200;
+const SHORT_DESCRIPTION_SIZE: usize = 0x80;
+const LONG_DESCRIPTION_SIZE: usize = 0x100;
+const REGION_LOCKOUT_OFFSET: usize = 0x2018;
+const SMALL_ICON_OFFSET: usize = 0x2040;
+const LARGE_ICON_OFFSET: usize = 0x24C0;
+const REGION_FREE: u32 = 0x7FFFFFFF;
+
+/// Regions in the order of their bits in the region lockout flags.
+const LOCKOUT_REGIONS: [Region; 7] = [
+    Region::Japan,
+    Region::USA,
+    Region::Europe,
+    Region::Australia,
+    Region::China,
+    Region::Korea,
+    Region::Taiwan,
+];
+
+/// Localized names of a title.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TitleNames {
+    /// Short description (usually the title's name).
+    pub short_description: String,
+    /// Long description (usually the title's full name).
+    pub long_description: String,
+    /// Publisher of the title.
+    pub publisher: String,
+}
+
+/// Parsed SMDH file.
+#[derive(Clone)]
+pub struct Smdh {
+    raw: Box<[u8; SMDH_SIZE]>,
+}
+
+impl Smdh {
+    /// Parse an SMDH file from its raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the data is shorter than [`SMDH_SIZE`] or if it doesn't start with the `SMDH` magic number.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<Self> {
+        if data.len() < SMDH_SIZE {
+            return Err(Error::BufferTooShort {
+                provided: data.len(),
+                wanted: SMDH_SIZE,
+            });
+        }
+
+        if &data[0..4] != SMDH_MAGIC {
+            return Err(Error::Other(String::from(
+                "data doesn't have a valid SMDH magic number",
+            )));
+        }
+
+        let mut raw = Box::new([0; SMDH_SIZE]);
+        raw.copy_from_slice(&data[..SMDH_SIZE]);
+
+        Ok(Self { raw })
+    }
+
+    /// Returns the raw bytes of the SMDH file.
+    pub fn as_bytes(&self) -> &[u8; SMDH_SIZE] {
+        &self.raw
+    }
+
+    /// Returns the names of the title in the chosen language.
+    ///
+    /// # Notes
+    ///
+    /// Some titles do not include names for all languages, in which case the returned strings may be empty.
+    pub fn names(&self, language: Language) -> TitleNames {
+        let offset = TITLES_OFFSET + (language as usize) * TITLE_ENTRY_SIZE;
+        let entry = &self.raw[offset..offset + TITLE_ENTRY_SIZE];
+
+        let (short_description, rest) = entry.split_at(SHORT_DESCRIPTION_SIZE);
+        let (long_description, publisher) = rest.split_at(LONG_DESCRIPTION_SIZE);
+
+        TitleNames {
+            short_description: str16::from_le_bytes(short_description),
+            long_description: str16::from_le_bytes(long_description),
+            publisher: str16::from_le_bytes(publisher),
+        }
+    }
+
+    /// Returns the raw region lockout flags.
+    ///
+    /// A value of `0x7FFFFFFF` means the title is region free.
+    pub fn region_lockout(&self) -> u32 {
+        u32::from_le_bytes(
+            self.raw[REGION_LOCKOUT_OFFSET..REGION_LOCKOUT_OFFSET + 4]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    /// Returns whether the title runs on consoles of all regions.
+    pub fn is_region_free(&self) -> bool {
+        self.region_lockout() == REGION_FREE
+    }
+
+    /// Returns the regions the title runs in, decoded from the [region lockout flags](Smdh::region_lockout).
+    pub fn regions(&self) -> Vec<Region> {
+        regions_from_lockout(self.region_lockout())
+    }
+
+    /// Returns the small icon ([`SMALL_ICON_SIZE`]x[`SMALL_ICON_SIZE`]) as RGB565 pixels in left-to-right, top-to-bottom order.
+    pub fn small_icon(&self) -> Vec<u16> {
+        untile(
+            &self.raw[SMALL_ICON_OFFSET..LARGE_ICON_OFFSET],
+            SMALL_ICON_SIZE,
+        )
+    }
+
+    /// Returns the large icon ([`LARGE_ICON_SIZE`]x[`LARGE_ICON_SIZE`]) as RGB565 pixels in left-to-right, top-to-bottom order.
+    pub fn large_icon(&self) -> Vec<u16> {
+        untile(&self.raw[LARGE_ICON_OFFSET..SMDH_SIZE], LARGE_ICON_SIZE)
+    }
+
+    /// Returns the small icon as an [`Image`].
+    pub fn small_icon_image(&self) -> Image {
+        Image::from_rgb565(SMALL_ICON_SIZE, SMALL_ICON_SIZE, &self.small_icon())
+    }
+
+    /// Returns the large icon as an [`Image`].
+    pub fn large_icon_image(&self) -> Image {
+        Image::from_rgb565(LARGE_ICON_SIZE, LARGE_ICON_SIZE, &self.large_icon())
+    }
+}
+
+fn regions_from_lockout(lockout: u32) -> Vec<Region> {
+    LOCKOUT_REGIONS
+        .iter()
+        .enumerate()
+        .filter(|(bit, _)| lockout & (1 << bit) != 0)
+        .map(|(_, &region)| region)
+        .collect()
+}
+
+/// Converts RGB565 pixels stored in the GPU's 8x8 tiled (Morton) order into a linear image of `size`x`size` pixels.
+fn untile(data: &[u8], size: usize) -> Vec<u16> {
+    let mut pixels = vec![0; size * size];
+    let mut source = data
+        .chunks_exact(2)
+        .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]));
+
+    for tile_y in (0..size).step_by(8) {
+        for tile_x in (0..size).step_by(8) {
+            for k in 0..64 {
+                let x = (k & 1) | ((k >> 1) & 2) | ((k >> 2) & 4);
+                let y = ((k >> 1) & 1) | ((k >> 2) & 2) | ((k >> 3) & 4);
+
+                pixels[(tile_y + y) * size + tile_x + x] = source.next().unwrap_or(0);
+            }
+        }
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untile_icon() {
+        // Each pixel holds its position in the tiled data: 8x8 tiles in Z-order, stored row by row.
+        let tiled: Vec<u8> = (0..256u16).flat_map(u16::to_le_bytes).collect();
+
+        let pixels = untile(&tiled, 16);
+
+        assert_eq!(
+            pixels[..16],
+            [0, 1, 4, 5, 16, 17, 20, 21, 64, 65, 68, 69, 80, 81, 84, 85]
+        );
+        assert_eq!(
+            pixels[16..32],
+            [2, 3, 6, 7, 18, 19, 22, 23, 66, 67, 70, 71, 82, 83, 86, 87]
+        );
+        assert_eq!(pixels[7 * 16..7 * 16 + 8], [42, 43, 46, 47, 58, 59, 62, 63]);
+        assert_eq!(pixels[8 * 16], 128);
+        assert_eq!(pixels[255], 255);
+    }
+
+    #[test]
+    fn lockout_regions() {
+        assert_eq!(regions_from_lockout(REGION_FREE), LOCKOUT_REGIONS);
+        assert_eq!(
+            regions_from_lockout(0b0000_0110),
+            [Region::USA, Region::Europe]
+        );
+        assert!(regions_from_lockout(0).is_empty());
+    }
+
+    #[test]
+    fn short_smdh() {
+        assert!(matches!(
+            Smdh::from_bytes(&[0; 16]),
+            Err(Error::BufferTooShort { provided: 16, .. })
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+//! Stereoscopic 3D parallax.
+//!
+//! The top screen shows a different image to each eye when 3D is enabled: the horizontal offset (disparity) between the two images
+//! of an object sets how far behind or in front of the screen it appears. This module computes these offsets from the 3D slider
+//! and the screen's geometry, so that every renderer (GPU projections as well as software renderers drawing each [`Side`])
+//! produces the same depth for the same slider position.
+//!
+//! Depths are expressed relative to the screen's plane: `0.0` is at the screen's surface (no disparity), `1.0` is the furthest
+//! comfortable depth behind the screen, and negative values pop out of the screen.
+//!
+//! # Notes
+//!
+//! Offsets are kept as fractions of pixels, since depth is perceived with sub-pixel precision: GPU renderers should apply them
+//! unrounded with [`Parallax::projection_offset()`], while software renderers can use [`Parallax::pixel_offsets()`], which rounds them
+//! consistently for both eyes.
+#![doc(alias = "3d")]
+#![doc(alias = "parallax")]
+
+use crate::services::gfx::Side;
+
+/// Width of the top screen's image for each eye, in pixels.
+pub const EYE_WIDTH: u16 = 400;
+
+/// Disparity between the two eyes' images of an object at depth `1.0`, with the 3D slider at its maximum, in pixels.
+///
+/// Larger disparities make objects harder to focus on, since the parallax barrier only works at short viewing distances.
+pub const MAX_DISPARITY: f32 = 10.;
+
+/// Parallax of the top screen for a 3D slider position.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::services::gfx::Side;
+/// use ctru::stereo::Parallax;
+///
+/// let parallax = Parallax::current();
+///
+/// // Offsets of the left and right images of an object halfway behind the screen.
+/// let (left, right) = parallax.pixel_offsets(0.5);
+/// assert_eq!(left, parallax.pixel_offset(Side::Left, 0.5));
+/// assert!(right >= left);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Parallax {
+    strength: f32,
+    max_disparity: f32,
+}
+
+impl Parallax {
+    /// Returns the parallax for the current position of the 3D slider.
+    #[doc(alias = "osGet3DSliderState")]
+    pub fn current() -> Self {
+        Self::new(crate::os::current_3d_slider_state())
+    }
+
+    /// Returns the parallax for a 3D slider position, between `0.0` (3D disabled) and `1.0`.
+    ///
+    /// Positions out of this range are clamped.
+    pub fn new(slider: f32) -> Self {
+        Self {
+            strength: slider.clamp(0., 1.),
+            max_disparity: MAX_DISPARITY,
+        }
+    }
+
+    /// Returns the parallax with a different disparity at depth `1.0` for the maximum slider position, instead of [`MAX_DISPARITY`].
+    pub fn with_max_disparity(self, max_disparity: f32) -> Self {
+        Self {
+            max_disparity,
+            ..self
+        }
+    }
+
+    /// Returns the 3D slider position the parallax was computed for.
+    pub fn strength(&self) -> f32 {
+        self.strength
+    }
+
+    /// Returns `true` if the 3D effect is visible, i.e. if the two eyes' images need to be rendered separately.
+    pub fn is_enabled(&self) -> bool {
+        self.strength > 0.
+    }
+
+    /// Returns the disparity of an object at `depth`, in pixels: the offset of its right image relative to its left image.
+    pub fn disparity(&self, depth: f32) -> f32 {
+        self.strength * self.max_disparity * depth
+    }
+
+    /// Returns the horizontal offset to apply to the image of an object at `depth` for the eye `side`, in (fractional) pixels.
+    ///
+    /// Objects behind the screen are moved to the left for the left eye, and to the right for the right eye.
+    pub fn eye_offset(&self, side: Side, depth: f32) -> f32 {
+        let half = self.disparity(depth) / 2.;
+
+        match side {
+            Side::Left => -half,
+            Side::Right => half,
+        }
+    }
+
+    /// Returns [`Parallax::eye_offset()`] in normalized device coordinates, where the screen's width spans from -1 to 1.
+    ///
+    /// Add it to the X coordinate of clip-space positions (multiplied by their W coordinate), or to the horizontal translation
+    /// of a projection matrix, to render each eye with a GPU shader.
+    pub fn projection_offset(&self, side: Side, depth: f32) -> f32 {
+        self.eye_offset(side, depth) * 2. / f32::from(EYE_WIDTH)
+    }
+
+    /// Returns the whole pixel offsets of the left and right images of an object at `depth`.
+    ///
+    /// The offsets are rounded together, so that the distance between them is always the rounded disparity,
+    /// instead of the sum of two rounded halves.
+    pub fn pixel_offsets(&self, depth: f32) -> (i32, i32) {
+        let disparity = self.disparity(depth).round() as i32;
+        let left = -disparity.div_euclid(2);
+
+        (left, left + disparity)
+    }
+
+    /// Returns the whole pixel offset of the image of an object at `depth` for the eye `side`.
+    ///
+    /// See [`Parallax::pixel_offsets()`] for more details.
+    pub fn pixel_offset(&self, side: Side, depth: f32) -> i32 {
+        let (left, right) = self.pixel_offsets(depth);
+
+        match side {
+            Side::Left => left,
+            Side::Right => right,
+        }
+    }
+}
+
+impl Default for Parallax {
+    /// Returns the parallax with 3D disabled.
+    fn default() -> Self {
+        Self::new(0.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eye_offsets() {
+        let parallax = Parallax::new(1.);
+        assert_eq!(parallax.eye_offset(Side::Left, 1.), -MAX_DISPARITY / 2.);
+        assert_eq!(parallax.eye_offset(Side::Right, -1.), -MAX_DISPARITY / 2.);
+        assert_eq!(parallax.projection_offset(Side::Right, 0.), 0.);
+
+        // Rounded offsets always keep the rounded disparity between them.
+        let parallax = Parallax::new(0.3);
+        for depth in [-1., -0.5, 0.25, 1.] {
+            let (left, right) = parallax.pixel_offsets(depth);
+            assert_eq!(right - left, parallax.disparity(depth).round() as i32);
+        }
+
+        assert!(!Parallax::new(-1.).is_enabled());
+        assert_eq!(Parallax::new(2.).strength(), 1.);
+    }
+}
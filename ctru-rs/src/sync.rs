@@ -0,0 +1,400 @@
+//! Synchronization primitives.
+//!
+//! This module provides a [`Mutex`] built directly on `libctru`'s `LightLock`, which can optionally record contention statistics.
+//! These are useful to diagnose problems caused by threads waiting on each other, such as audio underruns
+//! caused by a lock shared between the main loop and the audio callback.
+//!
+//! [`CancelToken`] lets a thread (e.g. the UI) stop blocking operations running on another thread, such as file copies and
+//! UDS receives, without having to wait for them to time out.
+#![doc(alias = "LightLock")]
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::ResultCode;
+use crate::os;
+use crate::progress::Progress;
+
+/// Mutual exclusion primitive built on `LightLock`.
+///
+/// # Notes
+///
+/// Unlike [`std::sync::Mutex`], this mutex doesn't implement poisoning: if a thread panics while holding the lock,
+/// the lock is simply released.
+///
+/// `LightLock` doesn't implement priority inheritance. If a low priority thread holds the lock while a higher priority thread waits on it,
+/// the waiting thread is stalled until the holder gets to run again, which is exactly the kind of problem the contention statistics help find.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// use ctru::sync::Mutex;
+///
+/// let counter = Mutex::with_stats(0);
+///
+/// *counter.lock() += 1;
+///
+/// // Debug formatting doesn't count as an acquisition.
+/// println!("{counter:?}");
+///
+/// let stats = counter.stats().unwrap();
+/// assert_eq!(stats.acquisitions, 1);
+/// ```
+pub struct Mutex<T: ?Sized> {
+    lock: UnsafeCell<ctru_sys::LightLock>,
+    // Chosen when creating the mutex, and only ever updated atomically, so that it can be read without holding `lock`.
+    stats: Option<AtomicStats>,
+    data: UnsafeCell<T>,
+}
+
+/// Counters behind the [`ContentionStats`] of a [`Mutex`].
+#[derive(Default)]
+struct AtomicStats {
+    acquisitions: AtomicU32,
+    contended: AtomicU32,
+    total_wait_ticks: AtomicU64,
+    max_wait_ticks: AtomicU64,
+}
+
+/// Guard giving access to the data protected by a [`Mutex`]. The lock is released when the guard is dropped.
+#[must_use = "if unused the Mutex will immediately unlock"]
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+/// Contention statistics recorded by a [`Mutex`] created with [`Mutex::with_stats()`].
+///
+/// Wait times are measured in system ticks (see [`os::SYSTEM_TICKS_PER_SECOND`]).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContentionStats {
+    /// Number of times the lock was acquired.
+    pub acquisitions: u32,
+    /// Number of times a thread had to wait to acquire the lock.
+    pub contended: u32,
+    /// Total amount of ticks spent waiting to acquire the lock.
+    pub total_wait_ticks: u64,
+    /// Longest amount of ticks spent waiting to acquire the lock.
+    pub max_wait_ticks: u64,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T> Mutex<T> {
+    /// Create a new mutex protecting `value`, without contention statistics.
+    #[doc(alias = "LightLock_Init")]
+    pub fn new(value: T) -> Self {
+        Self::with_optional_stats(value, false)
+    }
+
+    /// Create a new mutex protecting `value`, which records contention statistics.
+    pub fn with_stats(value: T) -> Self {
+        Self::with_optional_stats(value, true)
+    }
+
+    fn with_optional_stats(value: T, stats: bool) -> Self {
+        let mut lock = 0;
+        unsafe { ctru_sys::LightLock_Init(&mut lock) };
+
+        Self {
+            lock: UnsafeCell::new(lock),
+            stats: stats.then(AtomicStats::default),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consume the mutex, returning the protected data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquire the lock, blocking the current thread until it's available.
+    #[doc(alias = "LightLock_Lock")]
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        // Without statistics to record, there's no need to try acquiring the lock first.
+        if self.stats.is_none() {
+            unsafe { ctru_sys::LightLock_Lock(self.lock.get()) };
+            return MutexGuard { mutex: self };
+        }
+
+        if self.try_acquire() {
+            self.record(None);
+        } else {
+            let start = os::system_tick();
+            unsafe { ctru_sys::LightLock_Lock(self.lock.get()) };
+            self.record(Some(os::system_tick() - start));
+        }
+
+        MutexGuard { mutex: self }
+    }
+
+    /// Try to acquire the lock without blocking.
+    ///
+    /// Returns [`None`] if the lock is held by another thread.
+    #[doc(alias = "LightLock_TryLock")]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        if self.try_acquire() {
+            self.record(None);
+            Some(MutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a snapshot of the contention statistics, or [`None`] if the mutex wasn't created with [`Mutex::with_stats()`].
+    ///
+    /// Reading the statistics doesn't acquire the lock. While other threads use the mutex, the counters are read one by one,
+    /// so they may be off by the acquisitions happening in the meantime.
+    pub fn stats(&self) -> Option<ContentionStats> {
+        self.stats.as_ref().map(|stats| ContentionStats {
+            acquisitions: stats.acquisitions.load(Ordering::Relaxed),
+            contended: stats.contended.load(Ordering::Relaxed),
+            total_wait_ticks: stats.total_wait_ticks.load(Ordering::Relaxed),
+            max_wait_ticks: stats.max_wait_ticks.load(Ordering::Relaxed),
+        })
+    }
+
+    /// Reset the contention statistics to zero.
+    pub fn reset_stats(&self) {
+        if let Some(stats) = &self.stats {
+            stats.acquisitions.store(0, Ordering::Relaxed);
+            stats.contended.store(0, Ordering::Relaxed);
+            stats.total_wait_ticks.store(0, Ordering::Relaxed);
+            stats.max_wait_ticks.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns a mutable reference to the protected data. No locking is needed, since the mutex is mutably borrowed.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Acquire the lock if it's available, without recording it in the statistics.
+    fn try_acquire(&self) -> bool {
+        unsafe { ctru_sys::LightLock_TryLock(self.lock.get()) == 0 }
+    }
+
+    fn record(&self, wait_ticks: Option<u64>) {
+        if let Some(stats) = &self.stats {
+            stats.acquisitions.fetch_add(1, Ordering::Relaxed);
+
+            if let Some(ticks) = wait_ticks {
+                stats.contended.fetch_add(1, Ordering::Relaxed);
+                stats.total_wait_ticks.fetch_add(ticks, Ordering::Relaxed);
+                stats.max_wait_ticks.fetch_max(ticks, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl ContentionStats {
+    /// Returns the total time spent waiting to acquire the lock.
+    pub fn total_wait(&self) -> Duration {
+        os::ticks_to_duration(self.total_wait_ticks)
+    }
+
+    /// Returns the longest time spent waiting to acquire the lock.
+    pub fn max_wait(&self) -> Duration {
+        os::ticks_to_duration(self.max_wait_ticks)
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    #[doc(alias = "LightLock_Unlock")]
+    fn drop(&mut self) {
+        unsafe { ctru_sys::LightLock_Unlock(self.mutex.lock.get()) };
+    }
+}
+
+/// Token used to cancel blocking operations from another thread.
+///
+/// All clones of a token share the same state. Cancelling the token signals a kernel event, so operations waiting on
+/// kernel objects (see [`CancelToken::wait_for()`]) wake up immediately instead of at their next timeout.
+/// Operations reporting their [`Progress`] also accept a token, which is cancelled once they see it.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::fs;
+/// use ctru::sync::CancelToken;
+///
+/// let token = CancelToken::new()?;
+///
+/// let mut worker_token = token.clone();
+/// let worker = std::thread::spawn(move || {
+///     fs::copy_with_progress("sdmc:/video.avi", "sdmc:/backup/video.avi", &mut worker_token)
+/// });
+///
+/// // The user pressed B.
+/// token.cancel();
+///
+/// assert!(matches!(worker.join().unwrap(), Err(ctru::Error::Cancelled) | Ok(_)));
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CancelToken(Arc<CancelState>);
+
+struct CancelState {
+    event: ctru_sys::Handle,
+    cancelled: AtomicBool,
+}
+
+impl CancelToken {
+    /// Create a token which isn't cancelled.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the kernel event couldn't be created (e.g. because the process ran out of handles).
+    #[doc(alias = "svcCreateEvent")]
+    pub fn new() -> crate::Result<Self> {
+        let mut event = 0;
+        ResultCode(unsafe { ctru_sys::svcCreateEvent(&mut event, ctru_sys::RESET_STICKY) })?;
+
+        Ok(Self(Arc::new(CancelState {
+            event,
+            cancelled: AtomicBool::new(false),
+        })))
+    }
+
+    /// Cancel the operations using this token (or any of its clones).
+    ///
+    /// Cancelling a token can't be undone.
+    #[doc(alias = "svcSignalEvent")]
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Release);
+        let _ = unsafe { ctru_sys::svcSignalEvent(self.0.event) };
+    }
+
+    /// Returns `true` if the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Returns [`Error::Cancelled`](crate::Error::Cancelled) if the token was cancelled.
+    pub fn check(&self) -> crate::Result<()> {
+        if self.is_cancelled() {
+            Err(crate::Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the kernel event signalled when the token is cancelled.
+    ///
+    /// The handle stays valid as long as the token or any of its clones is alive. It must not be closed.
+    pub fn event(&self) -> ctru_sys::Handle {
+        self.0.event
+    }
+
+    /// Wait until `handle` is signalled, the token is cancelled, or `timeout` expires.
+    ///
+    /// Returns `true` if `handle` was signalled and `false` if `timeout` expired. Waits indefinitely if `timeout` is [`None`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`Error::Cancelled`](crate::Error::Cancelled) if the token was cancelled,
+    /// or another error if `handle` isn't a valid kernel object.
+    #[doc(alias = "svcWaitSynchronizationN")]
+    pub fn wait_for(
+        &self,
+        handle: ctru_sys::Handle,
+        timeout: Option<Duration>,
+    ) -> crate::Result<bool> {
+        self.check()?;
+
+        let timeout = match timeout {
+            Some(timeout) => i64::try_from(timeout.as_nanos()).unwrap_or(i64::MAX),
+            None => -1,
+        };
+
+        let handles = [self.0.event, handle];
+        let mut index = 0;
+
+        let result = unsafe {
+            ctru_sys::svcWaitSynchronizationN(&mut index, handles.as_ptr(), 2, false, timeout)
+        };
+
+        if crate::Error::Os(result).is_timeout() {
+            return Ok(false);
+        }
+        ResultCode(result)?;
+
+        // The cancellation event comes first, so it wins if both are signalled.
+        match index {
+            0 => Err(crate::Error::Cancelled),
+            _ => Ok(true),
+        }
+    }
+}
+
+impl Progress for CancelToken {
+    fn update(&mut self, _done: u64, _total: Option<u64>) {}
+
+    fn is_cancelled(&self) -> bool {
+        CancelToken::is_cancelled(self)
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for CancelState {
+    #[doc(alias = "svcCloseHandle")]
+    fn drop(&mut self) {
+        let _ = unsafe { ctru_sys::svcCloseHandle(self.event) };
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Mutex");
+
+        // Formatting the mutex isn't one of its acquisitions, so it isn't counted in the statistics.
+        if self.try_acquire() {
+            let guard = MutexGuard { mutex: self };
+            d.field("data", &&*guard);
+        } else {
+            d.field("data", &format_args!("<locked>"));
+        }
+
+        d.finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,366 @@
+//! HOME Menu themes.
+//!
+//! Themes are distributed as a directory (or an archive of it) holding the LZ11-compressed theme data `body_LZ.bin`,
+//! and optionally the background music `bgm.bcstm`, the theme's name and icon `info.smdh` and a `preview.png` screenshot.
+//! [`Theme`] reads and validates those files, and [`ThemeInstaller`] writes them to the HOME Menu's extdata,
+//! updating the size table of `ThemeManage.bin` and selecting the theme in the HOME Menu's save data.
+//!
+//! # Notes
+//!
+//! Only the Japanese, American and European HOME Menus support themes. Writing to their extdata requires
+//! the appropriate access rights, and the new theme is only loaded by the HOME Menu after a reboot.
+//! Only one theme is installed at a time: installing a theme turns theme shuffle off.
+//!
+//! # Additional Resources
+//!
+//! - <https://www.3dbrew.org/wiki/Home_Menu/Themes>
+//! - <https://www.3dbrew.org/wiki/Extdata#NAND_Shared_Extdata>
+//! - <https://github.com/astronautlevel2/Anemone3DS/blob/master/source/themes.c>, whose layout of `ThemeManage.bin` is followed here
+#![doc(alias = "body_LZ")]
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::formats::compress::{self, Compression};
+use crate::services::cfgu::Region;
+use crate::services::fs::{ArchiveID, ArchivePath, MediaType, MountedArchive};
+use crate::smdh::Smdh;
+use crate::Error;
+
+/// Maximum size of the compressed theme data (the size of `BodyCache.bin`).
+pub const MAX_BODY_SIZE: usize = 0x15_0000;
+
+/// Maximum size of the background music (the size of `BgmCache.bin`).
+pub const MAX_BGM_SIZE: usize = 0x33_7000;
+
+const THEME_MANAGE_SIZE: usize = 0x800;
+/// Offsets of the body and background music size tables of theme shuffle in `ThemeManage.bin`, one entry per shuffled theme.
+const SHUFFLE_BODY_SIZES: usize = 0x338;
+const SHUFFLE_BGM_SIZES: usize = 0x360;
+const HOME_MOUNT_NAME: &str = "homeext";
+const THEME_MOUNT_NAME: &str = "themeext";
+const SAVE_DATA_PATH: &str = "homeext:/SaveData.dat";
+const THEME_MANAGE_PATH: &str = "themeext:/ThemeManage.bin";
+const BODY_CACHE_PATH: &str = "themeext:/BodyCache.bin";
+const BGM_CACHE_PATH: &str = "themeext:/BgmCache.bin";
+
+/// Offset of the current theme entry in the HOME Menu's `SaveData.dat`.
+const THEME_ENTRY: usize = 0x13B8;
+/// Size of a theme entry: its index, the low bits of its DLC title ID, its type and padding.
+const THEME_ENTRY_SIZE: usize = 8;
+/// Number of entries of the theme shuffle list, following the current theme entry.
+const SHUFFLE_ENTRIES: usize = 10;
+/// Offset of the theme shuffle flag.
+const SHUFFLE_FLAG: usize = 0x141B;
+/// Type of the theme entries of custom themes, read from the extdata caches.
+const CUSTOM_THEME_TYPE: u8 = 3;
+
+/// HOME Menu theme.
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::services::cfgu::{Cfgu, Language};
+/// use ctru::themes::{Theme, ThemeInstaller};
+///
+/// let theme = Theme::from_dir("sdmc:/Themes/Ocean")?;
+///
+/// let cfgu = Cfgu::new()?;
+///
+/// if let Some(info) = theme.info() {
+///     println!("Installing {}", info.names(Language::English).short_description);
+/// }
+///
+/// let installer = ThemeInstaller::new(cfgu.region()?)?;
+/// installer.install(&theme)?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Theme {
+    body: Vec<u8>,
+    bgm: Option<Vec<u8>>,
+    info: Option<Smdh>,
+    bgm_enabled: bool,
+}
+
+impl Theme {
+    /// Create a theme from its compressed theme data (the contents of `body_LZ.bin`) and optional background music.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the theme data isn't valid LZ11-compressed theme data, if the background music isn't
+    /// a [BCSTM](crate::formats::bcstm) file, or if one of them is too big for the HOME Menu's caches
+    /// (see [`MAX_BODY_SIZE`] and [`MAX_BGM_SIZE`]).
+    pub fn new(body: Vec<u8>, bgm: Option<Vec<u8>>) -> crate::Result<Self> {
+        if Compression::detect(&body) != Some(Compression::Lz11) {
+            return Err(Error::Other(String::from(
+                "the theme data isn't LZ11-compressed",
+            )));
+        }
+        check_size("theme data", body.len(), MAX_BODY_SIZE)?;
+
+        let data = compress::decompress(&body)?;
+        if data.get(..4) != Some(&1u32.to_le_bytes()) {
+            return Err(Error::Other(String::from("unsupported theme data version")));
+        }
+        let bgm_enabled = data.get(5).is_some_and(|&enabled| enabled != 0);
+
+        if let Some(bgm) = &bgm {
+            if bgm.get(..4) != Some(b"CSTM") {
+                return Err(Error::Other(String::from(
+                    "the background music isn't a BCSTM file",
+                )));
+            }
+            check_size("background music", bgm.len(), MAX_BGM_SIZE)?;
+        }
+
+        Ok(Self {
+            body,
+            bgm,
+            info: None,
+            bgm_enabled,
+        })
+    }
+
+    /// Read a theme from a directory holding its `body_LZ.bin` file, and optionally `bgm.bcstm` and `info.smdh`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `body_LZ.bin` can't be read, if one of the files fails to read or parse,
+    /// or for the same reasons as [`Theme::new()`].
+    pub fn from_dir(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+
+        let body = fs::read(path.join("body_LZ.bin"))?;
+        let bgm = read_optional(&path.join("bgm.bcstm"))?;
+        let info = read_optional(&path.join("info.smdh"))?
+            .map(|data| Smdh::from_bytes(&data))
+            .transpose()?;
+
+        Ok(Self {
+            info,
+            ..Self::new(body, bgm)?
+        })
+    }
+
+    /// Set the theme's name and icon.
+    pub fn set_info(&mut self, info: Option<Smdh>) {
+        self.info = info;
+    }
+
+    /// Returns the theme's name and icon, if known.
+    pub fn info(&self) -> Option<&Smdh> {
+        self.info.as_ref()
+    }
+
+    /// Returns the compressed theme data.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the decompressed theme data.
+    pub fn decompressed_body(&self) -> crate::Result<Vec<u8>> {
+        compress::decompress(&self.body)
+    }
+
+    /// Returns the background music, if the theme has any.
+    pub fn bgm(&self) -> Option<&[u8]> {
+        self.bgm.as_deref()
+    }
+
+    /// Returns `true` if the theme data asks the HOME Menu to play the background music.
+    pub fn is_bgm_enabled(&self) -> bool {
+        self.bgm_enabled
+    }
+}
+
+/// The HOME Menu's extdata archives, mounted to install themes.
+///
+/// Both archives stay mounted as long as the installer is alive.
+pub struct ThemeInstaller {
+    _home: MountedArchive,
+    _themes: MountedArchive,
+}
+
+impl ThemeInstaller {
+    /// Mount the extdata archives of the HOME Menu of the given region.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the HOME Menu of `region` doesn't support themes,
+    /// or if the archives couldn't be mounted (e.g. because of insufficient access rights).
+    pub fn new(region: Region) -> crate::Result<Self> {
+        let (home_id, themes_id) = extdata_ids(region).ok_or_else(|| {
+            Error::Other(format!(
+                "the HOME Menu of region {region:?} doesn't support themes"
+            ))
+        })?;
+
+        let mount = |id, name| {
+            let path = ArchivePath::binary_words(&[MediaType::Sd as u32, id, 0]);
+            MountedArchive::with_path(ArchiveID::Extdata, &path, name)
+        };
+
+        Ok(Self {
+            _home: mount(home_id, HOME_MOUNT_NAME)?,
+            _themes: mount(themes_id, THEME_MOUNT_NAME)?,
+        })
+    }
+
+    /// Install `theme` as the current theme.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if one of the extdata files couldn't be read or written.
+    /// The theme extdata may be left partially written, but the HOME Menu only switches to the new theme
+    /// once its save data is written, as the last step.
+    pub fn install(&self, theme: &Theme) -> crate::Result<()> {
+        let bgm = theme.bgm().unwrap_or_default();
+
+        write_padded(BODY_CACHE_PATH, theme.body(), MAX_BODY_SIZE)?;
+        write_padded(BGM_CACHE_PATH, bgm, MAX_BGM_SIZE)?;
+
+        let mut manage = fs::read(THEME_MANAGE_PATH)?;
+        if manage.len() < THEME_MANAGE_SIZE {
+            return Err(Error::Other(String::from("ThemeManage.bin is too short")));
+        }
+        update_theme_manage(&mut manage, theme.body().len() as u32, bgm.len() as u32);
+        write_in_place(THEME_MANAGE_PATH, &manage)?;
+
+        let mut save_data = fs::read(SAVE_DATA_PATH)?;
+        if save_data.len() <= SHUFFLE_FLAG {
+            return Err(Error::Other(String::from("SaveData.dat is too short")));
+        }
+        select_custom_theme(&mut save_data);
+        write_in_place(SAVE_DATA_PATH, &save_data)?;
+
+        Ok(())
+    }
+}
+
+/// Returns the IDs of the HOME Menu's extdata and theme extdata for `region`.
+fn extdata_ids(region: Region) -> Option<(u32, u32)> {
+    match region {
+        Region::Japan => Some((0x82, 0x2CC)),
+        Region::USA => Some((0x8F, 0x2CD)),
+        Region::Europe | Region::Australia => Some((0x98, 0x2CE)),
+        _ => None,
+    }
+}
+
+/// Fill the fields of `ThemeManage.bin` describing the theme caches, as Anemone3DS does for a single theme.
+fn update_theme_manage(data: &mut [u8], body_size: u32, bgm_size: u32) {
+    data[0x00] = 1;
+    data[0x01] = 0;
+
+    let mut write = |offset: usize, value: u32| {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+    write(0x08, body_size);
+    write(0x0C, bgm_size);
+    write(0x10, 0xFF);
+    write(0x14, 1);
+    // No DLC theme: the theme is read from the caches.
+    write(0x18, 0xFF);
+    write(0x1C, 0x200);
+
+    // Theme shuffle is turned off, so none of its themes has any data.
+    data[SHUFFLE_BODY_SIZES..SHUFFLE_BODY_SIZES + SHUFFLE_ENTRIES * 4].fill(0);
+    data[SHUFFLE_BGM_SIZES..SHUFFLE_BGM_SIZES + SHUFFLE_ENTRIES * 4].fill(0);
+}
+
+/// Select the custom theme in the HOME Menu's `SaveData.dat`, turning theme shuffle off.
+fn select_custom_theme(data: &mut [u8]) {
+    data[THEME_ENTRY..THEME_ENTRY + THEME_ENTRY_SIZE * (SHUFFLE_ENTRIES + 1)].fill(0);
+    data[THEME_ENTRY] = 0xFF;
+    data[THEME_ENTRY + 5] = CUSTOM_THEME_TYPE;
+    data[SHUFFLE_FLAG] = 0;
+}
+
+fn check_size(what: &str, size: usize, max: usize) -> crate::Result<()> {
+    if size > max {
+        return Err(Error::Other(format!(
+            "the {what} is {size} bytes long, but the HOME Menu only supports up to {max} bytes"
+        )));
+    }
+
+    Ok(())
+}
+
+fn read_optional(path: &Path) -> crate::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Overwrite the start of a cache file, zeroing the rest of its `size` bytes.
+fn write_padded(path: &str, data: &[u8], size: usize) -> crate::Result<()> {
+    let mut padded = vec![0; size];
+    padded[..data.len()].copy_from_slice(data);
+
+    write_in_place(path, &padded)
+}
+
+fn write_in_place(path: &str, data: &[u8]) -> crate::Result<()> {
+    // Extdata files cannot be resized, so we write the data in-place.
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.write_all(data)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn theme_validation() {
+        // LZ11 header for 8 bytes, then a flag byte announcing 8 literals: version 1, with BGM enabled.
+        let body = vec![0x11, 8, 0, 0, 0x00, 1, 0, 0, 0, 0, 1, 0, 0];
+
+        let theme = Theme::new(body.clone(), Some(b"CSTM".to_vec())).unwrap();
+        assert!(theme.is_bgm_enabled());
+        assert_eq!(theme.decompressed_body().unwrap(), [1, 0, 0, 0, 0, 1, 0, 0]);
+
+        assert!(Theme::new(body.clone(), Some(b"RIFF".to_vec())).is_err());
+        assert!(Theme::new(body[4..].to_vec(), None).is_err());
+    }
+
+    #[test]
+    fn extdata_updates() {
+        let mut manage = vec![0xAA; THEME_MANAGE_SIZE];
+        update_theme_manage(&mut manage, 0x1234, 0x5678);
+
+        // Header written by Anemone3DS for a single theme with these sizes. Other bytes are left untouched.
+        #[rustfmt::skip]
+        let header = [
+            0x01, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA,
+            0x34, 0x12, 0x00, 0x00, 0x78, 0x56, 0x00, 0x00,
+            0xFF, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+            0xFF, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00,
+        ];
+        assert_eq!(manage[..0x20], header);
+        assert!(manage[0x20..0x338].iter().all(|&b| b == 0xAA));
+        assert!(manage[0x338..0x388].iter().all(|&b| b == 0));
+        assert!(manage[0x388..].iter().all(|&b| b == 0xAA));
+
+        let mut save_data = vec![0xAA; 0x1500];
+        select_custom_theme(&mut save_data);
+        assert_eq!(
+            save_data[THEME_ENTRY..THEME_ENTRY + 8],
+            [0xFF, 0, 0, 0, 0, CUSTOM_THEME_TYPE, 0, 0]
+        );
+        assert!(save_data[THEME_ENTRY + 8..0x1410].iter().all(|&b| b == 0));
+        assert_eq!(save_data[SHUFFLE_FLAG], 0);
+        assert_eq!(save_data[0x1410], 0xAA);
+    }
+}
@@ -0,0 +1,70 @@
+//! CPU core utilities.
+//!
+//! The Old 3DS has two ARM11 cores, while the New 3DS has four. Threads created via [`std::thread`] run on the application core (core 0)
+//! by default, but can be moved to other cores with `std::os::horizon::thread::BuilderExt::processor_id()` to balance workloads explicitly.
+//! This module provides the information needed to make that choice at runtime.
+//!
+//! # Notes
+//!
+//! Not all cores are freely available to applications: core 1 (the system core) only runs application threads for a limited amount of time
+//! (see `APT_SetAppCpuTimeLimit`), and the fourth core of the New 3DS is reserved for the system.
+#![doc(alias = "core")]
+#![doc(alias = "affinity")]
+
+use crate::error::ResultCode;
+
+/// Returns the ID of the core the current thread is running on.
+#[doc(alias = "svcGetProcessorID")]
+pub fn current_core() -> u8 {
+    unsafe { ctru_sys::svcGetProcessorID() as u8 }
+}
+
+/// Returns the number of ARM11 cores of the console: 2 on the Old 3DS and 4 on the New 3DS.
+#[doc(alias = "APT_CheckNew3DS")]
+pub fn available_core_count() -> usize {
+    let mut is_new_3ds = false;
+
+    // The check can only fail if APT is unavailable, which only happens on the Old 3DS' earliest firmwares.
+    let _ = unsafe { ctru_sys::APT_CheckNew3DS(&mut is_new_3ds) };
+
+    if is_new_3ds {
+        4
+    } else {
+        2
+    }
+}
+
+/// Returns the affinity mask of the current thread, where each bit corresponds to a core the thread is allowed to run on.
+#[doc(alias = "svcGetThreadAffinityMask")]
+pub fn affinity() -> crate::Result<u8> {
+    let mut mask = 0;
+
+    ResultCode(unsafe {
+        ctru_sys::svcGetThreadAffinityMask(
+            &mut mask,
+            ctru_sys::CUR_THREAD_HANDLE,
+            available_core_count() as i32,
+        )
+    })?;
+
+    Ok(mask)
+}
+
+/// Set the affinity mask of the current thread, where each bit corresponds to a core the thread is allowed to run on.
+///
+/// # Notes
+///
+/// The official kernel doesn't implement this operation for existing threads, so this function returns an error on most setups.
+/// Choosing the core when creating the thread (with `std::os::horizon::thread::BuilderExt::processor_id()`) is always supported.
+#[doc(alias = "svcSetThreadAffinityMask")]
+pub fn set_affinity(mask: u8) -> crate::Result<()> {
+    ResultCode(unsafe {
+        ctru_sys::svcSetThreadAffinityMask(
+            ctru_sys::CUR_THREAD_HANDLE,
+            &mask,
+            available_core_count() as i32,
+        )
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,229 @@
+//! Frame timing for games.
+//!
+//! [`GameClock`] measures the time between frames for the game logic. The time the application spends suspended
+//! (in the HOME Menu, an applet or Sleep mode) is left out (see [`os::suspended_time()`](crate::os::suspended_time)),
+//! and deltas are clamped, so a game doesn't try to catch up with minutes of simulation when it gets back to the foreground.
+#![doc(alias = "delta")]
+#![doc(alias = "timestep")]
+
+use std::time::Duration;
+
+use crate::os;
+
+/// Longest delta reported by a [`GameClock`] unless changed with [`GameClock::set_max_delta()`].
+pub const DEFAULT_MAX_DELTA: Duration = Duration::from_millis(100);
+
+/// Clock measuring the time elapsed between frames of the main loop.
+///
+/// Call [`GameClock::tick()`] once per frame, then read [`GameClock::delta()`] for variable-rate logic (e.g. animations),
+/// or run the simulation at a fixed rate with [`GameClock::fixed_steps()`].
+///
+/// # Example
+///
+/// ```
+/// # let _runner = test_runner::GdbRunner::default();
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::prelude::*;
+/// use ctru::time::GameClock;
+/// use std::time::Duration;
+///
+/// let apt = Apt::new()?;
+/// let gfx = Gfx::new()?;
+///
+/// let mut clock = GameClock::new();
+/// let mut position = 0.0;
+///
+/// while apt.main_loop() {
+///     clock.tick();
+///
+///     // Physics at 60 steps per second, whatever the frame rate.
+///     for _ in clock.fixed_steps(Duration::from_micros(16_667)) {
+///         position += 1.0 / 60.0;
+///     }
+///
+///     gfx.wait_for_vblank();
+/// #   break;
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct GameClock {
+    /// System tick and suspended time at the last call to `tick()`, or [`None`] before the first one.
+    last: Option<(u64, Duration)>,
+    delta: Duration,
+    total: Duration,
+    frames: u64,
+    max_delta: Duration,
+    time_scale: f64,
+    /// Time not yet consumed by `fixed_steps()`.
+    accumulator: Duration,
+}
+
+impl GameClock {
+    /// Create a clock starting at the first call to [`GameClock::tick()`].
+    pub fn new() -> Self {
+        // Start tracking suspensions now, so the first frames already account for them.
+        os::suspended_time();
+
+        Self {
+            last: None,
+            delta: Duration::ZERO,
+            total: Duration::ZERO,
+            frames: 0,
+            max_delta: DEFAULT_MAX_DELTA,
+            time_scale: 1.0,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// Start a new frame, measuring the time the application ran in the foreground since the previous one.
+    ///
+    /// Returns the new [`delta()`](GameClock::delta), which is zero for the first frame.
+    pub fn tick(&mut self) -> Duration {
+        let now = (os::system_tick(), os::suspended_time());
+
+        let elapsed = match self.last.replace(now) {
+            Some((tick, suspended)) => {
+                os::ticks_to_duration(now.0 - tick).saturating_sub(now.1.saturating_sub(suspended))
+            }
+            None => Duration::ZERO,
+        };
+
+        self.advance(elapsed)
+    }
+
+    fn advance(&mut self, elapsed: Duration) -> Duration {
+        self.delta = elapsed.min(self.max_delta).mul_f64(self.time_scale);
+        self.total += self.delta;
+        self.accumulator += self.delta;
+        self.frames += 1;
+
+        self.delta
+    }
+
+    /// Returns the (clamped and scaled) time elapsed between the last two frames.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    /// Returns [`GameClock::delta()`] in seconds.
+    pub fn delta_secs(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Returns the sum of all deltas, i.e. the game time elapsed since the first frame.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Returns the number of frames started with [`GameClock::tick()`].
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns the longest delta reported by the clock.
+    pub fn max_delta(&self) -> Duration {
+        self.max_delta
+    }
+
+    /// Set the longest delta reported by the clock, which limits the work done after a slow frame.
+    ///
+    /// The default value is [`DEFAULT_MAX_DELTA`].
+    pub fn set_max_delta(&mut self, max_delta: Duration) {
+        self.max_delta = max_delta;
+    }
+
+    /// Returns the factor applied to the elapsed time.
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Set a factor applied to the elapsed time (after clamping), e.g. `0.5` for slow motion or `0.0` to pause the game.
+    ///
+    /// Negative and non-finite values are treated as `0.0`.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = if time_scale.is_finite() {
+            time_scale.max(0.0)
+        } else {
+            0.0
+        };
+    }
+
+    /// Returns an iterator running the game time accumulated since the last call in steps of `step`.
+    ///
+    /// Time left over (less than a step) is kept for the next frames. See [`GameClock::step_alpha()`] to interpolate
+    /// the rendered state between steps.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `step` is zero.
+    pub fn fixed_steps(&mut self, step: Duration) -> FixedSteps<'_> {
+        assert!(!step.is_zero(), "the fixed step must not be zero");
+
+        FixedSteps { clock: self, step }
+    }
+
+    /// Returns how far (between 0 and 1) the game time left over by [`GameClock::fixed_steps()`] is into the next step.
+    pub fn step_alpha(&self, step: Duration) -> f32 {
+        if step.is_zero() {
+            return 0.0;
+        }
+
+        (self.accumulator.as_secs_f64() / step.as_secs_f64()).min(1.0) as f32
+    }
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator returned by [`GameClock::fixed_steps()`], yielding once per step to run.
+pub struct FixedSteps<'clock> {
+    clock: &'clock mut GameClock,
+    step: Duration,
+}
+
+impl Iterator for FixedSteps<'_> {
+    type Item = ();
+
+    fn next(&mut self) -> Option<()> {
+        self.clock.accumulator = self.clock.accumulator.checked_sub(self.step)?;
+
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamping_and_fixed_steps() {
+        let mut clock = GameClock::new();
+        let step = Duration::from_millis(10);
+
+        assert_eq!(
+            clock.advance(Duration::from_millis(25)),
+            Duration::from_millis(25)
+        );
+        assert_eq!(clock.fixed_steps(step).count(), 2);
+        assert!((clock.step_alpha(step) - 0.5).abs() < 1e-6);
+
+        // A long pause only advances the game by the maximum delta.
+        assert_eq!(clock.advance(Duration::from_secs(30)), DEFAULT_MAX_DELTA);
+        assert_eq!(clock.fixed_steps(step).count(), 10);
+
+        clock.set_time_scale(0.5);
+        assert_eq!(clock.advance(Duration::from_millis(20)), step);
+        assert_eq!(clock.fixed_steps(step).count(), 1);
+
+        assert_eq!(clock.frame_count(), 3);
+        assert_eq!(clock.total(), Duration::from_millis(135));
+    }
+}
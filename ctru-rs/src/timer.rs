@@ -0,0 +1,506 @@
+//! Timers multiplexed over a single kernel timer.
+//!
+//! Processes can only hold a limited amount of kernel handles, so creating a kernel timer for every timeout of an application
+//! doesn't scale. This module keeps all logical timers ([`after()`] and [`interval()`]) in a timing wheel with a resolution
+//! of one millisecond, and arms one kernel timer (see `svcCreateTimer`) for the earliest of them.
+//! A small driver thread, started by the first timer, waits on the kernel timer and fires the expired logical timers.
+//!
+//! Timers can be waited on from synchronous code ([`Timer::wait()`], [`Interval::wait()`]), or awaited from any executor,
+//! such as the [`runtime`](crate::runtime).
+//!
+//! # Notes
+//!
+//! The driver thread runs on the application core. As threads on the same core aren't preempted,
+//! expired timers are fired the next time the running thread waits (e.g. for the screen's V-blank).
+//!
+//! # Example
+//!
+//! ```
+//! # let _runner = test_runner::GdbRunner::default();
+//! use ctru::timer;
+//! use std::time::Duration;
+//!
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! #
+//! let mut interval = timer::interval(Duration::from_millis(5))?;
+//! let timeout = timer::after(Duration::from_millis(20))?;
+//!
+//! let mut ticks = 0;
+//! while !timeout.is_elapsed() {
+//!     ticks += interval.wait();
+//! }
+//!
+//! assert!(ticks >= 3);
+//! #
+//! # Ok(())
+//! # }
+//! ```
+#![doc(alias = "svcCreateTimer")]
+
+use crate::error::ResultCode;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Amount of slots of the wheel, each covering one tick.
+const WHEEL_SLOTS: u64 = 256;
+
+/// Resolution of the timers.
+const TICK: Duration = Duration::from_millis(1);
+
+/// State of a logical timer, shared between its handle and the wheel.
+#[derive(Default)]
+struct TimerShared {
+    state: Mutex<TimerState>,
+    fired: Condvar,
+    cancelled: AtomicBool,
+}
+
+#[derive(Default)]
+struct TimerState {
+    /// Amount of times the timer expired since it was created.
+    expirations: u64,
+    waker: Option<Waker>,
+}
+
+impl TimerShared {
+    fn fire(&self, expirations: u64) {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            state.expirations += expirations;
+            state.waker.take()
+        };
+
+        self.fired.notify_all();
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn expirations(&self) -> u64 {
+        self.state.lock().unwrap().expirations
+    }
+
+    /// Block until the timer expired more than `seen` times, returning its amount of expirations.
+    fn wait_past(&self, seen: u64) -> u64 {
+        let state = self.state.lock().unwrap();
+        let state = self
+            .fired
+            .wait_while(state, |state| state.expirations <= seen)
+            .unwrap();
+
+        state.expirations
+    }
+
+    /// Poll whether the timer expired more than `seen` times, returning its amount of expirations.
+    fn poll_past(&self, seen: u64, cx: &mut Context<'_>) -> Poll<u64> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.expirations > seen {
+            Poll::Ready(state.expirations)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+struct Entry {
+    /// Tick at which the timer expires.
+    deadline: u64,
+    /// Period of repeating timers, in ticks.
+    period: Option<u64>,
+    shared: Arc<TimerShared>,
+}
+
+/// Hashed timing wheel: each entry is stored in the slot of its deadline, modulo the amount of slots.
+struct Wheel {
+    slots: Vec<Vec<Entry>>,
+    /// Last tick whose slot was processed.
+    processed: u64,
+}
+
+impl Wheel {
+    fn new() -> Self {
+        Self {
+            slots: (0..WHEEL_SLOTS).map(|_| Vec::new()).collect(),
+            processed: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.slots.iter().all(Vec::is_empty)
+    }
+
+    fn insert(&mut self, mut entry: Entry) {
+        // Deadlines which are already due are fired with the next processed slot.
+        entry.deadline = entry.deadline.max(self.processed + 1);
+        self.slots[(entry.deadline % WHEEL_SLOTS) as usize].push(entry);
+    }
+
+    /// Process all slots up to `now`, returning the expired timers along with their amount of expirations.
+    fn advance(&mut self, now: u64) -> Vec<(Arc<TimerShared>, u64)> {
+        let mut expired = Vec::new();
+        let mut rescheduled = Vec::new();
+
+        // After a full revolution, every slot has been looked at.
+        let first = self.processed + 1;
+        let last = now.min(self.processed + WHEEL_SLOTS);
+
+        for tick in first..=last {
+            let slot = &mut self.slots[(tick % WHEEL_SLOTS) as usize];
+            let mut index = 0;
+
+            while index < slot.len() {
+                let entry = &slot[index];
+
+                if entry.shared.cancelled.load(Ordering::Acquire) {
+                    slot.swap_remove(index);
+                } else if entry.deadline <= now {
+                    let mut entry = slot.swap_remove(index);
+
+                    let expirations = match entry.period {
+                        // Count the periods missed while the driver couldn't run.
+                        Some(period) => {
+                            let expirations = (now - entry.deadline) / period + 1;
+                            entry.deadline += expirations * period;
+                            expired.push((Arc::clone(&entry.shared), expirations));
+                            rescheduled.push(entry);
+                            continue;
+                        }
+                        None => 1,
+                    };
+
+                    expired.push((entry.shared, expirations));
+                } else {
+                    index += 1;
+                }
+            }
+        }
+
+        self.processed = now.max(self.processed);
+
+        for entry in rescheduled {
+            self.insert(entry);
+        }
+
+        expired
+    }
+
+    /// Returns the earliest deadline of the wheel, if it isn't empty.
+    fn next_deadline(&self) -> Option<u64> {
+        // The entries of the next revolution are stored in order: the first occupied slot holds the earliest deadline,
+        // unless it only contains entries of later revolutions.
+        for tick in self.processed + 1..=self.processed + WHEEL_SLOTS {
+            let slot = &self.slots[(tick % WHEEL_SLOTS) as usize];
+
+            if slot.iter().any(|entry| entry.deadline == tick) {
+                return Some(tick);
+            }
+        }
+
+        self.slots
+            .iter()
+            .flatten()
+            .map(|entry| entry.deadline)
+            .min()
+    }
+}
+
+/// Kernel timer and wheel shared with the driver thread.
+struct Driver {
+    wheel: Mutex<Wheel>,
+    timer: ctru_sys::Handle,
+    start: Instant,
+}
+
+static DRIVER: OnceLock<&'static Driver> = OnceLock::new();
+
+/// Serializes the creation of the driver, so that failed attempts can be retried by later timers.
+static DRIVER_INIT: Mutex<()> = Mutex::new(());
+
+impl Driver {
+    fn get() -> crate::Result<&'static Self> {
+        if let Some(driver) = DRIVER.get() {
+            return Ok(driver);
+        }
+
+        let _guard = DRIVER_INIT.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(driver) = DRIVER.get() {
+            return Ok(driver);
+        }
+
+        let mut timer = 0;
+        ResultCode(unsafe { ctru_sys::svcCreateTimer(&mut timer, ctru_sys::RESET_ONESHOT) })?;
+
+        // The driver thread runs forever, so the driver is never freed once the thread is started.
+        let driver: &'static Self = Box::leak(Box::new(Self {
+            wheel: Mutex::new(Wheel::new()),
+            timer,
+            start: Instant::now(),
+        }));
+
+        let spawned = std::thread::Builder::new()
+            .name(String::from("timer"))
+            .stack_size(0x4000)
+            .spawn(move || driver.run());
+
+        if let Err(err) = spawned {
+            unsafe {
+                let _ = ctru_sys::svcCloseHandle(timer);
+                // Safety: the closure holding the only other reference was dropped without running.
+                drop(Box::from_raw(driver as *const Self as *mut Self));
+            }
+
+            return Err(err.into());
+        }
+
+        Ok(DRIVER.get_or_init(|| driver))
+    }
+
+    fn now(&self) -> u64 {
+        (self.start.elapsed().as_nanos() / TICK.as_nanos()) as u64
+    }
+
+    fn insert(&self, delay: Duration, period: Option<Duration>) -> Arc<TimerShared> {
+        let shared = Arc::new(TimerShared::default());
+
+        let mut wheel = self.wheel.lock().unwrap();
+        let now = self.now();
+
+        wheel.insert(Entry {
+            deadline: now + ticks(delay),
+            period: period.map(ticks),
+            shared: Arc::clone(&shared),
+        });
+        self.arm(&wheel, now);
+
+        shared
+    }
+
+    /// Arm the kernel timer for the next deadline of `wheel`.
+    #[doc(alias = "svcSetTimer")]
+    fn arm(&self, wheel: &Wheel, now: u64) {
+        match wheel.next_deadline() {
+            Some(deadline) => {
+                let delay = deadline
+                    .saturating_sub(now)
+                    .saturating_mul(TICK.as_nanos() as u64);
+                let delay = i64::try_from(delay).unwrap_or(i64::MAX);
+
+                let _ = unsafe { ctru_sys::svcSetTimer(self.timer, delay, 0) };
+            }
+            None => {
+                let _ = unsafe { ctru_sys::svcCancelTimer(self.timer) };
+            }
+        }
+    }
+
+    fn run(&self) {
+        loop {
+            let _ = unsafe { ctru_sys::svcWaitSynchronization(self.timer, -1) };
+
+            let expired = {
+                let mut wheel = self.wheel.lock().unwrap();
+                let now = self.now();
+
+                let expired = wheel.advance(now);
+                self.arm(&wheel, now);
+
+                expired
+            };
+
+            for (shared, expirations) in expired {
+                shared.fire(expirations);
+            }
+        }
+    }
+}
+
+/// Convert `duration` to ticks, rounding up so that timers never expire early.
+fn ticks(duration: Duration) -> u64 {
+    let tick = TICK.as_nanos();
+    ((duration.as_nanos() + tick - 1) / tick) as u64
+}
+
+/// One-shot timer created by [`after()`].
+///
+/// Dropping the timer cancels it.
+pub struct Timer {
+    shared: Arc<TimerShared>,
+}
+
+/// Create a timer expiring once `duration` has elapsed.
+///
+/// # Errors
+///
+/// The first timer created by the application starts the driver: this function returns an error
+/// if the kernel timer or the driver thread couldn't be created. Later calls retry creating them.
+#[doc(alias = "svcCreateTimer")]
+pub fn after(duration: Duration) -> crate::Result<Timer> {
+    Ok(Timer {
+        shared: Driver::get()?.insert(duration, None),
+    })
+}
+
+impl Timer {
+    /// Returns `true` if the timer expired.
+    pub fn is_elapsed(&self) -> bool {
+        self.shared.expirations() > 0
+    }
+
+    /// Block the current thread until the timer expires.
+    pub fn wait(&self) {
+        self.shared.wait_past(0);
+    }
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.shared.poll_past(0, cx).map(|_| ())
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.shared.cancelled.store(true, Ordering::Release);
+    }
+}
+
+/// Repeating timer created by [`interval()`].
+///
+/// Dropping the interval cancels it.
+pub struct Interval {
+    shared: Arc<TimerShared>,
+    period: Duration,
+    seen: u64,
+}
+
+/// Create a timer expiring every `period`, starting one period from now.
+///
+/// # Errors
+///
+/// This function returns an error if the driver couldn't be started (see [`after()`]).
+///
+/// # Panics
+///
+/// This function panics if `period` is zero.
+pub fn interval(period: Duration) -> crate::Result<Interval> {
+    assert!(!period.is_zero(), "the period of an interval can't be zero");
+
+    Ok(Interval {
+        shared: Driver::get()?.insert(period, Some(period)),
+        period,
+        seen: 0,
+    })
+}
+
+impl Interval {
+    /// Returns the period of the interval.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Block the current thread until the next expiration.
+    ///
+    /// Returns the amount of periods elapsed since the last call, which is more than 1 if the caller fell behind.
+    /// If the interval already expired since the last call, this function returns immediately.
+    pub fn wait(&mut self) -> u64 {
+        let expirations = self.shared.wait_past(self.seen);
+        let elapsed = expirations - self.seen;
+        self.seen = expirations;
+
+        elapsed
+    }
+
+    /// Wait for the next expiration from async code.
+    ///
+    /// The future resolves to the amount of periods elapsed since the last expiration was seen, like [`Interval::wait()`].
+    pub fn tick(&mut self) -> Tick<'_> {
+        Tick { interval: self }
+    }
+}
+
+impl Drop for Interval {
+    fn drop(&mut self) {
+        self.shared.cancelled.store(true, Ordering::Release);
+    }
+}
+
+/// Future returned by [`Interval::tick()`].
+pub struct Tick<'interval> {
+    interval: &'interval mut Interval,
+}
+
+impl Future for Tick<'_> {
+    type Output = u64;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u64> {
+        let seen = self.interval.seen;
+
+        self.interval.shared.poll_past(seen, cx).map(|expirations| {
+            self.interval.seen = expirations;
+            expirations - seen
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(deadline: u64, period: Option<u64>) -> (Entry, Arc<TimerShared>) {
+        let shared = Arc::new(TimerShared::default());
+        let entry = Entry {
+            deadline,
+            period,
+            shared: Arc::clone(&shared),
+        };
+
+        (entry, shared)
+    }
+
+    #[test]
+    fn wheel_expirations() {
+        let mut wheel = Wheel::new();
+        assert_eq!(wheel.next_deadline(), None);
+
+        let (late, _late) = entry(WHEEL_SLOTS + 10, None);
+        let (soon, _soon) = entry(5, None);
+        let (periodic, periodic_shared) = entry(3, Some(3));
+        let (cancelled, cancelled_shared) = entry(4, None);
+        cancelled_shared.cancelled.store(true, Ordering::Release);
+
+        wheel.insert(late);
+        wheel.insert(soon);
+        wheel.insert(periodic);
+        wheel.insert(cancelled);
+        assert_eq!(wheel.next_deadline(), Some(3));
+
+        let expired = wheel.advance(3);
+        assert_eq!(expired.len(), 1);
+        assert!(Arc::ptr_eq(&expired[0].0, &periodic_shared));
+        assert_eq!(wheel.next_deadline(), Some(4));
+
+        // The periodic timer missed two periods (6 and 9), and the cancelled one is dropped.
+        let expired = wheel.advance(10);
+        let counts: Vec<u64> = expired.iter().map(|(_, count)| *count).collect();
+        assert_eq!(counts.iter().sum::<u64>(), 1 + 2);
+        assert_eq!(wheel.next_deadline(), Some(12));
+
+        periodic_shared.cancelled.store(true, Ordering::Release);
+        wheel.advance(WHEEL_SLOTS + 9);
+        assert_eq!(wheel.next_deadline(), Some(WHEEL_SLOTS + 10));
+
+        assert_eq!(wheel.advance(WHEEL_SLOTS + 10).len(), 1);
+        assert!(wheel.is_empty());
+    }
+}
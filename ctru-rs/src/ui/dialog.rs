@@ -0,0 +1,150 @@
+//! Message and confirmation dialogs.
+
+use super::{wrap, Action, Area, Canvas, Focus, Style};
+
+/// Result of an [`Action`] handled by a [`Dialog`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DialogResult {
+    /// A message dialog was closed.
+    Dismissed,
+    /// The confirm button of a confirmation dialog was chosen.
+    Confirmed,
+    /// The decline button of a confirmation dialog was chosen, or the dialog was left with [`Action::Cancel`].
+    Declined,
+}
+
+/// Modal box with a title, a message and one (message) or two (confirmation) buttons.
+#[derive(Clone, Debug)]
+pub struct Dialog {
+    title: String,
+    message: String,
+    buttons: Vec<String>,
+    focus: Focus,
+}
+
+impl Dialog {
+    /// Create a dialog showing a message, closed with an "OK" button.
+    pub fn message(title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::with_buttons(title.into(), message.into(), vec![String::from("OK")])
+    }
+
+    /// Create a dialog asking for confirmation, with "Yes" and "No" buttons.
+    pub fn confirm(title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::with_buttons(
+            title.into(),
+            message.into(),
+            vec![String::from("Yes"), String::from("No")],
+        )
+    }
+
+    fn with_buttons(title: String, message: String, buttons: Vec<String>) -> Self {
+        let mut focus = Focus::new(buttons.len());
+        focus.set_wrap(false);
+
+        Self {
+            title,
+            message,
+            buttons,
+            focus,
+        }
+    }
+
+    /// Change the labels of the buttons of a confirmation dialog.
+    ///
+    /// Message dialogs only use the `confirm` label.
+    pub fn set_labels(&mut self, confirm: impl Into<String>, decline: impl Into<String>) {
+        self.buttons[0] = confirm.into();
+
+        if let Some(button) = self.buttons.get_mut(1) {
+            *button = decline.into();
+        }
+    }
+
+    /// Returns `true` if the dialog asks for a confirmation.
+    pub fn is_confirmation(&self) -> bool {
+        self.buttons.len() == 2
+    }
+
+    /// Move the focus between the buttons, or report the button chosen.
+    pub fn handle(&mut self, action: Action) -> Option<DialogResult> {
+        match action {
+            Action::Left | Action::Right => {
+                self.focus.handle(action);
+                None
+            }
+            Action::Accept if !self.is_confirmation() => Some(DialogResult::Dismissed),
+            Action::Accept if self.focus.focused() == 0 => Some(DialogResult::Confirmed),
+            Action::Accept => Some(DialogResult::Declined),
+            Action::Cancel if !self.is_confirmation() => Some(DialogResult::Dismissed),
+            Action::Cancel => Some(DialogResult::Declined),
+            _ => None,
+        }
+    }
+
+    /// Draw the dialog centered within `area`, sized to fit its contents.
+    pub fn draw(&self, canvas: &mut impl Canvas, area: Area) {
+        if area.width < 4 || area.height < 5 {
+            return;
+        }
+
+        let lines = wrap(&self.message, usize::from(area.width - 4));
+        let buttons: Vec<String> = self
+            .buttons
+            .iter()
+            .map(|label| format!("[ {label} ]"))
+            .collect();
+        let buttons_width = buttons.iter().map(|b| b.chars().count() + 2).sum::<usize>() - 2;
+
+        let content_width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .chain([self.title.chars().count(), buttons_width])
+            .max()
+            .unwrap_or(0);
+
+        // Border, title, blank line, message, blank line, buttons, border.
+        let frame = area.centered(content_width as u16 + 4, lines.len() as u16 + 5);
+        let right = frame.column + frame.width - 1;
+        let bottom = frame.row + frame.height - 1;
+
+        canvas.fill(frame, Style::Normal);
+
+        canvas.fill(
+            Area::new(frame.column, frame.row, frame.width, 1),
+            Style::Highlighted,
+        );
+        canvas.text(frame.column + 2, frame.row, &self.title, Style::Highlighted);
+
+        for row in frame.row + 1..bottom {
+            canvas.text(frame.column, row, "|", Style::Normal);
+            canvas.text(right, row, "|", Style::Normal);
+        }
+        let border = format!("+{}+", "-".repeat(usize::from(frame.width - 2)));
+        canvas.text(frame.column, bottom, &border, Style::Normal);
+
+        for (index, line) in lines
+            .iter()
+            .enumerate()
+            .take(usize::from(frame.height.saturating_sub(5)))
+        {
+            canvas.text(
+                frame.column + 2,
+                frame.row + 2 + index as u16,
+                line,
+                Style::Normal,
+            );
+        }
+
+        let mut column = frame.column + frame.width.saturating_sub(buttons_width as u16) / 2;
+        for (index, button) in buttons.iter().enumerate() {
+            let style = if self.focus.is_focused(index) {
+                Style::Highlighted
+            } else {
+                Style::Normal
+            };
+
+            canvas.text(column, bottom - 1, button, style);
+            column += button.chars().count() as u16 + 2;
+        }
+    }
+}
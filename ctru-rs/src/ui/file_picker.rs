@@ -0,0 +1,447 @@
+//! File picker.
+//!
+//! [`FilePicker`] browses a directory tree (on the SD card, or in any archive mounted with
+//! [`MountedArchive`](crate::services::fs::MountedArchive), such as extdata) and returns the path of the chosen file.
+//! Directories are read a batch of entries at a time, so that browsing huge directories doesn't freeze the application.
+
+use super::{truncate, Action, Area, Canvas, ListEvent, ListView, Navigator, Style};
+use crate::console::Console;
+use crate::services::apt::Apt;
+use crate::services::gfx::Gfx;
+use crate::services::hid::Hid;
+
+use std::fmt;
+use std::fs::ReadDir;
+use std::path::{Path, PathBuf};
+
+/// Result of an [`Action`] handled by a [`FilePicker`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilePickerEvent {
+    /// The file at this path was chosen.
+    Chosen(PathBuf),
+    /// The picker was left with [`Action::Cancel`] from its root directory.
+    Cancelled,
+}
+
+/// Entry of the directory being browsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    is_dir: bool,
+    /// Lowercase name, computed once for sorting.
+    sort_name: String,
+}
+
+impl Entry {
+    fn new(name: String, is_dir: bool) -> Self {
+        Self {
+            sort_name: name.to_lowercase(),
+            name,
+            is_dir,
+        }
+    }
+
+    /// Entry leading to the parent directory.
+    fn parent() -> Self {
+        Self::new(String::from(".."), true)
+    }
+
+    fn is_parent(&self) -> bool {
+        self.is_dir && self.name == ".."
+    }
+
+    /// Sort key: the parent entry first, then directories, then files, each sorted by name regardless of case.
+    fn sort_key(&self) -> (bool, bool, &str) {
+        (!self.is_parent(), !self.is_dir, &self.sort_name)
+    }
+}
+
+/// Merge `batch` into the sorted `items`, keeping them sorted without sorting them again.
+fn merge_sorted(items: &mut Vec<Entry>, mut batch: Vec<Entry>) {
+    batch.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+
+    let existing = std::mem::replace(items, Vec::with_capacity(items.len() + batch.len()));
+    let mut batch = batch.into_iter().peekable();
+
+    for entry in existing {
+        while let Some(next) = batch.next_if(|next| next.sort_key() < entry.sort_key()) {
+            items.push(next);
+        }
+        items.push(entry);
+    }
+
+    items.extend(batch);
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_dir && !self.is_parent() {
+            write!(f, "{}/", self.name)
+        } else {
+            write!(f, "{}", self.name)
+        }
+    }
+}
+
+/// Directory browser returning the path of a file.
+///
+/// Call [`FilePicker::update()`] every frame to keep reading the current directory, and draw the picker every frame
+/// (or whenever [`FilePicker::update()`] or [`FilePicker::handle()`] made changes) with [`FilePicker::draw()`].
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::prelude::*;
+/// use ctru::ui::FilePicker;
+///
+/// let apt = Apt::new()?;
+/// let mut hid = Hid::new()?;
+/// let gfx = Gfx::new()?;
+///
+/// let mut picker = FilePicker::new("sdmc:/3ds");
+/// picker.set_extensions(&["3dsx", "smdh"]);
+///
+/// if let Some(path) = picker.pick(&apt, &mut hid, &gfx) {
+///     println!("Chosen: {}", path.display());
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct FilePicker {
+    root: PathBuf,
+    current: PathBuf,
+    extensions: Vec<String>,
+    batch_size: usize,
+    list: ListView<Entry>,
+    /// Directory iterator of the current directory, until it's read completely.
+    pending: Option<ReadDir>,
+    /// Name of the directory to select once it's read, after going back to its parent.
+    reselect: Option<String>,
+    error: Option<String>,
+}
+
+impl FilePicker {
+    /// Create a picker browsing `root` and its subdirectories.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+
+        let mut picker = Self {
+            current: root.clone(),
+            root,
+            extensions: Vec::new(),
+            batch_size: 64,
+            list: ListView::new(Vec::new()),
+            pending: None,
+            reselect: None,
+            error: None,
+        };
+        picker.open(picker.current.clone());
+
+        picker
+    }
+
+    /// Only show the files with one of the given extensions (compared regardless of case), or all files if `extensions` is empty.
+    ///
+    /// The current directory is read again.
+    pub fn set_extensions(&mut self, extensions: &[&str]) {
+        self.extensions = extensions
+            .iter()
+            .map(|extension| extension.trim_start_matches('.').to_lowercase())
+            .collect();
+
+        self.open(self.current.clone());
+    }
+
+    /// Set the maximum amount of directory entries read by each call to [`FilePicker::update()`].
+    pub fn set_batch_size(&mut self, entries: usize) {
+        self.batch_size = entries.max(1);
+    }
+
+    /// Returns the directory being browsed.
+    pub fn current_dir(&self) -> &Path {
+        &self.current
+    }
+
+    /// Returns `true` if the current directory wasn't completely read yet.
+    pub fn is_loading(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Returns the error which occurred while reading the current directory, if any.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Read the next batch of entries of the current directory.
+    ///
+    /// Returns `true` if new entries were added.
+    pub fn update(&mut self) -> bool {
+        let Some(mut pending) = self.pending.take() else {
+            return false;
+        };
+
+        let mut batch = Vec::new();
+        let mut read = 0;
+
+        for entry in pending.by_ref().take(self.batch_size) {
+            read += 1;
+
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.error = Some(e.to_string());
+                    continue;
+                }
+            };
+
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if is_dir || self.matches(&name) {
+                batch.push(Entry::new(name, is_dir));
+            }
+        }
+
+        // The iterator ran out before filling the batch: the whole directory was read.
+        if read == self.batch_size {
+            self.pending = Some(pending);
+        }
+
+        if batch.is_empty() {
+            if self.pending.is_none() {
+                self.reselect = None;
+            }
+            return false;
+        }
+
+        // Keep the selection on the same entry while the list grows, unless the directory we came from just showed up.
+        let selected = self.list.selected_item().cloned();
+
+        self.list.update_items(|items| merge_sorted(items, batch));
+
+        let reselected = self.reselect.as_ref().and_then(|name| {
+            self.list
+                .items()
+                .iter()
+                .position(|e| e.is_dir && e.name == *name)
+        });
+
+        if let Some(index) = reselected {
+            self.list.select(index);
+            self.reselect = None;
+        } else if let Some(selected) = selected {
+            if let Some(index) = self.list.items().iter().position(|e| *e == selected) {
+                self.list.select(index);
+            }
+        }
+
+        if self.pending.is_none() {
+            self.reselect = None;
+        }
+
+        true
+    }
+
+    /// Move the selection, enter the selected directory, or choose the selected file.
+    ///
+    /// [`Action::Cancel`] goes back to the parent directory, or leaves the picker from the root directory.
+    pub fn handle(&mut self, action: Action) -> Option<FilePickerEvent> {
+        // The user moved on: the selection mustn't jump back to the directory we came from.
+        self.reselect = None;
+
+        match self.list.handle(action)? {
+            ListEvent::Cancelled => {
+                if self.current == self.root {
+                    return Some(FilePickerEvent::Cancelled);
+                }
+
+                self.go_up();
+            }
+            ListEvent::Activated(index) => {
+                let entry = self.list.items()[index].clone();
+
+                if entry.is_parent() {
+                    self.go_up();
+                } else if entry.is_dir {
+                    self.open(self.current.join(&entry.name));
+                } else {
+                    return Some(FilePickerEvent::Chosen(self.current.join(&entry.name)));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Draw the picker in `area`: the current directory on the first row, the entries below it and a status row at the bottom.
+    pub fn draw(&self, canvas: &mut impl Canvas, area: Area) {
+        if area.height < 3 || area.width == 0 {
+            return;
+        }
+
+        let title = self.current.to_string_lossy();
+        canvas.fill(
+            Area::new(area.column, area.row, area.width, 1),
+            Style::Highlighted,
+        );
+        canvas.text(
+            area.column,
+            area.row,
+            truncate(&title, area.width.into()),
+            Style::Highlighted,
+        );
+
+        self.list.draw(
+            canvas,
+            Area::new(area.column, area.row + 1, area.width, area.height - 2),
+        );
+
+        let status = if let Some(error) = &self.error {
+            error.clone()
+        } else if self.is_loading() {
+            format!("Loading... ({} entries)", self.list.items().len())
+        } else if self.list.items().iter().all(Entry::is_parent) {
+            String::from("No files")
+        } else {
+            String::new()
+        };
+
+        let bottom = area.row + area.height - 1;
+        canvas.fill(Area::new(area.column, bottom, area.width, 1), Style::Normal);
+        canvas.text(
+            area.column,
+            bottom,
+            truncate(&status, area.width.into()),
+            Style::Dimmed,
+        );
+    }
+
+    /// Show the picker on the top screen until a file is chosen (returning its path) or the picker is cancelled.
+    ///
+    /// A [`Console`] takes over the top screen while the picker is shown, so the screen mustn't be borrowed already.
+    /// Returns [`None`] if the picker is cancelled, or if the application is asked to close.
+    pub fn pick(&mut self, apt: &Apt, hid: &mut Hid, gfx: &Gfx) -> Option<PathBuf> {
+        let console = Console::new(gfx.top_screen.borrow_mut());
+        let (columns, rows) = console.window_size();
+        let area = Area::new(0, 0, columns.into(), rows.into());
+
+        let mut navigator = Navigator::new();
+        let mut dirty = true;
+
+        while apt.main_loop() {
+            hid.scan_input();
+
+            if let Some(action) = navigator.update(hid) {
+                dirty = true;
+
+                match self.handle(action) {
+                    Some(FilePickerEvent::Chosen(path)) => return Some(path),
+                    Some(FilePickerEvent::Cancelled) => return None,
+                    None => {}
+                }
+            }
+
+            dirty |= self.update();
+
+            if dirty {
+                console.clear();
+                self.draw(&mut super::ConsoleCanvas::new(&console), area);
+                dirty = false;
+            }
+
+            gfx.wait_for_vblank();
+        }
+
+        None
+    }
+
+    /// Start browsing `path`.
+    fn open(&mut self, path: PathBuf) {
+        self.error = None;
+        self.pending = match std::fs::read_dir(&path) {
+            Ok(entries) => Some(entries),
+            Err(e) => {
+                self.error = Some(e.to_string());
+                None
+            }
+        };
+
+        let items = if path == self.root {
+            Vec::new()
+        } else {
+            vec![Entry::parent()]
+        };
+        self.list.set_items(items);
+        self.list.select(0);
+
+        self.current = path;
+        self.reselect = None;
+    }
+
+    fn go_up(&mut self) {
+        let Some(parent) = self.current.parent().map(Path::to_path_buf) else {
+            return;
+        };
+
+        let previous = self
+            .current
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        self.open(parent);
+
+        // The parent directory is read again in batches, like any other: the directory we came from is selected once it's read.
+        self.reselect = previous;
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.extensions.is_empty()
+            || Path::new(name)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .is_some_and(|extension| self.extensions.contains(&extension))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filtering_and_sorting() {
+        let mut picker = FilePicker::new("sdmc:/missing");
+        picker.set_extensions(&[".3DSX", "smdh"]);
+        assert!(!picker.is_loading());
+        assert!(picker.error().is_some());
+
+        assert!(picker.matches("game.3dsx"));
+        assert!(picker.matches("ICON.SMDH"));
+        assert!(!picker.matches("readme.txt"));
+        assert!(!picker.matches("3dsx"));
+
+        // Batches arriving in any order are merged in sorted order.
+        let entry = |name: &str, is_dir| Entry::new(String::from(name), is_dir);
+        let mut entries = vec![Entry::parent()];
+        merge_sorted(
+            &mut entries,
+            vec![entry("b.3dsx", false), entry("Zelda", true)],
+        );
+        merge_sorted(
+            &mut entries,
+            vec![
+                entry("c.smdh", false),
+                entry("A.3dsx", false),
+                entry("apps", true),
+            ],
+        );
+
+        let names: Vec<String> = entries.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            names,
+            ["..", "apps/", "Zelda/", "A.3dsx", "b.3dsx", "c.smdh"]
+        );
+    }
+}
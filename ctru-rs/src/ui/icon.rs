@@ -0,0 +1,94 @@
+//! Title icons.
+
+use super::{truncate, Theme, CELL_SIZE};
+use crate::render2d::{Color, Rect, SoftwareRenderer, Texture};
+use crate::services::cfgu::Language;
+use crate::smdh::{Smdh, LARGE_ICON_SIZE};
+
+/// Width and height (in pixels) of the area covered by a [`TitleIcon`], label excluded.
+pub const ICON_TILE_SIZE: u16 = LARGE_ICON_SIZE as u16 + 2 * ICON_PADDING;
+
+/// Space (in pixels) between the icon and the edges of its tile, where the highlight frame is drawn.
+const ICON_PADDING: u16 = 4;
+
+/// Icon of a title with its name below it, as shown by launchers.
+///
+/// Unlike the other widgets, icons are placed in pixels rather than cells, and can only be drawn with a [`SoftwareRenderer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TitleIcon {
+    texture: Texture,
+    label: String,
+}
+
+impl TitleIcon {
+    /// Create an icon drawing `texture` with `label` below it.
+    pub fn new(texture: Texture, label: impl Into<String>) -> Self {
+        Self {
+            texture,
+            label: label.into(),
+        }
+    }
+
+    /// Create an icon from the large icon and the short description of a title's [`Smdh`], in the given language.
+    pub fn from_smdh(smdh: &Smdh, language: Language) -> crate::Result<Self> {
+        let texture = Texture::from_image(&smdh.large_icon_image())?;
+
+        Ok(Self::new(texture, smdh.names(language).short_description))
+    }
+
+    /// Returns the texture of the icon.
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Returns the label drawn below the icon.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Draw the icon in a tile of [`ICON_TILE_SIZE`] pixels with its top-left corner at (`x`, `y`), followed by its label
+    /// centered on the row of cells below it.
+    ///
+    /// Highlighted icons are framed and have their label drawn with the theme's highlight colours.
+    pub fn draw(
+        &self,
+        renderer: &mut SoftwareRenderer,
+        x: i32,
+        y: i32,
+        highlighted: bool,
+        theme: &Theme,
+    ) {
+        let tile = u32::from(ICON_TILE_SIZE);
+        let padding = i32::from(ICON_PADDING);
+
+        let (background, foreground) = if highlighted {
+            (theme.highlight_background, theme.highlight_foreground)
+        } else {
+            (theme.background, theme.foreground)
+        };
+
+        renderer.fill_rect(Rect::new(x, y, tile, tile), background);
+        renderer.draw_sprite(
+            &self.texture,
+            self.texture.bounds(),
+            Rect::new(
+                x + padding,
+                y + padding,
+                tile - 2 * u32::from(ICON_PADDING),
+                tile - 2 * u32::from(ICON_PADDING),
+            ),
+            Color::WHITE,
+        );
+
+        let label = truncate(&self.label, usize::from(ICON_TILE_SIZE / CELL_SIZE));
+        let width = label.chars().count() as i32 * i32::from(CELL_SIZE);
+        let label_x = x + (tile as i32 - width) / 2;
+        let label_y = y + tile as i32;
+
+        renderer.fill_rect(
+            Rect::new(label_x, label_y, width as u32, u32::from(CELL_SIZE)),
+            background,
+        );
+        renderer.draw_text(label_x, label_y, label, foreground, 1);
+    }
+}
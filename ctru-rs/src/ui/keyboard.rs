@@ -0,0 +1,463 @@
+//! On-screen keyboard.
+//!
+//! [`VirtualKeyboard`] is a touch (and gamepad) keyboard drawn by the application itself, for environments where the
+//! [Software Keyboard applet](crate::applets::swkbd) can't be launched (e.g. some homebrew launchers or emulator configurations).
+//! It returns the same [`KeyboardResult`] as the applet, so either can stand in for the other.
+
+use super::{truncate, Action, Area, Canvas, Navigator, Style, CELL_SIZE};
+use crate::applets::swkbd::{Button, Error, KeyboardResult};
+use crate::console::Console;
+use crate::services::apt::Apt;
+use crate::services::gfx::Gfx;
+use crate::services::hid::{Hid, KeyPad};
+
+use std::cell::Cell;
+
+/// Width (in cells) of a key unit.
+const KEY_WIDTH: u16 = 4;
+
+/// Height (in cells) of a row of keys.
+const KEY_HEIGHT: u16 = 3;
+
+/// Amount of key units in each row.
+const ROW_UNITS: u16 = 10;
+
+/// Amount of rows of keys.
+const ROWS: usize = 5;
+
+/// Rows above the keys: hint, text field and a blank row.
+const HEADER_ROWS: u16 = 3;
+
+/// Smallest area (in cells) the keyboard can be drawn in.
+pub const MIN_SIZE: (u16, u16) = (
+    ROW_UNITS * KEY_WIDTH,
+    HEADER_ROWS + ROWS as u16 * KEY_HEIGHT,
+);
+
+const LOWER: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl'", "zxcvbnm,.-"];
+const UPPER: [&str; 4] = ["1234567890", "QWERTYUIOP", "ASDFGHJKL\"", "ZXCVBNM;:_"];
+const SYMBOLS: [&str; 4] = ["1234567890", "!@#$%^&*()", "+=/\\<>[]{}", "?~`|;:_\"',"];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Page {
+    Lower,
+    /// Upper case letters. The flag is set when the page only applies to the next character.
+    Upper {
+        once: bool,
+    },
+    Symbols,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Shift,
+    Symbols,
+    Space,
+    Backspace,
+    Ok,
+}
+
+/// Keyboard widget for the bottom screen, operated by touch or with the D-Pad and A.
+///
+/// The B button cancels the input ([`Button::Left`]), and the on-screen OK key confirms it ([`Button::Right`]).
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// #
+/// use ctru::applets::swkbd::{Button, SoftwareKeyboard};
+/// use ctru::prelude::*;
+/// use ctru::ui::keyboard::VirtualKeyboard;
+///
+/// let apt = Apt::new()?;
+/// let mut hid = Hid::new()?;
+/// let gfx = Gfx::new()?;
+///
+/// // Fall back to the on-screen keyboard if the applet can't be used.
+/// let (text, button) = match SoftwareKeyboard::default().get_string(64, &apt, &gfx) {
+///     Ok(result) => result,
+///     Err(_) => VirtualKeyboard::new().get_string(&apt, &mut hid, &gfx)?,
+/// };
+///
+/// if button == Button::Right {
+///     println!("Hello, {text}!");
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct VirtualKeyboard {
+    text: String,
+    hint: String,
+    max_len: usize,
+    page: Page,
+    /// Focused row and key index, for gamepad input.
+    focus: (usize, usize),
+    /// Area of the last draw, used to map touches to keys.
+    area: Cell<Area>,
+}
+
+impl VirtualKeyboard {
+    /// Create an empty keyboard, accepting up to 256 characters.
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            hint: String::new(),
+            max_len: 256,
+            page: Page::Upper { once: true },
+            focus: (1, 0),
+            area: Cell::new(Area::new(0, 0, MIN_SIZE.0, MIN_SIZE.1)),
+        }
+    }
+
+    /// Set the text the keyboard starts with, truncated to the maximum length.
+    pub fn set_initial_text(&mut self, text: &str) {
+        self.text = String::from(truncate(text, self.max_len));
+
+        if !self.text.is_empty() {
+            self.page = Page::Lower;
+        }
+    }
+
+    /// Set the text shown above the input field.
+    pub fn set_hint_text(&mut self, hint: impl Into<String>) {
+        self.hint = hint.into();
+    }
+
+    /// Set the maximum amount of characters of the input.
+    pub fn set_max_text_len(&mut self, len: usize) {
+        self.max_len = len;
+        self.text = String::from(truncate(&self.text, len));
+    }
+
+    /// Returns the text written so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Handle a gamepad action: move the focus between keys, press the focused key with [`Action::Accept`]
+    /// or cancel the input with [`Action::Cancel`].
+    ///
+    /// Returns the result of the input once it's over.
+    pub fn handle(&mut self, action: Action) -> Option<KeyboardResult> {
+        let rows = self.rows();
+        let (row, index) = self.focus;
+
+        match action {
+            Action::Left => {
+                self.focus.1 = (index + rows[row].len() - 1) % rows[row].len();
+            }
+            Action::Right => self.focus.1 = (index + 1) % rows[row].len(),
+            Action::Up | Action::Down => {
+                let target = if action == Action::Up {
+                    (row + ROWS - 1) % ROWS
+                } else {
+                    (row + 1) % ROWS
+                };
+
+                // Keep the focus on the key below (or above) the middle of the current one.
+                let (start, width) = key_span(&rows[row], index);
+                self.focus = (target, key_at(&rows[target], start + width / 2));
+            }
+            Action::Accept => return self.press(rows[row][index].0),
+            Action::Cancel => return Some(Ok((self.text.clone(), Button::Left))),
+            Action::PageUp | Action::PageDown => {}
+        }
+
+        None
+    }
+
+    /// Handle a touch at the given position (in pixels, as returned by [`Hid::touch_position()`]), pressing the key under it.
+    ///
+    /// Call this function only for new touches (i.e. when [`KeyPad::TOUCH`] is in [`Hid::keys_down()`]), using the area of the last draw.
+    /// Returns the result of the input once it's over.
+    pub fn handle_touch(&mut self, x: u16, y: u16) -> Option<KeyboardResult> {
+        let area = self.area.get();
+        let column = (x / CELL_SIZE).checked_sub(area.column)?;
+        let row = (y / CELL_SIZE).checked_sub(area.row + HEADER_ROWS)?;
+
+        let row = usize::from(row / KEY_HEIGHT);
+        let unit = column / KEY_WIDTH;
+
+        if row >= ROWS || unit >= ROW_UNITS {
+            return None;
+        }
+
+        let rows = self.rows();
+        let index = key_at(&rows[row], unit);
+        self.focus = (row, index);
+
+        self.press(rows[row][index].0)
+    }
+
+    /// Draw the keyboard in `area`, which should be at least [`MIN_SIZE`] big (e.g. the whole bottom screen).
+    ///
+    /// The keys are centered horizontally and aligned to the bottom of the area.
+    pub fn draw(&self, canvas: &mut impl Canvas, area: Area) {
+        if area.width < MIN_SIZE.0 || area.height < MIN_SIZE.1 {
+            return;
+        }
+
+        let area = Area::new(
+            area.column + (area.width - MIN_SIZE.0) / 2,
+            area.row + area.height - MIN_SIZE.1,
+            MIN_SIZE.0,
+            MIN_SIZE.1,
+        );
+        self.area.set(area);
+
+        canvas.fill(area, Style::Normal);
+        canvas.text(
+            area.column,
+            area.row,
+            truncate(&self.hint, area.width.into()),
+            Style::Dimmed,
+        );
+
+        // Show the end of the text if it doesn't fit, followed by the cursor.
+        let visible = usize::from(area.width - 1);
+        let length = self.text.chars().count();
+        let shown: String = self
+            .text
+            .chars()
+            .skip(length.saturating_sub(visible))
+            .collect();
+        canvas.fill(
+            Area::new(area.column, area.row + 1, area.width, 1),
+            Style::Highlighted,
+        );
+        canvas.text(
+            area.column,
+            area.row + 1,
+            &format!("{shown}_"),
+            Style::Highlighted,
+        );
+
+        for (row, keys) in self.rows().iter().enumerate() {
+            let top = area.row + HEADER_ROWS + row as u16 * KEY_HEIGHT;
+
+            for (index, (key, units)) in keys.iter().enumerate() {
+                let (start, _) = key_span(keys, index);
+                let column = area.column + start * KEY_WIDTH;
+                let width = units * KEY_WIDTH - 1;
+
+                let style = if self.focus == (row, index) {
+                    Style::Highlighted
+                } else {
+                    Style::Dimmed
+                };
+
+                let label = self.label(*key);
+                canvas.fill(Area::new(column, top, width, KEY_HEIGHT - 1), style);
+                canvas.text(
+                    column + width.saturating_sub(label.chars().count() as u16) / 2,
+                    top,
+                    &label,
+                    style,
+                );
+            }
+        }
+    }
+
+    /// Show the keyboard on the bottom screen until the input is over.
+    ///
+    /// A [`Console`] takes over the bottom screen while the keyboard is shown, so the screen mustn't be borrowed already.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`Error::HomePressed`] if the application is asked to close (i.e. [`Apt::main_loop()`] returns `false`)
+    /// before the input is over.
+    pub fn get_string(&mut self, apt: &Apt, hid: &mut Hid, gfx: &Gfx) -> KeyboardResult {
+        let console = Console::new(gfx.bottom_screen.borrow_mut());
+        let (columns, rows) = console.window_size();
+        let area = Area::new(0, 0, columns.into(), rows.into());
+
+        let mut navigator = Navigator::new();
+        let mut dirty = true;
+
+        while apt.main_loop() {
+            hid.scan_input();
+
+            let mut result = navigator.update(hid).map(|action| {
+                dirty = true;
+                self.handle(action)
+            });
+
+            if hid.keys_down().contains(KeyPad::TOUCH) {
+                let (x, y) = hid.touch_position();
+                dirty = true;
+                result = Some(self.handle_touch(x, y));
+            }
+
+            if let Some(Some(result)) = result {
+                console.clear();
+                return result;
+            }
+
+            if dirty {
+                console.clear();
+                self.draw(&mut super::ConsoleCanvas::new(&console), area);
+                dirty = false;
+            }
+
+            gfx.wait_for_vblank();
+        }
+
+        Err(Error::HomePressed)
+    }
+
+    fn press(&mut self, key: Key) -> Option<KeyboardResult> {
+        match key {
+            Key::Char(c) => {
+                self.insert(c);
+
+                if self.page == (Page::Upper { once: true }) {
+                    self.page = Page::Lower;
+                }
+            }
+            Key::Space => self.insert(' '),
+            Key::Backspace => {
+                self.text.pop();
+            }
+            Key::Shift => {
+                self.page = match self.page {
+                    Page::Lower | Page::Symbols => Page::Upper { once: true },
+                    // Pressing shift twice locks the upper case.
+                    Page::Upper { once: true } => Page::Upper { once: false },
+                    Page::Upper { once: false } => Page::Lower,
+                };
+            }
+            Key::Symbols => {
+                self.page = if self.page == Page::Symbols {
+                    Page::Lower
+                } else {
+                    Page::Symbols
+                };
+            }
+            Key::Ok => return Some(Ok((self.text.clone(), Button::Right))),
+        }
+
+        None
+    }
+
+    fn insert(&mut self, c: char) {
+        if self.text.chars().count() < self.max_len {
+            self.text.push(c);
+        }
+    }
+
+    fn label(&self, key: Key) -> String {
+        match key {
+            Key::Char(c) => String::from(c),
+            Key::Shift => String::from(match self.page {
+                Page::Upper { once: false } => "SHIFT",
+                _ => "Shift",
+            }),
+            Key::Symbols => String::from(if self.page == Page::Symbols {
+                "abc"
+            } else {
+                "#+="
+            }),
+            Key::Space => String::from("Space"),
+            Key::Backspace => String::from("Del"),
+            Key::Ok => String::from("OK"),
+        }
+    }
+
+    /// Returns the keys of each row along with their width, in key units.
+    fn rows(&self) -> [Vec<(Key, u16)>; ROWS] {
+        let page = match self.page {
+            Page::Lower => LOWER,
+            Page::Upper { .. } => UPPER,
+            Page::Symbols => SYMBOLS,
+        };
+
+        let characters = |row: &str| row.chars().map(|c| (Key::Char(c), 1)).collect();
+
+        [
+            characters(page[0]),
+            characters(page[1]),
+            characters(page[2]),
+            characters(page[3]),
+            vec![
+                (Key::Shift, 2),
+                (Key::Symbols, 2),
+                (Key::Space, 3),
+                (Key::Backspace, 1),
+                (Key::Ok, 2),
+            ],
+        ]
+    }
+}
+
+impl Default for VirtualKeyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the first unit and the width (in units) of a key.
+fn key_span(keys: &[(Key, u16)], index: usize) -> (u16, u16) {
+    let start = keys[..index].iter().map(|(_, units)| units).sum();
+
+    (start, keys[index].1)
+}
+
+/// Returns the index of the key covering the given unit.
+fn key_at(keys: &[(Key, u16)], unit: u16) -> usize {
+    let mut end = 0;
+
+    keys.iter()
+        .position(|(_, units)| {
+            end += units;
+            unit < end
+        })
+        .unwrap_or(keys.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typing() {
+        let mut keyboard = VirtualKeyboard::new();
+        keyboard.set_max_text_len(3);
+
+        // "Q" on the second row, which lowers the case for the next letter.
+        assert_eq!(keyboard.handle(Action::Accept), None);
+        // "w", touched in the middle of the second key of the second row.
+        assert_eq!(keyboard.handle_touch(5 * 8, 7 * 8), None);
+        assert_eq!(keyboard.text(), "Qw");
+
+        // Move to the bottom row, below the "w" key: that's "Shift".
+        keyboard.handle(Action::Down);
+        keyboard.handle(Action::Down);
+        keyboard.handle(Action::Down);
+        assert_eq!(keyboard.focus, (4, 0));
+
+        // "Space", then a fourth character which doesn't fit.
+        keyboard.handle(Action::Right);
+        keyboard.handle(Action::Right);
+        keyboard.handle(Action::Accept);
+        keyboard.handle(Action::Accept);
+        assert_eq!(keyboard.text(), "Qw ");
+
+        keyboard.handle(Action::Right);
+        keyboard.handle(Action::Accept);
+        assert_eq!(keyboard.text(), "Qw");
+
+        assert_eq!(
+            keyboard.handle(Action::Cancel),
+            Some(Ok((String::from("Qw"), Button::Left)))
+        );
+        assert_eq!(
+            keyboard.handle_touch(38 * 8, 16 * 8),
+            Some(Ok((String::from("Qw"), Button::Right)))
+        );
+    }
+}
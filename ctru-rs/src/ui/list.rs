@@ -0,0 +1,207 @@
+//! Scrollable lists.
+
+use super::{truncate, Action, Area, Canvas, Focus, Style};
+
+use std::cell::Cell;
+use std::fmt::Display;
+
+/// Result of an [`Action`] handled by a [`ListView`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ListEvent {
+    /// The item at this index was activated with [`Action::Accept`].
+    Activated(usize),
+    /// The list was left with [`Action::Cancel`].
+    Cancelled,
+}
+
+/// List of items with a selection, scrolled to keep the selected item visible.
+///
+/// Items are drawn with their [`Display`] implementation, one per row.
+#[derive(Clone, Debug)]
+pub struct ListView<T> {
+    items: Vec<T>,
+    focus: Focus,
+    /// Index of the first visible item, updated when drawing.
+    scroll: Cell<usize>,
+    /// Amount of rows shown by the last draw, used to move by pages.
+    page: Cell<usize>,
+}
+
+impl<T> ListView<T> {
+    /// Create a list of `items`, with the first one selected.
+    pub fn new(items: Vec<T>) -> Self {
+        Self {
+            focus: Focus::new(items.len()),
+            items,
+            scroll: Cell::new(0),
+            page: Cell::new(1),
+        }
+    }
+
+    /// Returns the items of the list.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Replace the items of the list, keeping the selection within them.
+    pub fn set_items(&mut self, items: Vec<T>) {
+        self.focus.set_count(items.len());
+        self.items = items;
+    }
+
+    /// Add an item at the end of the list.
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+        self.focus.set_count(self.items.len());
+    }
+
+    /// Modify the items of the list in place (e.g. to add a batch of items and sort them), keeping the selection within them.
+    pub fn update_items(&mut self, update: impl FnOnce(&mut Vec<T>)) {
+        update(&mut self.items);
+        self.focus.set_count(self.items.len());
+    }
+
+    /// Returns the index of the selected item, or [`None`] if the list is empty.
+    pub fn selected(&self) -> Option<usize> {
+        (!self.items.is_empty()).then(|| self.focus.focused())
+    }
+
+    /// Returns the selected item, or [`None`] if the list is empty.
+    pub fn selected_item(&self) -> Option<&T> {
+        self.items.get(self.focus.focused())
+    }
+
+    /// Select the item at `index`, clamped to the amount of items.
+    pub fn select(&mut self, index: usize) {
+        self.focus.set_focused(index);
+    }
+
+    /// Move the selection (wrapping around with [`Action::Up`] and [`Action::Down`], stopping at the ends when moving by pages),
+    /// or report the activation of the selected item.
+    pub fn handle(&mut self, action: Action) -> Option<ListEvent> {
+        let focused = self.focus.focused();
+        let page = self.page.get().max(1);
+
+        match action {
+            Action::Up | Action::Down => {
+                self.focus.handle(action);
+            }
+            Action::PageUp => self.focus.set_focused(focused.saturating_sub(page)),
+            Action::PageDown => self.focus.set_focused(focused + page),
+            Action::Accept => return self.selected().map(ListEvent::Activated),
+            Action::Cancel => return Some(ListEvent::Cancelled),
+            Action::Left | Action::Right => {}
+        }
+
+        None
+    }
+}
+
+impl<T: Display> ListView<T> {
+    /// Draw the visible items in `area`, with the selected item highlighted.
+    ///
+    /// When the items don't fit, the rightmost column shows whether the list continues above (`^`) or below (`v`).
+    pub fn draw(&self, canvas: &mut impl Canvas, area: Area) {
+        let rows = usize::from(area.height);
+        if rows == 0 || area.width == 0 {
+            return;
+        }
+
+        self.page.set(rows);
+
+        let selected = self.focus.focused();
+        let mut scroll = self.scroll.get();
+        if selected < scroll {
+            scroll = selected;
+        } else if selected >= scroll + rows {
+            scroll = selected + 1 - rows;
+        }
+        scroll = scroll.min(self.items.len().saturating_sub(rows));
+        self.scroll.set(scroll);
+
+        let overflows = self.items.len() > rows;
+        let text_width = if overflows {
+            area.width - 1
+        } else {
+            area.width
+        };
+
+        canvas.fill(area, Style::Normal);
+
+        for (row, (index, item)) in self
+            .items
+            .iter()
+            .enumerate()
+            .skip(scroll)
+            .take(rows)
+            .enumerate()
+        {
+            let row = area.row + row as u16;
+            let style = if index == selected {
+                Style::Highlighted
+            } else {
+                Style::Normal
+            };
+
+            canvas.fill(Area::new(area.column, row, text_width, 1), style);
+            canvas.text(
+                area.column,
+                row,
+                truncate(&item.to_string(), text_width.into()),
+                style,
+            );
+        }
+
+        if overflows {
+            let column = area.column + area.width - 1;
+
+            if scroll > 0 {
+                canvas.text(column, area.row, "^", Style::Dimmed);
+            }
+            if scroll + rows < self.items.len() {
+                canvas.text(column, area.row + area.height - 1, "v", Style::Dimmed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Canvas recording the text written on each row.
+    struct Rows(Vec<String>);
+
+    impl Canvas for Rows {
+        fn size(&self) -> (u16, u16) {
+            (20, self.0.len() as u16)
+        }
+
+        fn fill(&mut self, _area: Area, _style: Style) {}
+
+        fn text(&mut self, _column: u16, row: u16, text: &str, _style: Style) {
+            self.0[usize::from(row)].push_str(text);
+        }
+    }
+
+    #[test]
+    fn scrolling() {
+        let mut list = ListView::new((0..10).collect::<Vec<i32>>());
+        let mut rows = Rows(vec![String::new(); 3]);
+
+        list.draw(&mut rows, Area::new(0, 0, 20, 3));
+        assert_eq!(rows.0, ["0", "1", "2v"]);
+
+        assert_eq!(list.handle(Action::PageDown), None);
+        assert_eq!(list.handle(Action::Down), None);
+        assert_eq!(list.selected(), Some(4));
+
+        let mut rows = Rows(vec![String::new(); 3]);
+        list.draw(&mut rows, Area::new(0, 0, 20, 3));
+        assert_eq!(rows.0, ["2^", "3", "4v"]);
+
+        assert_eq!(list.handle(Action::Up), None);
+        assert_eq!(list.handle(Action::Accept), Some(ListEvent::Activated(3)));
+        assert_eq!(list.handle(Action::Cancel), Some(ListEvent::Cancelled));
+    }
+}
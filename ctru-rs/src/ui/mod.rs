@@ -0,0 +1,651 @@
+//! Gamepad-driven UI primitives.
+//!
+//! Tools and homebrew menus all need the same handful of widgets: a list to pick an entry from, message and confirmation dialogs,
+//! progress bars and a way to move the focus between them with the D-Pad. This module provides them, driven by [`Hid`] input
+//! through a [`Navigator`] and drawn on any [`Canvas`]: a [`Console`] (see [`ConsoleCanvas`]) or, with the `render2d` feature,
+//! the 2D renderer (see `RendererCanvas`).
+//!
+//! # Notes
+//!
+//! Widgets are laid out on a grid of 8x8 pixel cells, which matches the console's character grid.
+//! They don't keep track of what they drew before: clear the canvas and draw all visible widgets every frame (or after every change).
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! #
+//! use ctru::prelude::*;
+//! use ctru::ui::{Area, ConsoleCanvas, ListEvent, ListView, Navigator};
+//!
+//! let apt = Apt::new()?;
+//! let mut hid = Hid::new()?;
+//! let gfx = Gfx::new()?;
+//! let console = Console::new(gfx.top_screen.borrow_mut());
+//!
+//! let mut navigator = Navigator::new();
+//! let mut list = ListView::new(vec!["New game", "Continue", "Options", "Quit"]);
+//!
+//! while apt.main_loop() {
+//!     hid.scan_input();
+//!
+//!     if let Some(action) = navigator.update(&hid) {
+//!         match list.handle(action) {
+//!             Some(ListEvent::Activated(3)) | Some(ListEvent::Cancelled) => break,
+//!             Some(ListEvent::Activated(index)) => println!("Selected {index}"),
+//!             None => {}
+//!         }
+//!     }
+//!
+//!     let mut canvas = ConsoleCanvas::new(&console);
+//!     list.draw(&mut canvas, Area::new(2, 2, 20, 10));
+//!
+//!     gfx.wait_for_vblank();
+//! }
+//! #
+//! # Ok(())
+//! # }
+//! ```
+#![doc(alias = "gui")]
+#![doc(alias = "menu")]
+
+pub mod dialog;
+pub mod file_picker;
+#[cfg(feature = "render2d")]
+pub mod icon;
+pub mod keyboard;
+pub mod list;
+pub mod progress;
+
+pub use dialog::{Dialog, DialogResult};
+pub use file_picker::{FilePicker, FilePickerEvent};
+#[cfg(feature = "render2d")]
+pub use icon::TitleIcon;
+pub use keyboard::VirtualKeyboard;
+pub use list::{ListEvent, ListView};
+pub use progress::ProgressBar;
+
+use crate::console::Console;
+use crate::services::hid::{Hid, KeyPad};
+
+/// Size (in pixels) of a cell of the UI grid.
+pub const CELL_SIZE: u16 = 8;
+
+/// Input event driving the widgets.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Move up (D-Pad or Circle Pad).
+    Up,
+    /// Move down (D-Pad or Circle Pad).
+    Down,
+    /// Move left (D-Pad or Circle Pad).
+    Left,
+    /// Move right (D-Pad or Circle Pad).
+    Right,
+    /// Move up by a page (L).
+    PageUp,
+    /// Move down by a page (R).
+    PageDown,
+    /// Activate the focused element (A).
+    Accept,
+    /// Go back (B).
+    Cancel,
+}
+
+/// Keys generating each action, in priority order.
+const KEY_MAP: [(KeyPad, Action); 8] = [
+    (KeyPad::A, Action::Accept),
+    (KeyPad::B, Action::Cancel),
+    (KeyPad::UP, Action::Up),
+    (KeyPad::DOWN, Action::Down),
+    (KeyPad::LEFT, Action::Left),
+    (KeyPad::RIGHT, Action::Right),
+    (KeyPad::L, Action::PageUp),
+    (KeyPad::R, Action::PageDown),
+];
+
+impl Action {
+    /// Returns the keys generating this action.
+    pub fn keys(&self) -> KeyPad {
+        KEY_MAP
+            .iter()
+            .find(|(_, action)| action == self)
+            .map(|(keys, _)| *keys)
+            .unwrap()
+    }
+
+    /// Returns `true` if holding the keys of this action repeats it.
+    pub fn repeats(&self) -> bool {
+        !matches!(self, Self::Accept | Self::Cancel)
+    }
+}
+
+/// Turns key presses into [`Action`]s, repeating movements while their key is held down.
+#[derive(Clone, Debug)]
+pub struct Navigator {
+    delay: u32,
+    interval: u32,
+    held: Option<(Action, u32)>,
+}
+
+impl Navigator {
+    /// Create a navigator repeating movements every 4 frames after holding their key for 20 frames.
+    pub fn new() -> Self {
+        Self::with_repeat(20, 4)
+    }
+
+    /// Create a navigator repeating movements every `interval` frames after holding their key for `delay` frames.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `interval` is 0.
+    pub fn with_repeat(delay: u32, interval: u32) -> Self {
+        assert!(interval > 0, "the repeat interval can't be 0");
+
+        Self {
+            delay,
+            interval,
+            held: None,
+        }
+    }
+
+    /// Returns the action for the current frame, reading the keys scanned by the last [`Hid::scan_input()`] call.
+    ///
+    /// Call this function once per frame.
+    pub fn update(&mut self, hid: &Hid) -> Option<Action> {
+        self.update_with_keys(hid.keys_down(), hid.keys_held())
+    }
+
+    /// Returns the action for the current frame, given the keys pressed during this frame and the keys held down.
+    pub fn update_with_keys(&mut self, down: KeyPad, held: KeyPad) -> Option<Action> {
+        if let Some(&(_, action)) = KEY_MAP.iter().find(|(keys, _)| down.intersects(*keys)) {
+            if action.repeats() {
+                self.held = Some((action, 0));
+            }
+
+            return Some(action);
+        }
+
+        let (action, frames) = self.held.as_mut()?;
+
+        if !held.intersects(action.keys()) {
+            self.held = None;
+            return None;
+        }
+
+        *frames += 1;
+
+        (*frames >= self.delay && (*frames - self.delay) % self.interval == 0).then_some(*action)
+    }
+}
+
+impl Default for Navigator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Focus over a fixed amount of elements, moved with [`Action`]s.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Focus {
+    count: usize,
+    focused: usize,
+    wrap: bool,
+}
+
+impl Focus {
+    /// Create a focus over `count` elements, starting on the first one, and wrapping around at both ends.
+    pub fn new(count: usize) -> Self {
+        Self {
+            count,
+            focused: 0,
+            wrap: true,
+        }
+    }
+
+    /// Choose whether moving past the last element goes back to the first one (and vice versa).
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    /// Returns the amount of elements.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Change the amount of elements, keeping the focus within them.
+    pub fn set_count(&mut self, count: usize) {
+        self.count = count;
+        self.focused = self.focused.min(count.saturating_sub(1));
+    }
+
+    /// Returns the index of the focused element.
+    pub fn focused(&self) -> usize {
+        self.focused
+    }
+
+    /// Returns `true` if the element at `index` is focused.
+    pub fn is_focused(&self, index: usize) -> bool {
+        self.count > 0 && self.focused == index
+    }
+
+    /// Focus the element at `index`, clamped to the amount of elements.
+    pub fn set_focused(&mut self, index: usize) {
+        self.focused = index.min(self.count.saturating_sub(1));
+    }
+
+    /// Move the focus by `offset` elements, wrapping around or stopping at the ends.
+    pub fn move_by(&mut self, offset: isize) {
+        if self.count == 0 {
+            return;
+        }
+
+        let target = self.focused as isize + offset;
+
+        self.focused = if self.wrap {
+            target.rem_euclid(self.count as isize) as usize
+        } else {
+            target.clamp(0, self.count as isize - 1) as usize
+        };
+    }
+
+    /// Move the focus to the previous ([`Action::Up`], [`Action::Left`]) or next ([`Action::Down`], [`Action::Right`]) element.
+    ///
+    /// Returns `true` if the action moved the focus.
+    pub fn handle(&mut self, action: Action) -> bool {
+        let previous = self.focused;
+
+        match action {
+            Action::Up | Action::Left => self.move_by(-1),
+            Action::Down | Action::Right => self.move_by(1),
+            _ => {}
+        }
+
+        self.focused != previous
+    }
+}
+
+/// Rectangular area of the UI grid, in cells.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Area {
+    /// Column of the left edge.
+    pub column: u16,
+    /// Row of the top edge.
+    pub row: u16,
+    /// Width, in cells.
+    pub width: u16,
+    /// Height, in cells.
+    pub height: u16,
+}
+
+impl Area {
+    /// Create an area from its top-left corner and size.
+    pub const fn new(column: u16, row: u16, width: u16, height: u16) -> Self {
+        Self {
+            column,
+            row,
+            width,
+            height,
+        }
+    }
+
+    /// Returns an area of the given size centered within this one.
+    pub fn centered(&self, width: u16, height: u16) -> Self {
+        let width = width.min(self.width);
+        let height = height.min(self.height);
+
+        Self::new(
+            self.column + (self.width - width) / 2,
+            self.row + (self.height - height) / 2,
+            width,
+            height,
+        )
+    }
+
+    /// Returns the area shrunk by `margin` cells on every side.
+    pub fn inset(&self, margin: u16) -> Self {
+        Self::new(
+            self.column + margin.min(self.width / 2),
+            self.row + margin.min(self.height / 2),
+            self.width.saturating_sub(2 * margin),
+            self.height.saturating_sub(2 * margin),
+        )
+    }
+}
+
+/// Appearance of cells drawn on a [`Canvas`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Style {
+    /// Regular content.
+    #[default]
+    Normal,
+    /// Focused elements, titles and filled progress.
+    Highlighted,
+    /// Inactive or secondary content.
+    Dimmed,
+}
+
+/// Surface the widgets draw onto, as a grid of cells.
+pub trait Canvas {
+    /// Returns the size (columns, rows) of the canvas.
+    fn size(&self) -> (u16, u16);
+
+    /// Fill an area with blank cells of the given style.
+    fn fill(&mut self, area: Area, style: Style);
+
+    /// Write a single line of text starting at the given cell.
+    ///
+    /// Text going past the right edge of the canvas is cut.
+    fn text(&mut self, column: u16, row: u16, text: &str, style: Style);
+}
+
+/// [`Canvas`] drawing on a [`Console`] with ANSI escape sequences.
+///
+/// [`Style::Highlighted`] is shown in reverse video, and [`Style::Dimmed`] with faint colours.
+pub struct ConsoleCanvas<'console, 'screen> {
+    console: &'console Console<'screen>,
+}
+
+impl<'console, 'screen> ConsoleCanvas<'console, 'screen> {
+    /// Start drawing on `console`, which is selected for printing.
+    pub fn new(console: &'console Console<'screen>) -> Self {
+        console.select();
+
+        Self { console }
+    }
+
+    fn print(&self, column: u16, row: u16, text: &str, style: Style) {
+        let style = match style {
+            Style::Normal => "0",
+            Style::Highlighted => "7",
+            Style::Dimmed => "2",
+        };
+
+        print!("\x1b[{};{}H\x1b[{style}m{text}\x1b[0m", row + 1, column + 1);
+    }
+}
+
+impl Canvas for ConsoleCanvas<'_, '_> {
+    fn size(&self) -> (u16, u16) {
+        let (columns, rows) = self.console.window_size();
+
+        (columns.into(), rows.into())
+    }
+
+    fn fill(&mut self, area: Area, style: Style) {
+        let (columns, rows) = self.size();
+        let area = clip(area, columns, rows);
+        let blank = " ".repeat(area.width.into());
+
+        for row in area.row..area.row + area.height {
+            self.print(area.column, row, &blank, style);
+        }
+    }
+
+    fn text(&mut self, column: u16, row: u16, text: &str, style: Style) {
+        let (columns, rows) = self.size();
+
+        if column >= columns || row >= rows {
+            return;
+        }
+
+        let text = truncate(text, (columns - column).into());
+
+        // Writing the last cell of the last row would scroll the whole console.
+        let text = if row == rows - 1 && text.chars().count() == usize::from(columns - column) {
+            truncate(text, usize::from(columns - column) - 1)
+        } else {
+            text
+        };
+
+        self.print(column, row, text, style);
+    }
+}
+
+#[cfg(feature = "render2d")]
+pub use canvas2d::{RendererCanvas, Theme};
+
+#[cfg(feature = "render2d")]
+mod canvas2d {
+    use super::{clip, truncate, Area, Canvas, Style, CELL_SIZE};
+    use crate::render2d::{Color, Rect, SoftwareRenderer};
+
+    /// Colours used by a [`RendererCanvas`] for each [`Style`].
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct Theme {
+        /// Background of [`Style::Normal`] cells.
+        pub background: Color,
+        /// Text of [`Style::Normal`] cells.
+        pub foreground: Color,
+        /// Background of [`Style::Highlighted`] cells.
+        pub highlight_background: Color,
+        /// Text of [`Style::Highlighted`] cells.
+        pub highlight_foreground: Color,
+        /// Background of [`Style::Dimmed`] cells.
+        pub dimmed_background: Color,
+        /// Text of [`Style::Dimmed`] cells.
+        pub dimmed_foreground: Color,
+    }
+
+    impl Theme {
+        fn colors(&self, style: Style) -> (Color, Color) {
+            match style {
+                Style::Normal => (self.background, self.foreground),
+                Style::Highlighted => (self.highlight_background, self.highlight_foreground),
+                Style::Dimmed => (self.dimmed_background, self.dimmed_foreground),
+            }
+        }
+    }
+
+    impl Default for Theme {
+        fn default() -> Self {
+            Self {
+                background: Color::rgb(0x20, 0x20, 0x28),
+                foreground: Color::WHITE,
+                highlight_background: Color::rgb(0x30, 0x80, 0xE0),
+                highlight_foreground: Color::WHITE,
+                dimmed_background: Color::rgb(0x20, 0x20, 0x28),
+                dimmed_foreground: Color::rgb(0x80, 0x80, 0x80),
+            }
+        }
+    }
+
+    /// [`Canvas`] drawing with a 2D [`SoftwareRenderer`], using 8x8 pixel cells.
+    pub struct RendererCanvas<'renderer, 'target> {
+        renderer: &'renderer mut SoftwareRenderer<'target>,
+        theme: Theme,
+        size: (u16, u16),
+    }
+
+    impl<'renderer, 'target> RendererCanvas<'renderer, 'target> {
+        /// Start drawing with `renderer`, covering a target of the given size in pixels (e.g. [`TOP_SCREEN_SIZE`](crate::render2d::TOP_SCREEN_SIZE)).
+        pub fn new(
+            renderer: &'renderer mut SoftwareRenderer<'target>,
+            size: (u16, u16),
+            theme: Theme,
+        ) -> Self {
+            Self {
+                renderer,
+                theme,
+                size: (size.0 / CELL_SIZE, size.1 / CELL_SIZE),
+            }
+        }
+
+        /// Returns the theme used by the canvas.
+        pub fn theme(&self) -> &Theme {
+            &self.theme
+        }
+    }
+
+    impl Canvas for RendererCanvas<'_, '_> {
+        fn size(&self) -> (u16, u16) {
+            self.size
+        }
+
+        fn fill(&mut self, area: Area, style: Style) {
+            let area = clip(area, self.size.0, self.size.1);
+            let (background, _) = self.theme.colors(style);
+
+            self.renderer.fill_rect(
+                Rect::new(
+                    i32::from(area.column * CELL_SIZE),
+                    i32::from(area.row * CELL_SIZE),
+                    u32::from(area.width * CELL_SIZE),
+                    u32::from(area.height * CELL_SIZE),
+                ),
+                background,
+            );
+        }
+
+        fn text(&mut self, column: u16, row: u16, text: &str, style: Style) {
+            if column >= self.size.0 || row >= self.size.1 {
+                return;
+            }
+
+            let text = truncate(text, (self.size.0 - column).into());
+            let width = text.chars().count() as u16;
+
+            self.fill(Area::new(column, row, width, 1), style);
+
+            let (_, foreground) = self.theme.colors(style);
+            self.renderer.draw_text(
+                i32::from(column * CELL_SIZE),
+                i32::from(row * CELL_SIZE),
+                text,
+                foreground,
+                1,
+            );
+        }
+    }
+}
+
+/// Restrict an area to a canvas of the given size.
+fn clip(area: Area, columns: u16, rows: u16) -> Area {
+    let column = area.column.min(columns);
+    let row = area.row.min(rows);
+
+    Area::new(
+        column,
+        row,
+        area.width.min(columns - column),
+        area.height.min(rows - row),
+    )
+}
+
+/// Returns the first `width` characters of `text`.
+pub(crate) fn truncate(text: &str, width: usize) -> &str {
+    match text.char_indices().nth(width) {
+        Some((index, _)) => &text[..index],
+        None => text,
+    }
+}
+
+/// Split `text` in lines of at most `width` characters, breaking lines between words when possible.
+pub(crate) fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if width == 0 {
+        return lines;
+    }
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut length = 0;
+
+        for word in paragraph.split_whitespace() {
+            let mut word = word;
+
+            loop {
+                let word_length = word.chars().count();
+                let needed = if length == 0 {
+                    word_length
+                } else {
+                    length + 1 + word_length
+                };
+
+                if needed <= width {
+                    if length > 0 {
+                        line.push(' ');
+                    }
+                    line.push_str(word);
+                    length = needed;
+                    break;
+                }
+
+                if length > 0 {
+                    lines.push(std::mem::take(&mut line));
+                    length = 0;
+                    continue;
+                }
+
+                // The word alone is longer than a line: split it.
+                let head = truncate(word, width);
+                lines.push(String::from(head));
+                word = &word[head.len()..];
+            }
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigator_repeat() {
+        let mut navigator = Navigator::with_repeat(2, 2);
+        assert_eq!(
+            navigator.update_with_keys(KeyPad::DPAD_DOWN, KeyPad::DPAD_DOWN),
+            Some(Action::Down)
+        );
+        assert_eq!(
+            navigator.update_with_keys(KeyPad::empty(), KeyPad::DPAD_DOWN),
+            None
+        );
+        assert_eq!(
+            navigator.update_with_keys(KeyPad::empty(), KeyPad::DPAD_DOWN),
+            Some(Action::Down)
+        );
+        assert_eq!(
+            navigator.update_with_keys(KeyPad::empty(), KeyPad::empty()),
+            None
+        );
+        assert_eq!(
+            navigator.update_with_keys(KeyPad::A, KeyPad::A),
+            Some(Action::Accept)
+        );
+    }
+
+    #[test]
+    fn focus_wrapping() {
+        let mut focus = Focus::new(3);
+        assert!(focus.handle(Action::Up));
+        assert_eq!(focus.focused(), 2);
+        focus.set_wrap(false);
+        assert!(!focus.handle(Action::Down));
+        focus.set_count(1);
+        assert!(focus.is_focused(0));
+    }
+
+    #[test]
+    fn word_wrapping() {
+        assert_eq!(
+            wrap("Delete this file? This can't be undone.", 12),
+            ["Delete this", "file? This", "can't be", "undone."]
+        );
+        assert_eq!(wrap("abcdefgh", 3), ["abc", "def", "gh"]);
+    }
+
+    #[test]
+    fn centered_area() {
+        assert_eq!(
+            Area::new(0, 0, 40, 30).centered(20, 10),
+            Area::new(10, 10, 20, 10)
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Progress bars.
+
+use super::{truncate, Area, Canvas, Style};
+use crate::progress::SharedProgress;
+
+/// Horizontal bar showing the progress of an operation, with an optional label above it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProgressBar {
+    progress: f32,
+    label: Option<String>,
+}
+
+impl ProgressBar {
+    /// Create an empty progress bar without a label.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the progress, between 0 and 1.
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// Set the progress, clamped between 0 and 1.
+    pub fn set_progress(&mut self, progress: f32) {
+        self.progress = if progress.is_nan() {
+            0.0
+        } else {
+            progress.clamp(0.0, 1.0)
+        };
+    }
+
+    /// Set the progress from the amount of work done out of `total` (e.g. bytes downloaded).
+    pub fn set_position(&mut self, done: u64, total: u64) {
+        if total == 0 {
+            self.set_progress(1.0);
+        } else {
+            self.set_progress(done as f32 / total as f32);
+        }
+    }
+
+    /// Show the state of an operation reporting to `progress`: its stage as the label, and the completion of that stage.
+    ///
+    /// The progress is left as is while the total of the current stage is unknown.
+    pub fn follow(&mut self, progress: &SharedProgress) {
+        let stage = progress.stage();
+        if stage.is_empty() {
+            self.clear_label();
+        } else {
+            self.set_label(stage);
+        }
+
+        if let Some(fraction) = progress.fraction() {
+            self.set_progress(fraction);
+        }
+    }
+
+    /// Returns the label shown above the bar.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Set the label shown above the bar.
+    pub fn set_label(&mut self, label: impl Into<String>) {
+        self.label = Some(label.into());
+    }
+
+    /// Remove the label shown above the bar.
+    pub fn clear_label(&mut self) {
+        self.label = None;
+    }
+
+    /// Draw the bar on the first row of `area` (on the second one if there is a label), with the percentage in its middle.
+    pub fn draw(&self, canvas: &mut impl Canvas, area: Area) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+
+        let mut row = area.row;
+
+        if let Some(label) = &self.label {
+            if area.height < 2 {
+                return;
+            }
+
+            canvas.text(
+                area.column,
+                row,
+                truncate(label, area.width.into()),
+                Style::Normal,
+            );
+            row += 1;
+        }
+
+        let filled = (self.progress * f32::from(area.width)).round() as u16;
+        let percentage = format!("{}%", (self.progress * 100.0).round() as u32);
+        let start = area.column + area.width.saturating_sub(percentage.len() as u16) / 2;
+
+        canvas.fill(Area::new(area.column, row, filled, 1), Style::Highlighted);
+        canvas.fill(
+            Area::new(area.column + filled, row, area.width - filled, 1),
+            Style::Dimmed,
+        );
+
+        // Each character takes the style of the part of the bar it lies on.
+        for (index, c) in percentage.chars().enumerate() {
+            let column = start + index as u16;
+            if column >= area.column + area.width {
+                break;
+            }
+
+            let style = if column < area.column + filled {
+                Style::Highlighted
+            } else {
+                Style::Normal
+            };
+
+            canvas.text(column, row, c.encode_utf8(&mut [0; 4]), style);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//! Miscellaneous utilities.
+//!
+//! Helpers for dealing with the data formats commonly used by the system's interfaces.
+
+pub mod str16;
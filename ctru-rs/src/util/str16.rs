@@ -0,0 +1,152 @@
+//! UTF-16 string conversions.
+//!
+//! Many system interfaces (Mii names, the Software Keyboard, news notifications, UDS usernames, etc.) store text
+//! as fixed-length arrays of UTF-16 code units, padded with NUL characters.
+//! The functions in this module convert between those arrays and Rust strings, taking care of byte order and of truncating
+//! strings without splitting characters encoded as surrogate pairs.
+#![doc(alias = "utf16")]
+
+/// Decode UTF-16 code units into a [`String`], stopping at the first NUL character.
+///
+/// Invalid code units are replaced with [`char::REPLACEMENT_CHARACTER`].
+///
+/// # Example
+///
+/// ```
+/// use ctru::util::str16;
+///
+/// let units = [0x0048, 0x0069, 0x0000, 0x0000];
+///
+/// assert_eq!(str16::from_units(&units), "Hi");
+/// ```
+pub fn from_units(units: &[u16]) -> String {
+    let length = units.iter().position(|u| *u == 0).unwrap_or(units.len());
+
+    String::from_utf16_lossy(&units[..length])
+}
+
+/// Decode little-endian UTF-16 bytes into a [`String`], stopping at the first NUL character.
+///
+/// A trailing odd byte is ignored.
+pub fn from_le_bytes(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+        .collect();
+
+    from_units(&units)
+}
+
+/// Decode big-endian UTF-16 bytes into a [`String`], stopping at the first NUL character.
+///
+/// A trailing odd byte is ignored.
+pub fn from_be_bytes(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+        .collect();
+
+    from_units(&units)
+}
+
+/// Encode `text` into `out` as UTF-16 code units, filling the unused space with NUL characters.
+///
+/// If `text` doesn't fit, it's truncated on a character boundary (a surrogate pair is never split).
+/// Returns the amount of code units written, excluding the padding.
+///
+/// # Notes
+///
+/// No space is reserved for a NUL terminator. If the interface requires one, pass a slice one element shorter than the buffer.
+///
+/// # Example
+///
+/// ```
+/// use ctru::util::str16;
+///
+/// let mut name = [0u16; 3];
+///
+/// // "🦀" needs two code units, so it's left out completely.
+/// assert_eq!(str16::encode_into("ab🦀", &mut name), 2);
+/// assert_eq!(name, [0x61, 0x62, 0]);
+/// ```
+pub fn encode_into(text: &str, out: &mut [u16]) -> usize {
+    let mut written = 0;
+    let mut buffer = [0; 2];
+
+    for c in text.chars() {
+        let encoded = c.encode_utf16(&mut buffer);
+
+        if written + encoded.len() > out.len() {
+            break;
+        }
+
+        out[written..written + encoded.len()].copy_from_slice(encoded);
+        written += encoded.len();
+    }
+
+    out[written..].fill(0);
+
+    written
+}
+
+/// Encode `text` into a fixed-length array of UTF-16 code units.
+///
+/// See [`encode_into()`] for details about truncation and padding.
+pub fn to_array<const N: usize>(text: &str) -> [u16; N] {
+    let mut array = [0; N];
+    encode_into(text, &mut array);
+
+    array
+}
+
+/// Encode `text` into `out` as little-endian UTF-16 bytes, filling the unused space with NUL characters.
+///
+/// See [`encode_into()`] for details about truncation and padding. Returns the amount of bytes written, excluding the padding.
+pub fn encode_le_bytes_into(text: &str, out: &mut [u8]) -> usize {
+    encode_bytes_into(text, out, u16::to_le_bytes)
+}
+
+/// Encode `text` into `out` as big-endian UTF-16 bytes, filling the unused space with NUL characters.
+///
+/// See [`encode_into()`] for details about truncation and padding. Returns the amount of bytes written, excluding the padding.
+pub fn encode_be_bytes_into(text: &str, out: &mut [u8]) -> usize {
+    encode_bytes_into(text, out, u16::to_be_bytes)
+}
+
+fn encode_bytes_into(text: &str, out: &mut [u8], to_bytes: fn(u16) -> [u8; 2]) -> usize {
+    let mut units = vec![0; out.len() / 2];
+    let written = encode_into(text, &mut units);
+
+    out.fill(0);
+    for (chunk, unit) in out.chunks_exact_mut(2).zip(&units[..written]) {
+        chunk.copy_from_slice(&to_bytes(*unit));
+    }
+
+    written * 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surrogate_pairs_round_trip() {
+        let units: [u16; 4] = to_array("a🦀");
+
+        assert_eq!(units, [0x61, 0xD83E, 0xDD80, 0]);
+        assert_eq!(from_units(&units), "a🦀");
+    }
+
+    #[test]
+    fn byte_order() {
+        let mut le = [0; 4];
+        let mut be = [0; 4];
+
+        assert_eq!(encode_le_bytes_into("Hi", &mut le), 4);
+        assert_eq!(encode_be_bytes_into("Hi", &mut be), 4);
+
+        assert_eq!(le, [0x48, 0, 0x69, 0]);
+        assert_eq!(be, [0, 0x48, 0, 0x69]);
+        assert_eq!(from_le_bytes(&le), from_be_bytes(&be));
+    }
+}